@@ -0,0 +1,34 @@
+//! Criterion benches for the indexer's CPU-bound path
+//!
+//! `cargo bench` reports how long decoding a synthetic history takes; compare against
+//! a saved baseline (`cargo bench -- --save-baseline main`, then `--baseline main`)
+//! before changing the ABIs or the decoder. Database-backed throughput is measured by
+//! the `bench` subcommand.
+
+use backend::abi::Abis;
+use backend::bench::{address_from_u64, synthetic_events};
+use backend::indexer::{build_event_map, decode_log};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+fn decode(c: &mut Criterion) {
+    let abis = Abis::new();
+    let events_by_signature =
+        build_event_map(&abis.factory, &abis.raffle, Some(&abis.provider)).expect("event map");
+    let events =
+        synthetic_events(&abis, address_from_u64(1), 1, 100, 20).expect("synthetic events");
+
+    let mut group = c.benchmark_group("indexer");
+    group.throughput(Throughput::Elements(events.len() as u64));
+    group.bench_function("decode_logs", |b| {
+        b.iter(|| {
+            for event in &events {
+                black_box(decode_log(&events_by_signature, black_box(&event.log)).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+-- Migration: Recompute raffle totals from purchases and refunds
+--
+-- The indexer previously maintained total_tickets/pot with incremental updates,
+-- which double-counted when a log was replayed. Totals are now derived from the
+-- event tables; this one-off backfill repairs rows affected by the old behavior.
+
+UPDATE raffles r
+SET total_tickets = (
+        SELECT COALESCE(SUM(p.count), 0) FROM purchases p WHERE p.raffle_id = r.raffle_id
+    ),
+    pot = (
+        SELECT COALESCE(SUM(p.amount), 0) FROM purchases p WHERE p.raffle_id = r.raffle_id
+    ) - (
+        SELECT COALESCE(SUM(f.amount), 0) FROM refunds f WHERE f.raffle_id = r.raffle_id
+    ),
+    updated_at = now()
+WHERE r.status <> 'FINALIZED';
@@ -0,0 +1,21 @@
+-- Migration: Widen INTEGER columns to BIGINT
+--
+-- The API decodes these columns as i64, which SQLx only accepts for INT8 columns.
+-- Widening them keeps the Rust and SQL types aligned across indexer and API.
+
+ALTER TABLE raffles
+    ALTER COLUMN max_tickets TYPE BIGINT,
+    ALTER COLUMN fee_bps TYPE BIGINT,
+    ALTER COLUMN total_tickets TYPE BIGINT,
+    ALTER COLUMN winning_index TYPE BIGINT;
+
+ALTER TABLE purchases
+    ALTER COLUMN start_index TYPE BIGINT,
+    ALTER COLUMN end_index TYPE BIGINT,
+    ALTER COLUMN count TYPE BIGINT,
+    ALTER COLUMN log_index TYPE BIGINT;
+
+ALTER TABLE refunds ALTER COLUMN log_index TYPE BIGINT;
+ALTER TABLE events_raw ALTER COLUMN log_index TYPE BIGINT;
+ALTER TABLE randomness_requests ALTER COLUMN log_index TYPE BIGINT;
+ALTER TABLE randomness_fulfillments ALTER COLUMN log_index TYPE BIGINT;
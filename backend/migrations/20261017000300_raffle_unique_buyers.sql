@@ -0,0 +1,12 @@
+-- Migration: Track unique participants per raffle
+--
+-- Maintained by the indexer alongside total_tickets/pot so list and detail
+-- endpoints don't need a COUNT(DISTINCT buyer) over purchases per request.
+
+ALTER TABLE raffles
+    ADD COLUMN IF NOT EXISTS unique_buyers BIGINT NOT NULL DEFAULT 0;
+
+UPDATE raffles r
+SET unique_buyers = (
+    SELECT COUNT(DISTINCT p.buyer) FROM purchases p WHERE p.raffle_id = r.raffle_id
+);
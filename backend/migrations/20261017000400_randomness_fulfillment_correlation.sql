@@ -0,0 +1,28 @@
+-- Migration: Correlate provider fulfillments with raffles by request_id
+--
+-- RandomnessDelivered only carries the raffle address. The provider's
+-- RandomnessRequested event maps request_id -> raffle_id, so fulfillments can be
+-- linked to the exact raffle (and request) they answer.
+
+ALTER TABLE randomness_fulfillments
+    ADD COLUMN IF NOT EXISTS raffle_id BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_randomness_fulfillments_raffle_id
+    ON randomness_fulfillments (raffle_id);
+
+UPDATE randomness_fulfillments f
+SET raffle_id = r.raffle_id
+FROM randomness_requests r
+WHERE f.raffle_id IS NULL
+  AND r.request_id = f.request_id
+  AND LOWER(r.provider_address) = LOWER(f.provider_address);
+
+-- Randomness as delivered by the provider, to cross-check the raffle's own value
+ALTER TABLE raffles
+    ADD COLUMN IF NOT EXISTS provider_randomness TEXT;
+
+UPDATE raffles r
+SET provider_randomness = f.randomness
+FROM randomness_fulfillments f
+WHERE f.raffle_id = r.raffle_id
+  AND f.request_id = r.provider_request_id;
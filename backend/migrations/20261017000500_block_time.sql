@@ -0,0 +1,17 @@
+-- Migration: Store block timestamps for indexed events
+--
+-- created_at is the database insert time, which is meaningless after a backfill.
+-- block_time is the timestamp of the block the event was emitted in. Rows indexed
+-- before this migration keep NULL.
+
+ALTER TABLE events_raw
+    ADD COLUMN IF NOT EXISTS block_time TIMESTAMPTZ;
+
+ALTER TABLE purchases
+    ADD COLUMN IF NOT EXISTS block_time TIMESTAMPTZ;
+
+ALTER TABLE refunds
+    ADD COLUMN IF NOT EXISTS block_time TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_purchases_raffle_block_time
+    ON purchases (raffle_id, block_time);
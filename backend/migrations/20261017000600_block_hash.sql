@@ -0,0 +1,20 @@
+-- Migration: Store block hashes for indexed events
+--
+-- Pins every indexed row to a specific chain history so consumers (and later
+-- reorg detection) can tell whether the block an event came from is still canonical.
+-- Rows indexed before this migration keep NULL.
+
+ALTER TABLE events_raw
+    ADD COLUMN IF NOT EXISTS block_hash TEXT;
+
+ALTER TABLE purchases
+    ADD COLUMN IF NOT EXISTS block_hash TEXT;
+
+ALTER TABLE refunds
+    ADD COLUMN IF NOT EXISTS block_hash TEXT;
+
+ALTER TABLE randomness_requests
+    ADD COLUMN IF NOT EXISTS block_hash TEXT;
+
+ALTER TABLE randomness_fulfillments
+    ADD COLUMN IF NOT EXISTS block_hash TEXT;
@@ -0,0 +1,30 @@
+-- Migration: Track raffle keepers and payout results
+--
+-- KeeperUpdated carries no raffleId; rows are keyed by the emitting raffle
+-- contract and resolved to raffle_id through raffles.raffle_address.
+-- A raffle's keeper starts out as its creator (set in the Raffle constructor).
+
+CREATE TABLE IF NOT EXISTS keeper_updates (
+    id BIGSERIAL PRIMARY KEY,
+    raffle_id BIGINT NOT NULL REFERENCES raffles (raffle_id),
+    old_keeper TEXT NOT NULL,
+    new_keeper TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT,
+    block_time TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tx_hash, log_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_keeper_updates_raffle_id
+    ON keeper_updates (raffle_id);
+
+ALTER TABLE raffles
+    ADD COLUMN IF NOT EXISTS keeper TEXT,
+    ADD COLUMN IF NOT EXISTS prize_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS fee_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS payout_tx TEXT;
+
+UPDATE raffles SET keeper = creator WHERE keeper IS NULL;
@@ -0,0 +1,36 @@
+-- Migration: Payouts table for fee accounting
+--
+-- One row per finalized raffle, written from WinnerSelected/PayoutsCompleted
+-- (both emitted by finalize()). Queried by GET /v1/fees.
+
+CREATE TABLE IF NOT EXISTS payouts (
+    raffle_id BIGINT PRIMARY KEY REFERENCES raffles (raffle_id),
+    winner TEXT NOT NULL,
+    fee_recipient TEXT NOT NULL,
+    prize_amount NUMERIC NOT NULL,
+    fee_amount NUMERIC NOT NULL,
+    tx_hash TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT,
+    block_time TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_payouts_fee_recipient
+    ON payouts (fee_recipient);
+CREATE INDEX IF NOT EXISTS idx_payouts_block_time
+    ON payouts (block_time);
+
+-- Backfill raffles whose payouts were indexed before this table existed
+INSERT INTO payouts
+    (raffle_id, winner, fee_recipient, prize_amount, fee_amount, tx_hash, block_number, block_hash, block_time)
+SELECT DISTINCT ON (r.raffle_id)
+    r.raffle_id, r.winner, r.fee_recipient, r.prize_amount, r.fee_amount, r.payout_tx,
+    e.block_number, e.block_hash, e.block_time
+FROM raffles r
+JOIN events_raw e ON e.tx_hash = r.payout_tx
+WHERE r.payout_tx IS NOT NULL
+  AND r.winner IS NOT NULL
+  AND r.prize_amount IS NOT NULL
+  AND r.fee_amount IS NOT NULL
+ON CONFLICT (raffle_id) DO NOTHING;
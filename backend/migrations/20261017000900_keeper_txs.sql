@@ -0,0 +1,32 @@
+-- Migration: Keeper transaction submissions
+--
+-- One row per signed transaction the keeper broadcasts. Replacing a stuck
+-- transaction (same nonce, bumped gas price) inserts a new row and marks the
+-- old one 'replaced', pointing at its successor through replaced_by.
+--
+-- status: pending | confirmed | reverted | replaced | dropped
+-- error: last failed replacement attempt, if any
+--   dropped = the nonce was consumed by another transaction
+
+CREATE TABLE IF NOT EXISTS keeper_txs (
+    id BIGSERIAL PRIMARY KEY,
+    raffle_id BIGINT NOT NULL REFERENCES raffles (raffle_id),
+    action TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    nonce NUMERIC NOT NULL,
+    tx_hash TEXT NOT NULL UNIQUE,
+    gas_price NUMERIC NOT NULL,
+    gas_limit NUMERIC NOT NULL,
+    attempt INTEGER NOT NULL DEFAULT 1,
+    status TEXT NOT NULL DEFAULT 'pending',
+    replaced_by BIGINT REFERENCES keeper_txs (id),
+    block_number BIGINT,
+    error TEXT,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_keeper_txs_status
+    ON keeper_txs (status);
+CREATE INDEX IF NOT EXISTS idx_keeper_txs_raffle_action
+    ON keeper_txs (raffle_id, action);
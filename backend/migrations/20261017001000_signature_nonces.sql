@@ -0,0 +1,17 @@
+-- Migration: Replay protection for EIP-712 signed requests
+--
+-- Every signed request carries a wallet-chosen nonce and a deadline. A nonce
+-- can be used once per wallet; rows past their deadline can no longer be
+-- replayed (the deadline check rejects them) and are pruned.
+
+CREATE TABLE IF NOT EXISTS signature_nonces (
+    wallet TEXT NOT NULL,
+    nonce NUMERIC NOT NULL,
+    primary_type TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (wallet, nonce)
+);
+
+CREATE INDEX IF NOT EXISTS idx_signature_nonces_expires_at
+    ON signature_nonces (expires_at);
@@ -0,0 +1,31 @@
+-- Migration: Refund reminder subscriptions
+--
+-- A wallet subscribes (via a signed request) to be told when a raffle it bought
+-- tickets in enters REFUNDING. The indexer enqueues one reminder per
+-- subscribed buyer when the raffle starts refunding; the notifier delivers
+-- queued reminders to the wallet's webhook and retries failures with backoff.
+
+CREATE TABLE IF NOT EXISTS refund_reminder_subscriptions (
+    wallet TEXT PRIMARY KEY,
+    webhook_url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS refund_reminders (
+    id BIGSERIAL PRIMARY KEY,
+    wallet TEXT NOT NULL,
+    raffle_id BIGINT NOT NULL REFERENCES raffles (raffle_id),
+    -- pending | delivered | failed | skipped (refund claimed before delivery)
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (wallet, raffle_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_refund_reminders_due
+    ON refund_reminders (next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,26 @@
+-- Migration: Issued API keys with usage quotas
+--
+-- Third-party developers send a key in `X-API-Key`. Only a SHA-256 hash of the
+-- key is stored; the key itself is shown once when minted. Requests are counted
+-- per key and UTC day against the key's daily quota.
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- First characters of the key, to recognize it in listings
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    daily_quota BIGINT NOT NULL,
+    rate_limit_per_minute INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS api_key_usage (
+    key_id BIGINT NOT NULL REFERENCES api_keys (id),
+    day DATE NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    -- Requests rejected for exceeding the daily quota
+    rejected BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (key_id, day)
+);
@@ -0,0 +1,23 @@
+-- Migration: Whale purchase alerts
+--
+-- Purchases at or above WHALE_MIN_TICKETS or WHALE_MIN_AMOUNT are flagged when
+-- indexed. With WHALE_WEBHOOK_URL set, the indexer also enqueues an alert in the
+-- same transaction, and the notifier delivers it to that webhook with retries.
+
+ALTER TABLE purchases ADD COLUMN IF NOT EXISTS whale BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS whale_alerts (
+    id BIGSERIAL PRIMARY KEY,
+    purchase_id BIGINT NOT NULL UNIQUE REFERENCES purchases (id),
+    -- pending | delivered | failed
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_whale_alerts_due
+    ON whale_alerts (next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,34 @@
+-- Migration: Daily digests
+--
+-- After each UTC day, the digest job compiles a summary of it (new raffles,
+-- ticket volume, biggest pots, winners) into `digests`. With DIGEST_WEBHOOK_URL
+-- set, a delivery is queued in the same transaction and sent by the notifier.
+--
+-- `raffles.created_block_time` records when the RaffleCreated log was mined, so
+-- new raffles are attributed to the right day even when indexed late.
+
+ALTER TABLE raffles ADD COLUMN IF NOT EXISTS created_block_time TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS digests (
+    id BIGSERIAL PRIMARY KEY,
+    -- UTC day summarized
+    day DATE NOT NULL UNIQUE,
+    summary JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS digest_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    digest_id BIGINT NOT NULL UNIQUE REFERENCES digests (id),
+    -- pending | delivered | failed
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_digest_deliveries_due
+    ON digest_deliveries (next_attempt_at)
+    WHERE status = 'pending';
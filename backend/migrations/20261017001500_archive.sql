@@ -0,0 +1,35 @@
+-- Migration: Archive tables for completed raffles
+--
+-- With ARCHIVE_AFTER_DAYS set, the archive job moves finalized and fully
+-- refunded raffles, with their purchases and refunds, into `*_archive` tables
+-- so the hot tables only hold recent history. Rows are moved unchanged: a
+-- column added to `raffles`, `purchases` or `refunds` must be added to its
+-- archive table in the same position, and the views below recreated.
+--
+-- Payouts, keeper history and notification queues stay where they are, so
+-- their raffle_id / purchase_id may now point into the archive.
+
+CREATE TABLE IF NOT EXISTS raffles_archive (LIKE raffles INCLUDING INDEXES);
+CREATE TABLE IF NOT EXISTS purchases_archive (LIKE purchases INCLUDING INDEXES);
+CREATE TABLE IF NOT EXISTS refunds_archive (LIKE refunds INCLUDING INDEXES);
+
+ALTER TABLE payouts DROP CONSTRAINT IF EXISTS payouts_raffle_id_fkey;
+ALTER TABLE keeper_updates DROP CONSTRAINT IF EXISTS keeper_updates_raffle_id_fkey;
+ALTER TABLE keeper_txs DROP CONSTRAINT IF EXISTS keeper_txs_raffle_id_fkey;
+ALTER TABLE refund_reminders DROP CONSTRAINT IF EXISTS refund_reminders_raffle_id_fkey;
+ALTER TABLE whale_alerts DROP CONSTRAINT IF EXISTS whale_alerts_purchase_id_fkey;
+
+-- Per-raffle API lookups read through these views. A raffle re-indexed into the
+-- hot tables (e.g. after a cursor rewind) shadows its archived copy until it is
+-- archived again.
+CREATE OR REPLACE VIEW raffles_all AS
+    SELECT * FROM raffles
+    UNION ALL
+    SELECT * FROM raffles_archive a
+    WHERE NOT EXISTS (SELECT 1 FROM raffles r WHERE r.raffle_id = a.raffle_id);
+
+CREATE OR REPLACE VIEW purchases_all AS
+    SELECT * FROM purchases
+    UNION ALL
+    SELECT * FROM purchases_archive a
+    WHERE NOT EXISTS (SELECT 1 FROM raffles r WHERE r.raffle_id = a.raffle_id);
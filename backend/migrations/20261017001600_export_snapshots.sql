@@ -0,0 +1,29 @@
+-- Migration: Parquet export snapshots
+--
+-- With EXPORT_BUCKET set, the export job periodically writes raffles, purchases
+-- and refunds (hot and archived) as Parquet files to S3-compatible storage and
+-- records each completed snapshot here. GET /v1/admin/exports lists them.
+
+CREATE TABLE IF NOT EXISTS export_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    -- Snapshot name, the UTC start time (e.g. 20261017T000000Z)
+    snapshot TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    -- Indexer checkpoint the snapshot is consistent with
+    block_number BIGINT NOT NULL,
+    -- [{"table": ..., "key": ..., "rows": ..., "bytes": ...}]
+    files JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (bucket, prefix, snapshot)
+);
+
+CREATE INDEX IF NOT EXISTS idx_export_snapshots_created_at
+    ON export_snapshots (created_at);
+
+-- Refunds counterpart of raffles_all / purchases_all (see the archive migration)
+CREATE OR REPLACE VIEW refunds_all AS
+    SELECT * FROM refunds
+    UNION ALL
+    SELECT * FROM refunds_archive a
+    WHERE NOT EXISTS (SELECT 1 FROM raffles r WHERE r.raffle_id = a.raffle_id);
@@ -0,0 +1,13 @@
+-- Migration: Store all topics of raw events
+--
+-- `events_raw` only kept topic0, so events with indexed parameters could not be
+-- decoded again from the raw table alone. With every topic stored, `backup-raw`
+-- / `restore-raw` can rebuild the derived tables without re-crawling the chain.
+-- Rows indexed before this migration keep NULL until `backup-raw` fills them in
+-- from their transaction receipts.
+
+ALTER TABLE events_raw
+    ADD COLUMN IF NOT EXISTS topics TEXT[];
+
+CREATE INDEX IF NOT EXISTS idx_events_raw_block_log
+    ON events_raw (block_number, log_index);
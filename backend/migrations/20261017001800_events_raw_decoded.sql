@@ -0,0 +1,13 @@
+-- Migration: Keep events the indexer can't decode
+--
+-- Logs from watched contracts whose topic0 is not in the loaded ABIs (e.g. an
+-- event added by a contract upgrade) used to be skipped. They are now stored in
+-- `events_raw` with `decoded = false` and counted by GET /v1/status, and flip to
+-- true once re-processed with ABIs that know them.
+
+ALTER TABLE events_raw
+    ADD COLUMN IF NOT EXISTS decoded BOOLEAN NOT NULL DEFAULT true;
+
+CREATE INDEX IF NOT EXISTS idx_events_raw_undecoded
+    ON events_raw (topic0)
+    WHERE NOT decoded;
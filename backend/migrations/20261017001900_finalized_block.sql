@@ -0,0 +1,14 @@
+-- Migration: Track the chain's finalized block
+--
+-- The indexer records the block behind the RPC node's `finalized` tag. Indexed rows
+-- at or below it are settled; the API reports them with `finalized: true`. NULL
+-- while unknown (e.g. the node doesn't support the tag), in which case every row is
+-- reported as provisional.
+
+ALTER TABLE indexer_state
+    ADD COLUMN IF NOT EXISTS finalized_block BIGINT;
+
+CREATE OR REPLACE FUNCTION block_is_finalized(block BIGINT) RETURNS BOOLEAN
+LANGUAGE sql STABLE AS $$
+    SELECT COALESCE(block <= finalized_block, false) FROM indexer_state WHERE id = 1
+$$;
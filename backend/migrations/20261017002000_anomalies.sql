@@ -0,0 +1,26 @@
+-- Migration: Raffle status anomalies
+--
+-- The indexer checks each newly indexed lifecycle event against the raffle status
+-- transitions the contract allows (e.g. WinnerSelected needs RANDOM_FULFILLED). An
+-- event that doesn't fit is still applied, as the chain emitted it, but recorded
+-- here so the impossible status it may produce doesn't go unnoticed.
+-- GET /v1/admin/anomalies lists them.
+
+CREATE TABLE IF NOT EXISTS anomalies (
+    id BIGSERIAL PRIMARY KEY,
+    raffle_id BIGINT NOT NULL,
+    -- Event name, e.g. WinnerSelected
+    event TEXT NOT NULL,
+    -- Status before the event (NULL: raffle not indexed)
+    from_status TEXT,
+    -- Status the event moves the raffle into (NULL: events that only require a status)
+    to_status TEXT,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tx_hash, log_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_anomalies_raffle_id ON anomalies (raffle_id);
@@ -0,0 +1,75 @@
+-- Migration: Double-entry ledger of pot movements
+--
+-- `raffles.pot` is recomputed or overwritten as events arrive, which leaves nothing to
+-- audit it against. The indexer now also posts every movement of tokens into or out
+-- of a raffle's pot here, as two entries that sum to zero: the pot (the raffle
+-- contract) on one side, the buyer, winner or fee recipient on the other.
+-- GET /v1/raffles/{raffle_id}/ledger lists them.
+
+CREATE TABLE IF NOT EXISTS ledger (
+    id BIGSERIAL PRIMARY KEY,
+    raffle_id BIGINT NOT NULL,
+    -- PURCHASE, REFUND, PRIZE or FEE
+    entry TEXT NOT NULL,
+    -- pot, buyer, winner or fee_recipient
+    account TEXT NOT NULL,
+    address TEXT NOT NULL,
+    -- Token base units; positive into the account, negative out of it
+    amount NUMERIC NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT,
+    block_time TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tx_hash, log_index, entry, account)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_raffle_id ON ledger (raffle_id, block_number, log_index);
+
+-- Backfill movements indexed before this table existed
+INSERT INTO ledger
+    (raffle_id, entry, account, address, amount, tx_hash, log_index, block_number, block_hash, block_time)
+SELECT p.raffle_id, 'PURCHASE', l.account, l.address, l.amount,
+    p.tx_hash, p.log_index, p.block_number, p.block_hash, p.block_time
+FROM purchases_all p
+JOIN raffles_all r ON r.raffle_id = p.raffle_id
+CROSS JOIN LATERAL (
+    VALUES ('buyer', p.buyer, -p.amount), ('pot', r.raffle_address, p.amount)
+) AS l (account, address, amount)
+WHERE p.amount > 0
+ON CONFLICT DO NOTHING;
+
+INSERT INTO ledger
+    (raffle_id, entry, account, address, amount, tx_hash, log_index, block_number, block_hash, block_time)
+SELECT f.raffle_id, 'REFUND', l.account, l.address, l.amount,
+    f.tx_hash, f.log_index, f.block_number, f.block_hash, f.block_time
+FROM refunds_all f
+JOIN raffles_all r ON r.raffle_id = f.raffle_id
+CROSS JOIN LATERAL (
+    VALUES ('pot', r.raffle_address, -f.amount), ('buyer', f.buyer, f.amount)
+) AS l (account, address, amount)
+WHERE f.amount > 0
+ON CONFLICT DO NOTHING;
+
+-- Payouts are posted from WinnerSelected, the raffle's first event in the finalize()
+-- transaction
+INSERT INTO ledger
+    (raffle_id, entry, account, address, amount, tx_hash, log_index, block_number, block_hash, block_time)
+SELECT p.raffle_id, l.entry, l.account, l.address, l.amount,
+    p.tx_hash, e.log_index, p.block_number, p.block_hash, p.block_time
+FROM payouts p
+JOIN raffles_all r ON r.raffle_id = p.raffle_id
+CROSS JOIN LATERAL (
+    SELECT MIN(log_index) AS log_index FROM events_raw
+    WHERE tx_hash = p.tx_hash AND address = r.raffle_address
+) AS e
+CROSS JOIN LATERAL (
+    VALUES
+        ('PRIZE', 'pot', r.raffle_address, -p.prize_amount, p.prize_amount > 0),
+        ('PRIZE', 'winner', p.winner, p.prize_amount, p.prize_amount > 0),
+        ('FEE', 'pot', r.raffle_address, -p.fee_amount, p.fee_amount > 0),
+        ('FEE', 'fee_recipient', p.fee_recipient, p.fee_amount, p.fee_amount > 0)
+) AS l (entry, account, address, amount, posted)
+WHERE l.posted AND e.log_index IS NOT NULL
+ON CONFLICT DO NOTHING;
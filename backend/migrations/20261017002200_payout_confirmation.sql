@@ -0,0 +1,14 @@
+-- Migration: Payout confirmation
+--
+-- A WinnerSelected event says the raffle meant to pay out, not that the tokens
+-- arrived. The payout check reads the receipt of each payout transaction and records
+-- here whether it succeeded with the expected token transfers to the winner and fee
+-- recipient. Shown as `payout_confirmed` on raffle details.
+
+ALTER TABLE payouts
+    -- NULL until checked; false if the receipt lacks the expected transfers
+    ADD COLUMN IF NOT EXISTS confirmed BOOLEAN,
+    ADD COLUMN IF NOT EXISTS confirmed_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_payouts_unconfirmed
+    ON payouts (block_number) WHERE confirmed IS NULL;
@@ -0,0 +1,8 @@
+-- Migration: Persist admin pauses of the indexer
+--
+-- POST /v1/admin/indexer/pause stops the indexer between batches while the API keeps
+-- serving; /resume lets it continue. The pause is stored here so a restart during an
+-- incident doesn't silently resume indexing. NULL while running.
+
+ALTER TABLE indexer_state
+    ADD COLUMN IF NOT EXISTS paused_at TIMESTAMPTZ;
@@ -0,0 +1,18 @@
+-- Migration: Audited changes of the indexer cursor
+--
+-- PUT /v1/admin/indexer/cursor replaces hand-written UPDATEs of
+-- indexer_state.last_processed_block. Every change is recorded here. With
+-- `snapshot: true` the derived tables are first copied into the cursor_snapshots
+-- schema as `<table>_<id>`, so rows can be compared or restored if the rewind goes
+-- wrong. Snapshots are never dropped automatically.
+
+CREATE SCHEMA IF NOT EXISTS cursor_snapshots;
+
+CREATE TABLE IF NOT EXISTS cursor_changes (
+    id BIGSERIAL PRIMARY KEY,
+    from_block BIGINT NOT NULL,
+    to_block BIGINT NOT NULL,
+    -- Whether the derived tables were copied to cursor_snapshots.<table>_<id>
+    snapshot BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,25 @@
+-- Migration: Audit log of admin API calls
+--
+-- Every request to a /v1/admin route is recorded once answered, including refused
+-- ones, and listed by GET /v1/admin/audit. Entries are never deleted automatically.
+
+CREATE TABLE IF NOT EXISTS admin_audit (
+    id BIGSERIAL PRIMARY KEY,
+    -- Token subject or 'admin-api-key'; NULL when the credential was refused
+    actor TEXT,
+    -- readonly, operator or admin; NULL when the credential was refused
+    role TEXT,
+    -- Method and route, e.g. 'PUT /admin/indexer/cursor'
+    action TEXT NOT NULL,
+    -- Requested path below the deployment prefix
+    path TEXT NOT NULL,
+    -- {"query": {...}, "body": ...}; body is null when it wasn't JSON
+    parameters JSONB NOT NULL DEFAULT '{}',
+    status SMALLINT NOT NULL,
+    -- Error code of the response, if it was an API error
+    error_code TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_created_at ON admin_audit (created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_admin_audit_actor ON admin_audit (actor, id DESC);
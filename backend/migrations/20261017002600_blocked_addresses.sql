@@ -0,0 +1,15 @@
+-- Migration: Admin-managed address blocklist
+--
+-- Sanctioned or flagged addresses, managed through /v1/admin/blocklist. Raffles
+-- created by a blocked address are left out of GET /v1/raffles and purchases by one
+-- are flagged with `buyer_blocked`. Indexing is unaffected: blocked addresses' events
+-- are still stored, so removing an address restores its raffles as they are on chain.
+
+CREATE TABLE IF NOT EXISTS blocked_addresses (
+    -- Lowercase 0x-prefixed, as stored in raffles.creator and purchases.buyer
+    address TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    -- Subject of the admin credential that blocked the address
+    blocked_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,11 @@
+-- Migration: Chain-order index for purchase pages
+--
+-- GET /v1/raffles/{id}/purchases orders by (block_number, log_index) and continues
+-- after an `after_block`/`after_log_index` cursor. Backfills insert rows out of chain
+-- order, so `id` order isn't stable for clients syncing incrementally.
+
+CREATE INDEX IF NOT EXISTS idx_purchases_raffle_block_log
+    ON purchases (raffle_id, block_number, log_index);
+
+CREATE INDEX IF NOT EXISTS idx_purchases_archive_raffle_block_log
+    ON purchases_archive (raffle_id, block_number, log_index);
@@ -0,0 +1,38 @@
+-- Migration: Webhook delivery attempts and rotatable signing secrets
+--
+-- The notifier records every delivery attempt, with the webhook's response status,
+-- for GET /v1/admin/webhooks/attempts. Entries are never deleted automatically.
+--
+-- POST /v1/admin/webhooks/signing-secret stores a new signing secret; the newest
+-- one replaces WEBHOOK_SIGNING_SECRET, and the one before it keeps signing for
+-- WEBHOOK_SECRET_OVERLAP_SECS so receivers can switch over.
+
+CREATE TABLE IF NOT EXISTS webhook_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    -- refund_reminders | whale_alerts | digest_deliveries
+    queue TEXT NOT NULL,
+    -- ID in the queue table
+    notification_id BIGINT NOT NULL,
+    -- Attempt number of the notification, from 1
+    attempt INTEGER NOT NULL,
+    -- HTTP status of the response; NULL when there was none (timeout, unreachable)
+    response_status SMALLINT,
+    -- NULL for delivered attempts
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_attempts_attempted_at
+    ON webhook_attempts (attempted_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_webhook_attempts_notification
+    ON webhook_attempts (queue, notification_id, id DESC);
+
+CREATE TABLE IF NOT EXISTS webhook_signing_secrets (
+    id BIGSERIAL PRIMARY KEY,
+    -- Needed in clear to sign payloads (secret - never log this)
+    secret TEXT NOT NULL,
+    -- Token subject of the admin who rotated
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,24 @@
+-- Migration: Outbox of live events
+--
+-- The indexer writes the live events of a log (purchases, status changes) in the
+-- same transaction as the rows they describe; the outbox dispatcher publishes them to
+-- WebSocket subscribers in id order and stamps published_at. Events of blocks that
+-- a reorg replaced are deleted by the orphan check while still unpublished.
+-- Published rows are deleted after a day.
+
+CREATE TABLE IF NOT EXISTS outbox (
+    id BIGSERIAL PRIMARY KEY,
+    -- live
+    topic TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    -- Block of the log the event was indexed from
+    block_number BIGINT NOT NULL,
+    block_hash TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    published_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_unpublished
+    ON outbox (id) WHERE published_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_outbox_published_at
+    ON outbox (published_at) WHERE published_at IS NOT NULL;
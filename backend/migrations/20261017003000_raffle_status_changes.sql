@@ -0,0 +1,41 @@
+-- Migration: Raffle status changes
+--
+-- The raffles row only holds the current status. The indexer now also records
+-- every lifecycle transition with the block it was mined in, for
+-- GET /v1/raffles/{raffle_id}/timeline and its draw time estimate. The orphan
+-- check deletes transitions of blocks a reorg replaced.
+
+CREATE TABLE IF NOT EXISTS raffle_status_changes (
+    id BIGSERIAL PRIMARY KEY,
+    raffle_id BIGINT NOT NULL,
+    -- Status the raffle moved into
+    status TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    -- NULL for transitions backfilled below
+    log_index BIGINT,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT,
+    block_time TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (raffle_id, status, tx_hash)
+);
+
+-- Recent finalizations, for the draw time estimate
+CREATE INDEX IF NOT EXISTS idx_raffle_status_changes_status
+    ON raffle_status_changes (status, block_number DESC);
+
+-- Backfill the transitions whose transaction the raffles row kept. Closing,
+-- refunds and cancellations indexed before this table existed have no record.
+INSERT INTO raffle_status_changes
+    (raffle_id, status, tx_hash, block_number, block_hash, block_time)
+SELECT DISTINCT ON (r.raffle_id, s.status)
+    r.raffle_id, s.status, s.tx_hash, e.block_number, e.block_hash, e.block_time
+FROM raffles_all r
+CROSS JOIN LATERAL (
+    VALUES ('RANDOM_REQUESTED', r.request_tx),
+           ('RANDOM_FULFILLED', r.randomness_tx),
+           ('FINALIZED', r.finalized_tx)
+) AS s (status, tx_hash)
+JOIN events_raw e ON e.tx_hash = s.tx_hash
+ORDER BY r.raffle_id, s.status, e.log_index
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,41 @@
+-- Migration: Time between draw stages per raffle
+--
+-- Derived from raffle_status_changes: when a raffle closed, had randomness
+-- requested and fulfilled and was finalized, and the seconds between each pair.
+-- The indexer recomputes a raffle's row with every status change (and the
+-- orphan check after deleting some), for GET /v1/stats/keeper.
+
+CREATE TABLE IF NOT EXISTS raffle_stage_durations (
+    raffle_id BIGINT PRIMARY KEY,
+    closed_at TIMESTAMPTZ,
+    randomness_requested_at TIMESTAMPTZ,
+    randomness_fulfilled_at TIMESTAMPTZ,
+    finalized_at TIMESTAMPTZ,
+    -- NULL until both ends of the stage are known
+    close_to_request_secs BIGINT,
+    request_to_fulfill_secs BIGINT,
+    fulfill_to_finalize_secs BIGINT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_raffle_stage_durations_finalized_at
+    ON raffle_stage_durations (finalized_at);
+
+-- Backfill raffles with recorded transitions
+INSERT INTO raffle_stage_durations
+    (raffle_id, closed_at, randomness_requested_at, randomness_fulfilled_at, finalized_at,
+     close_to_request_secs, request_to_fulfill_secs, fulfill_to_finalize_secs)
+SELECT raffle_id, closed_at, randomness_requested_at, randomness_fulfilled_at, finalized_at,
+    EXTRACT(EPOCH FROM randomness_requested_at - closed_at)::bigint,
+    EXTRACT(EPOCH FROM randomness_fulfilled_at - randomness_requested_at)::bigint,
+    EXTRACT(EPOCH FROM finalized_at - randomness_fulfilled_at)::bigint
+FROM (
+    SELECT raffle_id,
+        MIN(block_time) FILTER (WHERE status = 'CLOSED') AS closed_at,
+        MIN(block_time) FILTER (WHERE status = 'RANDOM_REQUESTED') AS randomness_requested_at,
+        MIN(block_time) FILTER (WHERE status = 'RANDOM_FULFILLED') AS randomness_fulfilled_at,
+        MIN(block_time) FILTER (WHERE status = 'FINALIZED') AS finalized_at
+    FROM raffle_status_changes
+    GROUP BY raffle_id
+) t
+ON CONFLICT (raffle_id) DO NOTHING;
@@ -0,0 +1,27 @@
+-- Migration: Stuck raffle alerts
+--
+-- A raffle that stays CLOSED or RANDOM_REQUESTED longer than its SLA
+-- (STUCK_CLOSED_SLA_SECS, STUCK_RANDOM_REQUESTED_SLA_SECS) is stuck. With
+-- STUCK_WEBHOOK_URL set, the stuck check enqueues one alert per raffle and
+-- status, and the notifier delivers it to that webhook with retries.
+
+CREATE TABLE IF NOT EXISTS stuck_alerts (
+    id BIGSERIAL PRIMARY KEY,
+    raffle_id BIGINT NOT NULL,
+    -- Status the raffle was stuck in
+    raffle_status TEXT NOT NULL,
+    -- When the raffle reached raffle_status
+    since TIMESTAMPTZ NOT NULL,
+    -- pending | delivered | failed | skipped (moved on before delivery)
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_error TEXT,
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (raffle_id, raffle_status)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stuck_alerts_due
+    ON stuck_alerts (next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,53 @@
+-- Migration: Block of each raffle's latest change, for GET /v1/changes
+--
+-- The indexer stamps `updated_block` with the block of every event that touches
+-- a raffle, and the orphan check with the first orphaned block when it
+-- recomputes one. Together with the block numbers of purchases and refunds it
+-- lets consumers sync incrementally by block cursor.
+--
+-- Existing raffles get the latest block of their indexed rows, or the indexer
+-- cursor when they have none. Both tables get the column in the same position,
+-- and raffles_all is recreated to include it (see the archive migration).
+
+ALTER TABLE raffles ADD COLUMN IF NOT EXISTS updated_block BIGINT;
+ALTER TABLE raffles_archive ADD COLUMN IF NOT EXISTS updated_block BIGINT;
+
+UPDATE raffles r
+SET updated_block = COALESCE(
+    GREATEST(
+        (SELECT MAX(block_number) FROM purchases t WHERE t.raffle_id = r.raffle_id),
+        (SELECT MAX(block_number) FROM refunds t WHERE t.raffle_id = r.raffle_id),
+        (SELECT MAX(block_number) FROM raffle_status_changes t WHERE t.raffle_id = r.raffle_id),
+        (SELECT MAX(block_number) FROM keeper_updates t WHERE t.raffle_id = r.raffle_id),
+        (SELECT block_number FROM payouts t WHERE t.raffle_id = r.raffle_id)
+    ),
+    (SELECT last_processed_block FROM indexer_state WHERE id = 1)
+)
+WHERE updated_block IS NULL;
+
+UPDATE raffles_archive r
+SET updated_block = COALESCE(
+    GREATEST(
+        (SELECT MAX(block_number) FROM purchases_archive t WHERE t.raffle_id = r.raffle_id),
+        (SELECT MAX(block_number) FROM refunds_archive t WHERE t.raffle_id = r.raffle_id),
+        (SELECT MAX(block_number) FROM raffle_status_changes t WHERE t.raffle_id = r.raffle_id),
+        (SELECT MAX(block_number) FROM keeper_updates t WHERE t.raffle_id = r.raffle_id),
+        (SELECT block_number FROM payouts t WHERE t.raffle_id = r.raffle_id)
+    ),
+    (SELECT last_processed_block FROM indexer_state WHERE id = 1)
+)
+WHERE updated_block IS NULL;
+
+CREATE OR REPLACE VIEW raffles_all AS
+    SELECT * FROM raffles
+    UNION ALL
+    SELECT * FROM raffles_archive a
+    WHERE NOT EXISTS (SELECT 1 FROM raffles r WHERE r.raffle_id = a.raffle_id);
+
+CREATE INDEX IF NOT EXISTS idx_raffles_updated_block ON raffles (updated_block);
+CREATE INDEX IF NOT EXISTS idx_raffles_archive_updated_block ON raffles_archive (updated_block);
+CREATE INDEX IF NOT EXISTS idx_purchases_block_log ON purchases (block_number, log_index);
+CREATE INDEX IF NOT EXISTS idx_purchases_archive_block_log
+    ON purchases_archive (block_number, log_index);
+CREATE INDEX IF NOT EXISTS idx_refunds_block_log ON refunds (block_number, log_index);
+CREATE INDEX IF NOT EXISTS idx_refunds_archive_block_log ON refunds_archive (block_number, log_index);
@@ -0,0 +1,20 @@
+-- Migration: Response snapshots of terminal raffles
+--
+-- Once a raffle is terminal (FINALIZED with a checked payout, or refunded down
+-- to an empty pot) and its last change is in a finalized block, its detail,
+-- proof and purchases responses can't change any more. The API stores the
+-- serialized JSON the first time it builds one and serves it from here after.
+
+CREATE TABLE IF NOT EXISTS raffle_snapshots (
+    raffle_id BIGINT NOT NULL,
+    -- details | proof | purchases
+    kind TEXT NOT NULL,
+    -- Snapshot format version and the config the body depends on (explorer URL);
+    -- snapshots with another fingerprint are rebuilt
+    fingerprint TEXT NOT NULL,
+    body TEXT NOT NULL,
+    -- Last-Modified of the response, if it has one
+    last_modified TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (raffle_id, kind)
+);
@@ -0,0 +1,25 @@
+-- Migration: Participant cohorts
+--
+-- The cohort job groups buyers by the UTC week (Monday) of their first purchase
+-- and records how many of each cohort bought again in every following week.
+-- Both tables are rebuilt in one transaction on each run, so readers see either
+-- the previous or the new result. `GET /v1/analytics/cohorts` serves them.
+
+CREATE TABLE IF NOT EXISTS participant_cohorts (
+    -- Monday of the week of the cohort's first purchases
+    cohort_week DATE PRIMARY KEY,
+    buyers BIGINT NOT NULL,
+    -- Buyers who bought tickets in more than one raffle
+    repeat_buyers BIGINT NOT NULL,
+    tickets BIGINT NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS participant_cohort_weeks (
+    cohort_week DATE NOT NULL REFERENCES participant_cohorts (cohort_week) ON DELETE CASCADE,
+    -- Weeks after `cohort_week`; 0 is the first purchase week
+    week_offset INTEGER NOT NULL,
+    active_buyers BIGINT NOT NULL,
+    tickets BIGINT NOT NULL,
+    PRIMARY KEY (cohort_week, week_offset)
+);
@@ -0,0 +1,28 @@
+-- Migration: Sybil clusters
+--
+-- The sybil job links buyers that look like one operator's wallets and stores the
+-- connected groups in `address_clusters`, rebuilt on every run. Participants
+-- listings show the flags to admins and the raffle's creator.
+--
+-- `purchase_tx_senders` caches the sender (`from`) of purchase transactions, read
+-- from their receipts when SYBIL_SENDER_LOOKUP is on.
+
+CREATE TABLE IF NOT EXISTS purchase_tx_senders (
+    tx_hash TEXT PRIMARY KEY,
+    sender TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_purchase_tx_senders_sender ON purchase_tx_senders (sender);
+
+CREATE TABLE IF NOT EXISTS address_clusters (
+    address TEXT PRIMARY KEY,
+    -- Lowest address of the cluster
+    cluster_id TEXT NOT NULL,
+    cluster_size INTEGER NOT NULL,
+    -- Signals that linked the cluster: co_purchase, shared_sender
+    reasons TEXT[] NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_address_clusters_cluster ON address_clusters (cluster_id);
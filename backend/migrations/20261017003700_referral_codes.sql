@@ -0,0 +1,27 @@
+-- Migration: Referral codes
+--
+-- Creators register codes with a signed request; front ends append a code to the
+-- calldata of `buyTickets` transactions (the contract ignores trailing bytes). With
+-- REFERRAL_TRACKING on, the indexer reads the calldata of each purchase transaction
+-- and records decoded codes in `purchase_referrals`, keyed by transaction, so
+-- orphaned purchases drop out of the stats through the join on `purchases`.
+
+CREATE TABLE IF NOT EXISTS referral_codes (
+    -- Upper case, 3-32 characters of A-Z, 0-9, `_` and `-`
+    code TEXT PRIMARY KEY,
+    -- Creator wallet that registered the code
+    owner TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_referral_codes_owner ON referral_codes (owner);
+
+CREATE TABLE IF NOT EXISTS purchase_referrals (
+    tx_hash TEXT PRIMARY KEY,
+    -- Decoded code, registered or not
+    code TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_purchase_referrals_code ON purchase_referrals (code);
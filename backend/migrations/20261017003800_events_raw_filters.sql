@@ -0,0 +1,11 @@
+-- Migration: Filter raw events by contract and signature
+--
+-- GET /v1/events pages through `events_raw` in chain order, optionally filtered by
+-- emitting contract and topic0. These indexes keep a filtered page from scanning
+-- every stored log.
+
+CREATE INDEX IF NOT EXISTS idx_events_raw_address_block_log
+    ON events_raw (address, block_number, log_index);
+
+CREATE INDEX IF NOT EXISTS idx_events_raw_topic0_block_log
+    ON events_raw (topic0, block_number, log_index);
@@ -0,0 +1,210 @@
+//! Event ABIs of the indexed contracts, in Solidity's human-readable form
+//!
+//! The indexer decodes with the Hardhat artifacts. These declarations let the
+//! end-to-end tests and the benchmark harness encode events, and `GET /v1/events`
+//! decode stored logs, without compiling the contracts; keep them in sync with the
+//! events in `contracts/contracts/`.
+
+use alloy::dyn_abi::{DynSolEvent, DynSolValue, Specifier};
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::{B256, LogData};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const FACTORY_EVENTS: &[&str] = &[
+    "event RaffleCreated(uint256 indexed raffleId, address indexed raffle, address indexed creator, uint256 endTime, uint256 ticketPrice, uint32 maxTickets, uint16 feeBps, address feeRecipient)",
+];
+
+const RAFFLE_EVENTS: &[&str] = &[
+    "event TicketsBought(uint256 indexed raffleId, address indexed buyer, uint32 startIndex, uint32 endIndex, uint32 count, uint256 amountPaid)",
+    "event RaffleClosed(uint256 indexed raffleId, uint256 totalTickets, uint256 pot)",
+    "event RandomnessRequested(uint256 indexed raffleId, uint256 requestId)",
+    "event RandomnessFulfilled(uint256 indexed raffleId, uint256 requestId, uint256 randomness)",
+    "event WinnerSelected(uint256 indexed raffleId, address indexed winner, uint256 winningIndex, uint256 prizeAmount, uint256 feeAmount)",
+    "event PayoutsCompleted(uint256 indexed raffleId, address indexed winner, address indexed feeRecipient, uint256 prizeAmount, uint256 feeAmount)",
+    "event KeeperUpdated(address indexed oldKeeper, address indexed newKeeper)",
+    "event RefundClaimed(uint256 indexed raffleId, address indexed buyer, uint32 ticketCount, uint256 amount)",
+    "event RefundsStarted(uint256 indexed raffleId, uint256 timestamp)",
+];
+
+const PROVIDER_EVENTS: &[&str] = &[
+    "event RandomnessRequested(uint256 indexed requestId, uint256 indexed raffleId, address indexed raffle)",
+    "event RandomnessDelivered(uint256 indexed requestId, uint256 randomness, bytes proof, address indexed raffle)",
+];
+
+/// Payment token events, found in payout transaction receipts (not indexed)
+const TOKEN_EVENTS: &[&str] =
+    &["event Transfer(address indexed from, address indexed to, uint256 value)"];
+
+/// ABIs of the indexed contracts and the payment token
+pub struct Abis {
+    pub factory: JsonAbi,
+    pub raffle: JsonAbi,
+    pub provider: JsonAbi,
+    pub token: JsonAbi,
+}
+
+impl Abis {
+    pub fn new() -> Self {
+        Self {
+            factory: JsonAbi::parse(FACTORY_EVENTS.iter().copied()).expect("factory ABI"),
+            raffle: JsonAbi::parse(RAFFLE_EVENTS.iter().copied()).expect("raffle ABI"),
+            provider: JsonAbi::parse(PROVIDER_EVENTS.iter().copied()).expect("provider ABI"),
+            token: JsonAbi::parse(TOKEN_EVENTS.iter().copied()).expect("token ABI"),
+        }
+    }
+}
+
+impl Default for Abis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes logs of any event in [`Abis`] by their signature (topic0)
+pub struct LogDecoder {
+    events: HashMap<B256, EventDecoder>,
+}
+
+struct EventDecoder {
+    name: String,
+    decoder: DynSolEvent,
+    /// Parameter names of the indexed values, in order
+    indexed_names: Vec<String>,
+    /// Parameter names of the non-indexed values, in order
+    body_names: Vec<String>,
+}
+
+/// A log decoded by [`LogDecoder`]
+#[derive(Serialize)]
+pub struct DecodedEvent {
+    pub event: String,
+    /// Parameters by ABI name: integers as decimal strings, addresses and bytes as
+    /// lowercase `0x` hex
+    pub params: Map<String, Value>,
+}
+
+impl LogDecoder {
+    pub fn new(abis: &Abis) -> Self {
+        let events = [&abis.factory, &abis.raffle, &abis.provider, &abis.token]
+            .into_iter()
+            .flat_map(JsonAbi::events)
+            .filter_map(|event| {
+                let names = |indexed: bool| {
+                    event
+                        .inputs
+                        .iter()
+                        .filter(|param| param.indexed == indexed)
+                        .map(|param| param.name.clone())
+                        .collect()
+                };
+                let decoder = EventDecoder {
+                    name: event.name.clone(),
+                    decoder: event.resolve().ok()?,
+                    indexed_names: names(true),
+                    body_names: names(false),
+                };
+                Some((event.selector(), decoder))
+            })
+            .collect();
+        Self { events }
+    }
+
+    /// Decodes a log, or `None` if its signature is unknown or it doesn't match the
+    /// event (e.g. indexed topics missing)
+    pub fn decode(&self, topics: Vec<B256>, data: Vec<u8>) -> Option<DecodedEvent> {
+        let event = self.events.get(topics.first()?)?;
+        let decoded = event
+            .decoder
+            .decode_log_data(&LogData::new_unchecked(topics, data.into()))
+            .ok()?;
+        let indexed = event.indexed_names.iter().zip(decoded.indexed);
+        let body = event.body_names.iter().zip(decoded.body);
+        let params = indexed
+            .chain(body)
+            .map(|(name, value)| (name.clone(), param_to_json(&value)))
+            .collect();
+        Some(DecodedEvent {
+            event: event.name.clone(),
+            params,
+        })
+    }
+
+    /// Decodes a log as stored in `events_raw` (`0x` hex topics and data)
+    pub fn decode_stored(&self, topics: &[String], data: &str) -> Option<DecodedEvent> {
+        let topics = topics
+            .iter()
+            .map(|topic| B256::from_str(topic).ok())
+            .collect::<Option<_>>()?;
+        let data = hex::decode(data.trim_start_matches("0x")).ok()?;
+        self.decode(topics, data)
+    }
+}
+
+/// Renders a decoded parameter as JSON without losing precision
+fn param_to_json(value: &DynSolValue) -> Value {
+    match value {
+        DynSolValue::Bool(value) => Value::Bool(*value),
+        DynSolValue::Int(value, _) => Value::String(value.to_string()),
+        DynSolValue::Uint(value, _) => Value::String(value.to_string()),
+        DynSolValue::Address(value) => Value::String(format!("{value:#x}")),
+        DynSolValue::FixedBytes(word, size) => {
+            Value::String(format!("0x{}", hex::encode(&word[..*size])))
+        }
+        DynSolValue::Bytes(value) => Value::String(format!("0x{}", hex::encode(value))),
+        DynSolValue::String(value) => Value::String(value.clone()),
+        DynSolValue::Array(values)
+        | DynSolValue::FixedArray(values)
+        | DynSolValue::Tuple(values) => Value::Array(values.iter().map(param_to_json).collect()),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{Address, U256};
+
+    #[test]
+    fn decodes_indexed_and_body_parameters() {
+        let abis = Abis::new();
+        let event = abis.raffle.event("RaffleClosed").unwrap()[0].clone();
+        let raffle_id = B256::from(U256::from(3).to_be_bytes::<32>());
+        let mut data = U256::from(12).to_be_bytes::<32>().to_vec();
+        data.extend(U256::from(12_000_000).to_be_bytes::<32>());
+
+        let decoded = LogDecoder::new(&abis)
+            .decode(vec![event.selector(), raffle_id], data)
+            .unwrap();
+        assert_eq!(decoded.event, "RaffleClosed");
+        assert_eq!(
+            Value::Object(decoded.params),
+            serde_json::json!({ "raffleId": "3", "totalTickets": "12", "pot": "12000000" })
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_and_malformed_logs_undecoded() {
+        let abis = Abis::new();
+        let decoder = LogDecoder::new(&abis);
+        assert!(
+            decoder
+                .decode(vec![B256::repeat_byte(1)], Vec::new())
+                .is_none()
+        );
+
+        // KeeperUpdated has two indexed addresses; topic0 alone doesn't decode
+        let event = abis.raffle.event("KeeperUpdated").unwrap()[0].clone();
+        assert!(decoder.decode(vec![event.selector()], Vec::new()).is_none());
+        let keeper = Address::repeat_byte(0xab).into_word();
+        let decoded = decoder
+            .decode(vec![event.selector(), keeper, keeper], Vec::new())
+            .unwrap();
+        assert_eq!(
+            decoded.params["newKeeper"],
+            format!("{:#x}", Address::repeat_byte(0xab))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Audit trail of admin API calls
+//!
+//! Every request to a `/v1/admin` route is recorded in `admin_audit` once it has been
+//! answered: who made it (token subject or `admin-api-key`, NULL if the credential was
+//! refused), the action (method and route), its parameters (path, query and JSON
+//! body), and the outcome (status and error code). `GET /v1/admin/audit` lists the
+//! entries. Nothing is recorded while admin endpoints are disabled.
+//!
+//! # Security Considerations
+//! - The `Authorization` header is never recorded; responses (which may carry a new
+//!   key or token) aren't either
+//! - Request bodies over [`MAX_BODY_BYTES`] are refused rather than recorded in part
+//! - A failed insert is logged but doesn't fail the request, which already ran
+
+use crate::auth;
+use crate::error::{self, ApiError};
+use crate::state::AppState;
+use axum::body::Body;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::{Map, Value};
+
+/// Path of admin routes below the deployment prefix
+const ADMIN_PATH: &str = "/admin/";
+
+/// Largest admin request body accepted
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Middleware recording admin requests in `admin_audit`
+pub async fn record(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let enabled = state.config.admin_api_key.is_some() || state.config.admin_jwt_secret.is_some();
+    if !path.starts_with(ADMIN_PATH) || !enabled {
+        return next.run(request).await;
+    }
+
+    // Matched paths include the deployment prefix the router is nested under
+    let route = matched_path
+        .as_ref()
+        .map(MatchedPath::as_str)
+        .map(|route| state.config.strip_route_prefix(route).unwrap_or(route))
+        .unwrap_or(path.as_str());
+    let action = format!("{} {route}", request.method());
+    let principal = auth::authenticate(&state.config, request.headers()).ok();
+
+    let mut parameters = Map::new();
+    if let Some(query) = request.uri().query() {
+        let query: Map<String, Value> = form_urlencoded::parse(query.as_bytes())
+            .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+            .collect();
+        parameters.insert("query".to_string(), Value::Object(query));
+    }
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => {
+            let response = ApiError::invalid_parameter(
+                "body",
+                format!("request body must not exceed {MAX_BODY_BYTES} bytes"),
+            )
+            .into_response();
+            let error_code = error::response_error_code(&response);
+            insert(
+                &state,
+                principal,
+                &action,
+                &path,
+                parameters,
+                response.status(),
+                error_code,
+            )
+            .await;
+            return response;
+        }
+    };
+    if !body.is_empty() {
+        let value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        parameters.insert("body".to_string(), value);
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    // Read before awaiting; the response body isn't `Sync`
+    let error_code = error::response_error_code(&response);
+    insert(
+        &state,
+        principal,
+        &action,
+        &path,
+        parameters,
+        response.status(),
+        error_code,
+    )
+    .await;
+    response
+}
+
+/// Writes one audit entry, logging rather than returning a failure
+async fn insert(
+    state: &AppState,
+    principal: Option<auth::Principal>,
+    action: &str,
+    path: &str,
+    parameters: Map<String, Value>,
+    status: StatusCode,
+    error_code: Option<String>,
+) {
+    let (actor, role) = principal
+        .map(|principal| (principal.subject, principal.role.as_str()))
+        .unzip();
+    let result = sqlx::query(
+        "INSERT INTO admin_audit (actor, role, action, path, parameters, status, error_code)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&actor)
+    .bind(role)
+    .bind(action)
+    .bind(path)
+    .bind(Value::Object(parameters))
+    .bind(i16::try_from(status.as_u16()).unwrap_or(i16::MAX))
+    .bind(error_code)
+    .execute(&state.db)
+    .await;
+    if let Err(err) = result {
+        tracing::error!(
+            error = %err,
+            action,
+            actor = actor.as_deref().unwrap_or("-"),
+            status = status.as_u16(),
+            "failed to record admin audit entry"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Randomness analytics
+//!
+//! Sanity tests over the `randomness` delivered to raffles, served by
+//! `GET /v1/audit/randomness`. Values are expected to be uniform over uint256, so:
+//! - their residues modulo small numbers should be uniform (chi-square test),
+//! - consecutive values, in raffle order, should be uncorrelated (serial correlation),
+//! - no value should repeat.
+//!
+//! Tests whose p-value falls below [`ANOMALY_P_VALUE`], and any repeated value, are
+//! flagged under `anomalies` for investigation. A flag is not proof of bias: with
+//! several tests, an occasional low p-value is expected by chance.
+
+use chrono::{DateTime, Utc};
+use ethers::types::U256;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+
+/// Moduli of the residue tests: the low bits, and the last decimal digit
+const RESIDUE_MODULI: [u64; 2] = [16, 10];
+
+/// p-value below which a test is flagged
+pub const ANOMALY_P_VALUE: f64 = 0.001;
+
+/// Smallest expected count per residue for the chi-square p-value to be reported
+pub const MIN_EXPECTED_PER_BUCKET: f64 = 5.0;
+
+/// Fewest values the serial correlation p-value is reported for
+const MIN_SERIAL_SAMPLES: usize = 30;
+
+#[derive(Serialize)]
+pub struct RandomnessReport {
+    pub generated_at: DateTime<Utc>,
+    /// Valid randomness values tested
+    pub samples: usize,
+    /// Raffles whose stored randomness is not a decimal uint256
+    pub invalid: Vec<i64>,
+    /// Values delivered to more than one raffle
+    pub duplicates: Vec<DuplicateRandomness>,
+    pub residues: Vec<ResidueTest>,
+    pub serial_correlation: SerialCorrelation,
+    /// Human-readable descriptions of the failed checks (empty when all passed)
+    pub anomalies: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateRandomness {
+    pub randomness: String,
+    pub raffle_ids: Vec<i64>,
+}
+
+/// Chi-square test of `randomness % modulus` against a uniform distribution
+#[derive(Serialize)]
+pub struct ResidueTest {
+    pub modulus: u64,
+    /// Values per residue, indexed by residue
+    pub counts: Vec<u64>,
+    pub expected: f64,
+    pub chi_square: Option<f64>,
+    pub degrees_of_freedom: usize,
+    /// Null without data or while a residue expects fewer than 5 values
+    pub p_value: Option<f64>,
+}
+
+/// Lag-1 serial correlation of the values in raffle order, scaled to [0, 1)
+#[derive(Serialize)]
+pub struct SerialCorrelation {
+    /// Null with fewer than 3 values or when they are all equal
+    pub coefficient: Option<f64>,
+    /// Two-sided; null with fewer than 30 values
+    pub p_value: Option<f64>,
+}
+
+/// Runs every test over the randomness of all raffles, hot and archived
+pub async fn randomness_report(db: &PgPool) -> Result<RandomnessReport, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT raffle_id, randomness
+         FROM raffles_all
+         WHERE randomness IS NOT NULL
+         ORDER BY raffle_id",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut values = Vec::with_capacity(rows.len());
+    let mut invalid = Vec::new();
+    let mut raffles_by_value: HashMap<U256, Vec<i64>> = HashMap::new();
+    for row in &rows {
+        let raffle_id: i64 = row.try_get("raffle_id")?;
+        let randomness: String = row.try_get("randomness")?;
+        match U256::from_dec_str(&randomness) {
+            Ok(value) => {
+                values.push(value);
+                raffles_by_value.entry(value).or_default().push(raffle_id);
+            }
+            Err(_) => invalid.push(raffle_id),
+        }
+    }
+
+    let mut duplicates: Vec<_> = raffles_by_value
+        .into_iter()
+        .filter(|(_, raffle_ids)| raffle_ids.len() > 1)
+        .map(|(value, raffle_ids)| DuplicateRandomness {
+            randomness: value.to_string(),
+            raffle_ids,
+        })
+        .collect();
+    duplicates.sort_by_key(|duplicate| duplicate.raffle_ids[0]);
+
+    let residues: Vec<_> = RESIDUE_MODULI
+        .iter()
+        .map(|&modulus| residue_test(&values, modulus))
+        .collect();
+    let serial_correlation = serial_correlation(&values);
+
+    let mut anomalies = Vec::new();
+    for duplicate in &duplicates {
+        anomalies.push(format!(
+            "randomness {} delivered to raffles {:?}",
+            duplicate.randomness, duplicate.raffle_ids
+        ));
+    }
+    for test in &residues {
+        if let Some(p_value) = test.p_value.filter(|p| *p < ANOMALY_P_VALUE) {
+            anomalies.push(format!(
+                "residues mod {} are not uniform (p = {p_value:.2e})",
+                test.modulus
+            ));
+        }
+    }
+    if let Some(p_value) = serial_correlation.p_value.filter(|p| *p < ANOMALY_P_VALUE) {
+        anomalies.push(format!(
+            "consecutive values are correlated (p = {p_value:.2e})"
+        ));
+    }
+
+    Ok(RandomnessReport {
+        generated_at: Utc::now(),
+        samples: values.len(),
+        invalid,
+        duplicates,
+        residues,
+        serial_correlation,
+        anomalies,
+    })
+}
+
+fn residue_test(values: &[U256], modulus: u64) -> ResidueTest {
+    let mut counts = vec![0u64; modulus as usize];
+    for value in values {
+        counts[(value % U256::from(modulus)).as_usize()] += 1;
+    }
+    let expected = values.len() as f64 / modulus as f64;
+    let degrees_of_freedom = modulus as usize - 1;
+    let chi_square = (!values.is_empty()).then(|| {
+        counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum::<f64>()
+    });
+    let p_value = chi_square
+        .filter(|_| expected >= MIN_EXPECTED_PER_BUCKET)
+        .map(|chi_square| chi_square_p_value(chi_square, degrees_of_freedom as f64));
+    ResidueTest {
+        modulus,
+        counts,
+        expected,
+        chi_square,
+        degrees_of_freedom,
+        p_value,
+    }
+}
+
+/// Knuth's circular lag-1 serial correlation test (TAOCP vol. 2, 3.3.2 K)
+fn serial_correlation(values: &[U256]) -> SerialCorrelation {
+    let n = values.len();
+    if n < 3 {
+        return SerialCorrelation {
+            coefficient: None,
+            p_value: None,
+        };
+    }
+    // The top 64 bits as a fraction of 2^64
+    let u: Vec<f64> = values
+        .iter()
+        .map(|value| (value >> 192).as_u64() as f64 / 2f64.powi(64))
+        .collect();
+    let sum: f64 = u.iter().sum();
+    let sum_squares: f64 = u.iter().map(|x| x * x).sum();
+    let sum_products: f64 = (0..n).map(|i| u[i] * u[(i + 1) % n]).sum();
+    let n_f = n as f64;
+    let denominator = n_f * sum_squares - sum * sum;
+    if denominator <= 0.0 {
+        return SerialCorrelation {
+            coefficient: None,
+            p_value: None,
+        };
+    }
+    let coefficient = (n_f * sum_products - sum * sum) / denominator;
+
+    // Mean and standard deviation of the coefficient for independent values
+    let mean = -1.0 / (n_f - 1.0);
+    let deviation = (n_f * (n_f - 3.0) / (n_f + 1.0)).sqrt() / (n_f - 1.0);
+    let p_value = (n >= MIN_SERIAL_SAMPLES)
+        .then(|| erfc(((coefficient - mean) / deviation).abs() / std::f64::consts::SQRT_2));
+    SerialCorrelation {
+        coefficient: Some(coefficient),
+        p_value,
+    }
+}
+
+/// Probability of a chi-square statistic at least this large under the null hypothesis
+///
+/// Uses the Wilson-Hilferty normal approximation, accurate to about 0.01 from a
+/// handful of degrees of freedom up, which is plenty for a transparency report.
+pub fn chi_square_p_value(chi_square: f64, degrees_of_freedom: f64) -> f64 {
+    let variance = 2.0 / (9.0 * degrees_of_freedom);
+    let z = ((chi_square / degrees_of_freedom).cbrt() - (1.0 - variance)) / variance.sqrt();
+    (0.5 * erfc(z / std::f64::consts::SQRT_2)).clamp(0.0, 1.0)
+}
+
+/// Complementary error function (Numerical Recipes `erfcc`, relative error below 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A value whose top 64 bits are `x`, i.e. `x / 2^64` as seen by the serial test
+    fn top_bits(x: u64) -> U256 {
+        U256::from(x) << 192
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn erfc_matches_reference_values() {
+        assert_close(erfc(0.0), 1.0, 1.2e-7);
+        assert_close(erfc(0.5), 0.479_500_122, 1.2e-7);
+        assert_close(erfc(1.0), 0.157_299_207, 1.2e-7);
+        assert_close(erfc(2.0), 0.004_677_735, 1.2e-7);
+        assert_close(erfc(-1.0), 1.842_700_793, 1.2e-7);
+    }
+
+    #[test]
+    fn chi_square_p_values_match_critical_values() {
+        // (statistic, degrees of freedom, upper-tail probability) from chi-square tables
+        for (chi_square, degrees_of_freedom, p) in [
+            (9.342, 10.0, 0.5),
+            (16.919, 9.0, 0.05),
+            (21.666, 9.0, 0.01),
+            (24.996, 15.0, 0.05),
+            (30.578, 15.0, 0.01),
+            (8.547, 15.0, 0.9),
+        ] {
+            assert_close(chi_square_p_value(chi_square, degrees_of_freedom), p, 0.01);
+        }
+        assert_close(chi_square_p_value(0.0, 15.0), 1.0, 1e-6);
+        assert!(chi_square_p_value(1000.0, 15.0) < 1e-12);
+    }
+
+    #[test]
+    fn residue_tests_uniform_skewed_and_sparse_values() {
+        let uniform: Vec<U256> = (0..160u64).map(U256::from).collect();
+        let test = residue_test(&uniform, 16);
+        assert_eq!(test.counts, vec![10; 16]);
+        assert_eq!((test.expected, test.degrees_of_freedom), (10.0, 15));
+        assert_eq!(test.chi_square, Some(0.0));
+        assert_close(test.p_value.unwrap(), 1.0, 1e-6);
+
+        // Every value ends in 0: (50 - 5)^2 / 5 + 9 * 5 = 450
+        let skewed: Vec<U256> = (0..50u64).map(|i| U256::from(i * 10)).collect();
+        let test = residue_test(&skewed, 10);
+        assert_eq!(test.chi_square, Some(450.0));
+        assert!(test.p_value.unwrap() < ANOMALY_P_VALUE);
+
+        // Fewer than 5 values expected per residue: no p-value
+        let sparse: Vec<U256> = (0..16u64).map(U256::from).collect();
+        let test = residue_test(&sparse, 16);
+        assert_eq!(test.chi_square, Some(0.0));
+        assert_eq!(test.p_value, None);
+
+        let test = residue_test(&[], 10);
+        assert_eq!((test.chi_square, test.p_value), (None, None));
+    }
+
+    #[test]
+    fn serial_correlation_of_alternating_values_is_minus_one() {
+        let alternating: Vec<U256> = (0..40)
+            .map(|i| top_bits(if i % 2 == 0 { 1 << 60 } else { 3 << 60 }))
+            .collect();
+        let result = serial_correlation(&alternating);
+        assert_close(result.coefficient.unwrap(), -1.0, 1e-9);
+        assert!(result.p_value.unwrap() < ANOMALY_P_VALUE);
+
+        // Below MIN_SERIAL_SAMPLES the coefficient is reported without a p-value
+        let result = serial_correlation(&alternating[..10]);
+        assert_close(result.coefficient.unwrap(), -1.0, 1e-9);
+        assert_eq!(result.p_value, None);
+    }
+
+    #[test]
+    fn serial_correlation_needs_three_distinct_values() {
+        let constant = vec![top_bits(1 << 62); 40];
+        let result = serial_correlation(&constant);
+        assert_eq!((result.coefficient, result.p_value), (None, None));
+
+        let short = [top_bits(1), top_bits(2)];
+        let result = serial_correlation(&short);
+        assert_eq!((result.coefficient, result.p_value), (None, None));
+        let result = serial_correlation(&[]);
+        assert_eq!((result.coefficient, result.p_value), (None, None));
+    }
+}
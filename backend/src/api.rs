@@ -7,28 +7,119 @@
 //! - `GET /v1/raffles` - List raffles with pagination and optional status filter
 //! - `GET /v1/raffles/:raffle_id` - Get raffle details
 //! - `GET /v1/raffles/:raffle_id/purchases` - Get ticket purchase ranges
+//! - `GET /v1/raffles/:raffle_id/participants` - Per-buyer ticket totals and merged ranges
+//! - `GET /v1/raffles/:raffle_id/stats/histogram` - Distribution of purchase sizes
+//! - `GET /v1/raffles/:raffle_id/purchase-curve` - Cumulative tickets sold over time
+//! - `GET /v1/raffles/:raffle_id/pending` - Unconfirmed purchases seen in the mempool
 //! - `GET /v1/raffles/:raffle_id/proof` - Get verification proof data
+//! - `GET /v1/raffles/:raffle_id/proof/bundle` - Proof, ticket ranges and raw logs as one download
+//! - `GET /v1/raffles/:raffle_id/attestation` - Get a signed statement of the final result
+//! - `GET /v1/raffles/:raffle_id/card` - Open Graph / Twitter card fields for link previews
+//! - `GET /v1/embed/raffles/:raffle_id` - Minimal cached payload for third-party widgets (open CORS)
+//! - `POST /v1/verify` - Recompute a winner from randomness and ticket ranges
+//! - `GET /v1/chain` - Deployment constants and current head for frontend bootstrapping
+//! - `GET /v1/status` - Indexer progress and RPC circuit breaker state
+//! - `GET /v1/events` - Stored raw logs filtered by contract, topic0 and blocks, decoded where possible
+//! - `GET /v1/fees` - Protocol fees per fee recipient, optionally bucketed by period
+//! - `GET /v1/ws` - WebSocket stream of purchases and status changes (see [`crate::live`])
 //! - `GET /v1/randomness/requests` - List randomness requests (with optional filters)
 //! - `GET /v1/randomness/requests/:request_id` - Get randomness request details
 //! - `GET /v1/randomness/fulfillments` - List randomness fulfillments
+//! - `POST /v1/refund-reminders` - Subscribe a wallet to refund reminders (signed request)
+//! - `GET /v1/digests/latest` - Summary of the last finished UTC day
+//! - `GET /v1/audit/fairness` - Winner recomputation and index distribution over finalized raffles
+//! - `GET /v1/analytics/overlap` - Buyers and ticket volume two raffles share
+//! - `GET /v1/analytics/cohorts` - Participant cohorts by first-purchase week and their retention
+//! - `POST /v1/referral-codes` - Register a referral code (signed by a raffle creator)
+//! - `GET /v1/referral-codes` - Conversion stats per referral code (see [`crate::referrals`])
+//! - `GET /v1/referral-codes/:code` - Conversion stats of a code, per raffle
+//! - `GET /v1/audit/randomness` - Statistical tests over delivered randomness (see [`crate::analytics`])
+//! - `GET /v1/usage` - Daily usage and quota of the caller's API key (see [`crate::api_keys`])
+//! - `GET /v1/admin/keeper/txs` - Keeper transaction submissions (`readonly` role)
+//! - `POST /v1/admin/api-keys` - Mint an API key (`admin` role)
+//! - `GET /v1/admin/api-keys` - List API keys with today's usage (`readonly` role)
+//! - `DELETE /v1/admin/api-keys/:key_id` - Revoke an API key (`admin` role)
+//! - `GET /v1/admin/api-keys/:key_id/usage` - Daily usage of an API key (`readonly` role)
+//! - `GET /v1/admin/exports` - Parquet snapshots in object storage (`readonly` role)
+//! - `POST /v1/admin/indexer/pause` - Stop indexing between batches (`operator` role)
+//! - `POST /v1/admin/indexer/resume` - Continue indexing (`operator` role)
+//! - `PUT /v1/admin/indexer/cursor` - Move the paused indexer's cursor (`admin` role)
+//! - `GET /v1/admin/overview` - Indexer, queues, jobs, tables and pools at a glance (`readonly` role)
+//! - `POST /v1/admin/tokens` - Issue a role-scoped admin token (`admin` role, see [`crate::auth`])
+//! - `GET /v1/admin/audit` - Admin API calls, newest first (`admin` role, see [`crate::admin_audit`])
+//! - `GET /v1/admin/blocklist` - Blocked addresses (`readonly` role)
+//! - `PUT /v1/admin/blocklist/:address` - Block an address (`admin` role)
+//! - `DELETE /v1/admin/blocklist/:address` - Unblock an address (`admin` role)
+//! - `GET /v1/admin/webhooks` - Registered webhooks and the signing secret in use (`readonly` role)
+//! - `GET /v1/admin/webhooks/attempts` - Webhook delivery attempts with response statuses (`readonly` role)
+//! - `POST /v1/admin/webhooks/replay` - Queue failed webhook notifications again (`operator` role)
+//! - `POST /v1/admin/webhooks/signing-secret` - Rotate the webhook signing secret (`admin` role)
 //!
 //! # Security Considerations
 //! - All queries use parameterized SQL (no injection risk)
+//! - Cacheable routes carry `Cache-Control` from `CACHE_CONTROL_ROUTES`; responses to
+//!   requests with an API key vary on it so shared caches don't mix keys
+//! - Raffle details (once the countdown ended) and proofs carry `Last-Modified` and
+//!   answer a matching `If-Modified-Since` with an empty 304
+//! - Terminal raffles' details, proofs and purchases are served from stored snapshots
+//!   (see [`crate::snapshots`])
+//! - Admin endpoints require the `ADMIN_API_KEY` bearer token or an admin token with
+//!   the endpoint's role, and are hidden (404) when neither is configured
+//! - Requests acting for a wallet must be signed by it (see [`crate::signatures`])
+//! - Only `/v1/embed` allows cross-origin reads (`Access-Control-Allow-Origin: *`); it
+//!   serves public data without credentials
 //! - Pagination is enforced with maximum limits
+//! - Path and query parameters are validated before use; unknown query parameters are
+//!   rejected (see [`crate::extract`])
 //! - Error messages don't expose internal details
 
+use crate::abi::{Abis, DecodedEvent, LogDecoder};
+use crate::analytics::{self, RandomnessReport};
+use crate::api_keys;
+use crate::auth::{self, RequireAdmin, RequireOperator, RequireReadOnly, RequiredRole, Role};
+use crate::chain::ChainReadError;
+use crate::circuit::{CircuitSnapshot, CircuitState};
+use crate::cohorts::{self, CohortReport};
+use crate::config::RouteCacheControl;
+use crate::digest::Digest;
+use crate::error::ApiError;
+use crate::export::ExportFile;
+use crate::extract::{self, Validate, ValidatedPath, ValidatedQuery};
+use crate::format::{self, AmountFormat};
+use crate::indexer::IndexerStatus;
+use crate::live;
+use crate::metrics;
+use crate::notify;
+use crate::referrals;
+use crate::signatures::{MessageType, SigningDomain};
+use crate::snapshots::{self, Snapshot};
 use crate::state::AppState;
+use crate::stuck::{self, StuckRaffle};
+use crate::sybil::ClusterFlag;
+use crate::tasks::TaskSnapshot;
 use axum::{
-    Json, Router,
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
+    Extension, Json, Router,
+    body::{Body, Bytes},
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header, request::Parts},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SubsecRound, Utc};
+use ethers::signers::Signer;
 use ethers::types::U256;
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::marker::PhantomData;
+use std::sync::LazyLock;
+use tracing::Instrument;
+
+mod v2;
+
+pub use v2::router as v2_router;
 
 // ============================================================================
 // CONSTANTS
@@ -39,6 +130,143 @@ const DEFAULT_PAGE_LIMIT: i64 = 50;
 /// Maximum allowed items per page (prevents DoS via large queries)
 const MAX_PAGE_LIMIT: i64 = 100;
 
+/// Maximum number of purchase ranges accepted by `POST /v1/verify`
+const MAX_VERIFY_PURCHASES: usize = 10_000;
+
+/// Maximum number of ticket indices per `POST /v1/raffles/:raffle_id/tickets/resolve`
+const MAX_RESOLVE_INDICES: usize = 1_000;
+
+/// `stale-while-revalidate` of embed payloads, as a multiple of `EMBED_CACHE_TTL_SECS`
+/// (also bounds how stale the server-side cache may get)
+pub const EMBED_STALE_FACTOR: u32 = 10;
+
+/// Oldest `GET /v1/network` response served while refreshing, as a multiple of
+/// `NETWORK_CACHE_TTL_SECS`
+pub const NETWORK_STALE_FACTOR: u32 = 3;
+
+/// Blocks the average block time of `GET /v1/network` is computed over
+const NETWORK_SAMPLE_BLOCKS: u64 = 20;
+
+/// Recent finalizations the draw time estimate of `GET /v1/raffles/{raffle_id}/timeline`
+/// is computed over
+const DRAW_ESTIMATE_SAMPLE: i64 = 100;
+
+/// `max-age` of embed payloads for finalized raffles, which no longer change
+const FINAL_EMBED_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// Signed message for `POST /v1/refund-reminders`; an empty `webhookUrl` unsubscribes
+const REFUND_REMINDER_MESSAGE: MessageType = MessageType {
+    primary_type: "RefundReminderSubscription",
+    fields: &[("webhookUrl", "string")],
+};
+
+/// Signed by a raffle's creator to see the sybil cluster flags of its participants
+const PARTICIPANT_CLUSTERS_MESSAGE: MessageType = MessageType {
+    primary_type: "ParticipantClusters",
+    fields: &[("raffleId", "uint256")],
+};
+
+/// Signed by a raffle creator to register a referral code
+const REFERRAL_CODE_MESSAGE: MessageType = MessageType {
+    primary_type: "ReferralCodeRegistration",
+    fields: &[("code", "string")],
+};
+
+/// Header carrying a [`SignedRequest`] as JSON on GET requests
+const SIGNED_REQUEST_HEADER: &str = "x-signed-request";
+
+/// Bucket width of `/purchase-curve` when no `interval` is given
+const DEFAULT_CURVE_INTERVAL_SECS: i64 = 60 * 60;
+
+/// Bounds on the `/purchase-curve` bucket width (1 minute to 7 days)
+const MIN_CURVE_INTERVAL_SECS: i64 = 60;
+const MAX_CURVE_INTERVAL_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Most buckets one `/purchase-curve` response may span
+const MAX_CURVE_POINTS: i64 = 2000;
+
+/// Cohorts `/analytics/cohorts` returns by default, and at most
+const DEFAULT_COHORT_WEEKS: i64 = 12;
+const MAX_COHORT_WEEKS: i64 = 104;
+
+/// Smallest ticket count of each purchase size bucket (1, 2-5, 6-20, 21-100, 101+)
+const PURCHASE_SIZE_BUCKETS: [i32; 5] = [1, 2, 6, 21, 101];
+
+/// Maximum length of an API key name
+const MAX_API_KEY_NAME_LEN: usize = 100;
+
+/// Default and maximum days of API key usage history
+const DEFAULT_USAGE_DAYS: i64 = 30;
+const MAX_USAGE_DAYS: i64 = 365;
+
+/// Maximum length of a webhook URL
+const MAX_WEBHOOK_URL_LEN: usize = 2048;
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Rows buffered between the database cursor and a streamed response
+const NDJSON_BUFFER_ROWS: usize = 64;
+
+/// `statement_timeout` of streamed listings, which outlive the API statement timeout
+const NDJSON_STATEMENT_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+
+/// Equal slices of the ticket range the fairness audit sorts winning indices into
+const FAIRNESS_BUCKETS: usize = 10;
+
+/// Values of the `status` filter of `GET /v1/raffles` (effective statuses)
+const RAFFLE_STATUSES: &[&str] = &[
+    "ACTIVE",
+    "ENDED",
+    "CLOSED",
+    "RANDOM_REQUESTED",
+    "RANDOM_FULFILLED",
+    "FINALIZED",
+    "REFUNDING",
+    "CANCELED",
+];
+
+/// Values of the `status` filter of `GET /v1/admin/keeper/txs`
+const KEEPER_TX_STATUSES: &[&str] = &["pending", "confirmed", "reverted", "replaced", "dropped"];
+
+/// SQL expression deriving the display status of a raffle.
+///
+/// ACTIVE raffles that are past `end_time` (or sold out) can no longer accept purchases
+/// but stay ACTIVE on-chain until someone calls `close()`. They are reported as ENDED.
+const EFFECTIVE_STATUS_SQL: &str = "CASE
+        WHEN status = 'ACTIVE'
+            AND (end_time <= now() OR (max_tickets > 0 AND total_tickets >= max_tickets))
+        THEN 'ENDED'
+        ELSE status
+    END";
+
+/// Condition on `raffles` leaving out raffles created by blocked addresses
+const CREATOR_NOT_BLOCKED_SQL: &str =
+    "NOT EXISTS (SELECT 1 FROM blocked_addresses b WHERE b.address = raffles.creator)";
+
+/// Purchases attributed to the referral codes of a `codes` CTE: those of referral
+/// transactions in raffles created by the code's owner, next to every buyer's first
+/// purchase ever (see [`crate::referrals`])
+const REFERRAL_PURCHASES_SQL: &str = "attributed AS (
+        SELECT c.code, p.raffle_id, p.buyer, p.count, p.amount, p.tx_hash, p.log_index
+        FROM codes c
+        JOIN purchase_referrals r ON r.code = c.code
+        JOIN purchases_all p ON p.tx_hash = r.tx_hash
+        JOIN raffles_all f ON f.raffle_id = p.raffle_id AND f.creator = c.owner
+     ),
+     first_purchases AS (
+        SELECT DISTINCT ON (buyer) buyer, tx_hash, log_index
+        FROM purchases_all
+        WHERE buyer IN (SELECT buyer FROM attributed)
+        ORDER BY buyer, block_number, log_index
+     )";
+
+/// [`ReferralStats`] columns over `attributed a LEFT JOIN first_purchases fp`
+const REFERRAL_STATS_SQL: &str = "COUNT(a.tx_hash) AS purchases,
+        COUNT(DISTINCT a.buyer) AS buyers,
+        COUNT(DISTINCT a.buyer) FILTER (WHERE fp.buyer IS NOT NULL) AS new_buyers,
+        COALESCE(SUM(a.count), 0)::bigint AS tickets,
+        COALESCE(SUM(a.amount), 0)::text AS volume";
+
 // ============================================================================
 // ROUTER
 // ============================================================================
@@ -48,19 +276,113 @@ pub fn router() -> Router<AppState> {
     Router::new()
         // Raffle endpoints
         .route("/raffles", get(list_raffles))
-        .route("/raffles/:raffle_id", get(get_raffle_by_id))
-        .route("/raffles/:raffle_id/purchases", get(list_purchases))
-        .route("/raffles/:raffle_id/proof", get(get_raffle_proof))
+        .route("/raffles/stuck", get(list_stuck_raffles))
+        .route("/raffles/{raffle_id}", get(get_raffle_by_id))
+        .route("/raffles/{raffle_id}/purchases", get(list_purchases))
+        .route(
+            "/raffles/{raffle_id}/purchases.ndjson",
+            get(list_purchases_ndjson),
+        )
+        .route("/raffles/{raffle_id}/participants", get(list_participants))
+        .route(
+            "/raffles/{raffle_id}/stats/histogram",
+            get(get_purchase_histogram),
+        )
+        .route(
+            "/raffles/{raffle_id}/purchase-curve",
+            get(get_purchase_curve),
+        )
+        .route("/raffles/{raffle_id}/pending", get(list_pending_purchases))
+        .route(
+            "/raffles/{raffle_id}/tickets/resolve",
+            post(resolve_tickets),
+        )
+        .route("/raffles/{raffle_id}/proof", get(get_raffle_proof))
+        .route(
+            "/raffles/{raffle_id}/proof/bundle",
+            get(get_raffle_proof_bundle),
+        )
+        .route(
+            "/raffles/{raffle_id}/attestation",
+            get(get_raffle_attestation),
+        )
+        .route("/raffles/{raffle_id}/card", get(get_raffle_card))
+        .route(
+            "/raffles/{raffle_id}/reconciliation",
+            get(get_raffle_reconciliation),
+        )
+        .route("/raffles/{raffle_id}/ledger", get(get_raffle_ledger))
+        .route("/raffles/{raffle_id}/timeline", get(get_raffle_timeline))
+        .route("/verify", post(verify_winner))
+        .route("/chain", get(get_chain_info))
+        .route("/network", get(get_network))
+        .route("/status", get(get_status))
+        .route("/fees", get(list_fees))
+        .route("/stats/keeper", get(get_keeper_stats))
+        .route("/digests/latest", get(get_latest_digest))
+        .route("/changes", get(list_changes))
+        .route("/events", get(list_events))
+        .route("/audit/fairness", get(get_fairness_audit))
+        .route("/audit/randomness", get(get_randomness_audit))
+        .route("/analytics/overlap", get(get_buyer_overlap))
+        .route("/analytics/cohorts", get(get_cohorts))
+        .route(
+            "/referral-codes",
+            get(list_referral_codes).post(register_referral_code),
+        )
+        .route("/referral-codes/{code}", get(get_referral_code))
+        .route("/ws", get(live::ws_handler))
+        .route("/refund-reminders", post(subscribe_refund_reminders))
+        .route("/usage", get(get_own_api_key_usage))
         // Randomness provider endpoints
         .route("/randomness/requests", get(list_randomness_requests))
         .route(
-            "/randomness/requests/:request_id",
+            "/randomness/requests/{request_id}",
             get(get_randomness_request),
         )
         .route(
             "/randomness/fulfillments",
             get(list_randomness_fulfillments),
         )
+        // Third-party widget endpoints with their own CORS and cache policy
+        .merge(embed_router())
+        // Admin endpoints (ADMIN_API_KEY)
+        .route("/admin/keeper/txs", get(list_keeper_txs))
+        .route("/admin/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/admin/api-keys/{key_id}", delete(revoke_api_key))
+        .route("/admin/api-keys/{key_id}/usage", get(get_api_key_usage))
+        .route("/admin/exports", get(list_export_snapshots))
+        .route("/admin/anomalies", get(list_anomalies))
+        .route("/admin/indexer/pause", post(pause_indexer))
+        .route("/admin/indexer/resume", post(resume_indexer))
+        .route("/admin/indexer/cursor", put(set_indexer_cursor))
+        .route("/admin/overview", get(get_admin_overview))
+        .route("/admin/tokens", post(create_admin_token))
+        .route("/admin/audit", get(list_admin_audit))
+        .route("/admin/webhooks", get(list_webhooks))
+        .route("/admin/webhooks/attempts", get(list_webhook_attempts))
+        .route("/admin/webhooks/replay", post(replay_webhooks))
+        .route(
+            "/admin/webhooks/signing-secret",
+            post(rotate_webhook_signing_secret),
+        )
+        .route("/admin/blocklist", get(list_blocked_addresses))
+        .route(
+            "/admin/blocklist/{address}",
+            put(block_address).delete(unblock_address),
+        )
+        // Tag queries with the route for slow query metrics
+        .route_layer(middleware::from_fn(tag_query_source))
+}
+
+/// Routes for third-party embeds, readable from any origin
+fn embed_router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/embed/raffles/{raffle_id}",
+            get(get_embed_raffle).options(embed_preflight),
+        )
+        .layer(middleware::from_fn(embed_cors))
 }
 
 // ============================================================================
@@ -69,18 +391,413 @@ pub fn router() -> Router<AppState> {
 
 /// Query parameters for listing raffles
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct ListRafflesQuery {
     limit: Option<i64>,
     offset: Option<i64>,
-    /// Filter by status: ACTIVE, CLOSED, RANDOM_REQUESTED, RANDOM_FULFILLED, FINALIZED, REFUNDING
+    /// Filter by effective status: ACTIVE, ENDED, CLOSED, RANDOM_REQUESTED, RANDOM_FULFILLED,
+    /// FINALIZED, REFUNDING, CANCELED
     status: Option<String>,
+    /// Include raffles by blocked creators; requires an admin credential
+    #[serde(default)]
+    include_blocked: bool,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for ListRafflesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        if let Some(status) = &self.status
+            && !RAFFLE_STATUSES.contains(&status.as_str())
+        {
+            return Err(ApiError::InvalidStatus {
+                status: status.clone(),
+                allowed: RAFFLE_STATUSES,
+            });
+        }
+        Ok(())
+    }
 }
 
 /// Query parameters for paginated lists
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 struct PaginationQuery {
     limit: Option<i64>,
     offset: Option<i64>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for PaginationQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)
+    }
+}
+
+/// Query parameters for a raffle's purchases
+///
+/// `after_block` and `after_log_index` page by position in the chain: the next page
+/// starts after the last purchase of the previous one, so rows backfilled in between
+/// are neither skipped nor repeated.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PurchasesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    after_block: Option<i64>,
+    after_log_index: Option<i64>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl PurchasesQuery {
+    /// Position to continue after, as `(block_number, log_index)`
+    fn after(&self) -> Option<(i64, i64)> {
+        self.after_block.zip(self.after_log_index)
+    }
+
+    /// Whether this asks for the first page with default settings
+    fn is_default(&self) -> bool {
+        self.limit.is_none()
+            && self.offset.is_none()
+            && self.after_block.is_none()
+            && self.after_log_index.is_none()
+            && self.format == AmountFormat::Raw
+    }
+}
+
+impl Validate for PurchasesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_purchase_cursor(self.after_block, self.after_log_index)?;
+        if self.after().is_some() && self.offset.is_some() {
+            return Err(ApiError::invalid_parameter(
+                "offset",
+                "offset can't be combined with after_block",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for streaming a raffle's purchases
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PurchaseStreamQuery {
+    after_block: Option<i64>,
+    after_log_index: Option<i64>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for PurchaseStreamQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_purchase_cursor(self.after_block, self.after_log_index)
+    }
+}
+
+/// Query parameters for raffle details
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RaffleDetailsQuery {
+    /// `chain` to read the contracts directly when the raffle isn't indexed yet
+    fallback: Option<String>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for RaffleDetailsQuery {}
+
+/// Query parameters for listing raffle participants
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParticipantsQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Sort order by ticket count (default: tickets_desc)
+    sort: Option<ParticipantSort>,
+    /// Include sybil cluster flags; requires an admin credential or the creator's signature
+    #[serde(default)]
+    include_clusters: bool,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for ParticipantsQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)
+    }
+}
+
+/// Query parameters for responses with amounts only
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FormatQuery {
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for FormatQuery {}
+
+/// Query parameters for participant cohorts
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CohortsQuery {
+    /// Newest cohorts returned
+    weeks: Option<i64>,
+}
+
+impl Validate for CohortsQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        if let Some(weeks) = self.weeks
+            && !(1..=MAX_COHORT_WEEKS).contains(&weeks)
+        {
+            return Err(ApiError::invalid_parameter(
+                "weeks",
+                format!("weeks must be between 1 and {MAX_COHORT_WEEKS}"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for buyer overlap between two raffles
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverlapQuery {
+    raffle_a: i64,
+    raffle_b: i64,
+    /// Page of `participants`
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl Validate for OverlapQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        if self.raffle_a == self.raffle_b {
+            return Err(ApiError::invalid_parameter(
+                "raffle_b",
+                "raffle_a and raffle_b must be different raffles",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for listing referral codes
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReferralCodesQuery {
+    /// Only codes registered by this wallet
+    owner: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for ReferralCodesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_address("owner", self.owner.as_deref())
+    }
+}
+
+/// Query parameters for a raffle's purchase curve
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PurchaseCurveQuery {
+    /// Bucket width: a number of minutes, hours or days (`15m`, `1h`, `1d`)
+    interval: Option<String>,
+}
+
+impl PurchaseCurveQuery {
+    /// Bucket width in seconds
+    fn interval_secs(&self) -> Option<i64> {
+        let Some(interval) = self.interval.as_deref() else {
+            return Some(DEFAULT_CURVE_INTERVAL_SECS);
+        };
+        let unit_secs = match interval.chars().last()? {
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return None,
+        };
+        let count: i64 = interval[..interval.len() - 1].parse().ok()?;
+        count
+            .checked_mul(unit_secs)
+            .filter(|secs| (MIN_CURVE_INTERVAL_SECS..=MAX_CURVE_INTERVAL_SECS).contains(secs))
+    }
+}
+
+impl Validate for PurchaseCurveQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.interval_secs().is_none() {
+            return Err(ApiError::invalid_parameter(
+                "interval",
+                "interval must be a number of minutes, hours or days (e.g. 15m, 1h, 1d) \
+                 between 1m and 7d",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for a raffle's pot reconciliation
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReconciliationQuery {
+    /// Also compare against the raffle contract's pot and token balance
+    #[serde(default)]
+    onchain: bool,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for ReconciliationQuery {}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ParticipantSort {
+    #[default]
+    TicketsDesc,
+    TicketsAsc,
+}
+
+/// Query parameters for fee accounting
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FeesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Inclusive lower bound on payout time
+    from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on payout time
+    to: Option<DateTime<Utc>>,
+    fee_recipient: Option<String>,
+    /// Bucket totals by period; omitted means one total per recipient
+    interval: Option<FeeInterval>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for FeesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_address("fee_recipient", self.fee_recipient.as_deref())
+    }
+}
+
+/// Query parameters for keeper performance
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeeperStatsQuery {
+    /// Only raffles with this keeper
+    keeper: Option<String>,
+    /// Inclusive lower bound on the time a stage ended
+    from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on the time a stage ended
+    to: Option<DateTime<Utc>>,
+}
+
+impl Validate for KeeperStatsQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_address("keeper", self.keeper.as_deref())?;
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && from >= to
+        {
+            return Err(ApiError::invalid_parameter(
+                "from",
+                "from must be before to",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for incremental sync
+///
+/// The cursor is a block: changes in blocks after `since_block`, or after the last
+/// indexed event at or before `since`, are returned.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChangesQuery {
+    since_block: Option<i64>,
+    since: Option<DateTime<Utc>>,
+    /// Rows per kind the window is cut down to (a block is never split)
+    limit: Option<i64>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for ChangesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, None)?;
+        validate_id("since_block", self.since_block)?;
+        if self.since_block.is_some() && self.since.is_some() {
+            return Err(ApiError::invalid_parameter(
+                "since",
+                "since can't be combined with since_block",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for raw events
+///
+/// Pages continue after `(after_block, after_log_index)`, as for purchases.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventsQuery {
+    /// Emitting contract
+    address: Option<String>,
+    /// Event signature hash
+    topic0: Option<String>,
+    from_block: Option<i64>,
+    to_block: Option<i64>,
+    limit: Option<i64>,
+    after_block: Option<i64>,
+    after_log_index: Option<i64>,
+}
+
+impl Validate for EventsQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, None)?;
+        validate_address("address", self.address.as_deref())?;
+        if self
+            .topic0
+            .as_deref()
+            .is_some_and(|topic0| !extract::is_hash(topic0))
+        {
+            return Err(ApiError::invalid_parameter(
+                "topic0",
+                "topic0 must be a 0x-prefixed 32-byte hex hash",
+            ));
+        }
+        validate_id("from_block", self.from_block)?;
+        validate_id("to_block", self.to_block)?;
+        if let (Some(from), Some(to)) = (self.from_block, self.to_block)
+            && from > to
+        {
+            return Err(ApiError::invalid_parameter(
+                "from_block",
+                "from_block must not be after to_block",
+            ));
+        }
+        validate_purchase_cursor(self.after_block, self.after_log_index)
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FeeInterval {
+    Day,
+    Week,
+    Month,
 }
 
 /// Summary view of a raffle for list endpoints
@@ -89,13 +806,241 @@ struct RaffleSummary {
     raffle_id: i64,
     raffle_address: String,
     status: String,
+    /// Display status (ACTIVE raffles past `end_time` are reported as ENDED)
+    effective_status: String,
+    end_time: Option<DateTime<Utc>>,
+    ticket_price: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_price_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_price_hex: Option<String>,
+    total_tickets: i64,
+    unique_buyers: i64,
+    pot: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_hex: Option<String>,
+    winner: Option<String>,
+    #[serde(flatten)]
+    progress: RaffleProgress,
+}
+
+/// Filters of a raffle list page
+struct RaffleListFilter {
+    limit: i64,
+    offset: i64,
+    /// Only raffles with a lower ID, for keyset paging
+    before_raffle_id: Option<i64>,
+    /// Effective status
+    status: Option<String>,
+    include_blocked: bool,
+}
+
+/// A raffle as read by [`fetch_raffle_list`]
+struct RaffleListRow {
+    raffle_id: i64,
+    raffle_address: String,
+    status: String,
+    effective_status: String,
     end_time: Option<DateTime<Utc>>,
     ticket_price: String,
+    max_tickets: i64,
     total_tickets: i64,
+    unique_buyers: i64,
     pot: String,
     winner: Option<String>,
 }
 
+/// Server-computed convenience fields shared by raffle list and detail responses
+#[derive(Serialize)]
+struct RaffleProgress {
+    /// Seconds until `end_time` (0 once passed, null when no end time is set)
+    time_remaining_seconds: Option<i64>,
+    /// Tickets still available (null when `max_tickets` is 0, i.e. unlimited)
+    tickets_remaining: Option<i64>,
+    /// Percentage of `max_tickets` sold, 0-100 (null when unlimited)
+    fill_percent: Option<f64>,
+    is_sold_out: bool,
+}
+
+/// Stored raffle totals checked against the indexed purchases and refunds
+///
+/// Deltas are stored minus compared value, so a positive delta means the stored
+/// figure is too high.
+#[derive(Serialize)]
+struct ReconciliationResponse {
+    raffle_id: i64,
+    status: String,
+    total_tickets: i64,
+    pot: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_hex: Option<String>,
+    ledger: ReconciliationLedger,
+    /// `total_tickets` minus the tickets purchased
+    total_tickets_delta: i64,
+    /// `pot` minus the ledger's expected pot
+    pot_delta: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_delta_formatted: Option<String>,
+    /// Contract state (only with `?onchain=true`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    onchain: Option<ReconciliationOnchain>,
+    /// Whether every delta is zero
+    consistent: bool,
+}
+
+/// Totals of the indexed purchases and refunds of a raffle
+#[derive(Serialize)]
+struct ReconciliationLedger {
+    purchases: i64,
+    purchased_tickets: i64,
+    purchased_amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purchased_amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purchased_amount_hex: Option<String>,
+    refunds: i64,
+    refunded_amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refunded_amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refunded_amount_hex: Option<String>,
+    /// Purchased minus refunded amount; 0 once the pot was paid out (FINALIZED)
+    expected_pot: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_pot_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_pot_hex: Option<String>,
+}
+
+/// A raffle's pot as read from its contract
+#[derive(Serialize)]
+struct ReconciliationOnchain {
+    raffle_address: String,
+    total_tickets: i64,
+    pot: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_hex: Option<String>,
+    /// Payment tokens held by the raffle contract
+    balance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance_hex: Option<String>,
+    /// Stored `total_tickets` minus the contract's
+    total_tickets_delta: i64,
+    /// Stored `pot` minus the contract's
+    pot_delta: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_delta_formatted: Option<String>,
+    /// Stored `pot` minus the contract's token balance
+    balance_delta: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance_delta_formatted: Option<String>,
+}
+
+/// A raffle's ledger: balances over all entries and one page of entries
+#[derive(Serialize)]
+struct LedgerResponse {
+    raffle_id: i64,
+    /// Whether all entries sum to zero
+    balanced: bool,
+    balances: Vec<LedgerBalance>,
+    entries: Vec<LedgerEntry>,
+}
+
+#[derive(Serialize)]
+struct LedgerBalance {
+    account: String,
+    address: String,
+    /// Sum of the account's entries (token base units)
+    balance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance_formatted: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LedgerEntry {
+    id: i64,
+    entry: String,
+    account: String,
+    address: String,
+    /// Positive into the account, negative out of it
+    amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_formatted: Option<String>,
+    tx_hash: String,
+    log_index: i64,
+    block_number: i64,
+    block_time: Option<DateTime<Utc>>,
+}
+
+/// Time the draw stages took, over completed stages, and the raffles waiting on one
+#[derive(Serialize)]
+struct KeeperStatsResponse {
+    keeper: Option<String>,
+    close_to_request: StageStats,
+    request_to_fulfill: StageStats,
+    fulfill_to_finalize: StageStats,
+    /// Raffles between closing and finalization, longest waiting first
+    pending: Vec<PendingDraw>,
+}
+
+/// Percentiles of one stage's duration in seconds; null without completed stages
+#[derive(Default, Serialize)]
+struct StageStats {
+    count: i64,
+    p50_secs: Option<f64>,
+    p90_secs: Option<f64>,
+    p99_secs: Option<f64>,
+    max_secs: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct PendingDraw {
+    raffle_id: i64,
+    status: String,
+    keeper: Option<String>,
+    /// When the raffle reached `status`; null if the transition wasn't recorded
+    since: Option<DateTime<Utc>>,
+    waiting_secs: Option<i64>,
+}
+
+/// When a raffle reached each lifecycle stage (block times; null if not reached or not
+/// recorded), and when its draw is expected while one is pending
+#[derive(Serialize)]
+struct TimelineResponse {
+    raffle_id: i64,
+    status: String,
+    created_at: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    closed_at: Option<DateTime<Utc>>,
+    randomness_requested_at: Option<DateTime<Utc>>,
+    randomness_fulfilled_at: Option<DateTime<Utc>>,
+    finalized_at: Option<DateTime<Utc>>,
+    refunds_started_at: Option<DateTime<Utc>>,
+    canceled_at: Option<DateTime<Utc>>,
+    /// Only for raffles between closing and finalization with enough history
+    draw_estimate: Option<DrawEstimate>,
+}
+
+#[derive(Serialize)]
+struct DrawEstimate {
+    /// In the past when the draw is overdue
+    estimated_at: DateTime<Utc>,
+    /// Stage the estimate counts from (the raffle's current status)
+    from_status: String,
+    /// Median time from `from_status` to finalization over recent raffles
+    median_secs: i64,
+    /// Recent finalized raffles the median is computed over
+    sample_size: i64,
+}
+
 #[derive(Serialize)]
 struct RaffleDetails {
     raffle_id: i64,
@@ -103,19 +1048,72 @@ struct RaffleDetails {
     creator: String,
     end_time: Option<DateTime<Utc>>,
     ticket_price: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_price_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_price_hex: Option<String>,
     max_tickets: i64,
     fee_bps: i64,
     fee_recipient: String,
     status: String,
+    /// Display status (ACTIVE raffles past `end_time` are reported as ENDED)
+    effective_status: String,
     total_tickets: i64,
+    unique_buyers: i64,
     pot: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pot_hex: Option<String>,
     request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id_hex: Option<String>,
     request_tx: Option<String>,
     randomness: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    randomness_hex: Option<String>,
     randomness_tx: Option<String>,
     winning_index: Option<i64>,
     winner: Option<String>,
     finalized_tx: Option<String>,
+    /// Secondary operator allowed to drive the raffle lifecycle (initially the creator)
+    keeper: Option<String>,
+    /// Amount paid to the winner (set once payouts completed)
+    prize_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prize_amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prize_amount_hex: Option<String>,
+    /// Protocol fee paid to `fee_recipient` (set once payouts completed)
+    fee_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fee_amount_hex: Option<String>,
+    payout_tx: Option<String>,
+    /// Whether the payout transaction's receipt shows the expected token transfers
+    /// (null until checked)
+    payout_confirmed: Option<bool>,
+    #[serde(flatten)]
+    progress: RaffleProgress,
+    /// Where the data came from: `index` (database) or `chain` (direct contract reads)
+    source: &'static str,
+    /// When the response last changed, once `end_time` passed and the countdown stopped
+    #[serde(skip)]
+    last_modified: Option<DateTime<Utc>>,
+}
+
+/// Columns read into a [`PurchaseRange`]
+const PURCHASE_COLUMNS: &str = "buyer, start_index, end_index, count,
+    amount::text AS amount, whale, tx_hash, log_index, block_number, block_hash, block_time,
+    block_is_finalized(block_number) AS finalized, created_at,
+    EXISTS (SELECT 1 FROM blocked_addresses b WHERE b.address = buyer) AS buyer_blocked";
+
+/// Keyset condition on `purchases_all` binding `after_block` as `$n` and
+/// `after_log_index` as `$n+1`
+fn purchase_after_sql(n: usize) -> String {
+    let m = n + 1;
+    format!("(${n}::bigint IS NULL OR (block_number, log_index) > (${n}, ${m}::bigint))")
 }
 
 #[derive(Serialize)]
@@ -125,644 +1123,5943 @@ struct PurchaseRange {
     end_index: i64,
     count: i64,
     amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_hex: Option<String>,
+    /// Reached the whale purchase threshold when indexed
+    whale: bool,
+    /// The buyer is on the admin blocklist
+    buyer_blocked: bool,
     tx_hash: String,
     log_index: i64,
     block_number: i64,
+    block_hash: Option<String>,
+    /// Timestamp of the block containing the purchase (null for rows indexed before it was tracked)
+    block_time: Option<DateTime<Utc>>,
+    /// The block is at or below the chain's finalized block; otherwise a reorg may still drop it
+    finalized: bool,
     created_at: DateTime<Utc>,
 }
 
+impl PurchaseRange {
+    /// Reads a row selected with [`PURCHASE_COLUMNS`]
+    fn from_row(row: &PgRow, format: AmountFormat, decimals: u32) -> Result<Self, sqlx::Error> {
+        let amount: String = row.try_get("amount")?;
+        Ok(Self {
+            buyer: row.try_get("buyer")?,
+            start_index: row.try_get("start_index")?,
+            end_index: row.try_get("end_index")?,
+            count: row.try_get("count")?,
+            amount_formatted: format.render(&amount, decimals),
+            amount_hex: format.render_hex(&amount),
+            amount,
+            whale: row.try_get("whale")?,
+            buyer_blocked: row.try_get("buyer_blocked")?,
+            tx_hash: row.try_get("tx_hash")?,
+            log_index: row.try_get("log_index")?,
+            block_number: row.try_get("block_number")?,
+            block_hash: row.try_get("block_hash")?,
+            block_time: row.try_get("block_time")?,
+            finalized: row.try_get("finalized")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Rows changed in a window of blocks, for `GET /v1/changes`
 #[derive(Serialize)]
-struct WinningRange {
+struct ChangesResponse {
+    /// Exclusive start of the window
+    since_block: i64,
+    /// Inclusive end of the window, to pass as `since_block` next
+    next_since_block: i64,
+    /// More blocks are indexed after `next_since_block`
+    has_more: bool,
+    /// Rows above this block may still be dropped by a reorg
+    finalized_block: Option<i64>,
+    raffles: Vec<RaffleDetails>,
+    purchases: Vec<ChangedPurchase>,
+    refunds: Vec<ChangedRefund>,
+}
+
+#[derive(Serialize)]
+struct ChangedPurchase {
+    raffle_id: i64,
+    #[serde(flatten)]
+    purchase: PurchaseRange,
+}
+
+#[derive(Serialize)]
+struct ChangedRefund {
+    raffle_id: i64,
     buyer: String,
-    start_index: i64,
-    end_index: i64,
+    amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_hex: Option<String>,
+    tx_hash: String,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    /// The block is at or below the chain's finalized block; otherwise a reorg may still drop it
+    finalized: bool,
 }
 
+/// A log stored in `events_raw`, for `GET /v1/events`
 #[derive(Serialize)]
-struct TxLinks {
-    request_tx: Option<String>,
-    request_url: Option<String>,
-    randomness_tx: Option<String>,
-    randomness_url: Option<String>,
-    finalized_tx: Option<String>,
-    finalized_url: Option<String>,
-    /// Provider-level request transaction
-    provider_request_tx: Option<String>,
-    provider_request_url: Option<String>,
-    /// Provider-level fulfillment transaction  
-    provider_fulfill_tx: Option<String>,
-    provider_fulfill_url: Option<String>,
+struct RawEvent {
+    tx_hash: String,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    /// The block is at or below the chain's finalized block; otherwise a reorg may still drop it
+    finalized: bool,
+    address: String,
+    topic0: String,
+    /// All topics, starting with topic0 (null for logs indexed before topics were stored)
+    topics: Option<Vec<String>>,
+    data: String,
+    /// Event name and parameters, when the event is in [`Abis`] and the stored topics suffice
+    decoded: Option<DecodedEvent>,
 }
 
+/// A `buyTickets` transaction seen in the mempool but not yet indexed
 #[derive(Serialize)]
-struct ProofResponse {
+struct PendingPurchaseResponse {
+    tx_hash: String,
+    buyer: String,
+    count: u32,
+    /// Always false; pending entries are never confirmed purchases
+    confirmed: bool,
+    seen_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Deployment constants for frontend bootstrapping
+#[derive(Serialize)]
+struct ChainInfo {
+    /// Deployment name (omitted when the backend serves a single deployment)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
+    chain_id: u64,
+    factory_addresses: Vec<String>,
+    randomness_provider_address: Option<String>,
+    explorer_base_url: String,
+    /// Decimals of the raffle payment token
+    token_decimals: u32,
+    /// Latest block seen by the indexer (null before its first successful poll)
+    head_block: Option<u64>,
+    /// Last block fully processed by the indexer (null before any progress)
+    indexed_block: Option<i64>,
+    /// EIP-712 domain for signed requests
+    signing_domain: SigningDomain,
+}
+
+/// Head block and gas prices for time and cost estimates
+#[derive(Serialize, Deserialize)]
+struct NetworkResponse {
+    head_block: u64,
+    head_block_time: Option<DateTime<Utc>>,
+    /// Mean seconds between recent blocks (null on a chain that short)
+    average_block_time_secs: Option<f64>,
+    /// Blocks `average_block_time_secs` is computed over
+    sample_blocks: u64,
+    gas: GasPrices,
+}
+
+/// Gas price suggestions in wei, as decimal strings
+#[derive(Serialize, Deserialize)]
+struct GasPrices {
+    /// Legacy `eth_gasPrice`
+    gas_price: String,
+    /// Head block's base fee (null before EIP-1559)
+    base_fee_per_gas: Option<String>,
+    /// Node's `eth_maxPriorityFeePerGas` (null if unsupported)
+    max_priority_fee_per_gas: Option<String>,
+    /// Two base fees plus the priority fee, enough to stay includable for a few full
+    /// blocks (null without both)
+    max_fee_per_gas: Option<String>,
+}
+
+/// Indexer health for operators and dashboards
+#[derive(Serialize)]
+struct StatusResponse {
+    /// `running`, or `paused` while the RPC circuit is not closed or an admin paused it
+    indexer: &'static str,
+    /// When an admin paused the indexer (null unless paused that way)
+    paused_at: Option<DateTime<Utc>>,
+    head_block: Option<u64>,
+    indexed_block: Option<i64>,
+    /// Blocks between the chain head and the last indexed block (null until both are known)
+    lag_blocks: Option<u64>,
+    /// The chain's finalized block as last seen by the indexer (null if the RPC node doesn't
+    /// support the `finalized` tag)
+    finalized_block: Option<i64>,
+    /// Stored events from watched contracts whose signature is not in the loaded ABIs
+    undecoded_events: i64,
+    rpc_circuit: CircuitSnapshot,
+    /// Supervised background tasks of the deployment, by name
+    tasks: Vec<TaskSnapshot>,
+}
+
+/// Pre-composed link preview fields for a raffle
+#[derive(Serialize)]
+struct RaffleCard {
     raffle_id: i64,
-    raffle_address: String,
-    /// Raffle's request_id (from Raffle contract)
-    request_id: Option<String>,
-    /// Provider's request_id (from DrandRandomnessProvider)
-    provider_request_id: Option<String>,
-    randomness: Option<String>,
-    /// Proof data from DrandRandomnessProvider (hex-encoded)
-    proof_data: Option<String>,
+    /// `og:title` / `twitter:title`
+    title: String,
+    /// One-line summary for `og:description` / `twitter:description`
+    status_line: String,
+    /// Raffle page (`og:url`); null without `SITE_BASE_URL`
+    url: Option<String>,
+    /// `og:image`; null without `CARD_IMAGE_URL_TEMPLATE`
+    image_url: Option<String>,
+    /// `twitter:card`: `summary_large_image` with an image, otherwise `summary`
+    twitter_card: &'static str,
+    effective_status: String,
+}
+
+/// Minimal raffle payload for third-party widgets (live pot counters)
+#[derive(Serialize, Deserialize)]
+struct EmbedRaffle {
+    raffle_id: i64,
+    /// Effective status (see `GET /v1/raffles/:raffle_id`)
+    status: String,
     total_tickets: i64,
-    winning_index: Option<i64>,
+    pot: String,
+    pot_formatted: Option<String>,
+    token_symbol: String,
+    end_time: Option<DateTime<Utc>>,
     winner: Option<String>,
-    winning_range: Option<WinningRange>,
-    txs: TxLinks,
+    /// Raffle page; null without `SITE_BASE_URL`
+    url: Option<String>,
 }
 
-/// Randomness request from DrandRandomnessProvider
+/// An EIP-712 message and its signature (see [`crate::signatures`])
+#[derive(Deserialize)]
+struct SignedRequest {
+    message: serde_json::Value,
+    signature: String,
+}
+
+/// Refund reminder subscription of a wallet after a signed request
 #[derive(Serialize)]
-struct RandomnessRequestResponse {
-    id: i64,
-    request_id: String,
+struct RefundReminderResponse {
+    wallet: String,
+    subscribed: bool,
+    webhook_url: Option<String>,
+    /// Reminders queued right away for raffles already refunding
+    queued_reminders: u64,
+}
+
+/// Query parameters for listing keeper transactions
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeeperTxQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// pending, confirmed, reverted, replaced or dropped
+    status: Option<String>,
     raffle_id: Option<i64>,
-    raffle_address: String,
-    provider_address: String,
+}
+
+impl Validate for KeeperTxQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_id("raffle_id", self.raffle_id)?;
+        if let Some(status) = &self.status
+            && !KEEPER_TX_STATUSES.contains(&status.as_str())
+        {
+            return Err(ApiError::InvalidStatus {
+                status: status.clone(),
+                allowed: KEEPER_TX_STATUSES,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// One transaction broadcast by the keeper
+#[derive(Serialize)]
+struct KeeperTxResponse {
+    id: i64,
+    raffle_id: i64,
+    /// close, request_random or finalize
+    action: String,
+    sender: String,
+    nonce: String,
     tx_hash: String,
-    tx_url: Option<String>,
-    log_index: i64,
-    block_number: i64,
-    created_at: DateTime<Utc>,
+    /// Gas price in wei
+    gas_price: String,
+    gas_limit: String,
+    /// 1 for the first submission, incremented by each gas-bumped replacement
+    attempt: i32,
+    status: String,
+    /// Row ID of the transaction that replaced this one
+    replaced_by: Option<i64>,
+    block_number: Option<i64>,
+    /// Last failed replacement attempt
+    error: Option<String>,
+    submitted_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
 }
 
-/// Randomness fulfillment from DrandRandomnessProvider  
+/// Query parameters for listing status anomalies
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnomalyQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    raffle_id: Option<i64>,
+}
+
+impl Validate for AnomalyQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_id("raffle_id", self.raffle_id)
+    }
+}
+
+/// A lifecycle event indexed although the raffle's status didn't allow it
 #[derive(Serialize)]
-struct RandomnessFulfillmentResponse {
+struct AnomalyResponse {
     id: i64,
-    request_id: String,
-    randomness: String,
-    proof: Option<String>,
-    raffle_address: String,
-    provider_address: String,
+    raffle_id: i64,
+    /// Event name, e.g. `WinnerSelected`
+    event: String,
+    /// Status before the event (null: the raffle wasn't indexed)
+    from_status: Option<String>,
+    /// Status the event moved the raffle into (null for refund claims)
+    to_status: Option<String>,
     tx_hash: String,
-    tx_url: Option<String>,
     log_index: i64,
     block_number: i64,
+    block_hash: Option<String>,
     created_at: DateTime<Utc>,
 }
 
-/// Query parameters for randomness requests
+/// Query parameters for listing blocked addresses
 #[derive(Deserialize)]
-struct RandomnessRequestQuery {
+#[serde(deny_unknown_fields)]
+struct BlocklistQuery {
     limit: Option<i64>,
     offset: Option<i64>,
-    raffle_address: Option<String>,
-    raffle_id: Option<i64>,
 }
 
+impl Validate for BlocklistQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)
+    }
+}
+
+/// Longest reason recorded for a blocked address
+const MAX_BLOCK_REASON_LEN: usize = 500;
+
+/// Body of `PUT /v1/admin/blocklist/:address`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockAddressRequest {
+    /// Why the address is blocked, e.g. the sanctions list naming it
+    reason: String,
+}
+
+/// An address on the admin blocklist
 #[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+struct BlockedAddress {
+    address: String,
+    reason: String,
+    /// Subject of the admin credential that blocked it
+    blocked_by: String,
+    created_at: DateTime<Utc>,
 }
 
-struct ApiError {
-    status: StatusCode,
-    message: String,
+/// Tables rebuilt from events, copied by `PUT /v1/admin/indexer/cursor` with `snapshot`
+const CURSOR_SNAPSHOT_TABLES: [&str; 13] = [
+    "raffles",
+    "raffles_archive",
+    "purchases",
+    "purchases_archive",
+    "refunds",
+    "refunds_archive",
+    "payouts",
+    "keeper_updates",
+    "randomness_requests",
+    "randomness_fulfillments",
+    "whale_alerts",
+    "anomalies",
+    "ledger",
+];
+
+/// Webhook delivery queues reported by `GET /v1/admin/overview` and managed under
+/// `/v1/admin/webhooks`
+const WEBHOOK_QUEUES: [&str; 4] = [
+    "refund_reminders",
+    "whale_alerts",
+    "digest_deliveries",
+    "stuck_alerts",
+];
+
+/// Operations summary of a deployment
+#[derive(Serialize)]
+struct AdminOverview {
+    indexer: IndexerOverview,
+    /// Stored logs whose event isn't in the loaded ABIs (`events_raw.decoded = false`)
+    undecoded_events: i64,
+    webhooks: Vec<WebhookQueueOverview>,
+    jobs: Vec<JobOverview>,
+    /// Largest first
+    tables: Vec<TableOverview>,
+    pools: PoolsOverview,
 }
 
-impl ApiError {
-    fn not_found(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::NOT_FOUND,
-            message: message.into(),
-        }
-    }
+#[derive(Serialize)]
+struct IndexerOverview {
+    /// `running` or `paused`, as in `GET /v1/status`
+    state: &'static str,
+    head_block: Option<u64>,
+    indexed_block: Option<i64>,
+    lag_blocks: Option<u64>,
+    finalized_block: Option<i64>,
+    paused_at: Option<DateTime<Utc>>,
+    /// When the cursor last moved
+    cursor_updated_at: DateTime<Utc>,
+}
 
-    fn bad_request(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::BAD_REQUEST,
-            message: message.into(),
-        }
-    }
+#[derive(Serialize)]
+struct WebhookQueueOverview {
+    queue: &'static str,
+    pending: i64,
+    /// Pending deliveries already tried at least once
+    retrying: i64,
+    failed: i64,
+    oldest_pending_at: Option<DateTime<Utc>>,
+    last_delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A supervised task with the time of its latest recorded output
+#[derive(Serialize)]
+struct JobOverview {
+    #[serde(flatten)]
+    task: TaskSnapshot,
+    /// Newest row the job wrote (null for jobs that leave none, or before the first)
+    last_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct TableOverview {
+    table: String,
+    /// Planner estimate, refreshed by autovacuum/ANALYZE (-1 if never analyzed)
+    estimated_rows: i64,
+    /// Data, indexes and TOAST
+    total_bytes: i64,
+}
+
+#[derive(Serialize)]
+struct PoolsOverview {
+    api: PoolOverview,
+    indexer: PoolOverview,
+}
+
+#[derive(Serialize)]
+struct PoolOverview {
+    max_connections: u32,
+    /// Open connections, idle ones included
+    open: u32,
+    idle: u32,
+    in_use: u32,
+}
 
-    fn internal(message: impl Into<String>) -> Self {
+impl PoolOverview {
+    fn of(pool: &PgPool) -> Self {
+        let open = pool.size();
+        let idle = u32::try_from(pool.num_idle()).unwrap_or(u32::MAX).min(open);
         Self {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.into(),
+            max_connections: pool.options().get_max_connections(),
+            open,
+            idle,
+            in_use: open - idle,
         }
     }
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> axum::response::Response {
-        let body = Json(ErrorResponse {
-            error: self.message,
-        });
-        (self.status, body).into_response()
+/// Body of `PUT /v1/admin/indexer/cursor`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetCursorRequest {
+    /// New `last_processed_block`; indexing continues from the block after it
+    block: i64,
+    /// Copy the derived tables before moving the cursor
+    #[serde(default)]
+    snapshot: bool,
+}
+
+/// Response of `PUT /v1/admin/indexer/cursor`
+#[derive(Serialize)]
+struct SetCursorResponse {
+    /// ID in `cursor_changes`
+    change_id: i64,
+    previous_block: i64,
+    indexed_block: i64,
+    /// Schema-qualified names of the copied tables (empty without `snapshot`)
+    snapshot_tables: Vec<String>,
+}
+
+/// Longest subject of an admin token
+const MAX_TOKEN_SUBJECT_LEN: usize = 100;
+
+/// Body of `POST /v1/admin/tokens`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateAdminTokenRequest {
+    /// Who the token is for, logged with their admin actions
+    subject: String,
+    role: Role,
+    /// Defaults to (and is capped at) `ADMIN_JWT_MAX_TTL_SECS`
+    ttl_secs: Option<u64>,
+}
+
+/// An issued admin token
+#[derive(Serialize)]
+struct AdminTokenResponse {
+    token: String,
+    subject: String,
+    role: Role,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Query parameters for the admin audit log
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AdminAuditQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Only entries by this token subject (`admin-api-key` for the master key)
+    actor: Option<String>,
+    /// Only entries for this action, e.g. `PUT /admin/indexer/cursor`
+    action: Option<String>,
+}
+
+impl Validate for AdminAuditQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)
     }
 }
 
-// ============================================================================
-// HANDLERS
-// ============================================================================
+/// One recorded admin API call
+#[derive(Serialize)]
+struct AdminAuditEntry {
+    id: i64,
+    /// Token subject or `admin-api-key`; null when the credential was refused
+    actor: Option<String>,
+    role: Option<String>,
+    action: String,
+    path: String,
+    /// `query` and `body` of the request, where present
+    parameters: serde_json::Value,
+    status: i16,
+    error_code: Option<String>,
+    created_at: DateTime<Utc>,
+}
 
-/// GET /v1/raffles - List raffles with optional status filter
-async fn list_raffles(
-    State(state): State<AppState>,
-    Query(params): Query<ListRafflesQuery>,
-) -> Result<Json<Vec<RaffleSummary>>, ApiError> {
-    let limit = normalize_limit(params.limit)?;
-    let offset = normalize_offset(params.offset)?;
+/// Webhooks notifications are delivered to, and how they are signed
+#[derive(Serialize)]
+struct WebhooksResponse {
+    signing: WebhookSigning,
+    webhooks: Vec<RegisteredWebhook>,
+}
 
-    // Use parameterized query - safe from SQL injection
-    let raffle_rows = if let Some(status) = params.status {
-        sqlx::query(
-            "SELECT raffle_id, raffle_address, status, end_time,
-                ticket_price::text AS ticket_price,
-                total_tickets, pot::text AS pot, winner
-             FROM raffles
-             WHERE status = $1
-             ORDER BY raffle_id DESC
-             LIMIT $2 OFFSET $3",
-        )
-        .bind(status)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
-    } else {
-        sqlx::query(
-            "SELECT raffle_id, raffle_address, status, end_time,
-                ticket_price::text AS ticket_price,
-                total_tickets, pot::text AS pot, winner
-             FROM raffles
-             ORDER BY raffle_id DESC
-             LIMIT $1 OFFSET $2",
-        )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
-    };
+#[derive(Serialize)]
+struct WebhookSigning {
+    /// `rotated` (from `POST /v1/admin/webhooks/signing-secret`), `config`
+    /// (`WEBHOOK_SIGNING_SECRET`) or `none` (requests are unsigned)
+    source: &'static str,
+    /// ID of the rotated secret in use
+    secret_id: Option<i64>,
+    rotated_at: Option<DateTime<Utc>>,
+    /// Until when the secret before it still signs too
+    previous_valid_until: Option<DateTime<Utc>>,
+}
 
-    let mut raffles = Vec::with_capacity(raffle_rows.len());
-    for row in raffle_rows {
-        raffles.push(RaffleSummary {
-            raffle_id: row.try_get("raffle_id").map_err(row_error_to_api_error)?,
-            raffle_address: row
-                .try_get("raffle_address")
-                .map_err(row_error_to_api_error)?,
-            status: row.try_get("status").map_err(row_error_to_api_error)?,
-            end_time: row.try_get("end_time").map_err(row_error_to_api_error)?,
-            ticket_price: row
-                .try_get("ticket_price")
-                .map_err(row_error_to_api_error)?,
-            total_tickets: row
-                .try_get("total_tickets")
-                .map_err(row_error_to_api_error)?,
-            pot: row.try_get("pot").map_err(row_error_to_api_error)?,
-            winner: row.try_get("winner").map_err(row_error_to_api_error)?,
-        });
+/// A webhook notifications are delivered to
+#[derive(Serialize)]
+struct RegisteredWebhook {
+    /// `refund_available`, `whale_purchase` or `daily_digest`
+    #[serde(rename = "type")]
+    kind: &'static str,
+    queue: &'static str,
+    /// Scheme and host only; the rest may embed a token
+    url: String,
+    /// Subscribed wallet (refund reminders only)
+    wallet: Option<String>,
+    subscribed_at: Option<DateTime<Utc>>,
+    pending: i64,
+    failed: i64,
+    last_delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Query parameters for webhook delivery attempts
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WebhookAttemptsQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Only attempts of this queue: refund_reminders, whale_alerts, digest_deliveries,
+    /// stuck_alerts
+    queue: Option<String>,
+    /// Only attempts of this notification (ID in `queue`)
+    notification_id: Option<i64>,
+    /// Only failed (`true`) or delivered (`false`) attempts
+    failed: Option<bool>,
+}
+
+impl Validate for WebhookAttemptsQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_webhook_queue(self.queue.as_deref())?;
+        validate_id("notification_id", self.notification_id)
     }
+}
 
-    Ok(Json(raffles))
+/// One webhook delivery attempt
+#[derive(Serialize)]
+struct WebhookAttempt {
+    id: i64,
+    queue: String,
+    notification_id: i64,
+    /// Attempt number, from 1 (restarts when the notification is replayed)
+    attempt: i32,
+    /// HTTP status of the webhook's response; null without a response
+    response_status: Option<i16>,
+    /// Null for delivered attempts
+    error: Option<String>,
+    duration_ms: i32,
+    attempted_at: DateTime<Utc>,
 }
 
-/// GET /v1/raffles/:raffle_id - Get raffle details by ID
-async fn get_raffle_by_id(
-    State(state): State<AppState>,
-    Path(raffle_id): Path<i64>,
-) -> Result<Json<RaffleDetails>, ApiError> {
-    let row = sqlx::query(
-        "SELECT raffle_id, raffle_address, creator, end_time,
-            ticket_price::text AS ticket_price,
-            max_tickets, fee_bps, fee_recipient, status,
-            total_tickets, pot::text AS pot, request_id, request_tx,
-            randomness, randomness_tx, winning_index, winner, finalized_tx
-         FROM raffles
-         WHERE raffle_id = $1",
-    )
-    .bind(raffle_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_error_to_api_error)?;
+/// Body of `POST /v1/admin/webhooks/replay`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplayWebhooksRequest {
+    queue: String,
+    /// Only these notifications; every failed one in `queue` when omitted
+    ids: Option<Vec<i64>>,
+}
 
-    let Some(row) = row else {
-        return Err(ApiError::not_found("raffle not found"));
-    };
+/// Response of `POST /v1/admin/webhooks/replay`
+#[derive(Serialize)]
+struct ReplayWebhooksResponse {
+    queue: String,
+    /// Failed notifications queued again; other IDs are left alone
+    replayed: Vec<i64>,
+}
 
-    Ok(Json(RaffleDetails {
-        raffle_id: row.try_get("raffle_id").map_err(row_error_to_api_error)?,
-        raffle_address: row
-            .try_get("raffle_address")
-            .map_err(row_error_to_api_error)?,
-        creator: row.try_get("creator").map_err(row_error_to_api_error)?,
-        end_time: row.try_get("end_time").map_err(row_error_to_api_error)?,
-        ticket_price: row
-            .try_get("ticket_price")
-            .map_err(row_error_to_api_error)?,
-        max_tickets: row.try_get("max_tickets").map_err(row_error_to_api_error)?,
-        fee_bps: row.try_get("fee_bps").map_err(row_error_to_api_error)?,
-        fee_recipient: row
-            .try_get("fee_recipient")
-            .map_err(row_error_to_api_error)?,
-        status: row.try_get("status").map_err(row_error_to_api_error)?,
-        total_tickets: row
-            .try_get("total_tickets")
-            .map_err(row_error_to_api_error)?,
-        pot: row.try_get("pot").map_err(row_error_to_api_error)?,
-        request_id: row.try_get("request_id").map_err(row_error_to_api_error)?,
-        request_tx: row.try_get("request_tx").map_err(row_error_to_api_error)?,
-        randomness: row.try_get("randomness").map_err(row_error_to_api_error)?,
-        randomness_tx: row
-            .try_get("randomness_tx")
-            .map_err(row_error_to_api_error)?,
-        winning_index: row
-            .try_get("winning_index")
-            .map_err(row_error_to_api_error)?,
-        winner: row.try_get("winner").map_err(row_error_to_api_error)?,
-        finalized_tx: row
-            .try_get("finalized_tx")
-            .map_err(row_error_to_api_error)?,
-    }))
+/// Response of `POST /v1/admin/webhooks/signing-secret`
+#[derive(Serialize)]
+struct SigningSecretResponse {
+    id: i64,
+    /// Only shown here
+    secret: String,
+    created_at: DateTime<Utc>,
+    /// Until when the secret it replaces still signs too (null if there was none)
+    previous_valid_until: Option<DateTime<Utc>>,
+}
+
+/// Body of `POST /v1/admin/api-keys`
+#[derive(Deserialize)]
+struct CreateApiKeyRequest {
+    /// Who the key is for
+    name: String,
+    /// Defaults to `API_KEY_DEFAULT_DAILY_QUOTA`
+    daily_quota: Option<i64>,
+    /// Defaults to `API_KEY_DEFAULT_RATE_LIMIT`
+    rate_limit_per_minute: Option<i32>,
+}
+
+/// An issued API key (the key itself is only returned when minted)
+#[derive(Serialize)]
+struct ApiKeyResponse {
+    id: i64,
+    name: String,
+    /// Only present in the response to `POST /v1/admin/api-keys`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    key_prefix: String,
+    daily_quota: i64,
+    rate_limit_per_minute: i32,
+    /// Requests counted today (UTC)
+    requests_today: i64,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A compiled daily digest
+#[derive(Serialize)]
+struct DigestResponse {
+    #[serde(flatten)]
+    digest: Digest,
+    generated_at: DateTime<Utc>,
+}
+
+/// Query parameters for API key usage
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiKeyUsageQuery {
+    /// Days of history, including today (default 30, max 365)
+    days: Option<i64>,
+}
+
+impl Validate for ApiKeyUsageQuery {}
+
+/// Usage of one API key per UTC day
+#[derive(Serialize)]
+struct ApiKeyUsageResponse {
+    key_id: i64,
+    key_prefix: String,
+    daily_quota: i64,
+    rate_limit_per_minute: i32,
+    /// Newest first; days without requests are omitted
+    days: Vec<ApiKeyUsageDay>,
+}
+
+#[derive(Serialize)]
+struct ApiKeyUsageDay {
+    day: chrono::NaiveDate,
+    /// Requests counted against the quota, including rejected ones
+    requests: i64,
+    /// Requests rejected for exceeding the daily quota
+    rejected: i64,
+}
+
+/// One Parquet snapshot written by the export job
+#[derive(Serialize)]
+struct ExportSnapshotResponse {
+    snapshot: String,
+    bucket: String,
+    /// Indexer checkpoint the snapshot is consistent with
+    block_number: i64,
+    files: Vec<ExportFile>,
+    created_at: DateTime<Utc>,
+}
+
+/// Fees and prizes paid out to one fee recipient (within one period when bucketed)
+#[derive(Serialize)]
+struct FeeSummary {
+    fee_recipient: String,
+    /// Start of the period (null when no `interval` was requested)
+    period_start: Option<DateTime<Utc>>,
+    raffle_count: i64,
+    total_fees: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_fees_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_fees_hex: Option<String>,
+    total_prizes: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_prizes_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_prizes_hex: Option<String>,
+}
+
+/// A referral code after registration
+#[derive(Serialize)]
+struct ReferralCodeResponse {
+    code: String,
+    owner: String,
+    created_at: DateTime<Utc>,
+}
+
+/// Purchases attributed to a referral code
+#[derive(Serialize)]
+struct ReferralStats {
+    purchases: i64,
+    buyers: i64,
+    /// Buyers whose first purchase ever was attributed to the code
+    new_buyers: i64,
+    tickets: i64,
+    volume: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume_hex: Option<String>,
+}
+
+impl ReferralStats {
+    /// Reads a row selected with [`REFERRAL_STATS_SQL`]
+    fn from_row(row: &PgRow, format: AmountFormat, decimals: u32) -> Result<Self, sqlx::Error> {
+        let volume: String = row.try_get("volume")?;
+        Ok(Self {
+            purchases: row.try_get("purchases")?,
+            buyers: row.try_get("buyers")?,
+            new_buyers: row.try_get("new_buyers")?,
+            tickets: row.try_get("tickets")?,
+            volume_formatted: format.render(&volume, decimals),
+            volume_hex: format.render_hex(&volume),
+            volume,
+        })
+    }
+}
+
+/// Conversion stats of a referral code
+#[derive(Serialize)]
+struct ReferralCodeSummary {
+    code: String,
+    owner: String,
+    created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    stats: ReferralStats,
+    /// Stats per raffle with attributed purchases, on `GET /v1/referral-codes/:code` only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raffles: Option<Vec<ReferralRaffleStats>>,
+}
+
+#[derive(Serialize)]
+struct ReferralRaffleStats {
+    raffle_id: i64,
+    #[serde(flatten)]
+    stats: ReferralStats,
+}
+
+/// Aggregated holdings of a single buyer within a raffle
+#[derive(Serialize)]
+struct Participant {
+    buyer: String,
+    /// The buyer is on the admin blocklist
+    buyer_blocked: bool,
+    ticket_count: i64,
+    total_spent: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_spent_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_spent_hex: Option<String>,
+    purchase_count: i64,
+    /// Owned ticket ranges with adjacent purchases merged
+    ranges: Vec<IndexRange>,
+    /// Sybil cluster of the buyer, with `include_clusters` (see [`crate::sybil`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cluster: Option<ClusterFlag>,
+}
+
+/// Purchases of a raffle grouped by ticket count
+#[derive(Serialize)]
+struct PurchaseHistogram {
+    raffle_id: i64,
+    total_purchases: i64,
+    total_tickets: i64,
+    /// Every bucket, smallest purchases first, including empty ones
+    buckets: Vec<PurchaseSizeBucket>,
+}
+
+#[derive(Serialize)]
+struct PurchaseSizeBucket {
+    /// Tickets per purchase, inclusive; `max_tickets` is null for the open-ended last bucket
+    min_tickets: i64,
+    max_tickets: Option<i64>,
+    purchases: i64,
+    tickets: i64,
+    amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_formatted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount_hex: Option<String>,
+}
+
+/// Audience shared by two raffles
+#[derive(Serialize)]
+struct BuyerOverlap {
+    raffle_a: OverlapSide,
+    raffle_b: OverlapSide,
+    /// Buyers of both raffles
+    shared_buyers: i64,
+    /// Buyers of either raffle
+    total_buyers: i64,
+    /// `shared_buyers / total_buyers`, rounded to four places (null without buyers)
+    jaccard_index: Option<f64>,
+    /// Page of the shared buyers, most tickets across both raffles first
+    participants: Vec<SharedBuyer>,
+}
+
+/// One raffle's side of a [`BuyerOverlap`]
+#[derive(Serialize)]
+struct OverlapSide {
+    raffle_id: i64,
+    buyers: i64,
+    tickets: i64,
+    /// Tickets of this raffle bought by shared buyers
+    shared_tickets: i64,
+    /// `shared_tickets` as a percentage of `tickets`, rounded to two places
+    shared_ticket_percent: Option<f64>,
+}
+
+impl OverlapSide {
+    fn new(raffle_id: i64, buyers: i64, tickets: i64, shared_tickets: i64) -> Self {
+        Self {
+            raffle_id,
+            buyers,
+            tickets,
+            shared_tickets,
+            shared_ticket_percent: (tickets > 0)
+                .then(|| (shared_tickets as f64 * 10_000.0 / tickets as f64).round() / 100.0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SharedBuyer {
+    buyer: String,
+    tickets_a: i64,
+    tickets_b: i64,
+}
+
+/// Tickets sold over time, for a raffle's sales chart
+#[derive(Serialize)]
+struct PurchaseCurve {
+    raffle_id: i64,
+    interval_secs: i64,
+    /// Tickets sold, including `untimed_tickets`
+    total_tickets: i64,
+    /// Tickets of purchases indexed without a block time, left out of `points`
+    untimed_tickets: i64,
+    /// One point per bucket from the first purchase to the last, including empty ones
+    points: Vec<PurchaseCurvePoint>,
+}
+
+#[derive(Serialize)]
+struct PurchaseCurvePoint {
+    /// Start of the bucket, aligned to the interval in UTC
+    bucket_start: DateTime<Utc>,
+    purchases: i64,
+    tickets: i64,
+    /// Tickets sold up to the end of this bucket
+    cumulative_tickets: i64,
+}
+
+#[derive(Serialize)]
+struct IndexRange {
+    start_index: i64,
+    end_index: i64,
+}
+
+#[derive(Serialize)]
+struct WinningRange {
+    buyer: String,
+    start_index: i64,
+    end_index: i64,
+}
+
+#[derive(Serialize)]
+struct TxLinks {
+    request_tx: Option<String>,
+    request_url: Option<String>,
+    randomness_tx: Option<String>,
+    randomness_url: Option<String>,
+    finalized_tx: Option<String>,
+    finalized_url: Option<String>,
+    /// Provider-level request transaction
+    provider_request_tx: Option<String>,
+    provider_request_url: Option<String>,
+    /// Provider-level fulfillment transaction  
+    provider_fulfill_tx: Option<String>,
+    provider_fulfill_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ProofResponse {
+    raffle_id: i64,
+    raffle_address: String,
+    /// Raffle's request_id (from Raffle contract)
+    request_id: Option<String>,
+    /// Provider's request_id (from DrandRandomnessProvider)
+    provider_request_id: Option<String>,
+    randomness: Option<String>,
+    /// Proof data from DrandRandomnessProvider (hex-encoded)
+    proof_data: Option<String>,
+    /// Randomness as emitted by the provider's RandomnessDelivered event
+    provider_randomness: Option<String>,
+    /// Whether the provider's randomness equals the raffle's (null until both are indexed)
+    provider_randomness_matches: Option<bool>,
+    total_tickets: i64,
+    winning_index: Option<i64>,
+    winner: Option<String>,
+    winning_range: Option<WinningRange>,
+    txs: TxLinks,
+}
+
+/// Everything needed to check a raffle's draw offline, as one JSON document
+#[derive(Serialize)]
+struct ProofBundle {
+    version: u32,
+    generated_at: DateTime<Utc>,
+    chain_id: u64,
+    proof: ProofResponse,
+    /// Every purchase in chain order; together they cover indices `0..total_tickets`
+    ticket_ranges: Vec<BundleTicketRange>,
+    /// Raw logs of the request, randomness, finalization and provider transactions
+    logs: Vec<BundleLog>,
+    instructions: &'static [&'static str],
+}
+
+#[derive(Serialize)]
+struct BundleTicketRange {
+    buyer: String,
+    start_index: i64,
+    end_index: i64,
+    count: i64,
+    tx_hash: String,
+    block_number: i64,
+    log_index: i64,
+}
+
+/// A log as stored in `events_raw`
+#[derive(Serialize)]
+struct BundleLog {
+    tx_hash: String,
+    block_number: i64,
+    block_hash: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    log_index: i64,
+    address: String,
+    /// All topics, starting with the event signature (null for logs indexed before
+    /// topics were stored)
+    topics: Option<Vec<String>>,
+    data: String,
+}
+
+/// Checks an auditor can run against a [`ProofBundle`] and any node of the chain
+const PROOF_BUNDLE_INSTRUCTIONS: &[&str] = &[
+    "Fetch the receipt of each transaction in `logs` from a node of `chain_id` and check that it contains the listed logs.",
+    "Check that `proof.randomness` is the randomness in the raffle's fulfillment log (`proof.txs.randomness_tx`) and, with a provider, equals `proof.provider_randomness`.",
+    "Check that `ticket_ranges` are contiguous from index 0 to `proof.total_tickets - 1`, and against the raffle's `TicketsBought` logs if archiving those as well.",
+    "Compute `winning_index = randomness mod total_tickets` with `randomness` as a 256-bit unsigned integer; it must equal `proof.winning_index`.",
+    "Find the range with `start_index <= winning_index <= end_index`; its `buyer` must equal `proof.winner`.",
+];
+
+/// Final result of a raffle as covered by an attestation signature
+///
+/// Serialized with a fixed field order; the exact JSON string is what gets signed.
+#[derive(Serialize)]
+struct AttestationPayload {
+    version: u32,
+    chain_id: u64,
+    raffle_id: i64,
+    raffle_address: String,
+    randomness: Option<String>,
+    total_tickets: i64,
+    winning_index: Option<i64>,
+    winner: Option<String>,
+    randomness_tx: Option<String>,
+    randomness_block: Option<i64>,
+    finalized_tx: Option<String>,
+    finalized_block: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct AttestationResponse {
+    payload: AttestationPayload,
+    /// The exact message that was signed (JSON-encoded payload)
+    message: String,
+    /// EIP-191 personal_sign signature over `message` (hex-encoded)
+    signature: String,
+    /// Address of the signing key
+    signer: String,
+    scheme: &'static str,
+}
+
+/// Request body for `POST /v1/verify`
+///
+/// Either reference an indexed raffle via `raffle_id`, supply the inputs directly,
+/// or both (explicit inputs override the indexed values).
+#[derive(Deserialize)]
+struct VerifyRequest {
+    raffle_id: Option<i64>,
+    /// Randomness as a decimal string
+    randomness: Option<String>,
+    total_tickets: Option<i64>,
+    purchases: Option<Vec<VerifyPurchase>>,
+}
+
+#[derive(Deserialize)]
+struct VerifyPurchase {
+    buyer: String,
+    start_index: i64,
+    end_index: i64,
+}
+
+#[derive(Serialize)]
+struct VerifyResponse {
+    randomness: String,
+    total_tickets: i64,
+    /// Recomputed as `randomness % total_tickets`
+    winning_index: i64,
+    /// Owner of the recomputed winning index, if ticket ranges were available
+    winner: Option<String>,
+    /// Indexed on-chain result (only when `raffle_id` was given)
+    onchain: Option<OnchainResult>,
+    /// Whether the recomputation matches the on-chain result (null if nothing to compare)
+    matches: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct OnchainResult {
+    winning_index: Option<i64>,
+    winner: Option<String>,
+}
+
+impl OnchainResult {
+    /// Whether a recomputed result agrees with this one (`None` before a result is indexed)
+    ///
+    /// An indexed winner is a mismatch when no winner could be recomputed, i.e. no
+    /// supplied or stored range contains the winning index.
+    fn matches(&self, winning_index: i64, winner: Option<&str>) -> Option<bool> {
+        let onchain_index = self.winning_index?;
+        let winner_matches = match (winner, &self.winner) {
+            (Some(computed), Some(stored)) => computed.eq_ignore_ascii_case(stored),
+            (None, Some(_)) => false,
+            (_, None) => true,
+        };
+        Some(onchain_index == winning_index && winner_matches)
+    }
+}
+
+#[derive(Deserialize)]
+struct ResolveTicketsRequest {
+    indices: Vec<i64>,
+}
+
+#[derive(Serialize)]
+struct ResolveTicketsResponse {
+    raffle_id: i64,
+    total_tickets: i64,
+    /// One entry per requested index, in request order
+    tickets: Vec<ResolvedTicket>,
+}
+
+/// Owner and purchase range of one ticket index; all but `index` are null when no
+/// indexed purchase covers it
+#[derive(Serialize)]
+struct ResolvedTicket {
+    index: i64,
+    owner: Option<String>,
+    start_index: Option<i64>,
+    end_index: Option<i64>,
+    tx_hash: Option<String>,
+}
+
+/// Fairness audit over all finalized raffles
+#[derive(Serialize)]
+struct FairnessReport {
+    generated_at: DateTime<Utc>,
+    finalized_raffles: i64,
+    /// Raffles whose indexed winning index equals `randomness % total_tickets`
+    verified: i64,
+    mismatches: Vec<FairnessMismatch>,
+    /// Raffles lacking the randomness or winning index needed for the check
+    unverifiable: Vec<i64>,
+    distribution: WinningIndexDistribution,
+}
+
+/// A finalized raffle whose indexed winning index differs from the recomputed one
+#[derive(Serialize)]
+struct FairnessMismatch {
+    raffle_id: i64,
+    randomness: String,
+    total_tickets: i64,
+    winning_index: i64,
+    recomputed_index: i64,
+}
+
+/// Winning indices by relative position in the ticket range, against the counts
+/// expected if every ticket were equally likely to win
+#[derive(Serialize)]
+struct WinningIndexDistribution {
+    buckets: Vec<FairnessBucket>,
+    chi_square: Option<f64>,
+    degrees_of_freedom: usize,
+    /// Approximate; null without data or while a bucket expects fewer than 5 wins
+    p_value: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct FairnessBucket {
+    /// Start (inclusive) and end (exclusive) as fractions of the ticket range
+    from: f64,
+    to: f64,
+    observed: i64,
+    expected: f64,
+}
+
+/// Randomness request from DrandRandomnessProvider
+#[derive(Serialize)]
+struct RandomnessRequestResponse {
+    id: i64,
+    request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id_hex: Option<String>,
+    raffle_id: Option<i64>,
+    raffle_address: String,
+    provider_address: String,
+    tx_hash: String,
+    tx_url: Option<String>,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    /// The block is at or below the chain's finalized block
+    finalized: bool,
+    created_at: DateTime<Utc>,
+}
+
+/// Randomness fulfillment from DrandRandomnessProvider  
+#[derive(Serialize)]
+struct RandomnessFulfillmentResponse {
+    id: i64,
+    request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id_hex: Option<String>,
+    /// Raffle correlated through the provider's request event (null if unknown)
+    raffle_id: Option<i64>,
+    randomness: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    randomness_hex: Option<String>,
+    proof: Option<String>,
+    raffle_address: String,
+    provider_address: String,
+    tx_hash: String,
+    tx_url: Option<String>,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    /// The block is at or below the chain's finalized block
+    finalized: bool,
+    created_at: DateTime<Utc>,
+}
+
+/// Query parameters for randomness requests
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RandomnessRequestQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    raffle_address: Option<String>,
+    raffle_id: Option<i64>,
+    #[serde(default)]
+    format: AmountFormat,
+}
+
+impl Validate for RandomnessRequestQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, self.offset)?;
+        validate_address("raffle_address", self.raffle_address.as_deref())?;
+        validate_id("raffle_id", self.raffle_id)
+    }
+}
+
+/// Path of routes under `/v1/raffles/{raffle_id}`
+#[derive(Deserialize)]
+struct RafflePath {
+    raffle_id: i64,
+}
+
+impl Validate for RafflePath {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_id("raffle_id", Some(self.raffle_id))
+    }
+}
+
+/// Path of `/v1/referral-codes/{code}`
+#[derive(Deserialize)]
+struct ReferralCodePath {
+    code: String,
+}
+
+impl Validate for ReferralCodePath {
+    fn validate(&self) -> Result<(), ApiError> {
+        if referrals::normalize_code(&self.code).is_none() {
+            return Err(ApiError::invalid_parameter(
+                "code",
+                "code is not a valid referral code",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Path of routes under `/v1/admin/api-keys/{key_id}`
+#[derive(Deserialize)]
+struct ApiKeyPath {
+    key_id: i64,
+}
+
+impl Validate for ApiKeyPath {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_id("key_id", Some(self.key_id))
+    }
+}
+
+/// Path of `/v1/admin/blocklist/{address}`
+#[derive(Deserialize)]
+struct BlockedAddressPath {
+    address: String,
+}
+
+impl Validate for BlockedAddressPath {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_address("address", Some(&self.address))
+    }
+}
+
+/// Path of `/v1/randomness/requests/{request_id}`
+#[derive(Deserialize)]
+struct RandomnessRequestPath {
+    /// Decimal uint256 assigned by the randomness provider
+    request_id: String,
+}
+
+impl Validate for RandomnessRequestPath {
+    fn validate(&self) -> Result<(), ApiError> {
+        // A uint256 has at most 78 decimal digits
+        let id = &self.request_id;
+        if id.is_empty() || id.len() > 78 || !id.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ApiError::invalid_parameter(
+                "request_id",
+                "request_id must be a decimal integer",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Extractor admitting admins allowed on a route requiring role `R`
+///
+/// Accepts `Authorization: Bearer <ADMIN_API_KEY>`, which holds every role, or an
+/// admin token (see [`crate::auth`]) whose role is at least `R::ROLE`. Responds 404
+/// when neither credential is configured, so admin routes aren't advertised.
+struct AdminAuth<R> {
+    /// Token subject, or [`auth::MASTER_KEY_SUBJECT`]
+    subject: String,
+    _role: PhantomData<R>,
+}
+
+impl<R> AdminAuth<R> {
+    fn new(subject: String) -> Self {
+        Self {
+            subject,
+            _role: PhantomData,
+        }
+    }
+}
+
+impl<R: RequiredRole> FromRequestParts<AppState> for AdminAuth<R> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let admin = auth::authenticate(&state.config, &parts.headers)?;
+        if admin.role < R::ROLE {
+            tracing::info!(
+                subject = admin.subject,
+                role = admin.role.as_str(),
+                required = R::ROLE.as_str(),
+                "admin token lacks the route's role"
+            );
+            return Err(ApiError::InsufficientRole { required: R::ROLE });
+        }
+        Ok(Self::new(admin.subject))
+    }
+}
+
+// ============================================================================
+// HANDLERS
+// ============================================================================
+
+/// GET /v1/raffles - List raffles with optional status filter
+///
+/// Raffles created by blocked addresses are left out unless `include_blocked` is set
+/// by an admin; those responses aren't cached.
+async fn list_raffles(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<ListRafflesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    let decimals = state.config.token_decimals;
+    let include_blocked = params.include_blocked;
+    if include_blocked {
+        // Every admin role may read the full list
+        let admin = auth::authenticate(&state.config, &headers)?;
+        tracing::debug!(by = admin.subject, "raffle list includes blocked creators");
+    }
+    let load = || {
+        load_raffle_list(
+            state.db.clone(),
+            limit,
+            offset,
+            params.status.clone(),
+            include_blocked,
+            params.format,
+            decimals,
+        )
+        .in_current_span()
+    };
+
+    let cache = state
+        .raffle_list_cache
+        .as_ref()
+        .filter(|_| !include_blocked);
+    let Some(cache) = cache else {
+        let mut response = json_response(load().await?);
+        if include_blocked {
+            response.headers_mut().insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("private, no-store"),
+            );
+        }
+        return Ok(response);
+    };
+
+    // Serve from the stale-while-revalidate cache; the key covers every parameter
+    let key = format!(
+        "limit={limit}&offset={offset}&status={}&format={:?}",
+        params.status.as_deref().unwrap_or(""),
+        params.format
+    );
+    let cached = cache.get(key, load).await?;
+    let mut response = json_response(cached.body);
+    let headers = response.headers_mut();
+    headers.insert(header::AGE, HeaderValue::from(cached.age.as_secs()));
+    headers.insert("x-cache", HeaderValue::from_static(cached.status.as_str()));
+    Ok(response)
+}
+
+/// Loads and serializes one page of the raffle list
+async fn load_raffle_list(
+    db: PgPool,
+    limit: i64,
+    offset: i64,
+    status: Option<String>,
+    include_blocked: bool,
+    format: AmountFormat,
+    decimals: u32,
+) -> Result<Bytes, ApiError> {
+    let filter = RaffleListFilter {
+        limit,
+        offset,
+        before_raffle_id: None,
+        status,
+        include_blocked,
+    };
+    let now = Utc::now();
+    let raffles: Vec<RaffleSummary> = fetch_raffle_list(&db, &filter)
+        .await?
+        .into_iter()
+        .map(|row| RaffleSummary {
+            raffle_id: row.raffle_id,
+            raffle_address: row.raffle_address,
+            status: row.status,
+            effective_status: row.effective_status,
+            end_time: row.end_time,
+            ticket_price_formatted: format.render(&row.ticket_price, decimals),
+            ticket_price_hex: format.render_hex(&row.ticket_price),
+            ticket_price: row.ticket_price,
+            total_tickets: row.total_tickets,
+            unique_buyers: row.unique_buyers,
+            pot_formatted: format.render(&row.pot, decimals),
+            pot_hex: format.render_hex(&row.pot),
+            pot: row.pot,
+            winner: row.winner,
+            progress: RaffleProgress::compute(
+                row.end_time,
+                row.max_tickets,
+                row.total_tickets,
+                now,
+            ),
+        })
+        .collect();
+
+    serde_json::to_vec(&raffles)
+        .map(Bytes::from)
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to serialize raffle list");
+            ApiError::Internal("serialization error")
+        })
+}
+
+/// Reads one page of raffles, newest first
+///
+/// Shared by every API version; each renders the rows its own way.
+async fn fetch_raffle_list(
+    db: &PgPool,
+    filter: &RaffleListFilter,
+) -> Result<Vec<RaffleListRow>, ApiError> {
+    // Use parameterized query - safe from SQL injection
+    let rows = sqlx::query(&format!(
+        "SELECT raffle_id, raffle_address, status,
+            {EFFECTIVE_STATUS_SQL} AS effective_status, end_time,
+            ticket_price::text AS ticket_price,
+            max_tickets, total_tickets, unique_buyers, pot::text AS pot, winner
+         FROM raffles
+         WHERE ($1::text IS NULL OR {EFFECTIVE_STATUS_SQL} = $1)
+             AND ($2 OR {CREATOR_NOT_BLOCKED_SQL})
+             AND ($3::bigint IS NULL OR raffle_id < $3)
+         ORDER BY raffle_id DESC
+         LIMIT $4 OFFSET $5"
+    ))
+    .bind(filter.status.as_deref())
+    .bind(filter.include_blocked)
+    .bind(filter.before_raffle_id)
+    .bind(filter.limit)
+    .bind(filter.offset)
+    .fetch_all(db)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(RaffleListRow {
+                raffle_id: row.try_get("raffle_id")?,
+                raffle_address: row.try_get("raffle_address")?,
+                status: row.try_get("status")?,
+                effective_status: row.try_get("effective_status")?,
+                end_time: row.try_get("end_time")?,
+                ticket_price: row.try_get("ticket_price")?,
+                max_tickets: row.try_get("max_tickets")?,
+                total_tickets: row.try_get("total_tickets")?,
+                unique_buyers: row.try_get("unique_buyers")?,
+                pot: row.try_get("pot")?,
+                winner: row.try_get("winner")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(row_error_to_api_error)
+}
+
+/// GET /v1/raffles/:raffle_id - Get raffle details by ID
+///
+/// With `?fallback=chain`, a raffle missing from the database is read from the
+/// contracts instead (e.g. right after creation, before the next indexer poll).
+/// Terminal raffles are answered from their snapshot (see [`snapshots`]).
+async fn get_raffle_by_id(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<RaffleDetailsQuery>,
+) -> Result<Response, ApiError> {
+    let decimals = state.config.token_decimals;
+    let use_chain_fallback = chain_fallback(params.fallback.as_deref())?;
+    let terminal = if params.format == AmountFormat::Raw {
+        match load_snapshot(&state, raffle_id, snapshots::Kind::Details).await? {
+            Ok(snapshot) => return Ok(snapshot_response(snapshot)),
+            Err(terminal) => terminal,
+        }
+    } else {
+        false
+    };
+
+    let details = match fetch_raffle_details(&state.db, raffle_id).await? {
+        Some(details) => details,
+        None if use_chain_fallback => fetch_raffle_from_chain(&state, raffle_id).await?,
+        None => return Err(ApiError::RaffleNotFound),
+    };
+    let last_modified = details.last_modified;
+    let details = details.with_format(params.format, decimals);
+    if terminal {
+        let snapshot = store_snapshot(
+            &state,
+            raffle_id,
+            snapshots::Kind::Details,
+            &details,
+            last_modified,
+        )
+        .await?;
+        return Ok(snapshot_response(snapshot));
+    }
+    Ok((
+        last_modified.map(|at| Extension(LastModified(at))),
+        Json(details),
+    )
+        .into_response())
+}
+
+/// Reads a raffle's current snapshot of `kind`, or else whether the raffle is terminal
+async fn load_snapshot(
+    state: &AppState,
+    raffle_id: i64,
+    kind: snapshots::Kind,
+) -> Result<Result<Snapshot, bool>, ApiError> {
+    let lookup = snapshots::lookup(&state.db, &state.config, raffle_id, kind).await?;
+    Ok(lookup.snapshot.ok_or(lookup.terminal))
+}
+
+/// Serializes a terminal raffle's response and stores it as the raffle's snapshot
+///
+/// Failing to store it (e.g. on a read-only replica) only costs the next request a
+/// rebuild, so it is logged rather than returned.
+async fn store_snapshot<T: Serialize>(
+    state: &AppState,
+    raffle_id: i64,
+    kind: snapshots::Kind,
+    value: &T,
+    last_modified: Option<DateTime<Utc>>,
+) -> Result<Snapshot, ApiError> {
+    let body = serde_json::to_string(value).map_err(|err| {
+        tracing::error!(error = %err, "failed to serialize raffle snapshot");
+        ApiError::Internal("serialization error")
+    })?;
+    let snapshot = Snapshot {
+        body,
+        last_modified,
+    };
+    if let Err(err) = snapshots::store(&state.db, &state.config, raffle_id, kind, &snapshot).await {
+        tracing::warn!(raffle_id, error = %err, "failed to store raffle snapshot");
+    }
+    Ok(snapshot)
+}
+
+/// Answers with a stored response, marked as unchanging for [`cache_control`]
+fn snapshot_response(snapshot: Snapshot) -> Response {
+    let mut response = json_response(Bytes::from(snapshot.body));
+    response.extensions_mut().insert(Finalized);
+    if let Some(at) = snapshot.last_modified {
+        response.extensions_mut().insert(LastModified(at));
+    }
+    response
+}
+
+/// Whether `?fallback=` asks for on-chain reads of raffles missing from the database
+fn chain_fallback(fallback: Option<&str>) -> Result<bool, ApiError> {
+    match fallback {
+        None => Ok(false),
+        Some("chain") => Ok(true),
+        Some(_) => Err(ApiError::invalid_parameter(
+            "fallback",
+            "fallback must be 'chain'",
+        )),
+    }
+}
+
+/// Columns read into a [`RaffleDetails`] from `raffles_all r`
+fn raffle_details_columns() -> String {
+    format!(
+        "raffle_id, raffle_address, creator, end_time,
+        ticket_price::text AS ticket_price,
+        max_tickets, fee_bps, fee_recipient, status,
+        {EFFECTIVE_STATUS_SQL} AS effective_status,
+        total_tickets, unique_buyers, pot::text AS pot, request_id, request_tx,
+        randomness, randomness_tx, winning_index, winner, finalized_tx, keeper,
+        prize_amount::text AS prize_amount, fee_amount::text AS fee_amount, payout_tx,
+        (SELECT p.confirmed FROM payouts p WHERE p.raffle_id = r.raffle_id) AS payout_confirmed,
+        GREATEST(
+            r.updated_at,
+            (SELECT p.confirmed_at FROM payouts p WHERE p.raffle_id = r.raffle_id)
+        ) AS last_modified"
+    )
+}
+
+/// Reads a raffle's indexed details, without the `?format=` fields
+///
+/// Shared by every API version; archived raffles are included.
+async fn fetch_raffle_details(
+    db: &PgPool,
+    raffle_id: i64,
+) -> Result<Option<RaffleDetails>, ApiError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM raffles_all r WHERE raffle_id = $1",
+        raffle_details_columns()
+    ))
+    .bind(raffle_id)
+    .fetch_optional(db)
+    .await?;
+
+    row.map(|row| RaffleDetails::from_row(&row, Utc::now()))
+        .transpose()
+        .map_err(row_error_to_api_error)
+}
+
+/// Builds a best-effort raffle details response from direct contract reads
+///
+/// Transaction hashes and `unique_buyers` are only known to the indexer and are
+/// left empty.
+async fn fetch_raffle_from_chain(
+    state: &AppState,
+    raffle_id: i64,
+) -> Result<RaffleDetails, ApiError> {
+    let Ok(chain_id) = u64::try_from(raffle_id) else {
+        return Err(ApiError::RaffleNotFound);
+    };
+    let raffle = state
+        .chain
+        .fetch_raffle(chain_id)
+        .await
+        .map_err(|err| {
+            match &err {
+                ChainReadError::RateLimited => {
+                    tracing::debug!(raffle_id, "on-chain raffle lookup rate limited");
+                }
+                ChainReadError::Rpc(err) => {
+                    tracing::warn!(raffle_id, error = %err, "on-chain raffle lookup failed");
+                }
+            }
+            ApiError::Chain(err)
+        })?
+        .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    let status = raffle.status_name();
+    let end_time = DateTime::<Utc>::from_timestamp(raffle.end_time as i64, 0);
+    let max_tickets = i64::from(raffle.max_tickets);
+    let total_tickets = i64::from(raffle.total_tickets);
+    let now = Utc::now();
+    let effective_status = if status == "ACTIVE"
+        && (end_time.is_some_and(|end| end <= now) || total_tickets >= max_tickets)
+    {
+        "ENDED"
+    } else {
+        status
+    };
+    // Randomness-derived fields are zero until the corresponding step happened
+    let has_randomness = !raffle.randomness.is_zero();
+
+    Ok(RaffleDetails {
+        raffle_id,
+        raffle_address: format!("{:#x}", raffle.raffle_address),
+        creator: format!("{:#x}", raffle.creator),
+        end_time,
+        ticket_price: raffle.ticket_price.to_string(),
+        ticket_price_formatted: None,
+        ticket_price_hex: None,
+        max_tickets,
+        fee_bps: i64::from(raffle.fee_bps),
+        fee_recipient: format!("{:#x}", raffle.fee_recipient),
+        status: status.to_string(),
+        effective_status: effective_status.to_string(),
+        total_tickets,
+        unique_buyers: 0,
+        pot: raffle.pot.to_string(),
+        pot_formatted: None,
+        pot_hex: None,
+        request_id: (!raffle.request_id.is_zero()).then(|| raffle.request_id.to_string()),
+        request_id_hex: None,
+        request_tx: None,
+        randomness: has_randomness.then(|| raffle.randomness.to_string()),
+        randomness_hex: None,
+        randomness_tx: None,
+        winning_index: has_randomness.then(|| raffle.winning_index.low_u64() as i64),
+        winner: (!raffle.winner.is_zero()).then(|| format!("{:#x}", raffle.winner)),
+        finalized_tx: None,
+        keeper: Some(format!("{:#x}", raffle.keeper)),
+        prize_amount: None,
+        prize_amount_formatted: None,
+        prize_amount_hex: None,
+        fee_amount: None,
+        fee_amount_formatted: None,
+        fee_amount_hex: None,
+        payout_tx: None,
+        payout_confirmed: None,
+        progress: RaffleProgress::compute(end_time, max_tickets, total_tickets, now),
+        source: "chain",
+        last_modified: None,
+    })
+}
+
+/// GET /v1/raffles/:raffle_id/reconciliation - Check a raffle's stored pot
+///
+/// Compares the stored `pot` and `total_tickets` with the sums of the indexed
+/// purchases and refunds and, with `?onchain=true`, with the raffle contract's pot,
+/// ticket count and token balance.
+async fn get_raffle_reconciliation(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<ReconciliationQuery>,
+) -> Result<Json<ReconciliationResponse>, ApiError> {
+    let (format, decimals) = (params.format, state.config.token_decimals);
+    let row = sqlx::query!(
+        r#"SELECT r.raffle_id, r.status AS "status!", r.total_tickets AS "total_tickets!", r.pot::text AS "pot!",
+            p.purchases AS "purchases!", p.purchased_tickets AS "purchased_tickets!", p.purchased_amount::text AS "purchased_amount!",
+            f.refunds AS "refunds!", f.refunded_amount::text AS "refunded_amount!",
+            e.expected_pot::text AS "expected_pot!",
+            (r.pot - e.expected_pot)::text AS "pot_delta!"
+         FROM raffles_all r
+         CROSS JOIN LATERAL (
+             SELECT COUNT(*) AS purchases,
+                 COALESCE(SUM(count), 0)::bigint AS purchased_tickets,
+                 COALESCE(SUM(amount), 0) AS purchased_amount
+             FROM purchases_all WHERE raffle_id = r.raffle_id
+         ) p
+         CROSS JOIN LATERAL (
+             SELECT COUNT(*) AS refunds, COALESCE(SUM(amount), 0) AS refunded_amount
+             FROM refunds_all WHERE raffle_id = r.raffle_id
+         ) f
+         CROSS JOIN LATERAL (
+             SELECT CASE WHEN r.status = 'FINALIZED' THEN 0
+                 ELSE p.purchased_amount - f.refunded_amount END AS expected_pot
+         ) e
+         WHERE r.raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::RaffleNotFound)?;
+
+    let total_tickets: i64 = row.total_tickets;
+    let pot: String = row.pot;
+    let purchased_tickets: i64 = row.purchased_tickets;
+    let purchased_amount: String = row.purchased_amount;
+    let refunded_amount: String = row.refunded_amount;
+    let expected_pot: String = row.expected_pot;
+    let pot_delta: String = row.pot_delta;
+    let total_tickets_delta = total_tickets - purchased_tickets;
+
+    let onchain = if params.onchain {
+        Some(fetch_reconciliation_from_chain(&state, raffle_id, total_tickets, &pot, format).await?)
+    } else {
+        None
+    };
+    let consistent = total_tickets_delta == 0
+        && pot_delta == "0"
+        && onchain.as_ref().is_none_or(|onchain| {
+            onchain.total_tickets_delta == 0
+                && onchain.pot_delta == "0"
+                && onchain.balance_delta == "0"
+        });
+
+    Ok(Json(ReconciliationResponse {
+        raffle_id,
+        status: row.status,
+        total_tickets,
+        pot_formatted: format.render(&pot, decimals),
+        pot_hex: format.render_hex(&pot),
+        pot,
+        ledger: ReconciliationLedger {
+            purchases: row.purchases,
+            purchased_tickets,
+            purchased_amount_formatted: format.render(&purchased_amount, decimals),
+            purchased_amount_hex: format.render_hex(&purchased_amount),
+            purchased_amount,
+            refunds: row.refunds,
+            refunded_amount_formatted: format.render(&refunded_amount, decimals),
+            refunded_amount_hex: format.render_hex(&refunded_amount),
+            refunded_amount,
+            expected_pot_formatted: format.render(&expected_pot, decimals),
+            expected_pot_hex: format.render_hex(&expected_pot),
+            expected_pot,
+        },
+        total_tickets_delta,
+        pot_delta_formatted: format.render(&pot_delta, decimals),
+        pot_delta,
+        onchain,
+        consistent,
+    }))
+}
+
+/// Reads a raffle's pot and token balance from its contract and compares them with
+/// the stored values
+async fn fetch_reconciliation_from_chain(
+    state: &AppState,
+    raffle_id: i64,
+    total_tickets: i64,
+    pot: &str,
+    format: AmountFormat,
+) -> Result<ReconciliationOnchain, ApiError> {
+    let decimals = state.config.token_decimals;
+    let Ok(chain_id) = u64::try_from(raffle_id) else {
+        return Err(ApiError::RaffleNotFound);
+    };
+    let raffle = state
+        .chain
+        .fetch_raffle_balance(chain_id)
+        .await
+        .map_err(|err| {
+            match &err {
+                ChainReadError::RateLimited => {
+                    tracing::debug!(raffle_id, "on-chain pot lookup rate limited");
+                }
+                ChainReadError::Rpc(err) => {
+                    tracing::warn!(raffle_id, error = %err, "on-chain pot lookup failed");
+                }
+            }
+            ApiError::Chain(err)
+        })?
+        // Indexed but unknown to the factory: the node is behind or on another chain
+        .ok_or(ApiError::RaffleNotFound)?;
+
+    let stored_pot = U256::from_dec_str(pot).map_err(|_| ApiError::Internal("invalid pot"))?;
+    let pot_delta = signed_difference(stored_pot, raffle.pot);
+    let balance_delta = signed_difference(stored_pot, raffle.balance);
+    let (onchain_pot, balance) = (raffle.pot.to_string(), raffle.balance.to_string());
+    Ok(ReconciliationOnchain {
+        raffle_address: format!("{:#x}", raffle.raffle_address),
+        total_tickets: i64::from(raffle.total_tickets),
+        pot_formatted: format.render(&onchain_pot, decimals),
+        pot_hex: format.render_hex(&onchain_pot),
+        pot: onchain_pot,
+        balance_formatted: format.render(&balance, decimals),
+        balance_hex: format.render_hex(&balance),
+        balance,
+        total_tickets_delta: total_tickets - i64::from(raffle.total_tickets),
+        pot_delta_formatted: format.render(&pot_delta, decimals),
+        pot_delta,
+        balance_delta_formatted: format.render(&balance_delta, decimals),
+        balance_delta,
+    })
+}
+
+/// `a - b` as a decimal string, negative when `b` is larger
+fn signed_difference(a: U256, b: U256) -> String {
+    if a >= b {
+        (a - b).to_string()
+    } else {
+        format!("-{}", b - a)
+    }
+}
+
+/// GET /v1/raffles/:raffle_id/ledger - Pot movements of a raffle
+///
+/// Balances cover every entry; `entries` is one page, oldest first.
+async fn get_raffle_ledger(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<PaginationQuery>,
+) -> Result<Json<LedgerResponse>, ApiError> {
+    let (format, decimals) = (params.format, state.config.token_decimals);
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let exists: bool = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM raffles_all WHERE raffle_id = $1) AS "exists!""#,
+        raffle_id,
+    )
+    .fetch_one(&state.db)
+    .await?;
+    if !exists {
+        return Err(ApiError::RaffleNotFound);
+    }
+
+    let balance_rows = sqlx::query!(
+        r#"SELECT account, address, SUM(amount)::text AS "balance!",
+            SUM(SUM(amount)) OVER () = 0 AS "balanced!"
+         FROM ledger
+         WHERE raffle_id = $1
+         GROUP BY account, address
+         ORDER BY account = 'pot' DESC, account, address"#,
+        raffle_id,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    // Every posting sums to zero, so an unbalanced ledger means a half-written one
+    let mut balanced = true;
+    let mut balances = Vec::with_capacity(balance_rows.len());
+    for row in balance_rows {
+        balanced = row.balanced;
+        let balance: String = row.balance;
+        balances.push(LedgerBalance {
+            account: row.account,
+            address: row.address,
+            balance_formatted: format.render(&balance, decimals),
+            balance,
+        });
+    }
+    let rows = sqlx::query!(
+        r#"SELECT id, entry, account, address, amount::text AS "amount!", tx_hash, log_index,
+            block_number, block_time
+         FROM ledger
+         WHERE raffle_id = $1
+         ORDER BY block_number, log_index, id
+         LIMIT $2 OFFSET $3"#,
+        raffle_id,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        let amount: String = row.amount;
+        entries.push(LedgerEntry {
+            id: row.id,
+            entry: row.entry,
+            account: row.account,
+            address: row.address,
+            amount_formatted: format.render(&amount, decimals),
+            amount,
+            tx_hash: row.tx_hash,
+            log_index: row.log_index,
+            block_number: row.block_number,
+            block_time: row.block_time,
+        });
+    }
+
+    Ok(Json(LedgerResponse {
+        raffle_id,
+        balanced,
+        balances,
+        entries,
+    }))
+}
+
+/// GET /v1/raffles/:raffle_id/timeline - Lifecycle timestamps and estimated draw time
+///
+/// The estimate adds the median keeper latency from the raffle's current stage to
+/// finalization, over the last [`DRAW_ESTIMATE_SAMPLE`] finalized raffles, to the time
+/// it reached that stage.
+async fn get_raffle_timeline(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Json<TimelineResponse>, ApiError> {
+    let row = sqlx::query!(
+        r#"SELECT r.status AS "status!", r.created_block_time, r.end_time,
+            MIN(c.block_time) FILTER (WHERE c.status = 'CLOSED') AS closed_at,
+            MIN(c.block_time) FILTER (WHERE c.status = 'RANDOM_REQUESTED') AS randomness_requested_at,
+            MIN(c.block_time) FILTER (WHERE c.status = 'RANDOM_FULFILLED') AS randomness_fulfilled_at,
+            MIN(c.block_time) FILTER (WHERE c.status = 'FINALIZED') AS finalized_at,
+            MIN(c.block_time) FILTER (WHERE c.status = 'REFUNDING') AS refunds_started_at,
+            MIN(c.block_time) FILTER (WHERE c.status = 'CANCELED') AS canceled_at
+         FROM raffles_all r
+         LEFT JOIN raffle_status_changes c ON c.raffle_id = r.raffle_id
+         WHERE r.raffle_id = $1
+         GROUP BY r.status, r.created_block_time, r.end_time"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::RaffleNotFound)?;
+
+    let pending_since = match row.status.as_str() {
+        "CLOSED" => row.closed_at,
+        "RANDOM_REQUESTED" => row.randomness_requested_at,
+        "RANDOM_FULFILLED" => row.randomness_fulfilled_at,
+        _ => None,
+    };
+    let draw_estimate = match pending_since {
+        Some(since) => {
+            let latency = sqlx::query!(
+                r#"SELECT
+                    percentile_cont(0.5) WITHIN GROUP (
+                        ORDER BY EXTRACT(EPOCH FROM f.block_time - s.block_time)
+                    )::bigint AS median_secs,
+                    COUNT(*) AS "sample_size!"
+                 FROM (
+                     SELECT raffle_id, block_time FROM raffle_status_changes
+                     WHERE status = 'FINALIZED' AND block_time IS NOT NULL
+                     ORDER BY block_number DESC
+                     LIMIT $2
+                 ) f
+                 JOIN raffle_status_changes s
+                   ON s.raffle_id = f.raffle_id AND s.status = $1 AND s.block_time IS NOT NULL"#,
+                row.status,
+                DRAW_ESTIMATE_SAMPLE,
+            )
+            .fetch_one(&state.db)
+            .await?;
+            latency.median_secs.map(|median_secs| DrawEstimate {
+                estimated_at: since + chrono::Duration::seconds(median_secs),
+                from_status: row.status.clone(),
+                median_secs,
+                sample_size: latency.sample_size,
+            })
+        }
+        None => None,
+    };
+
+    Ok(Json(TimelineResponse {
+        raffle_id,
+        status: row.status,
+        created_at: row.created_block_time,
+        end_time: row.end_time,
+        closed_at: row.closed_at,
+        randomness_requested_at: row.randomness_requested_at,
+        randomness_fulfilled_at: row.randomness_fulfilled_at,
+        finalized_at: row.finalized_at,
+        refunds_started_at: row.refunds_started_at,
+        canceled_at: row.canceled_at,
+        draw_estimate,
+    }))
+}
+
+/// GET /v1/raffles/:raffle_id/purchases - List ticket purchases for a raffle in chain order
+///
+/// Clients sending `Accept: application/x-ndjson` get every purchase streamed
+/// instead of one page (see [`stream_purchases`]). The default first page of a
+/// terminal raffle is answered from its snapshot (see [`snapshots`]).
+async fn list_purchases(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<PurchasesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let accepts_ndjson = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| media_type.trim().starts_with(NDJSON_CONTENT_TYPE));
+    let mut response = if accepts_ndjson {
+        stream_purchases(&state, raffle_id, params.after(), params.format).await?
+    } else if params.is_default() {
+        match load_snapshot(&state, raffle_id, snapshots::Kind::Purchases).await? {
+            Ok(snapshot) => snapshot_response(snapshot),
+            Err(terminal) => {
+                let page = load_purchase_page(&state, raffle_id, &params).await?;
+                if terminal {
+                    let snapshot =
+                        store_snapshot(&state, raffle_id, snapshots::Kind::Purchases, &page, None)
+                            .await?;
+                    snapshot_response(snapshot)
+                } else {
+                    Json(page).into_response()
+                }
+            }
+        }
+    } else {
+        Json(load_purchase_page(&state, raffle_id, &params).await?).into_response()
+    };
+    // Shared caches must key the response on the negotiated format
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    Ok(response)
+}
+
+/// GET /v1/raffles/:raffle_id/purchases.ndjson - Stream every purchase of a raffle
+async fn list_purchases_ndjson(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<PurchaseStreamQuery>,
+) -> Result<Response, ApiError> {
+    let after = params.after_block.zip(params.after_log_index);
+    stream_purchases(&state, raffle_id, after, params.format).await
+}
+
+/// Loads one page of a raffle's purchases
+async fn load_purchase_page(
+    state: &AppState,
+    raffle_id: i64,
+    params: &PurchasesQuery,
+) -> Result<Vec<PurchaseRange>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    let (after_block, after_log_index) = params.after().unzip();
+
+    let purchase_rows = sqlx::query(&format!(
+        "SELECT {PURCHASE_COLUMNS}
+         FROM purchases_all
+         WHERE raffle_id = $1 AND {}
+         ORDER BY block_number ASC, log_index ASC
+         LIMIT $2 OFFSET $3",
+        purchase_after_sql(4)
+    ))
+    .bind(raffle_id)
+    .bind(limit)
+    .bind(offset)
+    .bind(after_block)
+    .bind(after_log_index)
+    .fetch_all(&state.db)
+    .await?;
+
+    let decimals = state.config.token_decimals;
+    purchase_rows
+        .iter()
+        .map(|row| PurchaseRange::from_row(row, params.format, decimals))
+        .collect::<Result<_, _>>()
+        .map_err(row_error_to_api_error)
+}
+
+/// Streams the purchases of a raffle as newline-delimited JSON in chain order,
+/// starting after `after` (`(block_number, log_index)`) if given
+///
+/// Rows go straight from a database cursor to the response body, so there is no
+/// page limit. The query runs on its own connection with [`NDJSON_STATEMENT_TIMEOUT_MS`]
+/// instead of the API statement timeout; if it fails midway the response is cut off
+/// rather than completed.
+async fn stream_purchases(
+    state: &AppState,
+    raffle_id: i64,
+    after: Option<(i64, i64)>,
+    format: AmountFormat,
+) -> Result<Response, ApiError> {
+    // Acquired up front so an exhausted pool is reported as an error status
+    let mut db_tx = state.db.begin().await?;
+    sqlx::query(&format!(
+        "SET LOCAL statement_timeout = {NDJSON_STATEMENT_TIMEOUT_MS}"
+    ))
+    .execute(&mut *db_tx)
+    .await?;
+
+    let decimals = state.config.token_decimals;
+    let (lines, body) =
+        tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(NDJSON_BUFFER_ROWS);
+    tokio::spawn(
+        async move {
+            let sql = format!(
+                "SELECT {PURCHASE_COLUMNS}
+                 FROM purchases_all
+                 WHERE raffle_id = $1 AND {}
+                 ORDER BY block_number ASC, log_index ASC",
+                purchase_after_sql(2)
+            );
+            let (after_block, after_log_index) = after.unzip();
+            let mut rows = sqlx::query(&sql)
+                .bind(raffle_id)
+                .bind(after_block)
+                .bind(after_log_index)
+                .fetch(&mut *db_tx);
+            loop {
+                let line = match rows.try_next().await {
+                    Ok(Some(row)) => PurchaseRange::from_row(&row, format, decimals)
+                        .map(|purchase| {
+                            let mut line = serde_json::to_vec(&purchase).unwrap_or_default();
+                            line.push(b'\n');
+                            Bytes::from(line)
+                        })
+                        .map_err(std::io::Error::other),
+                    Ok(None) => return,
+                    Err(err) => {
+                        tracing::error!(error = %err, "purchase stream failed");
+                        Err(std::io::Error::other(err))
+                    }
+                };
+                let failed = line.is_err();
+                // A closed channel means the client went away
+                if lines.send(line).await.is_err() || failed {
+                    return;
+                }
+            }
+        }
+        .in_current_span(),
+    );
+
+    let body = futures::stream::unfold(body, |mut body| async move {
+        body.recv().await.map(|line| (line, body))
+    });
+    Ok((
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+/// GET /v1/raffles/:raffle_id/participants - List buyers with aggregated holdings
+///
+/// Returns one row per buyer, sorted by ticket count (ties broken by address).
+/// With `include_clusters`, clustered buyers carry their sybil cluster; only admins and
+/// the raffle's creator may ask, and those responses aren't cached.
+async fn list_participants(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<ParticipantsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    if params.include_clusters {
+        authorize_cluster_flags(&state, raffle_id, &headers).await?;
+    }
+    let order = match params.sort.unwrap_or_default() {
+        ParticipantSort::TicketsDesc => "ticket_count DESC, buyer ASC",
+        ParticipantSort::TicketsAsc => "ticket_count ASC, buyer ASC",
+    };
+
+    let rows = sqlx::query(&format!(
+        "SELECT buyer,
+            SUM(count)::bigint AS ticket_count,
+            SUM(amount)::text AS total_spent,
+            COUNT(*) AS purchase_count,
+            array_agg(start_index ORDER BY start_index) AS starts,
+            array_agg(end_index ORDER BY start_index) AS ends,
+            EXISTS (SELECT 1 FROM blocked_addresses b WHERE b.address = buyer) AS buyer_blocked,
+            c.cluster_id, c.cluster_size, c.reasons AS cluster_reasons
+         FROM purchases_all
+         LEFT JOIN address_clusters c ON $4 AND c.address = buyer
+         WHERE raffle_id = $1
+         GROUP BY buyer, c.cluster_id, c.cluster_size, c.reasons
+         ORDER BY {order}
+         LIMIT $2 OFFSET $3"
+    ))
+    .bind(raffle_id)
+    .bind(limit)
+    .bind(offset)
+    .bind(params.include_clusters)
+    .fetch_all(&state.db)
+    .await?;
+
+    let decimals = state.config.token_decimals;
+    let mut participants = Vec::with_capacity(rows.len());
+    for row in rows {
+        let starts: Vec<i64> = row.try_get("starts").map_err(row_error_to_api_error)?;
+        let ends: Vec<i64> = row.try_get("ends").map_err(row_error_to_api_error)?;
+        let total_spent: String = row.try_get("total_spent").map_err(row_error_to_api_error)?;
+        let cluster_id: Option<String> =
+            row.try_get("cluster_id").map_err(row_error_to_api_error)?;
+        let cluster = match cluster_id {
+            Some(id) => Some(ClusterFlag {
+                id,
+                size: row
+                    .try_get("cluster_size")
+                    .map_err(row_error_to_api_error)?,
+                reasons: row
+                    .try_get("cluster_reasons")
+                    .map_err(row_error_to_api_error)?,
+            }),
+            None => None,
+        };
+        participants.push(Participant {
+            buyer: row.try_get("buyer").map_err(row_error_to_api_error)?,
+            buyer_blocked: row
+                .try_get("buyer_blocked")
+                .map_err(row_error_to_api_error)?,
+            ticket_count: row
+                .try_get("ticket_count")
+                .map_err(row_error_to_api_error)?,
+            total_spent_formatted: params.format.render(&total_spent, decimals),
+            total_spent_hex: params.format.render_hex(&total_spent),
+            total_spent,
+            purchase_count: row
+                .try_get("purchase_count")
+                .map_err(row_error_to_api_error)?,
+            ranges: merge_ranges(starts.into_iter().zip(ends)),
+            cluster,
+        });
+    }
+
+    let mut response = Json(participants).into_response();
+    if params.include_clusters {
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-store"),
+        );
+    }
+    Ok(response)
+}
+
+/// Admits an admin credential, or a `ParticipantClusters` message for `raffle_id`
+/// signed by the raffle's creator in [`SIGNED_REQUEST_HEADER`]
+async fn authorize_cluster_flags(
+    state: &AppState,
+    raffle_id: i64,
+    headers: &HeaderMap,
+) -> Result<(), ApiError> {
+    let Some(signed) = headers.get(SIGNED_REQUEST_HEADER) else {
+        // Every admin role may see the flags
+        let admin = auth::authenticate(&state.config, headers)?;
+        tracing::debug!(
+            by = admin.subject,
+            raffle_id,
+            "participants include clusters"
+        );
+        return Ok(());
+    };
+    let request: SignedRequest = signed
+        .to_str()
+        .ok()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .ok_or_else(|| {
+            ApiError::invalid_parameter(
+                SIGNED_REQUEST_HEADER,
+                "must be a JSON object with message and signature",
+            )
+        })?;
+    let signed_raffle = request.message.get("raffleId").and_then(|id| match id {
+        serde_json::Value::Number(number) => number.as_i64(),
+        serde_json::Value::String(id) => id.parse().ok(),
+        _ => None,
+    });
+    if signed_raffle != Some(raffle_id) {
+        return Err(ApiError::invalid_parameter(
+            "raffleId",
+            "the signed raffleId must be the requested raffle",
+        ));
+    }
+
+    let creator: String = sqlx::query_scalar!(
+        r#"SELECT creator AS "creator!" FROM raffles_all WHERE raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::RaffleNotFound)?;
+    let domain = SigningDomain::for_config(&state.config);
+    let mut db_tx = state.db.begin().await?;
+    let verified = crate::signatures::verify(
+        &mut db_tx,
+        &domain,
+        &PARTICIPANT_CLUSTERS_MESSAGE,
+        &request.message,
+        &request.signature,
+    )
+    .await?;
+    if !format!("{:#x}", verified.wallet).eq_ignore_ascii_case(&creator) {
+        return Err(ApiError::NotRaffleCreator);
+    }
+    db_tx.commit().await?;
+    Ok(())
+}
+
+/// GET /v1/raffles/:raffle_id/stats/histogram - Distribution of purchase sizes
+///
+/// Counts each `buyTickets` call by its ticket count, so analysts can compare
+/// large and small buyers without exporting every purchase.
+async fn get_purchase_histogram(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<FormatQuery>,
+) -> Result<(Option<Extension<Finalized>>, Json<PurchaseHistogram>), ApiError> {
+    let status: String = sqlx::query_scalar!(
+        r#"SELECT status AS "status!" FROM raffles_all WHERE raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    // width_bucket numbers buckets from 0 (below the first threshold) upwards
+    let rows = sqlx::query!(
+        r#"SELECT width_bucket(count, $2::int[]) AS "bucket!",
+            COUNT(*) AS "purchases!",
+            SUM(count)::bigint AS "tickets!",
+            SUM(amount)::text AS "amount!"
+         FROM purchases_all
+         WHERE raffle_id = $1
+         GROUP BY 1"#,
+        raffle_id,
+        &PURCHASE_SIZE_BUCKETS[1..],
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let decimals = state.config.token_decimals;
+    let mut buckets: Vec<PurchaseSizeBucket> = PURCHASE_SIZE_BUCKETS
+        .iter()
+        .enumerate()
+        .map(|(i, min)| PurchaseSizeBucket {
+            min_tickets: i64::from(*min),
+            max_tickets: PURCHASE_SIZE_BUCKETS
+                .get(i + 1)
+                .map(|next| i64::from(*next) - 1),
+            purchases: 0,
+            tickets: 0,
+            amount_formatted: params.format.render("0", decimals),
+            amount_hex: params.format.render_hex("0"),
+            amount: "0".to_string(),
+        })
+        .collect();
+    for row in rows {
+        let index: i32 = row.bucket;
+        let Some(bucket) = usize::try_from(index)
+            .ok()
+            .and_then(|index| buckets.get_mut(index))
+        else {
+            continue;
+        };
+        let amount: String = row.amount;
+        bucket.purchases = row.purchases;
+        bucket.tickets = row.tickets;
+        bucket.amount_formatted = params.format.render(&amount, decimals);
+        bucket.amount_hex = params.format.render_hex(&amount);
+        bucket.amount = amount;
+    }
+
+    let finalized = (status == "FINALIZED").then_some(Extension(Finalized));
+    Ok((
+        finalized,
+        Json(PurchaseHistogram {
+            raffle_id,
+            total_purchases: buckets.iter().map(|bucket| bucket.purchases).sum(),
+            total_tickets: buckets.iter().map(|bucket| bucket.tickets).sum(),
+            buckets,
+        }),
+    ))
+}
+
+/// GET /v1/raffles/:raffle_id/purchase-curve - Cumulative tickets sold over time
+///
+/// Purchases are bucketed by block time (`?interval=`, default 1h) with `date_bin`, so
+/// buckets line up across raffles; gaps between purchases are filled with empty
+/// buckets. Refunds don't lower the curve.
+async fn get_purchase_curve(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<PurchaseCurveQuery>,
+) -> Result<(Option<Extension<Finalized>>, Json<PurchaseCurve>), ApiError> {
+    let interval_secs = params
+        .interval_secs()
+        .unwrap_or(DEFAULT_CURVE_INTERVAL_SECS);
+    let raffle = sqlx::query!(
+        r#"SELECT r.status AS "status!",
+            (SELECT COALESCE(SUM(p.count), 0)::bigint FROM purchases_all p
+             WHERE p.raffle_id = r.raffle_id AND p.block_time IS NULL) AS "untimed_tickets!"
+         FROM raffles_all r
+         WHERE r.raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    let rows = sqlx::query!(
+        r#"SELECT date_bin($2 * INTERVAL '1 second', block_time, TIMESTAMPTZ 'epoch')
+                AS "bucket_start!",
+            COUNT(*) AS "purchases!",
+            SUM(count)::bigint AS "tickets!"
+         FROM purchases_all
+         WHERE raffle_id = $1 AND block_time IS NOT NULL
+         GROUP BY 1
+         ORDER BY 1"#,
+        raffle_id,
+        interval_secs as f64,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    if let (Some(first), Some(last)) = (rows.first(), rows.last()) {
+        let span = (last.bucket_start - first.bucket_start).num_seconds() / interval_secs + 1;
+        if span > MAX_CURVE_POINTS {
+            return Err(ApiError::invalid_parameter(
+                "interval",
+                format!(
+                    "interval is too short for this raffle's sales ({span} buckets, at most \
+                     {MAX_CURVE_POINTS})"
+                ),
+            ));
+        }
+    }
+
+    let interval = chrono::Duration::seconds(interval_secs);
+    let mut points: Vec<PurchaseCurvePoint> = Vec::new();
+    let mut cumulative_tickets = 0;
+    for row in rows {
+        // Empty buckets since the previous purchase
+        while let Some(next) = points
+            .last()
+            .map(|point| point.bucket_start + interval)
+            .filter(|next| *next < row.bucket_start)
+        {
+            points.push(PurchaseCurvePoint {
+                bucket_start: next,
+                purchases: 0,
+                tickets: 0,
+                cumulative_tickets,
+            });
+        }
+        cumulative_tickets += row.tickets;
+        points.push(PurchaseCurvePoint {
+            bucket_start: row.bucket_start,
+            purchases: row.purchases,
+            tickets: row.tickets,
+            cumulative_tickets,
+        });
+    }
+
+    let finalized = (raffle.status == "FINALIZED").then_some(Extension(Finalized));
+    Ok((
+        finalized,
+        Json(PurchaseCurve {
+            raffle_id,
+            interval_secs,
+            total_tickets: cumulative_tickets + raffle.untimed_tickets,
+            untimed_tickets: raffle.untimed_tickets,
+            points,
+        }),
+    ))
+}
+
+/// GET /v1/raffles/:raffle_id/pending - List unconfirmed purchases from the mempool
+///
+/// Entries expire after `PENDING_PURCHASE_TTL_SECS` and are dropped as soon as the
+/// indexer has stored a purchase with the same transaction hash.
+async fn list_pending_purchases(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Json<Vec<PendingPurchaseResponse>>, ApiError> {
+    let Some(pending) = state.pending.as_ref() else {
+        return Err(ApiError::FeatureDisabled("mempool watching is not enabled"));
+    };
+
+    let raffle_address: String = sqlx::query_scalar!(
+        "SELECT raffle_address FROM raffles WHERE raffle_id = $1",
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    let entries = pending.for_raffle(&raffle_address);
+    if entries.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    // Hide transactions the indexer has already confirmed
+    let tx_hashes: Vec<String> = entries.iter().map(|p| p.tx_hash.clone()).collect();
+    let confirmed: Vec<String> = sqlx::query_scalar!(
+        r#"SELECT DISTINCT LOWER(tx_hash) AS "tx_hash!" FROM purchases
+         WHERE raffle_id = $1 AND LOWER(tx_hash) = ANY($2)"#,
+        raffle_id,
+        &tx_hashes,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let purchases = entries
+        .into_iter()
+        .filter(|p| !confirmed.contains(&p.tx_hash))
+        .map(|p| PendingPurchaseResponse {
+            tx_hash: p.tx_hash,
+            buyer: p.buyer,
+            count: p.count,
+            confirmed: false,
+            seen_at: p.seen_at,
+            expires_at: p.expires_at,
+        })
+        .collect();
+
+    Ok(Json(purchases))
+}
+
+/// GET /v1/raffles/:raffle_id/proof - Get verification proof for a raffle
+///
+/// Returns randomness, winning index, winner address, and relevant transaction links
+/// for client-side verification of fair winner selection. Terminal raffles are
+/// answered from their snapshot (see [`snapshots`]).
+async fn get_raffle_proof(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Response, ApiError> {
+    let terminal = match load_snapshot(&state, raffle_id, snapshots::Kind::Proof).await? {
+        Ok(snapshot) => return Ok(snapshot_response(snapshot)),
+        Err(terminal) => terminal,
+    };
+
+    let (proof, finalized, updated_at) = fetch_raffle_proof(&state, raffle_id).await?;
+    let finalized = finalized.then_some(Extension(Finalized));
+
+    if terminal {
+        let snapshot = store_snapshot(
+            &state,
+            raffle_id,
+            snapshots::Kind::Proof,
+            &proof,
+            Some(updated_at),
+        )
+        .await?;
+        return Ok(snapshot_response(snapshot));
+    }
+    Ok((finalized, Extension(LastModified(updated_at)), Json(proof)).into_response())
+}
+
+/// GET /v1/raffles/:raffle_id/proof/bundle - Download a self-contained proof bundle
+///
+/// Combines the proof with the raffle's ticket ranges, the raw logs of its draw
+/// transactions and verification steps, so auditors can archive one file and check
+/// the draw without this API. Built from the live tables on every request.
+async fn get_raffle_proof_bundle(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Response, ApiError> {
+    let (proof, finalized, updated_at) = fetch_raffle_proof(&state, raffle_id).await?;
+
+    let ticket_ranges = sqlx::query!(
+        r#"SELECT buyer AS "buyer!", start_index AS "start_index!", end_index AS "end_index!",
+            count AS "count!", tx_hash AS "tx_hash!", block_number AS "block_number!",
+            log_index AS "log_index!"
+         FROM purchases_all
+         WHERE raffle_id = $1
+         ORDER BY block_number, log_index"#,
+        raffle_id,
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|row| BundleTicketRange {
+        buyer: row.buyer,
+        start_index: row.start_index,
+        end_index: row.end_index,
+        count: row.count,
+        tx_hash: row.tx_hash,
+        block_number: row.block_number,
+        log_index: row.log_index,
+    })
+    .collect();
+
+    let txs = &proof.txs;
+    let tx_hashes: Vec<String> = [
+        &txs.request_tx,
+        &txs.randomness_tx,
+        &txs.finalized_tx,
+        &txs.provider_request_tx,
+        &txs.provider_fulfill_tx,
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect();
+    let logs = sqlx::query!(
+        "SELECT tx_hash, block_number, block_hash, block_time, log_index, address, topics, data
+         FROM events_raw
+         WHERE tx_hash = ANY($1)
+         ORDER BY block_number, log_index",
+        &tx_hashes,
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|row| BundleLog {
+        tx_hash: row.tx_hash,
+        block_number: row.block_number,
+        block_hash: row.block_hash,
+        block_time: row.block_time,
+        log_index: row.log_index,
+        address: row.address,
+        topics: row.topics,
+        data: row.data,
+    })
+    .collect();
+
+    let bundle = ProofBundle {
+        version: 1,
+        generated_at: Utc::now(),
+        chain_id: state.config.chain_id,
+        proof,
+        ticket_ranges,
+        logs,
+        instructions: PROOF_BUNDLE_INSTRUCTIONS,
+    };
+    let disposition = format!("attachment; filename=\"raffle-{raffle_id}-proof-bundle.json\"");
+    Ok((
+        finalized.then_some(Extension(Finalized)),
+        Extension(LastModified(updated_at)),
+        [(header::CONTENT_DISPOSITION, disposition)],
+        Json(bundle),
+    )
+        .into_response())
+}
+
+/// Builds the proof of a raffle from its stored row, with whether it is finalized and
+/// when the row last changed
+async fn fetch_raffle_proof(
+    state: &AppState,
+    raffle_id: i64,
+) -> Result<(ProofResponse, bool, DateTime<Utc>), ApiError> {
+    let raffle_row = sqlx::query!(
+        r#"SELECT raffle_id AS "raffle_id!", raffle_address AS "raffle_address!", status AS "status!", request_id, request_tx, randomness, randomness_tx,
+            winning_index, winner, total_tickets AS "total_tickets!", finalized_tx,
+            provider_request_id, provider_request_tx, provider_fulfill_tx, proof_data,
+            provider_randomness, updated_at AS "updated_at!"
+         FROM raffles_all
+         WHERE raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    let Some(row) = raffle_row else {
+        return Err(ApiError::RaffleNotFound);
+    };
+
+    let raffle_address: String = row.raffle_address;
+    let request_id: Option<String> = row.request_id;
+    let request_tx: Option<String> = row.request_tx;
+    let randomness: Option<String> = row.randomness;
+    let randomness_tx: Option<String> = row.randomness_tx;
+    let winner: Option<String> = row.winner;
+    let finalized_tx: Option<String> = row.finalized_tx;
+    let total_tickets: i64 = row.total_tickets;
+    let mut winning_index: Option<i64> = row.winning_index;
+
+    // Provider-level fields
+    let provider_request_id: Option<String> = row.provider_request_id;
+    let provider_request_tx: Option<String> = row.provider_request_tx;
+    let provider_fulfill_tx: Option<String> = row.provider_fulfill_tx;
+    let proof_data: Option<String> = row.proof_data;
+    let provider_randomness: Option<String> = row.provider_randomness;
+    let provider_randomness_matches = randomness
+        .as_ref()
+        .zip(provider_randomness.as_ref())
+        .map(|(raffle, provider)| raffle == provider);
+
+    // If the winning index was not stored, recompute it from randomness.
+    // This allows clients to verify: winningIndex = randomness % totalTickets
+    if winning_index.is_none()
+        && let Some(ref randomness_str) = randomness
+    {
+        winning_index = compute_winning_index(randomness_str, total_tickets);
+    }
+
+    // Look up the winning ticket range
+    let winning_range = if let Some(index) = winning_index {
+        find_ticket_range(&state.db, raffle_id, index).await?
+    } else {
+        None
+    };
+
+    let txs = TxLinks {
+        request_tx: request_tx.clone(),
+        request_url: build_tx_url(&state.config.explorer_base_url, &request_tx),
+        randomness_tx: randomness_tx.clone(),
+        randomness_url: build_tx_url(&state.config.explorer_base_url, &randomness_tx),
+        finalized_tx: finalized_tx.clone(),
+        finalized_url: build_tx_url(&state.config.explorer_base_url, &finalized_tx),
+        provider_request_tx: provider_request_tx.clone(),
+        provider_request_url: build_tx_url(&state.config.explorer_base_url, &provider_request_tx),
+        provider_fulfill_tx: provider_fulfill_tx.clone(),
+        provider_fulfill_url: build_tx_url(&state.config.explorer_base_url, &provider_fulfill_tx),
+    };
+
+    let finalized = row.status == "FINALIZED";
+    let proof = ProofResponse {
+        raffle_id: row.raffle_id,
+        raffle_address,
+        request_id,
+        provider_request_id,
+        randomness,
+        proof_data,
+        provider_randomness,
+        provider_randomness_matches,
+        total_tickets,
+        winning_index,
+        winner,
+        winning_range,
+        txs,
+    };
+    Ok((proof, finalized, row.updated_at))
+}
+
+/// Response extension marking data about a finalized raffle, which can't change any more
+///
+/// [`cache_control`] gives marked responses the route's finalized max-age.
+#[derive(Clone, Copy)]
+struct Finalized;
+
+/// Response extension carrying when the returned data last changed
+///
+/// [`cache_control`] sends it as `Last-Modified` and answers a matching
+/// `If-Modified-Since` with 304 Not Modified.
+#[derive(Clone, Copy)]
+struct LastModified(DateTime<Utc>);
+
+/// GET /v1/raffles/:raffle_id/attestation - Get a signed statement of a finalized result
+///
+/// The payload is serialized to JSON and signed with the configured attestation key
+/// using EIP-191 (`personal_sign`), so anyone can recover the signer from
+/// `message` and `signature` and compare it with our published address.
+async fn get_raffle_attestation(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Json<AttestationResponse>, ApiError> {
+    let Some(signer) = state.attestation_signer.as_ref() else {
+        return Err(ApiError::FeatureDisabled("attestations are not enabled"));
+    };
+
+    let row = sqlx::query!(
+        r#"SELECT r.raffle_id AS "raffle_id!", r.raffle_address AS "raffle_address!", r.status AS "status!", r.randomness, r.total_tickets AS "total_tickets!",
+            r.winning_index, r.winner, r.randomness_tx, r.finalized_tx,
+            (SELECT e.block_number FROM events_raw e
+             WHERE e.tx_hash = r.randomness_tx ORDER BY e.log_index LIMIT 1) AS randomness_block,
+            (SELECT e.block_number FROM events_raw e
+             WHERE e.tx_hash = r.finalized_tx ORDER BY e.log_index LIMIT 1) AS finalized_block
+         FROM raffles_all r
+         WHERE r.raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    let Some(row) = row else {
+        return Err(ApiError::RaffleNotFound);
+    };
+
+    let status: String = row.status;
+    if status != "FINALIZED" {
+        return Err(ApiError::RaffleNotFinalized);
+    }
+
+    let payload = AttestationPayload {
+        version: 1,
+        chain_id: state.config.chain_id,
+        raffle_id: row.raffle_id,
+        raffle_address: row.raffle_address,
+        randomness: row.randomness,
+        total_tickets: row.total_tickets,
+        winning_index: row.winning_index,
+        winner: row.winner,
+        randomness_tx: row.randomness_tx,
+        randomness_block: row.randomness_block,
+        finalized_tx: row.finalized_tx,
+        finalized_block: row.finalized_block,
+    };
+
+    let message = serde_json::to_string(&payload).map_err(|err| {
+        tracing::error!(error = %err, "failed to serialize attestation payload");
+        ApiError::Internal("failed to build attestation")
+    })?;
+    let signature = signer.sign_message(&message).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to sign attestation");
+        ApiError::Internal("failed to sign attestation")
+    })?;
+
+    Ok(Json(AttestationResponse {
+        payload,
+        message,
+        signature: format!("0x{}", signature),
+        signer: format!("{:#x}", signer.address()),
+        scheme: "eip191",
+    }))
+}
+
+/// GET /v1/raffles/:raffle_id/card - Social card fields for link previews
+///
+/// Composes the title and status line (e.g. "4,200 tickets · pot 3.1 USDC · ends in 2h")
+/// here, so the frontend's SSR layer and other unfurlers render identical previews.
+async fn get_raffle_card(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Json<RaffleCard>, ApiError> {
+    let row = sqlx::query(&format!(
+        "SELECT raffle_id, end_time, total_tickets, pot::text AS pot,
+            prize_amount::text AS prize_amount, winner,
+            {EFFECTIVE_STATUS_SQL} AS effective_status
+         FROM raffles_all
+         WHERE raffle_id = $1"
+    ))
+    .bind(raffle_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    let end_time: Option<DateTime<Utc>> =
+        row.try_get("end_time").map_err(row_error_to_api_error)?;
+    let total_tickets: i64 = row
+        .try_get("total_tickets")
+        .map_err(row_error_to_api_error)?;
+    let pot: String = row.try_get("pot").map_err(row_error_to_api_error)?;
+    let prize_amount: Option<String> = row
+        .try_get("prize_amount")
+        .map_err(row_error_to_api_error)?;
+    let winner: Option<String> = row.try_get("winner").map_err(row_error_to_api_error)?;
+    let effective_status: String = row
+        .try_get("effective_status")
+        .map_err(row_error_to_api_error)?;
+
+    let config = &state.config;
+    let amount = |raw: &str| {
+        let value = format::format_display_amount(raw, config.token_decimals, 2)
+            .unwrap_or_else(|| raw.to_string());
+        format!("{} {}", value, config.token_symbol)
+    };
+
+    let tickets = match total_tickets {
+        1 => "1 ticket".to_string(),
+        n => format!("{} tickets", format::format_count(n)),
+    };
+    let mut parts = vec![tickets];
+    match effective_status.as_str() {
+        "FINALIZED" => {
+            if let Some(prize_amount) = &prize_amount {
+                parts.push(format!("prize {}", amount(prize_amount)));
+            }
+            if let Some(winner) = &winner {
+                parts.push(format!("won by {}", short_address(winner)));
+            }
+        }
+        "REFUNDING" => parts.push("refunds open".to_string()),
+        "CANCELED" => parts.push("canceled, refunds open".to_string()),
+        status => {
+            parts.push(format!("pot {}", amount(&pot)));
+            match status {
+                "ACTIVE" => {
+                    if let Some(end_time) = end_time {
+                        let remaining = format::format_duration_short(end_time - Utc::now());
+                        parts.push(format!("ends in {remaining}"));
+                    }
+                }
+                "ENDED" => parts.push("ended".to_string()),
+                _ => parts.push("drawing winner".to_string()),
+            }
+        }
+    }
+
+    let image_url = config
+        .card_image_url_template
+        .as_ref()
+        .map(|template| template.replace("{raffle_id}", &raffle_id.to_string()));
+
+    Ok(Json(RaffleCard {
+        raffle_id,
+        title: format!("Ticket Arcade Raffle #{raffle_id}"),
+        status_line: parts.join(" · "),
+        url: config
+            .site_base_url
+            .as_ref()
+            .map(|base| format!("{base}/raffles/{raffle_id}")),
+        twitter_card: if image_url.is_some() {
+            "summary_large_image"
+        } else {
+            "summary"
+        },
+        image_url,
+        effective_status,
+    }))
+}
+
+/// GET /v1/embed/raffles/:raffle_id - Widget payload with long-lived cache headers
+///
+/// Served from a stale-while-revalidate cache (`EMBED_CACHE_TTL_SECS`) because
+/// partner pages poll it from every visitor's browser.
+async fn get_embed_raffle(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+) -> Result<Response, ApiError> {
+    let load = || load_embed_raffle(state.clone(), raffle_id).in_current_span();
+    let cached = state.embed_cache.get(raffle_id.to_string(), load).await?;
+
+    // Finalized raffles can't change any more
+    let finalized = serde_json::from_slice::<EmbedRaffle>(&cached.body)
+        .is_ok_and(|raffle| raffle.status == "FINALIZED");
+    let ttl = state.config.embed_cache_ttl_secs;
+    let cache_control = if finalized {
+        format!("public, max-age={FINAL_EMBED_MAX_AGE_SECS}, immutable")
+    } else {
+        let stale = ttl * u64::from(EMBED_STALE_FACTOR);
+        format!("public, max-age={ttl}, s-maxage={ttl}, stale-while-revalidate={stale}")
+    };
+
+    let mut response = json_response(cached.body);
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    headers.insert(header::AGE, HeaderValue::from(cached.age.as_secs()));
+    headers.insert("x-cache", HeaderValue::from_static(cached.status.as_str()));
+    Ok(response)
+}
+
+/// Loads and serializes the embed payload of one raffle
+async fn load_embed_raffle(state: AppState, raffle_id: i64) -> Result<Bytes, ApiError> {
+    let row = sqlx::query(&format!(
+        "SELECT raffle_id, {EFFECTIVE_STATUS_SQL} AS effective_status, total_tickets,
+            pot::text AS pot, end_time, winner
+         FROM raffles_all
+         WHERE raffle_id = $1"
+    ))
+    .bind(raffle_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    let config = &state.config;
+    let pot: String = row.try_get("pot").map_err(row_error_to_api_error)?;
+    let raffle = EmbedRaffle {
+        raffle_id,
+        status: row
+            .try_get("effective_status")
+            .map_err(row_error_to_api_error)?,
+        total_tickets: row
+            .try_get("total_tickets")
+            .map_err(row_error_to_api_error)?,
+        pot_formatted: format::format_units(&pot, config.token_decimals),
+        pot,
+        token_symbol: config.token_symbol.clone(),
+        end_time: row.try_get("end_time").map_err(row_error_to_api_error)?,
+        winner: row.try_get("winner").map_err(row_error_to_api_error)?,
+        url: config
+            .site_base_url
+            .as_ref()
+            .map(|base| format!("{base}/raffles/{raffle_id}")),
+    };
+    serde_json::to_vec(&raffle).map(Bytes::from).map_err(|err| {
+        tracing::error!(error = %err, "failed to serialize embed payload");
+        ApiError::Internal("serialization error")
+    })
+}
+
+/// OPTIONS /v1/embed/... - CORS preflight (headers are added by [`embed_cors`])
+async fn embed_preflight() -> impl IntoResponse {
+    (
+        StatusCode::NO_CONTENT,
+        [(header::ALLOW, "GET,HEAD,OPTIONS")],
+    )
+}
+
+/// POST /v1/verify - Independently recompute a raffle winner
+///
+/// Applies the contract's selection rule (`randomness % totalTickets`, then the range
+/// containing that index) to the supplied or indexed inputs and compares the outcome
+/// with the indexed on-chain result.
+async fn verify_winner(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, ApiError> {
+    if let Some(purchases) = &request.purchases
+        && purchases.len() > MAX_VERIFY_PURCHASES
+    {
+        return Err(ApiError::invalid_parameter(
+            "purchases",
+            format!("at most {} purchases may be supplied", MAX_VERIFY_PURCHASES),
+        ));
+    }
+
+    let mut randomness = request.randomness;
+    let mut total_tickets = request.total_tickets;
+    let mut onchain = None;
+
+    if let Some(raffle_id) = request.raffle_id {
+        let row = sqlx::query!(
+            r#"SELECT randomness, total_tickets AS "total_tickets!", winning_index, winner
+             FROM raffles_all
+             WHERE raffle_id = $1"#,
+            raffle_id,
+        )
+        .fetch_optional(&state.db)
+        .await?;
+
+        let Some(row) = row else {
+            return Err(ApiError::RaffleNotFound);
+        };
+
+        if randomness.is_none() {
+            randomness = row.randomness;
+        }
+        if total_tickets.is_none() {
+            total_tickets = Some(row.total_tickets);
+        }
+        onchain = Some(OnchainResult {
+            winning_index: row.winning_index,
+            winner: row.winner,
+        });
+    }
+
+    let Some(randomness) = randomness else {
+        return Err(ApiError::invalid_parameter(
+            "randomness",
+            "randomness is required",
+        ));
+    };
+    let Some(total_tickets) = total_tickets.filter(|total| *total > 0) else {
+        return Err(ApiError::invalid_parameter(
+            "total_tickets",
+            "total_tickets must be positive",
+        ));
+    };
+    let Some(winning_index) = compute_winning_index(&randomness, total_tickets) else {
+        return Err(ApiError::invalid_parameter(
+            "randomness",
+            "randomness must be a decimal uint256 string",
+        ));
+    };
+
+    let winner = match (&request.purchases, request.raffle_id) {
+        (Some(purchases), _) => purchases
+            .iter()
+            .find(|p| p.start_index <= winning_index && winning_index <= p.end_index)
+            .map(|p| p.buyer.to_lowercase()),
+        (None, Some(raffle_id)) => find_ticket_range(&state.db, raffle_id, winning_index)
+            .await?
+            .map(|range| range.buyer),
+        (None, None) => None,
+    };
+
+    let matches = onchain
+        .as_ref()
+        .and_then(|result| result.matches(winning_index, winner.as_deref()));
+
+    Ok(Json(VerifyResponse {
+        randomness,
+        total_tickets,
+        winning_index,
+        winner,
+        onchain,
+        matches,
+    }))
+}
+
+/// GET /v1/chain - Chain and deployment info for frontend bootstrapping
+async fn get_chain_info(State(state): State<AppState>) -> Result<Json<ChainInfo>, ApiError> {
+    let indexed_block: i64 =
+        sqlx::query_scalar!("SELECT last_processed_block FROM indexer_state WHERE id = 1",)
+            .fetch_optional(&state.db)
+            .await?
+            .unwrap_or(0);
+
+    let config = &state.config;
+    Ok(Json(ChainInfo {
+        deployment: config.deployment.clone(),
+        chain_id: config.chain_id,
+        factory_addresses: vec![config.raffle_factory_address.to_lowercase()],
+        randomness_provider_address: config
+            .randomness_provider_address
+            .as_ref()
+            .map(|address| address.to_lowercase()),
+        explorer_base_url: config.explorer_base_url.clone(),
+        token_decimals: config.token_decimals,
+        head_block: state.indexer.head_block(),
+        indexed_block: (indexed_block > 0).then_some(indexed_block),
+        signing_domain: SigningDomain::for_config(config),
+    }))
+}
+
+/// GET /v1/network - Head block, average block time and gas prices
+///
+/// Read from the RPC node and cached (`NETWORK_CACHE_TTL_SECS`), so polling clients
+/// share one set of requests.
+async fn get_network(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let load = || load_network(state.clone()).in_current_span();
+    let cached = state.network_cache.get("network".to_string(), load).await?;
+
+    let mut response = json_response(cached.body);
+    let headers = response.headers_mut();
+    headers.insert(header::AGE, HeaderValue::from(cached.age.as_secs()));
+    headers.insert("x-cache", HeaderValue::from_static(cached.status.as_str()));
+    Ok(response)
+}
+
+/// Reads and serializes the network status
+async fn load_network(state: AppState) -> Result<Bytes, ApiError> {
+    let network = state
+        .chain
+        .fetch_network(NETWORK_SAMPLE_BLOCKS)
+        .await
+        .map_err(|err| {
+            match &err {
+                ChainReadError::RateLimited => tracing::debug!("network lookup rate limited"),
+                ChainReadError::Rpc(err) => {
+                    tracing::warn!(error = %err, "network lookup failed");
+                }
+            }
+            ApiError::Chain(err)
+        })?;
+
+    let max_fee_per_gas = network
+        .base_fee_per_gas
+        .zip(network.max_priority_fee_per_gas)
+        .map(|(base_fee, priority_fee)| {
+            base_fee
+                .saturating_mul(U256::from(2))
+                .saturating_add(priority_fee)
+                .to_string()
+        });
+    let response = NetworkResponse {
+        head_block: network.head_block,
+        head_block_time: DateTime::<Utc>::from_timestamp(network.head_timestamp as i64, 0),
+        average_block_time_secs: network.average_block_time,
+        sample_blocks: NETWORK_SAMPLE_BLOCKS,
+        gas: GasPrices {
+            gas_price: network.gas_price.to_string(),
+            base_fee_per_gas: network.base_fee_per_gas.map(|fee| fee.to_string()),
+            max_priority_fee_per_gas: network.max_priority_fee_per_gas.map(|fee| fee.to_string()),
+            max_fee_per_gas,
+        },
+    };
+    serde_json::to_vec(&response)
+        .map(Bytes::from)
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to serialize network status");
+            ApiError::Internal("serialization error")
+        })
+}
+
+/// `paused` while an admin paused the indexer or its RPC circuit isn't closed
+fn indexer_state(indexer: &IndexerStatus, rpc_circuit: &CircuitSnapshot) -> &'static str {
+    match rpc_circuit.state {
+        _ if indexer.is_paused() => "paused",
+        CircuitState::Closed => "running",
+        CircuitState::Open | CircuitState::HalfOpen => "paused",
+    }
+}
+
+/// GET /v1/status - Indexer progress and RPC circuit state
+async fn get_status(State(state): State<AppState>) -> Result<Json<StatusResponse>, ApiError> {
+    let (indexed_block, finalized_block, paused_at) = sqlx::query!(
+        "SELECT last_processed_block, finalized_block, paused_at
+         FROM indexer_state WHERE id = 1",
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .map(|row| (row.last_processed_block, row.finalized_block, row.paused_at))
+    .unwrap_or((0, None, None));
+    let indexed_block = (indexed_block > 0).then_some(indexed_block);
+    let head_block = state.indexer.head_block();
+    let undecoded_events: i64 =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM events_raw WHERE NOT decoded"#)
+            .fetch_one(&state.db)
+            .await?;
+
+    let rpc_circuit = state.indexer.rpc.snapshot();
+
+    Ok(Json(StatusResponse {
+        indexer: indexer_state(&state.indexer, &rpc_circuit),
+        paused_at,
+        head_block,
+        indexed_block,
+        lag_blocks: head_block
+            .zip(indexed_block)
+            .map(|(head, indexed)| head.saturating_sub(indexed as u64)),
+        finalized_block,
+        undecoded_events,
+        rpc_circuit,
+        tasks: state.tasks.snapshot(),
+    }))
+}
+
+/// GET /v1/fees - Summarize protocol fees per fee recipient
+///
+/// Payouts are attributed to the block time of their finalize transaction (falling
+/// back to index time for rows without one).
+async fn list_fees(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<FeesQuery>,
+) -> Result<Json<Vec<FeeSummary>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+    if let (Some(from), Some(to)) = (params.from, params.to)
+        && from >= to
+    {
+        return Err(ApiError::invalid_parameter(
+            "from",
+            "from must be before to",
+        ));
+    }
+    let period = match params.interval {
+        None => "NULL::timestamptz",
+        Some(FeeInterval::Day) => "date_trunc('day', COALESCE(block_time, created_at))",
+        Some(FeeInterval::Week) => "date_trunc('week', COALESCE(block_time, created_at))",
+        Some(FeeInterval::Month) => "date_trunc('month', COALESCE(block_time, created_at))",
+    };
+
+    let rows = sqlx::query(&format!(
+        "SELECT fee_recipient,
+            {period} AS period_start,
+            COUNT(*) AS raffle_count,
+            SUM(fee_amount)::text AS total_fees,
+            SUM(prize_amount)::text AS total_prizes
+         FROM payouts
+         WHERE ($1::timestamptz IS NULL OR COALESCE(block_time, created_at) >= $1)
+           AND ($2::timestamptz IS NULL OR COALESCE(block_time, created_at) < $2)
+           AND ($3::text IS NULL OR LOWER(fee_recipient) = LOWER($3))
+         GROUP BY fee_recipient, period_start
+         ORDER BY period_start ASC NULLS FIRST, fee_recipient ASC
+         LIMIT $4 OFFSET $5"
+    ))
+    .bind(params.from)
+    .bind(params.to)
+    .bind(params.fee_recipient)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
+
+    let decimals = state.config.token_decimals;
+    let mut fees = Vec::with_capacity(rows.len());
+    for row in rows {
+        let total_fees: String = row.try_get("total_fees").map_err(row_error_to_api_error)?;
+        let total_prizes: String = row
+            .try_get("total_prizes")
+            .map_err(row_error_to_api_error)?;
+        fees.push(FeeSummary {
+            fee_recipient: row
+                .try_get("fee_recipient")
+                .map_err(row_error_to_api_error)?,
+            period_start: row
+                .try_get("period_start")
+                .map_err(row_error_to_api_error)?,
+            raffle_count: row
+                .try_get("raffle_count")
+                .map_err(row_error_to_api_error)?,
+            total_fees_formatted: params.format.render(&total_fees, decimals),
+            total_fees_hex: params.format.render_hex(&total_fees),
+            total_fees,
+            total_prizes_formatted: params.format.render(&total_prizes, decimals),
+            total_prizes_hex: params.format.render_hex(&total_prizes),
+            total_prizes,
+        });
+    }
+
+    Ok(Json(fees))
+}
+
+/// GET /v1/stats/keeper - Draw stage durations and raffles waiting on the keeper
+///
+/// Each stage is attributed to the time it ended; `pending` reads the hot table only.
+async fn get_keeper_stats(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<KeeperStatsQuery>,
+) -> Result<Json<KeeperStatsResponse>, ApiError> {
+    let keeper = params.keeper.map(|keeper| keeper.to_lowercase());
+    let rows = sqlx::query!(
+        r#"SELECT s.stage AS "stage!", COUNT(*) AS "count!",
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY s.secs) AS p50_secs,
+            percentile_cont(0.9) WITHIN GROUP (ORDER BY s.secs) AS p90_secs,
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY s.secs) AS p99_secs,
+            MAX(s.secs) AS max_secs
+         FROM raffle_stage_durations d
+         JOIN raffles_all r ON r.raffle_id = d.raffle_id
+         CROSS JOIN LATERAL (
+             VALUES ('close_to_request', d.close_to_request_secs, d.randomness_requested_at),
+                    ('request_to_fulfill', d.request_to_fulfill_secs, d.randomness_fulfilled_at),
+                    ('fulfill_to_finalize', d.fulfill_to_finalize_secs, d.finalized_at)
+         ) AS s (stage, secs, ended_at)
+         WHERE s.secs IS NOT NULL
+           AND ($1::text IS NULL OR r.keeper = $1)
+           AND ($2::timestamptz IS NULL OR s.ended_at >= $2)
+           AND ($3::timestamptz IS NULL OR s.ended_at < $3)
+         GROUP BY s.stage"#,
+        keeper,
+        params.from,
+        params.to,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let mut stats = KeeperStatsResponse {
+        keeper: keeper.clone(),
+        close_to_request: StageStats::default(),
+        request_to_fulfill: StageStats::default(),
+        fulfill_to_finalize: StageStats::default(),
+        pending: Vec::new(),
+    };
+    for row in rows {
+        let stage = match row.stage.as_str() {
+            "close_to_request" => &mut stats.close_to_request,
+            "request_to_fulfill" => &mut stats.request_to_fulfill,
+            _ => &mut stats.fulfill_to_finalize,
+        };
+        *stage = StageStats {
+            count: row.count,
+            p50_secs: row.p50_secs,
+            p90_secs: row.p90_secs,
+            p99_secs: row.p99_secs,
+            max_secs: row.max_secs,
+        };
+    }
+
+    stats.pending = sqlx::query_as!(
+        PendingDraw,
+        r#"SELECT r.raffle_id, r.status, r.keeper, c.since,
+            EXTRACT(EPOCH FROM now() - c.since)::bigint AS waiting_secs
+         FROM raffles r
+         LEFT JOIN LATERAL (
+             SELECT MAX(block_time) AS since FROM raffle_status_changes c
+             WHERE c.raffle_id = r.raffle_id AND c.status = r.status
+         ) c ON true
+         WHERE r.status IN ('CLOSED', 'RANDOM_REQUESTED', 'RANDOM_FULFILLED')
+           AND ($1::text IS NULL OR r.keeper = $1)
+         ORDER BY c.since ASC NULLS FIRST, r.raffle_id
+         LIMIT $2"#,
+        keeper,
+        MAX_PAGE_LIMIT,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(stats))
+}
+
+/// GET /v1/raffles/stuck - Raffles stuck in CLOSED or RANDOM_REQUESTED past their SLA
+///
+/// Longest stuck first, at most [`MAX_PAGE_LIMIT`]; reads the hot table only.
+async fn list_stuck_raffles(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<StuckRaffle>>, ApiError> {
+    Ok(Json(
+        stuck::find(&state.db, &state.config, MAX_PAGE_LIMIT).await?,
+    ))
+}
+
+/// GET /v1/changes - Raffles, purchases and refunds changed after a block
+///
+/// A window ends at the indexer cursor, so its blocks are fully indexed. It is cut
+/// before the block of the first row past `limit` in any table, unless that is its
+/// first block, which is served whole. Raffles are listed in the window of their latest
+/// change, and with every window holding their purchases or refunds.
+async fn list_changes(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<ChangesQuery>,
+) -> Result<Json<ChangesResponse>, ApiError> {
+    let decimals = state.config.token_decimals;
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let (indexed_block, finalized_block) = sqlx::query!(
+        "SELECT last_processed_block, finalized_block FROM indexer_state WHERE id = 1",
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .map(|row| (row.last_processed_block, row.finalized_block))
+    .unwrap_or((0, None));
+    let since_block = match (params.since_block, params.since) {
+        (Some(since_block), _) => since_block,
+        (None, Some(since)) => {
+            sqlx::query_scalar!(
+                r#"SELECT COALESCE(MAX(block_number), 0) AS "block!" FROM events_raw WHERE block_time <= $1"#,
+                since,
+            )
+            .fetch_one(&state.db)
+            .await?
+        }
+        (None, None) => 0,
+    };
+
+    let cut = sqlx::query_scalar!(
+        "SELECT MIN(block) FROM (
+            (SELECT block_number AS block FROM purchases_all
+             WHERE block_number > $1 AND block_number <= $2
+             ORDER BY block_number LIMIT 1 OFFSET $3)
+            UNION ALL
+            (SELECT block_number FROM refunds_all
+             WHERE block_number > $1 AND block_number <= $2
+             ORDER BY block_number LIMIT 1 OFFSET $3)
+            UNION ALL
+            (SELECT updated_block FROM raffles_all
+             WHERE updated_block > $1 AND updated_block <= $2
+             ORDER BY updated_block LIMIT 1 OFFSET $3)
+         ) c",
+        since_block,
+        indexed_block,
+        limit,
+    )
+    .fetch_one(&state.db)
+    .await?;
+    let to_block = match cut {
+        Some(cut) if cut > since_block + 1 => cut - 1,
+        Some(cut) => cut,
+        None => indexed_block.max(since_block),
+    };
+
+    let rows = sqlx::query(&format!(
+        "SELECT raffle_id, {PURCHASE_COLUMNS} FROM purchases_all
+         WHERE block_number > $1 AND block_number <= $2
+         ORDER BY block_number, log_index"
+    ))
+    .bind(since_block)
+    .bind(to_block)
+    .fetch_all(&state.db)
+    .await?;
+    let purchases = rows
+        .iter()
+        .map(|row| {
+            Ok(ChangedPurchase {
+                raffle_id: row.try_get("raffle_id")?,
+                purchase: PurchaseRange::from_row(row, params.format, decimals)?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(row_error_to_api_error)?;
+
+    let refunds: Vec<ChangedRefund> = sqlx::query!(
+        r#"SELECT raffle_id AS "raffle_id!", buyer AS "buyer!", amount::text AS "amount!",
+            tx_hash AS "tx_hash!", log_index AS "log_index!", block_number AS "block_number!",
+            block_hash, block_time, block_is_finalized(block_number) AS "finalized!"
+         FROM refunds_all
+         WHERE block_number > $1 AND block_number <= $2
+         ORDER BY block_number, log_index"#,
+        since_block,
+        to_block,
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|row| ChangedRefund {
+        raffle_id: row.raffle_id,
+        buyer: row.buyer,
+        amount_formatted: params.format.render(&row.amount, decimals),
+        amount_hex: params.format.render_hex(&row.amount),
+        amount: row.amount,
+        tx_hash: row.tx_hash,
+        log_index: row.log_index,
+        block_number: row.block_number,
+        block_hash: row.block_hash,
+        block_time: row.block_time,
+        finalized: row.finalized,
+    })
+    .collect();
+
+    let parents: Vec<i64> = purchases
+        .iter()
+        .map(|changed| changed.raffle_id)
+        .chain(refunds.iter().map(|changed| changed.raffle_id))
+        .collect();
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM raffles_all r
+         WHERE (updated_block > $1 AND updated_block <= $2) OR raffle_id = ANY($3)
+         ORDER BY raffle_id",
+        raffle_details_columns()
+    ))
+    .bind(since_block)
+    .bind(to_block)
+    .bind(&parents)
+    .fetch_all(&state.db)
+    .await?;
+    let now = Utc::now();
+    let raffles = rows
+        .iter()
+        .map(|row| {
+            RaffleDetails::from_row(row, now)
+                .map(|details| details.with_format(params.format, decimals))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(row_error_to_api_error)?;
+
+    Ok(Json(ChangesResponse {
+        since_block,
+        next_since_block: to_block,
+        has_more: to_block < indexed_block,
+        finalized_block,
+        raffles,
+        purchases,
+        refunds,
+    }))
+}
+
+/// Decoder for `GET /v1/events`, built from the human-readable ABIs on first use
+static EVENT_DECODER: LazyLock<LogDecoder> = LazyLock::new(|| LogDecoder::new(&Abis::new()));
+
+/// GET /v1/events - Page through stored raw logs in chain order
+///
+/// Filters are optional and combine. Logs are decoded with [`Abis`] rather than the
+/// indexer's artifacts, which the API doesn't load; logs whose event is unknown, or
+/// whose indexed topics weren't stored, come with `decoded: null`.
+async fn list_events(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<EventsQuery>,
+) -> Result<Json<Vec<RawEvent>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let rows = sqlx::query(&format!(
+        "SELECT tx_hash, log_index, block_number, block_hash, block_time,
+            block_is_finalized(block_number) AS finalized, address, topic0, topics, data
+         FROM events_raw
+         WHERE ($1::text IS NULL OR address = LOWER($1))
+           AND ($2::text IS NULL OR topic0 = LOWER($2))
+           AND ($3::bigint IS NULL OR block_number >= $3)
+           AND ($4::bigint IS NULL OR block_number <= $4)
+           AND {}
+         ORDER BY block_number, log_index
+         LIMIT $7",
+        purchase_after_sql(5)
+    ))
+    .bind(params.address.as_deref())
+    .bind(params.topic0.as_deref())
+    .bind(params.from_block)
+    .bind(params.to_block)
+    .bind(params.after_block)
+    .bind(params.after_log_index)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?;
+
+    let events = rows
+        .iter()
+        .map(|row| {
+            let topic0: String = row.try_get("topic0")?;
+            let topics: Option<Vec<String>> = row.try_get("topics")?;
+            let data: String = row.try_get("data")?;
+            let decoded = EVENT_DECODER.decode_stored(
+                topics.as_deref().unwrap_or(std::slice::from_ref(&topic0)),
+                &data,
+            );
+            Ok(RawEvent {
+                tx_hash: row.try_get("tx_hash")?,
+                log_index: row.try_get("log_index")?,
+                block_number: row.try_get("block_number")?,
+                block_hash: row.try_get("block_hash")?,
+                block_time: row.try_get("block_time")?,
+                finalized: row.try_get("finalized")?,
+                address: row.try_get("address")?,
+                topic0,
+                topics,
+                data,
+                decoded,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(row_error_to_api_error)?;
+    Ok(Json(events))
+}
+
+/// GET /v1/digests/latest - Summary of the last finished UTC day
+async fn get_latest_digest(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<FormatQuery>,
+) -> Result<Json<DigestResponse>, ApiError> {
+    let row = sqlx::query!(
+        r#"SELECT summary::text AS "summary!", created_at
+         FROM digests
+         ORDER BY day DESC
+         LIMIT 1"#,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("no digest compiled yet"))?;
+
+    let summary: String = row.summary;
+    let digest: Digest = serde_json::from_str(&summary).map_err(|err| {
+        tracing::error!(error = %err, "invalid stored digest");
+        ApiError::Internal("invalid stored digest")
+    })?;
+
+    Ok(Json(DigestResponse {
+        digest: digest.with_format(params.format, state.config.token_decimals),
+        generated_at: row.created_at,
+    }))
+}
+
+/// GET /v1/analytics/cohorts - Participant cohorts by first-purchase week
+///
+/// Served from the tables the cohort job (see [`crate::cohorts`]) rebuilds.
+async fn get_cohorts(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<CohortsQuery>,
+) -> Result<Json<CohortReport>, ApiError> {
+    let weeks = params.weeks.unwrap_or(DEFAULT_COHORT_WEEKS);
+    Ok(Json(cohorts::load(&state.db, weeks).await?))
+}
+
+/// POST /v1/referral-codes - Register a referral code
+///
+/// Takes a signed `ReferralCodeRegistration` message from a wallet that created at
+/// least one raffle. Codes are case-insensitive and stored upper case; registering a
+/// code again from its owner returns it unchanged.
+async fn register_referral_code(
+    State(state): State<AppState>,
+    Json(request): Json<SignedRequest>,
+) -> Result<(StatusCode, Json<ReferralCodeResponse>), ApiError> {
+    // Check the code before verifying, so a rejected request doesn't burn the nonce
+    let code = request
+        .message
+        .get("code")
+        .and_then(|code| code.as_str())
+        .and_then(referrals::normalize_code)
+        .ok_or_else(|| {
+            ApiError::invalid_parameter(
+                "code",
+                format!(
+                    "code must be {}-{} letters, digits, _ or -",
+                    referrals::MIN_CODE_LEN,
+                    referrals::MAX_CODE_LEN
+                ),
+            )
+        })?;
+
+    let domain = SigningDomain::for_config(&state.config);
+    let mut db_tx = state.db.begin().await?;
+    let verified = crate::signatures::verify(
+        &mut db_tx,
+        &domain,
+        &REFERRAL_CODE_MESSAGE,
+        &request.message,
+        &request.signature,
+    )
+    .await?;
+    let wallet = format!("{:#x}", verified.wallet);
+
+    let is_creator: bool = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM raffles_all WHERE creator = $1) AS "exists!""#,
+        &wallet,
+    )
+    .fetch_one(&mut *db_tx)
+    .await?;
+    if !is_creator {
+        return Err(ApiError::NotCreator);
+    }
+
+    let inserted = sqlx::query!(
+        "INSERT INTO referral_codes (code, owner) VALUES ($1, $2)
+         ON CONFLICT (code) DO NOTHING
+         RETURNING created_at",
+        &code,
+        &wallet,
+    )
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    if let Some(row) = inserted {
+        db_tx.commit().await?;
+        return Ok((
+            StatusCode::CREATED,
+            Json(ReferralCodeResponse {
+                code,
+                owner: wallet,
+                created_at: row.created_at,
+            }),
+        ));
+    }
+
+    let existing = sqlx::query!(
+        "SELECT owner, created_at FROM referral_codes WHERE code = $1",
+        &code,
+    )
+    .fetch_one(&mut *db_tx)
+    .await?;
+    if existing.owner != wallet {
+        return Err(ApiError::Conflict(
+            "referral code is registered by another wallet",
+        ));
+    }
+    db_tx.commit().await?;
+    Ok((
+        StatusCode::OK,
+        Json(ReferralCodeResponse {
+            code,
+            owner: existing.owner,
+            created_at: existing.created_at,
+        }),
+    ))
+}
+
+/// GET /v1/referral-codes - Conversion stats per referral code, oldest code first
+async fn list_referral_codes(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<ReferralCodesQuery>,
+) -> Result<Json<Vec<ReferralCodeSummary>>, ApiError> {
+    let owner = params.owner.as_deref().map(str::to_ascii_lowercase);
+    let codes = fetch_referral_codes(
+        &state,
+        owner.as_deref(),
+        None,
+        params.limit.unwrap_or(DEFAULT_PAGE_LIMIT),
+        params.offset.unwrap_or(0),
+        params.format,
+    )
+    .await?;
+    Ok(Json(codes))
+}
+
+/// GET /v1/referral-codes/:code - Conversion stats of a referral code, per raffle
+async fn get_referral_code(
+    State(state): State<AppState>,
+    ValidatedPath(ReferralCodePath { code }): ValidatedPath<ReferralCodePath>,
+    ValidatedQuery(params): ValidatedQuery<FormatQuery>,
+) -> Result<Json<ReferralCodeSummary>, ApiError> {
+    let code = code.to_ascii_uppercase();
+    let mut summary = fetch_referral_codes(&state, None, Some(&code), 1, 0, params.format)
+        .await?
+        .pop()
+        .ok_or(ApiError::NotFound("referral code not found"))?;
+
+    let rows = sqlx::query(&format!(
+        "WITH codes AS (SELECT code, owner FROM referral_codes WHERE code = $1),
+         {REFERRAL_PURCHASES_SQL}
+         SELECT a.raffle_id, {REFERRAL_STATS_SQL}
+         FROM attributed a
+         LEFT JOIN first_purchases fp ON fp.tx_hash = a.tx_hash AND fp.log_index = a.log_index
+         GROUP BY a.raffle_id
+         ORDER BY a.raffle_id"
+    ))
+    .bind(&code)
+    .fetch_all(&state.db)
+    .await?;
+    let decimals = state.config.token_decimals;
+    let mut raffles = Vec::with_capacity(rows.len());
+    for row in rows {
+        raffles.push(ReferralRaffleStats {
+            raffle_id: row.try_get("raffle_id").map_err(row_error_to_api_error)?,
+            stats: ReferralStats::from_row(&row, params.format, decimals)
+                .map_err(row_error_to_api_error)?,
+        });
+    }
+    summary.raffles = Some(raffles);
+    Ok(Json(summary))
+}
+
+/// Reads a page of referral codes, optionally of one owner or one code, with stats
+async fn fetch_referral_codes(
+    state: &AppState,
+    owner: Option<&str>,
+    code: Option<&str>,
+    limit: i64,
+    offset: i64,
+    format: AmountFormat,
+) -> Result<Vec<ReferralCodeSummary>, ApiError> {
+    let rows = sqlx::query(&format!(
+        "WITH codes AS (
+            SELECT code, owner, created_at
+            FROM referral_codes
+            WHERE ($1::text IS NULL OR owner = $1) AND ($2::text IS NULL OR code = $2)
+            ORDER BY created_at, code
+            LIMIT $3 OFFSET $4
+         ),
+         {REFERRAL_PURCHASES_SQL}
+         SELECT c.code, c.owner, c.created_at, {REFERRAL_STATS_SQL}
+         FROM codes c
+         LEFT JOIN attributed a ON a.code = c.code
+         LEFT JOIN first_purchases fp ON fp.tx_hash = a.tx_hash AND fp.log_index = a.log_index
+         GROUP BY c.code, c.owner, c.created_at
+         ORDER BY c.created_at, c.code"
+    ))
+    .bind(owner)
+    .bind(code)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
+
+    let decimals = state.config.token_decimals;
+    let mut codes = Vec::with_capacity(rows.len());
+    for row in rows {
+        codes.push(ReferralCodeSummary {
+            code: row.try_get("code").map_err(row_error_to_api_error)?,
+            owner: row.try_get("owner").map_err(row_error_to_api_error)?,
+            created_at: row.try_get("created_at").map_err(row_error_to_api_error)?,
+            stats: ReferralStats::from_row(&row, format, decimals)
+                .map_err(row_error_to_api_error)?,
+            raffles: None,
+        });
+    }
+    Ok(codes)
+}
+
+/// GET /v1/analytics/overlap - Buyers and ticket volume two raffles share
+///
+/// Shared buyers are the `INTERSECT` of the raffles' buyer sets; ticket volume is
+/// summed per raffle over those buyers. Archived raffles are included.
+async fn get_buyer_overlap(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<OverlapQuery>,
+) -> Result<Json<BuyerOverlap>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let found: i64 = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM raffles_all WHERE raffle_id IN ($1, $2)"#,
+        params.raffle_a,
+        params.raffle_b,
+    )
+    .fetch_one(&state.db)
+    .await?;
+    if found < 2 {
+        return Err(ApiError::RaffleNotFound);
+    }
+
+    let summary = sqlx::query!(
+        r#"WITH a AS (
+                SELECT buyer, SUM(count)::bigint AS tickets
+                FROM purchases_all WHERE raffle_id = $1 GROUP BY buyer
+            ),
+            b AS (
+                SELECT buyer, SUM(count)::bigint AS tickets
+                FROM purchases_all WHERE raffle_id = $2 GROUP BY buyer
+            ),
+            shared AS (SELECT buyer FROM a INTERSECT SELECT buyer FROM b)
+         SELECT
+            (SELECT COUNT(*) FROM a) AS "buyers_a!",
+            (SELECT COUNT(*) FROM b) AS "buyers_b!",
+            (SELECT COUNT(*) FROM shared) AS "shared_buyers!",
+            (SELECT COUNT(*) FROM (SELECT buyer FROM a UNION SELECT buyer FROM b) u)
+                AS "total_buyers!",
+            (SELECT COALESCE(SUM(tickets), 0)::bigint FROM a) AS "tickets_a!",
+            (SELECT COALESCE(SUM(tickets), 0)::bigint FROM b) AS "tickets_b!",
+            (SELECT COALESCE(SUM(tickets), 0)::bigint FROM a JOIN shared USING (buyer))
+                AS "shared_tickets_a!",
+            (SELECT COALESCE(SUM(tickets), 0)::bigint FROM b JOIN shared USING (buyer))
+                AS "shared_tickets_b!""#,
+        params.raffle_a,
+        params.raffle_b,
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let participants = sqlx::query!(
+        r#"WITH a AS (
+                SELECT buyer, SUM(count)::bigint AS tickets
+                FROM purchases_all WHERE raffle_id = $1 GROUP BY buyer
+            ),
+            b AS (
+                SELECT buyer, SUM(count)::bigint AS tickets
+                FROM purchases_all WHERE raffle_id = $2 GROUP BY buyer
+            )
+         SELECT a.buyer AS "buyer!", a.tickets AS "tickets_a!", b.tickets AS "tickets_b!"
+         FROM a JOIN b USING (buyer)
+         ORDER BY a.tickets + b.tickets DESC, a.buyer ASC
+         LIMIT $3 OFFSET $4"#,
+        params.raffle_a,
+        params.raffle_b,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|row| SharedBuyer {
+        buyer: row.buyer,
+        tickets_a: row.tickets_a,
+        tickets_b: row.tickets_b,
+    })
+    .collect();
+
+    Ok(Json(BuyerOverlap {
+        raffle_a: OverlapSide::new(
+            params.raffle_a,
+            summary.buyers_a,
+            summary.tickets_a,
+            summary.shared_tickets_a,
+        ),
+        raffle_b: OverlapSide::new(
+            params.raffle_b,
+            summary.buyers_b,
+            summary.tickets_b,
+            summary.shared_tickets_b,
+        ),
+        shared_buyers: summary.shared_buyers,
+        total_buyers: summary.total_buyers,
+        jaccard_index: (summary.total_buyers > 0).then(|| {
+            (summary.shared_buyers as f64 * 10_000.0 / summary.total_buyers as f64).round()
+                / 10_000.0
+        }),
+        participants,
+    }))
+}
+
+/// GET /v1/audit/fairness - Transparency report over all finalized raffles
+///
+/// Recomputes every winning index from the stored randomness and compares where the
+/// winners fell in their ticket ranges with a uniform draw (chi-square test).
+async fn get_fairness_audit(
+    State(state): State<AppState>,
+) -> Result<Json<FairnessReport>, ApiError> {
+    let rows = sqlx::query!(
+        r#"SELECT raffle_id AS "raffle_id!", randomness, total_tickets AS "total_tickets!", winning_index
+         FROM raffles_all
+         WHERE status = 'FINALIZED'
+         ORDER BY raffle_id"#,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut verified = 0;
+    let mut mismatches = Vec::new();
+    let mut unverifiable = Vec::new();
+    let mut observed = [0i64; FAIRNESS_BUCKETS];
+    let mut expected = [0f64; FAIRNESS_BUCKETS];
+    for row in &rows {
+        let raffle_id: i64 = row.raffle_id;
+        let randomness: Option<String> = row.randomness.clone();
+        let total_tickets: i64 = row.total_tickets;
+        let winning_index: Option<i64> = row.winning_index;
+
+        let (Some(randomness), Some(winning_index)) = (randomness, winning_index) else {
+            unverifiable.push(raffle_id);
+            continue;
+        };
+        let Some(recomputed_index) = compute_winning_index(&randomness, total_tickets) else {
+            unverifiable.push(raffle_id);
+            continue;
+        };
+        if recomputed_index != winning_index {
+            mismatches.push(FairnessMismatch {
+                raffle_id,
+                randomness,
+                total_tickets,
+                winning_index,
+                recomputed_index,
+            });
+            continue;
+        }
+        verified += 1;
+
+        // Bucket b holds indices [ceil(b*T/n), ceil((b+1)*T/n)), so a raffle with fewer
+        // tickets than buckets leaves some of them empty
+        // compute_winning_index guarantees 0 <= winning_index < total_tickets
+        let (index, total, buckets) = (
+            winning_index as u64,
+            total_tickets as u64,
+            FAIRNESS_BUCKETS as u64,
+        );
+        observed[(index * buckets / total) as usize] += 1;
+        for (bucket, expected) in expected.iter_mut().enumerate() {
+            let bucket = bucket as u64;
+            let start = (bucket * total).div_ceil(buckets);
+            let end = ((bucket + 1) * total).div_ceil(buckets);
+            *expected += (end - start) as f64 / total as f64;
+        }
+    }
+
+    Ok(Json(FairnessReport {
+        generated_at: Utc::now(),
+        finalized_raffles: rows.len() as i64,
+        verified,
+        mismatches,
+        unverifiable,
+        distribution: winning_index_distribution(&observed, &expected),
+    }))
+}
+
+/// GET /v1/audit/randomness - Sanity tests over the randomness delivered to raffles
+async fn get_randomness_audit(
+    State(state): State<AppState>,
+) -> Result<Json<RandomnessReport>, ApiError> {
+    analytics::randomness_report(&state.db)
+        .await
+        .map(Json)
+        .map_err(ApiError::from)
+}
+
+/// Chi-square goodness of fit of the observed bucket counts
+///
+/// Buckets no raffle could land in are left out of the test.
+fn winning_index_distribution(observed: &[i64], expected: &[f64]) -> WinningIndexDistribution {
+    let count = observed.len() as f64;
+    let buckets = observed
+        .iter()
+        .zip(expected)
+        .enumerate()
+        .map(|(bucket, (&observed, &expected))| FairnessBucket {
+            from: bucket as f64 / count,
+            to: (bucket + 1) as f64 / count,
+            observed,
+            expected,
+        })
+        .collect::<Vec<_>>();
+
+    let tested = buckets
+        .iter()
+        .filter(|bucket| bucket.expected > 0.0)
+        .collect::<Vec<_>>();
+    let degrees_of_freedom = tested.len().saturating_sub(1);
+    let chi_square = (degrees_of_freedom > 0).then(|| {
+        tested
+            .iter()
+            .map(|bucket| (bucket.observed as f64 - bucket.expected).powi(2) / bucket.expected)
+            .sum::<f64>()
+    });
+    let p_value = chi_square
+        .filter(|_| {
+            tested
+                .iter()
+                .all(|bucket| bucket.expected >= analytics::MIN_EXPECTED_PER_BUCKET)
+        })
+        .map(|chi_square| analytics::chi_square_p_value(chi_square, degrees_of_freedom as f64));
+
+    WinningIndexDistribution {
+        buckets,
+        chi_square,
+        degrees_of_freedom,
+        p_value,
+    }
+}
+
+/// GET /v1/randomness/requests - List randomness requests from DrandRandomnessProvider
+async fn list_randomness_requests(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<RandomnessRequestQuery>,
+) -> Result<Json<Vec<RandomnessRequestResponse>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    // raffle_address takes precedence over raffle_id
+    let raffle_id = params.raffle_id.filter(|_| params.raffle_address.is_none());
+    let rows = sqlx::query!(
+        r#"SELECT id, request_id::text AS request_id, raffle_id, raffle_address,
+            provider_address, tx_hash, log_index, block_number, block_hash,
+            block_is_finalized(block_number) AS "finalized!", created_at
+         FROM randomness_requests
+         WHERE ($1::text IS NULL OR LOWER(raffle_address) = LOWER($1))
+           AND ($2::bigint IS NULL OR raffle_id = $2)
+         ORDER BY id DESC
+         LIMIT $3 OFFSET $4"#,
+        params.raffle_address,
+        raffle_id,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut requests = Vec::with_capacity(rows.len());
+    for row in rows {
+        let tx_hash: String = row.tx_hash;
+        let request_id: String = row.request_id;
+        requests.push(RandomnessRequestResponse {
+            id: row.id,
+            request_id_hex: params.format.render_hex(&request_id),
+            request_id,
+            raffle_id: row.raffle_id,
+            raffle_address: row.raffle_address,
+            provider_address: row.provider_address,
+            tx_hash: tx_hash.clone(),
+            tx_url: build_tx_url(&state.config.explorer_base_url, &Some(tx_hash)),
+            log_index: row.log_index,
+            block_number: row.block_number,
+            block_hash: row.block_hash,
+            finalized: row.finalized,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(Json(requests))
+}
+
+/// GET /v1/randomness/requests/:request_id - Get a specific randomness request
+async fn get_randomness_request(
+    State(state): State<AppState>,
+    ValidatedPath(RandomnessRequestPath { request_id }): ValidatedPath<RandomnessRequestPath>,
+    ValidatedQuery(params): ValidatedQuery<FormatQuery>,
+) -> Result<Json<RandomnessRequestResponse>, ApiError> {
+    let row = sqlx::query!(
+        r#"SELECT id, request_id::text AS request_id, raffle_id, raffle_address,
+            provider_address, tx_hash, log_index, block_number, block_hash,
+            block_is_finalized(block_number) AS "finalized!", created_at
+         FROM randomness_requests
+         WHERE request_id::text = $1
+         LIMIT 1"#,
+        &request_id,
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    let Some(row) = row else {
+        return Err(ApiError::NotFound("randomness request not found"));
+    };
+
+    let tx_hash: String = row.tx_hash;
+    let request_id: String = row.request_id;
+    Ok(Json(RandomnessRequestResponse {
+        id: row.id,
+        request_id_hex: params.format.render_hex(&request_id),
+        request_id,
+        raffle_id: row.raffle_id,
+        raffle_address: row.raffle_address,
+        provider_address: row.provider_address,
+        tx_hash: tx_hash.clone(),
+        tx_url: build_tx_url(&state.config.explorer_base_url, &Some(tx_hash)),
+        log_index: row.log_index,
+        block_number: row.block_number,
+        block_hash: row.block_hash,
+        finalized: row.finalized,
+        created_at: row.created_at,
+    }))
+}
+
+/// GET /v1/randomness/fulfillments - List randomness fulfillments from DrandRandomnessProvider
+async fn list_randomness_fulfillments(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<RandomnessRequestQuery>,
+) -> Result<Json<Vec<RandomnessFulfillmentResponse>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    // raffle_address takes precedence over raffle_id
+    let raffle_id = params.raffle_id.filter(|_| params.raffle_address.is_none());
+    let rows = sqlx::query!(
+        r#"SELECT id, request_id::text AS request_id, raffle_id, randomness::text AS randomness,
+            proof, raffle_address, provider_address, tx_hash, log_index, block_number, block_hash,
+            block_is_finalized(block_number) AS "finalized!", created_at
+         FROM randomness_fulfillments
+         WHERE ($1::text IS NULL OR LOWER(raffle_address) = LOWER($1))
+           AND ($2::bigint IS NULL OR raffle_id = $2)
+         ORDER BY id DESC
+         LIMIT $3 OFFSET $4"#,
+        params.raffle_address,
+        raffle_id,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut fulfillments = Vec::with_capacity(rows.len());
+    for row in rows {
+        let tx_hash: String = row.tx_hash;
+        let request_id: String = row.request_id;
+        let randomness: String = row.randomness;
+        fulfillments.push(RandomnessFulfillmentResponse {
+            id: row.id,
+            request_id_hex: params.format.render_hex(&request_id),
+            request_id,
+            raffle_id: row.raffle_id,
+            randomness_hex: params.format.render_hex(&randomness),
+            randomness,
+            proof: row.proof,
+            raffle_address: row.raffle_address,
+            provider_address: row.provider_address,
+            tx_hash: tx_hash.clone(),
+            tx_url: build_tx_url(&state.config.explorer_base_url, &Some(tx_hash)),
+            log_index: row.log_index,
+            block_number: row.block_number,
+            block_hash: row.block_hash,
+            finalized: row.finalized,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(Json(fulfillments))
+}
+
+/// POST /v1/refund-reminders - Subscribe a wallet to refund reminders
+///
+/// Takes a signed `RefundReminderSubscription` message. The wallet's webhook is
+/// notified whenever a raffle it bought tickets in enters REFUNDING or is canceled (see
+/// [`crate::notify`]); raffles already refunding or canceled with an unclaimed refund
+/// are queued immediately. Signing an empty `webhookUrl` unsubscribes.
+async fn subscribe_refund_reminders(
+    State(state): State<AppState>,
+    Json(request): Json<SignedRequest>,
+) -> Result<Json<RefundReminderResponse>, ApiError> {
+    // Check the URL before verifying, so a rejected request doesn't burn the nonce
+    let webhook_url = request
+        .message
+        .get("webhookUrl")
+        .and_then(|url| url.as_str())
+        .filter(|url| !url.is_empty())
+        .map(str::to_string);
+    if let Some(url) = &webhook_url {
+        validate_webhook_url(url)?;
+    }
+
+    let domain = SigningDomain::for_config(&state.config);
+    let mut db_tx = state.db.begin().await?;
+    let verified = crate::signatures::verify(
+        &mut db_tx,
+        &domain,
+        &REFUND_REMINDER_MESSAGE,
+        &request.message,
+        &request.signature,
+    )
+    .await?;
+    let wallet = format!("{:#x}", verified.wallet);
+
+    let queued_reminders = match &webhook_url {
+        Some(url) => {
+            sqlx::query!(
+                "INSERT INTO refund_reminder_subscriptions (wallet, webhook_url)
+                 VALUES ($1, $2)
+                 ON CONFLICT (wallet) DO UPDATE
+                 SET webhook_url = EXCLUDED.webhook_url, updated_at = now()",
+                &wallet,
+                url,
+            )
+            .execute(&mut *db_tx)
+            .await?;
+
+            sqlx::query!(
+                "INSERT INTO refund_reminders (wallet, raffle_id)
+                 SELECT DISTINCT p.buyer, p.raffle_id
+                 FROM purchases p
+                 JOIN raffles r ON r.raffle_id = p.raffle_id
+                 WHERE p.buyer = $1
+                   AND r.status IN ('REFUNDING', 'CANCELED')
+                   AND NOT EXISTS (
+                       SELECT 1 FROM refunds f WHERE f.raffle_id = p.raffle_id AND f.buyer = p.buyer
+                   )
+                 ON CONFLICT (wallet, raffle_id) DO NOTHING",
+                &wallet,
+            )
+            .execute(&mut *db_tx)
+            .await?
+            .rows_affected()
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM refund_reminder_subscriptions WHERE wallet = $1",
+                &wallet,
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            sqlx::query!(
+                "DELETE FROM refund_reminders WHERE wallet = $1 AND status = 'pending'",
+                &wallet,
+            )
+            .execute(&mut *db_tx)
+            .await?;
+            0
+        }
+    };
+    db_tx.commit().await?;
+
+    Ok(Json(RefundReminderResponse {
+        wallet,
+        subscribed: webhook_url.is_some(),
+        webhook_url,
+        queued_reminders,
+    }))
+}
+
+/// GET /v1/admin/keeper/txs - Keeper transaction submissions, newest first
+async fn list_keeper_txs(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<KeeperTxQuery>,
+) -> Result<Json<Vec<KeeperTxResponse>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let rows = sqlx::query!(
+        r#"SELECT id, raffle_id, action, sender, nonce::text AS "nonce!", tx_hash,
+            gas_price::text AS "gas_price!", gas_limit::text AS "gas_limit!", attempt, status,
+            replaced_by, block_number, error, submitted_at, updated_at
+         FROM keeper_txs
+         WHERE ($1::text IS NULL OR status = $1)
+           AND ($2::bigint IS NULL OR raffle_id = $2)
+         ORDER BY id DESC
+         LIMIT $3 OFFSET $4"#,
+        params.status,
+        params.raffle_id,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut txs = Vec::with_capacity(rows.len());
+    for row in rows {
+        txs.push(KeeperTxResponse {
+            id: row.id,
+            raffle_id: row.raffle_id,
+            action: row.action,
+            sender: row.sender,
+            nonce: row.nonce,
+            tx_hash: row.tx_hash,
+            gas_price: row.gas_price,
+            gas_limit: row.gas_limit,
+            attempt: row.attempt,
+            status: row.status,
+            replaced_by: row.replaced_by,
+            block_number: row.block_number,
+            error: row.error,
+            submitted_at: row.submitted_at,
+            updated_at: row.updated_at,
+        });
+    }
+
+    Ok(Json(txs))
+}
+
+/// Response of `POST /v1/admin/indexer/pause` and `/resume`
+#[derive(Serialize)]
+struct IndexerPauseResponse {
+    paused: bool,
+    paused_at: Option<DateTime<Utc>>,
+    /// Last block fully processed; stays put while paused
+    indexed_block: Option<i64>,
+}
+
+/// POST /v1/admin/indexer/pause - Stop indexing while the API keeps serving
+///
+/// The batch in progress is finished first. The pause is persisted, so it holds
+/// across restarts until `POST /v1/admin/indexer/resume`. Pausing again keeps the
+/// original `paused_at`.
+async fn pause_indexer(
+    admin: AdminAuth<RequireOperator>,
+    State(state): State<AppState>,
+) -> Result<Json<IndexerPauseResponse>, ApiError> {
+    let response = set_indexer_paused(&state, true).await?;
+    tracing::warn!(paused_at = ?response.paused_at, by = admin.subject, "indexer paused by admin");
+    Ok(Json(response))
+}
+
+/// POST /v1/admin/indexer/resume - Continue indexing from the stored cursor
+async fn resume_indexer(
+    admin: AdminAuth<RequireOperator>,
+    State(state): State<AppState>,
+) -> Result<Json<IndexerPauseResponse>, ApiError> {
+    let response = set_indexer_paused(&state, false).await?;
+    tracing::info!(by = admin.subject, "indexer resumed by admin");
+    Ok(Json(response))
+}
+
+/// Persists the pause flag, then hands it to the indexer
+async fn set_indexer_paused(
+    state: &AppState,
+    paused: bool,
+) -> Result<IndexerPauseResponse, ApiError> {
+    let row = sqlx::query!(
+        "UPDATE indexer_state
+         SET paused_at = CASE WHEN $1 THEN COALESCE(paused_at, now()) END
+         WHERE id = 1
+         RETURNING paused_at, last_processed_block",
+        paused,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::Internal("indexer_state row missing"))?;
+    state.indexer.set_paused(paused);
+
+    let indexed_block: i64 = row.last_processed_block;
+    Ok(IndexerPauseResponse {
+        paused,
+        paused_at: row.paused_at,
+        indexed_block: (indexed_block > 0).then_some(indexed_block),
+    })
+}
+
+/// PUT /v1/admin/indexer/cursor - Move the indexer's cursor
+///
+/// Only while the indexer is paused, so no batch commits over the change, and never
+/// beyond the chain head it last saw. Blocks after the cursor are indexed again on
+/// resume (rows already stored are kept); blocks skipped forward are never indexed.
+/// The change, and the optional snapshot, is one transaction recorded in
+/// `cursor_changes`; it also drops the stored responses of terminal raffles (see
+/// [`snapshots`]).
+async fn set_indexer_cursor(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    Json(request): Json<SetCursorRequest>,
+) -> Result<Json<SetCursorResponse>, ApiError> {
+    if request.block < 0 {
+        return Err(ApiError::invalid_parameter(
+            "block",
+            "block must not be negative",
+        ));
+    }
+    if !state.indexer.is_paused() {
+        return Err(ApiError::Conflict(
+            "pause the indexer (POST /v1/admin/indexer/pause) before moving its cursor",
+        ));
+    }
+    let Some(head_block) = state.indexer.head_block() else {
+        return Err(ApiError::Conflict(
+            "the indexer hasn't seen the chain head yet",
+        ));
+    };
+    if request.block as u64 > head_block {
+        return Err(ApiError::invalid_parameter(
+            "block",
+            format!("block must not be beyond the chain head ({head_block})"),
+        ));
+    }
+
+    let mut db_tx = state.db.begin().await?;
+    // Locks the cursor against the orphan check until the change commits
+    let previous_block: i64 = sqlx::query_scalar!(
+        "SELECT last_processed_block FROM indexer_state WHERE id = 1 FOR UPDATE",
+    )
+    .fetch_optional(&mut *db_tx)
+    .await?
+    .ok_or(ApiError::Internal("indexer_state row missing"))?;
+    let change_id: i64 = sqlx::query_scalar!(
+        "INSERT INTO cursor_changes (from_block, to_block, snapshot)
+         VALUES ($1, $2, $3)
+         RETURNING id",
+        previous_block,
+        request.block,
+        request.snapshot,
+    )
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let mut snapshot_tables = Vec::new();
+    if request.snapshot {
+        for table in CURSOR_SNAPSHOT_TABLES {
+            let copy = format!("cursor_snapshots.{table}_{change_id}");
+            sqlx::query(&format!("CREATE TABLE {copy} AS TABLE {table}"))
+                .execute(&mut *db_tx)
+                .await?;
+            snapshot_tables.push(copy);
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE indexer_state SET last_processed_block = $1, updated_at = now() WHERE id = 1",
+        request.block,
+    )
+    .execute(&mut *db_tx)
+    .await?;
+    snapshots::drop_all(&mut db_tx).await?;
+    db_tx.commit().await?;
+
+    tracing::warn!(
+        change_id,
+        previous_block,
+        block = request.block,
+        snapshot = request.snapshot,
+        by = admin.subject,
+        "indexer cursor moved by admin"
+    );
+    Ok(Json(SetCursorResponse {
+        change_id,
+        previous_block,
+        indexed_block: request.block,
+        snapshot_tables,
+    }))
+}
+
+/// GET /v1/admin/overview - One document for the operations dashboard
+///
+/// Job run times are read from what each job writes, so they survive restarts; jobs
+/// that write nothing (orphan check, mempool watcher) only report their task state.
+async fn get_admin_overview(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+) -> Result<Json<AdminOverview>, ApiError> {
+    let row = sqlx::query!(
+        "SELECT last_processed_block, finalized_block, paused_at, updated_at
+         FROM indexer_state WHERE id = 1",
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::Internal("indexer_state row missing"))?;
+    let indexed_block: i64 = row.last_processed_block;
+    let indexed_block = (indexed_block > 0).then_some(indexed_block);
+    let head_block = state.indexer.head_block();
+    let indexer = IndexerOverview {
+        state: indexer_state(&state.indexer, &state.indexer.rpc.snapshot()),
+        head_block,
+        indexed_block,
+        lag_blocks: head_block
+            .zip(indexed_block)
+            .map(|(head, indexed)| head.saturating_sub(indexed as u64)),
+        finalized_block: row.finalized_block,
+        paused_at: row.paused_at,
+        cursor_updated_at: row.updated_at,
+    };
+
+    let undecoded_events: i64 =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM events_raw WHERE NOT decoded"#)
+            .fetch_one(&state.db)
+            .await?;
+
+    let mut webhooks = Vec::with_capacity(WEBHOOK_QUEUES.len());
+    for queue in WEBHOOK_QUEUES {
+        let row = sqlx::query(&format!(
+            "SELECT
+                COUNT(*) FILTER (WHERE status = 'pending') AS pending,
+                COUNT(*) FILTER (WHERE status = 'pending' AND attempts > 0) AS retrying,
+                COUNT(*) FILTER (WHERE status = 'failed') AS failed,
+                MIN(created_at) FILTER (WHERE status = 'pending') AS oldest_pending_at,
+                MAX(delivered_at) AS last_delivered_at
+             FROM {queue}"
+        ))
+        .fetch_one(&state.db)
+        .await?;
+        webhooks.push(WebhookQueueOverview {
+            queue,
+            pending: row.try_get("pending").map_err(row_error_to_api_error)?,
+            retrying: row.try_get("retrying").map_err(row_error_to_api_error)?,
+            failed: row.try_get("failed").map_err(row_error_to_api_error)?,
+            oldest_pending_at: row
+                .try_get("oldest_pending_at")
+                .map_err(row_error_to_api_error)?,
+            last_delivered_at: row
+                .try_get("last_delivered_at")
+                .map_err(row_error_to_api_error)?,
+        });
+    }
+
+    let last_runs = sqlx::query!(
+        r#"SELECT 'indexer' AS "job!", updated_at AS last_run_at FROM indexer_state WHERE id = 1
+         UNION ALL SELECT 'keeper', MAX(updated_at) FROM keeper_txs
+         UNION ALL SELECT 'notify', MAX(delivered_at) FROM (
+             SELECT delivered_at FROM refund_reminders
+             UNION ALL SELECT delivered_at FROM whale_alerts
+             UNION ALL SELECT delivered_at FROM digest_deliveries
+             UNION ALL SELECT delivered_at FROM stuck_alerts
+         ) d
+         UNION ALL SELECT 'digest', MAX(created_at) FROM digests
+         UNION ALL SELECT 'export', MAX(created_at) FROM export_snapshots
+         UNION ALL SELECT 'payouts', MAX(confirmed_at) FROM payouts"#,
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let jobs = state
+        .tasks
+        .snapshot()
+        .into_iter()
+        .map(|task| JobOverview {
+            last_run_at: last_runs
+                .iter()
+                .find(|run| run.job == task.name)
+                .and_then(|run| run.last_run_at),
+            task,
+        })
+        .collect();
+
+    let tables = sqlx::query!(
+        r#"SELECT c.relname AS table_name,
+            c.reltuples::bigint AS "estimated_rows!",
+            pg_total_relation_size(c.oid) AS "total_bytes!"
+         FROM pg_class c
+         JOIN pg_namespace n ON n.oid = c.relnamespace
+         WHERE c.relkind IN ('r', 'p') AND n.nspname = current_schema()
+         ORDER BY 3 DESC, 1"#,
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|row| TableOverview {
+        table: row.table_name,
+        estimated_rows: row.estimated_rows,
+        total_bytes: row.total_bytes,
+    })
+    .collect();
+
+    Ok(Json(AdminOverview {
+        indexer,
+        undecoded_events,
+        webhooks,
+        jobs,
+        tables,
+        pools: PoolsOverview {
+            api: PoolOverview::of(&state.db),
+            indexer: PoolOverview::of(&state.indexer_db),
+        },
+    }))
+}
+
+/// GET /v1/admin/anomalies - Lifecycle events that didn't fit the raffle's status
+///
+/// See [`crate::indexer`] for the transitions checked.
+async fn list_anomalies(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AnomalyQuery>,
+) -> Result<Json<Vec<AnomalyResponse>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let rows = sqlx::query!(
+        "SELECT id, raffle_id, event, from_status, to_status, tx_hash, log_index,
+            block_number, block_hash, created_at
+         FROM anomalies
+         WHERE ($1::bigint IS NULL OR raffle_id = $1)
+         ORDER BY id DESC
+         LIMIT $2 OFFSET $3",
+        params.raffle_id,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut anomalies = Vec::with_capacity(rows.len());
+    for row in rows {
+        anomalies.push(AnomalyResponse {
+            id: row.id,
+            raffle_id: row.raffle_id,
+            event: row.event,
+            from_status: row.from_status,
+            to_status: row.to_status,
+            tx_hash: row.tx_hash,
+            log_index: row.log_index,
+            block_number: row.block_number,
+            block_hash: row.block_hash,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(Json(anomalies))
+}
+
+/// GET /v1/admin/blocklist - Blocked addresses, most recently blocked first
+async fn list_blocked_addresses(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<BlocklistQuery>,
+) -> Result<Json<Vec<BlockedAddress>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let rows = sqlx::query!(
+        "SELECT address, reason, blocked_by, created_at
+         FROM blocked_addresses
+         ORDER BY created_at DESC, address ASC
+         LIMIT $1 OFFSET $2",
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut addresses = Vec::with_capacity(rows.len());
+    for row in rows {
+        addresses.push(BlockedAddress {
+            address: row.address,
+            reason: row.reason,
+            blocked_by: row.blocked_by,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(Json(addresses))
+}
+
+/// PUT /v1/admin/blocklist/:address - Block an address, or update its reason
+///
+/// Cached raffle list pages keep its raffles until they expire
+/// (`RAFFLE_LIST_CACHE_TTL_MS`); purchases snapshots are dropped for their
+/// `buyer_blocked` flags.
+async fn block_address(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    ValidatedPath(BlockedAddressPath { address }): ValidatedPath<BlockedAddressPath>,
+    Json(request): Json<BlockAddressRequest>,
+) -> Result<Json<BlockedAddress>, ApiError> {
+    let reason = request.reason.trim();
+    if reason.is_empty() || reason.len() > MAX_BLOCK_REASON_LEN {
+        return Err(ApiError::invalid_parameter(
+            "reason",
+            format!("reason must be 1 to {MAX_BLOCK_REASON_LEN} characters"),
+        ));
+    }
+    let address = address.to_lowercase();
+
+    let created_at: DateTime<Utc> = sqlx::query_scalar!(
+        "INSERT INTO blocked_addresses (address, reason, blocked_by)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (address) DO UPDATE
+         SET reason = excluded.reason, blocked_by = excluded.blocked_by
+         RETURNING created_at",
+        &address,
+        reason,
+        &admin.subject,
+    )
+    .fetch_one(&state.db)
+    .await?;
+    snapshots::drop_purchases(&state.db).await?;
+    tracing::info!(address, by = admin.subject, "address blocked");
+
+    Ok(Json(BlockedAddress {
+        address,
+        reason: reason.to_string(),
+        blocked_by: admin.subject,
+        created_at,
+    }))
+}
+
+/// DELETE /v1/admin/blocklist/:address - Unblock an address
+async fn unblock_address(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    ValidatedPath(BlockedAddressPath { address }): ValidatedPath<BlockedAddressPath>,
+) -> Result<StatusCode, ApiError> {
+    let address = address.to_lowercase();
+    let removed = sqlx::query!("DELETE FROM blocked_addresses WHERE address = $1", &address,)
+        .execute(&state.db)
+        .await?
+        .rows_affected();
+    if removed == 0 {
+        return Err(ApiError::NotFound("address is not blocked"));
+    }
+    snapshots::drop_purchases(&state.db).await?;
+    tracing::info!(address, by = admin.subject, "address unblocked");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /v1/admin/api-keys - Mint an API key
+///
+/// The response is the only time the key is shown; only its hash is stored.
+async fn create_api_key(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<ApiKeyResponse>), ApiError> {
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > MAX_API_KEY_NAME_LEN {
+        return Err(ApiError::invalid_parameter(
+            "name",
+            format!("name must be 1 to {} characters", MAX_API_KEY_NAME_LEN),
+        ));
+    }
+    let daily_quota = request
+        .daily_quota
+        .unwrap_or(state.config.api_key_default_daily_quota);
+    if daily_quota <= 0 {
+        return Err(ApiError::invalid_parameter(
+            "daily_quota",
+            "daily_quota must be positive",
+        ));
+    }
+    let rate_limit_per_minute = request
+        .rate_limit_per_minute
+        .unwrap_or(state.config.api_key_default_rate_limit);
+    if rate_limit_per_minute <= 0 {
+        return Err(ApiError::invalid_parameter(
+            "rate_limit_per_minute",
+            "rate_limit_per_minute must be positive",
+        ));
+    }
+
+    let new_key = api_keys::generate_key().map_err(|err| {
+        tracing::error!(error = %err, "failed to generate API key");
+        ApiError::Internal("failed to generate API key")
+    })?;
+    let row = sqlx::query!(
+        "INSERT INTO api_keys (name, key_prefix, key_hash, daily_quota, rate_limit_per_minute)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, created_at",
+        name,
+        &new_key.key_prefix,
+        &new_key.key_hash,
+        daily_quota,
+        rate_limit_per_minute,
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let id: i64 = row.id;
+    tracing::info!(key_id = id, name, by = admin.subject, "API key minted");
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiKeyResponse {
+            id,
+            name: name.to_string(),
+            key: Some(new_key.key),
+            key_prefix: new_key.key_prefix,
+            daily_quota,
+            rate_limit_per_minute,
+            requests_today: 0,
+            created_at: row.created_at,
+            revoked_at: None,
+        }),
+    ))
+}
+
+/// POST /v1/admin/tokens - Issue an admin token scoped to a role
+///
+/// Tokens aren't stored; the response is the only copy. See [`crate::auth`].
+async fn create_admin_token(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    Json(request): Json<CreateAdminTokenRequest>,
+) -> Result<(StatusCode, Json<AdminTokenResponse>), ApiError> {
+    let Some(secret) = state.config.admin_jwt_secret.as_deref() else {
+        return Err(ApiError::FeatureDisabled(
+            "admin tokens are not configured (ADMIN_JWT_SECRET)",
+        ));
+    };
+    let subject = request.subject.trim();
+    if subject.is_empty() || subject.len() > MAX_TOKEN_SUBJECT_LEN {
+        return Err(ApiError::invalid_parameter(
+            "subject",
+            format!("subject must be 1 to {MAX_TOKEN_SUBJECT_LEN} characters"),
+        ));
+    }
+    let max_ttl = state.config.admin_jwt_max_ttl_secs;
+    let ttl_secs = request.ttl_secs.unwrap_or(max_ttl);
+    if ttl_secs == 0 || ttl_secs > max_ttl {
+        return Err(ApiError::invalid_parameter(
+            "ttl_secs",
+            format!("ttl_secs must be between 1 and {max_ttl}"),
+        ));
+    }
+
+    // Whole seconds, as in the token
+    let issued_at = Utc::now().trunc_subsecs(0);
+    let expires_at = issued_at + chrono::Duration::seconds(ttl_secs as i64);
+    let claims = auth::Claims {
+        sub: subject.to_string(),
+        role: request.role,
+        iat: issued_at.timestamp(),
+        exp: expires_at.timestamp(),
+    };
+    let token = auth::issue(secret.as_bytes(), &claims);
+    tracing::info!(
+        subject,
+        role = request.role.as_str(),
+        ttl_secs,
+        by = admin.subject,
+        "admin token issued"
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(AdminTokenResponse {
+            token,
+            subject: claims.sub,
+            role: claims.role,
+            issued_at,
+            expires_at,
+        }),
+    ))
+}
+
+/// GET /v1/admin/audit - Recorded admin API calls, newest first
+///
+/// Includes refused calls and this endpoint's own requests.
+async fn list_admin_audit(
+    _admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AdminAuditQuery>,
+) -> Result<Json<Vec<AdminAuditEntry>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let rows = sqlx::query!(
+        "SELECT id, actor, role, action, path, parameters, status, error_code, created_at
+         FROM admin_audit
+         WHERE ($1::text IS NULL OR actor = $1)
+           AND ($2::text IS NULL OR action = $2)
+         ORDER BY id DESC
+         LIMIT $3 OFFSET $4",
+        params.actor.as_deref(),
+        params.action.as_deref(),
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        entries.push(AdminAuditEntry {
+            id: row.id,
+            actor: row.actor,
+            role: row.role,
+            action: row.action,
+            path: row.path,
+            parameters: row.parameters,
+            status: row.status,
+            error_code: row.error_code,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(Json(entries))
+}
+
+/// GET /v1/admin/webhooks - Registered webhooks and the signing secret in use
+///
+/// Lists the configured whale and digest webhooks, then refund reminder subscriptions,
+/// newest first, each with its queue backlog.
+async fn list_webhooks(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+) -> Result<Json<WebhooksResponse>, ApiError> {
+    let config = &state.config;
+    let secrets =
+        sqlx::query!("SELECT id, created_at FROM webhook_signing_secrets ORDER BY id DESC LIMIT 2")
+            .fetch_all(&state.db)
+            .await?;
+    let signing = match secrets.first() {
+        Some(newest) => {
+            let has_previous = secrets.len() > 1 || config.webhook_signing_secret.is_some();
+            let overlap = chrono::Duration::seconds(config.webhook_secret_overlap_secs as i64);
+            WebhookSigning {
+                source: "rotated",
+                secret_id: Some(newest.id),
+                rotated_at: Some(newest.created_at),
+                previous_valid_until: Some(newest.created_at + overlap)
+                    .filter(|until| has_previous && *until > Utc::now()),
+            }
+        }
+        None => WebhookSigning {
+            source: if config.webhook_signing_secret.is_some() {
+                "config"
+            } else {
+                "none"
+            },
+            secret_id: None,
+            rotated_at: None,
+            previous_valid_until: None,
+        },
+    };
+
+    let mut webhooks = Vec::new();
+    if let Some(url) = &config.whale_webhook_url {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+                COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+                MAX(delivered_at) AS last_delivered_at
+             FROM whale_alerts"#
+        )
+        .fetch_one(&state.db)
+        .await?;
+        webhooks.push(RegisteredWebhook {
+            kind: "whale_purchase",
+            queue: "whale_alerts",
+            url: redact_webhook_url(url),
+            wallet: None,
+            subscribed_at: None,
+            pending: row.pending,
+            failed: row.failed,
+            last_delivered_at: row.last_delivered_at,
+        });
+    }
+    if let Some(url) = &config.digest_webhook_url {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+                COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+                MAX(delivered_at) AS last_delivered_at
+             FROM digest_deliveries"#
+        )
+        .fetch_one(&state.db)
+        .await?;
+        webhooks.push(RegisteredWebhook {
+            kind: "daily_digest",
+            queue: "digest_deliveries",
+            url: redact_webhook_url(url),
+            wallet: None,
+            subscribed_at: None,
+            pending: row.pending,
+            failed: row.failed,
+            last_delivered_at: row.last_delivered_at,
+        });
+    }
+    if let Some(url) = &config.stuck_webhook_url {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+                COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+                MAX(delivered_at) AS last_delivered_at
+             FROM stuck_alerts"#
+        )
+        .fetch_one(&state.db)
+        .await?;
+        webhooks.push(RegisteredWebhook {
+            kind: "raffle_stuck",
+            queue: "stuck_alerts",
+            url: redact_webhook_url(url),
+            wallet: None,
+            subscribed_at: None,
+            pending: row.pending,
+            failed: row.failed,
+            last_delivered_at: row.last_delivered_at,
+        });
+    }
+
+    let subscriptions = sqlx::query!(
+        r#"SELECT s.wallet, s.webhook_url, s.created_at,
+            COUNT(r.id) FILTER (WHERE r.status = 'pending') AS "pending!",
+            COUNT(r.id) FILTER (WHERE r.status = 'failed') AS "failed!",
+            MAX(r.delivered_at) AS last_delivered_at
+         FROM refund_reminder_subscriptions s
+         LEFT JOIN refund_reminders r ON r.wallet = s.wallet
+         GROUP BY s.wallet
+         ORDER BY s.created_at DESC, s.wallet"#
+    )
+    .fetch_all(&state.db)
+    .await?;
+    for row in subscriptions {
+        webhooks.push(RegisteredWebhook {
+            kind: "refund_available",
+            queue: "refund_reminders",
+            url: redact_webhook_url(&row.webhook_url),
+            wallet: Some(row.wallet),
+            subscribed_at: Some(row.created_at),
+            pending: row.pending,
+            failed: row.failed,
+            last_delivered_at: row.last_delivered_at,
+        });
+    }
+
+    Ok(Json(WebhooksResponse { signing, webhooks }))
+}
+
+/// GET /v1/admin/webhooks/attempts - Webhook delivery attempts, newest first
+async fn list_webhook_attempts(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<WebhookAttemptsQuery>,
+) -> Result<Json<Vec<WebhookAttempt>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let attempts = sqlx::query_as!(
+        WebhookAttempt,
+        "SELECT id, queue, notification_id, attempt, response_status, error, duration_ms,
+            attempted_at
+         FROM webhook_attempts
+         WHERE ($1::text IS NULL OR queue = $1)
+           AND ($2::bigint IS NULL OR notification_id = $2)
+           AND ($3::boolean IS NULL OR (error IS NOT NULL) = $3)
+         ORDER BY id DESC
+         LIMIT $4 OFFSET $5",
+        params.queue.as_deref(),
+        params.notification_id,
+        params.failed,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(attempts))
+}
+
+/// POST /v1/admin/webhooks/replay - Queue failed webhook notifications again
+///
+/// Replayed notifications get a fresh `WEBHOOK_MAX_ATTEMPTS` and are picked up by the
+/// next notifier cycle.
+async fn replay_webhooks(
+    admin: AdminAuth<RequireOperator>,
+    State(state): State<AppState>,
+    Json(request): Json<ReplayWebhooksRequest>,
+) -> Result<Json<ReplayWebhooksResponse>, ApiError> {
+    let queue = validate_webhook_queue(Some(&request.queue))?.unwrap_or_default();
+    if let Some(ids) = &request.ids {
+        if ids.is_empty() || ids.len() > MAX_PAGE_LIMIT as usize {
+            return Err(ApiError::invalid_parameter(
+                "ids",
+                format!("ids must list 1 to {MAX_PAGE_LIMIT} notifications"),
+            ));
+        }
+        for id in ids {
+            validate_id("ids", Some(*id))?;
+        }
+    }
+
+    // The queue is one of WEBHOOK_QUEUES, so it is safe to interpolate
+    let mut replayed: Vec<i64> = sqlx::query_scalar(&format!(
+        "UPDATE {queue}
+         SET status = 'pending', attempts = 0, next_attempt_at = now(), last_error = NULL
+         WHERE status = 'failed' AND ($1::bigint[] IS NULL OR id = ANY($1))
+         RETURNING id"
+    ))
+    .bind(request.ids.as_deref())
+    .fetch_all(&state.db)
+    .await?;
+    replayed.sort_unstable();
+
+    tracing::info!(
+        queue,
+        count = replayed.len(),
+        by = admin.subject,
+        "webhook notifications replayed"
+    );
+    Ok(Json(ReplayWebhooksResponse {
+        queue: queue.to_string(),
+        replayed,
+    }))
+}
+
+/// POST /v1/admin/webhooks/signing-secret - Rotate the webhook signing secret
+///
+/// The response is the only time the secret is shown. The secret it replaces keeps
+/// signing alongside it for `WEBHOOK_SECRET_OVERLAP_SECS`.
+async fn rotate_webhook_signing_secret(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<SigningSecretResponse>), ApiError> {
+    let secret = notify::generate_signing_secret().map_err(|err| {
+        tracing::error!(error = %err, "failed to generate webhook signing secret");
+        ApiError::Internal("failed to generate signing secret")
+    })?;
+    let row = sqlx::query!(
+        r#"INSERT INTO webhook_signing_secrets (secret, created_by)
+         VALUES ($1, $2)
+         RETURNING id, created_at,
+            (SELECT COUNT(*) FROM webhook_signing_secrets) AS "previous!""#,
+        &secret,
+        &admin.subject,
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let has_previous = row.previous > 0 || state.config.webhook_signing_secret.is_some();
+    let overlap = chrono::Duration::seconds(state.config.webhook_secret_overlap_secs as i64);
+    tracing::info!(
+        secret_id = row.id,
+        by = admin.subject,
+        "webhook signing secret rotated"
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(SigningSecretResponse {
+            id: row.id,
+            secret,
+            created_at: row.created_at,
+            previous_valid_until: has_previous.then(|| row.created_at + overlap),
+        }),
+    ))
+}
+
+/// GET /v1/admin/api-keys - API keys with today's usage, newest first
+async fn list_api_keys(
+    _admin: AdminAuth<RequireReadOnly>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<PaginationQuery>,
+) -> Result<Json<Vec<ApiKeyResponse>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let rows = sqlx::query!(
+        r#"SELECT k.id, k.name, k.key_prefix, k.daily_quota, k.rate_limit_per_minute,
+            COALESCE(u.requests, 0) AS "requests_today!", k.created_at, k.revoked_at
+         FROM api_keys k
+         LEFT JOIN api_key_usage u
+           ON u.key_id = k.id AND u.day = (now() AT TIME ZONE 'UTC')::date
+         ORDER BY k.id DESC
+         LIMIT $1 OFFSET $2"#,
+        limit,
+        offset,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut keys = Vec::with_capacity(rows.len());
+    for row in rows {
+        keys.push(ApiKeyResponse {
+            id: row.id,
+            name: row.name,
+            key: None,
+            key_prefix: row.key_prefix,
+            daily_quota: row.daily_quota,
+            rate_limit_per_minute: row.rate_limit_per_minute,
+            requests_today: row.requests_today,
+            created_at: row.created_at,
+            revoked_at: row.revoked_at,
+        });
+    }
+
+    Ok(Json(keys))
+}
+
+/// DELETE /v1/admin/api-keys/:key_id - Revoke an API key
+///
+/// Takes effect within the key lookup cache lifetime (see [`api_keys::KEY_CACHE_TTL`]).
+async fn revoke_api_key(
+    admin: AdminAuth<RequireAdmin>,
+    State(state): State<AppState>,
+    ValidatedPath(ApiKeyPath { key_id }): ValidatedPath<ApiKeyPath>,
+) -> Result<StatusCode, ApiError> {
+    let revoked = sqlx::query!(
+        "UPDATE api_keys SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL",
+        key_id,
+    )
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+    if revoked == 0 {
+        return Err(ApiError::NotFound("API key not found or already revoked"));
+    }
+    tracing::info!(key_id, by = admin.subject, "API key revoked");
+    Ok(StatusCode::NO_CONTENT)
 }
 
-/// GET /v1/raffles/:raffle_id/purchases - List ticket purchases for a raffle
-async fn list_purchases(
+/// GET /v1/admin/api-keys/:key_id/usage - Daily usage of an API key
+async fn get_api_key_usage(
+    _admin: AdminAuth<RequireReadOnly>,
     State(state): State<AppState>,
-    Path(raffle_id): Path<i64>,
-    Query(params): Query<PaginationQuery>,
-) -> Result<Json<Vec<PurchaseRange>>, ApiError> {
-    let limit = normalize_limit(params.limit)?;
-    let offset = normalize_offset(params.offset)?;
+    ValidatedPath(ApiKeyPath { key_id }): ValidatedPath<ApiKeyPath>,
+    ValidatedQuery(params): ValidatedQuery<ApiKeyUsageQuery>,
+) -> Result<Json<ApiKeyUsageResponse>, ApiError> {
+    load_api_key_usage(&state.db, key_id, params.days).await
+}
 
-    let purchase_rows = sqlx::query(
-        "SELECT buyer, start_index, end_index, count,
-            amount::text AS amount, tx_hash, log_index, block_number, created_at
-         FROM purchases
-         WHERE raffle_id = $1
-         ORDER BY id ASC
-         LIMIT $2 OFFSET $3",
+/// GET /v1/usage - Daily usage and quota of the API key in `X-API-Key`
+async fn get_own_api_key_usage(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    ValidatedQuery(params): ValidatedQuery<ApiKeyUsageQuery>,
+) -> Result<Json<ApiKeyUsageResponse>, ApiError> {
+    // The API key middleware has already rejected unknown and revoked keys
+    let key = headers
+        .get(api_keys::API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::ApiKeyRequired)?;
+    let key_id: i64 = sqlx::query_scalar!(
+        "SELECT id FROM api_keys WHERE key_hash = $1",
+        api_keys::hash_key(key),
     )
-    .bind(raffle_id)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(&state.db)
-    .await
-    .map_err(db_error_to_api_error)?;
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::InvalidApiKey)?;
+    load_api_key_usage(&state.db, key_id, params.days).await
+}
 
-    let mut purchases = Vec::with_capacity(purchase_rows.len());
-    for row in purchase_rows {
-        purchases.push(PurchaseRange {
-            buyer: row.try_get("buyer").map_err(row_error_to_api_error)?,
-            start_index: row.try_get("start_index").map_err(row_error_to_api_error)?,
-            end_index: row.try_get("end_index").map_err(row_error_to_api_error)?,
-            count: row.try_get("count").map_err(row_error_to_api_error)?,
-            amount: row.try_get("amount").map_err(row_error_to_api_error)?,
-            tx_hash: row.try_get("tx_hash").map_err(row_error_to_api_error)?,
-            log_index: row.try_get("log_index").map_err(row_error_to_api_error)?,
-            block_number: row
-                .try_get("block_number")
-                .map_err(row_error_to_api_error)?,
-            created_at: row.try_get("created_at").map_err(row_error_to_api_error)?,
+async fn load_api_key_usage(
+    db: &PgPool,
+    key_id: i64,
+    days: Option<i64>,
+) -> Result<Json<ApiKeyUsageResponse>, ApiError> {
+    let days = days.unwrap_or(DEFAULT_USAGE_DAYS);
+    if !(1..=MAX_USAGE_DAYS).contains(&days) {
+        return Err(ApiError::invalid_parameter(
+            "days",
+            format!("days must be between 1 and {}", MAX_USAGE_DAYS),
+        ));
+    }
+
+    let key = sqlx::query!(
+        "SELECT key_prefix, daily_quota, rate_limit_per_minute FROM api_keys WHERE id = $1",
+        key_id,
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("API key not found"))?;
+
+    let rows = sqlx::query!(
+        "SELECT day, requests, rejected
+         FROM api_key_usage
+         WHERE key_id = $1 AND day > (now() AT TIME ZONE 'UTC')::date - $2::int
+         ORDER BY day DESC",
+        key_id,
+        days as i32,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut usage = Vec::with_capacity(rows.len());
+    for row in rows {
+        usage.push(ApiKeyUsageDay {
+            day: row.day,
+            requests: row.requests,
+            rejected: row.rejected,
         });
     }
 
-    Ok(Json(purchases))
+    Ok(Json(ApiKeyUsageResponse {
+        key_id,
+        key_prefix: key.key_prefix,
+        daily_quota: key.daily_quota,
+        rate_limit_per_minute: key.rate_limit_per_minute,
+        days: usage,
+    }))
 }
 
-/// GET /v1/raffles/:raffle_id/proof - Get verification proof for a raffle
-///
-/// Returns randomness, winning index, winner address, and relevant transaction links
-/// for client-side verification of fair winner selection.
-async fn get_raffle_proof(
+/// GET /v1/admin/exports - Parquet snapshots written to object storage, newest first
+async fn list_export_snapshots(
+    _admin: AdminAuth<RequireReadOnly>,
     State(state): State<AppState>,
-    Path(raffle_id): Path<i64>,
-) -> Result<Json<ProofResponse>, ApiError> {
-    let raffle_row = sqlx::query(
-        "SELECT raffle_id, raffle_address, request_id, request_tx, randomness, randomness_tx,
-            winning_index, winner, total_tickets, finalized_tx,
-            provider_request_id, provider_request_tx, provider_fulfill_tx, proof_data
-         FROM raffles
-         WHERE raffle_id = $1",
+    ValidatedQuery(params): ValidatedQuery<PaginationQuery>,
+) -> Result<Json<Vec<ExportSnapshotResponse>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let offset = params.offset.unwrap_or(0);
+
+    let rows = sqlx::query!(
+        r#"SELECT snapshot, bucket, block_number, files::text AS "files!", created_at
+         FROM export_snapshots
+         ORDER BY created_at DESC, id DESC
+         LIMIT $1 OFFSET $2"#,
+        limit,
+        offset,
     )
-    .bind(raffle_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_error_to_api_error)?;
+    .fetch_all(&state.db)
+    .await?;
 
-    let Some(row) = raffle_row else {
-        return Err(ApiError::not_found("raffle not found"));
-    };
+    let mut snapshots = Vec::with_capacity(rows.len());
+    for row in rows {
+        let files: String = row.files;
+        snapshots.push(ExportSnapshotResponse {
+            snapshot: row.snapshot,
+            bucket: row.bucket,
+            block_number: row.block_number,
+            files: serde_json::from_str(&files).map_err(|err| {
+                tracing::error!(error = %err, "invalid stored export files");
+                ApiError::Internal("invalid stored export files")
+            })?,
+            created_at: row.created_at,
+        });
+    }
 
-    let raffle_address: String = row
-        .try_get("raffle_address")
-        .map_err(row_error_to_api_error)?;
-    let request_id: Option<String> = row.try_get("request_id").map_err(row_error_to_api_error)?;
-    let request_tx: Option<String> = row.try_get("request_tx").map_err(row_error_to_api_error)?;
-    let randomness: Option<String> = row.try_get("randomness").map_err(row_error_to_api_error)?;
-    let randomness_tx: Option<String> = row
-        .try_get("randomness_tx")
-        .map_err(row_error_to_api_error)?;
-    let winner: Option<String> = row.try_get("winner").map_err(row_error_to_api_error)?;
-    let finalized_tx: Option<String> = row
-        .try_get("finalized_tx")
-        .map_err(row_error_to_api_error)?;
-    let total_tickets: i64 = row
-        .try_get("total_tickets")
-        .map_err(row_error_to_api_error)?;
-    let mut winning_index: Option<i64> = row
-        .try_get("winning_index")
-        .map_err(row_error_to_api_error)?;
+    Ok(Json(snapshots))
+}
 
-    // Provider-level fields
-    let provider_request_id: Option<String> = row
-        .try_get("provider_request_id")
-        .map_err(row_error_to_api_error)?;
-    let provider_request_tx: Option<String> = row
-        .try_get("provider_request_tx")
-        .map_err(row_error_to_api_error)?;
-    let provider_fulfill_tx: Option<String> = row
-        .try_get("provider_fulfill_tx")
-        .map_err(row_error_to_api_error)?;
-    let proof_data: Option<String> = row.try_get("proof_data").map_err(row_error_to_api_error)?;
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
 
-    // If the winning index was not stored, recompute it from randomness.
-    // This allows clients to verify: winningIndex = randomness % totalTickets
-    if winning_index.is_none()
-        && let Some(ref randomness_str) = randomness
-        && total_tickets > 0
-        && let Ok(rand) = U256::from_dec_str(randomness_str)
-    {
-        let idx = (rand % U256::from(total_tickets as u64)).as_u64() as i64;
-        winning_index = Some(idx);
+impl RaffleDetails {
+    /// Reads a row selected with [`raffle_details_columns`], without the `?format=` fields
+    fn from_row(row: &PgRow, now: DateTime<Utc>) -> Result<Self, sqlx::Error> {
+        let end_time: Option<DateTime<Utc>> = row.try_get("end_time")?;
+        let max_tickets: i64 = row.try_get("max_tickets")?;
+        let total_tickets: i64 = row.try_get("total_tickets")?;
+        Ok(Self {
+            raffle_id: row.try_get("raffle_id")?,
+            raffle_address: row.try_get("raffle_address")?,
+            creator: row.try_get("creator")?,
+            end_time,
+            ticket_price: row.try_get("ticket_price")?,
+            ticket_price_formatted: None,
+            ticket_price_hex: None,
+            max_tickets,
+            fee_bps: row.try_get("fee_bps")?,
+            fee_recipient: row.try_get("fee_recipient")?,
+            status: row.try_get("status")?,
+            effective_status: row.try_get("effective_status")?,
+            total_tickets,
+            unique_buyers: row.try_get("unique_buyers")?,
+            pot: row.try_get("pot")?,
+            pot_formatted: None,
+            pot_hex: None,
+            request_id: row.try_get("request_id")?,
+            request_id_hex: None,
+            request_tx: row.try_get("request_tx")?,
+            randomness: row.try_get("randomness")?,
+            randomness_hex: None,
+            randomness_tx: row.try_get("randomness_tx")?,
+            winning_index: row.try_get("winning_index")?,
+            winner: row.try_get("winner")?,
+            finalized_tx: row.try_get("finalized_tx")?,
+            keeper: row.try_get("keeper")?,
+            prize_amount: row.try_get("prize_amount")?,
+            prize_amount_formatted: None,
+            prize_amount_hex: None,
+            fee_amount: row.try_get("fee_amount")?,
+            fee_amount_formatted: None,
+            fee_amount_hex: None,
+            payout_tx: row.try_get("payout_tx")?,
+            payout_confirmed: row.try_get("payout_confirmed")?,
+            progress: RaffleProgress::compute(end_time, max_tickets, total_tickets, now),
+            source: "index",
+            last_modified: if end_time.is_none_or(|end| end <= now) {
+                row.try_get("last_modified")?
+            } else {
+                None
+            },
+        })
     }
 
-    // Look up the winning ticket range
-    let winning_range = if let Some(index) = winning_index {
-        let range_row = sqlx::query(
-            "SELECT buyer, start_index, end_index
-             FROM purchases
-             WHERE raffle_id = $1 AND start_index <= $2 AND end_index >= $2
-             ORDER BY id ASC
-             LIMIT 1",
-        )
-        .bind(raffle_id)
-        .bind(index)
-        .fetch_optional(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?;
+    /// Fills the `*_formatted` and `*_hex` fields requested via `?format=`
+    fn with_format(mut self, format: AmountFormat, decimals: u32) -> Self {
+        let hex = |value: &Option<String>| value.as_deref().and_then(|v| format.render_hex(v));
+        self.ticket_price_formatted = format.render(&self.ticket_price, decimals);
+        self.ticket_price_hex = format.render_hex(&self.ticket_price);
+        self.pot_formatted = format.render(&self.pot, decimals);
+        self.pot_hex = format.render_hex(&self.pot);
+        self.prize_amount_formatted = self
+            .prize_amount
+            .as_deref()
+            .and_then(|amount| format.render(amount, decimals));
+        self.prize_amount_hex = hex(&self.prize_amount);
+        self.fee_amount_formatted = self
+            .fee_amount
+            .as_deref()
+            .and_then(|amount| format.render(amount, decimals));
+        self.fee_amount_hex = hex(&self.fee_amount);
+        self.request_id_hex = hex(&self.request_id);
+        self.randomness_hex = hex(&self.randomness);
+        self
+    }
+}
 
-        range_row.map(|r| WinningRange {
-            buyer: r.try_get("buyer").unwrap_or_default(),
-            start_index: r.try_get("start_index").unwrap_or_default(),
-            end_index: r.try_get("end_index").unwrap_or_default(),
-        })
-    } else {
-        None
-    };
+impl RaffleProgress {
+    /// Computes progress fields relative to `now`.
+    ///
+    /// `max_tickets == 0` means the raffle has no ticket cap, so there is nothing
+    /// to count down or fill and it can never sell out.
+    fn compute(
+        end_time: Option<DateTime<Utc>>,
+        max_tickets: i64,
+        total_tickets: i64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let time_remaining_seconds = end_time.map(|end| (end - now).num_seconds().max(0));
 
-    let txs = TxLinks {
-        request_tx: request_tx.clone(),
-        request_url: build_tx_url(&state.config.explorer_base_url, &request_tx),
-        randomness_tx: randomness_tx.clone(),
-        randomness_url: build_tx_url(&state.config.explorer_base_url, &randomness_tx),
-        finalized_tx: finalized_tx.clone(),
-        finalized_url: build_tx_url(&state.config.explorer_base_url, &finalized_tx),
-        provider_request_tx: provider_request_tx.clone(),
-        provider_request_url: build_tx_url(&state.config.explorer_base_url, &provider_request_tx),
-        provider_fulfill_tx: provider_fulfill_tx.clone(),
-        provider_fulfill_url: build_tx_url(&state.config.explorer_base_url, &provider_fulfill_tx),
-    };
+        if max_tickets <= 0 {
+            return Self {
+                time_remaining_seconds,
+                tickets_remaining: None,
+                fill_percent: None,
+                is_sold_out: false,
+            };
+        }
 
-    Ok(Json(ProofResponse {
-        raffle_id: row.try_get("raffle_id").map_err(row_error_to_api_error)?,
-        raffle_address,
-        request_id,
-        provider_request_id,
-        randomness,
-        proof_data,
-        total_tickets,
-        winning_index,
-        winner,
-        winning_range,
-        txs,
-    }))
+        let sold = total_tickets.clamp(0, max_tickets);
+        // Round to two decimal places to keep the JSON stable
+        let fill_percent = (sold as f64 * 10_000.0 / max_tickets as f64).round() / 100.0;
+
+        Self {
+            time_remaining_seconds,
+            tickets_remaining: Some(max_tickets - sold),
+            fill_percent: Some(fill_percent),
+            is_sold_out: total_tickets >= max_tickets,
+        }
+    }
 }
 
-/// GET /v1/randomness/requests - List randomness requests from DrandRandomnessProvider
-async fn list_randomness_requests(
+/// Computes the winning ticket index the same way the Raffle contract does:
+/// `randomness % totalTickets`. Returns `None` for unparsable randomness or no tickets.
+fn compute_winning_index(randomness: &str, total_tickets: i64) -> Option<i64> {
+    if total_tickets <= 0 {
+        return None;
+    }
+    let rand = U256::from_dec_str(randomness).ok()?;
+    Some((rand % U256::from(total_tickets as u64)).as_u64() as i64)
+}
+
+/// POST /v1/raffles/:raffle_id/tickets/resolve - Owners of many ticket indices at once
+///
+/// Resolves every index with one range join against the raffle's purchases, so audit
+/// tools don't need a request per index.
+async fn resolve_tickets(
     State(state): State<AppState>,
-    Query(params): Query<RandomnessRequestQuery>,
-) -> Result<Json<Vec<RandomnessRequestResponse>>, ApiError> {
-    let limit = normalize_limit(params.limit)?;
-    let offset = normalize_offset(params.offset)?;
-
-    let rows = if let Some(raffle_addr) = params.raffle_address {
-        sqlx::query(
-            "SELECT id, request_id::text AS request_id, raffle_id, raffle_address,
-                provider_address, tx_hash, log_index, block_number, created_at
-             FROM randomness_requests
-             WHERE LOWER(raffle_address) = LOWER($1)
-             ORDER BY id DESC
-             LIMIT $2 OFFSET $3",
-        )
-        .bind(raffle_addr)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
-    } else if let Some(raffle_id) = params.raffle_id {
-        sqlx::query(
-            "SELECT id, request_id::text AS request_id, raffle_id, raffle_address,
-                provider_address, tx_hash, log_index, block_number, created_at
-             FROM randomness_requests
-             WHERE raffle_id = $1
-             ORDER BY id DESC
-             LIMIT $2 OFFSET $3",
-        )
-        .bind(raffle_id)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
-    } else {
-        sqlx::query(
-            "SELECT id, request_id::text AS request_id, raffle_id, raffle_address,
-                provider_address, tx_hash, log_index, block_number, created_at
-             FROM randomness_requests
-             ORDER BY id DESC
-             LIMIT $1 OFFSET $2",
-        )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
-    };
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    Json(request): Json<ResolveTicketsRequest>,
+) -> Result<Json<ResolveTicketsResponse>, ApiError> {
+    if request.indices.is_empty() {
+        return Err(ApiError::invalid_parameter(
+            "indices",
+            "indices must not be empty",
+        ));
+    }
+    if request.indices.len() > MAX_RESOLVE_INDICES {
+        return Err(ApiError::invalid_parameter(
+            "indices",
+            format!("at most {MAX_RESOLVE_INDICES} indices may be resolved per request"),
+        ));
+    }
+    if request.indices.iter().any(|index| *index < 0) {
+        return Err(ApiError::invalid_parameter(
+            "indices",
+            "ticket indices must be >= 0",
+        ));
+    }
 
-    let mut requests = Vec::with_capacity(rows.len());
+    let total_tickets: i64 = sqlx::query_scalar!(
+        r#"SELECT total_tickets AS "total_tickets!" FROM raffles_all WHERE raffle_id = $1"#,
+        raffle_id,
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| ApiError::RaffleNotFound)?;
+
+    let rows = sqlx::query!(
+        r#"SELECT i.index AS "index!", p.buyer, p.start_index, p.end_index, p.tx_hash
+         FROM UNNEST($2::bigint[]) WITH ORDINALITY AS i (index, position)
+         LEFT JOIN LATERAL (
+             SELECT buyer, start_index, end_index, tx_hash
+             FROM purchases_all
+             WHERE raffle_id = $1 AND start_index <= i.index AND end_index >= i.index
+             ORDER BY id ASC
+             LIMIT 1
+         ) p ON true
+         ORDER BY i.position"#,
+        raffle_id,
+        &request.indices,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut tickets = Vec::with_capacity(rows.len());
     for row in rows {
-        let tx_hash: String = row.try_get("tx_hash").map_err(row_error_to_api_error)?;
-        requests.push(RandomnessRequestResponse {
-            id: row.try_get("id").map_err(row_error_to_api_error)?,
-            request_id: row.try_get("request_id").map_err(row_error_to_api_error)?,
-            raffle_id: row.try_get("raffle_id").map_err(row_error_to_api_error)?,
-            raffle_address: row
-                .try_get("raffle_address")
-                .map_err(row_error_to_api_error)?,
-            provider_address: row
-                .try_get("provider_address")
-                .map_err(row_error_to_api_error)?,
-            tx_hash: tx_hash.clone(),
-            tx_url: build_tx_url(&state.config.explorer_base_url, &Some(tx_hash)),
-            log_index: row.try_get("log_index").map_err(row_error_to_api_error)?,
-            block_number: row
-                .try_get("block_number")
-                .map_err(row_error_to_api_error)?,
-            created_at: row.try_get("created_at").map_err(row_error_to_api_error)?,
+        tickets.push(ResolvedTicket {
+            index: row.index,
+            owner: row.buyer,
+            start_index: row.start_index,
+            end_index: row.end_index,
+            tx_hash: row.tx_hash,
         });
     }
 
-    Ok(Json(requests))
+    Ok(Json(ResolveTicketsResponse {
+        raffle_id,
+        total_tickets,
+        tickets,
+    }))
 }
 
-/// GET /v1/randomness/requests/:request_id - Get a specific randomness request
-async fn get_randomness_request(
-    State(state): State<AppState>,
-    Path(request_id): Path<String>,
-) -> Result<Json<RandomnessRequestResponse>, ApiError> {
-    let row = sqlx::query(
-        "SELECT id, request_id::text AS request_id, raffle_id, raffle_address,
-            provider_address, tx_hash, log_index, block_number, created_at
-         FROM randomness_requests
-         WHERE request_id::text = $1
+/// Finds the stored purchase range containing a ticket index
+async fn find_ticket_range(
+    db: &sqlx::PgPool,
+    raffle_id: i64,
+    index: i64,
+) -> Result<Option<WinningRange>, ApiError> {
+    let range_row = sqlx::query!(
+        "SELECT buyer, start_index, end_index
+         FROM purchases_all
+         WHERE raffle_id = $1 AND start_index <= $2 AND end_index >= $2
+         ORDER BY id ASC
          LIMIT 1",
+        raffle_id,
+        index,
     )
-    .bind(&request_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(db_error_to_api_error)?;
-
-    let Some(row) = row else {
-        return Err(ApiError::not_found("randomness request not found"));
-    };
+    .fetch_optional(db)
+    .await?;
 
-    let tx_hash: String = row.try_get("tx_hash").map_err(row_error_to_api_error)?;
-    Ok(Json(RandomnessRequestResponse {
-        id: row.try_get("id").map_err(row_error_to_api_error)?,
-        request_id: row.try_get("request_id").map_err(row_error_to_api_error)?,
-        raffle_id: row.try_get("raffle_id").map_err(row_error_to_api_error)?,
-        raffle_address: row
-            .try_get("raffle_address")
-            .map_err(row_error_to_api_error)?,
-        provider_address: row
-            .try_get("provider_address")
-            .map_err(row_error_to_api_error)?,
-        tx_hash: tx_hash.clone(),
-        tx_url: build_tx_url(&state.config.explorer_base_url, &Some(tx_hash)),
-        log_index: row.try_get("log_index").map_err(row_error_to_api_error)?,
-        block_number: row
-            .try_get("block_number")
-            .map_err(row_error_to_api_error)?,
-        created_at: row.try_get("created_at").map_err(row_error_to_api_error)?,
+    Ok(range_row.map(|r| WinningRange {
+        buyer: r.buyer.unwrap_or_default(),
+        start_index: r.start_index.unwrap_or_default(),
+        end_index: r.end_index.unwrap_or_default(),
     }))
 }
 
-/// GET /v1/randomness/fulfillments - List randomness fulfillments from DrandRandomnessProvider
-async fn list_randomness_fulfillments(
+/// Checks pagination parameters: `limit` in 1..=MAX_PAGE_LIMIT, `offset` non-negative
+fn validate_page(limit: Option<i64>, offset: Option<i64>) -> Result<(), ApiError> {
+    if let Some(limit) = limit
+        && !(1..=MAX_PAGE_LIMIT).contains(&limit)
+    {
+        return Err(ApiError::invalid_parameter(
+            "limit",
+            format!("limit must be between 1 and {MAX_PAGE_LIMIT}"),
+        ));
+    }
+    if offset.is_some_and(|offset| offset < 0) {
+        return Err(ApiError::invalid_parameter("offset", "offset must be >= 0"));
+    }
+    Ok(())
+}
+
+/// Checks that an optional ID parameter isn't negative
+fn validate_id(param: &'static str, id: Option<i64>) -> Result<(), ApiError> {
+    if id.is_some_and(|id| id < 0) {
+        return Err(ApiError::invalid_parameter(
+            param,
+            format!("{param} must be a non-negative integer"),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a `(block_number, log_index)` cursor is complete and non-negative
+fn validate_purchase_cursor(
+    after_block: Option<i64>,
+    after_log_index: Option<i64>,
+) -> Result<(), ApiError> {
+    match (after_block, after_log_index) {
+        (Some(_), None) => Err(ApiError::invalid_parameter(
+            "after_log_index",
+            "after_log_index is required with after_block",
+        )),
+        (None, Some(_)) => Err(ApiError::invalid_parameter(
+            "after_block",
+            "after_block is required with after_log_index",
+        )),
+        _ => {
+            validate_id("after_block", after_block)?;
+            validate_id("after_log_index", after_log_index)
+        }
+    }
+}
+
+/// Checks an optional address parameter
+fn validate_address(param: &'static str, address: Option<&str>) -> Result<(), ApiError> {
+    if address.is_some_and(|address| !extract::is_address(address)) {
+        return Err(ApiError::InvalidAddress { param });
+    }
+    Ok(())
+}
+
+/// Builds a 200 JSON response from an already serialized body
+fn json_response(body: Bytes) -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+/// Runs the request inside a [`metrics::query_span`] named after the matched route
+async fn tag_query_source(
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = matched_path
+        .as_ref()
+        .map(MatchedPath::as_str)
+        .unwrap_or("unmatched");
+    let span = metrics::query_span(&format!("{} {route}", request.method()));
+    next.run(request).instrument(span).await
+}
+
+/// Sets `Cache-Control` on successful GET/HEAD responses of routes in `CACHE_CONTROL_ROUTES`
+///
+/// Handlers that set their own `Cache-Control` (the embed routes) keep it. Responses
+/// marked with [`LastModified`] also get `Last-Modified`, and become an empty 304 when
+/// the request's `If-Modified-Since` is at or after it.
+pub async fn cache_control(
     State(state): State<AppState>,
-    Query(params): Query<RandomnessRequestQuery>,
-) -> Result<Json<Vec<RandomnessFulfillmentResponse>>, ApiError> {
-    let limit = normalize_limit(params.limit)?;
-    let offset = normalize_offset(params.offset)?;
-
-    let rows = if let Some(raffle_addr) = params.raffle_address {
-        sqlx::query(
-            "SELECT id, request_id::text AS request_id, randomness::text AS randomness,
-                proof, raffle_address, provider_address, tx_hash, log_index, block_number, created_at
-             FROM randomness_fulfillments
-             WHERE LOWER(raffle_address) = LOWER($1)
-             ORDER BY id DESC
-             LIMIT $2 OFFSET $3",
-        )
-        .bind(raffle_addr)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let keyed = request.headers().contains_key(api_keys::API_KEY_HEADER);
+    let cacheable = matches!(
+        *request.method(),
+        axum::http::Method::GET | axum::http::Method::HEAD
+    );
+    let policy = cacheable
+        .then(|| {
+            // Matched paths include the deployment prefix the router is nested under
+            let route = state
+                .config
+                .strip_route_prefix(matched_path.as_ref()?.as_str())?;
+            state
+                .config
+                .cache_control_routes
+                .iter()
+                .find(|policy| policy.route == route)
+        })
+        .flatten();
+
+    let if_modified_since = cacheable
+        .then(|| request.headers().get(header::IF_MODIFIED_SINCE))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date);
+
+    let mut response = next.run(request).await;
+    if !response.status().is_success() {
+        return response;
+    }
+    let last_modified = response
+        .extensions()
+        .get::<LastModified>()
+        .map(|LastModified(at)| *at);
+    if let Some(at) = last_modified
+        && let Ok(value) = HeaderValue::from_str(&format_http_date(at))
+    {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    if let Some(policy) = policy
+        && !response.headers().contains_key(header::CACHE_CONTROL)
+    {
+        apply_cache_policy(&mut response, policy, keyed);
+    }
+
+    // HTTP dates have second precision
+    match (last_modified, if_modified_since) {
+        (Some(at), Some(since)) if at.timestamp() <= since.timestamp() => {
+            let (mut parts, _) = response.into_parts();
+            parts.status = StatusCode::NOT_MODIFIED;
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::empty())
+        }
+        _ => response,
+    }
+}
+
+/// Sets the `Cache-Control` (and `Vary`) headers a route's policy asks for
+fn apply_cache_policy(response: &mut Response, policy: &RouteCacheControl, keyed: bool) {
+    let max_age = if response.extensions().get::<Finalized>().is_some() {
+        policy.finalized_max_age_secs.or(policy.max_age_secs)
     } else {
-        sqlx::query(
-            "SELECT id, request_id::text AS request_id, randomness::text AS randomness,
-                proof, raffle_address, provider_address, tx_hash, log_index, block_number, created_at
-             FROM randomness_fulfillments
-             ORDER BY id DESC
-             LIMIT $1 OFFSET $2",
-        )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&state.db)
-        .await
-        .map_err(db_error_to_api_error)?
+        policy.max_age_secs
     };
-
-    let mut fulfillments = Vec::with_capacity(rows.len());
-    for row in rows {
-        let tx_hash: String = row.try_get("tx_hash").map_err(row_error_to_api_error)?;
-        fulfillments.push(RandomnessFulfillmentResponse {
-            id: row.try_get("id").map_err(row_error_to_api_error)?,
-            request_id: row.try_get("request_id").map_err(row_error_to_api_error)?,
-            randomness: row.try_get("randomness").map_err(row_error_to_api_error)?,
-            proof: row.try_get("proof").map_err(row_error_to_api_error)?,
-            raffle_address: row
-                .try_get("raffle_address")
-                .map_err(row_error_to_api_error)?,
-            provider_address: row
-                .try_get("provider_address")
-                .map_err(row_error_to_api_error)?,
-            tx_hash: tx_hash.clone(),
-            tx_url: build_tx_url(&state.config.explorer_base_url, &Some(tx_hash)),
-            log_index: row.try_get("log_index").map_err(row_error_to_api_error)?,
-            block_number: row
-                .try_get("block_number")
-                .map_err(row_error_to_api_error)?,
-            created_at: row.try_get("created_at").map_err(row_error_to_api_error)?,
-        });
+    let value = match max_age {
+        Some(secs) => format!("public, max-age={secs}, s-maxage={secs}"),
+        None => "no-store".to_string(),
+    };
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(header::CACHE_CONTROL, value);
     }
+    if keyed && max_age.is_some() {
+        headers.append(
+            header::VARY,
+            HeaderValue::from_static(api_keys::API_KEY_HEADER),
+        );
+    }
+}
 
-    Ok(Json(fulfillments))
+/// Formats a timestamp as an HTTP date (`Sun, 06 Nov 1994 08:49:37 GMT`)
+fn format_http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
 }
 
-// ============================================================================
-// HELPER FUNCTIONS
-// ============================================================================
+/// Parses an HTTP date header, ignoring the obsolete RFC 850 and asctime forms
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Answers `OPTIONS` on every route with 204 No Content and its `Allow` methods
+///
+/// The router answers a method a route has no handler for with 405 and the methods it
+/// has (GET routes also take HEAD); that list, plus `OPTIONS`, is the answer. Other
+/// 405s list `OPTIONS` too. Routes with their own OPTIONS handler (the embed CORS
+/// preflight) keep it.
+pub async fn allow_options(request: Request, next: Next) -> Response {
+    let options = request.method() == axum::http::Method::OPTIONS;
+    let mut response = next.run(request).await;
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    let allow = match response
+        .headers()
+        .get(header::ALLOW)
+        .and_then(|allow| allow.to_str().ok())
+    {
+        Some(allow) if !allow.is_empty() => format!("{allow},OPTIONS"),
+        _ => "OPTIONS".to_string(),
+    };
+    let Ok(allow) = HeaderValue::from_str(&allow) else {
+        return response;
+    };
+    if options {
+        return (StatusCode::NO_CONTENT, [(header::ALLOW, allow)]).into_response();
+    }
+    response.headers_mut().insert(header::ALLOW, allow);
+    response
+}
 
-/// Normalizes pagination limit with bounds checking
-fn normalize_limit(limit: Option<i64>) -> Result<i64, ApiError> {
-    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
-    if limit <= 0 {
-        return Err(ApiError::bad_request("limit must be positive"));
+/// Shortens an address for display text: `0x1234…abcd`
+fn short_address(address: &str) -> String {
+    match (
+        address.get(..6),
+        address
+            .len()
+            .checked_sub(4)
+            .and_then(|start| address.get(start..)),
+    ) {
+        (Some(head), Some(tail)) if address.len() > 10 => format!("{head}…{tail}"),
+        _ => address.to_string(),
     }
-    Ok(limit.min(MAX_PAGE_LIMIT))
 }
 
-/// Normalizes pagination offset with bounds checking
-fn normalize_offset(offset: Option<i64>) -> Result<i64, ApiError> {
-    let offset = offset.unwrap_or(0);
-    if offset < 0 {
-        return Err(ApiError::bad_request("offset must be >= 0"));
+/// Allows cross-origin reads of embed routes, including their error responses
+async fn embed_cors(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        HeaderValue::from_static("*"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("GET, OPTIONS"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_MAX_AGE,
+        HeaderValue::from_static("86400"),
+    );
+    response
+}
+
+/// Checks that an optional queue parameter names a webhook queue
+fn validate_webhook_queue(queue: Option<&str>) -> Result<Option<&'static str>, ApiError> {
+    let Some(queue) = queue else {
+        return Ok(None);
+    };
+    WEBHOOK_QUEUES
+        .into_iter()
+        .find(|known| *known == queue)
+        .map(Some)
+        .ok_or_else(|| {
+            ApiError::invalid_parameter(
+                "queue",
+                format!("queue must be one of {}", WEBHOOK_QUEUES.join(", ")),
+            )
+        })
+}
+
+/// Shortens a webhook URL to its scheme and host, dropping paths that may embed a token
+fn redact_webhook_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => format!(
+            "{}://{}/...",
+            parsed.scheme(),
+            parsed.host_str().unwrap_or_default()
+        ),
+        Err(_) => "[invalid]".to_string(),
     }
-    Ok(offset)
 }
 
-/// Converts database error to API error without exposing internal details
-fn db_error_to_api_error(err: sqlx::Error) -> ApiError {
-    // Log the actual error for debugging, but don't expose to client
-    tracing::error!(error = %err, "database error");
-    ApiError::internal("database error")
+/// Checks that a webhook URL is an absolute https URL without credentials
+fn validate_webhook_url(url: &str) -> Result<(), ApiError> {
+    if url.len() > MAX_WEBHOOK_URL_LEN {
+        return Err(ApiError::invalid_parameter(
+            "webhookUrl",
+            format!("webhookUrl must be at most {MAX_WEBHOOK_URL_LEN} characters"),
+        ));
+    }
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| ApiError::invalid_parameter("webhookUrl", "webhookUrl must be a valid URL"))?;
+    if parsed.scheme() != "https" || parsed.host_str().is_none() {
+        return Err(ApiError::invalid_parameter(
+            "webhookUrl",
+            "webhookUrl must be an https URL",
+        ));
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err(ApiError::invalid_parameter(
+            "webhookUrl",
+            "webhookUrl must not contain credentials",
+        ));
+    }
+    Ok(())
 }
 
 /// Converts row extraction error to API error
 fn row_error_to_api_error(err: sqlx::Error) -> ApiError {
     tracing::error!(error = %err, "row extraction error");
-    ApiError::internal("data extraction error")
+    ApiError::Internal("data extraction error")
+}
+
+/// Merges `(start, end)` ticket ranges sorted by start, joining adjacent or overlapping ones
+fn merge_ranges(ranges: impl IntoIterator<Item = (i64, i64)>) -> Vec<IndexRange> {
+    let mut merged: Vec<IndexRange> = Vec::new();
+    for (start_index, end_index) in ranges {
+        match merged.last_mut() {
+            Some(last) if start_index <= last.end_index.saturating_add(1) => {
+                last.end_index = last.end_index.max(end_index);
+            }
+            _ => merged.push(IndexRange {
+                start_index,
+                end_index,
+            }),
+        }
+    }
+    merged
 }
 
 /// Builds a block explorer URL for a transaction hash
@@ -772,3 +7069,129 @@ fn build_tx_url(explorer_base_url: &str, tx_hash: &Option<String>) -> Option<Str
         format!("{}/tx/{}", base, hash)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_winning_index_like_the_contract() {
+        assert_eq!(compute_winning_index("123456789", 12), Some(9));
+        assert_eq!(compute_winning_index("0", 5), Some(0));
+        // 2^256 - 1 mod 1000
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(compute_winning_index(max, 1000), Some(935));
+        assert_eq!(compute_winning_index("42", 0), None);
+        assert_eq!(compute_winning_index("0x2a", 10), None);
+        assert_eq!(compute_winning_index("not a number", 10), None);
+    }
+
+    #[test]
+    fn compares_recomputed_and_indexed_results() {
+        let onchain = OnchainResult {
+            winning_index: Some(9),
+            winner: Some("0x00000000000000000000000000000000000000b2".to_string()),
+        };
+        let winner = "0x00000000000000000000000000000000000000B2";
+        assert_eq!(onchain.matches(9, Some(winner)), Some(true));
+        assert_eq!(onchain.matches(8, Some(winner)), Some(false));
+        assert_eq!(
+            onchain.matches(9, Some("0x00000000000000000000000000000000000000b1")),
+            Some(false)
+        );
+        // Ranges that don't cover the winning index can't confirm the winner
+        assert_eq!(onchain.matches(9, None), Some(false));
+
+        let no_winner = OnchainResult {
+            winning_index: Some(9),
+            winner: None,
+        };
+        assert_eq!(no_winner.matches(9, Some(winner)), Some(true));
+        assert_eq!(no_winner.matches(9, None), Some(true));
+
+        let not_drawn = OnchainResult {
+            winning_index: None,
+            winner: None,
+        };
+        assert_eq!(not_drawn.matches(9, Some(winner)), None);
+    }
+
+    #[test]
+    fn progress_of_uncapped_raffles() {
+        let now = Utc::now();
+        let progress =
+            RaffleProgress::compute(Some(now + chrono::Duration::seconds(90)), 0, 500, now);
+        assert_eq!(progress.time_remaining_seconds, Some(90));
+        assert_eq!(progress.tickets_remaining, None);
+        assert_eq!(progress.fill_percent, None);
+        assert!(!progress.is_sold_out);
+
+        let progress = RaffleProgress::compute(None, -1, 500, now);
+        assert_eq!(progress.time_remaining_seconds, None);
+        assert_eq!(progress.fill_percent, None);
+        assert!(!progress.is_sold_out);
+    }
+
+    #[test]
+    fn progress_of_capped_raffles() {
+        let now = Utc::now();
+        let ended = Some(now - chrono::Duration::seconds(5));
+
+        let progress = RaffleProgress::compute(ended, 3, 1, now);
+        assert_eq!(progress.time_remaining_seconds, Some(0));
+        assert_eq!(progress.tickets_remaining, Some(2));
+        // 33.333... rounds to two decimals
+        assert_eq!(progress.fill_percent, Some(33.33));
+        assert!(!progress.is_sold_out);
+        assert_eq!(
+            RaffleProgress::compute(ended, 3, 2, now).fill_percent,
+            Some(66.67)
+        );
+        assert_eq!(
+            RaffleProgress::compute(ended, 8, 1, now).fill_percent,
+            Some(12.5)
+        );
+
+        let progress = RaffleProgress::compute(ended, 100, 100, now);
+        assert_eq!(progress.tickets_remaining, Some(0));
+        assert_eq!(progress.fill_percent, Some(100.0));
+        assert!(progress.is_sold_out);
+
+        // Oversold totals (e.g. mid-reorg) are clamped to the cap
+        let progress = RaffleProgress::compute(ended, 100, 130, now);
+        assert_eq!(progress.tickets_remaining, Some(0));
+        assert_eq!(progress.fill_percent, Some(100.0));
+        assert!(progress.is_sold_out);
+    }
+
+    #[tokio::test]
+    async fn sold_out_agrees_with_effective_status() {
+        let Some(db) = crate::testkit::TestDb::create().await.unwrap() else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+
+        let now = Utc::now();
+        let end_time = now + chrono::Duration::hours(1);
+        for (max_tickets, total_tickets) in [(0, 0), (0, 500), (-1, 5), (10, 9), (10, 10), (10, 11)]
+        {
+            let effective_status: String = sqlx::query_scalar(&format!(
+                "SELECT {EFFECTIVE_STATUS_SQL}
+                 FROM (SELECT 'ACTIVE' AS status, $1::timestamptz AS end_time,
+                              $2::bigint AS max_tickets, $3::bigint AS total_tickets) raffles"
+            ))
+            .bind(end_time)
+            .bind(max_tickets)
+            .bind(total_tickets)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+            let progress = RaffleProgress::compute(Some(end_time), max_tickets, total_tickets, now);
+            assert_eq!(
+                progress.is_sold_out,
+                effective_status == "ENDED",
+                "max_tickets {max_tickets}, total_tickets {total_tickets}"
+            );
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! Version 2 of the REST API
+//!
+//! `/v2` serves the same data as `/v1`, from the same queries, with the conventions
+//! `/v1` can't adopt without breaking its clients:
+//! - Every success body is an envelope: `{"data": ...}`, plus `page` for lists.
+//! - Lists page with an opaque `cursor` (the previous page's `page.next_cursor`)
+//!   instead of `offset`, so rows inserted meanwhile are neither skipped nor repeated.
+//! - Errors are `{"error": {"code", "message", "details"}}` (see [`error::envelope`]).
+//! - Addresses are EIP-55 checksummed.
+//! - Token amounts are `{"value", "formatted"}` objects; there is no `?format=`.
+//! - Computed fields are grouped under `progress`.
+//!
+//! `/v1` is frozen; new endpoints and fields are added here.
+//!
+//! # Endpoints
+//! - `GET /v2/raffles` - List raffles, newest first, optionally by status
+//! - `GET /v2/raffles/:raffle_id` - Get raffle details
+//! - `GET /v2/raffles/:raffle_id/purchases` - Get ticket purchase ranges in chain order
+//!
+//! [`error::envelope`]: crate::error::envelope
+
+use super::{
+    DEFAULT_PAGE_LIMIT, LastModified, PurchaseRange, PurchasesQuery, RAFFLE_STATUSES,
+    RaffleDetails, RaffleListFilter, RafflePath, RaffleProgress, chain_fallback,
+    fetch_raffle_details, fetch_raffle_from_chain, fetch_raffle_list, load_purchase_page,
+    tag_query_source, validate_page,
+};
+use crate::auth;
+use crate::error::ApiError;
+use crate::extract::{Validate, ValidatedPath, ValidatedQuery};
+use crate::format::{self, AmountFormat};
+use crate::state::AppState;
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::{HeaderMap, HeaderValue, header},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Cursor kind of the raffle list, keyed by `raffle_id`
+const RAFFLES_CURSOR: &str = "raffles";
+
+/// Cursor kind of purchase lists, keyed by `(block_number, log_index)`
+const PURCHASES_CURSOR: &str = "purchases";
+
+/// Creates the v2 API router
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/raffles", get(list_raffles))
+        .route("/raffles/{raffle_id}", get(get_raffle))
+        .route("/raffles/{raffle_id}/purchases", get(list_purchases))
+        // Tag queries with the route for slow query metrics
+        .route_layer(middleware::from_fn(tag_query_source))
+}
+
+// ============================================================================
+// ENVELOPES
+// ============================================================================
+
+/// A single resource
+#[derive(Serialize)]
+struct Data<T> {
+    data: T,
+}
+
+/// One page of a list
+#[derive(Serialize)]
+struct Page<T> {
+    data: Vec<T>,
+    page: PageInfo,
+}
+
+#[derive(Serialize)]
+struct PageInfo {
+    limit: i64,
+    /// Pass as `cursor` for the next page; null on the last page
+    next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from up to `limit + 1` items; the extra item only tells
+    /// whether there is a next page, which starts after the last item kept
+    fn new(mut items: Vec<T>, limit: i64, cursor: impl Fn(&T) -> String) -> Self {
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+        let next_cursor = has_more.then(|| items.last().map(cursor)).flatten();
+        Page {
+            data: items,
+            page: PageInfo { limit, next_cursor },
+        }
+    }
+}
+
+/// Encodes the position of a page's last item as an opaque cursor
+fn encode_cursor(kind: &str, position: &[i64]) -> String {
+    let position: Vec<String> = position.iter().map(i64::to_string).collect();
+    BASE64URL.encode(format!("{kind}:{}", position.join(":")))
+}
+
+/// Decodes a cursor made by [`encode_cursor`] for a list of `kind`
+fn decode_cursor<const N: usize>(kind: &str, cursor: &str) -> Result<[i64; N], ApiError> {
+    let invalid = || ApiError::invalid_parameter("cursor", "cursor is not valid for this list");
+    let decoded = BASE64URL.decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let mut parts = decoded.split(':');
+    if parts.next() != Some(kind) {
+        return Err(invalid());
+    }
+    let mut position = [0; N];
+    for value in &mut position {
+        *value = parts
+            .next()
+            .and_then(|part| part.parse().ok())
+            .filter(|value: &i64| *value >= 0)
+            .ok_or_else(invalid)?;
+    }
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    Ok(position)
+}
+
+// ============================================================================
+// RESOURCES
+// ============================================================================
+
+/// A token amount in the payment token's smallest unit and as a decimal string
+#[derive(Serialize)]
+struct Amount {
+    value: String,
+    /// `value` scaled by `TOKEN_DECIMALS`, e.g. `"1.5"`
+    formatted: Option<String>,
+}
+
+impl Amount {
+    fn new(value: String, decimals: u32) -> Self {
+        Amount {
+            formatted: format::format_units(&value, decimals),
+            value,
+        }
+    }
+}
+
+/// EIP-55 form of a stored address (returned as is if it doesn't parse)
+fn checksummed(address: String) -> String {
+    format::to_checksum(&address).unwrap_or(address)
+}
+
+/// A raffle in the raffle list
+#[derive(Serialize)]
+struct RaffleSummary {
+    raffle_id: i64,
+    raffle_address: String,
+    status: String,
+    /// Display status (ACTIVE raffles past `end_time` are reported as ENDED)
+    effective_status: String,
+    end_time: Option<DateTime<Utc>>,
+    ticket_price: Amount,
+    max_tickets: i64,
+    total_tickets: i64,
+    unique_buyers: i64,
+    pot: Amount,
+    winner: Option<String>,
+    progress: RaffleProgress,
+}
+
+/// A raffle with its randomness, payout and lifecycle details
+#[derive(Serialize)]
+struct Raffle {
+    raffle_id: i64,
+    raffle_address: String,
+    creator: String,
+    end_time: Option<DateTime<Utc>>,
+    ticket_price: Amount,
+    max_tickets: i64,
+    fee_bps: i64,
+    fee_recipient: String,
+    status: String,
+    /// Display status (ACTIVE raffles past `end_time` are reported as ENDED)
+    effective_status: String,
+    total_tickets: i64,
+    unique_buyers: i64,
+    pot: Amount,
+    keeper: Option<String>,
+    request_id: Option<String>,
+    request_tx: Option<String>,
+    randomness: Option<String>,
+    randomness_tx: Option<String>,
+    winning_index: Option<i64>,
+    winner: Option<String>,
+    finalized_tx: Option<String>,
+    /// Amount paid to the winner (set once payouts completed)
+    prize_amount: Option<Amount>,
+    /// Protocol fee paid to `fee_recipient` (set once payouts completed)
+    fee_amount: Option<Amount>,
+    payout_tx: Option<String>,
+    /// Whether the payout transaction's receipt shows the expected token transfers
+    /// (null until checked)
+    payout_confirmed: Option<bool>,
+    progress: RaffleProgress,
+    /// Where the data came from: `index` (database) or `chain` (direct contract reads)
+    source: &'static str,
+}
+
+impl Raffle {
+    fn new(details: RaffleDetails, decimals: u32) -> Self {
+        let amount = |value: Option<String>| value.map(|value| Amount::new(value, decimals));
+        Raffle {
+            raffle_id: details.raffle_id,
+            raffle_address: checksummed(details.raffle_address),
+            creator: checksummed(details.creator),
+            end_time: details.end_time,
+            ticket_price: Amount::new(details.ticket_price, decimals),
+            max_tickets: details.max_tickets,
+            fee_bps: details.fee_bps,
+            fee_recipient: checksummed(details.fee_recipient),
+            status: details.status,
+            effective_status: details.effective_status,
+            total_tickets: details.total_tickets,
+            unique_buyers: details.unique_buyers,
+            pot: Amount::new(details.pot, decimals),
+            keeper: details.keeper.map(checksummed),
+            request_id: details.request_id,
+            request_tx: details.request_tx,
+            randomness: details.randomness,
+            randomness_tx: details.randomness_tx,
+            winning_index: details.winning_index,
+            winner: details.winner.map(checksummed),
+            finalized_tx: details.finalized_tx,
+            prize_amount: amount(details.prize_amount),
+            fee_amount: amount(details.fee_amount),
+            payout_tx: details.payout_tx,
+            payout_confirmed: details.payout_confirmed,
+            progress: details.progress,
+            source: details.source,
+        }
+    }
+}
+
+/// A range of tickets bought in one transaction
+#[derive(Serialize)]
+struct Purchase {
+    buyer: String,
+    start_index: i64,
+    end_index: i64,
+    count: i64,
+    amount: Amount,
+    /// Reached the whale purchase threshold when indexed
+    whale: bool,
+    /// The buyer is on the admin blocklist
+    buyer_blocked: bool,
+    tx_hash: String,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    /// The block is at or below the chain's finalized block; otherwise a reorg may still drop it
+    finalized: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl Purchase {
+    fn new(purchase: PurchaseRange, decimals: u32) -> Self {
+        Purchase {
+            buyer: checksummed(purchase.buyer),
+            start_index: purchase.start_index,
+            end_index: purchase.end_index,
+            count: purchase.count,
+            amount: Amount::new(purchase.amount, decimals),
+            whale: purchase.whale,
+            buyer_blocked: purchase.buyer_blocked,
+            tx_hash: purchase.tx_hash,
+            log_index: purchase.log_index,
+            block_number: purchase.block_number,
+            block_hash: purchase.block_hash,
+            block_time: purchase.block_time,
+            finalized: purchase.finalized,
+            created_at: purchase.created_at,
+        }
+    }
+}
+
+// ============================================================================
+// QUERIES
+// ============================================================================
+
+/// Query parameters for listing raffles
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListRafflesQuery {
+    limit: Option<i64>,
+    cursor: Option<String>,
+    /// Filter by effective status (see `GET /v1/raffles`)
+    status: Option<String>,
+    /// Include raffles by blocked creators; requires an admin credential
+    #[serde(default)]
+    include_blocked: bool,
+}
+
+impl Validate for ListRafflesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, None)?;
+        if let Some(cursor) = &self.cursor {
+            decode_cursor::<1>(RAFFLES_CURSOR, cursor)?;
+        }
+        if let Some(status) = &self.status
+            && !RAFFLE_STATUSES.contains(&status.as_str())
+        {
+            return Err(ApiError::InvalidStatus {
+                status: status.clone(),
+                allowed: RAFFLE_STATUSES,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Query parameters for raffle details
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RaffleQuery {
+    /// `chain` reads raffles missing from the database from the contracts
+    fallback: Option<String>,
+}
+
+impl Validate for RaffleQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        chain_fallback(self.fallback.as_deref()).map(|_| ())
+    }
+}
+
+/// Query parameters for a raffle's purchases
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListPurchasesQuery {
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+impl Validate for ListPurchasesQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_page(self.limit, None)?;
+        if let Some(cursor) = &self.cursor {
+            decode_cursor::<2>(PURCHASES_CURSOR, cursor)?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// HANDLERS
+// ============================================================================
+
+/// GET /v2/raffles - List raffles, newest first
+///
+/// Raffles created by blocked addresses are left out unless `include_blocked` is set
+/// by an admin; those responses aren't cached.
+async fn list_raffles(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<ListRafflesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let decimals = state.config.token_decimals;
+    if params.include_blocked {
+        // Every admin role may read the full list
+        let admin = auth::authenticate(&state.config, &headers)?;
+        tracing::debug!(by = admin.subject, "raffle list includes blocked creators");
+    }
+    let before_raffle_id = params
+        .cursor
+        .as_deref()
+        .map(|cursor| decode_cursor::<1>(RAFFLES_CURSOR, cursor))
+        .transpose()?
+        .map(|[raffle_id]| raffle_id);
+
+    let filter = RaffleListFilter {
+        limit: limit + 1,
+        offset: 0,
+        before_raffle_id,
+        status: params.status,
+        include_blocked: params.include_blocked,
+    };
+    let now = Utc::now();
+    let raffles = fetch_raffle_list(&state.db, &filter)
+        .await?
+        .into_iter()
+        .map(|row| RaffleSummary {
+            raffle_id: row.raffle_id,
+            raffle_address: checksummed(row.raffle_address),
+            status: row.status,
+            effective_status: row.effective_status,
+            end_time: row.end_time,
+            ticket_price: Amount::new(row.ticket_price, decimals),
+            max_tickets: row.max_tickets,
+            total_tickets: row.total_tickets,
+            unique_buyers: row.unique_buyers,
+            pot: Amount::new(row.pot, decimals),
+            winner: row.winner.map(checksummed),
+            progress: RaffleProgress::compute(
+                row.end_time,
+                row.max_tickets,
+                row.total_tickets,
+                now,
+            ),
+        })
+        .collect();
+
+    let page = Page::new(raffles, limit, |raffle: &RaffleSummary| {
+        encode_cursor(RAFFLES_CURSOR, &[raffle.raffle_id])
+    });
+    let mut response = Json(page).into_response();
+    if params.include_blocked {
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-store"),
+        );
+    }
+    Ok(response)
+}
+
+/// GET /v2/raffles/:raffle_id - Get raffle details by ID
+///
+/// With `?fallback=chain`, a raffle missing from the database is read from the
+/// contracts instead.
+async fn get_raffle(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<RaffleQuery>,
+) -> Result<(Option<Extension<LastModified>>, Json<Data<Raffle>>), ApiError> {
+    let details = match fetch_raffle_details(&state.db, raffle_id).await? {
+        Some(details) => details,
+        None if chain_fallback(params.fallback.as_deref())? => {
+            fetch_raffle_from_chain(&state, raffle_id).await?
+        }
+        None => return Err(ApiError::RaffleNotFound),
+    };
+    let last_modified = details.last_modified.map(|at| Extension(LastModified(at)));
+    Ok((
+        last_modified,
+        Json(Data {
+            data: Raffle::new(details, state.config.token_decimals),
+        }),
+    ))
+}
+
+/// GET /v2/raffles/:raffle_id/purchases - List ticket purchases for a raffle in chain order
+async fn list_purchases(
+    State(state): State<AppState>,
+    ValidatedPath(RafflePath { raffle_id }): ValidatedPath<RafflePath>,
+    ValidatedQuery(params): ValidatedQuery<ListPurchasesQuery>,
+) -> Result<Json<Page<Purchase>>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    let decimals = state.config.token_decimals;
+    let after = params
+        .cursor
+        .as_deref()
+        .map(|cursor| decode_cursor::<2>(PURCHASES_CURSOR, cursor))
+        .transpose()?;
+
+    let query = PurchasesQuery {
+        limit: Some(limit + 1),
+        offset: None,
+        after_block: after.map(|[block, _]| block),
+        after_log_index: after.map(|[_, log_index]| log_index),
+        format: AmountFormat::Raw,
+    };
+    let purchases = load_purchase_page(&state, raffle_id, &query)
+        .await?
+        .into_iter()
+        .map(|purchase| Purchase::new(purchase, decimals))
+        .collect();
+
+    Ok(Json(Page::new(purchases, limit, |purchase: &Purchase| {
+        encode_cursor(
+            PURCHASES_CURSOR,
+            &[purchase.block_number, purchase.log_index],
+        )
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip_per_list() {
+        let cursor = encode_cursor(PURCHASES_CURSOR, &[102, 7]);
+        assert_eq!(
+            decode_cursor::<2>(PURCHASES_CURSOR, &cursor).unwrap(),
+            [102, 7]
+        );
+        assert!(decode_cursor::<1>(RAFFLES_CURSOR, &cursor).is_err());
+        assert!(decode_cursor::<1>(RAFFLES_CURSOR, "not a cursor").is_err());
+        assert!(decode_cursor::<1>(RAFFLES_CURSOR, &encode_cursor(RAFFLES_CURSOR, &[-1])).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Issued API keys with usage quotas
+//!
+//! Third-party developers get a key from an admin (`POST /v1/admin/api-keys`) and
+//! send it as `X-API-Key`. Requests without a key are served as before; requests
+//! with a key are checked and metered:
+//! - Unknown or revoked keys get 401
+//! - Each key has a per-minute rate limit (token bucket, per process) and a daily
+//!   quota counted in `api_key_usage` per UTC day (shared by all replicas); either
+//!   limit answers 429 with `Retry-After`
+//! - `GET /v1/usage` only checks the key; it isn't rate limited or counted
+//! - Keyed responses carry `X-RateLimit-Limit`, `X-RateLimit-Remaining` and
+//!   `X-RateLimit-Reset` for the daily quota
+//!
+//! # Security Considerations
+//! - Only a SHA-256 hash of each key is stored; the key is shown once when minted
+//! - Key lookups are cached for [`KEY_CACHE_TTL`], so a revocation takes effect
+//!   within that time
+//! - The lookup cache and rate limiter hold a bounded number of entries
+
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::extract::{Request, State};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{Days, Utc};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Request header carrying the key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Prefix of every issued key, so leaked keys are easy to recognize
+const KEY_PREFIX: &str = "ta_";
+
+/// Random bytes per key
+const KEY_BYTES: usize = 24;
+
+/// Characters of the key kept in `api_keys.key_prefix`
+const KEY_DISPLAY_PREFIX_LEN: usize = 11;
+
+/// How long a key lookup (including a miss) is reused
+pub const KEY_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Route (relative to the deployment prefix) that reports usage; checked but not metered,
+/// so a key over its quota can still see why
+const USAGE_PATH: &str = "/usage";
+
+/// Maximum cached key lookups and rate limit buckets
+const MAX_ENTRIES: usize = 10_000;
+
+/// A key as seen by request metering
+#[derive(Clone, Copy)]
+struct KeyInfo {
+    id: i64,
+    daily_quota: i64,
+    rate_limit_per_minute: i32,
+}
+
+/// A freshly minted key; `key` is never stored
+pub struct NewKey {
+    pub key: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+}
+
+/// Generates a random key with its display prefix and hash
+pub fn generate_key() -> anyhow::Result<NewKey> {
+    let mut bytes = [0u8; KEY_BYTES];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("failed to generate random key"))?;
+    let key = format!("{KEY_PREFIX}{}", hex::encode(bytes));
+    Ok(NewKey {
+        key_prefix: key[..KEY_DISPLAY_PREFIX_LEN].to_string(),
+        key_hash: hash_key(&key),
+        key,
+    })
+}
+
+/// Hex SHA-256 of a key, as stored in `api_keys.key_hash`
+pub fn hash_key(key: &str) -> String {
+    hex::encode(digest::digest(&digest::SHA256, key.as_bytes()))
+}
+
+/// A cached key lookup (`None` for unknown or revoked keys) and when it was made
+type CachedLookup = (Option<KeyInfo>, Instant);
+
+/// Key lookup cache and per-key rate limiter; cheap to clone
+#[derive(Clone, Default)]
+pub struct ApiKeyGuard {
+    /// Key hash -> last lookup
+    keys: Arc<Mutex<HashMap<String, CachedLookup>>>,
+    /// Key ID -> available requests and when they were last topped up
+    buckets: Arc<Mutex<HashMap<i64, (f64, Instant)>>>,
+}
+
+impl ApiKeyGuard {
+    async fn lookup(&self, db: &PgPool, key_hash: &str) -> Result<Option<KeyInfo>, sqlx::Error> {
+        {
+            let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((info, looked_up)) = keys.get(key_hash)
+                && looked_up.elapsed() < KEY_CACHE_TTL
+            {
+                return Ok(*info);
+            }
+        }
+
+        let info = sqlx::query(
+            "SELECT id, daily_quota, rate_limit_per_minute
+             FROM api_keys
+             WHERE key_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(key_hash)
+        .fetch_optional(db)
+        .await?
+        .map(|row| -> Result<KeyInfo, sqlx::Error> {
+            Ok(KeyInfo {
+                id: row.try_get("id")?,
+                daily_quota: row.try_get("daily_quota")?,
+                rate_limit_per_minute: row.try_get("rate_limit_per_minute")?,
+            })
+        })
+        .transpose()?;
+
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if keys.len() >= MAX_ENTRIES {
+            keys.retain(|_, (_, looked_up)| looked_up.elapsed() < KEY_CACHE_TTL);
+            if keys.len() >= MAX_ENTRIES {
+                keys.clear();
+            }
+        }
+        keys.insert(key_hash.to_string(), (info, Instant::now()));
+        Ok(info)
+    }
+
+    /// Takes one request from the key's bucket, or returns how long until one is available
+    fn try_acquire(&self, key: &KeyInfo) -> Result<(), Duration> {
+        let per_second = f64::from(key.rate_limit_per_minute.max(1)) / 60.0;
+        let capacity = f64::from(key.rate_limit_per_minute.max(1));
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_ENTRIES && !buckets.contains_key(&key.id) {
+            // Full buckets are the same as no bucket
+            buckets.retain(|_, (available, _)| *available < capacity);
+        }
+        let (available, last_refill) = buckets.entry(key.id).or_insert((capacity, now));
+        *available = (*available + now.duration_since(*last_refill).as_secs_f64() * per_second)
+            .min(capacity);
+        *last_refill = now;
+        if *available < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - *available) / per_second));
+        }
+        *available -= 1.0;
+        Ok(())
+    }
+}
+
+/// Seconds until the daily quota resets (next UTC midnight)
+fn secs_until_reset() -> u64 {
+    let now = Utc::now();
+    now.date_naive()
+        .checked_add_days(Days::new(1))
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map(|midnight| (midnight.and_utc() - now).num_seconds().max(1) as u64)
+        .unwrap_or(1)
+}
+
+/// Middleware checking and metering requests that carry `X-API-Key`
+pub async fn enforce(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(key) = request.headers().get(API_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key_hash = hash_key(key.to_str().unwrap_or_default());
+
+    let info = match state.api_keys.lookup(&state.db, &key_hash).await {
+        Ok(Some(info)) => info,
+        Ok(None) => return ApiError::InvalidApiKey.into_response(),
+        Err(err) => {
+            tracing::error!(error = %err, "API key lookup failed");
+            return ApiError::ApiKeyCheckUnavailable.into_response();
+        }
+    };
+
+    if request.uri().path() == USAGE_PATH {
+        return next.run(request).await;
+    }
+
+    if let Err(wait) = state.api_keys.try_acquire(&info) {
+        let retry_after_secs = wait.as_secs_f64().ceil().max(1.0) as u64;
+        return ApiError::RateLimited { retry_after_secs }.into_response();
+    }
+
+    // Count the request; the key is over quota once the count exceeds it
+    let used: i64 = match sqlx::query_scalar(
+        "INSERT INTO api_key_usage (key_id, day, requests)
+         VALUES ($1, (now() AT TIME ZONE 'UTC')::date, 1)
+         ON CONFLICT (key_id, day) DO UPDATE SET requests = api_key_usage.requests + 1
+         RETURNING requests",
+    )
+    .bind(info.id)
+    .fetch_one(&state.db)
+    .await
+    {
+        Ok(used) => used,
+        Err(err) => {
+            tracing::error!(error = %err, "API key usage update failed");
+            return ApiError::ApiKeyCheckUnavailable.into_response();
+        }
+    };
+    let reset = secs_until_reset();
+
+    if used > info.daily_quota {
+        let rejected = sqlx::query(
+            "UPDATE api_key_usage SET rejected = rejected + 1
+             WHERE key_id = $1 AND day = (now() AT TIME ZONE 'UTC')::date",
+        )
+        .bind(info.id)
+        .execute(&state.db)
+        .await;
+        if let Err(err) = rejected {
+            tracing::warn!(error = %err, "failed to count rejected API key request");
+        }
+        let mut response = ApiError::QuotaExceeded {
+            retry_after_secs: reset,
+        }
+        .into_response();
+        insert_quota_headers(&mut response, info.daily_quota, 0, reset);
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    insert_quota_headers(
+        &mut response,
+        info.daily_quota,
+        info.daily_quota - used,
+        reset,
+    );
+    response
+}
+
+fn insert_quota_headers(response: &mut Response, limit: i64, remaining: i64, reset: u64) {
+    let headers = response.headers_mut();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining.max(0)));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(reset));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(rate_limit_per_minute: i32) -> KeyInfo {
+        KeyInfo {
+            id: 1,
+            daily_quota: 1_000,
+            rate_limit_per_minute,
+        }
+    }
+
+    #[test]
+    fn generated_keys_are_stored_as_hashes() {
+        let new_key = generate_key().unwrap();
+        assert!(new_key.key.starts_with(KEY_PREFIX));
+        assert_eq!(new_key.key.len(), KEY_PREFIX.len() + 2 * KEY_BYTES);
+        assert!(new_key.key.starts_with(&new_key.key_prefix));
+        assert_eq!(new_key.key_prefix.len(), KEY_DISPLAY_PREFIX_LEN);
+        assert_eq!(new_key.key_hash, hash_key(&new_key.key));
+        assert_ne!(generate_key().unwrap().key, new_key.key);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_refills_over_the_minute() {
+        let guard = ApiKeyGuard::default();
+        let key = key(2);
+        assert_eq!(guard.try_acquire(&key), Ok(()));
+        assert_eq!(guard.try_acquire(&key), Ok(()));
+        assert_eq!(guard.try_acquire(&key), Err(Duration::from_secs(30)));
+
+        tokio::time::advance(Duration::from_secs(15)).await;
+        assert_eq!(guard.try_acquire(&key), Err(Duration::from_secs(15)));
+        tokio::time::advance(Duration::from_secs(15)).await;
+        assert_eq!(guard.try_acquire(&key), Ok(()));
+        assert!(guard.try_acquire(&key).is_err());
+
+        // A full minute refills the bucket, but never beyond one minute's worth
+        tokio::time::advance(Duration::from_secs(600)).await;
+        assert_eq!(guard.try_acquire(&key), Ok(()));
+        assert_eq!(guard.try_acquire(&key), Ok(()));
+        assert!(guard.try_acquire(&key).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keys_have_separate_buckets() {
+        let guard = ApiKeyGuard::default();
+        let first = key(1);
+        let second = KeyInfo { id: 2, ..first };
+        assert_eq!(guard.try_acquire(&first), Ok(()));
+        assert!(guard.try_acquire(&first).is_err());
+        assert_eq!(guard.try_acquire(&second), Ok(()));
+    }
+
+    #[test]
+    fn quota_resets_by_next_utc_midnight() {
+        let secs = secs_until_reset();
+        assert!((1..=86_400).contains(&secs), "{secs}");
+    }
+}
@@ -0,0 +1,109 @@
+//! Archival of completed raffles
+//!
+//! With `ARCHIVE_AFTER_DAYS` set, periodically moves raffles that are finalized or
+//! fully refunded (REFUNDING or CANCELED with nothing left in the pot) and haven't
+//! changed for that many days into `raffles_archive`, together with their purchases
+//! and refunds (`purchases_archive`, `refunds_archive`). List endpoints only see the
+//! hot tables; per-raffle endpoints read the `raffles_all` / `purchases_all` views,
+//! which include the archive.
+//!
+//! Raffles with undelivered refund reminders or whale alerts, or with a keeper
+//! transaction still pending, are left for a later run. Archived raffles drop out of
+//! the indexer's watched addresses once it restarts.
+
+use crate::config::AppConfig;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// How often the job looks for raffles to archive
+const ARCHIVE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Raffles moved per transaction
+const BATCH_SIZE: i64 = 100;
+
+/// Runs the archive job until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig) -> anyhow::Result<()> {
+    let Some(after_days) = config.archive_after_days else {
+        return Ok(());
+    };
+    loop {
+        match archive_completed(&db, after_days).await {
+            Ok(0) => {}
+            Ok(archived) => tracing::info!(archived, "completed raffles archived"),
+            Err(err) => tracing::warn!(error = %format!("{err:#}"), "archive cycle failed"),
+        }
+        tokio::time::sleep(ARCHIVE_INTERVAL).await;
+    }
+}
+
+/// Archives every eligible raffle in batches, returning how many were moved
+pub(crate) async fn archive_completed(db: &PgPool, after_days: i32) -> anyhow::Result<u64> {
+    let mut archived = 0;
+    loop {
+        let moved = archive_batch(db, after_days).await?;
+        archived += moved;
+        if moved < BATCH_SIZE as u64 {
+            return Ok(archived);
+        }
+    }
+}
+
+/// Moves one batch of eligible raffles and their rows in a single transaction
+async fn archive_batch(db: &PgPool, after_days: i32) -> anyhow::Result<u64> {
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+
+    let raffle_ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT r.raffle_id FROM raffles r
+         WHERE r.updated_at < now() - make_interval(days => $1)
+           AND (r.status = 'FINALIZED' OR (r.status IN ('REFUNDING', 'CANCELED') AND r.pot = 0))
+           AND NOT EXISTS (
+               SELECT 1 FROM refund_reminders m
+               WHERE m.raffle_id = r.raffle_id AND m.status = 'pending'
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM whale_alerts w JOIN purchases p ON p.id = w.purchase_id
+               WHERE p.raffle_id = r.raffle_id AND w.status = 'pending'
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM keeper_txs k
+               WHERE k.raffle_id = r.raffle_id AND k.status = 'pending'
+           )
+         ORDER BY r.raffle_id
+         LIMIT $2
+         FOR UPDATE SKIP LOCKED",
+    )
+    .bind(after_days)
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *db_tx)
+    .await
+    .context("failed to select raffles to archive")?;
+    if raffle_ids.is_empty() {
+        return Ok(0);
+    }
+
+    // Children first; conflicts are rows archived before a re-index of the raffle
+    for (table, archive) in [
+        ("purchases", "purchases_archive"),
+        ("refunds", "refunds_archive"),
+        ("raffles", "raffles_archive"),
+    ] {
+        sqlx::query(&format!(
+            "WITH moved AS (
+                DELETE FROM {table} WHERE raffle_id = ANY($1) RETURNING *
+            )
+            INSERT INTO {archive} SELECT * FROM moved
+            ON CONFLICT DO NOTHING"
+        ))
+        .bind(&raffle_ids)
+        .execute(&mut *db_tx)
+        .await
+        .with_context(|| format!("failed to move {table} to {archive}"))?;
+    }
+
+    db_tx
+        .commit()
+        .await
+        .context("failed to commit archive batch")?;
+    Ok(raffle_ids.len() as u64)
+}
@@ -0,0 +1,288 @@
+//! Scoped admin tokens
+//!
+//! `ADMIN_API_KEY` is one master credential with every permission. With
+//! `ADMIN_JWT_SECRET` set, admins can also issue tokens for a person and a [`Role`]
+//! (`POST /v1/admin/tokens`), so on-call engineers don't need the master key:
+//! - `readonly` reads the admin endpoints (overview, queues, keys, exports, anomalies)
+//! - `operator` can also pause and resume the indexer
+//! - `admin` can do everything the master key can
+//!
+//! Tokens are JWTs (RFC 7519) signed with HMAC-SHA256 and sent like the master key,
+//! as `Authorization: Bearer <token>`. They carry `sub`, `role`, `iat` and `exp` and
+//! are valid until `exp` (at most `ADMIN_JWT_MAX_TTL_SECS` after issue).
+//!
+//! # Security Considerations
+//! - Tokens aren't stored, so one can't be revoked on its own; rotating
+//!   `ADMIN_JWT_SECRET` revokes all of them. Keep lifetimes short.
+//! - Only `HS256` is accepted; the header's `alg` is checked, not trusted
+//! - Signatures are compared in constant time
+
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use axum::http::{HeaderMap, header};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use chrono::Utc;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+/// Subject recorded for requests made with the master key
+pub const MASTER_KEY_SUBJECT: &str = "admin-api-key";
+
+/// Admin permissions, each including the ones before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[serde(rename = "readonly")]
+    ReadOnly,
+    Operator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::ReadOnly => "readonly",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// The role an admin route requires, named in the extractor's type
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+/// Read-only admin routes
+pub struct RequireReadOnly;
+
+/// Routes changing how the deployment runs (pausing the indexer)
+pub struct RequireOperator;
+
+/// Routes changing data or credentials
+pub struct RequireAdmin;
+
+impl RequiredRole for RequireReadOnly {
+    const ROLE: Role = Role::ReadOnly;
+}
+
+impl RequiredRole for RequireOperator {
+    const ROLE: Role = Role::Operator;
+}
+
+impl RequiredRole for RequireAdmin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// An authenticated admin
+pub struct Principal {
+    /// Token subject, or [`MASTER_KEY_SUBJECT`]
+    pub subject: String,
+    pub role: Role,
+}
+
+/// Identifies the admin behind a request's `Authorization: Bearer` credential
+///
+/// Fails with 404 when neither `ADMIN_API_KEY` nor `ADMIN_JWT_SECRET` is configured,
+/// so admin routes aren't advertised, and with 401 for any other credential.
+pub fn authenticate(config: &AppConfig, headers: &HeaderMap) -> Result<Principal, ApiError> {
+    let master_key = config.admin_api_key.as_deref();
+    let jwt_secret = config.admin_jwt_secret.as_deref();
+    if master_key.is_none() && jwt_secret.is_none() {
+        return Err(ApiError::NotFound("not found"));
+    }
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if master_key.is_some_and(|key| constant_time_eq(provided.as_bytes(), key.as_bytes())) {
+        return Ok(Principal {
+            subject: MASTER_KEY_SUBJECT.to_string(),
+            role: Role::Admin,
+        });
+    }
+
+    let Some(secret) = jwt_secret.filter(|_| looks_like_jwt(provided)) else {
+        return Err(ApiError::InvalidAdminCredentials);
+    };
+    let claims = verify(secret.as_bytes(), provided, Utc::now().timestamp()).map_err(|err| {
+        tracing::debug!(error = %err, "admin token refused");
+        ApiError::InvalidAdminCredentials
+    })?;
+    Ok(Principal {
+        subject: claims.sub,
+        role: claims.role,
+    })
+}
+
+/// Compares secrets without short-circuiting on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Payload of an admin token
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    /// Who the token was issued to
+    pub sub: String,
+    pub role: Role,
+    /// Issue time (unix seconds)
+    pub iat: i64,
+    /// Expiry (unix seconds)
+    pub exp: i64,
+}
+
+/// Why a token was refused (only logged; clients get a plain 401)
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TokenError {
+    #[error("token is not a JWT")]
+    Malformed,
+    #[error("token algorithm is not HS256")]
+    UnsupportedAlgorithm,
+    #[error("token signature is invalid")]
+    InvalidSignature,
+    #[error("token expired")]
+    Expired,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default)]
+    typ: Option<String>,
+}
+
+/// Whether a bearer credential has the shape of a JWT rather than the master key
+fn looks_like_jwt(credential: &str) -> bool {
+    credential.split('.').count() == 3
+}
+
+/// Signs `claims` into a compact JWT
+pub fn issue(secret: &[u8], claims: &Claims) -> String {
+    let header = Header {
+        alg: "HS256".to_string(),
+        typ: Some("JWT".to_string()),
+    };
+    // Serializing these structs can't fail
+    let header = BASE64URL.encode(serde_json::to_vec(&header).unwrap_or_default());
+    let payload = BASE64URL.encode(serde_json::to_vec(claims).unwrap_or_default());
+    let signing_input = format!("{header}.{payload}");
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    let signature = BASE64URL.encode(hmac::sign(&key, signing_input.as_bytes()));
+    format!("{signing_input}.{signature}")
+}
+
+/// Checks a token's algorithm, signature and expiry at `now` (unix seconds)
+pub fn verify(secret: &[u8], token: &str, now: i64) -> Result<Claims, TokenError> {
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(TokenError::Malformed);
+    };
+
+    let header: Header = BASE64URL
+        .decode(header)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or(TokenError::Malformed)?;
+    if header.alg != "HS256" {
+        return Err(TokenError::UnsupportedAlgorithm);
+    }
+
+    let signature = BASE64URL
+        .decode(signature)
+        .map_err(|_| TokenError::Malformed)?;
+    let signing_input = &token[..header_and_payload_len(token)];
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hmac::verify(&key, signing_input.as_bytes(), &signature)
+        .map_err(|_| TokenError::InvalidSignature)?;
+
+    let claims: Claims = BASE64URL
+        .decode(payload)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or(TokenError::Malformed)?;
+    if claims.exp <= now {
+        return Err(TokenError::Expired);
+    }
+    Ok(claims)
+}
+
+/// Length of `header.payload`, the signed part of a token
+fn header_and_payload_len(token: &str) -> usize {
+    token.rfind('.').unwrap_or(token.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"an-admin-jwt-secret-of-32-bytes!";
+
+    fn claims(role: Role, exp: i64) -> Claims {
+        Claims {
+            sub: "alice".to_string(),
+            role,
+            iat: 1_000,
+            exp,
+        }
+    }
+
+    #[test]
+    fn issued_tokens_verify_until_expiry() {
+        let token = issue(SECRET, &claims(Role::Operator, 2_000));
+        assert!(looks_like_jwt(&token));
+        assert_eq!(
+            verify(SECRET, &token, 1_999),
+            Ok(claims(Role::Operator, 2_000))
+        );
+        assert_eq!(verify(SECRET, &token, 2_000), Err(TokenError::Expired));
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_refused() {
+        let token = issue(SECRET, &claims(Role::ReadOnly, 2_000));
+        assert_eq!(
+            verify(b"another-secret-of-at-least-32-by", &token, 1_500),
+            Err(TokenError::InvalidSignature)
+        );
+
+        // Same signature over an escalated role
+        let (_, rest) = token.split_once('.').unwrap();
+        let (_, signature) = rest.split_once('.').unwrap();
+        let escalated = issue(SECRET, &claims(Role::Admin, 2_000));
+        let (signed, _) = escalated.rsplit_once('.').unwrap();
+        assert_eq!(
+            verify(SECRET, &format!("{signed}.{signature}"), 1_500),
+            Err(TokenError::InvalidSignature)
+        );
+
+        let unsigned = format!(
+            "{}.{}.",
+            BASE64URL.encode(br#"{"alg":"none"}"#),
+            signed.split_once('.').unwrap().1
+        );
+        assert_eq!(
+            verify(SECRET, &unsigned, 1_500),
+            Err(TokenError::UnsupportedAlgorithm)
+        );
+        assert_eq!(
+            verify(SECRET, "not-a-token", 1_500),
+            Err(TokenError::Malformed)
+        );
+    }
+
+    #[test]
+    fn roles_include_lower_ones() {
+        assert!(Role::Admin >= RequireOperator::ROLE);
+        assert!(Role::Operator >= RequireReadOnly::ROLE);
+        assert!(Role::ReadOnly < RequireOperator::ROLE);
+        assert_eq!(
+            serde_json::to_string(&Role::ReadOnly).unwrap(),
+            "\"readonly\""
+        );
+    }
+}
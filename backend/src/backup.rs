@@ -0,0 +1,433 @@
+//! Raw event backups for disaster recovery
+//!
+//! `backup-raw` streams `events_raw` as gzip-compressed NDJSON to `BACKUP_BUCKET`.
+//! `restore-raw` loads such a backup into a freshly migrated database and rebuilds
+//! raffles, purchases, refunds and every other derived table by replaying the events
+//! through the indexer (see [`indexer::replay_raw_events`]), so recovering doesn't
+//! require re-crawling the chain from `START_BLOCK`. Payout confirmations aren't
+//! events; the payout check (see [`crate::payouts`]) makes them again afterwards.
+//!
+//! The first line of a backup is a [`BackupHeader`]; every following line is one
+//! event, in chain order. The rows are read in one repeatable-read transaction, so a
+//! backup matches a single indexer checkpoint. Rows indexed before all topics were
+//! stored are completed from their transaction receipts before the backup is written.
+
+use crate::config::AppConfig;
+use crate::indexer;
+use crate::rpc::RpcBudget;
+use crate::storage::{Bucket, MultipartUpload};
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::H256;
+use flate2::Compression;
+use flate2::write::{GzDecoder, GzEncoder};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPoolOptions, PgRow};
+use sqlx::{PgPool, Row};
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Value of [`BackupHeader::format`]
+const FORMAT: &str = "events_raw";
+
+/// Bumped when the line format changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+
+/// Compressed bytes buffered before they are uploaded as one part
+const PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// Events inserted per statement when restoring
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// Timeout for each transaction receipt request
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+const CONTENT_TYPE: &str = "application/gzip";
+
+/// First line of a backup
+#[derive(Serialize, Deserialize)]
+struct BackupHeader {
+    format: String,
+    version: u32,
+    chain_id: u64,
+    /// Indexer checkpoint the backup is consistent with
+    checkpoint: i64,
+    /// Number of event lines that follow
+    events: i64,
+    created_at: DateTime<Utc>,
+}
+
+/// One `events_raw` row
+#[derive(Serialize, Deserialize)]
+struct RawEvent {
+    tx_hash: String,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    address: String,
+    /// All topics, starting with the event signature
+    topics: Vec<String>,
+    data: String,
+}
+
+impl RawEvent {
+    fn from_row(row: &PgRow) -> anyhow::Result<Self> {
+        let topics: Option<Vec<String>> = row.try_get("topics")?;
+        Ok(Self {
+            tx_hash: row.try_get("tx_hash")?,
+            log_index: row.try_get("log_index")?,
+            block_number: row.try_get("block_number")?,
+            block_hash: row.try_get("block_hash")?,
+            block_time: row.try_get("block_time")?,
+            address: row.try_get("address")?,
+            topics: topics.ok_or_else(|| anyhow!("raw event is missing its topics"))?,
+            data: row.try_get("data")?,
+        })
+    }
+}
+
+/// Writes a backup of `events_raw` to `key`, or to a timestamped key under
+/// `BACKUP_PREFIX`
+pub async fn backup_raw(config: &AppConfig, key: Option<String>) -> anyhow::Result<()> {
+    let backup = config
+        .backup
+        .as_ref()
+        .ok_or_else(|| anyhow!("BACKUP_BUCKET must be set to back up raw events"))?;
+    let bucket = Bucket::new(&backup.store)?;
+    let db = connect(config).await?;
+
+    fill_missing_topics(&db, config).await?;
+
+    let key = key.unwrap_or_else(|| {
+        let name = format!(
+            "events_raw-{}.ndjson.gz",
+            Utc::now().format("%Y%m%dT%H%M%SZ")
+        );
+        [
+            backup.prefix.as_str(),
+            config.deployment.as_deref().unwrap_or_default(),
+            &name,
+        ]
+        .into_iter()
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+    });
+
+    // One consistent view of the table
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to start backup transaction")?;
+    let checkpoint: i64 =
+        sqlx::query_scalar("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+            .fetch_optional(&mut *db_tx)
+            .await
+            .context("failed to read indexer checkpoint")?
+            .unwrap_or(0);
+    let events: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events_raw")
+        .fetch_one(&mut *db_tx)
+        .await
+        .context("failed to count raw events")?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let header = BackupHeader {
+        format: FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        chain_id: config.chain_id,
+        checkpoint,
+        events,
+        created_at: Utc::now(),
+    };
+    serde_json::to_writer(&mut encoder, &header)?;
+    encoder.write_all(b"\n")?;
+
+    // Parts are only used once the backup outgrows a single one
+    let mut upload = None;
+    let result = async {
+        let mut rows = sqlx::query(
+            "SELECT tx_hash, log_index, block_number, block_hash, block_time, address, topics, data
+             FROM events_raw
+             ORDER BY block_number, log_index",
+        )
+        .fetch(&mut *db_tx);
+        while let Some(row) = rows.try_next().await.context("failed to read raw events")? {
+            serde_json::to_writer(&mut encoder, &RawEvent::from_row(&row)?)?;
+            encoder.write_all(b"\n")?;
+            if encoder.get_ref().len() >= PART_SIZE {
+                let part = std::mem::take(encoder.get_mut());
+                upload_part(&bucket, &mut upload, &key, part).await?;
+            }
+        }
+        drop(rows);
+
+        let rest = encoder.finish()?;
+        match upload.take() {
+            None => bucket.put(&key, rest, CONTENT_TYPE).await,
+            Some(mut parts) => {
+                let result = bucket.upload_part(&mut parts, rest).await;
+                upload = Some(parts);
+                result?;
+                bucket
+                    .complete_multipart(upload.take().expect("upload started"))
+                    .await
+            }
+        }
+    }
+    .await;
+    if let Err(err) = result {
+        if let Some(parts) = upload
+            && let Err(abort_err) = bucket.abort_multipart(parts).await
+        {
+            tracing::warn!(error = %format!("{abort_err:#}"), "failed to abort backup upload");
+        }
+        return Err(err.context(format!("failed to upload {key}")));
+    }
+    db_tx.commit().await.context("failed to end backup")?;
+
+    println!(
+        "Backed up {events} raw events (checkpoint block {checkpoint}) to s3://{}/{key}",
+        backup.store.bucket
+    );
+    db.close().await;
+    Ok(())
+}
+
+/// Uploads one part, starting the multipart upload on the first one
+async fn upload_part(
+    bucket: &Bucket,
+    upload: &mut Option<MultipartUpload>,
+    key: &str,
+    part: Vec<u8>,
+) -> anyhow::Result<()> {
+    if upload.is_none() {
+        *upload = Some(bucket.start_multipart(key, CONTENT_TYPE).await?);
+    }
+    bucket
+        .upload_part(upload.as_mut().expect("upload started"), part)
+        .await
+}
+
+/// Fills in the topics of rows indexed before all topics were stored, using the
+/// logs of their transaction receipts
+async fn fill_missing_topics(db: &PgPool, config: &AppConfig) -> anyhow::Result<()> {
+    let tx_hashes: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT tx_hash FROM events_raw WHERE topics IS NULL")
+            .fetch_all(db)
+            .await
+            .context("failed to find raw events without topics")?;
+    if tx_hashes.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        transactions = tx_hashes.len(),
+        "completing topics of older raw events from transaction receipts"
+    );
+
+    let provider = RpcBudget::from_config(config).provider(&config.rpc_url, "backup")?;
+    for tx_hash in &tx_hashes {
+        let hash = H256::from_str(tx_hash).with_context(|| format!("invalid tx hash {tx_hash}"))?;
+        let receipt = tokio::time::timeout(RPC_TIMEOUT, provider.get_transaction_receipt(hash))
+            .await
+            .context("transaction receipt request timed out")?
+            .context("failed to get transaction receipt")?
+            .ok_or_else(|| anyhow!("no receipt for transaction {tx_hash}"))?;
+        for log in &receipt.logs {
+            let Some(log_index) = log.log_index else {
+                continue;
+            };
+            let topics: Vec<String> = log.topics.iter().map(|t| format!("{t:#x}")).collect();
+            sqlx::query(
+                "UPDATE events_raw SET topics = $3
+                 WHERE tx_hash = $1 AND log_index = $2 AND topics IS NULL AND topic0 = $3[1]",
+            )
+            .bind(tx_hash)
+            .bind(log_index.as_u64() as i64)
+            .bind(&topics)
+            .execute(db)
+            .await
+            .context("failed to store raw event topics")?;
+        }
+    }
+
+    let missing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events_raw WHERE topics IS NULL")
+        .fetch_one(db)
+        .await
+        .context("failed to count raw events without topics")?;
+    if missing > 0 {
+        anyhow::bail!("{missing} raw events have no matching log in their transaction receipt");
+    }
+    Ok(())
+}
+
+/// Loads the backup at `key` into an empty database and rebuilds the derived tables
+pub async fn restore_raw(config: &AppConfig, key: &str) -> anyhow::Result<()> {
+    let backup = config
+        .backup
+        .as_ref()
+        .ok_or_else(|| anyhow!("BACKUP_BUCKET must be set to restore raw events"))?;
+    let bucket = Bucket::new(&backup.store)?;
+    // Fail before restoring anything if the events can't be replayed
+    indexer::check_abis(config.randomness_provider_address.is_some())?;
+    let db = connect(config).await?;
+
+    let has_data: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM events_raw)
+             OR EXISTS (SELECT 1 FROM raffles)
+             OR EXISTS (SELECT 1 FROM raffles_archive)",
+    )
+    .fetch_one(&db)
+    .await
+    .context("failed to check for existing data")?;
+    if has_data {
+        anyhow::bail!(
+            "the database already has indexed events; restore into a freshly migrated one"
+        );
+    }
+
+    let mut response = bucket
+        .get(key)
+        .await
+        .with_context(|| format!("failed to download {key}"))?;
+    let mut decoder = GzDecoder::new(Vec::new());
+    let mut restore = Restore {
+        db: &db,
+        chain_id: config.chain_id,
+        header: None,
+        batch: Vec::new(),
+        restored: 0,
+    };
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("failed to download {key}"))?
+    {
+        decoder
+            .write_all(&chunk)
+            .context("backup is not valid gzip")?;
+        // Only complete lines; the rest waits for the next chunk
+        let buffer = decoder.get_mut();
+        let complete = buffer
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |index| index + 1);
+        let lines: Vec<u8> = buffer.drain(..complete).collect();
+        restore.lines(&lines).await?;
+    }
+    let rest = decoder.finish().context("backup is not valid gzip")?;
+    restore.lines(&rest).await?;
+    restore.flush().await?;
+
+    let Some(header) = restore.header else {
+        anyhow::bail!("{key} is empty");
+    };
+    if restore.restored != header.events {
+        anyhow::bail!(
+            "backup is incomplete: expected {} events, found {}",
+            header.events,
+            restore.restored
+        );
+    }
+    println!(
+        "Restored {} raw events, rebuilding derived tables...",
+        restore.restored
+    );
+
+    let summary = indexer::replay_raw_events(&db, config, header.checkpoint.max(0) as u64).await?;
+    println!(
+        "Replayed {} events ({} failed); indexer resumes after block {}",
+        summary.events, summary.failed, summary.checkpoint
+    );
+    db.close().await;
+    Ok(())
+}
+
+/// Parser state of a backup being restored
+struct Restore<'a> {
+    db: &'a PgPool,
+    chain_id: u64,
+    header: Option<BackupHeader>,
+    batch: Vec<RawEvent>,
+    restored: i64,
+}
+
+impl Restore<'_> {
+    /// Handles complete NDJSON lines
+    async fn lines(&mut self, lines: &[u8]) -> anyhow::Result<()> {
+        for line in lines.split(|byte| *byte == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+            if self.header.is_none() {
+                let header: BackupHeader = serde_json::from_slice(line)
+                    .ok()
+                    .filter(|header: &BackupHeader| header.format == FORMAT)
+                    .ok_or_else(|| anyhow!("not a raw event backup"))?;
+                if header.version != FORMAT_VERSION {
+                    anyhow::bail!("unsupported backup version {}", header.version);
+                }
+                if header.chain_id != self.chain_id {
+                    anyhow::bail!(
+                        "backup is for chain {}, but CHAIN_ID is {}",
+                        header.chain_id,
+                        self.chain_id
+                    );
+                }
+                self.header = Some(header);
+                continue;
+            }
+            self.batch
+                .push(serde_json::from_slice(line).context("invalid event in backup")?);
+            if self.batch.len() >= INSERT_BATCH_SIZE {
+                self.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts the buffered events
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let events = std::mem::take(&mut self.batch);
+        // Topics are joined since UNNEST can't yield arrays of arrays
+        sqlx::query(
+            "INSERT INTO events_raw
+                (tx_hash, log_index, block_number, block_hash, block_time, address, topic0, topics, data)
+             SELECT t.tx_hash, t.log_index, t.block_number, t.block_hash, t.block_time, t.address,
+                    split_part(t.topics, ',', 1), string_to_array(t.topics, ','), t.data
+             FROM UNNEST($1::text[], $2::bigint[], $3::bigint[], $4::text[], $5::timestamptz[],
+                         $6::text[], $7::text[], $8::text[])
+                 AS t (tx_hash, log_index, block_number, block_hash, block_time, address, topics, data)
+             ON CONFLICT (tx_hash, log_index) DO NOTHING",
+        )
+        .bind(events.iter().map(|e| e.tx_hash.clone()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.log_index).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.block_number).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.block_hash.clone()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.block_time).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.address.clone()).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.topics.join(",")).collect::<Vec<_>>())
+        .bind(events.iter().map(|e| e.data.clone()).collect::<Vec<_>>())
+        .execute(self.db)
+        .await
+        .context("failed to insert raw events")?;
+        self.restored += events.len() as i64;
+        Ok(())
+    }
+}
+
+async fn connect(config: &AppConfig) -> anyhow::Result<PgPool> {
+    PgPoolOptions::new()
+        .max_connections(2)
+        .acquire_timeout(Duration::from_secs(30))
+        .connect_with(config.pg_connect_options()?)
+        .await
+        .context("failed to connect to database")
+}
@@ -0,0 +1,532 @@
+//! Benchmark harness (`bench` subcommand)
+//!
+//! Seeds an empty database with a synthetic history, then measures the two paths
+//! that query and decoding changes tend to slow down:
+//!
+//! - **indexer throughput**: the synthetic events are written to `events_raw` and
+//!   replayed through the indexer's decoder and database writes
+//!   ([`indexer::replay_raw_events`]), so no RPC node is involved, and
+//! - **API latency**: a fixed set of read endpoints is requested through the real
+//!   router (in process, without the network), reporting percentiles per endpoint.
+//!
+//! Caches are disabled so every request reaches the database. The dataset stays in
+//! the database afterwards; point `DATABASE_URL` at a scratch database.
+//!
+//! [`synthetic_events`] is also used by the criterion benches under `benches/`.
+
+use crate::abi::Abis;
+use crate::circuit::CircuitBreaker;
+use crate::config::AppConfig;
+use crate::indexer::{self, IndexerStatus};
+use crate::rpc::RpcBudget;
+use crate::state::AppState;
+use crate::tasks::TaskGroup;
+use crate::{api_keys, cache, chain::ChainReader, live};
+use alloy::dyn_abi::DynSolValue;
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::{Address, B256, LogData, U256, keccak256};
+use alloy::rpc::types::Log;
+use anyhow::Context;
+use anyhow::anyhow;
+use axum::body::Body;
+use axum::http::Request;
+use axum::{Router, http::StatusCode};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+/// Events per synthetic block
+const EVENTS_PER_BLOCK: u64 = 10;
+
+/// Seconds between synthetic blocks
+const BLOCK_INTERVAL_SECS: i64 = 2;
+
+/// Distinct synthetic buyers
+const BUYERS: u64 = 1000;
+
+/// Rows per `events_raw` insert while seeding
+const SEED_BATCH_SIZE: usize = 1000;
+
+/// Size of the synthetic dataset and of the latency sample
+#[derive(Clone, Debug)]
+pub struct BenchOptions {
+    pub raffles: u64,
+    /// Purchases per raffle
+    pub purchases: u64,
+    /// Requests per endpoint
+    pub requests: usize,
+    /// Print the results as JSON instead of a table
+    pub json: bool,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            raffles: 200,
+            purchases: 50,
+            requests: 200,
+            json: false,
+        }
+    }
+}
+
+/// A synthetic log with the time of its block
+pub struct SyntheticEvent {
+    pub log: Log,
+    pub block_time: DateTime<Utc>,
+}
+
+/// Generates the event history of `raffles` raffles with `purchases` purchases each
+///
+/// Three raffles in four are closed and paid out after their purchases; the rest stay
+/// active. Logs are numbered from `start_block` in chain order, with block times
+/// ending now.
+pub fn synthetic_events(
+    abis: &Abis,
+    factory: Address,
+    start_block: u64,
+    raffles: u64,
+    purchases: u64,
+) -> anyhow::Result<Vec<SyntheticEvent>> {
+    let mut events = Vec::new();
+    // Far enough ahead that unfinished raffles stay active
+    let end_time = U256::from((Utc::now().timestamp() + 30 * 86_400) as u64);
+    let uint = |value: U256| DynSolValue::Uint(value, 256);
+    let fee_recipient = address_from_u64(0xfe);
+    for raffle_id in 1..=raffles {
+        let raffle = address_from_u64(0xa000_0000 + raffle_id);
+        let id = uint(U256::from(raffle_id));
+        let ticket_price = U256::from(1_000_000u64);
+        events.push((
+            factory,
+            encode(
+                &abis.factory,
+                "RaffleCreated",
+                vec![
+                    id.clone(),
+                    DynSolValue::Address(raffle),
+                    DynSolValue::Address(address_from_u64(0xc000_0000 + raffle_id % 50)),
+                    uint(end_time),
+                    uint(ticket_price),
+                    uint(U256::from((purchases * 5).max(1))),
+                    uint(U256::from(500u64)),
+                    DynSolValue::Address(fee_recipient),
+                ],
+            )?,
+        ));
+
+        let mut tickets = 0u64;
+        let mut first_buyer = None;
+        for purchase in 0..purchases {
+            let buyer =
+                address_from_u64(0xb000_0000 + (raffle_id * purchases + purchase) * 7919 % BUYERS);
+            first_buyer.get_or_insert(buyer);
+            let count = 1 + purchase % 5;
+            events.push((
+                raffle,
+                encode(
+                    &abis.raffle,
+                    "TicketsBought",
+                    vec![
+                        id.clone(),
+                        DynSolValue::Address(buyer),
+                        uint(U256::from(tickets)),
+                        uint(U256::from(tickets + count - 1)),
+                        uint(U256::from(count)),
+                        uint(ticket_price * U256::from(count)),
+                    ],
+                )?,
+            ));
+            tickets += count;
+        }
+
+        let Some(winner) = first_buyer.filter(|_| raffle_id % 4 != 0) else {
+            continue;
+        };
+        let pot = ticket_price * U256::from(tickets);
+        let fee = pot * U256::from(500) / U256::from(10_000);
+        let randomness = U256::from_be_bytes(keccak256(raffle_id.to_be_bytes()).0);
+        for (name, args) in [
+            (
+                "RaffleClosed",
+                vec![id.clone(), uint(U256::from(tickets)), uint(pot)],
+            ),
+            (
+                "RandomnessRequested",
+                vec![id.clone(), uint(U256::from(raffle_id))],
+            ),
+            (
+                "RandomnessFulfilled",
+                vec![id.clone(), uint(U256::from(raffle_id)), uint(randomness)],
+            ),
+            (
+                "WinnerSelected",
+                vec![
+                    id.clone(),
+                    DynSolValue::Address(winner),
+                    uint(U256::ZERO),
+                    uint(pot - fee),
+                    uint(fee),
+                ],
+            ),
+            (
+                "PayoutsCompleted",
+                vec![
+                    id.clone(),
+                    DynSolValue::Address(winner),
+                    DynSolValue::Address(fee_recipient),
+                    uint(pot - fee),
+                    uint(fee),
+                ],
+            ),
+        ] {
+            events.push((raffle, encode(&abis.raffle, name, args)?));
+        }
+    }
+
+    let blocks = (events.len() as u64).div_ceil(EVENTS_PER_BLOCK);
+    let first_time = Utc::now() - chrono::Duration::seconds(blocks as i64 * BLOCK_INTERVAL_SECS);
+    Ok(events
+        .into_iter()
+        .enumerate()
+        .map(|(seq, (address, (topics, data)))| {
+            let seq = seq as u64;
+            let block = seq / EVENTS_PER_BLOCK;
+            SyntheticEvent {
+                log: Log {
+                    inner: alloy::primitives::Log {
+                        address,
+                        data: LogData::new_unchecked(topics, data.into()),
+                    },
+                    block_hash: Some(keccak256(format!("block {block}"))),
+                    block_number: Some(start_block + block),
+                    transaction_hash: Some(keccak256(format!("tx {seq}"))),
+                    log_index: Some(seq % EVENTS_PER_BLOCK),
+                    ..Default::default()
+                },
+                block_time: first_time
+                    + chrono::Duration::seconds(block as i64 * BLOCK_INTERVAL_SECS),
+            }
+        })
+        .collect())
+}
+
+/// Encodes an event's arguments (in declaration order) as its topics and data
+fn encode(
+    abi: &JsonAbi,
+    name: &str,
+    args: Vec<DynSolValue>,
+) -> anyhow::Result<(Vec<B256>, Vec<u8>)> {
+    let event = abi
+        .event(name)
+        .and_then(|events| events.first())
+        .ok_or_else(|| anyhow!("no event {name}"))?;
+    let mut topics = vec![event.selector()];
+    let mut data = Vec::new();
+    for (input, token) in event.inputs.iter().zip(args) {
+        if input.indexed {
+            topics.push(B256::from_slice(&token.abi_encode()));
+        } else {
+            data.push(token);
+        }
+    }
+    Ok((topics, DynSolValue::Tuple(data).abi_encode_params()))
+}
+
+/// The address whose low eight bytes are `n`
+pub fn address_from_u64(n: u64) -> Address {
+    Address::left_padding_from(&n.to_be_bytes())
+}
+
+/// Latency percentiles of one endpoint, in milliseconds
+#[derive(Serialize)]
+struct EndpointReport {
+    endpoint: String,
+    requests: usize,
+    /// Responses other than 200
+    errors: usize,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+#[derive(Serialize)]
+struct BenchReport {
+    raffles: u64,
+    purchases_per_raffle: u64,
+    events: u64,
+    /// Events that failed to replay
+    failed_events: u64,
+    replay_secs: f64,
+    events_per_sec: f64,
+    endpoints: Vec<EndpointReport>,
+}
+
+/// Seeds the deployment's (empty) database and prints throughput and latencies
+pub async fn run(config: &AppConfig, options: &BenchOptions) -> anyhow::Result<()> {
+    // Fail before seeding anything if the events can't be replayed
+    indexer::check_abis(config.randomness_provider_address.is_some())?;
+    let db = PgPoolOptions::new()
+        .max_connections(config.api_pool.max_connections.max(2))
+        .acquire_timeout(Duration::from_secs(30))
+        .connect_with(config.pg_connect_options()?)
+        .await
+        .context("failed to connect to database")?;
+
+    let has_data: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM events_raw)
+             OR EXISTS (SELECT 1 FROM raffles)
+             OR EXISTS (SELECT 1 FROM raffles_archive)",
+    )
+    .fetch_one(&db)
+    .await
+    .context("failed to check for existing data")?;
+    if has_data {
+        anyhow::bail!("the database already has indexed events; benchmark a freshly migrated one");
+    }
+
+    let factory = Address::from_str(&config.raffle_factory_address)
+        .context("invalid RAFFLE_FACTORY_ADDRESS")?;
+    let events = synthetic_events(
+        &Abis::new(),
+        factory,
+        config.start_block,
+        options.raffles,
+        options.purchases,
+    )?;
+    tracing::info!(events = events.len(), "seeding synthetic events");
+    seed(&db, &events).await?;
+
+    let started = Instant::now();
+    let summary = indexer::replay_raw_events(&db, config, config.start_block).await?;
+    let replay = started.elapsed();
+
+    let router = crate::deployment_router(bench_state(config, &db)?);
+    let mut endpoints = Vec::new();
+    for (label, paths) in endpoint_paths(options.raffles, options.requests) {
+        endpoints.push(measure(&router, label, &paths).await?);
+    }
+
+    let report = BenchReport {
+        raffles: options.raffles,
+        purchases_per_raffle: options.purchases,
+        events: summary.events,
+        failed_events: summary.failed,
+        replay_secs: replay.as_secs_f64(),
+        events_per_sec: summary.events as f64 / replay.as_secs_f64().max(f64::EPSILON),
+        endpoints,
+    };
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    db.close().await;
+    Ok(())
+}
+
+/// Inserts the events into `events_raw`, as `restore-raw` does
+async fn seed(db: &PgPool, events: &[SyntheticEvent]) -> anyhow::Result<()> {
+    let hex = |hash: B256| format!("{hash:#x}");
+    for batch in events.chunks(SEED_BATCH_SIZE) {
+        let logs = || batch.iter().map(|event| &event.log);
+        // Topics are joined since UNNEST can't yield arrays of arrays
+        sqlx::query(
+            "INSERT INTO events_raw
+                (tx_hash, log_index, block_number, block_hash, block_time, address, topic0, topics, data)
+             SELECT t.tx_hash, t.log_index, t.block_number, t.block_hash, t.block_time, t.address,
+                    split_part(t.topics, ',', 1), string_to_array(t.topics, ','), t.data
+             FROM UNNEST($1::text[], $2::bigint[], $3::bigint[], $4::text[], $5::timestamptz[],
+                         $6::text[], $7::text[], $8::text[])
+                 AS t (tx_hash, log_index, block_number, block_hash, block_time, address, topics, data)",
+        )
+        .bind(logs().map(|log| log.transaction_hash.map(hex)).collect::<Vec<_>>())
+        .bind(logs().map(|log| log.log_index.map(|i| i as i64)).collect::<Vec<_>>())
+        .bind(logs().map(|log| log.block_number.map(|n| n as i64)).collect::<Vec<_>>())
+        .bind(logs().map(|log| log.block_hash.map(hex)).collect::<Vec<_>>())
+        .bind(batch.iter().map(|event| event.block_time).collect::<Vec<_>>())
+        .bind(logs().map(|log| format!("{:#x}", log.address())).collect::<Vec<_>>())
+        .bind(
+            logs()
+                .map(|log| log.topics().iter().map(|t| hex(*t)).collect::<Vec<_>>().join(","))
+                .collect::<Vec<_>>(),
+        )
+        .bind(logs().map(|log| format!("0x{}", hex::encode(&log.data().data))).collect::<Vec<_>>())
+        .execute(db)
+        .await
+        .context("failed to seed raw events")?;
+    }
+    Ok(())
+}
+
+/// API state over the seeded database, with caches disabled
+fn bench_state(config: &AppConfig, db: &PgPool) -> anyhow::Result<AppState> {
+    let name = config.deployment.as_deref().unwrap_or("default");
+    Ok(AppState {
+        db: db.clone(),
+        indexer_db: db.clone(),
+        config: config.clone(),
+        chain: ChainReader::new(
+            &config.api_rpc_url,
+            &config.raffle_factory_address,
+            config.api_rpc_rate_limit,
+            &RpcBudget::unlimited("bench"),
+        )?,
+        attestation_signer: None,
+        pending: None,
+        live: live::channel(),
+        indexer: IndexerStatus::new(CircuitBreaker::new(name, 5, Duration::from_secs(30))),
+        raffle_list_cache: None,
+        embed_cache: cache::SwrCache::new(Duration::ZERO, Duration::ZERO),
+        network_cache: cache::SwrCache::new(Duration::ZERO, Duration::ZERO),
+        api_keys: api_keys::ApiKeyGuard::default(),
+        tasks: TaskGroup::new(name, tracing::Span::none()),
+        geo: None,
+    })
+}
+
+/// The measured endpoints, each with `requests` paths spread over the raffles
+fn endpoint_paths(raffles: u64, requests: usize) -> Vec<(&'static str, Vec<String>)> {
+    let raffle = |i: usize| (i as u64 * 7919) % raffles.max(1) + 1;
+    let fixed = |path: &str| vec![path.to_string(); requests];
+    let per_raffle = |suffix: &str| {
+        (0..requests)
+            .map(|i| format!("/v1/raffles/{}{suffix}", raffle(i)))
+            .collect()
+    };
+    vec![
+        ("GET /v1/raffles", fixed("/v1/raffles")),
+        (
+            "GET /v1/raffles?status=ACTIVE",
+            fixed("/v1/raffles?status=ACTIVE"),
+        ),
+        ("GET /v1/raffles/{id}", per_raffle("")),
+        ("GET /v1/raffles/{id}/purchases", per_raffle("/purchases")),
+        (
+            "GET /v1/raffles/{id}/participants",
+            per_raffle("/participants"),
+        ),
+        (
+            "GET /v1/raffles/{id}/stats/histogram",
+            per_raffle("/stats/histogram"),
+        ),
+        ("GET /v1/status", fixed("/v1/status")),
+        ("GET /v1/fees", fixed("/v1/fees")),
+        ("GET /v1/audit/fairness", fixed("/v1/audit/fairness")),
+    ]
+}
+
+/// Requests `paths` one after another and summarizes their latencies
+async fn measure(
+    router: &Router,
+    endpoint: &str,
+    paths: &[String],
+) -> anyhow::Result<EndpointReport> {
+    let mut latencies = Vec::with_capacity(paths.len());
+    let mut errors = 0;
+    for path in paths {
+        let started = Instant::now();
+        let response = router
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty())?)
+            .await?;
+        let status = response.status();
+        axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        latencies.push(started.elapsed());
+        if status != StatusCode::OK {
+            errors += 1;
+        }
+    }
+    if errors > 0 {
+        tracing::warn!(
+            endpoint,
+            errors,
+            "some benchmark requests did not return 200"
+        );
+    }
+    latencies.sort_unstable();
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    Ok(EndpointReport {
+        endpoint: endpoint.to_string(),
+        requests: latencies.len(),
+        errors,
+        p50_ms: ms(percentile(&latencies, 0.50)),
+        p95_ms: ms(percentile(&latencies, 0.95)),
+        p99_ms: ms(percentile(&latencies, 0.99)),
+        max_ms: ms(latencies.last().copied().unwrap_or_default()),
+    })
+}
+
+/// The nearest-rank percentile `p` of sorted durations
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn print_report(report: &BenchReport) {
+    println!(
+        "Dataset: {} raffles x {} purchases ({} events)",
+        report.raffles, report.purchases_per_raffle, report.events
+    );
+    println!(
+        "Indexer replay: {:.2}s, {:.0} events/s ({} failed)\n",
+        report.replay_secs, report.events_per_sec, report.failed_events
+    );
+    println!(
+        "{:<40} {:>9} {:>9} {:>9} {:>9} {:>7}",
+        "endpoint", "p50 ms", "p95 ms", "p99 ms", "max ms", "errors"
+    );
+    for endpoint in &report.endpoints {
+        println!(
+            "{:<40} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>7}",
+            endpoint.endpoint,
+            endpoint.p50_ms,
+            endpoint.p95_ms,
+            endpoint.p99_ms,
+            endpoint.max_ms,
+            endpoint.errors
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_events_decode_with_the_indexer_abis() {
+        let abis = Abis::new();
+        let events_by_signature =
+            indexer::build_event_map(&abis.factory, &abis.raffle, Some(&abis.provider)).unwrap();
+        let events = synthetic_events(&abis, address_from_u64(1), 100, 4, 3).unwrap();
+
+        // Per raffle: created, 3 purchases, and 5 payout events for 3 of the 4
+        assert_eq!(events.len(), 4 * 4 + 3 * 5);
+        for event in &events {
+            assert!(
+                indexer::decode_log(&events_by_signature, &event.log)
+                    .unwrap()
+                    .is_some()
+            );
+        }
+        assert_eq!(events[0].log.block_number, Some(100));
+        assert_eq!(events[10].log.block_number, Some(101));
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0.50), Duration::from_millis(50));
+        assert_eq!(percentile(&sorted, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&sorted[..1], 0.95), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+}
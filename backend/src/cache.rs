@@ -0,0 +1,298 @@
+//! Stale-while-revalidate response cache
+//!
+//! Caches serialized response bodies by key. A fresh entry (younger than the TTL)
+//! is served as-is. A stale entry (older than the TTL but younger than the
+//! max-stale bound) is still served immediately while a single background task
+//! reloads it. Entries older than max-stale, and misses, are loaded inline.
+//!
+//! # Security Considerations
+//! - The number of cached entries is bounded; the oldest entry is evicted first
+//! - At most one background refresh runs per key
+
+use axum::body::Bytes;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Maximum number of cached entries (distinct query strings)
+const MAX_ENTRIES: usize = 256;
+
+/// How a cached response was obtained
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Served from a fresh entry
+    Hit,
+    /// Served from a stale entry while a refresh runs in the background
+    Stale,
+    /// Loaded inline (no usable entry)
+    Miss,
+}
+
+impl CacheStatus {
+    /// Value of the `X-Cache` response header
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Stale => "STALE",
+            CacheStatus::Miss => "MISS",
+        }
+    }
+}
+
+/// A response body with its age
+pub struct Cached {
+    pub body: Bytes,
+    pub age: Duration,
+    pub status: CacheStatus,
+}
+
+struct Entry {
+    body: Bytes,
+    fetched_at: Instant,
+    refreshing: bool,
+}
+
+/// Shared stale-while-revalidate cache; cheap to clone
+#[derive(Clone)]
+pub struct SwrCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    ttl: Duration,
+    max_stale: Duration,
+}
+
+impl SwrCache {
+    pub fn new(ttl: Duration, max_stale: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            max_stale: max_stale.max(ttl),
+        }
+    }
+
+    /// Returns the cached body for `key`, calling `load` when it is missing or stale
+    ///
+    /// `load` runs inline on a miss (its error is returned) or in a background task
+    /// when a stale entry is served (its error is dropped and the stale entry kept;
+    /// loaders are expected to log their own failures).
+    pub async fn get<F, Fut, E>(&self, key: String, load: F) -> Result<Cached, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Bytes, E>> + Send + 'static,
+        E: Send + 'static,
+    {
+        let stale_hit = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            match entries.get_mut(&key) {
+                Some(entry) => {
+                    let age = entry.fetched_at.elapsed();
+                    if age <= self.ttl {
+                        return Ok(Cached {
+                            body: entry.body.clone(),
+                            age,
+                            status: CacheStatus::Hit,
+                        });
+                    }
+                    if age <= self.max_stale {
+                        let start_refresh = !entry.refreshing;
+                        entry.refreshing = true;
+                        Some((entry.body.clone(), age, start_refresh))
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            }
+        };
+
+        match stale_hit {
+            Some((body, age, start_refresh)) => {
+                if start_refresh {
+                    let cache = self.clone();
+                    let refresh = load();
+                    tokio::spawn(async move {
+                        match refresh.await {
+                            Ok(body) => cache.insert(key, body),
+                            Err(_) => {
+                                tracing::debug!(key = %key, "cache refresh failed, serving stale");
+                                cache.finish_failed_refresh(&key);
+                            }
+                        }
+                    });
+                }
+                Ok(Cached {
+                    body,
+                    age,
+                    status: CacheStatus::Stale,
+                })
+            }
+            None => {
+                let body = load().await?;
+                self.insert(key, body.clone());
+                Ok(Cached {
+                    body,
+                    age: Duration::ZERO,
+                    status: CacheStatus::Miss,
+                })
+            }
+        }
+    }
+
+    fn insert(&self, key: String, body: Bytes) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                body,
+                fetched_at: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+
+    /// Allows another refresh attempt after a failed one
+    fn finish_failed_refresh(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get_mut(key) {
+            entry.refreshing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(text: &'static str) -> impl Future<Output = Result<Bytes, ()>> + Send + 'static {
+        std::future::ready(Ok(Bytes::from_static(text.as_bytes())))
+    }
+
+    async fn get(cache: &SwrCache, key: &str, text: &'static str) -> (Bytes, CacheStatus) {
+        let cached = cache.get(key.to_string(), || body(text)).await.unwrap();
+        (cached.body, cached.status)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fresh_then_stale_then_refreshed() {
+        let cache = SwrCache::new(Duration::from_secs(10), Duration::from_secs(60));
+        assert_eq!(
+            get(&cache, "k", "v1").await,
+            ("v1".into(), CacheStatus::Miss)
+        );
+        assert_eq!(
+            get(&cache, "k", "v2").await,
+            ("v1".into(), CacheStatus::Hit)
+        );
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        let cached = cache.get("k".to_string(), || body("v2")).await.unwrap();
+        assert_eq!(cached.status, CacheStatus::Stale);
+        assert_eq!(cached.body, "v1");
+        assert_eq!(cached.age, Duration::from_secs(11));
+        // Only one refresh runs while it is in flight
+        assert_eq!(
+            get(&cache, "k", "v3").await,
+            ("v1".into(), CacheStatus::Stale)
+        );
+
+        tokio::task::yield_now().await;
+        assert_eq!(
+            get(&cache, "k", "v4").await,
+            ("v2".into(), CacheStatus::Hit)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_entries_load_inline() {
+        let cache = SwrCache::new(Duration::from_secs(10), Duration::from_secs(60));
+        get(&cache, "k", "v1").await;
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert_eq!(
+            get(&cache, "k", "v2").await,
+            ("v2".into(), CacheStatus::Miss)
+        );
+
+        // A failed inline load is returned and leaves nothing cached
+        tokio::time::advance(Duration::from_secs(61)).await;
+        let failed = cache
+            .get("k".to_string(), || {
+                std::future::ready(Err::<Bytes, _>("down"))
+            })
+            .await;
+        assert_eq!(failed.err(), Some("down"));
+        assert_eq!(
+            get(&cache, "k", "v3").await,
+            ("v3".into(), CacheStatus::Miss)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_refresh_keeps_stale_entry() {
+        let cache = SwrCache::new(Duration::from_secs(10), Duration::from_secs(60));
+        get(&cache, "k", "v1").await;
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        let stale = cache
+            .get("k".to_string(), || std::future::ready(Err::<Bytes, _>(())))
+            .await
+            .unwrap();
+        assert_eq!(stale.status, CacheStatus::Stale);
+        tokio::task::yield_now().await;
+
+        // The next request retries the refresh
+        assert_eq!(
+            get(&cache, "k", "v2").await,
+            ("v1".into(), CacheStatus::Stale)
+        );
+        tokio::task::yield_now().await;
+        assert_eq!(
+            get(&cache, "k", "v3").await,
+            ("v2".into(), CacheStatus::Hit)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keys_are_cached_separately() {
+        let cache = SwrCache::new(Duration::from_secs(10), Duration::from_secs(60));
+        assert_eq!(
+            get(&cache, "a", "a1").await,
+            ("a1".into(), CacheStatus::Miss)
+        );
+        assert_eq!(
+            get(&cache, "b", "b1").await,
+            ("b1".into(), CacheStatus::Miss)
+        );
+        assert_eq!(
+            get(&cache, "a", "a2").await,
+            ("a1".into(), CacheStatus::Hit)
+        );
+        assert_eq!(
+            get(&cache, "b", "b2").await,
+            ("b1".into(), CacheStatus::Hit)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn oldest_entry_is_evicted() {
+        let cache = SwrCache::new(Duration::from_secs(3600), Duration::from_secs(3600));
+        for key in 0..MAX_ENTRIES {
+            get(&cache, &key.to_string(), "v1").await;
+            tokio::time::advance(Duration::from_millis(1)).await;
+        }
+        get(&cache, "new", "v1").await;
+
+        assert_eq!(get(&cache, "1", "v2").await.1, CacheStatus::Hit);
+        assert_eq!(get(&cache, "0", "v2").await.1, CacheStatus::Miss);
+    }
+}
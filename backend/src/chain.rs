@@ -0,0 +1,436 @@
+//! Direct contract reads for API handlers
+//!
+//! The API normally serves indexed data from PostgreSQL. This module covers the
+//! cases where a handler needs to read contract state over RPC instead, e.g. a
+//! raffle that was created after the indexer's last poll, the pot and token
+//! balance a stored pot is reconciled against, or the head block and gas prices.
+//!
+//! Reads go through their own provider (`API_RPC_URL`, defaulting to `RPC_URL`)
+//! with a separate request budget, so interactive lookups don't compete with the
+//! indexer's backfill traffic. When that provider is `RPC_URL`, requests also draw on
+//! the deployment's shared `RPC_RATE_LIMIT` budget (see `rpc`).
+//!
+//! # Security Considerations
+//! - All RPC calls have timeouts to prevent hanging request handlers
+//! - Only view functions are called; no keys are involved
+//! - RPC requests are capped by `API_RPC_RATE_LIMIT`, so API traffic can't
+//!   exhaust the endpoint's quota
+
+use crate::rpc::{RpcBudget, RpcProvider};
+use anyhow::Context;
+use ethers::contract::{ContractError, abigen};
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockNumber, U256};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Timeout for a batch of contract reads
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// RPC requests issued by [`ChainReader::fetch_raffle`] (factory lookup + raffle getters)
+const FETCH_RAFFLE_REQUESTS: u32 = 16;
+
+/// RPC requests issued by [`ChainReader::fetch_raffle_balance`] (factory lookup, pot,
+/// ticket count, token address and token balance)
+const FETCH_BALANCE_REQUESTS: u32 = 5;
+
+/// RPC requests issued by [`ChainReader::fetch_network`] (two blocks, gas price and
+/// priority fee)
+const FETCH_NETWORK_REQUESTS: u32 = 4;
+
+/// Errors from direct contract reads
+#[derive(Debug, thiserror::Error)]
+pub enum ChainReadError {
+    /// The API's RPC request budget is exhausted for now
+    #[error("API RPC request budget exhausted")]
+    RateLimited,
+    #[error(transparent)]
+    Rpc(#[from] anyhow::Error),
+}
+
+abigen!(
+    RaffleFactoryContract,
+    r#"[
+        function raffles(uint256) external view returns (address)
+    ]"#
+);
+
+abigen!(
+    RaffleContract,
+    r#"[
+        function creator() external view returns (address)
+        function endTime() external view returns (uint256)
+        function ticketPrice() external view returns (uint256)
+        function maxTickets() external view returns (uint32)
+        function feeBps() external view returns (uint16)
+        function feeRecipient() external view returns (address)
+        function status() external view returns (uint8)
+        function refundsEnabled() external view returns (bool)
+        function totalTickets() external view returns (uint32)
+        function pot() external view returns (uint256)
+        function requestId() external view returns (uint256)
+        function randomness() external view returns (uint256)
+        function winningIndex() external view returns (uint256)
+        function winner() external view returns (address)
+        function keeper() external view returns (address)
+        function usdc() external view returns (address)
+    ]"#
+);
+
+abigen!(
+    TokenContract,
+    r#"[
+        function balanceOf(address) external view returns (uint256)
+    ]"#
+);
+
+/// Raffle state as read directly from the Raffle contract
+#[derive(Debug)]
+pub struct ChainRaffle {
+    pub raffle_address: Address,
+    pub creator: Address,
+    pub end_time: u64,
+    pub ticket_price: U256,
+    pub max_tickets: u32,
+    pub fee_bps: u16,
+    pub fee_recipient: Address,
+    /// Raw `Raffle.Status` enum value
+    pub status: u8,
+    pub refunds_enabled: bool,
+    pub total_tickets: u32,
+    pub pot: U256,
+    pub request_id: U256,
+    pub randomness: U256,
+    pub winning_index: U256,
+    pub winner: Address,
+    pub keeper: Address,
+}
+
+impl ChainRaffle {
+    /// Maps the contract status to the status strings used by the indexer
+    pub fn status_name(&self) -> &'static str {
+        if self.refunds_enabled {
+            return "REFUNDING";
+        }
+        match self.status {
+            0 => "ACTIVE",
+            1 => "CLOSED",
+            2 => "RANDOM_REQUESTED",
+            3 => "RANDOM_FULFILLED",
+            4 => "FINALIZED",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+/// A raffle's pot as read from its contract, next to the tokens it actually holds
+#[derive(Debug)]
+pub struct ChainRaffleBalance {
+    pub raffle_address: Address,
+    pub total_tickets: u32,
+    pub pot: U256,
+    /// Payment token balance of the raffle contract
+    pub balance: U256,
+}
+
+/// Head block and gas prices as reported by the node
+#[derive(Debug)]
+pub struct ChainNetwork {
+    pub head_block: u64,
+    /// Unix timestamp of the head block
+    pub head_timestamp: u64,
+    /// Mean seconds between the last `sample` blocks (`None` on a chain that short)
+    pub average_block_time: Option<f64>,
+    /// `eth_gasPrice`, in wei
+    pub gas_price: U256,
+    /// Base fee of the head block (`None` before EIP-1559)
+    pub base_fee_per_gas: Option<U256>,
+    /// `eth_maxPriorityFeePerGas` (`None` if the node doesn't support it)
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+/// Token bucket limiting RPC requests per second
+///
+/// Holds at most one second of budget (or one lookup, if larger) so idle periods
+/// don't build up a burst.
+struct RateBudget {
+    /// Available requests and when they were last topped up
+    state: Mutex<(f64, Instant)>,
+    per_second: f64,
+    capacity: f64,
+}
+
+impl RateBudget {
+    fn new(per_second: u32) -> Self {
+        let per_second = f64::from(per_second);
+        let capacity = per_second.max(f64::from(FETCH_RAFFLE_REQUESTS));
+        Self {
+            state: Mutex::new((capacity, Instant::now())),
+            per_second,
+            capacity,
+        }
+    }
+
+    /// Takes `cost` requests from the budget, or returns false if not enough are left
+    fn try_acquire(&self, cost: u32) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (available, last_refill) = &mut *state;
+        let now = Instant::now();
+        *available = (*available
+            + now.duration_since(*last_refill).as_secs_f64() * self.per_second)
+            .min(self.capacity);
+        *last_refill = now;
+        if *available < f64::from(cost) {
+            return false;
+        }
+        *available -= f64::from(cost);
+        true
+    }
+}
+
+/// Read-only contract access shared by API handlers
+#[derive(Clone)]
+pub struct ChainReader {
+    provider: Arc<RpcProvider>,
+    factory: Address,
+    /// Request budget (`None` when unlimited)
+    budget: Option<Arc<RateBudget>>,
+}
+
+impl ChainReader {
+    /// Creates a reader for the given RPC endpoint and factory address
+    ///
+    /// `rate_limit` caps RPC requests per second; 0 means unlimited. Requests also draw on
+    /// `rpc_budget`, the deployment's shared budget when the endpoint is `RPC_URL`.
+    pub fn new(
+        rpc_url: &str,
+        factory_address: &str,
+        rate_limit: u32,
+        rpc_budget: &RpcBudget,
+    ) -> anyhow::Result<Self> {
+        let provider = rpc_budget
+            .provider(rpc_url, "api")
+            .context("invalid API_RPC_URL")?;
+        let factory =
+            Address::from_str(factory_address).context("invalid factory address format")?;
+        Ok(Self {
+            provider: Arc::new(provider),
+            factory,
+            budget: (rate_limit > 0).then(|| Arc::new(RateBudget::new(rate_limit))),
+        })
+    }
+
+    /// Reads a raffle by ID from the factory and raffle contracts
+    ///
+    /// Returns `Ok(None)` when the factory has no raffle with that ID, and
+    /// [`ChainReadError::RateLimited`] without calling the RPC when the request
+    /// budget is exhausted.
+    pub async fn fetch_raffle(
+        &self,
+        raffle_id: u64,
+    ) -> Result<Option<ChainRaffle>, ChainReadError> {
+        if let Some(budget) = &self.budget
+            && !budget.try_acquire(FETCH_RAFFLE_REQUESTS)
+        {
+            return Err(ChainReadError::RateLimited);
+        }
+        let raffle = tokio::time::timeout(RPC_TIMEOUT, self.fetch_raffle_inner(raffle_id))
+            .await
+            .context("contract reads timed out")??;
+        Ok(raffle)
+    }
+
+    /// Reads a raffle's pot, ticket count and payment token balance
+    ///
+    /// Returns `Ok(None)` and [`ChainReadError::RateLimited`] like
+    /// [`fetch_raffle`](Self::fetch_raffle).
+    pub async fn fetch_raffle_balance(
+        &self,
+        raffle_id: u64,
+    ) -> Result<Option<ChainRaffleBalance>, ChainReadError> {
+        if let Some(budget) = &self.budget
+            && !budget.try_acquire(FETCH_BALANCE_REQUESTS)
+        {
+            return Err(ChainReadError::RateLimited);
+        }
+        let balance = tokio::time::timeout(RPC_TIMEOUT, self.fetch_raffle_balance_inner(raffle_id))
+            .await
+            .context("contract reads timed out")??;
+        Ok(balance)
+    }
+
+    /// Reads the head block, the average block time over the last `sample` blocks and
+    /// the node's gas price suggestions
+    ///
+    /// Returns [`ChainReadError::RateLimited`] like [`fetch_raffle`](Self::fetch_raffle).
+    pub async fn fetch_network(&self, sample: u64) -> Result<ChainNetwork, ChainReadError> {
+        if let Some(budget) = &self.budget
+            && !budget.try_acquire(FETCH_NETWORK_REQUESTS)
+        {
+            return Err(ChainReadError::RateLimited);
+        }
+        let network = tokio::time::timeout(RPC_TIMEOUT, self.fetch_network_inner(sample))
+            .await
+            .context("network reads timed out")??;
+        Ok(network)
+    }
+
+    async fn fetch_network_inner(&self, sample: u64) -> anyhow::Result<ChainNetwork> {
+        let (head, gas_price, priority_fee) = tokio::join!(
+            self.provider.get_block(BlockNumber::Latest),
+            self.provider.get_gas_price(),
+            self.provider
+                .request::<_, U256>("eth_maxPriorityFeePerGas", ()),
+        );
+        let head = head
+            .context("failed to read the head block")?
+            .context("node returned no head block")?;
+        let gas_price = gas_price.context("failed to read the gas price")?;
+        let head_block = head.number.context("head block has no number")?.as_u64();
+        let head_timestamp = head.timestamp.low_u64();
+
+        let average_block_time = match head_block.checked_sub(sample) {
+            Some(first) if sample > 0 => {
+                let first = self
+                    .provider
+                    .get_block(first)
+                    .await
+                    .context("failed to read a sample block")?
+                    .context("node returned no sample block")?;
+                let elapsed = head_timestamp.saturating_sub(first.timestamp.low_u64());
+                Some(elapsed as f64 / sample as f64)
+            }
+            _ => None,
+        };
+
+        Ok(ChainNetwork {
+            head_block,
+            head_timestamp,
+            average_block_time,
+            gas_price,
+            base_fee_per_gas: head.base_fee_per_gas,
+            // Nodes without EIP-1559 support reject the method
+            max_priority_fee_per_gas: priority_fee.ok(),
+        })
+    }
+
+    /// Looks up a raffle's contract address in the factory
+    async fn raffle_address(&self, raffle_id: u64) -> anyhow::Result<Option<Address>> {
+        // Raffle IDs start at 1 and are pushed to the factory's array in order
+        let Some(index) = raffle_id.checked_sub(1) else {
+            return Ok(None);
+        };
+
+        let factory = RaffleFactoryContract::new(self.factory, self.provider.clone());
+        match factory.raffles(U256::from(index)).call().await {
+            Ok(address) => Ok(Some(address)),
+            // Out-of-range index reverts: the raffle doesn't exist (yet)
+            Err(ContractError::Revert(_)) => Ok(None),
+            Err(err) => Err(err).context("failed to read factory raffles"),
+        }
+    }
+
+    async fn fetch_raffle_balance_inner(
+        &self,
+        raffle_id: u64,
+    ) -> anyhow::Result<Option<ChainRaffleBalance>> {
+        let Some(raffle_address) = self.raffle_address(raffle_id).await? else {
+            return Ok(None);
+        };
+
+        let raffle = RaffleContract::new(raffle_address, self.provider.clone());
+        let total_tickets = raffle.total_tickets();
+        let pot = raffle.pot();
+        let token = raffle.usdc();
+        let (total_tickets, pot, token) =
+            tokio::try_join!(total_tickets.call(), pot.call(), token.call())
+                .context("failed to read raffle pot")?;
+        let balance = TokenContract::new(token, self.provider.clone())
+            .balance_of(raffle_address)
+            .call()
+            .await
+            .context("failed to read raffle token balance")?;
+
+        Ok(Some(ChainRaffleBalance {
+            raffle_address,
+            total_tickets,
+            pot,
+            balance,
+        }))
+    }
+
+    async fn fetch_raffle_inner(&self, raffle_id: u64) -> anyhow::Result<Option<ChainRaffle>> {
+        let Some(raffle_address) = self.raffle_address(raffle_id).await? else {
+            return Ok(None);
+        };
+
+        let raffle = RaffleContract::new(raffle_address, self.provider.clone());
+        let creator = raffle.creator();
+        let end_time = raffle.end_time();
+        let ticket_price = raffle.ticket_price();
+        let max_tickets = raffle.max_tickets();
+        let fee_bps = raffle.fee_bps();
+        let fee_recipient = raffle.fee_recipient();
+        let status = raffle.status();
+        let refunds_enabled = raffle.refunds_enabled();
+        let total_tickets = raffle.total_tickets();
+        let pot = raffle.pot();
+        let request_id = raffle.request_id();
+        let randomness = raffle.randomness();
+        let winning_index = raffle.winning_index();
+        let winner = raffle.winner();
+        let keeper = raffle.keeper();
+
+        let (creator, end_time, ticket_price, max_tickets, fee_bps, fee_recipient, status) =
+            tokio::try_join!(
+                creator.call(),
+                end_time.call(),
+                ticket_price.call(),
+                max_tickets.call(),
+                fee_bps.call(),
+                fee_recipient.call(),
+                status.call(),
+            )
+            .context("failed to read raffle configuration")?;
+        let (
+            refunds_enabled,
+            total_tickets,
+            pot,
+            request_id,
+            randomness,
+            winning_index,
+            winner,
+            keeper,
+        ) = tokio::try_join!(
+            refunds_enabled.call(),
+            total_tickets.call(),
+            pot.call(),
+            request_id.call(),
+            randomness.call(),
+            winning_index.call(),
+            winner.call(),
+            keeper.call(),
+        )
+        .context("failed to read raffle state")?;
+
+        Ok(Some(ChainRaffle {
+            raffle_address,
+            creator,
+            end_time: end_time.low_u64(),
+            ticket_price,
+            max_tickets,
+            fee_bps,
+            fee_recipient,
+            status,
+            refunds_enabled,
+            total_tickets,
+            pot,
+            request_id,
+            randomness,
+            winning_index,
+            winner,
+            keeper,
+        }))
+    }
+}
@@ -0,0 +1,282 @@
+//! Circuit breaker for the indexer's RPC endpoint
+//!
+//! After `RPC_CIRCUIT_FAILURE_THRESHOLD` consecutive failed RPC calls the circuit
+//! opens and indexing pauses instead of retrying against a dead endpoint. Every
+//! `RPC_CIRCUIT_PROBE_INTERVAL_SECS` the circuit goes half-open and lets one
+//! indexing cycle through as a probe: success closes the circuit, failure reopens
+//! it for another interval.
+//!
+//! Transitions are logged once, exported as metrics and reported by `GET /v1/status`.
+//!
+//! # Security Considerations
+//! - RPC error messages may contain the endpoint URL (and API keys); they are
+//!   logged but never returned by the API
+
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// State of the circuit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// RPC calls flow normally
+    Closed,
+    /// RPC calls are paused until the next probe
+    Open,
+    /// The next indexing cycle is a probe
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Point-in-time view of the circuit for the status endpoint
+#[derive(Clone, Debug, Serialize)]
+pub struct CircuitSnapshot {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// When the circuit last opened (null while closed)
+    pub opened_at: Option<DateTime<Utc>>,
+    /// When the next probe is allowed (null unless open)
+    pub next_probe_at: Option<DateTime<Utc>>,
+}
+
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<DateTime<Utc>>,
+    next_probe_at: Option<DateTime<Utc>>,
+}
+
+/// Shared circuit breaker; cheap to clone, all clones observe the same state
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    /// Label for logs and metrics (the deployment name)
+    name: Arc<str>,
+    inner: Arc<Mutex<Inner>>,
+    failure_threshold: u32,
+    probe_interval: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, failure_threshold: u32, probe_interval: Duration) -> Self {
+        metrics::set_circuit_state(name, CircuitState::Closed);
+        Self {
+            name: name.into(),
+            inner: Arc::new(Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                next_probe_at: None,
+            })),
+            failure_threshold: failure_threshold.max(1),
+            probe_interval,
+        }
+    }
+
+    pub fn snapshot(&self) -> CircuitSnapshot {
+        let inner = self.lock();
+        CircuitSnapshot {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            opened_at: inner.opened_at,
+            next_probe_at: inner.next_probe_at,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.lock().state == CircuitState::Open
+    }
+
+    /// Returns how long to wait before RPC calls are allowed, or `None` if they are
+    ///
+    /// An open circuit whose probe time has passed moves to half-open.
+    pub fn wait_time(&self) -> Option<Duration> {
+        self.wait_time_at(Utc::now())
+    }
+
+    fn wait_time_at(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let mut inner = self.lock();
+        if inner.state != CircuitState::Open {
+            return None;
+        }
+        let remaining = inner
+            .next_probe_at
+            .and_then(|at| (at - now).to_std().ok())
+            .filter(|remaining| !remaining.is_zero());
+        if remaining.is_none() {
+            self.transition(&mut inner, CircuitState::HalfOpen);
+            tracing::info!(circuit = %self.name, "RPC circuit half-open, probing");
+        }
+        remaining
+    }
+
+    /// Awaits an RPC call and records its outcome
+    pub async fn call<T>(
+        &self,
+        call: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let result = call.await;
+        match &result {
+            Ok(_) => self.record_success(),
+            Err(err) => self.record_failure(err, Utc::now()),
+        }
+        result
+    }
+
+    fn record_success(&self) {
+        let mut inner = self.lock();
+        inner.consecutive_failures = 0;
+        if inner.state != CircuitState::Closed {
+            inner.opened_at = None;
+            inner.next_probe_at = None;
+            self.transition(&mut inner, CircuitState::Closed);
+            tracing::info!(circuit = %self.name, "RPC circuit closed, indexing resumed");
+        }
+    }
+
+    fn record_failure(&self, err: &anyhow::Error, now: DateTime<Utc>) {
+        let mut inner = self.lock();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let should_open = match inner.state {
+            CircuitState::Closed => inner.consecutive_failures >= self.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if !should_open {
+            return;
+        }
+
+        let probe_interval =
+            chrono::Duration::from_std(self.probe_interval).unwrap_or(chrono::Duration::MAX);
+        let was_probing = inner.state == CircuitState::HalfOpen;
+        if !was_probing {
+            inner.opened_at = Some(now);
+        }
+        inner.next_probe_at = now.checked_add_signed(probe_interval);
+        self.transition(&mut inner, CircuitState::Open);
+        if was_probing {
+            tracing::warn!(
+                circuit = %self.name,
+                probe_in_secs = self.probe_interval.as_secs(),
+                error = %err,
+                "RPC probe failed, indexing stays paused"
+            );
+        } else {
+            tracing::error!(
+                circuit = %self.name,
+                consecutive_failures = inner.consecutive_failures,
+                probe_in_secs = self.probe_interval.as_secs(),
+                error = %err,
+                "RPC circuit open, indexing paused"
+            );
+        }
+    }
+
+    fn transition(&self, inner: &mut Inner, to: CircuitState) {
+        inner.state = to;
+        metrics::record_circuit_transition(&self.name, to);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new("test", 3, Duration::from_secs(60))
+    }
+
+    fn secs(secs: i64) -> chrono::Duration {
+        chrono::Duration::seconds(secs)
+    }
+
+    fn open(breaker: &CircuitBreaker, now: DateTime<Utc>) {
+        let err = anyhow::anyhow!("connection refused");
+        for _ in 0..3 {
+            breaker.record_failure(&err, now);
+        }
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker();
+        let now = Utc::now();
+        let err = anyhow::anyhow!("connection refused");
+
+        breaker.record_failure(&err, now);
+        breaker.record_failure(&err, now);
+        assert_eq!(breaker.snapshot().state, CircuitState::Closed);
+        assert_eq!(breaker.wait_time_at(now), None);
+
+        // A success in between starts the count over
+        breaker.record_success();
+        breaker.record_failure(&err, now);
+        breaker.record_failure(&err, now);
+        assert_eq!(breaker.snapshot().state, CircuitState::Closed);
+
+        breaker.record_failure(&err, now);
+        let snapshot = breaker.snapshot();
+        assert_eq!(snapshot.state, CircuitState::Open);
+        assert_eq!(snapshot.consecutive_failures, 3);
+        assert_eq!(snapshot.opened_at, Some(now));
+        assert_eq!(snapshot.next_probe_at, Some(now + secs(60)));
+        assert_eq!(
+            breaker.wait_time_at(now + secs(45)),
+            Some(Duration::from_secs(15))
+        );
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn successful_probe_closes() {
+        let breaker = breaker();
+        let now = Utc::now();
+        open(&breaker, now);
+
+        assert_eq!(breaker.wait_time_at(now + secs(60)), None);
+        assert_eq!(breaker.snapshot().state, CircuitState::HalfOpen);
+
+        breaker.record_success();
+        let snapshot = breaker.snapshot();
+        assert_eq!(snapshot.state, CircuitState::Closed);
+        assert_eq!(snapshot.consecutive_failures, 0);
+        assert_eq!(snapshot.opened_at, None);
+        assert_eq!(snapshot.next_probe_at, None);
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let breaker = breaker();
+        let now = Utc::now();
+        open(&breaker, now);
+        let probe_at = now + secs(90);
+        assert_eq!(breaker.wait_time_at(probe_at), None);
+
+        // One failure is enough while probing; the circuit stays open since `now`
+        breaker.record_failure(&anyhow::anyhow!("timed out"), probe_at);
+        let snapshot = breaker.snapshot();
+        assert_eq!(snapshot.state, CircuitState::Open);
+        assert_eq!(snapshot.opened_at, Some(now));
+        assert_eq!(snapshot.next_probe_at, Some(probe_at + secs(60)));
+        assert_eq!(
+            breaker.wait_time_at(probe_at + secs(1)),
+            Some(Duration::from_secs(59))
+        );
+    }
+}
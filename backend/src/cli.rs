@@ -0,0 +1,481 @@
+//! Command-line entry points
+//!
+//! The binary starts the API server and indexer by default. Subcommands run
+//! one-off operator tasks instead:
+//!
+//! - `check-config` (alias `--validate`) - preflight check of configuration,
+//!   database and RPC without starting any services
+//! - `backup-raw` / `restore-raw` - back up `events_raw` to object storage, or
+//!   rebuild a fresh database from such a backup (see [`crate::backup`])
+//! - `bench` - seed an empty database with synthetic raffles and measure indexer
+//!   throughput and API latencies (see [`crate::bench`])
+//! - `verify-raffle` - recompute a raffle's totals and winner from chain logs and
+//!   compare them with the contract and an API instance (see [`crate::verify`])
+
+use crate::backup;
+use crate::bench::{self, BenchOptions};
+use crate::config::AppConfig;
+use crate::geo;
+use crate::indexer;
+use crate::schema;
+use crate::signer::KeeperSigner;
+use crate::verify::{self, VerifyOptions};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::LocalWallet;
+use ethers::types::Address;
+use sqlx::postgres::PgPoolOptions;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Timeout for each preflight probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const USAGE: &str = "Usage: backend [COMMAND]
+
+Commands:
+  serve          Run the API server and indexer (default)
+  check-config   Validate configuration, database and RPC, then exit
+                 (alias: --validate)
+  backup-raw [KEY]
+                 Back up raw events to BACKUP_BUCKET (default key:
+                 {BACKUP_PREFIX}/[{deployment}/]events_raw-{time}.ndjson.gz)
+  restore-raw KEY
+                 Restore a raw event backup into an empty database and
+                 rebuild all derived tables from it
+  bench          Seed an empty database with synthetic raffles, then report
+                 indexer throughput and API endpoint latencies
+  verify-raffle RAFFLE_ID
+                 Recompute a raffle's tickets, pot and winner from chain logs
+                 and compare them with the contract and, with --api-url, an
+                 API instance; needs no database
+
+Options:
+  --deployment NAME
+                 Deployment to back up, restore or benchmark when
+                 DEPLOYMENTS is set
+  --raffles N    bench: synthetic raffles (default 200)
+  --purchases N  bench: purchases per raffle (default 50)
+  --requests N   bench: requests per endpoint (default 200)
+  --json         bench: print results as JSON
+  --rpc-url URL  verify-raffle: RPC endpoint (default: RPC_URL)
+  --factory ADDRESS
+                 verify-raffle: factory contract (default: RAFFLE_FACTORY_ADDRESS)
+  --from-block N verify-raffle: first block to read logs from (default:
+                 START_BLOCK, else 0)
+  --api-url URL  verify-raffle: API base URL to compare with";
+
+/// What the process should do
+pub enum Command {
+    Serve,
+    CheckConfig,
+    BackupRaw {
+        deployment: Option<String>,
+        key: Option<String>,
+    },
+    RestoreRaw {
+        deployment: Option<String>,
+        key: String,
+    },
+    Bench {
+        deployment: Option<String>,
+        options: BenchOptions,
+    },
+    VerifyRaffle {
+        options: VerifyOptions,
+    },
+}
+
+impl Command {
+    /// Parses the command from process arguments (excluding the binary name)
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let command = args.next();
+        let mut deployment = None;
+        let mut bench_options = None::<BenchOptions>;
+        let mut verify_options = None::<VerifyOptions>;
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--deployment" => {
+                    deployment = Some(args.next().ok_or_else(|| {
+                        anyhow::anyhow!("--deployment requires a name\n\n{USAGE}")
+                    })?);
+                }
+                "--raffles" | "--purchases" | "--requests" => {
+                    let value = args
+                        .next()
+                        .and_then(|value| value.parse::<u64>().ok())
+                        .ok_or_else(|| anyhow::anyhow!("{arg} requires a number\n\n{USAGE}"))?;
+                    let options = bench_options.get_or_insert_with(BenchOptions::default);
+                    match arg.as_str() {
+                        "--raffles" => options.raffles = value,
+                        "--purchases" => options.purchases = value,
+                        _ => options.requests = value as usize,
+                    }
+                }
+                "--json" => bench_options.get_or_insert_with(BenchOptions::default).json = true,
+                "--rpc-url" | "--factory" | "--api-url" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("{arg} requires a value\n\n{USAGE}"))?;
+                    let options = verify_options.get_or_insert_with(VerifyOptions::default);
+                    match arg.as_str() {
+                        "--rpc-url" => options.rpc_url = Some(value),
+                        "--factory" => options.factory = Some(value),
+                        _ => options.api_url = Some(value),
+                    }
+                }
+                "--from-block" => {
+                    let value = args
+                        .next()
+                        .and_then(|value| value.parse::<u64>().ok())
+                        .ok_or_else(|| anyhow::anyhow!("{arg} requires a number\n\n{USAGE}"))?;
+                    verify_options
+                        .get_or_insert_with(VerifyOptions::default)
+                        .from_block = Some(value);
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let command = match command.as_deref() {
+            None | Some("serve") => Command::Serve,
+            Some("check-config" | "--validate") => Command::CheckConfig,
+            Some("backup-raw") if positional.len() <= 1 => Command::BackupRaw {
+                deployment: deployment.take(),
+                key: positional.pop(),
+            },
+            Some("restore-raw") if positional.len() == 1 => Command::RestoreRaw {
+                deployment: deployment.take(),
+                key: positional.remove(0),
+            },
+            Some("bench") => Command::Bench {
+                deployment: deployment.take(),
+                options: bench_options.take().unwrap_or_default(),
+            },
+            Some("verify-raffle") if positional.len() == 1 => {
+                let raffle_id = positional.remove(0);
+                let raffle_id = raffle_id
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("'{raffle_id}' is not a raffle ID\n\n{USAGE}"))?;
+                Command::VerifyRaffle {
+                    options: VerifyOptions {
+                        raffle_id,
+                        ..verify_options.take().unwrap_or_default()
+                    },
+                }
+            }
+            Some("verify-raffle") if positional.is_empty() => {
+                anyhow::bail!("verify-raffle requires a raffle ID\n\n{USAGE}")
+            }
+            Some("restore-raw") if positional.is_empty() => {
+                anyhow::bail!("restore-raw requires the key of a backup\n\n{USAGE}")
+            }
+            Some("-h" | "--help" | "help") => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            Some("backup-raw" | "restore-raw" | "verify-raffle") => {
+                anyhow::bail!("unexpected argument '{}'\n\n{USAGE}", positional[1])
+            }
+            Some(other) => anyhow::bail!("unknown command '{other}'\n\n{USAGE}"),
+        };
+        if let Some(extra) = positional.first() {
+            anyhow::bail!("unexpected argument '{extra}'\n\n{USAGE}");
+        }
+        if deployment.is_some() {
+            anyhow::bail!(
+                "--deployment only applies to backup-raw, restore-raw and bench\n\n{USAGE}"
+            );
+        }
+        if bench_options.is_some() {
+            anyhow::bail!(
+                "--raffles, --purchases, --requests and --json only apply to bench\n\n{USAGE}"
+            );
+        }
+        if verify_options.is_some() {
+            anyhow::bail!(
+                "--rpc-url, --factory, --from-block and --api-url only apply to verify-raffle\n\n{USAGE}"
+            );
+        }
+        Ok(command)
+    }
+}
+
+/// Backs up the raw events of one deployment
+pub async fn backup_raw(deployment: Option<String>, key: Option<String>) -> anyhow::Result<()> {
+    let config = select_deployment(deployment.as_deref())?;
+    backup::backup_raw(&config, key).await
+}
+
+/// Restores a raw event backup into one deployment's database
+pub async fn restore_raw(deployment: Option<String>, key: String) -> anyhow::Result<()> {
+    let config = select_deployment(deployment.as_deref())?;
+    backup::restore_raw(&config, &key).await
+}
+
+/// Benchmarks one deployment's (empty) database
+pub async fn bench(deployment: Option<String>, options: BenchOptions) -> anyhow::Result<()> {
+    let config = select_deployment(deployment.as_deref())?;
+    bench::run(&config, &options).await
+}
+
+/// Verifies a raffle from chain data, returning whether every check passed
+pub async fn verify_raffle(options: VerifyOptions) -> anyhow::Result<bool> {
+    verify::run(options).await
+}
+
+/// Picks the configuration named by `--deployment`, which is required when
+/// `DEPLOYMENTS` lists more than one
+fn select_deployment(name: Option<&str>) -> anyhow::Result<AppConfig> {
+    let mut configs = AppConfig::load_all()?;
+    match name {
+        Some(name) => configs
+            .into_iter()
+            .find(|config| config.deployment.as_deref() == Some(name))
+            .ok_or_else(|| anyhow::anyhow!("unknown deployment '{name}'")),
+        None if configs.len() == 1 => Ok(configs.remove(0)),
+        None => anyhow::bail!("DEPLOYMENTS is set; pass --deployment NAME"),
+    }
+}
+
+/// Collects pass/fail results for the preflight report
+#[derive(Default)]
+pub(crate) struct Report {
+    pub(crate) failures: usize,
+}
+
+impl Report {
+    pub(crate) fn pass(&mut self, check: &str, detail: impl std::fmt::Display) {
+        println!("[PASS] {check}: {detail}");
+    }
+
+    pub(crate) fn fail(&mut self, check: &str, detail: impl std::fmt::Display) {
+        self.failures += 1;
+        println!("[FAIL] {check}: {detail}");
+    }
+
+    pub(crate) fn skip(&mut self, check: &str, reason: &str) {
+        println!("[SKIP] {check}: {reason}");
+    }
+}
+
+/// Runs every preflight check and prints a report
+///
+/// Returns `true` when all checks passed. Checks that depend on a failed one
+/// are skipped rather than reported as separate failures.
+pub async fn check_config() -> bool {
+    let mut report = Report::default();
+
+    let configs = match AppConfig::load_all() {
+        Ok(configs) => {
+            report.pass("configuration", "environment variables are valid");
+            configs
+        }
+        Err(err) => {
+            report.fail("configuration", format!("{err:#}"));
+            return finish(report);
+        }
+    };
+
+    for config in &configs {
+        if let Some(name) = &config.deployment {
+            println!("\nDeployment '{name}':");
+        }
+        check_deployment(&mut report, config).await;
+    }
+
+    finish(report)
+}
+
+async fn check_deployment(report: &mut Report, config: &AppConfig) {
+    if let Some(key) = config.attestation_signing_key.as_deref() {
+        match key.parse::<LocalWallet>() {
+            Ok(_) => report.pass("attestation key", "valid private key"),
+            Err(_) => report.fail("attestation key", "not a valid private key"),
+        }
+    }
+
+    if let Some(signer_config) = &config.keeper_signer {
+        match probe(KeeperSigner::connect(signer_config)).await {
+            Ok(signer) => report.pass(
+                "keeper signer",
+                format!("{} signer for {:#x}", signer.kind(), signer.address()),
+            ),
+            Err(err) => report.fail("keeper signer", err),
+        }
+    }
+
+    if let Some(geo_config) = &config.geo {
+        match geo::GeoScreen::load(geo_config) {
+            Ok(_) if geo_config.ip_db_dir.is_some() => {
+                report.pass("country screening", "GeoLite2 database loaded")
+            }
+            Ok(_) => report.pass("country screening", "country header only"),
+            Err(err) => report.fail("country screening", format!("{err:#}")),
+        }
+    }
+
+    match indexer::check_abis(config.randomness_provider_address.is_some()) {
+        Ok(()) => report.pass("contract ABIs", "artifacts found"),
+        Err(err) => report.fail("contract ABIs", format!("{err:#}")),
+    }
+
+    check_database(report, config).await;
+    check_rpc(report, config).await;
+}
+
+fn finish(report: Report) -> bool {
+    if report.failures == 0 {
+        println!("\nAll checks passed.");
+        true
+    } else {
+        println!("\n{} check(s) failed.", report.failures);
+        false
+    }
+}
+
+async fn check_database(report: &mut Report, config: &AppConfig) {
+    let options = match config.pg_connect_options() {
+        Ok(options) => options,
+        Err(err) => {
+            report.fail("database", err);
+            report.skip("migrations", "database unreachable");
+            report.skip("indexer cursor", "database unreachable");
+            return;
+        }
+    };
+    let pool = match probe(
+        PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options),
+    )
+    .await
+    {
+        Ok(pool) => {
+            report.pass("database", "connected");
+            pool
+        }
+        Err(err) => {
+            report.fail("database", err);
+            report.skip("migrations", "database unreachable");
+            report.skip("indexer cursor", "database unreachable");
+            return;
+        }
+    };
+
+    match probe(schema::inspect(&pool)).await {
+        Ok(schema) if schema.is_compatible() => report.pass("migrations", schema),
+        Ok(schema) => report.fail("migrations", schema),
+        Err(err) => report.fail(
+            "migrations",
+            format!("applied migrations unreadable ({err})"),
+        ),
+    }
+
+    // The indexer cursor row is created by the first migration
+    let cursor = probe(
+        sqlx::query_scalar::<_, i64>("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+            .fetch_optional(&pool),
+    )
+    .await;
+    match cursor {
+        Ok(Some(block)) => report.pass("indexer cursor", format!("at block {block}")),
+        Ok(None) => report.fail("indexer cursor", "indexer_state row missing"),
+        Err(err) => report.fail("indexer cursor", format!("schema not migrated ({err})")),
+    }
+
+    pool.close().await;
+}
+
+async fn check_rpc(report: &mut Report, config: &AppConfig) {
+    let provider = match Provider::<Http>::try_from(config.rpc_url.as_str()) {
+        Ok(provider) => provider,
+        Err(err) => {
+            report.fail("rpc", format!("invalid RPC_URL ({err})"));
+            return;
+        }
+    };
+
+    match probe(provider.get_chainid()).await {
+        Ok(chain_id) if chain_id.as_u64() == config.chain_id => {
+            report.pass("rpc chain id", chain_id);
+        }
+        Ok(chain_id) => {
+            report.fail(
+                "rpc chain id",
+                format!("RPC reports {chain_id}, CHAIN_ID is {}", config.chain_id),
+            );
+        }
+        Err(err) => {
+            report.fail("rpc chain id", err);
+            report.skip("factory contract", "RPC unreachable");
+            return;
+        }
+    }
+
+    check_code(
+        report,
+        &provider,
+        "factory contract",
+        &config.raffle_factory_address,
+    )
+    .await;
+    if let Some(address) = config.randomness_provider_address.as_deref() {
+        check_code(report, &provider, "randomness provider contract", address).await;
+    }
+
+    if config.api_rpc_url != config.rpc_url {
+        check_api_rpc(report, config).await;
+    }
+}
+
+/// Verifies the separate RPC endpoint used for API contract reads
+async fn check_api_rpc(report: &mut Report, config: &AppConfig) {
+    let provider = match Provider::<Http>::try_from(config.api_rpc_url.as_str()) {
+        Ok(provider) => provider,
+        Err(err) => {
+            report.fail("api rpc", format!("invalid API_RPC_URL ({err})"));
+            return;
+        }
+    };
+    match probe(provider.get_chainid()).await {
+        Ok(chain_id) if chain_id.as_u64() == config.chain_id => {
+            report.pass("api rpc chain id", chain_id);
+        }
+        Ok(chain_id) => report.fail(
+            "api rpc chain id",
+            format!(
+                "API RPC reports {chain_id}, CHAIN_ID is {}",
+                config.chain_id
+            ),
+        ),
+        Err(err) => report.fail("api rpc chain id", err),
+    }
+}
+
+/// Verifies that a contract is deployed at `address`
+async fn check_code(report: &mut Report, provider: &Provider<Http>, check: &str, address: &str) {
+    let Ok(parsed) = Address::from_str(address) else {
+        report.fail(check, format!("{address} is not a valid address"));
+        return;
+    };
+    match probe(provider.get_code(parsed, None)).await {
+        Ok(code) if !code.is_empty() => {
+            report.pass(check, format!("{address} has {} bytes of code", code.len()));
+        }
+        Ok(_) => report.fail(check, format!("no contract deployed at {address}")),
+        Err(err) => report.fail(check, err),
+    }
+}
+
+/// Awaits a probe with [`PROBE_TIMEOUT`], flattening timeout and probe errors
+pub(crate) async fn probe<T, E: std::fmt::Display>(
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, future).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err(format!("timed out after {}s", PROBE_TIMEOUT.as_secs())),
+    }
+}
@@ -0,0 +1,215 @@
+//! Participant cohorts
+//!
+//! Every `COHORT_REFRESH_INTERVAL_SECS` this job groups buyers into cohorts by the UTC
+//! week (starting Monday) of their first purchase, and counts for each cohort how many
+//! of its buyers bought tickets again in each following week and how many joined more
+//! than one raffle. The result replaces `participant_cohorts` and
+//! `participant_cohort_weeks` in one transaction; `GET /v1/analytics/cohorts` serves it.
+//!
+//! Purchases are attributed to a week by block time (insert time for rows indexed
+//! before block times were tracked). Archived raffles are included. Each run
+//! recomputes all cohorts from the indexed purchases, so reorgs and re-indexing are
+//! picked up by the next run.
+
+use crate::config::AppConfig;
+use anyhow::Context;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Runs the cohort job until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig) -> anyhow::Result<()> {
+    let interval = Duration::from_secs(config.cohort_refresh_interval_secs);
+    loop {
+        match refresh(&db).await {
+            Ok(cohorts) => tracing::debug!(cohorts, "participant cohorts refreshed"),
+            Err(err) => tracing::warn!(error = %format!("{err:#}"), "cohort refresh failed"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Recomputes every cohort from the indexed purchases, returning how many there are
+pub(crate) async fn refresh(db: &PgPool) -> anyhow::Result<u64> {
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+
+    // Replicas refreshing at the same time would otherwise both insert
+    sqlx::query("LOCK TABLE participant_cohorts IN EXCLUSIVE MODE")
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to lock cohorts")?;
+    sqlx::query("DELETE FROM participant_cohorts")
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to clear cohorts")?;
+
+    let cohorts = sqlx::query(
+        "WITH buyers AS (
+            SELECT buyer,
+                date_trunc('week', MIN(COALESCE(block_time, created_at)) AT TIME ZONE 'UTC')::date
+                    AS cohort_week,
+                COUNT(DISTINCT raffle_id) AS raffles,
+                SUM(count)::bigint AS tickets
+            FROM purchases_all
+            GROUP BY buyer
+         )
+         INSERT INTO participant_cohorts (cohort_week, buyers, repeat_buyers, tickets)
+         SELECT cohort_week, COUNT(*), COUNT(*) FILTER (WHERE raffles > 1), SUM(tickets)
+         FROM buyers
+         GROUP BY cohort_week",
+    )
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to compute cohorts")?
+    .rows_affected();
+
+    sqlx::query(
+        "WITH activity AS (
+            SELECT buyer,
+                date_trunc('week', COALESCE(block_time, created_at) AT TIME ZONE 'UTC')::date
+                    AS week,
+                count
+            FROM purchases_all
+         ),
+         cohorts AS (
+            SELECT buyer, MIN(week) AS cohort_week FROM activity GROUP BY buyer
+         )
+         INSERT INTO participant_cohort_weeks (cohort_week, week_offset, active_buyers, tickets)
+         SELECT c.cohort_week, (a.week - c.cohort_week) / 7,
+            COUNT(DISTINCT a.buyer), SUM(a.count)::bigint
+         FROM activity a
+         JOIN cohorts c USING (buyer)
+         GROUP BY c.cohort_week, (a.week - c.cohort_week) / 7",
+    )
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to compute cohort retention")?;
+
+    db_tx.commit().await.context("failed to commit cohorts")?;
+    Ok(cohorts)
+}
+
+/// Latest cohorts, as served by `GET /v1/analytics/cohorts`
+#[derive(Serialize)]
+pub struct CohortReport {
+    /// When the job last computed the cohorts (null until there are any)
+    pub computed_at: Option<DateTime<Utc>>,
+    /// Newest cohort first
+    pub cohorts: Vec<Cohort>,
+}
+
+#[derive(Serialize)]
+pub struct Cohort {
+    /// Monday of the week of the cohort's first purchases
+    pub cohort_week: NaiveDate,
+    pub buyers: i64,
+    /// Buyers who bought tickets in more than one raffle
+    pub repeat_buyers: i64,
+    /// `repeat_buyers / buyers`, rounded to four places
+    pub repeat_rate: f64,
+    pub tickets: i64,
+    /// Activity in the cohort's first week and each later week up to the newest week
+    /// with purchases; weeks without purchases have 0 active buyers
+    pub retention: Vec<CohortWeek>,
+}
+
+#[derive(Serialize)]
+pub struct CohortWeek {
+    pub week_offset: i32,
+    pub active_buyers: i64,
+    /// `active_buyers / buyers`, rounded to four places
+    pub rate: f64,
+    pub tickets: i64,
+}
+
+/// Reads the `limit` newest cohorts with their weekly retention
+pub(crate) async fn load(db: &PgPool, limit: i64) -> Result<CohortReport, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT cohort_week, buyers, repeat_buyers, tickets, computed_at
+         FROM participant_cohorts
+         ORDER BY cohort_week DESC
+         LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    let mut computed_at = None;
+    let mut cohorts = Vec::with_capacity(rows.len());
+    for row in rows {
+        computed_at = Some(row.try_get("computed_at")?);
+        let buyers: i64 = row.try_get("buyers")?;
+        let repeat_buyers: i64 = row.try_get("repeat_buyers")?;
+        cohorts.push(Cohort {
+            cohort_week: row.try_get("cohort_week")?,
+            buyers,
+            repeat_buyers,
+            repeat_rate: rate(repeat_buyers, buyers),
+            tickets: row.try_get("tickets")?,
+            retention: Vec::new(),
+        });
+    }
+    if computed_at.is_none() {
+        return Ok(CohortReport {
+            computed_at,
+            cohorts,
+        });
+    }
+
+    let weeks: Vec<NaiveDate> = cohorts.iter().map(|cohort| cohort.cohort_week).collect();
+    let rows = sqlx::query(
+        "SELECT cohort_week, week_offset, active_buyers, tickets
+         FROM participant_cohort_weeks
+         WHERE cohort_week = ANY($1)
+         ORDER BY cohort_week, week_offset",
+    )
+    .bind(&weeks)
+    .fetch_all(db)
+    .await?;
+    let mut activity: BTreeMap<(NaiveDate, i32), (i64, i64)> = BTreeMap::new();
+    for row in rows {
+        activity.insert(
+            (row.try_get("cohort_week")?, row.try_get("week_offset")?),
+            (row.try_get("active_buyers")?, row.try_get("tickets")?),
+        );
+    }
+
+    // Every cohort runs up to the newest active week, gaps filled with zeros
+    let newest_week = activity
+        .keys()
+        .filter_map(|(week, offset)| week.checked_add_days(Days::new(*offset as u64 * 7)))
+        .max();
+    for cohort in &mut cohorts {
+        let weeks_since = newest_week
+            .map(|newest| (newest - cohort.cohort_week).num_weeks() as i32)
+            .unwrap_or(0);
+        cohort.retention = (0..=weeks_since.max(0))
+            .map(|week_offset| {
+                let (active_buyers, tickets) = activity
+                    .get(&(cohort.cohort_week, week_offset))
+                    .copied()
+                    .unwrap_or((0, 0));
+                CohortWeek {
+                    week_offset,
+                    active_buyers,
+                    rate: rate(active_buyers, cohort.buyers),
+                    tickets,
+                }
+            })
+            .collect();
+    }
+
+    Ok(CohortReport {
+        computed_at,
+        cohorts,
+    })
+}
+
+fn rate(part: i64, whole: i64) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    (part as f64 * 10_000.0 / whole as f64).round() / 10_000.0
+}
@@ -4,8 +4,50 @@
 //! - DATABASE_URL contains credentials and is never logged
 //! - All required variables are validated on startup
 //! - Default values are safe fallbacks for development
+//!
+//! # Multiple deployments
+//! Setting `DEPLOYMENTS` to a comma-separated list of names (e.g. `testnet,mainnet`)
+//! serves several logically separate deployments from one process. Each deployment
+//! reads its variables from `<NAME>_<VAR>` (e.g. `MAINNET_RPC_URL`), falling back to
+//! the unprefixed `<VAR>` so shared settings only need to be set once. `BIND_ADDR`,
+//! `API_ERROR_FORMAT` and `SHUTDOWN_GRACE_SECONDS` are always shared.
 
+use crate::error::ErrorFormat;
+use crate::indexer::HeadTag;
+use crate::rpc::RpcBudgetMode;
+use crate::schema::SchemaCheck;
+use anyhow::Context;
+use log::LevelFilter;
+use sqlx::ConnectOptions;
+use sqlx::postgres::PgConnectOptions;
 use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Cache lifetimes per route: 5 seconds for lists and details, an hour for the proof
+/// and purchase statistics of a finalized raffle, a minute for fees and the audits,
+/// 5 minutes for the daily digest, and never for live status or per-key usage
+pub const DEFAULT_CACHE_CONTROL_ROUTES: &str = "/raffles=5,\
+    /raffles/{raffle_id}=5/3600,\
+    /raffles/{raffle_id}/purchases=5/3600,\
+    /raffles/{raffle_id}/participants=5,\
+    /raffles/{raffle_id}/proof=5/3600,\
+    /raffles/{raffle_id}/proof/bundle=5/3600,\
+    /raffles/{raffle_id}/stats/histogram=5/3600,\
+    /raffles/{raffle_id}/purchase-curve=5/3600,\
+    /fees=60,\
+    /randomness/requests=5,\
+    /randomness/fulfillments=5,\
+    /events=5,\
+    /digests/latest=300,\
+    /audit/fairness=60,\
+    /audit/randomness=60,\
+    /analytics/cohorts=300,\
+    /analytics/overlap=60,\
+    /referral-codes=30,\
+    /referral-codes/{code}=30,\
+    /status=no-store,\
+    /usage=no-store";
 
 /// Application configuration loaded from environment variables
 ///
@@ -15,36 +57,423 @@ use std::env;
 ///
 /// Optional environment variables with defaults:
 /// - `RPC_URL` - Arc testnet RPC URL (default: https://rpc.testnet.arc.network)
+/// - `API_RPC_URL` - RPC URL for API-initiated contract reads (default: `RPC_URL`)
+/// - `API_RPC_RATE_LIMIT` - Max API-initiated RPC requests per second, 0 = unlimited (default: 20)
+/// - `RPC_RATE_LIMIT` - Max requests per second to `RPC_URL`, shared by the indexer, keeper,
+///   orphan check, mempool watcher and API reads on the same URL; 0 = unlimited (default: 0)
+/// - `RPC_RATE_LIMIT_MODE` - What happens to requests over `RPC_RATE_LIMIT`: `queue` (wait)
+///   or `drop` (fail) (default: queue)
 /// - `CHAIN_ID` - Expected chain ID (default: 5042002)
 /// - `START_BLOCK` - Block to start indexing from (default: 0)
 /// - `EXPLORER_BASE_URL` - Block explorer URL (default: https://testnet.arcscan.app)
 /// - `BIND_ADDR` - Server bind address (default: 0.0.0.0:8080)
+/// - `SHUTDOWN_GRACE_SECONDS` - How long shutdown waits for in-flight requests and indexer
+///   batches before exiting anyway (default: 30)
 /// - `INDEXER_BATCH_SIZE` - Blocks per indexing batch (default: 2000)
-/// - `INDEXER_POLL_INTERVAL_MS` - Poll interval in milliseconds (default: 3000)
+/// - `INDEXER_POLL_INTERVAL_MS` - Initial poll interval in milliseconds (default: 3000)
+/// - `INDEXER_POLL_MIN_INTERVAL_MS` - Fastest adaptive poll interval (default: 500)
+/// - `INDEXER_POLL_MAX_INTERVAL_MS` - Slowest adaptive poll interval when idle (default: 30000)
+/// - `INDEXER_HEAD_TAG` - Block indexed up to: `latest`, `safe` or `finalized`, falling back to
+///   `latest` when the RPC node doesn't support the tag (default: `latest`)
+/// - `ORPHAN_CHECK_BLOCKS` - Recently indexed blocks re-checked for orphaned events, 0 disables
+///   (default: 64; see [`crate::orphans`])
+/// - `ORPHAN_CHECK_INTERVAL_SECS` - Seconds between orphan checks (default: 60)
+/// - `PAYOUT_CHECK_INTERVAL_SECS` - Seconds between checks of payout transaction receipts, 0
+///   disables (default: 60; see [`crate::payouts`])
+/// - `COHORT_REFRESH_INTERVAL_SECS` - Seconds between recomputations of participant cohorts, 0
+///   disables (default: 3600; see [`crate::cohorts`])
+/// - `SYBIL_INTERVAL_SECS` - Seconds between sybil clustering runs, 0 disables (default: 3600;
+///   see [`crate::sybil`])
+/// - `SYBIL_MIN_SHARED_BLOCKS` - Raffles two buyers must have bought in the same block of
+///   before they're linked (default: 2)
+/// - `SYBIL_SENDER_LOOKUP` - Look up the senders of purchase transactions from their receipts
+///   to link buyers funded by the same account (default: false)
+/// - `REFERRAL_TRACKING` - Decode referral codes from the calldata of purchase transactions,
+///   one RPC call per transaction (default: false; see [`crate::referrals`])
+/// - `RPC_CIRCUIT_FAILURE_THRESHOLD` - Consecutive RPC failures that pause indexing (default: 5)
+/// - `RPC_CIRCUIT_PROBE_INTERVAL_SECS` - Seconds between probes while paused (default: 30)
 /// - `RANDOMNESS_PROVIDER_ADDRESS` - Optional randomness provider address
+/// - `ATTESTATION_SIGNING_KEY` - Optional hex private key used to sign result attestations
+/// - `TOKEN_DECIMALS` - Decimals of the raffle payment token (default: 6, USDC)
+/// - `TOKEN_SYMBOL` - Symbol of the raffle payment token in display text (default: USDC)
+/// - `SITE_BASE_URL` - Optional public frontend URL; raffle pages are `<url>/raffles/<id>`
+/// - `CARD_IMAGE_URL_TEMPLATE` - Optional social card image URL, `{raffle_id}` is substituted
+/// - `MEMPOOL_WATCHER_ENABLED` - Watch pending `buyTickets` transactions (default: false)
+/// - `PENDING_PURCHASE_TTL_SECS` - Seconds a pending purchase stays visible (default: 120)
+/// - `DATABASE_SCHEMA` - Postgres schema for this deployment's tables (default: search_path)
+/// - `SCHEMA_CHECK` - `fail` to refuse starting against a database with pending, unknown or
+///   failed migrations, `warn` to only log them (default: `fail`; see [`crate::schema`])
+/// - `API_STATEMENT_TIMEOUT_MS` - `statement_timeout` for API queries, 0 disables (default: 5000)
+/// - `API_DB_MAX_CONNECTIONS`, `API_DB_MIN_CONNECTIONS`, `API_DB_ACQUIRE_TIMEOUT_MS`,
+///   `API_DB_MAX_LIFETIME_SECS`, `API_DB_IDLE_TIMEOUT_SECS` - API pool sizing (see [`PoolConfig`];
+///   defaults: 10, 0, 5000, 1800, 600)
+/// - `INDEXER_DB_*` - The same for the pool of the indexer and background jobs (defaults: 2, 0,
+///   30000, 1800, 600)
+/// - `API_REQUEST_TIMEOUT_MS` - Deadline of a whole API request before it is answered 503
+///   (default: 10000)
+/// - `API_MAX_CONCURRENT_REQUESTS` - API requests handled at once per deployment; requests
+///   beyond it are shed with 503 (default: 128)
+/// - `API_ERROR_FORMAT` - Body of API errors: `json` or `problem` for RFC 7807
+///   `application/problem+json` (default: `json`; see [`crate::error::problem_details`])
+/// - `SLOW_QUERY_THRESHOLD_MS` - Log and count queries slower than this, 0 disables (default: 500)
+/// - `RAFFLE_LIST_CACHE_TTL_MS` - Freshness of cached `GET /v1/raffles` pages, 0 disables (default: 2000)
+/// - `RAFFLE_LIST_CACHE_MAX_STALE_SECS` - Oldest cached page served while refreshing (default: 60)
+/// - `EMBED_CACHE_TTL_SECS` - Freshness of embed widget payloads, server-side and in `Cache-Control`
+///   (default: 30)
+/// - `NETWORK_CACHE_TTL_SECS` - Freshness of the head block and gas prices served by
+///   `GET /v1/network` (default: 10)
+/// - `CACHE_CONTROL_ROUTES` - `Cache-Control` per API route, empty disables (default:
+///   [`DEFAULT_CACHE_CONTROL_ROUTES`]; see [`RouteCacheControl`])
+/// - `KEEPER_SIGNER` - Keeper signer: `local`, `kms` or `remote` (default: `local` when
+///   `KEEPER_PRIVATE_KEY` is set, otherwise the keeper is disabled; see [`crate::signer`])
+/// - `KEEPER_PRIVATE_KEY` - Hex private key for the `local` signer
+/// - `KEEPER_KMS_KEY_ID` - AWS KMS key ID or ARN for the `kms` signer
+/// - `KEEPER_KMS_REGION` - AWS region of the KMS key (default: `AWS_REGION`)
+/// - `KEEPER_KMS_ENDPOINT` - Optional KMS endpoint override (e.g. a VPC endpoint)
+/// - `KEEPER_REMOTE_SIGNER_URL` - JSON-RPC URL of the `remote` signer
+/// - `KEEPER_ADDRESS` - Keeper account managed by the `remote` signer
+/// - `KEEPER_POLL_INTERVAL_SECS` - Seconds between keeper cycles (default: 15)
+/// - `KEEPER_TX_STUCK_SECS` - Seconds before a pending keeper transaction is replaced (default: 60)
+/// - `KEEPER_GAS_BUMP_PERCENT` - Gas price increase per replacement, at least 10 (default: 20)
+/// - `KEEPER_MAX_GAS_PRICE_GWEI` - Highest gas price the keeper pays (default: 500)
+/// - `ADMIN_API_KEY` - Optional bearer token enabling the `/v1/admin` endpoints
+/// - `ADMIN_JWT_SECRET` - Optional HMAC key (at least 32 bytes) for role-scoped admin tokens
+///   (see [`crate::auth`])
+/// - `ADMIN_JWT_MAX_TTL_SECS` - Longest lifetime of an admin token (default: 43200)
+/// - `API_KEY_DEFAULT_DAILY_QUOTA` - Daily request quota of newly minted API keys (default: 10000)
+/// - `API_KEY_DEFAULT_RATE_LIMIT` - Requests per minute of newly minted API keys (default: 60)
+/// - `OUTBOX_POLL_INTERVAL_MS` - Milliseconds between outbox dispatch cycles (default: 250)
+/// - `WEBHOOK_POLL_INTERVAL_SECS` - Seconds between webhook delivery cycles (default: 10)
+/// - `WEBHOOK_MAX_ATTEMPTS` - Delivery attempts before a notification is marked failed (default: 8)
+/// - `WEBHOOK_SIGNING_SECRET` - Optional HMAC key for the `X-Webhook-Signature` header
+///   (replaced by secrets rotated through the admin API)
+/// - `WEBHOOK_SECRET_OVERLAP_SECS` - How long the previous signing secret keeps signing
+///   after a rotation (default: 86400)
+/// - `WHALE_MIN_TICKETS` - Optional ticket count from which a purchase is a whale purchase
+/// - `WHALE_MIN_AMOUNT` - Optional amount (token base units) from which a purchase is a whale
+///   purchase
+/// - `WHALE_WEBHOOK_URL` - Optional webhook receiving `whale_purchase` events
+/// - `DIGEST_WEBHOOK_URL` - Optional webhook receiving `daily_digest` events
+/// - `STUCK_CLOSED_SLA_SECS` - Seconds a raffle may stay `CLOSED` before it is stuck
+///   (default: 900, 0 disables the check)
+/// - `STUCK_RANDOM_REQUESTED_SLA_SECS` - Seconds a raffle may wait for randomness before it
+///   is stuck (default: 600, 0 disables the check)
+/// - `STUCK_WEBHOOK_URL` - Optional webhook receiving `raffle_stuck` events
+/// - `ARCHIVE_AFTER_DAYS` - Optional age in days after which completed raffles are moved to
+///   the archive tables (unset disables archival)
+/// - `EXPORT_BUCKET` - Optional bucket receiving Parquet snapshots (unset disables exports)
+/// - `EXPORT_ENDPOINT` - S3-compatible endpoint (default: `https://s3.{EXPORT_REGION}.amazonaws.com`)
+/// - `EXPORT_REGION` - Signing region of the bucket (default: `AWS_REGION`, else `us-east-1`)
+/// - `EXPORT_PREFIX` - Key prefix of snapshots in the bucket (default: `snapshots`)
+/// - `EXPORT_INTERVAL_SECS` - Seconds between snapshots (default: 86400)
+/// - `EXPORT_ACCESS_KEY_ID` / `EXPORT_SECRET_ACCESS_KEY` - Optional access key for the bucket
+///   (default: the AWS credential chain)
+/// - `BACKUP_BUCKET` - Optional bucket for `backup-raw` / `restore-raw` (unset disables them)
+/// - `BACKUP_ENDPOINT`, `BACKUP_REGION`, `BACKUP_ACCESS_KEY_ID` / `BACKUP_SECRET_ACCESS_KEY` -
+///   Same as their `EXPORT_*` counterparts, for the backup bucket
+/// - `BACKUP_PREFIX` - Key prefix of raw event backups in the bucket (default: `backups`)
+/// - `GEO_DENY_COUNTRIES` / `GEO_ALLOW_COUNTRIES` - Optional comma-separated ISO 3166-1
+///   alpha-2 codes; clients in a denied country, or outside the allowed ones, are restricted
+///   (set at most one; unset disables screening, see [`crate::geo`])
+/// - `GEO_COUNTRY_HEADER` - Header carrying the client's country, set by the edge (e.g.
+///   `CF-IPCountry`)
+/// - `GEO_IP_DB_DIR` - Directory of a MaxMind GeoLite2 Country CSV database, used when the
+///   header is unset or absent
+/// - `GEO_CLIENT_IP_HEADER` - Header whose first address is the client IP for database
+///   lookups, e.g. `X-Forwarded-For` (default: the connection's peer address)
+/// - `GEO_RESTRICT_UNKNOWN` - Treat clients whose country can't be determined as restricted
+///   (default: false)
+/// - `GEO_ROUTES` - What restricted clients get per API route (default: `*=block`; see
+///   [`RouteGeoPolicy`])
 #[derive(Clone)]
 pub struct AppConfig {
+    /// Deployment name (`None` when `DEPLOYMENTS` is unset)
+    pub deployment: Option<String>,
     pub rpc_url: String,
+    pub api_rpc_url: String,
+    pub api_rpc_rate_limit: u32,
+    pub rpc_rate_limit: u32,
+    pub rpc_rate_limit_mode: RpcBudgetMode,
     pub chain_id: u64,
     pub start_block: u64,
     /// PostgreSQL connection string (contains credentials - never log this)
     pub database_url: String,
+    pub database_schema: Option<String>,
+    pub schema_check: SchemaCheck,
+    pub api_statement_timeout_ms: u64,
+    /// Pool serving API handlers
+    pub api_pool: PoolConfig,
+    /// Pool of the indexer and background jobs, so API bursts can't starve indexing
+    pub indexer_pool: PoolConfig,
+    pub api_request_timeout_ms: u64,
+    pub api_max_concurrent_requests: usize,
+    pub api_error_format: ErrorFormat,
+    pub slow_query_threshold_ms: u64,
+    pub raffle_list_cache_ttl_ms: u64,
+    pub raffle_list_cache_max_stale_secs: u64,
+    pub embed_cache_ttl_secs: u64,
+    pub network_cache_ttl_secs: u64,
+    pub cache_control_routes: Vec<RouteCacheControl>,
     pub raffle_factory_address: String,
     pub randomness_provider_address: Option<String>,
     pub explorer_base_url: String,
     pub bind_addr: String,
+    pub shutdown_grace_secs: u64,
     pub indexer_batch_size: u64,
     pub indexer_poll_interval_ms: u64,
+    pub indexer_poll_min_interval_ms: u64,
+    pub indexer_poll_max_interval_ms: u64,
+    pub indexer_head_tag: HeadTag,
+    pub orphan_check_blocks: u64,
+    pub orphan_check_interval_secs: u64,
+    pub payout_check_interval_secs: u64,
+    pub cohort_refresh_interval_secs: u64,
+    pub sybil_interval_secs: u64,
+    pub sybil_min_shared_blocks: i64,
+    pub sybil_sender_lookup: bool,
+    pub referral_tracking: bool,
+    pub rpc_circuit_failure_threshold: u32,
+    pub rpc_circuit_probe_interval_secs: u64,
+    pub token_decimals: u32,
+    pub token_symbol: String,
+    pub site_base_url: Option<String>,
+    pub card_image_url_template: Option<String>,
+    /// Private key for signing result attestations (secret - never log this)
+    pub attestation_signing_key: Option<String>,
+    pub mempool_watcher_enabled: bool,
+    pub pending_purchase_ttl_secs: u64,
+    /// Keeper signer (`None` disables the keeper)
+    pub keeper_signer: Option<KeeperSignerConfig>,
+    pub keeper_poll_interval_secs: u64,
+    pub keeper_tx_stuck_secs: u64,
+    pub keeper_gas_bump_percent: u64,
+    pub keeper_max_gas_price_gwei: u64,
+    /// Bearer token for admin endpoints (secret - never log this)
+    pub admin_api_key: Option<String>,
+    /// Signing key of admin tokens (secret - never log this)
+    pub admin_jwt_secret: Option<String>,
+    pub admin_jwt_max_ttl_secs: u64,
+    pub api_key_default_daily_quota: i64,
+    pub api_key_default_rate_limit: i32,
+    pub outbox_poll_interval_ms: u64,
+    pub webhook_poll_interval_secs: u64,
+    pub webhook_max_attempts: u32,
+    /// HMAC key for webhook payload signatures (secret - never log this)
+    pub webhook_signing_secret: Option<String>,
+    pub webhook_secret_overlap_secs: u64,
+    pub whale_min_tickets: Option<i64>,
+    pub whale_min_amount: Option<u128>,
+    /// Webhook for whale purchase events; chat webhook URLs embed a token (secret - never log this)
+    pub whale_webhook_url: Option<String>,
+    /// Webhook for daily digests (secret - never log this)
+    pub digest_webhook_url: Option<String>,
+    pub stuck_closed_sla_secs: u64,
+    pub stuck_random_requested_sla_secs: u64,
+    /// Webhook for stuck raffle alerts (secret - never log this)
+    pub stuck_webhook_url: Option<String>,
+    pub archive_after_days: Option<i32>,
+    /// Parquet snapshot uploads (`None` disables exports)
+    pub export: Option<ExportConfig>,
+    /// Raw event backups (`None` disables `backup-raw` / `restore-raw`)
+    pub backup: Option<BackupConfig>,
+    /// Country screening of API requests (`None` disables it)
+    pub geo: Option<GeoConfig>,
+}
+
+/// `Cache-Control` policy of one API route
+///
+/// `CACHE_CONTROL_ROUTES` is a comma-separated list of `<route>=<policy>`, where the
+/// route is the pattern below the `/v1` (or `/v1/{deployment}`) prefix, e.g.
+/// `/raffles/{raffle_id}/proof`, and the policy is `no-store`, `<max-age secs>`, or
+/// `<max-age secs>/<finalized max-age secs>` for routes that can answer about a
+/// finalized raffle, which no longer changes.
+#[derive(Clone, Debug)]
+pub struct RouteCacheControl {
+    pub route: String,
+    /// `None` sends `no-store`
+    pub max_age_secs: Option<u64>,
+    /// Lifetime of responses marked finalized (default: `max_age_secs`)
+    pub finalized_max_age_secs: Option<u64>,
+}
+
+/// Countries whose clients are restricted (see [`crate::geo`])
+#[derive(Clone, Debug)]
+pub enum CountryRule {
+    /// Only these countries are unrestricted
+    Allow(Vec<String>),
+    /// These countries are restricted
+    Deny(Vec<String>),
+}
+
+/// What a restricted client gets from a route
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeoAction {
+    /// Answered 451 without running the handler
+    Block,
+    /// Served, marked with `X-Restricted-Region` and kept out of shared caches
+    Watermark,
+    /// Served unchanged
+    Off,
+}
+
+/// Screening policy of one API route
+///
+/// `GEO_ROUTES` is a comma-separated list of `<route>=<action>`, where the route is the
+/// pattern below the `/v1` (or `/v1/{deployment}`) prefix as in `CACHE_CONTROL_ROUTES`,
+/// or `*` for every route not listed, and the action is `block`, `watermark` or `off`.
+/// `*` doesn't cover the `/admin` routes; list them to screen them.
+#[derive(Clone, Debug)]
+pub struct RouteGeoPolicy {
+    pub route: String,
+    pub action: GeoAction,
+}
+
+/// Country screening of API requests (see [`crate::geo`])
+#[derive(Clone, Debug)]
+pub struct GeoConfig {
+    pub countries: CountryRule,
+    /// Header carrying the client's country, trusted as set by the edge
+    pub country_header: Option<String>,
+    /// Directory of a GeoLite2 Country CSV database
+    pub ip_db_dir: Option<String>,
+    /// Header whose first address is the client IP (`None`: the peer address)
+    pub client_ip_header: Option<String>,
+    pub restrict_unknown: bool,
+    pub routes: Vec<RouteGeoPolicy>,
+}
+
+/// Sizing and connection lifetimes of a database pool
+///
+/// Read from `{PREFIX}_MAX_CONNECTIONS`, `_MIN_CONNECTIONS` (kept open even when idle),
+/// `_ACQUIRE_TIMEOUT_MS` (wait for a free connection before failing), `_MAX_LIFETIME_SECS`
+/// and `_IDLE_TIMEOUT_SECS` (`0` disables either), with `API_DB` and `INDEXER_DB` as
+/// prefixes.
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    /// Connections are closed and replaced after this long; `None` keeps them
+    pub max_lifetime: Option<Duration>,
+    /// Connections above `min_connections` idle this long are closed; `None` keeps them
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Where the keeper's transactions are signed (see [`crate::signer`])
+#[derive(Clone)]
+pub enum KeeperSignerConfig {
+    /// Raw private key (secret - never log this)
+    Local { private_key: String },
+    /// AWS KMS secp256k1 key
+    Kms {
+        key_id: String,
+        region: String,
+        endpoint: Option<String>,
+    },
+    /// Remote signer answering `eth_signTransaction`
+    Remote { url: String, address: String },
+}
+
+// Implement Debug manually to avoid logging the private key
+impl std::fmt::Debug for KeeperSignerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeeperSignerConfig::Local { .. } => f
+                .debug_struct("Local")
+                .field("private_key", &"[REDACTED]")
+                .finish(),
+            KeeperSignerConfig::Kms {
+                key_id,
+                region,
+                endpoint,
+            } => f
+                .debug_struct("Kms")
+                .field("key_id", key_id)
+                .field("region", region)
+                .field("endpoint", endpoint)
+                .finish(),
+            KeeperSignerConfig::Remote { url, address } => f
+                .debug_struct("Remote")
+                .field("url", url)
+                .field("address", address)
+                .finish(),
+        }
+    }
+}
+
+/// An S3-compatible bucket (see [`crate::storage`])
+#[derive(Clone)]
+pub struct ObjectStoreConfig {
+    pub bucket: String,
+    /// S3 API base URL; objects are addressed path-style (`{endpoint}/{bucket}/{key}`)
+    pub endpoint: String,
+    pub region: String,
+    /// Access key ID and secret (secret - never log this); `None` uses the AWS credential chain
+    pub access_key: Option<(String, String)>,
+}
+
+// Implement Debug manually to avoid logging the secret access key
+impl std::fmt::Debug for ObjectStoreConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectStoreConfig")
+            .field("bucket", &self.bucket)
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region)
+            .field("access_key_id", &self.access_key.as_ref().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+/// Where Parquet snapshots are uploaded (see [`crate::export`])
+#[derive(Clone, Debug)]
+pub struct ExportConfig {
+    pub store: ObjectStoreConfig,
+    /// Key prefix of snapshot directories, without slashes at either end
+    pub prefix: String,
+    pub interval_secs: u64,
+}
+
+/// Where `backup-raw` writes raw event backups (see [`crate::backup`])
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    pub store: ObjectStoreConfig,
+    /// Key prefix of backups, without slashes at either end
+    pub prefix: String,
 }
 
 // Implement Debug manually to avoid logging DATABASE_URL
 impl std::fmt::Debug for AppConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AppConfig")
+            .field("deployment", &self.deployment)
             .field("rpc_url", &self.rpc_url)
+            .field("api_rpc_url", &self.api_rpc_url)
+            .field("api_rpc_rate_limit", &self.api_rpc_rate_limit)
+            .field("rpc_rate_limit", &self.rpc_rate_limit)
+            .field("rpc_rate_limit_mode", &self.rpc_rate_limit_mode)
             .field("chain_id", &self.chain_id)
             .field("start_block", &self.start_block)
             .field("database_url", &"[REDACTED]")
+            .field("database_schema", &self.database_schema)
+            .field("schema_check", &self.schema_check)
+            .field("api_statement_timeout_ms", &self.api_statement_timeout_ms)
+            .field("api_pool", &self.api_pool)
+            .field("indexer_pool", &self.indexer_pool)
+            .field("api_request_timeout_ms", &self.api_request_timeout_ms)
+            .field(
+                "api_max_concurrent_requests",
+                &self.api_max_concurrent_requests,
+            )
+            .field("api_error_format", &self.api_error_format)
+            .field("slow_query_threshold_ms", &self.slow_query_threshold_ms)
+            .field("raffle_list_cache_ttl_ms", &self.raffle_list_cache_ttl_ms)
+            .field(
+                "raffle_list_cache_max_stale_secs",
+                &self.raffle_list_cache_max_stale_secs,
+            )
+            .field("embed_cache_ttl_secs", &self.embed_cache_ttl_secs)
+            .field("network_cache_ttl_secs", &self.network_cache_ttl_secs)
+            .field("cache_control_routes", &self.cache_control_routes)
             .field("raffle_factory_address", &self.raffle_factory_address)
             .field(
                 "randomness_provider_address",
@@ -52,8 +481,112 @@ impl std::fmt::Debug for AppConfig {
             )
             .field("explorer_base_url", &self.explorer_base_url)
             .field("bind_addr", &self.bind_addr)
+            .field("shutdown_grace_secs", &self.shutdown_grace_secs)
             .field("indexer_batch_size", &self.indexer_batch_size)
             .field("indexer_poll_interval_ms", &self.indexer_poll_interval_ms)
+            .field(
+                "indexer_poll_min_interval_ms",
+                &self.indexer_poll_min_interval_ms,
+            )
+            .field(
+                "indexer_poll_max_interval_ms",
+                &self.indexer_poll_max_interval_ms,
+            )
+            .field("indexer_head_tag", &self.indexer_head_tag)
+            .field("orphan_check_blocks", &self.orphan_check_blocks)
+            .field(
+                "orphan_check_interval_secs",
+                &self.orphan_check_interval_secs,
+            )
+            .field(
+                "payout_check_interval_secs",
+                &self.payout_check_interval_secs,
+            )
+            .field(
+                "cohort_refresh_interval_secs",
+                &self.cohort_refresh_interval_secs,
+            )
+            .field("sybil_interval_secs", &self.sybil_interval_secs)
+            .field("sybil_min_shared_blocks", &self.sybil_min_shared_blocks)
+            .field("sybil_sender_lookup", &self.sybil_sender_lookup)
+            .field("referral_tracking", &self.referral_tracking)
+            .field(
+                "rpc_circuit_failure_threshold",
+                &self.rpc_circuit_failure_threshold,
+            )
+            .field(
+                "rpc_circuit_probe_interval_secs",
+                &self.rpc_circuit_probe_interval_secs,
+            )
+            .field("token_decimals", &self.token_decimals)
+            .field("token_symbol", &self.token_symbol)
+            .field("site_base_url", &self.site_base_url)
+            .field("card_image_url_template", &self.card_image_url_template)
+            .field(
+                "attestation_signing_key",
+                &self.attestation_signing_key.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("mempool_watcher_enabled", &self.mempool_watcher_enabled)
+            .field("pending_purchase_ttl_secs", &self.pending_purchase_ttl_secs)
+            .field("keeper_signer", &self.keeper_signer)
+            .field("keeper_poll_interval_secs", &self.keeper_poll_interval_secs)
+            .field("keeper_tx_stuck_secs", &self.keeper_tx_stuck_secs)
+            .field("keeper_gas_bump_percent", &self.keeper_gas_bump_percent)
+            .field("keeper_max_gas_price_gwei", &self.keeper_max_gas_price_gwei)
+            .field(
+                "admin_api_key",
+                &self.admin_api_key.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field(
+                "admin_jwt_secret",
+                &self.admin_jwt_secret.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("admin_jwt_max_ttl_secs", &self.admin_jwt_max_ttl_secs)
+            .field(
+                "api_key_default_daily_quota",
+                &self.api_key_default_daily_quota,
+            )
+            .field(
+                "api_key_default_rate_limit",
+                &self.api_key_default_rate_limit,
+            )
+            .field("outbox_poll_interval_ms", &self.outbox_poll_interval_ms)
+            .field(
+                "webhook_poll_interval_secs",
+                &self.webhook_poll_interval_secs,
+            )
+            .field("webhook_max_attempts", &self.webhook_max_attempts)
+            .field(
+                "webhook_signing_secret",
+                &self.webhook_signing_secret.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field(
+                "webhook_secret_overlap_secs",
+                &self.webhook_secret_overlap_secs,
+            )
+            .field("whale_min_tickets", &self.whale_min_tickets)
+            .field("whale_min_amount", &self.whale_min_amount)
+            .field(
+                "whale_webhook_url",
+                &self.whale_webhook_url.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field(
+                "digest_webhook_url",
+                &self.digest_webhook_url.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("stuck_closed_sla_secs", &self.stuck_closed_sla_secs)
+            .field(
+                "stuck_random_requested_sla_secs",
+                &self.stuck_random_requested_sla_secs,
+            )
+            .field(
+                "stuck_webhook_url",
+                &self.stuck_webhook_url.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("archive_after_days", &self.archive_after_days)
+            .field("export", &self.export)
+            .field("backup", &self.backup)
+            .field("geo", &self.geo)
             .finish()
     }
 }
@@ -66,25 +599,177 @@ impl AppConfig {
     /// - Required variables are missing (DATABASE_URL, RAFFLE_FACTORY_ADDRESS)
     /// - Numeric values fail to parse
     pub fn from_env() -> anyhow::Result<Self> {
+        Self::load(None)
+    }
+
+    /// Loads the configuration of every deployment
+    ///
+    /// Returns a single unnamed configuration when `DEPLOYMENTS` is unset.
+    ///
+    /// # Errors
+    /// Returns error if a deployment name is invalid or repeated, or if any
+    /// deployment's configuration is invalid (see [`AppConfig::from_env`])
+    pub fn load_all() -> anyhow::Result<Vec<Self>> {
+        let Some(names) = env::var("DEPLOYMENTS")
+            .ok()
+            .filter(|names| !names.trim().is_empty())
+        else {
+            return Ok(vec![Self::from_env()?]);
+        };
+
+        let mut configs: Vec<Self> = Vec::new();
+        for name in names.split(',').map(str::trim) {
+            if !is_valid_deployment_name(name) {
+                anyhow::bail!(
+                    "DEPLOYMENTS entry '{name}' must be lowercase letters, digits and '-' (max 32 chars)"
+                );
+            }
+            if configs
+                .iter()
+                .any(|c| c.deployment.as_deref() == Some(name))
+            {
+                anyhow::bail!("DEPLOYMENTS lists '{name}' more than once");
+            }
+            let config = Self::load(Some(name)).with_context(|| format!("deployment '{name}'"))?;
+            configs.push(config);
+        }
+        Ok(configs)
+    }
+
+    /// Whether a purchase reaches `WHALE_MIN_TICKETS` or `WHALE_MIN_AMOUNT`
+    pub fn is_whale_purchase(&self, tickets: i64, amount: u128) -> bool {
+        self.whale_min_tickets.is_some_and(|min| tickets >= min)
+            || self.whale_min_amount.is_some_and(|min| amount >= min)
+    }
+
+    /// Path the deployment's `/v1` routes are mounted at
+    pub fn route_prefix(&self) -> String {
+        match &self.deployment {
+            Some(name) => format!("/v1/{name}"),
+            None => "/v1".to_string(),
+        }
+    }
+
+    /// Path the deployment's `/v2` routes are mounted at
+    pub fn route_prefix_v2(&self) -> String {
+        match &self.deployment {
+            Some(name) => format!("/v2/{name}"),
+            None => "/v2".to_string(),
+        }
+    }
+
+    /// Strips the deployment's `/v1` or `/v2` prefix from a matched route, so
+    /// per-route settings apply to both API versions
+    pub fn strip_route_prefix<'a>(&self, route: &'a str) -> Option<&'a str> {
+        route
+            .strip_prefix(self.route_prefix().as_str())
+            .or_else(|| route.strip_prefix(self.route_prefix_v2().as_str()))
+    }
+
+    /// Connection options for this deployment's database
+    ///
+    /// Applies `DATABASE_SCHEMA` as the connection `search_path`, so deployments can
+    /// share one database with their tables in separate schemas, and logs statements
+    /// slower than `SLOW_QUERY_THRESHOLD_MS` at `WARN`.
+    pub fn pg_connect_options(&self) -> anyhow::Result<PgConnectOptions> {
+        if self.database_url.starts_with("sqlite:") {
+            anyhow::bail!(
+                "DATABASE_URL must be a Postgres connection string; SQLite is not supported \
+                 (see \"Database\" in the README)"
+            );
+        }
+        let options = PgConnectOptions::from_str(&self.database_url)
+            .map_err(|_| anyhow::anyhow!("DATABASE_URL is not a valid connection string"))?;
+        let options = match self.slow_query_threshold_ms {
+            0 => options.log_slow_statements(LevelFilter::Off, Duration::ZERO),
+            ms => options.log_slow_statements(LevelFilter::Warn, Duration::from_millis(ms)),
+        };
+        Ok(match &self.database_schema {
+            Some(schema) => options.options([("search_path", schema.as_str())]),
+            None => options,
+        })
+    }
+
+    /// Connection options for the API pool
+    ///
+    /// Like [`AppConfig::pg_connect_options`], plus `API_STATEMENT_TIMEOUT_MS` as the
+    /// session `statement_timeout` so a slow query can't hold an API connection for
+    /// minutes. The indexer uses its own pool without this limit.
+    pub fn api_pg_connect_options(&self) -> anyhow::Result<PgConnectOptions> {
+        let options = self.pg_connect_options()?;
+        Ok(match self.api_statement_timeout_ms {
+            0 => options,
+            ms => options.options([("statement_timeout", ms.to_string())]),
+        })
+    }
+
+    /// Loads an unnamed configuration from `vars` instead of the environment
+    #[cfg(test)]
+    pub fn from_vars(vars: &[(&str, &str)]) -> anyhow::Result<Self> {
+        Self::load_from(None, |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+                .ok_or(env::VarError::NotPresent)
+        })
+    }
+
+    /// Loads configuration from the environment
+    fn load(deployment: Option<&str>) -> anyhow::Result<Self> {
+        Self::load_from(deployment, |name| env::var(name))
+    }
+
+    /// Loads configuration, reading `<DEPLOYMENT>_<VAR>` before `<VAR>` when named
+    fn load_from(
+        deployment: Option<&str>,
+        lookup: impl Fn(&str) -> Result<String, env::VarError>,
+    ) -> anyhow::Result<Self> {
+        let prefix = deployment.map(|name| name.to_ascii_uppercase().replace('-', "_"));
+        let var = |name: &str| match &prefix {
+            Some(prefix) => lookup(&format!("{prefix}_{name}")).or_else(|_| lookup(name)),
+            None => lookup(name),
+        };
+
         let rpc_url =
-            env::var("RPC_URL").unwrap_or_else(|_| "https://rpc.testnet.arc.network".to_string());
+            var("RPC_URL").unwrap_or_else(|_| "https://rpc.testnet.arc.network".to_string());
 
-        let chain_id = env::var("CHAIN_ID")
+        let api_rpc_url = var("API_RPC_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| rpc_url.clone());
+
+        let api_rpc_rate_limit = var("API_RPC_RATE_LIMIT")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("API_RPC_RATE_LIMIT must be a valid u32"))?;
+
+        let rpc_rate_limit = var("RPC_RATE_LIMIT")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("RPC_RATE_LIMIT must be a valid u32"))?;
+
+        let rpc_rate_limit_mode = match var("RPC_RATE_LIMIT_MODE").as_deref() {
+            Err(_) | Ok("") | Ok("queue") => RpcBudgetMode::Queue,
+            Ok("drop") => RpcBudgetMode::Drop,
+            Ok(_) => anyhow::bail!("RPC_RATE_LIMIT_MODE must be 'queue' or 'drop'"),
+        };
+
+        let chain_id = var("CHAIN_ID")
             .unwrap_or_else(|_| "5042002".to_string())
             .parse()
             .map_err(|_| anyhow::anyhow!("CHAIN_ID must be a valid u64"))?;
 
-        let start_block = env::var("START_BLOCK")
+        let start_block = var("START_BLOCK")
             .unwrap_or_else(|_| "0".to_string())
             .parse()
             .map_err(|_| anyhow::anyhow!("START_BLOCK must be a valid u64"))?;
 
         // Required: DATABASE_URL (contains credentials)
         let database_url =
-            env::var("DATABASE_URL").map_err(|_| anyhow::anyhow!("DATABASE_URL is required"))?;
+            var("DATABASE_URL").map_err(|_| anyhow::anyhow!("DATABASE_URL is required"))?;
 
         // Required: RAFFLE_FACTORY_ADDRESS
-        let raffle_factory_address = env::var("RAFFLE_FACTORY_ADDRESS")
+        let raffle_factory_address = var("RAFFLE_FACTORY_ADDRESS")
             .map_err(|_| anyhow::anyhow!("RAFFLE_FACTORY_ADDRESS is required"))?;
 
         // Validate address format (basic check)
@@ -94,34 +779,862 @@ impl AppConfig {
             );
         }
 
-        let randomness_provider_address = env::var("RANDOMNESS_PROVIDER_ADDRESS").ok();
+        let database_schema = var("DATABASE_SCHEMA").ok().filter(|s| !s.is_empty());
+        if let Some(schema) = &database_schema
+            && !is_valid_schema_name(schema)
+        {
+            anyhow::bail!("DATABASE_SCHEMA must be a lowercase SQL identifier");
+        }
 
-        let explorer_base_url = env::var("EXPLORER_BASE_URL")
-            .unwrap_or_else(|_| "https://testnet.arcscan.app".to_string());
+        let schema_check = match var("SCHEMA_CHECK").as_deref() {
+            Err(_) | Ok("") | Ok("fail") => SchemaCheck::Fail,
+            Ok("warn") => SchemaCheck::Warn,
+            Ok(_) => anyhow::bail!("SCHEMA_CHECK must be 'fail' or 'warn'"),
+        };
 
-        let bind_addr = env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+        let api_statement_timeout_ms = var("API_STATEMENT_TIMEOUT_MS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("API_STATEMENT_TIMEOUT_MS must be a valid u64"))?;
 
-        let indexer_batch_size = env::var("INDEXER_BATCH_SIZE")
+        let api_pool = load_pool(
+            &var,
+            "API_DB",
+            PoolConfig {
+                max_connections: 10,
+                min_connections: 0,
+                acquire_timeout: Duration::from_secs(5),
+                max_lifetime: Some(Duration::from_secs(30 * 60)),
+                idle_timeout: Some(Duration::from_secs(10 * 60)),
+            },
+        )?;
+        let indexer_pool = load_pool(
+            &var,
+            "INDEXER_DB",
+            PoolConfig {
+                max_connections: 2,
+                min_connections: 0,
+                acquire_timeout: Duration::from_secs(30),
+                max_lifetime: Some(Duration::from_secs(30 * 60)),
+                idle_timeout: Some(Duration::from_secs(10 * 60)),
+            },
+        )?;
+
+        let api_request_timeout_ms = var("API_REQUEST_TIMEOUT_MS")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .ok()
+            .filter(|ms| *ms > 0)
+            .ok_or_else(|| anyhow::anyhow!("API_REQUEST_TIMEOUT_MS must be a positive integer"))?;
+
+        let api_max_concurrent_requests = var("API_MAX_CONCURRENT_REQUESTS")
+            .unwrap_or_else(|_| "128".to_string())
+            .parse()
+            .ok()
+            .filter(|max| *max > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("API_MAX_CONCURRENT_REQUESTS must be a positive integer")
+            })?;
+
+        let api_error_format = match lookup("API_ERROR_FORMAT").as_deref() {
+            Err(_) | Ok("") | Ok("json") => ErrorFormat::Json,
+            Ok("problem") => ErrorFormat::Problem,
+            Ok(_) => anyhow::bail!("API_ERROR_FORMAT must be 'json' or 'problem'"),
+        };
+
+        let slow_query_threshold_ms = var("SLOW_QUERY_THRESHOLD_MS")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("SLOW_QUERY_THRESHOLD_MS must be a valid u64"))?;
+
+        let raffle_list_cache_ttl_ms = var("RAFFLE_LIST_CACHE_TTL_MS")
+            .unwrap_or_else(|_| "2000".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("RAFFLE_LIST_CACHE_TTL_MS must be a valid u64"))?;
+
+        let raffle_list_cache_max_stale_secs = var("RAFFLE_LIST_CACHE_MAX_STALE_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("RAFFLE_LIST_CACHE_MAX_STALE_SECS must be a valid u64"))?;
+
+        let embed_cache_ttl_secs = var("EMBED_CACHE_TTL_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| anyhow::anyhow!("EMBED_CACHE_TTL_SECS must be a positive integer"))?;
+
+        let network_cache_ttl_secs = var("NETWORK_CACHE_TTL_SECS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| anyhow::anyhow!("NETWORK_CACHE_TTL_SECS must be a positive integer"))?;
+
+        let cache_control_routes = parse_cache_control_routes(
+            &var("CACHE_CONTROL_ROUTES")
+                .unwrap_or_else(|_| DEFAULT_CACHE_CONTROL_ROUTES.to_string()),
+        )?;
+
+        let randomness_provider_address = var("RANDOMNESS_PROVIDER_ADDRESS").ok();
+
+        let explorer_base_url =
+            var("EXPLORER_BASE_URL").unwrap_or_else(|_| "https://testnet.arcscan.app".to_string());
+
+        let bind_addr = lookup("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+
+        let shutdown_grace_secs = lookup("SHUTDOWN_GRACE_SECONDS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("SHUTDOWN_GRACE_SECONDS must be a valid u64"))?;
+
+        let indexer_batch_size = var("INDEXER_BATCH_SIZE")
             .unwrap_or_else(|_| "2000".to_string())
             .parse()
             .map_err(|_| anyhow::anyhow!("INDEXER_BATCH_SIZE must be a valid u64"))?;
 
-        let indexer_poll_interval_ms = env::var("INDEXER_POLL_INTERVAL_MS")
+        let indexer_poll_interval_ms = var("INDEXER_POLL_INTERVAL_MS")
             .unwrap_or_else(|_| "3000".to_string())
             .parse()
             .map_err(|_| anyhow::anyhow!("INDEXER_POLL_INTERVAL_MS must be a valid u64"))?;
 
+        let indexer_poll_min_interval_ms = var("INDEXER_POLL_MIN_INTERVAL_MS")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("INDEXER_POLL_MIN_INTERVAL_MS must be a valid u64"))?;
+
+        let indexer_poll_max_interval_ms = var("INDEXER_POLL_MAX_INTERVAL_MS")
+            .unwrap_or_else(|_| "30000".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("INDEXER_POLL_MAX_INTERVAL_MS must be a valid u64"))?;
+
+        if indexer_poll_min_interval_ms > indexer_poll_max_interval_ms {
+            anyhow::bail!(
+                "INDEXER_POLL_MIN_INTERVAL_MS must not exceed INDEXER_POLL_MAX_INTERVAL_MS"
+            );
+        }
+
+        let indexer_head_tag = match var("INDEXER_HEAD_TAG").as_deref() {
+            Err(_) | Ok("") | Ok("latest") => HeadTag::Latest,
+            Ok("safe") => HeadTag::Safe,
+            Ok("finalized") => HeadTag::Finalized,
+            Ok(_) => anyhow::bail!("INDEXER_HEAD_TAG must be 'latest', 'safe' or 'finalized'"),
+        };
+
+        let orphan_check_blocks = var("ORPHAN_CHECK_BLOCKS")
+            .unwrap_or_else(|_| "64".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("ORPHAN_CHECK_BLOCKS must be a valid u64"))?;
+
+        let orphan_check_interval_secs = var("ORPHAN_CHECK_INTERVAL_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("ORPHAN_CHECK_INTERVAL_SECS must be a positive integer")
+            })?;
+
+        let payout_check_interval_secs = var("PAYOUT_CHECK_INTERVAL_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("PAYOUT_CHECK_INTERVAL_SECS must be a valid u64"))?;
+
+        let cohort_refresh_interval_secs = var("COHORT_REFRESH_INTERVAL_SECS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("COHORT_REFRESH_INTERVAL_SECS must be a valid u64"))?;
+
+        let sybil_interval_secs = var("SYBIL_INTERVAL_SECS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("SYBIL_INTERVAL_SECS must be a valid u64"))?;
+
+        let sybil_min_shared_blocks = var("SYBIL_MIN_SHARED_BLOCKS")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|raffles| *raffles > 0)
+            .ok_or_else(|| anyhow::anyhow!("SYBIL_MIN_SHARED_BLOCKS must be a positive integer"))?;
+
+        let sybil_sender_lookup = var("SYBIL_SENDER_LOOKUP")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("SYBIL_SENDER_LOOKUP must be true or false"))?;
+
+        let referral_tracking = var("REFERRAL_TRACKING")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("REFERRAL_TRACKING must be true or false"))?;
+
+        let rpc_circuit_failure_threshold = var("RPC_CIRCUIT_FAILURE_THRESHOLD")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .ok()
+            .filter(|threshold| *threshold > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("RPC_CIRCUIT_FAILURE_THRESHOLD must be a positive integer")
+            })?;
+
+        let rpc_circuit_probe_interval_secs = var("RPC_CIRCUIT_PROBE_INTERVAL_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("RPC_CIRCUIT_PROBE_INTERVAL_SECS must be a valid u64"))?;
+
+        let token_decimals = var("TOKEN_DECIMALS")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse()
+            .ok()
+            .filter(|decimals| *decimals <= 77)
+            .ok_or_else(|| anyhow::anyhow!("TOKEN_DECIMALS must be an integer between 0 and 77"))?;
+
+        let token_symbol = var("TOKEN_SYMBOL")
+            .ok()
+            .filter(|symbol| !symbol.is_empty())
+            .unwrap_or_else(|| "USDC".to_string());
+
+        let site_base_url = var("SITE_BASE_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| url.trim_end_matches('/').to_string());
+
+        let card_image_url_template = var("CARD_IMAGE_URL_TEMPLATE")
+            .ok()
+            .filter(|template| !template.is_empty());
+
+        let attestation_signing_key = var("ATTESTATION_SIGNING_KEY")
+            .ok()
+            .filter(|key| !key.is_empty());
+
+        let mempool_watcher_enabled = var("MEMPOOL_WATCHER_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("MEMPOOL_WATCHER_ENABLED must be true or false"))?;
+
+        let pending_purchase_ttl_secs = var("PENDING_PURCHASE_TTL_SECS")
+            .unwrap_or_else(|_| "120".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("PENDING_PURCHASE_TTL_SECS must be a valid u64"))?;
+
+        let keeper_signer = load_keeper_signer(&var)?;
+
+        let keeper_poll_interval_secs = var("KEEPER_POLL_INTERVAL_SECS")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("KEEPER_POLL_INTERVAL_SECS must be a positive integer")
+            })?;
+
+        let keeper_tx_stuck_secs = var("KEEPER_TX_STUCK_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("KEEPER_TX_STUCK_SECS must be a valid u64"))?;
+
+        // Nodes reject replacements that don't raise the gas price by at least 10%
+        let keeper_gas_bump_percent = var("KEEPER_GAS_BUMP_PERCENT")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse()
+            .ok()
+            .filter(|percent| (10..=1000).contains(percent))
+            .ok_or_else(|| {
+                anyhow::anyhow!("KEEPER_GAS_BUMP_PERCENT must be an integer between 10 and 1000")
+            })?;
+
+        let keeper_max_gas_price_gwei = var("KEEPER_MAX_GAS_PRICE_GWEI")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("KEEPER_MAX_GAS_PRICE_GWEI must be a valid u64"))?;
+
+        let admin_api_key = var("ADMIN_API_KEY").ok().filter(|key| !key.is_empty());
+
+        let admin_jwt_secret = var("ADMIN_JWT_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        if admin_jwt_secret
+            .as_ref()
+            .is_some_and(|secret| secret.len() < 32)
+        {
+            return Err(anyhow::anyhow!(
+                "ADMIN_JWT_SECRET must be at least 32 bytes"
+            ));
+        }
+
+        let admin_jwt_max_ttl_secs = var("ADMIN_JWT_MAX_TTL_SECS")
+            .unwrap_or_else(|_| "43200".to_string())
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| anyhow::anyhow!("ADMIN_JWT_MAX_TTL_SECS must be a positive integer"))?;
+
+        let api_key_default_daily_quota = var("API_KEY_DEFAULT_DAILY_QUOTA")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .ok()
+            .filter(|quota| *quota > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("API_KEY_DEFAULT_DAILY_QUOTA must be a positive integer")
+            })?;
+
+        let api_key_default_rate_limit = var("API_KEY_DEFAULT_RATE_LIMIT")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("API_KEY_DEFAULT_RATE_LIMIT must be a positive integer")
+            })?;
+
+        let outbox_poll_interval_ms = var("OUTBOX_POLL_INTERVAL_MS")
+            .unwrap_or_else(|_| "250".to_string())
+            .parse()
+            .ok()
+            .filter(|ms| *ms > 0)
+            .ok_or_else(|| anyhow::anyhow!("OUTBOX_POLL_INTERVAL_MS must be a positive integer"))?;
+
+        let webhook_poll_interval_secs = var("WEBHOOK_POLL_INTERVAL_SECS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("WEBHOOK_POLL_INTERVAL_SECS must be a positive integer")
+            })?;
+
+        let webhook_max_attempts = var("WEBHOOK_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse()
+            .ok()
+            .filter(|attempts| *attempts > 0)
+            .ok_or_else(|| anyhow::anyhow!("WEBHOOK_MAX_ATTEMPTS must be a positive integer"))?;
+
+        let webhook_signing_secret = var("WEBHOOK_SIGNING_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+
+        let webhook_secret_overlap_secs = var("WEBHOOK_SECRET_OVERLAP_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse()
+            .map_err(|_| {
+                anyhow::anyhow!("WEBHOOK_SECRET_OVERLAP_SECS must be a non-negative integer")
+            })?;
+
+        let whale_min_tickets = var("WHALE_MIN_TICKETS")
+            .ok()
+            .filter(|tickets| !tickets.is_empty())
+            .map(|tickets| {
+                tickets
+                    .parse()
+                    .ok()
+                    .filter(|tickets| *tickets > 0)
+                    .ok_or_else(|| anyhow::anyhow!("WHALE_MIN_TICKETS must be a positive integer"))
+            })
+            .transpose()?;
+
+        let whale_min_amount = var("WHALE_MIN_AMOUNT")
+            .ok()
+            .filter(|amount| !amount.is_empty())
+            .map(|amount| {
+                amount
+                    .parse()
+                    .ok()
+                    .filter(|amount| *amount > 0)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "WHALE_MIN_AMOUNT must be a positive integer in token base units"
+                        )
+                    })
+            })
+            .transpose()?;
+
+        let whale_webhook_url = var("WHALE_WEBHOOK_URL").ok().filter(|url| !url.is_empty());
+        if let Some(url) = &whale_webhook_url {
+            if !is_http_url(url) {
+                anyhow::bail!("WHALE_WEBHOOK_URL must be an http(s) URL");
+            }
+            if whale_min_tickets.is_none() && whale_min_amount.is_none() {
+                anyhow::bail!("WHALE_WEBHOOK_URL requires WHALE_MIN_TICKETS or WHALE_MIN_AMOUNT");
+            }
+        }
+
+        let digest_webhook_url = var("DIGEST_WEBHOOK_URL").ok().filter(|url| !url.is_empty());
+        if digest_webhook_url
+            .as_deref()
+            .is_some_and(|url| !is_http_url(url))
+        {
+            anyhow::bail!("DIGEST_WEBHOOK_URL must be an http(s) URL");
+        }
+
+        let stuck_closed_sla_secs = var("STUCK_CLOSED_SLA_SECS")
+            .unwrap_or_else(|_| "900".to_string())
+            .parse()
+            .map_err(|_| anyhow::anyhow!("STUCK_CLOSED_SLA_SECS must be a non-negative integer"))?;
+
+        let stuck_random_requested_sla_secs = var("STUCK_RANDOM_REQUESTED_SLA_SECS")
+            .unwrap_or_else(|_| "600".to_string())
+            .parse()
+            .map_err(|_| {
+                anyhow::anyhow!("STUCK_RANDOM_REQUESTED_SLA_SECS must be a non-negative integer")
+            })?;
+
+        let stuck_webhook_url = var("STUCK_WEBHOOK_URL").ok().filter(|url| !url.is_empty());
+        if stuck_webhook_url
+            .as_deref()
+            .is_some_and(|url| !is_http_url(url))
+        {
+            anyhow::bail!("STUCK_WEBHOOK_URL must be an http(s) URL");
+        }
+
+        let archive_after_days = var("ARCHIVE_AFTER_DAYS")
+            .ok()
+            .filter(|days| !days.is_empty())
+            .map(|days| {
+                days.parse()
+                    .ok()
+                    .filter(|days| *days > 0)
+                    .ok_or_else(|| anyhow::anyhow!("ARCHIVE_AFTER_DAYS must be a positive integer"))
+            })
+            .transpose()?;
+
+        let export = load_export(&var)?;
+        let backup = load_backup(&var)?;
+        let geo = load_geo(&var)?;
+
         Ok(Self {
+            deployment: deployment.map(str::to_string),
             rpc_url,
+            api_rpc_url,
+            api_rpc_rate_limit,
+            rpc_rate_limit,
+            rpc_rate_limit_mode,
             chain_id,
             start_block,
             database_url,
+            database_schema,
+            schema_check,
+            api_statement_timeout_ms,
+            api_pool,
+            indexer_pool,
+            api_request_timeout_ms,
+            api_max_concurrent_requests,
+            api_error_format,
+            slow_query_threshold_ms,
+            raffle_list_cache_ttl_ms,
+            raffle_list_cache_max_stale_secs,
+            embed_cache_ttl_secs,
+            network_cache_ttl_secs,
+            cache_control_routes,
             raffle_factory_address,
             randomness_provider_address,
             explorer_base_url,
             bind_addr,
+            shutdown_grace_secs,
             indexer_batch_size,
             indexer_poll_interval_ms,
+            indexer_poll_min_interval_ms,
+            indexer_poll_max_interval_ms,
+            indexer_head_tag,
+            orphan_check_blocks,
+            orphan_check_interval_secs,
+            payout_check_interval_secs,
+            cohort_refresh_interval_secs,
+            sybil_interval_secs,
+            sybil_min_shared_blocks,
+            sybil_sender_lookup,
+            referral_tracking,
+            rpc_circuit_failure_threshold,
+            rpc_circuit_probe_interval_secs,
+            token_decimals,
+            token_symbol,
+            site_base_url,
+            card_image_url_template,
+            attestation_signing_key,
+            mempool_watcher_enabled,
+            pending_purchase_ttl_secs,
+            keeper_signer,
+            keeper_poll_interval_secs,
+            keeper_tx_stuck_secs,
+            keeper_gas_bump_percent,
+            keeper_max_gas_price_gwei,
+            admin_api_key,
+            admin_jwt_secret,
+            admin_jwt_max_ttl_secs,
+            api_key_default_daily_quota,
+            api_key_default_rate_limit,
+            outbox_poll_interval_ms,
+            webhook_poll_interval_secs,
+            webhook_max_attempts,
+            webhook_signing_secret,
+            webhook_secret_overlap_secs,
+            whale_min_tickets,
+            whale_min_amount,
+            whale_webhook_url,
+            digest_webhook_url,
+            stuck_closed_sla_secs,
+            stuck_random_requested_sla_secs,
+            stuck_webhook_url,
+            archive_after_days,
+            export,
+            backup,
+            geo,
+        })
+    }
+}
+
+/// Reads `KEEPER_SIGNER` and the variables of the selected signer
+fn load_keeper_signer(
+    var: &impl Fn(&str) -> Result<String, env::VarError>,
+) -> anyhow::Result<Option<KeeperSignerConfig>> {
+    let get = |name: &str| var(name).ok().filter(|value| !value.is_empty());
+    let private_key = get("KEEPER_PRIVATE_KEY");
+    let kind = get("KEEPER_SIGNER").or_else(|| private_key.as_ref().map(|_| "local".to_string()));
+
+    // A hot key left in the environment next to a KMS/remote signer is a mistake
+    if matches!(kind.as_deref(), Some("kms" | "remote")) && private_key.is_some() {
+        anyhow::bail!("KEEPER_PRIVATE_KEY must not be set when KEEPER_SIGNER is kms or remote");
+    }
+
+    let signer = match kind.as_deref() {
+        None => return Ok(None),
+        Some("local") => KeeperSignerConfig::Local {
+            private_key: private_key.ok_or_else(|| {
+                anyhow::anyhow!("KEEPER_SIGNER=local requires KEEPER_PRIVATE_KEY")
+            })?,
+        },
+        Some("kms") => KeeperSignerConfig::Kms {
+            key_id: get("KEEPER_KMS_KEY_ID")
+                .ok_or_else(|| anyhow::anyhow!("KEEPER_SIGNER=kms requires KEEPER_KMS_KEY_ID"))?,
+            region: get("KEEPER_KMS_REGION")
+                .or_else(|| get("AWS_REGION"))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("KEEPER_SIGNER=kms requires KEEPER_KMS_REGION or AWS_REGION")
+                })?,
+            endpoint: get("KEEPER_KMS_ENDPOINT"),
+        },
+        Some("remote") => {
+            let address = get("KEEPER_ADDRESS")
+                .ok_or_else(|| anyhow::anyhow!("KEEPER_SIGNER=remote requires KEEPER_ADDRESS"))?;
+            if !address.starts_with("0x") || address.len() != 42 {
+                anyhow::bail!(
+                    "KEEPER_ADDRESS must be a valid Ethereum address (0x + 40 hex chars)"
+                );
+            }
+            KeeperSignerConfig::Remote {
+                url: get("KEEPER_REMOTE_SIGNER_URL").ok_or_else(|| {
+                    anyhow::anyhow!("KEEPER_SIGNER=remote requires KEEPER_REMOTE_SIGNER_URL")
+                })?,
+                address,
+            }
+        }
+        Some(_) => anyhow::bail!("KEEPER_SIGNER must be local, kms or remote"),
+    };
+    Ok(Some(signer))
+}
+
+/// Parses `CACHE_CONTROL_ROUTES` (see [`RouteCacheControl`])
+fn parse_cache_control_routes(value: &str) -> anyhow::Result<Vec<RouteCacheControl>> {
+    let mut routes: Vec<RouteCacheControl> = Vec::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let invalid = || {
+            anyhow::anyhow!(
+                "CACHE_CONTROL_ROUTES entry '{entry}' must be <route>=no-store, <route>=<secs> or <route>=<secs>/<finalized secs>"
+            )
+        };
+        let (route, policy) = entry.split_once('=').ok_or_else(invalid)?;
+        let route = route.trim();
+        if !route.starts_with('/') {
+            return Err(invalid());
+        }
+        let (max_age_secs, finalized_max_age_secs) = match policy.trim() {
+            "no-store" => (None, None),
+            policy => match policy.split_once('/') {
+                Some((secs, finalized)) => (
+                    Some(secs.trim().parse().map_err(|_| invalid())?),
+                    Some(finalized.trim().parse().map_err(|_| invalid())?),
+                ),
+                None => (Some(policy.parse().map_err(|_| invalid())?), None),
+            },
+        };
+        if routes.iter().any(|existing| existing.route == route) {
+            anyhow::bail!("CACHE_CONTROL_ROUTES lists '{route}' more than once");
+        }
+        routes.push(RouteCacheControl {
+            route: route.to_string(),
+            max_age_secs,
+            finalized_max_age_secs,
+        });
+    }
+    Ok(routes)
+}
+
+/// Reads `EXPORT_BUCKET` and the rest of the snapshot export settings
+fn load_export(
+    var: &impl Fn(&str) -> Result<String, env::VarError>,
+) -> anyhow::Result<Option<ExportConfig>> {
+    let get = |name: &str| var(name).ok().filter(|value| !value.is_empty());
+    let Some(store) = load_object_store(var, "EXPORT")? else {
+        return Ok(None);
+    };
+
+    let prefix = get("EXPORT_PREFIX")
+        .unwrap_or_else(|| "snapshots".to_string())
+        .trim_matches('/')
+        .to_string();
+
+    let interval_secs = get("EXPORT_INTERVAL_SECS")
+        .unwrap_or_else(|| "86400".to_string())
+        .parse()
+        .ok()
+        .filter(|secs| *secs > 0)
+        .ok_or_else(|| anyhow::anyhow!("EXPORT_INTERVAL_SECS must be a positive integer"))?;
+
+    Ok(Some(ExportConfig {
+        store,
+        prefix,
+        interval_secs,
+    }))
+}
+
+/// Reads `BACKUP_BUCKET` and the rest of the raw event backup settings
+fn load_backup(
+    var: &impl Fn(&str) -> Result<String, env::VarError>,
+) -> anyhow::Result<Option<BackupConfig>> {
+    let Some(store) = load_object_store(var, "BACKUP")? else {
+        return Ok(None);
+    };
+    let prefix = var("BACKUP_PREFIX")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "backups".to_string())
+        .trim_matches('/')
+        .to_string();
+    Ok(Some(BackupConfig { store, prefix }))
+}
+
+/// Reads `GEO_DENY_COUNTRIES` / `GEO_ALLOW_COUNTRIES` and the rest of the screening settings
+fn load_geo(
+    var: &impl Fn(&str) -> Result<String, env::VarError>,
+) -> anyhow::Result<Option<GeoConfig>> {
+    let get = |name: &str| {
+        var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let countries = match (get("GEO_DENY_COUNTRIES"), get("GEO_ALLOW_COUNTRIES")) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            anyhow::bail!("set only one of GEO_DENY_COUNTRIES and GEO_ALLOW_COUNTRIES")
+        }
+        (Some(deny), None) => CountryRule::Deny(parse_countries("GEO_DENY_COUNTRIES", &deny)?),
+        (None, Some(allow)) => CountryRule::Allow(parse_countries("GEO_ALLOW_COUNTRIES", &allow)?),
+    };
+
+    let country_header = get("GEO_COUNTRY_HEADER");
+    let ip_db_dir = get("GEO_IP_DB_DIR");
+    let client_ip_header = get("GEO_CLIENT_IP_HEADER");
+    if country_header.is_none() && ip_db_dir.is_none() {
+        anyhow::bail!("country screening requires GEO_COUNTRY_HEADER or GEO_IP_DB_DIR");
+    }
+    for (name, header) in [
+        ("GEO_COUNTRY_HEADER", &country_header),
+        ("GEO_CLIENT_IP_HEADER", &client_ip_header),
+    ] {
+        if let Some(header) = header
+            && axum::http::HeaderName::try_from(header.as_str()).is_err()
+        {
+            anyhow::bail!("{name} '{header}' is not a valid header name");
+        }
+    }
+    let restrict_unknown = match get("GEO_RESTRICT_UNKNOWN") {
+        None => false,
+        Some(value) => value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("GEO_RESTRICT_UNKNOWN must be true or false"))?,
+    };
+    let routes = parse_geo_routes(&get("GEO_ROUTES").unwrap_or_else(|| "*=block".to_string()))?;
+
+    Ok(Some(GeoConfig {
+        countries,
+        country_header,
+        ip_db_dir,
+        client_ip_header,
+        restrict_unknown,
+        routes,
+    }))
+}
+
+/// Parses a list of ISO 3166-1 alpha-2 codes, uppercased
+fn parse_countries(name: &str, value: &str) -> anyhow::Result<Vec<String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(|code| {
+            if code.len() == 2 && code.bytes().all(|b| b.is_ascii_alphabetic()) {
+                Ok(code.to_ascii_uppercase())
+            } else {
+                Err(anyhow::anyhow!(
+                    "{name} entry '{code}' must be a two-letter country code"
+                ))
+            }
         })
+        .collect()
+}
+
+/// Parses `GEO_ROUTES` (see [`RouteGeoPolicy`])
+fn parse_geo_routes(value: &str) -> anyhow::Result<Vec<RouteGeoPolicy>> {
+    let mut routes: Vec<RouteGeoPolicy> = Vec::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let invalid = || {
+            anyhow::anyhow!(
+                "GEO_ROUTES entry '{entry}' must be <route>=block, <route>=watermark or <route>=off"
+            )
+        };
+        let (route, action) = entry.split_once('=').ok_or_else(invalid)?;
+        let route = route.trim();
+        if route != "*" && !route.starts_with('/') {
+            return Err(invalid());
+        }
+        let action = match action.trim() {
+            "block" => GeoAction::Block,
+            "watermark" => GeoAction::Watermark,
+            "off" => GeoAction::Off,
+            _ => return Err(invalid()),
+        };
+        if routes.iter().any(|existing| existing.route == route) {
+            anyhow::bail!("GEO_ROUTES lists '{route}' more than once");
+        }
+        routes.push(RouteGeoPolicy {
+            route: route.to_string(),
+            action,
+        });
     }
+    Ok(routes)
+}
+
+/// Reads the `{PREFIX}_*` pool settings, falling back to `defaults`
+fn load_pool(
+    var: &impl Fn(&str) -> Result<String, env::VarError>,
+    prefix: &str,
+    defaults: PoolConfig,
+) -> anyhow::Result<PoolConfig> {
+    let get = |name: &str| -> anyhow::Result<Option<u64>> {
+        let name = format!("{prefix}_{name}");
+        match var(&name).ok().filter(|value| !value.is_empty()) {
+            None => Ok(None),
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| anyhow::anyhow!("{name} must be a non-negative integer")),
+        }
+    };
+    let seconds = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+
+    let max_connections = match get("MAX_CONNECTIONS")? {
+        None => defaults.max_connections,
+        Some(max) => u32::try_from(max)
+            .ok()
+            .filter(|max| *max > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("{prefix}_MAX_CONNECTIONS must be a positive integer")
+            })?,
+    };
+    let min_connections = match get("MIN_CONNECTIONS")? {
+        None => defaults.min_connections.min(max_connections),
+        Some(min) => u32::try_from(min)
+            .ok()
+            .filter(|min| *min <= max_connections)
+            .ok_or_else(|| {
+                anyhow::anyhow!("{prefix}_MIN_CONNECTIONS must not exceed {prefix}_MAX_CONNECTIONS")
+            })?,
+    };
+    let acquire_timeout = match get("ACQUIRE_TIMEOUT_MS")? {
+        None => defaults.acquire_timeout,
+        Some(0) => anyhow::bail!("{prefix}_ACQUIRE_TIMEOUT_MS must be a positive integer"),
+        Some(ms) => Duration::from_millis(ms),
+    };
+
+    Ok(PoolConfig {
+        max_connections,
+        min_connections,
+        acquire_timeout,
+        max_lifetime: get("MAX_LIFETIME_SECS")?.map_or(defaults.max_lifetime, seconds),
+        idle_timeout: get("IDLE_TIMEOUT_SECS")?.map_or(defaults.idle_timeout, seconds),
+    })
+}
+
+/// Reads `{PREFIX}_BUCKET`, `_ENDPOINT`, `_REGION` and the optional access key
+fn load_object_store(
+    var: &impl Fn(&str) -> Result<String, env::VarError>,
+    prefix: &str,
+) -> anyhow::Result<Option<ObjectStoreConfig>> {
+    let get = |name: &str| {
+        var(&format!("{prefix}_{name}"))
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    let Some(bucket) = get("BUCKET") else {
+        return Ok(None);
+    };
+    if bucket.contains('/') {
+        anyhow::bail!("{prefix}_BUCKET must be a bucket name, not a path");
+    }
+
+    let region = get("REGION")
+        .or_else(|| var("AWS_REGION").ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "us-east-1".to_string());
+    let endpoint = get("ENDPOINT")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+    if !is_http_url(&endpoint) {
+        anyhow::bail!("{prefix}_ENDPOINT must be an http(s) URL");
+    }
+
+    let access_key = match (get("ACCESS_KEY_ID"), get("SECRET_ACCESS_KEY")) {
+        (Some(id), Some(secret)) => Some((id, secret)),
+        (None, None) => None,
+        _ => anyhow::bail!(
+            "{prefix}_ACCESS_KEY_ID and {prefix}_SECRET_ACCESS_KEY must be set together"
+        ),
+    };
+
+    Ok(Some(ObjectStoreConfig {
+        bucket,
+        endpoint,
+        region,
+        access_key,
+    }))
+}
+
+/// Operator-configured webhooks may be plain http (e.g. an internal relay)
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Deployment names appear in URL paths and env var prefixes
+fn is_valid_deployment_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Schema names are passed to Postgres unquoted, so only plain identifiers are allowed
+fn is_valid_schema_name(name: &str) -> bool {
+    name.len() <= 63
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
 }
@@ -0,0 +1,126 @@
+//! Runtime diagnostics (`diagnostics` feature)
+//!
+//! For chasing scheduler-level problems such as latency spikes during an indexer
+//! backfill. Off by default; build with
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg tokio_unstable" cargo build --release --features diagnostics
+//! ```
+//!
+//! to get:
+//! - a `console-subscriber` layer, so `tokio-console` can attach (port 6669, or
+//!   `TOKIO_CONSOLE_BIND`), and
+//! - `GET /debug/runtime`, a JSON snapshot of tokio's runtime metrics: task counts,
+//!   per-worker busy time and polls, and a poll time histogram. Long polls (a blocking
+//!   call or CPU-heavy decoding on a worker) show up in its top buckets.
+//!
+//! The endpoint is unauthenticated like `/metrics`; don't expose such builds publicly.
+
+#[cfg(not(tokio_unstable))]
+compile_error!("the diagnostics feature needs RUSTFLAGS=\"--cfg tokio_unstable\"");
+
+use axum::Json;
+use axum::response::IntoResponse;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::runtime::{Builder, Handle, HistogramConfiguration, LogHistogram};
+
+/// Enables the poll time histogram on the runtime being built
+///
+/// Buckets grow logarithmically from 10µs to 1s (polls above land in the last bucket).
+pub fn configure_runtime(builder: &mut Builder) {
+    builder
+        .enable_metrics_poll_time_histogram()
+        .metrics_poll_time_histogram_configuration(HistogramConfiguration::log(
+            LogHistogram::builder()
+                .min_value(Duration::from_micros(10))
+                .max_value(Duration::from_secs(1))
+                .max_error(0.25),
+        ));
+}
+
+/// Runtime-wide counters and per-worker activity since startup
+#[derive(Serialize)]
+struct RuntimeStats {
+    workers: usize,
+    alive_tasks: usize,
+    spawned_tasks_total: u64,
+    global_queue_depth: usize,
+    blocking_threads: usize,
+    idle_blocking_threads: usize,
+    blocking_queue_depth: usize,
+    /// Times a task was forced to yield after using up its cooperative budget
+    budget_forced_yields_total: u64,
+    worker_stats: Vec<WorkerStats>,
+    /// Task polls by duration, summed over workers
+    poll_time_histogram: Vec<PollBucket>,
+}
+
+#[derive(Serialize)]
+struct WorkerStats {
+    worker: usize,
+    busy_ms: u128,
+    polls: u64,
+    /// Exponentially weighted moving average of poll durations
+    mean_poll_time_us: u128,
+    steals: u64,
+    local_queue_depth: usize,
+    parks: u64,
+}
+
+#[derive(Serialize)]
+struct PollBucket {
+    /// Lower bound of the bucket, inclusive
+    min_us: u128,
+    /// Upper bound of the bucket, exclusive (null for the last one)
+    max_us: Option<u128>,
+    count: u64,
+}
+
+/// GET /debug/runtime - Tokio runtime metrics snapshot
+pub async fn runtime_stats() -> impl IntoResponse {
+    let metrics = Handle::current().metrics();
+    let workers = metrics.num_workers();
+    let worker_stats = (0..workers)
+        .map(|worker| WorkerStats {
+            worker,
+            busy_ms: metrics.worker_total_busy_duration(worker).as_millis(),
+            polls: metrics.worker_poll_count(worker),
+            mean_poll_time_us: metrics.worker_mean_poll_time(worker).as_micros(),
+            steals: metrics.worker_steal_count(worker),
+            local_queue_depth: metrics.worker_local_queue_depth(worker),
+            parks: metrics.worker_park_count(worker),
+        })
+        .collect();
+
+    let buckets = if metrics.poll_time_histogram_enabled() {
+        metrics.poll_time_histogram_num_buckets()
+    } else {
+        0
+    };
+    let poll_time_histogram = (0..buckets)
+        .map(|bucket| {
+            let range = metrics.poll_time_histogram_bucket_range(bucket);
+            PollBucket {
+                min_us: range.start.as_micros(),
+                max_us: (bucket + 1 < buckets).then_some(range.end.as_micros()),
+                count: (0..workers)
+                    .map(|worker| metrics.poll_time_histogram_bucket_count(worker, bucket))
+                    .sum(),
+            }
+        })
+        .collect();
+
+    Json(RuntimeStats {
+        workers,
+        alive_tasks: metrics.num_alive_tasks(),
+        spawned_tasks_total: metrics.spawned_tasks_count(),
+        global_queue_depth: metrics.global_queue_depth(),
+        blocking_threads: metrics.num_blocking_threads(),
+        idle_blocking_threads: metrics.num_idle_blocking_threads(),
+        blocking_queue_depth: metrics.blocking_queue_depth(),
+        budget_forced_yields_total: metrics.budget_forced_yield_count(),
+        worker_stats,
+        poll_time_histogram,
+    })
+}
@@ -0,0 +1,294 @@
+//! Daily digest
+//!
+//! Once a UTC day is over and the indexer has caught up, compiles a summary of it
+//! into `digests`: raffles created, ticket volume, the biggest pots and the winners
+//! paid out. `GET /v1/digests/latest` serves the newest one. With
+//! `DIGEST_WEBHOOK_URL` set, each digest is also queued in `digest_deliveries` and
+//! POSTed by the notifier (see [`crate::notify`]).
+//!
+//! Activity is attributed to a day by block time (insert time for rows indexed
+//! before block times were tracked). Only the last finished day is compiled; days
+//! the backend was down for are not backfilled. Replicas racing to compile the same
+//! day insert it once.
+
+use crate::config::AppConfig;
+use crate::format::AmountFormat;
+use crate::indexer::IndexerStatus;
+use anyhow::Context;
+use chrono::{DateTime, Days, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+/// How often the job checks whether a digest is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Wait after midnight UTC before compiling the previous day, for late blocks
+const DAY_END_GRACE: TimeDelta = TimeDelta::minutes(10);
+
+/// Largest indexer lag at which the previous day counts as fully indexed
+const MAX_LAG_BLOCKS: u64 = 50;
+
+/// Raffles listed under `biggest_pots`
+const TOP_POTS: i64 = 5;
+
+/// Most winners listed
+const MAX_WINNERS: i64 = 20;
+
+/// Summary of one UTC day, as stored in `digests.summary`
+#[derive(Serialize, Deserialize)]
+pub struct Digest {
+    pub day: NaiveDate,
+    pub new_raffles: i64,
+    pub purchases: i64,
+    pub tickets: i64,
+    /// Ticket sales in token base units
+    pub volume: String,
+    /// `volume` rendered per `?format=` (never stored)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_hex: Option<String>,
+    pub unique_buyers: i64,
+    /// Raffles with purchases that day, by ticket sales up to the end of the day
+    pub biggest_pots: Vec<DigestPot>,
+    /// Prizes paid out that day, largest first
+    pub winners: Vec<DigestWinner>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DigestPot {
+    pub raffle_id: i64,
+    pub raffle_address: String,
+    /// Ticket sales in token base units
+    pub pot: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pot_formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pot_hex: Option<String>,
+    pub tickets: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DigestWinner {
+    pub raffle_id: i64,
+    pub winner: String,
+    /// Prize in token base units
+    pub prize_amount: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prize_amount_formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prize_amount_hex: Option<String>,
+    pub tx_hash: String,
+}
+
+impl Digest {
+    /// Fills the `*_formatted` and `*_hex` amount fields requested via `?format=`
+    pub fn with_format(mut self, format: AmountFormat, decimals: u32) -> Self {
+        self.volume_formatted = format.render(&self.volume, decimals);
+        self.volume_hex = format.render_hex(&self.volume);
+        for pot in &mut self.biggest_pots {
+            pot.pot_formatted = format.render(&pot.pot, decimals);
+            pot.pot_hex = format.render_hex(&pot.pot);
+        }
+        for winner in &mut self.winners {
+            winner.prize_amount_formatted = format.render(&winner.prize_amount, decimals);
+            winner.prize_amount_hex = format.render_hex(&winner.prize_amount);
+        }
+        self
+    }
+}
+
+/// Runs the digest job until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig, indexer: IndexerStatus) -> anyhow::Result<()> {
+    loop {
+        if let Err(err) = run_digest_cycle(&db, &config, &indexer).await {
+            tracing::warn!(error = %format!("{err:#}"), "digest cycle failed");
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+async fn run_digest_cycle(
+    db: &PgPool,
+    config: &AppConfig,
+    indexer: &IndexerStatus,
+) -> anyhow::Result<()> {
+    // The last day that ended at least DAY_END_GRACE ago
+    let Some(day) = (Utc::now() - DAY_END_GRACE).date_naive().pred_opt() else {
+        return Ok(());
+    };
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM digests WHERE day = $1)")
+        .bind(day)
+        .fetch_one(db)
+        .await
+        .context("failed to check for an existing digest")?;
+    if exists {
+        return Ok(());
+    }
+
+    // Blocks from before midnight may still be unindexed while the indexer is behind
+    let indexed_block: Option<i64> =
+        sqlx::query_scalar("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+            .fetch_optional(db)
+            .await
+            .context("failed to read indexer checkpoint")?;
+    let caught_up = indexer
+        .head_block()
+        .zip(indexed_block)
+        .is_some_and(|(head, indexed)| head.saturating_sub(indexed as u64) <= MAX_LAG_BLOCKS);
+    if !caught_up {
+        tracing::debug!(%day, "indexer behind, digest postponed");
+        return Ok(());
+    }
+    store(db, config, day).await?;
+    Ok(())
+}
+
+/// Compiles and stores the digest of `day` (queuing its delivery), returning whether
+/// this call stored it
+pub(crate) async fn store(db: &PgPool, config: &AppConfig, day: NaiveDate) -> anyhow::Result<bool> {
+    let digest = compile(db, day).await?;
+    let summary = serde_json::to_string(&digest).context("failed to serialize digest")?;
+
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+    let digest_id: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO digests (day, summary) VALUES ($1, $2::jsonb)
+         ON CONFLICT (day) DO NOTHING
+         RETURNING id",
+    )
+    .bind(day)
+    .bind(&summary)
+    .fetch_optional(&mut *db_tx)
+    .await
+    .context("failed to store digest")?;
+    if let Some(digest_id) = digest_id
+        && config.digest_webhook_url.is_some()
+    {
+        sqlx::query("INSERT INTO digest_deliveries (digest_id) VALUES ($1)")
+            .bind(digest_id)
+            .execute(&mut *db_tx)
+            .await
+            .context("failed to enqueue digest delivery")?;
+    }
+    db_tx.commit().await.context("failed to commit digest")?;
+
+    if digest_id.is_some() {
+        tracing::info!(
+            %day,
+            new_raffles = digest.new_raffles,
+            purchases = digest.purchases,
+            "daily digest compiled"
+        );
+    }
+    Ok(digest_id.is_some())
+}
+
+/// Summarizes the activity indexed for one UTC day
+async fn compile(db: &PgPool, day: NaiveDate) -> anyhow::Result<Digest> {
+    let start: DateTime<Utc> = day.and_time(chrono::NaiveTime::MIN).and_utc();
+    let end = start
+        .checked_add_days(Days::new(1))
+        .context("digest day out of range")?;
+
+    let new_raffles: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM raffles
+         WHERE COALESCE(created_block_time, created_at) >= $1
+           AND COALESCE(created_block_time, created_at) < $2",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_one(db)
+    .await
+    .context("failed to count new raffles")?;
+
+    let volume = sqlx::query(
+        "SELECT COUNT(*) AS purchases,
+            COALESCE(SUM(count), 0)::bigint AS tickets,
+            COALESCE(SUM(amount), 0)::text AS volume,
+            COUNT(DISTINCT buyer) AS unique_buyers
+         FROM purchases
+         WHERE COALESCE(block_time, created_at) >= $1
+           AND COALESCE(block_time, created_at) < $2",
+    )
+    .bind(start)
+    .bind(end)
+    .fetch_one(db)
+    .await
+    .context("failed to sum ticket volume")?;
+
+    let biggest_pots = sqlx::query(
+        "SELECT p.raffle_id, r.raffle_address,
+            SUM(p.amount)::text AS pot, SUM(p.count)::bigint AS tickets
+         FROM purchases p
+         JOIN raffles r ON r.raffle_id = p.raffle_id
+         WHERE COALESCE(p.block_time, p.created_at) < $2
+           AND p.raffle_id IN (
+               SELECT raffle_id FROM purchases
+               WHERE COALESCE(block_time, created_at) >= $1
+                 AND COALESCE(block_time, created_at) < $2
+           )
+         GROUP BY p.raffle_id, r.raffle_address
+         ORDER BY SUM(p.amount) DESC, p.raffle_id
+         LIMIT $3",
+    )
+    .bind(start)
+    .bind(end)
+    .bind(TOP_POTS)
+    .fetch_all(db)
+    .await
+    .context("failed to load biggest pots")?
+    .into_iter()
+    .map(|row| {
+        Ok(DigestPot {
+            raffle_id: row.try_get("raffle_id")?,
+            raffle_address: row.try_get("raffle_address")?,
+            pot: row.try_get("pot")?,
+            pot_formatted: None,
+            pot_hex: None,
+            tickets: row.try_get("tickets")?,
+        })
+    })
+    .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+    let winners = sqlx::query(
+        "SELECT raffle_id, winner, prize_amount::text AS prize_amount, tx_hash
+         FROM payouts
+         WHERE COALESCE(block_time, created_at) >= $1
+           AND COALESCE(block_time, created_at) < $2
+         ORDER BY payouts.prize_amount DESC, raffle_id
+         LIMIT $3",
+    )
+    .bind(start)
+    .bind(end)
+    .bind(MAX_WINNERS)
+    .fetch_all(db)
+    .await
+    .context("failed to load winners")?
+    .into_iter()
+    .map(|row| {
+        Ok(DigestWinner {
+            raffle_id: row.try_get("raffle_id")?,
+            winner: row.try_get("winner")?,
+            prize_amount: row.try_get("prize_amount")?,
+            prize_amount_formatted: None,
+            prize_amount_hex: None,
+            tx_hash: row.try_get("tx_hash")?,
+        })
+    })
+    .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+    Ok(Digest {
+        day,
+        new_raffles,
+        purchases: volume.try_get("purchases")?,
+        tickets: volume.try_get("tickets")?,
+        volume: volume.try_get("volume")?,
+        volume_formatted: None,
+        volume_hex: None,
+        unique_buyers: volume.try_get("unique_buyers")?,
+        biggest_pots,
+        winners,
+    })
+}
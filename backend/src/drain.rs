@@ -0,0 +1,100 @@
+//! In-flight request tracking for connection draining
+//!
+//! On shutdown the server stops accepting connections and waits up to
+//! `SHUTDOWN_GRACE_SECONDS` for open requests to finish. [`InFlight`] records which
+//! requests are still running, so those cut off when the grace period runs out can be
+//! logged by method and path.
+//!
+//! A request counts as in flight until its response body has been sent, not just until
+//! the handler returns: an NDJSON export is still running while its rows stream out.
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http_body::{Frame, SizeHint};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Requests currently being served, by sequence number
+#[derive(Clone, Default)]
+pub struct InFlight {
+    next_id: Arc<AtomicU64>,
+    requests: Arc<Mutex<BTreeMap<u64, String>>>,
+}
+
+impl InFlight {
+    /// `METHOD path` of every request still being served, oldest first
+    pub fn snapshot(&self) -> Vec<String> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn register(&self, request: &Request) -> Guard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let label = format!("{} {}", request.method(), request.uri().path());
+        self.lock().insert(id, label);
+        Guard {
+            in_flight: self.clone(),
+            id,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, String>> {
+        // The map stays consistent even if a holder panicked
+        self.requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Removes its request from [`InFlight`] when dropped
+struct Guard {
+    in_flight: InFlight,
+    id: u64,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.in_flight.lock().remove(&self.id);
+    }
+}
+
+/// Middleware registering each request in `in_flight` until its body is done
+pub async fn track(State(in_flight): State<InFlight>, request: Request, next: Next) -> Response {
+    let guard = in_flight.register(&request);
+    next.run(request).await.map(|body| {
+        Body::new(TrackedBody {
+            inner: body,
+            _guard: guard,
+        })
+    })
+}
+
+/// A response body holding its request's [`Guard`]; dropped once sent or abandoned
+struct TrackedBody {
+    inner: Body,
+    _guard: Guard,
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,612 @@
+//! API error responses
+//!
+//! Every API error is answered with
+//! `{"error": <message>, "code": <CODE>, "details": {...}}`. `code` is stable and
+//! meant for programs; `error` is English text for people and may change. `details`
+//! is only present for some codes (see [`ApiError::details`]).
+//!
+//! The same error can instead be rendered as an RFC 7807 problem
+//! (`application/problem+json`), for clients sending `Accept: application/problem+json`
+//! or for every client with `API_ERROR_FORMAT=problem` (see [`problem_details`]).
+//! `/v2` nests the same fields in an envelope instead (see [`envelope`]).
+//!
+//! Failures of other modules (database, chain reads, signed requests) are wrapped
+//! rather than flattened, so handlers can use `?` and the status and code are
+//! derived in one place.
+
+use crate::auth::Role;
+use crate::chain::ChainReadError;
+use crate::signatures::SignatureError;
+use axum::{
+    Json,
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::borrow::Cow;
+use tower::BoxError;
+
+/// Postgres `query_canceled` code, raised when `statement_timeout` is exceeded
+const QUERY_CANCELED: &str = "57014";
+
+/// `Retry-After` of shed requests; capacity frees up as soon as any request finishes
+const OVERLOADED_RETRY_AFTER_SECS: u64 = 1;
+
+/// `Content-Type` of RFC 7807 problem responses
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Prefix of problem `type` URIs; the code in kebab case follows
+const PROBLEM_TYPE_PREFIX: &str = "urn:ticket-arcade:error:";
+
+/// Body format of API errors (`API_ERROR_FORMAT`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `{"error", "code", "details"}` (default)
+    #[default]
+    Json,
+    /// RFC 7807 `application/problem+json`
+    Problem,
+}
+
+/// Machine-readable error codes, serialized in SCREAMING_SNAKE_CASE
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    NotFound,
+    RaffleNotFound,
+    InvalidParameter,
+    UnknownParameter,
+    InvalidAddress,
+    InvalidStatus,
+    RaffleNotFinalized,
+    Conflict,
+    InvalidSignature,
+    SignatureExpired,
+    NonceReused,
+    Unauthorized,
+    Forbidden,
+    RegionRestricted,
+    ApiKeyRequired,
+    InvalidApiKey,
+    RateLimited,
+    QuotaExceeded,
+    FeatureDisabled,
+    UpstreamUnavailable,
+    Timeout,
+    Overloaded,
+    Unavailable,
+    Internal,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("raffle not found")]
+    RaffleNotFound,
+    /// Any other missing resource; the message names it
+    #[error("{0}")]
+    NotFound(&'static str),
+    /// A malformed or out-of-range request parameter
+    #[error("{message}")]
+    InvalidParameter {
+        param: Cow<'static, str>,
+        message: String,
+    },
+    /// A query parameter the route doesn't take (often a misspelling)
+    #[error("unknown query parameter '{param}'")]
+    UnknownParameter { param: String },
+    #[error("{param} must be a 0x-prefixed 20-byte hex address")]
+    InvalidAddress { param: &'static str },
+    #[error("unknown status '{status}'")]
+    InvalidStatus {
+        status: String,
+        allowed: &'static [&'static str],
+    },
+    #[error("raffle is not finalized")]
+    RaffleNotFinalized,
+    /// The request doesn't fit the current state; the message says what to change
+    #[error("{0}")]
+    Conflict(&'static str),
+    #[error("{}", signature_message(.0))]
+    Signature(#[from] SignatureError),
+    #[error("invalid admin credentials")]
+    InvalidAdminCredentials,
+    /// An admin token whose role is below the route's
+    #[error("this endpoint requires the {} role", .required.as_str())]
+    InsufficientRole { required: Role },
+    /// A signed request for creator-only data, not signed by the raffle's creator
+    #[error("only the raffle's creator may request this")]
+    NotRaffleCreator,
+    /// A signed request reserved for creators, from a wallet that created no raffle
+    #[error("only wallets that created a raffle may do this")]
+    NotCreator,
+    /// The client's country is restricted on this route (see [`crate::geo`])
+    #[error("this service is not available in your region")]
+    RegionRestricted { country: Option<String> },
+    #[error("X-API-Key header required")]
+    ApiKeyRequired,
+    #[error("invalid API key")]
+    InvalidApiKey,
+    #[error("API key rate limit exceeded")]
+    RateLimited { retry_after_secs: u64 },
+    #[error("API key daily quota exceeded")]
+    QuotaExceeded { retry_after_secs: u64 },
+    /// An optional feature is not configured; the message names it
+    #[error("{0}")]
+    FeatureDisabled(&'static str),
+    #[error("{}", chain_message(.0))]
+    Chain(#[from] ChainReadError),
+    #[error("{}", database_message(.0))]
+    Database(#[from] sqlx::Error),
+    /// API keys couldn't be checked, so keyed requests are refused
+    #[error("API key check unavailable")]
+    ApiKeyCheckUnavailable,
+    /// The request outlived `API_REQUEST_TIMEOUT_MS`
+    #[error("request timed out")]
+    RequestTimeout,
+    /// `API_MAX_CONCURRENT_REQUESTS` requests are already in flight
+    #[error("server is overloaded, retry shortly")]
+    Overloaded,
+    /// A handler panicked; the ID finds the logged panic
+    #[error("internal error")]
+    Panicked { request_id: String },
+    /// An unexpected failure, logged where it happened; the message stays generic
+    #[error("{0}")]
+    Internal(&'static str),
+}
+
+impl ApiError {
+    pub fn invalid_parameter(
+        param: impl Into<Cow<'static, str>>,
+        message: impl Into<String>,
+    ) -> Self {
+        ApiError::InvalidParameter {
+            param: param.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Maps a failure of the API router's timeout and load-shedding layers
+    pub fn from_middleware(err: BoxError) -> Self {
+        if err.is::<tower::timeout::error::Elapsed>() {
+            ApiError::RequestTimeout
+        } else if err.is::<tower::load_shed::error::Overloaded>() {
+            ApiError::Overloaded
+        } else {
+            tracing::error!(error = %err, "API middleware error");
+            ApiError::Internal("internal error")
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::RaffleNotFound | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::InvalidParameter { .. }
+            | ApiError::UnknownParameter { .. }
+            | ApiError::InvalidAddress { .. }
+            | ApiError::InvalidStatus { .. } => StatusCode::BAD_REQUEST,
+            ApiError::RaffleNotFinalized | ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Signature(err) => match err {
+                SignatureError::InvalidMessage(_)
+                | SignatureError::Expired
+                | SignatureError::DeadlineTooFar => StatusCode::BAD_REQUEST,
+                SignatureError::InvalidSignature | SignatureError::WrongSigner => {
+                    StatusCode::UNAUTHORIZED
+                }
+                SignatureError::Replayed => StatusCode::CONFLICT,
+                SignatureError::Database(err) => database_status(err),
+            },
+            ApiError::InvalidAdminCredentials
+            | ApiError::ApiKeyRequired
+            | ApiError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            ApiError::InsufficientRole { .. }
+            | ApiError::NotRaffleCreator
+            | ApiError::NotCreator => StatusCode::FORBIDDEN,
+            ApiError::RegionRestricted { .. } => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            ApiError::RateLimited { .. } | ApiError::QuotaExceeded { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            ApiError::FeatureDisabled(_)
+            | ApiError::Chain(_)
+            | ApiError::ApiKeyCheckUnavailable
+            | ApiError::RequestTimeout
+            | ApiError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(err) => database_status(err),
+            ApiError::Panicked { .. } | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::RaffleNotFound => ErrorCode::RaffleNotFound,
+            ApiError::NotFound(_) => ErrorCode::NotFound,
+            ApiError::InvalidParameter { .. } => ErrorCode::InvalidParameter,
+            ApiError::UnknownParameter { .. } => ErrorCode::UnknownParameter,
+            ApiError::InvalidAddress { .. } => ErrorCode::InvalidAddress,
+            ApiError::InvalidStatus { .. } => ErrorCode::InvalidStatus,
+            ApiError::RaffleNotFinalized => ErrorCode::RaffleNotFinalized,
+            ApiError::Conflict(_) => ErrorCode::Conflict,
+            ApiError::Signature(err) => match err {
+                SignatureError::InvalidMessage(_) => ErrorCode::InvalidParameter,
+                SignatureError::Expired | SignatureError::DeadlineTooFar => {
+                    ErrorCode::SignatureExpired
+                }
+                SignatureError::InvalidSignature | SignatureError::WrongSigner => {
+                    ErrorCode::InvalidSignature
+                }
+                SignatureError::Replayed => ErrorCode::NonceReused,
+                SignatureError::Database(err) => database_code(err),
+            },
+            ApiError::InvalidAdminCredentials => ErrorCode::Unauthorized,
+            ApiError::InsufficientRole { .. }
+            | ApiError::NotRaffleCreator
+            | ApiError::NotCreator => ErrorCode::Forbidden,
+            ApiError::RegionRestricted { .. } => ErrorCode::RegionRestricted,
+            ApiError::ApiKeyRequired => ErrorCode::ApiKeyRequired,
+            ApiError::InvalidApiKey => ErrorCode::InvalidApiKey,
+            ApiError::RateLimited { .. } => ErrorCode::RateLimited,
+            ApiError::QuotaExceeded { .. } => ErrorCode::QuotaExceeded,
+            ApiError::FeatureDisabled(_) => ErrorCode::FeatureDisabled,
+            ApiError::Chain(ChainReadError::RateLimited) => ErrorCode::RateLimited,
+            ApiError::Chain(ChainReadError::Rpc(_)) => ErrorCode::UpstreamUnavailable,
+            ApiError::Database(err) => database_code(err),
+            ApiError::ApiKeyCheckUnavailable => ErrorCode::Unavailable,
+            ApiError::RequestTimeout => ErrorCode::Timeout,
+            ApiError::Overloaded => ErrorCode::Overloaded,
+            ApiError::Panicked { .. } | ApiError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// Structured context for codes where the message alone isn't enough to act on:
+    /// the offending `param`, the `allowed` statuses, the `required_role`, the `country`,
+    /// `retry_after_secs`, or the `request_id` of a panicked request
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            ApiError::InvalidParameter { param, .. } => Some(json!({ "param": param })),
+            ApiError::UnknownParameter { param } => Some(json!({ "param": param })),
+            ApiError::InvalidAddress { param } => Some(json!({ "param": param })),
+            ApiError::InvalidStatus { allowed, .. } => Some(json!({ "allowed": allowed })),
+            ApiError::InsufficientRole { required } => Some(json!({ "required_role": required })),
+            ApiError::RegionRestricted { country } => Some(json!({ "country": country })),
+            ApiError::RateLimited { retry_after_secs }
+            | ApiError::QuotaExceeded { retry_after_secs } => {
+                Some(json!({ "retry_after_secs": retry_after_secs }))
+            }
+            ApiError::Panicked { request_id } => Some(json!({ "request_id": request_id })),
+            _ => None,
+        }
+    }
+
+    fn retry_after(&self) -> Option<u64> {
+        match self {
+            ApiError::RateLimited { retry_after_secs }
+            | ApiError::QuotaExceeded { retry_after_secs } => Some(*retry_after_secs),
+            ApiError::Overloaded => Some(OVERLOADED_RETRY_AFTER_SECS),
+            _ => None,
+        }
+    }
+}
+
+/// Body of an error response; also left in the response extensions so
+/// [`problem_details`] can render it again as a problem
+#[derive(Clone, Serialize)]
+struct ErrorResponse {
+    error: String,
+    code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        // Log the actual database error for debugging, but don't expose it to the client
+        let database_error = match &self {
+            ApiError::Database(err) | ApiError::Signature(SignatureError::Database(err)) => {
+                Some(err)
+            }
+            _ => None,
+        };
+        if let Some(err) = database_error {
+            if is_timeout(err) {
+                tracing::warn!(error = %err, "database query timed out");
+            } else {
+                tracing::error!(error = %err, "database error");
+            }
+        }
+
+        let status = self.status();
+        let body = ErrorResponse {
+            error: self.to_string(),
+            code: self.code(),
+            details: self.details(),
+        };
+        let mut response = (status, Json(body.clone())).into_response();
+        response.extensions_mut().insert(body);
+        if let Some(secs) = self.retry_after() {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+/// An RFC 7807 problem; `code` and the `details` members are extension members
+#[derive(Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: u16,
+    detail: String,
+    instance: String,
+    code: ErrorCode,
+    #[serde(flatten)]
+    details: Option<serde_json::Value>,
+}
+
+impl Problem {
+    fn new(error: ErrorResponse, status: StatusCode, instance: String) -> Self {
+        // SCREAMING_SNAKE_CASE, the same string clients see in `code`
+        let code = serde_json::to_value(error.code)
+            .ok()
+            .and_then(|code| code.as_str().map(str::to_ascii_lowercase))
+            .unwrap_or_default();
+        let mut title = code.replace('_', " ");
+        if let Some(first) = title.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        Problem {
+            problem_type: format!("{PROBLEM_TYPE_PREFIX}{}", code.replace('_', "-")),
+            title,
+            status: status.as_u16(),
+            detail: error.error,
+            instance,
+            code: error.code,
+            details: error.details,
+        }
+    }
+}
+
+/// Code of the [`ApiError`] a response was rendered from, as clients see it
+pub fn response_error_code(response: &Response) -> Option<String> {
+    let error = response.extensions().get::<ErrorResponse>()?;
+    serde_json::to_value(error.code)
+        .ok()
+        .and_then(|code| code.as_str().map(str::to_string))
+}
+
+/// Middleware rendering API errors as `application/problem+json`
+///
+/// Applies when the request's `Accept` names `application/problem+json`, or for every
+/// request when `format` is [`ErrorFormat::Problem`]. `type` is
+/// `urn:ticket-arcade:error:<code>` and `title` is the same for every error of a code;
+/// `detail` is the message, `instance` the request path. Status and headers such as
+/// `Retry-After` are kept. Responses that aren't [`ApiError`]s pass through unchanged.
+pub async fn problem_details(
+    State(format): State<ErrorFormat>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let wants_problem = format == ErrorFormat::Problem
+        || request
+            .headers()
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|accept| accept.contains(PROBLEM_CONTENT_TYPE));
+    let instance = request.uri().path().to_string();
+
+    let mut response = next.run(request).await;
+    let Some(error) = response.extensions_mut().remove::<ErrorResponse>() else {
+        return response;
+    };
+    if !wants_problem {
+        return response;
+    }
+
+    let status = response.status();
+    let body = match serde_json::to_vec(&Problem::new(error, status, instance)) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::error!(error = %err, "failed to serialize problem details");
+            return response;
+        }
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Error body of `/v2` routes
+#[derive(Serialize)]
+struct ErrorEnvelope<'a> {
+    error: EnvelopedError<'a>,
+}
+
+#[derive(Serialize)]
+struct EnvelopedError<'a> {
+    code: ErrorCode,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a serde_json::Value>,
+}
+
+/// Middleware rendering API errors of `/v2` routes as
+/// `{"error": {"code", "message", "details"}}`
+///
+/// The [`ErrorResponse`] stays in the extensions, so [`problem_details`] still
+/// applies. Responses that aren't [`ApiError`]s pass through unchanged.
+pub async fn envelope(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let Some(error) = response.extensions().get::<ErrorResponse>() else {
+        return response;
+    };
+    let body = ErrorEnvelope {
+        error: EnvelopedError {
+            code: error.code,
+            message: &error.error,
+            details: error.details.as_ref(),
+        },
+    };
+    let body = match serde_json::to_vec(&body) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::error!(error = %err, "failed to serialize error envelope");
+            return response;
+        }
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Statement timeouts and pool exhaustion, which clients can retry
+fn is_timeout(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::PoolTimedOut)
+        || err
+            .as_database_error()
+            .and_then(|db_err| db_err.code())
+            .is_some_and(|code| code == QUERY_CANCELED)
+}
+
+fn database_status(err: &sqlx::Error) -> StatusCode {
+    if is_timeout(err) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+fn database_code(err: &sqlx::Error) -> ErrorCode {
+    if is_timeout(err) {
+        ErrorCode::Timeout
+    } else {
+        ErrorCode::Internal
+    }
+}
+
+fn database_message(err: &sqlx::Error) -> &'static str {
+    if is_timeout(err) {
+        "database query timed out"
+    } else {
+        "database error"
+    }
+}
+
+fn signature_message(err: &SignatureError) -> String {
+    match err {
+        SignatureError::Database(err) => database_message(err).to_string(),
+        err => err.to_string(),
+    }
+}
+
+fn chain_message(err: &ChainReadError) -> &'static str {
+    match err {
+        ChainReadError::RateLimited => "on-chain lookups are rate limited, retry shortly",
+        ChainReadError::Rpc(_) => "on-chain lookup failed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    async fn get_error(format: ErrorFormat, accept: &str) -> (Response, serde_json::Value) {
+        let app = Router::new()
+            .route(
+                "/v1/raffles/{raffle_id}",
+                get(|| async {
+                    ApiError::QuotaExceeded {
+                        retry_after_secs: 60,
+                    }
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                format,
+                problem_details,
+            ));
+        let request = Request::builder()
+            .uri("/v1/raffles/7")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json = serde_json::from_slice(&body).unwrap();
+        (Response::from_parts(parts, Body::empty()), json)
+    }
+
+    #[tokio::test]
+    async fn renders_problem_when_accepted() {
+        let (response, body) = get_error(ErrorFormat::Json, "application/problem+json").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            PROBLEM_CONTENT_TYPE
+        );
+        assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+        assert_eq!(
+            body,
+            json!({
+                "type": "urn:ticket-arcade:error:quota-exceeded",
+                "title": "Quota exceeded",
+                "status": 429,
+                "detail": "API key daily quota exceeded",
+                "instance": "/v1/raffles/7",
+                "code": "QUOTA_EXCEEDED",
+                "retry_after_secs": 60,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_default_body_unless_configured() {
+        let (response, body) = get_error(ErrorFormat::Json, "application/json").await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(body["code"], "QUOTA_EXCEEDED");
+
+        let (response, body) = get_error(ErrorFormat::Problem, "application/json").await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            PROBLEM_CONTENT_TYPE
+        );
+        assert_eq!(body["status"], 429);
+    }
+
+    #[tokio::test]
+    async fn envelopes_v2_errors() {
+        let app = Router::new()
+            .route(
+                "/v2/raffles/{raffle_id}",
+                get(|| async { ApiError::RaffleNotFound }),
+            )
+            .layer(axum::middleware::from_fn(envelope));
+        let request = Request::builder()
+            .uri("/v2/raffles/7")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.extensions().get::<ErrorResponse>().is_some());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({ "error": { "code": "RAFFLE_NOT_FOUND", "message": "raffle not found" } })
+        );
+    }
+}
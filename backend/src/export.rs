@@ -0,0 +1,372 @@
+//! Parquet snapshots in object storage
+//!
+//! With `EXPORT_BUCKET` set, every `EXPORT_INTERVAL_SECS` the export job writes the
+//! raffles, purchases and refunds tables (archived rows included) as Parquet files to
+//! `{EXPORT_PREFIX}/{snapshot}/{table}.parquet` in an S3-compatible bucket (AWS S3,
+//! GCS through its XML API with HMAC keys, MinIO, R2, ...), so history can be queried
+//! from DuckDB or BigQuery without touching Postgres. Each completed snapshot is
+//! recorded in `export_snapshots` and listed by `GET /v1/admin/exports`.
+//!
+//! All tables are read in one repeatable-read transaction, so a snapshot matches a
+//! single indexer checkpoint. Amounts are exported as decimal strings (uint256 values
+//! don't fit Parquet decimals) and timestamps as UTC microseconds. Files of a failed
+//! run are left in the bucket but never listed.
+
+use crate::config::{AppConfig, ExportConfig};
+use crate::storage::Bucket;
+use anyhow::Context;
+use arrow_array::builder::{
+    BooleanBuilder, Int64Builder, StringBuilder, TimestampMicrosecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest wait between checks for a due snapshot
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Rows per Parquet record batch
+const BATCH_ROWS: usize = 8192;
+
+/// How a column is read from Postgres and typed in Parquet
+#[derive(Clone, Copy)]
+enum Kind {
+    Int,
+    Text,
+    /// `numeric` column, exported as a decimal string
+    Amount,
+    Bool,
+    Time,
+}
+
+/// One exported table
+struct Table {
+    name: &'static str,
+    /// Table or view read, including archived rows
+    source: &'static str,
+    order_by: &'static str,
+    columns: &'static [(&'static str, Kind)],
+}
+
+const TABLES: [Table; 3] = [
+    Table {
+        name: "raffles",
+        source: "raffles_all",
+        order_by: "raffle_id",
+        columns: &[
+            ("raffle_id", Kind::Int),
+            ("raffle_address", Kind::Text),
+            ("creator", Kind::Text),
+            ("keeper", Kind::Text),
+            ("status", Kind::Text),
+            ("end_time", Kind::Time),
+            ("ticket_price", Kind::Amount),
+            ("max_tickets", Kind::Int),
+            ("fee_bps", Kind::Int),
+            ("fee_recipient", Kind::Text),
+            ("total_tickets", Kind::Int),
+            ("unique_buyers", Kind::Int),
+            ("pot", Kind::Amount),
+            ("request_id", Kind::Text),
+            ("randomness", Kind::Text),
+            ("winning_index", Kind::Int),
+            ("winner", Kind::Text),
+            ("prize_amount", Kind::Amount),
+            ("fee_amount", Kind::Amount),
+            ("finalized_tx", Kind::Text),
+            ("created_block_time", Kind::Time),
+            ("created_at", Kind::Time),
+            ("updated_at", Kind::Time),
+        ],
+    },
+    Table {
+        name: "purchases",
+        source: "purchases_all",
+        order_by: "id",
+        columns: &[
+            ("id", Kind::Int),
+            ("raffle_id", Kind::Int),
+            ("buyer", Kind::Text),
+            ("start_index", Kind::Int),
+            ("end_index", Kind::Int),
+            ("count", Kind::Int),
+            ("amount", Kind::Amount),
+            ("whale", Kind::Bool),
+            ("tx_hash", Kind::Text),
+            ("log_index", Kind::Int),
+            ("block_number", Kind::Int),
+            ("block_hash", Kind::Text),
+            ("block_time", Kind::Time),
+            ("created_at", Kind::Time),
+        ],
+    },
+    Table {
+        name: "refunds",
+        source: "refunds_all",
+        order_by: "id",
+        columns: &[
+            ("id", Kind::Int),
+            ("raffle_id", Kind::Int),
+            ("buyer", Kind::Text),
+            ("amount", Kind::Amount),
+            ("tx_hash", Kind::Text),
+            ("log_index", Kind::Int),
+            ("block_number", Kind::Int),
+            ("block_hash", Kind::Text),
+            ("block_time", Kind::Time),
+            ("created_at", Kind::Time),
+        ],
+    },
+];
+
+/// One uploaded file of a snapshot, as stored in `export_snapshots.files`
+#[derive(Serialize, Deserialize)]
+pub struct ExportFile {
+    pub table: String,
+    pub key: String,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+/// Runs the export job until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig) -> anyhow::Result<()> {
+    let Some(export) = config.export else {
+        return Ok(());
+    };
+    let bucket = Bucket::new(&export.store)?;
+    let check_interval = Duration::from_secs(export.interval_secs).min(MAX_CHECK_INTERVAL);
+    loop {
+        if let Err(err) = run_export_cycle(&db, &export, &bucket).await {
+            tracing::warn!(error = %format!("{err:#}"), "export cycle failed");
+        }
+        tokio::time::sleep(check_interval).await;
+    }
+}
+
+/// Writes a snapshot when the last one is at least `EXPORT_INTERVAL_SECS` old
+pub(crate) async fn run_export_cycle(
+    db: &PgPool,
+    export: &ExportConfig,
+    bucket: &Bucket,
+) -> anyhow::Result<()> {
+    let last: Option<DateTime<Utc>> = sqlx::query_scalar(
+        "SELECT MAX(created_at) FROM export_snapshots WHERE bucket = $1 AND prefix = $2",
+    )
+    .bind(&export.store.bucket)
+    .bind(&export.prefix)
+    .fetch_one(db)
+    .await
+    .context("failed to read last export snapshot")?;
+    let interval = chrono::Duration::seconds(export.interval_secs as i64);
+    if last.is_some_and(|last| last + interval > Utc::now()) {
+        return Ok(());
+    }
+
+    let snapshot = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let directory = if export.prefix.is_empty() {
+        snapshot.clone()
+    } else {
+        format!("{}/{snapshot}", export.prefix)
+    };
+
+    // One consistent view of all tables
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to start snapshot transaction")?;
+    let block_number: i64 =
+        sqlx::query_scalar("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+            .fetch_optional(&mut *db_tx)
+            .await
+            .context("failed to read indexer checkpoint")?
+            .unwrap_or(0);
+
+    let mut files = Vec::with_capacity(TABLES.len());
+    for table in &TABLES {
+        let (parquet, rows) = write_table(&mut db_tx, table).await?;
+        let key = format!("{directory}/{}.parquet", table.name);
+        let bytes = parquet.len() as u64;
+        bucket
+            .put(&key, parquet, "application/vnd.apache.parquet")
+            .await
+            .with_context(|| format!("failed to upload {key}"))?;
+        files.push(ExportFile {
+            table: table.name.to_string(),
+            key,
+            rows,
+            bytes,
+        });
+    }
+    db_tx.commit().await.context("failed to end snapshot")?;
+
+    sqlx::query(
+        "INSERT INTO export_snapshots (snapshot, bucket, prefix, block_number, files)
+         VALUES ($1, $2, $3, $4, $5::jsonb)",
+    )
+    .bind(&snapshot)
+    .bind(&export.store.bucket)
+    .bind(&export.prefix)
+    .bind(block_number)
+    .bind(serde_json::to_string(&files).context("failed to serialize export files")?)
+    .execute(db)
+    .await
+    .context("failed to record export snapshot")?;
+
+    tracing::info!(
+        %snapshot,
+        block_number,
+        purchases = files[1].rows,
+        "export snapshot written"
+    );
+    Ok(())
+}
+
+/// Reads a whole table into an in-memory Parquet file, returning it with its row count
+async fn write_table(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    table: &Table,
+) -> anyhow::Result<(Vec<u8>, u64)> {
+    let schema = Arc::new(Schema::new(
+        table
+            .columns
+            .iter()
+            .map(|(name, kind)| Field::new(*name, kind.data_type(), true))
+            .collect::<Vec<_>>(),
+    ));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))
+        .context("failed to create Parquet writer")?;
+
+    let select = table
+        .columns
+        .iter()
+        .map(|(name, kind)| match kind {
+            Kind::Amount => format!("{name}::text AS {name}"),
+            _ => name.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "SELECT {select} FROM {} ORDER BY {}",
+        table.source, table.order_by
+    );
+
+    let mut builders: Vec<ColumnBuilder> = table
+        .columns
+        .iter()
+        .map(|(_, kind)| ColumnBuilder::new(*kind))
+        .collect();
+    let mut rows = 0u64;
+    let mut pending = 0usize;
+    let mut stream = sqlx::query(&sql).fetch(&mut **db_tx);
+    while let Some(row) = stream
+        .try_next()
+        .await
+        .with_context(|| format!("failed to read {}", table.name))?
+    {
+        for (builder, (name, _)) in builders.iter_mut().zip(table.columns) {
+            builder
+                .append(&row, name)
+                .with_context(|| format!("failed to read {}.{name}", table.name))?;
+        }
+        rows += 1;
+        pending += 1;
+        if pending == BATCH_ROWS {
+            write_batch(&mut writer, &schema, &mut builders)?;
+            pending = 0;
+        }
+    }
+    drop(stream);
+    if pending > 0 {
+        write_batch(&mut writer, &schema, &mut builders)?;
+    }
+
+    let parquet = writer
+        .into_inner()
+        .context("failed to finish Parquet file")?;
+    Ok((parquet, rows))
+}
+
+fn write_batch(
+    writer: &mut ArrowWriter<Vec<u8>>,
+    schema: &Arc<Schema>,
+    builders: &mut [ColumnBuilder],
+) -> anyhow::Result<()> {
+    let columns = builders.iter_mut().map(ColumnBuilder::finish).collect();
+    let batch = RecordBatch::try_new(schema.clone(), columns).context("invalid record batch")?;
+    writer
+        .write(&batch)
+        .context("failed to write Parquet batch")
+}
+
+impl Kind {
+    fn data_type(self) -> DataType {
+        match self {
+            Kind::Int => DataType::Int64,
+            Kind::Text | Kind::Amount => DataType::Utf8,
+            Kind::Bool => DataType::Boolean,
+            Kind::Time => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        }
+    }
+}
+
+/// Arrow builder of one column
+enum ColumnBuilder {
+    Int(Int64Builder),
+    Text(StringBuilder),
+    Bool(BooleanBuilder),
+    Time(TimestampMicrosecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(kind: Kind) -> Self {
+        match kind {
+            Kind::Int => ColumnBuilder::Int(Int64Builder::new()),
+            Kind::Text | Kind::Amount => ColumnBuilder::Text(StringBuilder::new()),
+            Kind::Bool => ColumnBuilder::Bool(BooleanBuilder::new()),
+            Kind::Time => {
+                ColumnBuilder::Time(TimestampMicrosecondBuilder::new().with_timezone("UTC"))
+            }
+        }
+    }
+
+    fn append(&mut self, row: &PgRow, name: &str) -> Result<(), sqlx::Error> {
+        match self {
+            ColumnBuilder::Int(builder) => {
+                builder.append_option(row.try_get::<Option<i64>, _>(name)?)
+            }
+            ColumnBuilder::Text(builder) => {
+                builder.append_option(row.try_get::<Option<String>, _>(name)?)
+            }
+            ColumnBuilder::Bool(builder) => {
+                builder.append_option(row.try_get::<Option<bool>, _>(name)?)
+            }
+            ColumnBuilder::Time(builder) => builder.append_option(
+                row.try_get::<Option<DateTime<Utc>>, _>(name)?
+                    .map(|time| time.timestamp_micros()),
+            ),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Int(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Text(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Bool(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Time(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Validating request extractors
+//!
+//! [`ValidatedPath`] and [`ValidatedQuery`] stand in for axum's `Path` and `Query` in
+//! API handlers. Parameters that don't parse, unknown query parameters and values
+//! rejected by [`Validate`] are answered `400` with an [`ApiError`] before the handler
+//! runs, so malformed input never reaches SQL.
+//!
+//! Query structs should be `#[serde(deny_unknown_fields)]`; without it unknown
+//! parameters are silently ignored.
+
+use crate::error::ApiError;
+use axum::extract::path::ErrorKind;
+use axum::extract::rejection::PathRejection;
+use axum::extract::{FromRequestParts, Path};
+use axum::http::request::Parts;
+use serde::de::DeserializeOwned;
+
+/// Checks of a parameter set beyond what deserialization enforces
+pub trait Validate {
+    fn validate(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+}
+
+/// Path parameters deserialized into `T` and checked with [`Validate`]
+pub struct ValidatedPath<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ValidatedPath<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Validate + Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(path_error)?;
+        value.validate()?;
+        Ok(ValidatedPath(value))
+    }
+}
+
+/// Query string deserialized into `T` and checked with [`Validate`]
+pub struct ValidatedQuery<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ValidatedQuery<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Validate,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+        let value = parse_query::<T>(query)?;
+        value.validate()?;
+        Ok(ValidatedQuery(value))
+    }
+}
+
+/// Deserializes a query string, naming the offending parameter on failure
+fn parse_query<T: DeserializeOwned>(query: &str) -> Result<T, ApiError> {
+    let deserializer =
+        serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let path = err.path().to_string();
+        let message = err.inner().to_string();
+        // serde_urlencoded errors are plain messages; serde words these two
+        // the same way for every type
+        if message.starts_with("unknown field") {
+            ApiError::UnknownParameter { param: path }
+        } else if message.starts_with("duplicate field") {
+            let param = backquoted(&message).unwrap_or(path);
+            ApiError::invalid_parameter(param.clone(), format!("{param} given more than once"))
+        } else {
+            ApiError::invalid_parameter(path.clone(), format!("invalid {path}: {message}"))
+        }
+    })
+}
+
+/// The first `` `name` `` in a serde error message
+fn backquoted(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once('`')?;
+    let (name, _) = rest.split_once('`')?;
+    Some(name.to_string())
+}
+
+fn path_error(rejection: PathRejection) -> ApiError {
+    if let PathRejection::FailedToDeserializePathParams(err) = &rejection {
+        match err.kind() {
+            ErrorKind::ParseErrorAtKey { key, value, .. } => {
+                return ApiError::invalid_parameter(
+                    key.clone(),
+                    format!("invalid {key} '{value}'"),
+                );
+            }
+            ErrorKind::DeserializeError { key, message, .. } => {
+                return ApiError::invalid_parameter(key.clone(), message.clone());
+            }
+            ErrorKind::InvalidUtf8InPathParam { key } => {
+                return ApiError::invalid_parameter(
+                    key.clone(),
+                    format!("{key} must be valid UTF-8"),
+                );
+            }
+            _ => {}
+        }
+    }
+    // Wrong parameter counts and unsupported types are routing bugs, not bad input
+    tracing::error!(error = %rejection, "path extraction failed");
+    ApiError::Internal("internal error")
+}
+
+/// Whether `value` is a `0x`-prefixed 20-byte hex address (any letter case)
+pub fn is_address(value: &str) -> bool {
+    value
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Whether `value` is a `0x`-prefixed 32-byte hex hash (any letter case)
+pub fn is_hash(value: &str) -> bool {
+    value
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Params {
+        limit: Option<i64>,
+        name: Option<String>,
+    }
+
+    fn param_of(err: &ApiError) -> String {
+        err.details().unwrap()["param"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn names_the_malformed_parameter() {
+        let params: Params = parse_query("limit=5&name=a%20b").unwrap();
+        assert_eq!(params.limit, Some(5));
+        assert_eq!(params.name.as_deref(), Some("a b"));
+
+        let err = parse_query::<Params>("limit=abc").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidParameter);
+        assert_eq!(param_of(&err), "limit");
+    }
+
+    #[test]
+    fn rejects_unknown_parameters() {
+        let err = parse_query::<Params>("limit=5&lmit=6").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnknownParameter);
+        assert_eq!(param_of(&err), "lmit");
+    }
+
+    #[test]
+    fn rejects_repeated_parameters() {
+        let err = parse_query::<Params>("limit=1&limit=2").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidParameter);
+        assert_eq!(param_of(&err), "limit");
+    }
+}
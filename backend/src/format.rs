@@ -0,0 +1,192 @@
+//! Token amount formatting
+//!
+//! Amounts are stored and returned as raw integer strings in the payment token's
+//! smallest unit (USDC has 6 decimals). This module turns them into decimal strings
+//! so API consumers don't each reimplement the conversion, and composes the short
+//! human-readable strings used in display text (social cards).
+//!
+//! Some client libraries lose precision parsing long decimal strings (randomness has
+//! up to 78 digits), so `?format=hex` adds the `0x` form the chain uses instead.
+//!
+//! Addresses are stored lowercase; [`to_checksum`] gives the EIP-55 form `/v2` returns.
+
+use alloy::primitives::{Address, U256};
+use serde::Deserialize;
+
+/// How token amounts are rendered in API responses (`?format=`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountFormat {
+    /// Raw integer strings only (default)
+    #[default]
+    Raw,
+    /// Raw strings plus `*_formatted` decimal strings (`eth` is accepted too; the
+    /// payment token's `TOKEN_DECIMALS` apply either way)
+    #[serde(alias = "eth")]
+    Decimal,
+    /// Raw strings plus `*_hex` strings, for amounts and other 256-bit values
+    Hex,
+}
+
+impl AmountFormat {
+    /// Returns the formatted counterpart of `raw`, or `None` when this format
+    /// doesn't include one (or `raw` isn't an integer string)
+    pub fn render(self, raw: &str, decimals: u32) -> Option<String> {
+        match self {
+            AmountFormat::Decimal => format_units(raw, decimals),
+            AmountFormat::Raw | AmountFormat::Hex => None,
+        }
+    }
+
+    /// Returns the `*_hex` counterpart of a 256-bit integer string, or `None` when this
+    /// format doesn't include one (or `raw` isn't an unsigned integer string)
+    pub fn render_hex(self, raw: &str) -> Option<String> {
+        match self {
+            AmountFormat::Hex => to_hex(raw),
+            AmountFormat::Raw | AmountFormat::Decimal => None,
+        }
+    }
+}
+
+/// Formats an unsigned decimal integer string as `0x`-prefixed hex: `"123"` is `"0x7b"`
+pub fn to_hex(raw: &str) -> Option<String> {
+    if raw.is_empty() || !raw.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    U256::from_str_radix(raw, 10)
+        .ok()
+        .map(|value| format!("{value:#x}"))
+}
+
+/// Formats a `0x`-prefixed address with EIP-55 checksum casing, or returns `None`
+/// if it isn't a 20-byte hex address
+pub fn to_checksum(address: &str) -> Option<String> {
+    address
+        .parse::<Address>()
+        .ok()
+        .map(|address| address.to_checksum(None))
+}
+
+/// Formats an integer amount given in the token's smallest unit as a decimal string
+///
+/// Trailing fractional zeros are trimmed: `format_units("1500000", 6)` is `"1.5"`
+/// and `format_units("15000000", 6)` is `"15"`. Returns `None` if `raw` is not an
+/// optionally negative string of ASCII digits.
+pub fn format_units(raw: &str, decimals: u32) -> Option<String> {
+    let (negative, digits) = match raw.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, raw),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let digits = digits.trim_start_matches('0');
+    let decimals = decimals as usize;
+    // Left-pad so there is at least one integer digit
+    let padded = format!("{digits:0>width$}", width = decimals + 1);
+    let (int_part, frac_part) = padded.split_at(padded.len() - decimals);
+    let frac_part = frac_part.trim_end_matches('0');
+
+    let mut out = String::with_capacity(padded.len() + 2);
+    if negative && !(int_part == "0" && frac_part.is_empty()) {
+        out.push('-');
+    }
+    out.push_str(int_part);
+    if !frac_part.is_empty() {
+        out.push('.');
+        out.push_str(frac_part);
+    }
+    Some(out)
+}
+
+/// Formats an amount for display text: at most `max_fraction_digits` fractional
+/// digits (truncated) and thousands separators, e.g. `"1234567890000"` with 6
+/// decimals and 2 fraction digits is `"1,234,567.89"`
+pub fn format_display_amount(
+    raw: &str,
+    decimals: u32,
+    max_fraction_digits: usize,
+) -> Option<String> {
+    let formatted = format_units(raw, decimals)?;
+    let (int_part, frac_part) = formatted.split_once('.').unwrap_or((&formatted, ""));
+    let (negative, int_part) = match int_part.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, int_part),
+    };
+    let frac_part = frac_part[..frac_part.len().min(max_fraction_digits)].trim_end_matches('0');
+
+    let mut out = String::new();
+    if negative {
+        out.push('-');
+    }
+    out.push_str(&group_thousands(int_part));
+    if !frac_part.is_empty() {
+        out.push('.');
+        out.push_str(frac_part);
+    }
+    Some(out)
+}
+
+/// Formats a count with thousands separators: `4200` is `"4,200"`
+pub fn format_count(count: i64) -> String {
+    let grouped = group_thousands(&count.unsigned_abs().to_string());
+    if count < 0 {
+        format!("-{grouped}")
+    } else {
+        grouped
+    }
+}
+
+/// Formats a duration as its largest whole unit: `"3d"`, `"2h"`, `"45m"`, or `"<1m"`
+pub fn format_duration_short(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    match secs {
+        86_400.. => format!("{}d", secs / 86_400),
+        3_600.. => format!("{}h", secs / 3_600),
+        60.. => format!("{}m", secs / 60),
+        _ => "<1m".to_string(),
+    }
+}
+
+/// Inserts `,` between groups of three digits of an unsigned digit string
+fn group_thousands(digits: &str) -> String {
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_amounts_exactly() {
+        // Beyond f64's 53 bits of mantissa
+        assert_eq!(
+            format_units("123456789012345678901234567", 6).as_deref(),
+            Some("123456789012345678901.234567")
+        );
+        assert_eq!(format_units("1", 6).as_deref(), Some("0.000001"));
+        assert_eq!(format_units("0", 6).as_deref(), Some("0"));
+        assert_eq!(format_units("1e6", 6), None);
+        assert_eq!(to_hex("123").as_deref(), Some("0x7b"));
+
+        let format: AmountFormat = serde_json::from_str("\"eth\"").unwrap();
+        assert_eq!(format.render("42000000", 6).as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn checksums_addresses() {
+        assert_eq!(
+            to_checksum("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").as_deref(),
+            Some("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed")
+        );
+        assert_eq!(to_checksum("0x5aaeb6"), None);
+    }
+}
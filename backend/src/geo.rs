@@ -0,0 +1,435 @@
+//! Country screening of API requests
+//!
+//! With `GEO_DENY_COUNTRIES` or `GEO_ALLOW_COUNTRIES` set, every deployment route is
+//! screened before its handler runs. The client's country comes from the first
+//! [`CountryLookup`] that knows it:
+//! - [`HeaderCountry`] reads a header the edge sets (`GEO_COUNTRY_HEADER`, e.g.
+//!   Cloudflare's `CF-IPCountry`)
+//! - [`IpCountryDb`] looks the client IP up in a MaxMind GeoLite2 Country CSV database
+//!   (`GEO_IP_DB_DIR`)
+//!
+//! Restricted clients get what `GEO_ROUTES` says for the route (see
+//! [`RouteGeoPolicy`]): `block` answers 451 `REGION_RESTRICTED`, `watermark` serves the
+//! response marked with `X-Restricted-Region` and kept out of shared caches, `off` serves
+//! it unchanged.
+//!
+//! # Security Considerations
+//! - The country header is trusted as is; only set `GEO_COUNTRY_HEADER` when the edge
+//!   overwrites it on every request. The same goes for `GEO_CLIENT_IP_HEADER`.
+//! - Screening is per request, not per wallet: VPNs get around it. It is an enforcement
+//!   point for the API, not a substitute for checks in the frontend or contracts.
+
+use crate::config::{CountryRule, GeoAction, GeoConfig, RouteGeoPolicy};
+use crate::error::ApiError;
+use crate::metrics;
+use crate::state::AppState;
+use anyhow::Context;
+use axum::extract::{ConnectInfo, MatchedPath, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+/// Marks watermarked responses with the client's country (`unknown` if undetermined)
+pub const RESTRICTED_REGION_HEADER: &str = "x-restricted-region";
+
+/// Routes below the deployment prefix that `*` in `GEO_ROUTES` doesn't cover
+const ADMIN_ROUTES: &str = "/admin/";
+
+/// A source of the client's country
+pub trait CountryLookup: Send + Sync {
+    /// ISO 3166-1 alpha-2 code in uppercase, or `None` if this source doesn't know
+    fn country(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<String>;
+}
+
+/// Country set by the edge in a request header
+pub struct HeaderCountry {
+    header: HeaderName,
+}
+
+impl HeaderCountry {
+    pub fn new(header: &str) -> anyhow::Result<Self> {
+        let header = HeaderName::try_from(header)
+            .with_context(|| format!("invalid country header '{header}'"))?;
+        Ok(Self { header })
+    }
+}
+
+impl CountryLookup for HeaderCountry {
+    fn country(&self, headers: &HeaderMap, _client_ip: Option<IpAddr>) -> Option<String> {
+        let value = headers.get(&self.header)?.to_str().ok()?.trim();
+        // Edges send `XX` for unknown and codes like `T1` for Tor
+        let known = value.len() == 2
+            && value.bytes().all(|b| b.is_ascii_alphabetic())
+            && !value.eq_ignore_ascii_case("XX");
+        known.then(|| value.to_ascii_uppercase())
+    }
+}
+
+/// Address ranges sorted by their first address
+struct Ranges<T> {
+    ranges: Vec<(T, T, [u8; 2])>,
+}
+
+impl<T: Ord + Copy> Ranges<T> {
+    fn new(mut ranges: Vec<(T, T, [u8; 2])>) -> Self {
+        ranges.sort_unstable_by_key(|(start, _, _)| *start);
+        Self { ranges }
+    }
+
+    fn get(&self, address: T) -> Option<[u8; 2]> {
+        let index = self
+            .ranges
+            .partition_point(|(start, _, _)| *start <= address)
+            .checked_sub(1)?;
+        let (_, end, country) = self.ranges[index];
+        (address <= end).then_some(country)
+    }
+}
+
+/// GeoLite2 Country database, loaded from its CSV edition
+///
+/// Reads `GeoLite2-Country-Locations-en.csv` and the `-Blocks-IPv4.csv` and
+/// `-Blocks-IPv6.csv` files from one directory. A block's `geoname_id` (where the
+/// address is used) takes precedence over `registered_country_geoname_id`.
+pub struct IpCountryDb {
+    v4: Ranges<u32>,
+    v6: Ranges<u128>,
+}
+
+impl IpCountryDb {
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))
+        };
+        Self::parse(
+            &read("GeoLite2-Country-Locations-en.csv")?,
+            &read("GeoLite2-Country-Blocks-IPv4.csv")?,
+            &read("GeoLite2-Country-Blocks-IPv6.csv")?,
+        )
+    }
+
+    fn parse(locations: &str, blocks_v4: &str, blocks_v6: &str) -> anyhow::Result<Self> {
+        // geoname_id,locale_code,continent_code,continent_name,country_iso_code,...
+        let mut countries = HashMap::new();
+        for line in locations.lines().skip(1) {
+            let mut columns = line.split(',');
+            let (Some(id), Some(code)) = (columns.next(), columns.nth(3)) else {
+                continue;
+            };
+            if let [a, b] = code.as_bytes() {
+                countries.insert(id, [a.to_ascii_uppercase(), b.to_ascii_uppercase()]);
+            }
+        }
+
+        // network,geoname_id,registered_country_geoname_id,...
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for line in blocks_v4.lines().chain(blocks_v6.lines()) {
+            let mut columns = line.split(',');
+            let (Some(network), Some(id), Some(registered_id)) =
+                (columns.next(), columns.next(), columns.next())
+            else {
+                continue;
+            };
+            if network == "network" {
+                continue;
+            }
+            let Some(&country) = countries.get(id).or_else(|| countries.get(registered_id)) else {
+                continue;
+            };
+            match parse_network(network)
+                .with_context(|| format!("invalid network '{network}' in GeoLite2 blocks"))?
+            {
+                (IpAddr::V4(address), prefix) => {
+                    let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                    let start = u32::from(address) & mask;
+                    v4.push((start, start | !mask, country));
+                }
+                (IpAddr::V6(address), prefix) => {
+                    let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                    let start = u128::from(address) & mask;
+                    v6.push((start, start | !mask, country));
+                }
+            }
+        }
+        if v4.is_empty() && v6.is_empty() {
+            anyhow::bail!("GeoLite2 database has no blocks with a country");
+        }
+        Ok(Self {
+            v4: Ranges::new(v4),
+            v6: Ranges::new(v6),
+        })
+    }
+}
+
+/// Parses `address/prefix` notation
+fn parse_network(network: &str) -> Option<(IpAddr, u32)> {
+    let (address, prefix) = network.split_once('/')?;
+    let address: IpAddr = address.parse().ok()?;
+    let prefix: u32 = prefix.parse().ok()?;
+    let bits = if address.is_ipv4() { 32 } else { 128 };
+    (prefix <= bits).then_some((address, prefix))
+}
+
+impl CountryLookup for IpCountryDb {
+    fn country(&self, _headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<String> {
+        let country = match client_ip?.to_canonical() {
+            IpAddr::V4(address) => self.v4.get(u32::from(address)),
+            IpAddr::V6(address) => self.v6.get(u128::from(address)),
+        }?;
+        String::from_utf8(country.to_vec()).ok()
+    }
+}
+
+/// Screening settings and country sources of one deployment
+pub struct GeoScreen {
+    config: GeoConfig,
+    lookups: Vec<Box<dyn CountryLookup>>,
+}
+
+impl GeoScreen {
+    /// Builds the lookups `config` names, the country header first
+    pub fn load(config: &GeoConfig) -> anyhow::Result<Self> {
+        let mut lookups: Vec<Box<dyn CountryLookup>> = Vec::new();
+        if let Some(header) = &config.country_header {
+            lookups.push(Box::new(HeaderCountry::new(header)?));
+        }
+        if let Some(dir) = &config.ip_db_dir {
+            lookups.push(Box::new(IpCountryDb::load(Path::new(dir))?));
+        }
+        Ok(Self::new(config.clone(), lookups))
+    }
+
+    /// Screens with custom lookups, consulted in order
+    pub fn new(config: GeoConfig, lookups: Vec<Box<dyn CountryLookup>>) -> Self {
+        Self { config, lookups }
+    }
+
+    /// Action for restricted clients on `route` (below the deployment prefix)
+    fn action(&self, route: &str) -> GeoAction {
+        let find = |pattern: &str| {
+            self.config
+                .routes
+                .iter()
+                .find(|policy| policy.route == pattern)
+                .map(|policy: &RouteGeoPolicy| policy.action)
+        };
+        find(route)
+            .or_else(|| {
+                (!route.starts_with(ADMIN_ROUTES))
+                    .then(|| find("*"))
+                    .flatten()
+            })
+            .unwrap_or(GeoAction::Off)
+    }
+
+    /// The client IP from `GEO_CLIENT_IP_HEADER`, else the connection's peer
+    fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        match &self.config.client_ip_header {
+            Some(name) => request
+                .headers()
+                .get(name.as_str())?
+                .to_str()
+                .ok()?
+                .split(',')
+                .next()?
+                .trim()
+                .parse()
+                .ok(),
+            None => request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(peer)| peer.ip()),
+        }
+    }
+
+    fn country(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<String> {
+        self.lookups
+            .iter()
+            .find_map(|lookup| lookup.country(headers, client_ip))
+    }
+
+    fn is_restricted(&self, country: Option<&str>) -> bool {
+        match (country, &self.config.countries) {
+            (None, _) => self.config.restrict_unknown,
+            (Some(country), CountryRule::Deny(denied)) => denied.iter().any(|code| code == country),
+            (Some(country), CountryRule::Allow(allowed)) => {
+                !allowed.iter().any(|code| code == country)
+            }
+        }
+    }
+}
+
+/// Middleware applying `GEO_ROUTES` to restricted clients
+///
+/// Routes that screen clients by the country header list it in `Vary`, so shared caches
+/// keep restricted and unrestricted responses apart.
+pub async fn screen(
+    State(state): State<AppState>,
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(geo) = state.geo.clone() else {
+        return next.run(request).await;
+    };
+    // Matched paths include the deployment prefix the router is nested under
+    let action = matched_path
+        .as_ref()
+        .and_then(|route| state.config.strip_route_prefix(route.as_str()))
+        .map_or(GeoAction::Off, |route| geo.action(route));
+    if action == GeoAction::Off {
+        return next.run(request).await;
+    }
+
+    let client_ip = geo.client_ip(&request);
+    let country = geo.country(request.headers(), client_ip);
+    let mut response = if !geo.is_restricted(country.as_deref()) {
+        next.run(request).await
+    } else {
+        let deployment = state.config.deployment.as_deref().unwrap_or("default");
+        tracing::debug!(
+            country = country.as_deref().unwrap_or("unknown"),
+            ?action,
+            path = request.uri().path(),
+            "request from restricted region"
+        );
+        match action {
+            GeoAction::Block => {
+                metrics::record_geo_screened(deployment, "block");
+                let mut response = ApiError::RegionRestricted { country }.into_response();
+                response
+                    .headers_mut()
+                    .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                response
+            }
+            _ => {
+                metrics::record_geo_screened(deployment, "watermark");
+                let mut response = next.run(request).await;
+                let region = country.as_deref().unwrap_or("unknown");
+                let headers = response.headers_mut();
+                if let Ok(value) = HeaderValue::from_str(region) {
+                    headers.insert(RESTRICTED_REGION_HEADER, value);
+                }
+                headers.insert(
+                    header::CACHE_CONTROL,
+                    HeaderValue::from_static("private, no-store"),
+                );
+                response
+            }
+        }
+    };
+    if let Some(name) = &geo.config.country_header
+        && let Ok(value) = HeaderValue::from_str(name)
+    {
+        response.headers_mut().append(header::VARY, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCATIONS: &str = "geoname_id,locale_code,continent_code,continent_name,country_iso_code,country_name,is_in_european_union
+2635167,en,EU,Europe,GB,\"United Kingdom\",0
+6252001,en,NA,\"North America\",US,\"United States\",0
+6255148,en,EU,Europe,,,0";
+
+    fn config(countries: CountryRule, routes: &str) -> GeoConfig {
+        GeoConfig {
+            countries,
+            country_header: Some("cf-ipcountry".to_string()),
+            ip_db_dir: None,
+            client_ip_header: None,
+            restrict_unknown: false,
+            routes: routes
+                .split(',')
+                .map(|entry| {
+                    let (route, action) = entry.split_once('=').unwrap();
+                    RouteGeoPolicy {
+                        route: route.to_string(),
+                        action: match action {
+                            "block" => GeoAction::Block,
+                            "watermark" => GeoAction::Watermark,
+                            _ => GeoAction::Off,
+                        },
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn looks_up_geolite2_blocks() {
+        let db = IpCountryDb::parse(
+            LOCATIONS,
+            "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider
+2.16.0.0/13,2635167,2635167,,0,0
+3.0.0.0/15,,6252001,,0,0
+5.0.0.0/16,6255148,,,0,0",
+            "network,geoname_id,registered_country_geoname_id,represented_country_geoname_id,is_anonymous_proxy,is_satellite_provider
+2600:1f00::/24,6252001,6252001,,0,0",
+        )
+        .unwrap();
+        let country = |ip: &str| db.country(&HeaderMap::new(), Some(ip.parse().unwrap()));
+
+        assert_eq!(country("2.23.255.255").as_deref(), Some("GB"));
+        assert_eq!(country("2.24.0.0"), None);
+        // Falls back to the registered country
+        assert_eq!(country("3.1.2.3").as_deref(), Some("US"));
+        // Continent-only locations have no country
+        assert_eq!(country("5.0.0.1"), None);
+        assert_eq!(country("2600:1f00::1").as_deref(), Some("US"));
+        assert_eq!(country("::ffff:2.16.0.1").as_deref(), Some("GB"));
+    }
+
+    #[test]
+    fn restricts_by_header_country() {
+        let geo = GeoScreen::load(&config(
+            CountryRule::Deny(vec!["US".to_string()]),
+            "*=block",
+        ))
+        .unwrap();
+        let country = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("cf-ipcountry", HeaderValue::from_str(value).unwrap());
+            geo.country(&headers, None)
+        };
+
+        assert_eq!(country("us").as_deref(), Some("US"));
+        assert!(geo.is_restricted(Some("US")));
+        assert!(!geo.is_restricted(Some("GB")));
+        assert_eq!(country("XX"), None);
+        assert_eq!(country("T1"), None);
+        assert!(!geo.is_restricted(None));
+
+        let allow = GeoScreen::load(&config(
+            CountryRule::Allow(vec!["GB".to_string()]),
+            "*=block",
+        ))
+        .unwrap();
+        assert!(allow.is_restricted(Some("US")));
+        assert!(!allow.is_restricted(Some("GB")));
+    }
+
+    #[test]
+    fn routes_override_the_default_except_admin() {
+        let geo = GeoScreen::load(&config(
+            CountryRule::Deny(vec!["US".to_string()]),
+            "*=block,/raffles=watermark,/chain=off,/admin/overview=block",
+        ))
+        .unwrap();
+
+        assert_eq!(geo.action("/raffles/{raffle_id}"), GeoAction::Block);
+        assert_eq!(geo.action("/raffles"), GeoAction::Watermark);
+        assert_eq!(geo.action("/chain"), GeoAction::Off);
+        assert_eq!(geo.action("/admin/anomalies"), GeoAction::Off);
+        assert_eq!(geo.action("/admin/overview"), GeoAction::Block);
+    }
+}
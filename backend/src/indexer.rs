@@ -10,19 +10,33 @@
 //! - Errors are logged without exposing sensitive data
 //! - Idempotent inserts prevent duplicate event processing
 
+use crate::circuit::CircuitBreaker;
 use crate::config::AppConfig;
+use crate::live::LiveEvent;
+use crate::metrics;
+use crate::outbox;
+use crate::referrals;
+use crate::rpc::{AlloyProvider, RpcBudget};
+use alloy::dyn_abi::{DynSolEvent, DynSolValue, Specifier};
+use alloy::json_abi::{Event, JsonAbi};
+use alloy::primitives::{Address, B256, Bytes, LogData, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::{BlockNumberOrTag, Filter, Log, TransactionTrait};
 use anyhow::{Context, anyhow};
 use chrono::{DateTime, Utc};
-use ethers::abi::{Abi, Event, RawLog, Token};
-use ethers::providers::{Http, Middleware, Provider};
-use ethers::types::{Address, Filter, H256, Log, U256};
-use sqlx::{PgPool, Row};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use sqlx::PgPool;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
 use std::time::Duration;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 // ============================================================================
 // CONSTANTS
@@ -46,10 +60,37 @@ const MAX_ADDRESSES_PER_QUERY: usize = 100;
 /// Backoff sleep duration when RPC errors occur
 const ERROR_BACKOFF: Duration = Duration::from_secs(5);
 
+/// How often a paused indexer checks whether it was resumed
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum concurrent block requests when resolving log timestamps
+const BLOCK_TIME_CONCURRENCY: usize = 8;
+
+/// Number of cached block timestamps before the cache is reset
+const BLOCK_TIME_CACHE_LIMIT: usize = 10_000;
+
+/// Raw events read per query when replaying `events_raw`
+const REPLAY_BATCH_SIZE: i64 = 1000;
+
+/// How often the `finalized` block is read for row finality, unless it is the head tag
+const FINALIZED_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 // ============================================================================
 // TYPES
 // ============================================================================
 
+/// Block the indexer indexes up to (`INDEXER_HEAD_TAG`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeadTag {
+    /// The latest block; recent rows may still be reorged away
+    #[default]
+    Latest,
+    /// The node's `safe` block, unlikely to be reorged
+    Safe,
+    /// The node's `finalized` block, which can't be reorged
+    Finalized,
+}
+
 /// Enumeration of all indexed event types
 #[derive(Clone, Copy, Debug)]
 enum EventKind {
@@ -65,16 +106,284 @@ enum EventKind {
     KeeperUpdated,
     RefundsStarted,
     PayoutsCompleted,
+    RaffleCanceled,
     // DrandRandomnessProvider events
     ProviderRandomnessRequested,
     ProviderRandomnessDelivered,
 }
 
+impl EventKind {
+    /// Raffle status this event moves the raffle into, if it is a lifecycle transition
+    fn resulting_status(self) -> Option<&'static str> {
+        match self {
+            EventKind::RaffleCreated => Some("ACTIVE"),
+            EventKind::RaffleClosed => Some("CLOSED"),
+            EventKind::RandomnessRequested => Some("RANDOM_REQUESTED"),
+            EventKind::RandomnessFulfilled => Some("RANDOM_FULFILLED"),
+            EventKind::WinnerSelected => Some("FINALIZED"),
+            EventKind::RefundsStarted => Some("REFUNDING"),
+            EventKind::RaffleCanceled => Some("CANCELED"),
+            _ => None,
+        }
+    }
+
+    /// Statuses a raffle may be in when this event is applied, per the contract's state
+    /// machine (`None`: the event doesn't depend on the status)
+    ///
+    /// A raffle already in [`Self::resulting_status`] is always fine: that's an event
+    /// re-included after a reorg being applied again. `RaffleCreated` needs the raffle
+    /// not to be indexed yet; every other event needs it to be.
+    fn allowed_statuses(self) -> Option<&'static [&'static str]> {
+        Some(match self {
+            EventKind::RaffleCreated => &[],
+            EventKind::TicketsBought | EventKind::RaffleClosed => &["ACTIVE"],
+            EventKind::RandomnessRequested => &["CLOSED"],
+            EventKind::RandomnessFulfilled => &["RANDOM_REQUESTED"],
+            EventKind::WinnerSelected => &["RANDOM_FULFILLED"],
+            // The first refund of a CLOSED or RANDOM_REQUESTED raffle starts refunds;
+            // canceled raffles stay CANCELED
+            EventKind::RefundsStarted => &["CLOSED", "RANDOM_REQUESTED", "CANCELED"],
+            EventKind::RefundClaimed => &["REFUNDING", "CANCELED"],
+            EventKind::RaffleCanceled => {
+                &["ACTIVE", "CLOSED", "RANDOM_REQUESTED", "RANDOM_FULFILLED"]
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Indexer progress shared with API handlers
+///
+/// Cheap to clone; all clones observe the same values.
+#[derive(Clone)]
+pub struct IndexerStatus {
+    /// Latest chain head seen by the indexer (0 until the first successful poll)
+    head_block: Arc<AtomicU64>,
+    /// Set by an admin to stop indexing between batches (persisted in
+    /// `indexer_state.paused_at`)
+    paused: Arc<AtomicBool>,
+    /// Circuit breaker guarding the indexer's RPC calls
+    pub rpc: CircuitBreaker,
+}
+
+impl IndexerStatus {
+    pub fn new(rpc: CircuitBreaker) -> Self {
+        Self {
+            head_block: Arc::new(AtomicU64::new(0)),
+            paused: Arc::new(AtomicBool::new(false)),
+            rpc,
+        }
+    }
+
+    /// Whether an admin paused the indexer
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Pauses or resumes indexing; the batch in progress, if any, is finished first
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, AtomicOrdering::Relaxed);
+    }
+
+    /// Latest chain head observed by the indexer, if it has polled successfully
+    pub fn head_block(&self) -> Option<u64> {
+        match self.head_block.load(AtomicOrdering::Relaxed) {
+            0 => None,
+            block => Some(block),
+        }
+    }
+
+    fn set_head_block(&self, block: u64) {
+        self.head_block.store(block, AtomicOrdering::Relaxed);
+    }
+}
+
+/// The RPC calls the indexer makes
+///
+/// Implemented for the HTTP provider; tests script a chain instead (see
+/// `testkit::MockChain`). Callers apply their own timeouts.
+pub trait ChainClient: Send + Sync {
+    fn chain_id(&self) -> impl Future<Output = anyhow::Result<u64>> + Send;
+
+    /// Number of the latest block
+    fn block_number(&self) -> impl Future<Output = anyhow::Result<u64>> + Send;
+
+    /// Logs emitted by `addresses` in `from_block..=to_block`, in any order
+    fn logs(
+        &self,
+        addresses: Vec<Address>,
+        from_block: u64,
+        to_block: u64,
+    ) -> impl Future<Output = anyhow::Result<Vec<Log>>> + Send;
+
+    /// Timestamp of a block, `None` if the node doesn't know the block
+    fn block_timestamp(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = anyhow::Result<Option<u64>>> + Send;
+
+    /// Hash of the canonical block at a height, `None` if the node doesn't know the block
+    fn block_hash(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = anyhow::Result<Option<B256>>> + Send;
+
+    /// Number of the block behind a tag such as `safe` or `finalized`, `None` if the
+    /// node doesn't support the tag
+    fn tagged_block_number(
+        &self,
+        tag: BlockNumberOrTag,
+    ) -> impl Future<Output = anyhow::Result<Option<u64>>> + Send;
+
+    /// Receipt of a mined transaction, `None` if the node doesn't know it
+    fn transaction_receipt(
+        &self,
+        tx_hash: B256,
+    ) -> impl Future<Output = anyhow::Result<Option<TxReceipt>>> + Send;
+
+    /// Calldata of a transaction, `None` if the node doesn't know it
+    fn transaction_input(
+        &self,
+        tx_hash: B256,
+    ) -> impl Future<Output = anyhow::Result<Option<Bytes>>> + Send;
+}
+
+/// What a transaction receipt says about its outcome
+#[derive(Clone, Debug)]
+pub struct TxReceipt {
+    /// Whether the transaction succeeded (didn't revert)
+    pub success: bool,
+    /// Account that sent (and paid for) the transaction
+    pub from: Address,
+    pub logs: Vec<Log>,
+}
+
+impl ChainClient for AlloyProvider {
+    async fn chain_id(&self) -> anyhow::Result<u64> {
+        Ok(self.get_chain_id().await?)
+    }
+
+    async fn block_number(&self) -> anyhow::Result<u64> {
+        Ok(self.get_block_number().await?)
+    }
+
+    async fn logs(
+        &self,
+        addresses: Vec<Address>,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Log>> {
+        let filter = Filter::new()
+            .address(addresses)
+            .from_block(from_block)
+            .to_block(to_block);
+        Ok(self.get_logs(&filter).await?)
+    }
+
+    async fn block_timestamp(&self, block_number: u64) -> anyhow::Result<Option<u64>> {
+        Ok(self
+            .get_block_by_number(block_number.into())
+            .await?
+            .map(|block| block.header.timestamp))
+    }
+
+    async fn block_hash(&self, block_number: u64) -> anyhow::Result<Option<B256>> {
+        Ok(self
+            .get_block_by_number(block_number.into())
+            .await?
+            .map(|block| block.header.hash))
+    }
+
+    async fn tagged_block_number(&self, tag: BlockNumberOrTag) -> anyhow::Result<Option<u64>> {
+        match self.get_block_by_number(tag).await {
+            Ok(block) => Ok(block.map(|block| block.header.number)),
+            // Nodes predating the tag reject it with a JSON-RPC error
+            Err(err) if err.as_error_resp().is_some() => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn transaction_receipt(&self, tx_hash: B256) -> anyhow::Result<Option<TxReceipt>> {
+        Ok(self
+            .get_transaction_receipt(tx_hash)
+            .await?
+            .map(|receipt| TxReceipt {
+                success: receipt.status(),
+                from: receipt.from,
+                logs: receipt.inner.logs().to_vec(),
+            }))
+    }
+
+    async fn transaction_input(&self, tx_hash: B256) -> anyhow::Result<Option<Bytes>> {
+        Ok(self
+            .get_transaction_by_hash(tx_hash)
+            .await?
+            .map(|tx| tx.input().clone()))
+    }
+}
+
+/// Long-lived inputs shared by every indexing cycle
+struct IndexerContext<C> {
+    db_pool: PgPool,
+    config: AppConfig,
+    chain: C,
+    events_by_signature: HashMap<B256, EventDef>,
+    factory_address: Address,
+    provider_address: Option<Address>,
+    status: IndexerStatus,
+    /// Cancelled to stop the indexer after its current batch
+    shutdown: CancellationToken,
+    /// Set once the fallback to `latest` for an unsupported head tag was logged
+    head_tag_unsupported: AtomicBool,
+}
+
+impl<C: ChainClient> IndexerContext<C> {
+    fn new(
+        db_pool: PgPool,
+        config: AppConfig,
+        chain: C,
+        events_by_signature: HashMap<B256, EventDef>,
+        status: IndexerStatus,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<Self> {
+        let factory_address = Address::from_str(&config.raffle_factory_address)
+            .context("invalid factory address format")?;
+
+        // Parse optional randomness provider address
+        let provider_address = config
+            .randomness_provider_address
+            .as_ref()
+            .and_then(|addr| Address::from_str(addr).ok());
+
+        Ok(Self {
+            db_pool,
+            config,
+            chain,
+            events_by_signature,
+            factory_address,
+            provider_address,
+            status,
+            shutdown,
+            head_tag_unsupported: AtomicBool::new(false),
+        })
+    }
+}
+
 /// Event definition combining kind with ABI for decoding
 #[derive(Clone, Debug)]
-struct EventDef {
+pub struct EventDef {
     kind: EventKind,
-    event: Event,
+    /// Decoder resolved from the ABI once, not for every log
+    decoder: DynSolEvent,
+    /// Parameter names of the indexed values, in order
+    indexed_names: Vec<String>,
+    /// Parameter names of the non-indexed values, in order
+    body_names: Vec<String>,
+}
+
+/// Parameters of a decoded log by name, in ABI order within indexed and data values
+pub struct DecodedLog<'a> {
+    params: Vec<(&'a str, DynSolValue)>,
 }
 
 // ============================================================================
@@ -86,20 +395,53 @@ struct EventDef {
 /// # Arguments
 /// * `db_pool` - PostgreSQL connection pool
 /// * `config` - Application configuration
+/// * `status` - Progress shared with the API
+/// * `shutdown` - Cancel to stop; the batch in progress is finished first, so the
+///   cursor always matches the committed rows
 ///
 /// # Errors
 /// Returns error only for unrecoverable issues (ABI load failure, chain ID mismatch).
-/// Transient RPC/DB errors trigger backoff and retry.
-pub async fn run(db_pool: PgPool, config: AppConfig) -> anyhow::Result<()> {
-    let provider = Provider::<Http>::try_from(config.rpc_url.as_str())?
-        .interval(Duration::from_millis(config.indexer_poll_interval_ms));
-
-    // Verify chain ID with timeout (security: prevent wrong-chain indexing)
-    let rpc_chain_id = tokio::time::timeout(RPC_TIMEOUT, provider.get_chainid())
-        .await
-        .context("chain ID request timed out")?
-        .context("failed to get chain ID")?
-        .as_u64();
+/// Transient RPC/DB errors trigger backoff and retry; repeated RPC failures open the
+/// circuit breaker in `status.rpc`, pausing indexing until a probe succeeds. While
+/// `status` is paused by an admin, no new batch is started.
+pub async fn run(
+    db_pool: PgPool,
+    config: AppConfig,
+    status: IndexerStatus,
+    rpc_budget: RpcBudget,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let provider = rpc_budget.alloy_provider(&config.rpc_url, "indexer")?;
+
+    // Verify chain ID with timeout (security: prevent wrong-chain indexing).
+    // An unreachable RPC is retried through the circuit breaker, not treated as fatal.
+    let rpc_chain_id = loop {
+        if shutdown.is_cancelled() {
+            tracing::info!("indexer stopped before start");
+            return Ok(());
+        }
+        if let Some(wait) = status.rpc.wait_time() {
+            sleep_unless_cancelled(wait, &shutdown).await;
+            continue;
+        }
+        let chain_id = status
+            .rpc
+            .call(async {
+                tokio::time::timeout(RPC_TIMEOUT, provider.chain_id())
+                    .await
+                    .context("chain ID request timed out")?
+                    .context("failed to get chain ID")
+            })
+            .await;
+        match chain_id {
+            Ok(chain_id) => break chain_id,
+            Err(err) if !status.rpc.is_open() => {
+                tracing::error!(error = %err, "chain ID request failed, retrying after backoff");
+                sleep_unless_cancelled(ERROR_BACKOFF, &shutdown).await;
+            }
+            Err(_) => {}
+        }
+    };
 
     if rpc_chain_id != config.chain_id {
         return Err(anyhow!(
@@ -109,75 +451,178 @@ pub async fn run(db_pool: PgPool, config: AppConfig) -> anyhow::Result<()> {
         ));
     }
 
-    // Load ABIs from artifact files
-    let factory_abi =
-        load_abi(FACTORY_ARTIFACT_PATH).context("failed to load RaffleFactory ABI")?;
-    let raffle_abi = load_abi(RAFFLE_ARTIFACT_PATH).context("failed to load Raffle ABI")?;
-    let provider_abi = match config.randomness_provider_address.as_ref() {
-        Some(_) => Some(
-            load_abi(DRAND_PROVIDER_ARTIFACT_PATH)
-                .context("failed to load DrandRandomnessProvider ABI")?,
-        ),
-        None => load_abi(DRAND_PROVIDER_ARTIFACT_PATH).ok(), // Optional when provider disabled
-    };
-
-    let events_by_signature = build_event_map(&factory_abi, &raffle_abi, provider_abi.as_ref())?;
-    let factory_address = Address::from_str(&config.raffle_factory_address)
-        .context("invalid factory address format")?;
-
-    // Parse optional randomness provider address
-    let provider_address = config
-        .randomness_provider_address
-        .as_ref()
-        .and_then(|addr| Address::from_str(addr).ok());
+    let events_by_signature = load_event_map(config.randomness_provider_address.is_some())?;
+    let ctx = IndexerContext::new(
+        db_pool,
+        config,
+        provider,
+        events_by_signature,
+        status,
+        shutdown,
+    )?;
 
     tracing::info!(
-        start_block = config.start_block,
-        batch_size = config.indexer_batch_size,
-        factory = %factory_address,
-        provider = ?provider_address,
+        start_block = ctx.config.start_block,
+        batch_size = ctx.config.indexer_batch_size,
+        factory = %ctx.factory_address,
+        provider = ?ctx.provider_address,
         "indexer started"
     );
 
-    // Main polling loop with error recovery
+    // Block timestamps survive across cycles so retries don't refetch them
+    let mut block_times = BlockTimeCache::default();
+    let mut head = HeadTracker::new(&ctx.config);
+
+    // Main polling loop with error recovery. Shutdown is only checked between
+    // cycles: a batch is never abandoned halfway.
     loop {
-        match run_indexing_cycle(
-            &db_pool,
-            &config,
-            &provider,
-            &events_by_signature,
-            factory_address,
-            provider_address,
-        )
-        .await
-        {
+        if ctx.shutdown.is_cancelled() {
+            tracing::info!("indexer stopped");
+            return Ok(());
+        }
+
+        // Paused by an admin; checked here so a batch is never cut short
+        if ctx.status.is_paused() {
+            sleep_unless_cancelled(PAUSE_CHECK_INTERVAL, &ctx.shutdown).await;
+            continue;
+        }
+
+        // Indexing is paused while the RPC circuit is open
+        if let Some(wait) = ctx.status.rpc.wait_time() {
+            sleep_unless_cancelled(wait, &ctx.shutdown).await;
+            continue;
+        }
+
+        match run_indexing_cycle(&ctx, &mut block_times, &mut head).await {
             Ok(()) => {}
             Err(err) => {
-                // Log without exposing sensitive details, then backoff
-                tracing::error!(error = %err, "indexing cycle failed, retrying after backoff");
-                tokio::time::sleep(ERROR_BACKOFF).await;
+                // The circuit logs its own transitions; don't repeat failed probes
+                if !ctx.status.rpc.is_open() {
+                    // Log without exposing sensitive details, then backoff
+                    tracing::error!(error = %err, "indexing cycle failed, retrying after backoff");
+                    sleep_unless_cancelled(ERROR_BACKOFF, &ctx.shutdown).await;
+                }
             }
         }
     }
 }
 
+/// Indexes every block up to the chain's current head, then returns
+///
+/// Runs the same cycles as [`run`], without retries; used by the end-to-end tests to
+/// drive the indexer against a scripted chain (see `testkit`).
+#[cfg(test)]
+pub async fn index_to_head<C: ChainClient>(
+    db_pool: PgPool,
+    config: AppConfig,
+    chain: C,
+    events_by_signature: HashMap<B256, EventDef>,
+    status: IndexerStatus,
+) -> anyhow::Result<()> {
+    let ctx = IndexerContext::new(
+        db_pool,
+        config,
+        chain,
+        events_by_signature,
+        status,
+        CancellationToken::new(),
+    )?;
+    let mut block_times = BlockTimeCache::default();
+    let mut head = HeadTracker::new(&ctx.config);
+    loop {
+        run_indexing_cycle(&ctx, &mut block_times, &mut head).await?;
+        if head.is_caught_up(ctx.status.head_block().unwrap_or(0)) {
+            return Ok(());
+        }
+    }
+}
+
 /// Executes a single indexing cycle (poll and process one batch)
-async fn run_indexing_cycle(
-    db_pool: &PgPool,
-    config: &AppConfig,
-    provider: &Provider<Http>,
-    events_by_signature: &HashMap<H256, EventDef>,
-    factory_address: Address,
-    provider_address: Option<Address>,
+async fn run_indexing_cycle<C: ChainClient>(
+    ctx: &IndexerContext<C>,
+    block_times: &mut BlockTimeCache,
+    head: &mut HeadTracker,
 ) -> anyhow::Result<()> {
+    let IndexerContext {
+        db_pool,
+        config,
+        chain,
+        events_by_signature,
+        factory_address,
+        provider_address,
+        status,
+        shutdown,
+        head_tag_unsupported,
+    } = ctx;
+
+    let rpc = &status.rpc;
+
     // Get latest block with timeout
-    let latest = tokio::time::timeout(RPC_TIMEOUT, provider.get_block_number())
-        .await
-        .context("get_block_number timed out")?
-        .context("failed to get latest block number")?
-        .as_u64();
+    let latest = rpc
+        .call(async {
+            tokio::time::timeout(RPC_TIMEOUT, chain.block_number())
+                .await
+                .context("get_block_number timed out")?
+                .context("failed to get latest block number")
+        })
+        .await?;
+    status.set_head_block(latest);
+    head.observe(latest);
+
+    // No new blocks since the last processed batch: skip the cursor read and get_logs
+    if head.is_caught_up(latest) {
+        head.wait(shutdown).await;
+        return Ok(());
+    }
 
-    let last_processed = get_last_processed_block(db_pool).await?;
+    // Finality of indexed rows, reported by the API. The `finalized` head tag needs
+    // the block every cycle; otherwise it only has to keep up with finality.
+    let finalized = if config.indexer_head_tag == HeadTag::Finalized || head.finalized_check_due() {
+        let finalized = rpc
+            .call(fetch_tagged_block_with_timeout(
+                chain,
+                BlockNumberOrTag::Finalized,
+            ))
+            .await?;
+        head.mark_finalized_checked();
+        if let Some(finalized) = finalized {
+            set_finalized_block(db_pool, finalized)
+                .instrument(metrics::query_span("indexer:cursor"))
+                .await?;
+        }
+        finalized
+    } else {
+        None
+    };
+
+    // Highest block to index: the head, or the configured tag's block
+    let tagged = match config.indexer_head_tag {
+        HeadTag::Latest => Some(latest),
+        HeadTag::Safe => {
+            rpc.call(fetch_tagged_block_with_timeout(
+                chain,
+                BlockNumberOrTag::Safe,
+            ))
+            .await?
+        }
+        HeadTag::Finalized => finalized,
+    };
+    let target = match tagged {
+        Some(tagged) => tagged.min(latest),
+        None => {
+            if !head_tag_unsupported.swap(true, AtomicOrdering::Relaxed) {
+                tracing::warn!(
+                    tag = ?config.indexer_head_tag,
+                    "RPC node doesn't support the head tag, indexing up to the latest block"
+                );
+            }
+            latest
+        }
+    };
+
+    let last_processed = get_last_processed_block(db_pool)
+        .instrument(metrics::query_span("indexer:cursor"))
+        .await?;
     let mut from_block = if last_processed == 0 {
         config.start_block
     } else {
@@ -186,25 +631,36 @@ async fn run_indexing_cycle(
     from_block = from_block.max(config.start_block);
 
     // Nothing new to process - sleep and return
-    if from_block > latest {
-        tokio::time::sleep(Duration::from_millis(config.indexer_poll_interval_ms)).await;
+    if from_block > target {
+        head.mark_caught_up(latest);
+        head.wait(shutdown).await;
         return Ok(());
     }
 
     // Calculate batch end (use saturating math to prevent overflow)
     let to_block = from_block
         .saturating_add(config.indexer_batch_size.saturating_sub(1))
-        .min(latest);
+        .min(target);
     tracing::info!(from_block, to_block, "processing block range");
 
     // 1. Fetch and process factory events (RaffleCreated)
-    let factory_logs =
-        fetch_logs_with_timeout(provider, vec![factory_address], from_block, to_block)
-            .await
-            .context("failed to fetch factory logs")?;
+    let factory_logs = rpc
+        .call(fetch_logs_with_timeout(
+            chain,
+            vec![*factory_address],
+            from_block,
+            to_block,
+        ))
+        .await
+        .context("failed to fetch factory logs")?;
+    rpc.call(block_times.fill(chain, &factory_logs)).await?;
 
     for log_entry in &factory_logs {
-        if let Err(err) = process_log(db_pool, events_by_signature, log_entry).await {
+        let block_time = block_times.get(log_entry);
+        if let Err(err) = process_log(db_pool, events_by_signature, log_entry, block_time, config)
+            .instrument(metrics::query_span("indexer:factory_logs"))
+            .await
+        {
             tracing::warn!(
                 tx_hash = ?log_entry.transaction_hash,
                 error = %err,
@@ -215,13 +671,24 @@ async fn run_indexing_cycle(
 
     // 2. Fetch and process randomness provider events (if configured)
     if let Some(prov_addr) = provider_address {
-        let provider_logs =
-            fetch_logs_with_timeout(provider, vec![prov_addr], from_block, to_block)
-                .await
-                .context("failed to fetch provider logs")?;
+        let provider_logs = rpc
+            .call(fetch_logs_with_timeout(
+                chain,
+                vec![*prov_addr],
+                from_block,
+                to_block,
+            ))
+            .await
+            .context("failed to fetch provider logs")?;
+        rpc.call(block_times.fill(chain, &provider_logs)).await?;
 
         for log_entry in &provider_logs {
-            if let Err(err) = process_log(db_pool, events_by_signature, log_entry).await {
+            let block_time = block_times.get(log_entry);
+            if let Err(err) =
+                process_log(db_pool, events_by_signature, log_entry, block_time, config)
+                    .instrument(metrics::query_span("indexer:provider_logs"))
+                    .await
+            {
                 tracing::warn!(
                     tx_hash = ?log_entry.transaction_hash,
                     error = %err,
@@ -232,17 +699,30 @@ async fn run_indexing_cycle(
     }
 
     // 3. Load known raffle addresses and fetch their events
-    let raffle_addresses = load_raffle_addresses(db_pool).await?;
+    let raffle_addresses = load_raffle_addresses(db_pool)
+        .instrument(metrics::query_span("indexer:load_raffles"))
+        .await?;
     if !raffle_addresses.is_empty() {
         // Process in chunks to prevent DoS via unbounded queries
         for chunk in raffle_addresses.chunks(MAX_ADDRESSES_PER_QUERY) {
-            let raffle_logs =
-                fetch_logs_with_timeout(provider, chunk.to_vec(), from_block, to_block)
-                    .await
-                    .context("failed to fetch raffle logs")?;
+            let raffle_logs = rpc
+                .call(fetch_logs_with_timeout(
+                    chain,
+                    chunk.to_vec(),
+                    from_block,
+                    to_block,
+                ))
+                .await
+                .context("failed to fetch raffle logs")?;
+            rpc.call(block_times.fill(chain, &raffle_logs)).await?;
 
             for log_entry in &raffle_logs {
-                if let Err(err) = process_log(db_pool, events_by_signature, log_entry).await {
+                let block_time = block_times.get(log_entry);
+                if let Err(err) =
+                    process_log(db_pool, events_by_signature, log_entry, block_time, config)
+                        .instrument(metrics::query_span("indexer:raffle_logs"))
+                        .await
+                {
                     tracing::warn!(
                         tx_hash = ?log_entry.transaction_hash,
                         error = %err,
@@ -250,27 +730,63 @@ async fn run_indexing_cycle(
                     );
                 }
             }
+
+            // Referral codes appended to the calldata of purchase transactions
+            if config.referral_tracking
+                && let Some(purchase_topic) = purchase_topic(events_by_signature)
+            {
+                rpc.call(referrals::record_batch(
+                    db_pool,
+                    chain,
+                    &raffle_logs,
+                    purchase_topic,
+                ))
+                .instrument(metrics::query_span("indexer:referrals"))
+                .await
+                .context("failed to record referral codes")?;
+            }
         }
     }
 
-    // 4. Update last processed block
-    set_last_processed_block(db_pool, to_block).await?;
+    // 4. Update last processed block, unless the orphan check rewound it meanwhile
+    let advanced = advance_last_processed_block(db_pool, last_processed, to_block)
+        .instrument(metrics::query_span("indexer:cursor"))
+        .await?;
+    if !advanced {
+        tracing::info!(to_block, "cursor rewound during batch, not advancing");
+    } else if to_block == target {
+        head.mark_caught_up(latest);
+    }
     Ok(())
 }
 
+/// Signature (topic0) of `TicketsBought`
+fn purchase_topic(events_by_signature: &HashMap<B256, EventDef>) -> Option<B256> {
+    events_by_signature
+        .iter()
+        .find(|(_, event_def)| matches!(event_def.kind, EventKind::TicketsBought))
+        .map(|(topic, _)| *topic)
+}
+
+/// Fetches the number of a tagged block with timeout
+async fn fetch_tagged_block_with_timeout<C: ChainClient>(
+    chain: &C,
+    tag: BlockNumberOrTag,
+) -> anyhow::Result<Option<u64>> {
+    tokio::time::timeout(RPC_TIMEOUT, chain.tagged_block_number(tag))
+        .await
+        .with_context(|| format!("get_block({tag}) timed out"))?
+        .with_context(|| format!("failed to get {tag} block"))
+}
+
 /// Fetches logs with timeout and deterministic ordering
-async fn fetch_logs_with_timeout(
-    provider: &Provider<Http>,
+async fn fetch_logs_with_timeout<C: ChainClient>(
+    chain: &C,
     addresses: Vec<Address>,
     from_block: u64,
     to_block: u64,
 ) -> anyhow::Result<Vec<Log>> {
-    let filter = Filter::new()
-        .address(addresses)
-        .from_block(from_block)
-        .to_block(to_block);
-
-    let mut logs = tokio::time::timeout(RPC_TIMEOUT, provider.get_logs(&filter))
+    let mut logs = tokio::time::timeout(RPC_TIMEOUT, chain.logs(addresses, from_block, to_block))
         .await
         .context("get_logs timed out")?
         .context("failed to fetch logs")?;
@@ -293,8 +809,199 @@ async fn fetch_logs_with_timeout(
     Ok(logs)
 }
 
+/// Chain head tracking between cycles, used to skip redundant polling
+///
+/// Once the indexer has processed up to the current head (or the block of its head
+/// tag), later cycles that see the same head skip the cursor read and `get_logs` calls entirely. The sleep
+/// between polls adapts to the chain: it follows the smoothed time between new
+/// heads and grows by half each time a poll finds no new block, within
+/// `INDEXER_POLL_MIN_INTERVAL_MS..=INDEXER_POLL_MAX_INTERVAL_MS`. The `finalized`
+/// block is read at most every [`FINALIZED_CHECK_INTERVAL`] unless it is the head tag.
+struct HeadTracker {
+    /// Highest head observed and when it was first seen
+    head: Option<(u64, Instant)>,
+    /// Head at which the indexer had processed everything up to its head tag (nothing
+    /// to do until it moves)
+    caught_up_at: Option<u64>,
+    /// Smoothed time between blocks
+    block_time: Option<Duration>,
+    /// When the `finalized` block was last read
+    finalized_checked_at: Option<Instant>,
+    interval: Duration,
+    min_interval: Duration,
+    max_interval: Duration,
+}
+
+impl HeadTracker {
+    fn new(config: &AppConfig) -> Self {
+        let min_interval = Duration::from_millis(config.indexer_poll_min_interval_ms);
+        let max_interval = Duration::from_millis(config.indexer_poll_max_interval_ms);
+        Self {
+            head: None,
+            caught_up_at: None,
+            block_time: None,
+            finalized_checked_at: None,
+            interval: Duration::from_millis(config.indexer_poll_interval_ms)
+                .clamp(min_interval, max_interval),
+            min_interval,
+            max_interval,
+        }
+    }
+
+    /// Records a polled head and adapts the poll interval
+    fn observe(&mut self, latest: u64) {
+        let now = Instant::now();
+        match self.head {
+            Some((head, seen_at)) if latest > head => {
+                let blocks = u32::try_from(latest - head).unwrap_or(u32::MAX);
+                let per_block = now.duration_since(seen_at) / blocks;
+                let block_time = match self.block_time {
+                    Some(previous) => (previous * 3 + per_block) / 4,
+                    None => per_block,
+                };
+                self.block_time = Some(block_time);
+                self.interval = block_time.clamp(self.min_interval, self.max_interval);
+                self.head = Some((latest, now));
+            }
+            // Same head (or a lagging RPC node reporting an older one): back off
+            Some(_) => {
+                self.interval = (self.interval * 3 / 2).min(self.max_interval);
+            }
+            None => self.head = Some((latest, now)),
+        }
+    }
+
+    /// Whether everything up to the head tag's block was processed at head `latest`
+    fn is_caught_up(&self, latest: u64) -> bool {
+        self.caught_up_at.is_some_and(|block| latest <= block)
+    }
+
+    fn mark_caught_up(&mut self, block: u64) {
+        self.caught_up_at = Some(block);
+    }
+
+    /// Whether the `finalized` block is due to be read again for row finality
+    fn finalized_check_due(&self) -> bool {
+        self.finalized_checked_at
+            .is_none_or(|checked_at| checked_at.elapsed() >= FINALIZED_CHECK_INTERVAL)
+    }
+
+    fn mark_finalized_checked(&mut self) {
+        self.finalized_checked_at = Some(Instant::now());
+    }
+
+    /// Sleeps until the next poll is due or shutdown is requested
+    async fn wait(&self, shutdown: &CancellationToken) {
+        tracing::debug!(
+            interval_ms = self.interval.as_millis() as u64,
+            block_time_ms = self.block_time.map(|t| t.as_millis() as u64),
+            "waiting for new blocks"
+        );
+        sleep_unless_cancelled(self.interval, shutdown).await;
+    }
+}
+
+/// Sleeps for `duration`, returning early once `shutdown` is cancelled
+async fn sleep_unless_cancelled(duration: Duration, shutdown: &CancellationToken) {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        _ = shutdown.cancelled() => {}
+    }
+}
+
+/// Block number -> timestamp cache used to stamp logs with their block time
+#[derive(Default)]
+struct BlockTimeCache {
+    times: HashMap<u64, DateTime<Utc>>,
+}
+
+impl BlockTimeCache {
+    /// Returns the block time of a log, if known
+    ///
+    /// Nodes that return `blockTimestamp` with logs carry it on the log itself.
+    fn get(&self, log_entry: &Log) -> Option<DateTime<Utc>> {
+        if let Some(timestamp) = log_entry.block_timestamp {
+            return timestamp_to_datetime(timestamp).ok();
+        }
+        self.times.get(&log_entry.block_number?).copied()
+    }
+
+    /// Fetches timestamps for all blocks referenced by `logs` that aren't cached yet
+    /// (or carried by the logs)
+    ///
+    /// Requests run concurrently (bounded by [`BLOCK_TIME_CONCURRENCY`]), each with
+    /// its own timeout.
+    async fn fill<C: ChainClient>(&mut self, chain: &C, logs: &[Log]) -> anyhow::Result<()> {
+        let mut missing: Vec<u64> = logs
+            .iter()
+            .filter(|log_entry| log_entry.block_timestamp.is_none())
+            .filter_map(|log_entry| log_entry.block_number)
+            .filter(|n| !self.times.contains_key(n))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        if self.times.len() + missing.len() > BLOCK_TIME_CACHE_LIMIT {
+            self.times.clear();
+        }
+
+        let fetched: Vec<(u64, Option<DateTime<Utc>>)> = stream::iter(missing)
+            .map(|block_number| async move {
+                let timestamp =
+                    tokio::time::timeout(RPC_TIMEOUT, chain.block_timestamp(block_number))
+                        .await
+                        .context("get_block timed out")?
+                        .with_context(|| format!("failed to fetch block {}", block_number))?;
+                let time = timestamp.map(timestamp_to_datetime).transpose()?;
+                anyhow::Ok((block_number, time))
+            })
+            .buffer_unordered(BLOCK_TIME_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        for (block_number, time) in fetched {
+            match time {
+                Some(time) => {
+                    self.times.insert(block_number, time);
+                }
+                None => tracing::warn!(block_number, "block not found, leaving block_time empty"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verifies that the ABI artifacts the indexer needs can be loaded
+///
+/// Used by the `check-config` preflight.
+pub fn check_abis(provider_enabled: bool) -> anyhow::Result<()> {
+    load_event_map(provider_enabled)?;
+    Ok(())
+}
+
+/// Loads the ABI artifacts and builds the event lookup map
+///
+/// The DrandRandomnessProvider ABI is only required when the provider is enabled.
+pub fn load_event_map(provider_enabled: bool) -> anyhow::Result<HashMap<B256, EventDef>> {
+    let factory_abi =
+        load_abi(FACTORY_ARTIFACT_PATH).context("failed to load RaffleFactory ABI")?;
+    let raffle_abi = load_abi(RAFFLE_ARTIFACT_PATH).context("failed to load Raffle ABI")?;
+    let provider_abi = if provider_enabled {
+        Some(
+            load_abi(DRAND_PROVIDER_ARTIFACT_PATH)
+                .context("failed to load DrandRandomnessProvider ABI")?,
+        )
+    } else {
+        load_abi(DRAND_PROVIDER_ARTIFACT_PATH).ok()
+    };
+    build_event_map(&factory_abi, &raffle_abi, provider_abi.as_ref())
+}
+
 /// Loads an ABI from a Hardhat artifact JSON file
-fn load_abi(relative_path: &str) -> anyhow::Result<Abi> {
+fn load_abi(relative_path: &str) -> anyhow::Result<JsonAbi> {
     let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(relative_path);
     let contents = fs::read_to_string(&path)
         .with_context(|| format!("failed to read ABI file: {}", path.display()))?;
@@ -310,73 +1017,79 @@ fn load_abi(relative_path: &str) -> anyhow::Result<Abi> {
 }
 
 /// Builds a lookup map from event signature (topic0) to event definition
-fn build_event_map(
-    factory_abi: &Abi,
-    raffle_abi: &Abi,
-    provider_abi: Option<&Abi>,
-) -> anyhow::Result<HashMap<H256, EventDef>> {
+pub fn build_event_map(
+    factory_abi: &JsonAbi,
+    raffle_abi: &JsonAbi,
+    provider_abi: Option<&JsonAbi>,
+) -> anyhow::Result<HashMap<B256, EventDef>> {
     let mut map = HashMap::new();
 
     // Factory events
     register_event(
         &mut map,
         EventKind::RaffleCreated,
-        factory_abi.event("RaffleCreated")?,
+        abi_event(factory_abi, "RaffleCreated")?,
     );
 
     // Raffle events
     register_event(
         &mut map,
         EventKind::TicketsBought,
-        raffle_abi.event("TicketsBought")?,
+        abi_event(raffle_abi, "TicketsBought")?,
     );
     register_event(
         &mut map,
         EventKind::RaffleClosed,
-        raffle_abi.event("RaffleClosed")?,
+        abi_event(raffle_abi, "RaffleClosed")?,
     );
     register_event(
         &mut map,
         EventKind::RandomnessRequested,
-        raffle_abi.event("RandomnessRequested")?,
+        abi_event(raffle_abi, "RandomnessRequested")?,
     );
     register_event(
         &mut map,
         EventKind::RandomnessFulfilled,
-        raffle_abi.event("RandomnessFulfilled")?,
+        abi_event(raffle_abi, "RandomnessFulfilled")?,
     );
     register_event(
         &mut map,
         EventKind::WinnerSelected,
-        raffle_abi.event("WinnerSelected")?,
+        abi_event(raffle_abi, "WinnerSelected")?,
     );
     register_event(
         &mut map,
         EventKind::RefundClaimed,
-        raffle_abi.event("RefundClaimed")?,
+        abi_event(raffle_abi, "RefundClaimed")?,
     );
     register_event(
         &mut map,
         EventKind::KeeperUpdated,
-        raffle_abi.event("KeeperUpdated")?,
+        abi_event(raffle_abi, "KeeperUpdated")?,
     );
     register_event(
         &mut map,
         EventKind::RefundsStarted,
-        raffle_abi.event("RefundsStarted")?,
+        abi_event(raffle_abi, "RefundsStarted")?,
     );
     register_event(
         &mut map,
         EventKind::PayoutsCompleted,
-        raffle_abi.event("PayoutsCompleted")?,
+        abi_event(raffle_abi, "PayoutsCompleted")?,
     );
+    // Only emitted by contract releases that support cancellation
+    if let Ok(event) = abi_event(raffle_abi, "RaffleCanceled") {
+        register_event(&mut map, EventKind::RaffleCanceled, event);
+    } else {
+        tracing::warn!("Raffle ABI has no RaffleCanceled event, cancellations won't be indexed");
+    }
 
     // DrandRandomnessProvider events (optional)
     if let Some(prov_abi) = provider_abi {
-        if let Ok(event) = prov_abi.event("RandomnessRequested") {
+        if let Ok(event) = abi_event(prov_abi, "RandomnessRequested") {
             register_event(&mut map, EventKind::ProviderRandomnessRequested, event);
         }
-        if let Ok(event) = prov_abi.event("RandomnessDelivered") {
+        if let Ok(event) = abi_event(prov_abi, "RandomnessDelivered") {
             register_event(&mut map, EventKind::ProviderRandomnessDelivered, event);
         }
     }
@@ -384,12 +1097,36 @@ fn build_event_map(
     Ok(map)
 }
 
-fn register_event(map: &mut HashMap<H256, EventDef>, kind: EventKind, event: &Event) {
+/// The event named `name` in an ABI (the first, if overloaded)
+fn abi_event<'a>(abi: &'a JsonAbi, name: &str) -> anyhow::Result<&'a Event> {
+    abi.event(name)
+        .and_then(|events| events.first())
+        .ok_or_else(|| anyhow!("ABI has no {name} event"))
+}
+
+fn register_event(map: &mut HashMap<B256, EventDef>, kind: EventKind, event: &Event) {
+    let names = |indexed: bool| {
+        event
+            .inputs
+            .iter()
+            .filter(|param| param.indexed == indexed)
+            .map(|param| param.name.clone())
+            .collect()
+    };
+    let decoder = match event.resolve() {
+        Ok(decoder) => decoder,
+        Err(err) => {
+            tracing::warn!(event = %event.name, error = %err, "unsupported event ABI, not indexing it");
+            return;
+        }
+    };
     map.insert(
-        event.signature(),
+        event.selector(),
         EventDef {
             kind,
-            event: event.clone(),
+            decoder,
+            indexed_names: names(true),
+            body_names: names(false),
         },
     );
 }
@@ -398,20 +1135,58 @@ fn register_event(map: &mut HashMap<H256, EventDef>, kind: EventKind, event: &Ev
 // EVENT PROCESSING
 // ============================================================================
 
+/// Decodes a log with the ABI of its event, or `None` if its signature is unknown
+pub fn decode_log<'a>(
+    events_by_signature: &'a HashMap<B256, EventDef>,
+    log_entry: &Log,
+) -> anyhow::Result<Option<(&'a EventDef, DecodedLog<'a>)>> {
+    let Some(event_def) = log_entry
+        .topics()
+        .first()
+        .and_then(|topic0| events_by_signature.get(topic0))
+    else {
+        return Ok(None);
+    };
+    let decoded = event_def
+        .decoder
+        .decode_log_data(log_entry.data())
+        .context("failed to parse log")?;
+    let indexed = event_def.indexed_names.iter().zip(decoded.indexed);
+    let body = event_def.body_names.iter().zip(decoded.body);
+    let params = indexed
+        .chain(body)
+        .map(|(name, value)| (name.as_str(), value))
+        .collect();
+    Ok(Some((event_def, DecodedLog { params })))
+}
+
 /// Processes a single log entry and updates the database
 ///
 /// Uses a database transaction to ensure atomicity.
 /// Idempotent via ON CONFLICT DO NOTHING on unique constraints.
 async fn process_log(
     db_pool: &PgPool,
-    events_by_signature: &HashMap<H256, EventDef>,
+    events_by_signature: &HashMap<B256, EventDef>,
     log_entry: &Log,
+    block_time: Option<DateTime<Utc>>,
+    config: &AppConfig,
 ) -> anyhow::Result<()> {
-    // Extract topic0 (event signature)
-    let topic0 = log_entry.topics.first().cloned().unwrap_or_default();
-
-    // Skip unknown events
-    let Some(event_def) = events_by_signature.get(&topic0) else {
+    // Keep events missing from the ABIs (e.g. added by a contract upgrade) undecoded, so
+    // they show up in `/v1/status` instead of being lost
+    let Some((event_def, parsed)) = decode_log(events_by_signature, log_entry)? else {
+        let topic0 = log_entry.topics().first().copied().unwrap_or_default();
+        let mut conn = db_pool
+            .acquire()
+            .await
+            .context("failed to acquire connection")?;
+        if store_raw_event(&mut conn, log_entry, block_time, false).await? {
+            tracing::warn!(
+                address = %format!("{:#x}", log_entry.address()),
+                topic0 = %format!("{:#x}", topic0),
+                tx_hash = ?log_entry.transaction_hash,
+                "stored undecoded event with unknown signature"
+            );
+        }
         return Ok(());
     };
 
@@ -425,21 +1200,11 @@ async fn process_log(
     let block_number = log_entry
         .block_number
         .ok_or_else(|| anyhow!("log missing block number"))?;
+    let block_hash_hex = log_entry.block_hash.map(|hash| format!("{:#x}", hash));
 
     // Format for database storage (lowercase hex with 0x prefix)
     let tx_hash_hex = format!("{:#x}", tx_hash);
-    let address_hex = format!("{:#x}", log_entry.address);
-    let data_hex = format!("0x{}", hex::encode(log_entry.data.as_ref()));
-
-    // Parse the log according to ABI
-    let raw_log = RawLog {
-        topics: log_entry.topics.clone(),
-        data: log_entry.data.to_vec(),
-    };
-    let parsed = event_def
-        .event
-        .parse_log(raw_log)
-        .context("failed to parse log")?;
+    let address_hex = format!("{:#x}", log_entry.address());
 
     // Begin database transaction
     let mut db_tx = db_pool
@@ -447,19 +1212,32 @@ async fn process_log(
         .await
         .context("failed to begin transaction")?;
     // Store raw logs for debugging and easy reprocessing.
-    sqlx::query(
-        "INSERT INTO events_raw (tx_hash, log_index, block_number, address, topic0, data)
-         VALUES ($1, $2, $3, $4, $5, $6)
-         ON CONFLICT (tx_hash, log_index) DO NOTHING",
-    )
-    .bind(&tx_hash_hex)
-    .bind(log_index.as_u64() as i64)
-    .bind(block_number.as_u64() as i64)
-    .bind(&address_hex)
-    .bind(format!("{:#x}", topic0))
-    .bind(&data_hex)
-    .execute(&mut *db_tx)
-    .await?;
+    // A conflict means this log was already indexed (e.g. after a restart).
+    let is_new = store_raw_event(&mut db_tx, log_entry, block_time, true).await?;
+
+    // Events indexed before were checked then
+    if is_new && event_def.kind.allowed_statuses().is_some() {
+        check_status_transition(
+            &mut db_tx,
+            event_def.kind,
+            u256_to_i64(token_u256(&parsed, "raffleId")?)?,
+            &tx_hash_hex,
+            log_index as i64,
+            block_number as i64,
+            block_hash_hex.as_deref(),
+        )
+        .await?;
+    }
+
+    // Live updates, written to the outbox with the rows they describe
+    let mut live_events = Vec::new();
+    let source = LedgerSource {
+        tx_hash: &tx_hash_hex,
+        log_index: log_index as i64,
+        block_number: block_number as i64,
+        block_hash: block_hash_hex.as_deref(),
+        block_time,
+    };
 
     match event_def.kind {
         EventKind::RaffleCreated => {
@@ -473,10 +1251,10 @@ async fn process_log(
             let fee_recipient = token_address(&parsed, "feeRecipient")?;
 
             let end_time = u256_to_datetime(end_time)?;
-            sqlx::query(
+            sqlx::query!(
                 "INSERT INTO raffles
-                (raffle_id, raffle_address, creator, end_time, ticket_price, max_tickets, fee_bps, fee_recipient, status)
-                VALUES ($1, $2, $3, $4, $5::numeric, $6, $7, $8, $9)
+                (raffle_id, raffle_address, creator, end_time, ticket_price, max_tickets, fee_bps, fee_recipient, status, keeper, created_block_time)
+                VALUES ($1, $2, $3, $4, $5::text::numeric, $6, $7, $8, $9, $3, $10)
                 ON CONFLICT (raffle_id) DO UPDATE SET
                     raffle_address = excluded.raffle_address,
                     creator = excluded.creator,
@@ -486,17 +1264,19 @@ async fn process_log(
                     fee_bps = excluded.fee_bps,
                     fee_recipient = excluded.fee_recipient,
                     status = excluded.status,
+                    created_block_time = COALESCE(excluded.created_block_time, raffles.created_block_time),
                     updated_at = now()",
+                u256_to_i64(raffle_id)?,
+                format!("{:#x}", raffle_address),
+                format!("{:#x}", creator),
+                end_time,
+                ticket_price.to_string(),
+                u256_to_i64(max_tickets)?,
+                u256_to_i64(fee_bps)?,
+                format!("{:#x}", fee_recipient),
+                "ACTIVE",
+                block_time,
             )
-            .bind(u256_to_i64(raffle_id)?)
-            .bind(format!("{:#x}", raffle_address))
-            .bind(format!("{:#x}", creator))
-            .bind(end_time)
-            .bind(ticket_price.to_string())
-            .bind(u256_to_i64(max_tickets)?)
-            .bind(u256_to_i64(fee_bps)?)
-            .bind(format!("{:#x}", fee_recipient))
-            .bind("ACTIVE")
             .execute(&mut *db_tx)
             .await?;
         }
@@ -508,74 +1288,101 @@ async fn process_log(
             let count = token_u256(&parsed, "count")?;
             let amount_paid = token_u256(&parsed, "amountPaid")?;
 
-            let inserted = sqlx::query(
+            let whale = config.is_whale_purchase(
+                u256_to_i64(count)?,
+                u128::try_from(amount_paid).unwrap_or(u128::MAX),
+            );
+
+            let purchase_id: Option<i64> = sqlx::query_scalar!(
                 "INSERT INTO purchases
-                (raffle_id, buyer, start_index, end_index, count, amount, tx_hash, log_index, block_number)
-                VALUES ($1, $2, $3, $4, $5, $6::numeric, $7, $8, $9)
-                ON CONFLICT (tx_hash, log_index) DO NOTHING",
+                (raffle_id, buyer, start_index, end_index, count, amount, tx_hash, log_index, block_number, block_hash, block_time, whale)
+                VALUES ($1, $2, $3, $4, $5, $6::text::numeric, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (tx_hash, log_index) DO NOTHING
+                RETURNING id",
+                u256_to_i64(raffle_id)?,
+                format!("{:#x}", buyer),
+                u256_to_i64(start_index)?,
+                u256_to_i64(end_index)?,
+                u256_to_i64(count)?,
+                amount_paid.to_string(),
+                &tx_hash_hex,
+                log_index as i64,
+                block_number as i64,
+                block_hash_hex.as_deref(),
+                block_time,
+                whale,
             )
-            .bind(u256_to_i64(raffle_id)?)
-            .bind(format!("{:#x}", buyer))
-            .bind(u256_to_i64(start_index)?)
-            .bind(u256_to_i64(end_index)?)
-            .bind(u256_to_i64(count)?)
-            .bind(amount_paid.to_string())
-            .bind(&tx_hash_hex)
-            .bind(log_index.as_u64() as i64)
-            .bind(block_number.as_u64() as i64)
-            .execute(&mut *db_tx)
-            .await?
-            .rows_affected();
+            .fetch_optional(&mut *db_tx)
+            .await?;
 
-            if inserted > 0 {
-                sqlx::query(
-                    "UPDATE raffles
-                    SET total_tickets = total_tickets + $1,
-                        pot = pot + $2::numeric,
-                        updated_at = now()
-                    WHERE raffle_id = $3",
+            if let Some(purchase_id) = purchase_id {
+                post_ledger(
+                    &mut db_tx,
+                    &source,
+                    u256_to_i64(raffle_id)?,
+                    "PURCHASE",
+                    ("buyer", &format!("{:#x}", buyer)),
+                    ("pot", &address_hex),
+                    amount_paid,
                 )
-                .bind(u256_to_i64(count)?)
-                .bind(amount_paid.to_string())
-                .bind(u256_to_i64(raffle_id)?)
-                .execute(&mut *db_tx)
                 .await?;
+                recompute_raffle_totals(&mut db_tx, u256_to_i64(raffle_id)?).await?;
+                if whale && config.whale_webhook_url.is_some() {
+                    sqlx::query!(
+                        "INSERT INTO whale_alerts (purchase_id) VALUES ($1)",
+                        purchase_id,
+                    )
+                    .execute(&mut *db_tx)
+                    .await
+                    .context("failed to enqueue whale alert")?;
+                }
+                live_events.push(LiveEvent::Purchase {
+                    raffle_id: u256_to_i64(raffle_id)?,
+                    buyer: format!("{:#x}", buyer),
+                    start_index: u256_to_i64(start_index)?,
+                    end_index: u256_to_i64(end_index)?,
+                    count: u256_to_i64(count)?,
+                    amount: amount_paid.to_string(),
+                    whale,
+                    tx_hash: tx_hash_hex.clone(),
+                    block_number: block_number as i64,
+                });
             }
         }
         EventKind::RaffleClosed => {
             let raffle_id = token_u256(&parsed, "raffleId")?;
             let total_tickets = token_u256(&parsed, "totalTickets")?;
             let pot = token_u256(&parsed, "pot")?;
-            sqlx::query(
+            sqlx::query!(
                 "UPDATE raffles
                 SET status = $1,
                     total_tickets = $2,
-                    pot = $3::numeric,
+                    pot = $3::text::numeric,
                     updated_at = now()
                 WHERE raffle_id = $4",
+                "CLOSED",
+                u256_to_i64(total_tickets)?,
+                pot.to_string(),
+                u256_to_i64(raffle_id)?,
             )
-            .bind("CLOSED")
-            .bind(u256_to_i64(total_tickets)?)
-            .bind(pot.to_string())
-            .bind(u256_to_i64(raffle_id)?)
             .execute(&mut *db_tx)
             .await?;
         }
         EventKind::RandomnessRequested => {
             let raffle_id = token_u256(&parsed, "raffleId")?;
             let request_id = token_u256(&parsed, "requestId")?;
-            sqlx::query(
+            sqlx::query!(
                 "UPDATE raffles
                 SET status = $1,
                     request_id = $2,
                     request_tx = $3,
                     updated_at = now()
                 WHERE raffle_id = $4",
+                "RANDOM_REQUESTED",
+                request_id.to_string(),
+                &tx_hash_hex,
+                u256_to_i64(raffle_id)?,
             )
-            .bind("RANDOM_REQUESTED")
-            .bind(request_id.to_string())
-            .bind(&tx_hash_hex)
-            .bind(u256_to_i64(raffle_id)?)
             .execute(&mut *db_tx)
             .await?;
         }
@@ -583,7 +1390,7 @@ async fn process_log(
             let raffle_id = token_u256(&parsed, "raffleId")?;
             let request_id = token_u256(&parsed, "requestId")?;
             let randomness = token_u256(&parsed, "randomness")?;
-            sqlx::query(
+            sqlx::query!(
                 "UPDATE raffles
                 SET status = $1,
                     request_id = $2,
@@ -591,12 +1398,12 @@ async fn process_log(
                     randomness_tx = $4,
                     updated_at = now()
                 WHERE raffle_id = $5",
+                "RANDOM_FULFILLED",
+                request_id.to_string(),
+                randomness.to_string(),
+                &tx_hash_hex,
+                u256_to_i64(raffle_id)?,
             )
-            .bind("RANDOM_FULFILLED")
-            .bind(request_id.to_string())
-            .bind(randomness.to_string())
-            .bind(&tx_hash_hex)
-            .bind(u256_to_i64(raffle_id)?)
             .execute(&mut *db_tx)
             .await?;
         }
@@ -604,7 +1411,7 @@ async fn process_log(
             let raffle_id = token_u256(&parsed, "raffleId")?;
             let winner = token_address(&parsed, "winner")?;
             let winning_index = token_u256(&parsed, "winningIndex")?;
-            sqlx::query(
+            sqlx::query!(
                 "UPDATE raffles
                 SET status = $1,
                     winner = $2,
@@ -613,66 +1420,225 @@ async fn process_log(
                     pot = 0,
                     updated_at = now()
                 WHERE raffle_id = $5",
+                "FINALIZED",
+                format!("{:#x}", winner),
+                u256_to_i64(winning_index)?,
+                &tx_hash_hex,
+                u256_to_i64(raffle_id)?,
             )
-            .bind("FINALIZED")
-            .bind(format!("{:#x}", winner))
-            .bind(u256_to_i64(winning_index)?)
-            .bind(&tx_hash_hex)
-            .bind(u256_to_i64(raffle_id)?)
             .execute(&mut *db_tx)
             .await?;
+
+            // Record the payout; PayoutsCompleted (same tx) confirms the fee recipient
+            let prize_amount = token_u256(&parsed, "prizeAmount")?;
+            let fee_amount = token_u256(&parsed, "feeAmount")?;
+            sqlx::query!(
+                "INSERT INTO payouts
+                (raffle_id, winner, fee_recipient, prize_amount, fee_amount, tx_hash, block_number, block_hash, block_time)
+                SELECT raffle_id, $2, fee_recipient, $3::text::numeric, $4::text::numeric, $5, $6, $7, $8
+                FROM raffles
+                WHERE raffle_id = $1
+                ON CONFLICT (raffle_id) DO NOTHING",
+                u256_to_i64(raffle_id)?,
+                format!("{:#x}", winner),
+                prize_amount.to_string(),
+                fee_amount.to_string(),
+                &tx_hash_hex,
+                block_number as i64,
+                block_hash_hex.as_deref(),
+                block_time,
+            )
+            .execute(&mut *db_tx)
+            .await
+            .context("failed to insert payout")?;
+
+            let fee_recipient: String = sqlx::query_scalar!(
+                "SELECT fee_recipient FROM raffles WHERE raffle_id = $1",
+                u256_to_i64(raffle_id)?,
+            )
+            .fetch_optional(&mut *db_tx)
+            .await
+            .context("failed to fetch fee recipient")?
+            .ok_or_else(|| anyhow!("WinnerSelected for unknown raffle {raffle_id}"))?;
+            post_ledger(
+                &mut db_tx,
+                &source,
+                u256_to_i64(raffle_id)?,
+                "PRIZE",
+                ("pot", &address_hex),
+                ("winner", &format!("{:#x}", winner)),
+                prize_amount,
+            )
+            .await?;
+            post_ledger(
+                &mut db_tx,
+                &source,
+                u256_to_i64(raffle_id)?,
+                "FEE",
+                ("pot", &address_hex),
+                ("fee_recipient", &fee_recipient),
+                fee_amount,
+            )
+            .await?;
         }
         EventKind::RefundClaimed => {
             let raffle_id = token_u256(&parsed, "raffleId")?;
             let buyer = token_address(&parsed, "buyer")?;
             let amount = token_u256(&parsed, "amount")?;
-            let inserted = sqlx::query(
+            let inserted = sqlx::query!(
                 "INSERT INTO refunds
-                (raffle_id, buyer, amount, tx_hash, log_index, block_number)
-                VALUES ($1, $2, $3::numeric, $4, $5, $6)
+                (raffle_id, buyer, amount, tx_hash, log_index, block_number, block_hash, block_time)
+                VALUES ($1, $2, $3::text::numeric, $4, $5, $6, $7, $8)
                 ON CONFLICT (tx_hash, log_index) DO NOTHING",
+                u256_to_i64(raffle_id)?,
+                format!("{:#x}", buyer),
+                amount.to_string(),
+                &tx_hash_hex,
+                log_index as i64,
+                block_number as i64,
+                block_hash_hex.as_deref(),
+                block_time,
             )
-            .bind(u256_to_i64(raffle_id)?)
-            .bind(format!("{:#x}", buyer))
-            .bind(amount.to_string())
-            .bind(&tx_hash_hex)
-            .bind(log_index.as_u64() as i64)
-            .bind(block_number.as_u64() as i64)
             .execute(&mut *db_tx)
             .await?
             .rows_affected();
 
+            // Refunds of a canceled raffle keep it CANCELED
             if inserted > 0 {
-                sqlx::query(
+                post_ledger(
+                    &mut db_tx,
+                    &source,
+                    u256_to_i64(raffle_id)?,
+                    "REFUND",
+                    ("pot", &address_hex),
+                    ("buyer", &format!("{:#x}", buyer)),
+                    amount,
+                )
+                .await?;
+                sqlx::query!(
                     "UPDATE raffles
                     SET status = $1,
-                        pot = pot - $2::numeric,
                         updated_at = now()
-                    WHERE raffle_id = $3",
+                    WHERE raffle_id = $2 AND status <> 'CANCELED'",
+                    "REFUNDING",
+                    u256_to_i64(raffle_id)?,
                 )
-                .bind("REFUNDING")
-                .bind(amount.to_string())
-                .bind(u256_to_i64(raffle_id)?)
                 .execute(&mut *db_tx)
                 .await?;
+                recompute_raffle_totals(&mut db_tx, u256_to_i64(raffle_id)?).await?;
             }
         }
         EventKind::RefundsStarted => {
             let raffle_id = token_u256(&parsed, "raffleId")?;
-            sqlx::query(
+            sqlx::query!(
                 "UPDATE raffles
                 SET status = $1,
                     updated_at = now()
-                WHERE raffle_id = $2",
+                WHERE raffle_id = $2 AND status <> 'CANCELED'",
+                "REFUNDING",
+                u256_to_i64(raffle_id)?,
             )
-            .bind("REFUNDING")
-            .bind(u256_to_i64(raffle_id)?)
             .execute(&mut *db_tx)
             .await
             .context("failed to update raffle to REFUNDING")?;
+            enqueue_refund_reminders(&mut db_tx, u256_to_i64(raffle_id)?).await?;
+        }
+        // Every buyer of a canceled raffle is owed a refund of their tickets
+        EventKind::RaffleCanceled => {
+            let raffle_id = token_u256(&parsed, "raffleId")?;
+            sqlx::query!(
+                "UPDATE raffles
+                SET status = $1,
+                    updated_at = now()
+                WHERE raffle_id = $2",
+                "CANCELED",
+                u256_to_i64(raffle_id)?,
+            )
+            .execute(&mut *db_tx)
+            .await
+            .context("failed to update raffle to CANCELED")?;
+            enqueue_refund_reminders(&mut db_tx, u256_to_i64(raffle_id)?).await?;
+        }
+        // KeeperUpdated(address indexed oldKeeper, address indexed newKeeper)
+        // has no raffleId: resolve the raffle from the emitting contract.
+        EventKind::KeeperUpdated => {
+            let old_keeper = token_address(&parsed, "oldKeeper")?;
+            let new_keeper = token_address(&parsed, "newKeeper")?;
+
+            let raffle_id: i64 = sqlx::query_scalar!(
+                "SELECT raffle_id FROM raffles WHERE raffle_address = $1",
+                &address_hex,
+            )
+            .fetch_optional(&mut *db_tx)
+            .await
+            .context("failed to look up raffle by address")?
+            .ok_or_else(|| anyhow!("KeeperUpdated from unknown raffle {}", address_hex))?;
+
+            sqlx::query!(
+                "INSERT INTO keeper_updates
+                (raffle_id, old_keeper, new_keeper, tx_hash, log_index, block_number, block_hash, block_time)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (tx_hash, log_index) DO NOTHING",
+                raffle_id,
+                format!("{:#x}", old_keeper),
+                format!("{:#x}", new_keeper),
+                &tx_hash_hex,
+                log_index as i64,
+                block_number as i64,
+                block_hash_hex.as_deref(),
+                block_time,
+            )
+            .execute(&mut *db_tx)
+            .await
+            .context("failed to insert keeper update")?;
+
+            recompute_raffle_keeper(&mut db_tx, raffle_id).await?;
+        }
+        EventKind::PayoutsCompleted => {
+            let raffle_id = token_u256(&parsed, "raffleId")?;
+            let winner = token_address(&parsed, "winner")?;
+            let fee_recipient = token_address(&parsed, "feeRecipient")?;
+            let prize_amount = token_u256(&parsed, "prizeAmount")?;
+            let fee_amount = token_u256(&parsed, "feeAmount")?;
+            sqlx::query!(
+                "UPDATE raffles
+                SET prize_amount = $1::text::numeric,
+                    fee_amount = $2::text::numeric,
+                    payout_tx = $3,
+                    updated_at = now()
+                WHERE raffle_id = $4",
+                prize_amount.to_string(),
+                fee_amount.to_string(),
+                &tx_hash_hex,
+                u256_to_i64(raffle_id)?,
+            )
+            .execute(&mut *db_tx)
+            .await
+            .context("failed to update raffle payouts")?;
+
+            sqlx::query!(
+                "INSERT INTO payouts
+                (raffle_id, winner, fee_recipient, prize_amount, fee_amount, tx_hash, block_number, block_hash, block_time)
+                VALUES ($1, $2, $3, $4::text::numeric, $5::text::numeric, $6, $7, $8, $9)
+                ON CONFLICT (raffle_id) DO UPDATE SET
+                    winner = excluded.winner,
+                    fee_recipient = excluded.fee_recipient,
+                    prize_amount = excluded.prize_amount,
+                    fee_amount = excluded.fee_amount",
+                u256_to_i64(raffle_id)?,
+                format!("{:#x}", winner),
+                format!("{:#x}", fee_recipient),
+                prize_amount.to_string(),
+                fee_amount.to_string(),
+                &tx_hash_hex,
+                block_number as i64,
+                block_hash_hex.as_deref(),
+                block_time,
+            )
+            .execute(&mut *db_tx)
+            .await
+            .context("failed to upsert payout")?;
         }
-        // Events we log but don't need to store derived state for
-        EventKind::KeeperUpdated | EventKind::PayoutsCompleted => {}
 
         // DrandRandomnessProvider: RandomnessRequested(uint256 indexed requestId, uint256 indexed raffleId, address indexed raffle)
         EventKind::ProviderRandomnessRequested => {
@@ -681,35 +1647,36 @@ async fn process_log(
             let raffle_address = token_address(&parsed, "raffle")?;
 
             // Insert into randomness_requests table
-            sqlx::query(
+            sqlx::query!(
                 "INSERT INTO randomness_requests
-                (request_id, raffle_id, raffle_address, provider_address, tx_hash, log_index, block_number)
-                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                (request_id, raffle_id, raffle_address, provider_address, tx_hash, log_index, block_number, block_hash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
                 ON CONFLICT (tx_hash, log_index) DO NOTHING",
+                request_id.to_string(),
+                u256_to_i64(raffle_id).ok(), // May be too large
+                format!("{:#x}", raffle_address),
+                &address_hex, // provider address is the log emitter
+                &tx_hash_hex,
+                log_index as i64,
+                block_number as i64,
+                block_hash_hex.as_deref(),
             )
-            .bind(request_id.to_string())
-            .bind(u256_to_i64(raffle_id).ok())  // May be too large
-            .bind(format!("{:#x}", raffle_address))
-            .bind(&address_hex)  // provider address is the log emitter
-            .bind(&tx_hash_hex)
-            .bind(log_index.as_u64() as i64)
-            .bind(block_number.as_u64() as i64)
             .execute(&mut *db_tx)
             .await
             .context("failed to insert randomness request")?;
 
             // Update raffle with provider request info
             if let Ok(raffle_id_i64) = u256_to_i64(raffle_id) {
-                sqlx::query(
+                sqlx::query!(
                     "UPDATE raffles
                     SET provider_request_id = $1,
                         provider_request_tx = $2,
                         updated_at = now()
                     WHERE raffle_id = $3",
+                    request_id.to_string(),
+                    &tx_hash_hex,
+                    raffle_id_i64,
                 )
-                .bind(request_id.to_string())
-                .bind(&tx_hash_hex)
-                .bind(raffle_id_i64)
                 .execute(&mut *db_tx)
                 .await
                 .context("failed to update raffle with provider request")?;
@@ -725,42 +1692,125 @@ async fn process_log(
 
             let proof_hex = proof.map(|p| format!("0x{}", hex::encode(p)));
 
+            // Correlate with the raffle through the provider's own request event
+            let raffle_id: Option<i64> = sqlx::query_scalar!(
+                "SELECT raffle_id FROM randomness_requests
+                WHERE request_id = $1 AND provider_address = $2
+                ORDER BY id DESC
+                LIMIT 1",
+                request_id.to_string(),
+                &address_hex,
+            )
+            .fetch_optional(&mut *db_tx)
+            .await
+            .context("failed to look up randomness request")?
+            .flatten();
+
             // Insert into randomness_fulfillments table
-            sqlx::query(
+            sqlx::query!(
                 "INSERT INTO randomness_fulfillments
-                (request_id, randomness, proof, raffle_address, provider_address, tx_hash, log_index, block_number)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                (request_id, raffle_id, randomness, proof, raffle_address, provider_address, tx_hash, log_index, block_number, block_hash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
                 ON CONFLICT (tx_hash, log_index) DO NOTHING",
+                request_id.to_string(),
+                raffle_id,
+                randomness.to_string(),
+                proof_hex.as_deref(),
+                format!("{:#x}", raffle_address),
+                &address_hex, // provider address is the log emitter
+                &tx_hash_hex,
+                log_index as i64,
+                block_number as i64,
+                block_hash_hex.as_deref(),
             )
-            .bind(request_id.to_string())
-            .bind(randomness.to_string())
-            .bind(&proof_hex)
-            .bind(format!("{:#x}", raffle_address))
-            .bind(&address_hex)  // provider address is the log emitter
-            .bind(&tx_hash_hex)
-            .bind(log_index.as_u64() as i64)
-            .bind(block_number.as_u64() as i64)
             .execute(&mut *db_tx)
             .await
             .context("failed to insert randomness fulfillment")?;
 
-            // Update raffle with provider fulfillment info (find by raffle_address)
-            sqlx::query(
+            // Update raffle with provider fulfillment info (by correlated raffle_id,
+            // falling back to raffle_address when the request wasn't indexed)
+            sqlx::query!(
                 "UPDATE raffles
                 SET provider_fulfill_tx = $1,
                     proof_data = $2,
+                    provider_randomness = $3,
                     updated_at = now()
-                WHERE raffle_address = $3",
+                WHERE raffle_id = $4 OR ($4 IS NULL AND raffle_address = $5)",
+                &tx_hash_hex,
+                proof_hex.as_deref(),
+                randomness.to_string(),
+                raffle_id,
+                format!("{:#x}", raffle_address),
             )
-            .bind(&tx_hash_hex)
-            .bind(&proof_hex)
-            .bind(format!("{:#x}", raffle_address))
             .execute(&mut *db_tx)
             .await
             .context("failed to update raffle with provider fulfillment")?;
         }
     }
 
+    // Stamped on every pass like the rows themselves, for `GET /v1/changes`. Provider
+    // fulfillments name the raffle by address only.
+    let touched_id = token_u256(&parsed, "raffleId")
+        .ok()
+        .and_then(|raffle_id| u256_to_i64(raffle_id).ok());
+    let touched_address = token_address(&parsed, "raffle")
+        .ok()
+        .map(|raffle| format!("{raffle:#x}"));
+    if touched_id.is_some() || touched_address.is_some() {
+        sqlx::query!(
+            "UPDATE raffles
+            SET updated_block = GREATEST(updated_block, $1)
+            WHERE raffle_id = $2 OR ($2 IS NULL AND raffle_address = $3)",
+            block_number as i64,
+            touched_id,
+            touched_address,
+        )
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to stamp raffle change")?;
+    }
+
+    if let Some(status) = event_def.kind.resulting_status() {
+        let raffle_id = u256_to_i64(token_u256(&parsed, "raffleId")?)?;
+        // Recorded on every pass, so a replay fills in transitions indexed before the table
+        sqlx::query!(
+            "INSERT INTO raffle_status_changes
+            (raffle_id, status, tx_hash, log_index, block_number, block_hash, block_time)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (raffle_id, status, tx_hash) DO NOTHING",
+            raffle_id,
+            status,
+            source.tx_hash,
+            source.log_index,
+            source.block_number,
+            source.block_hash,
+            source.block_time,
+        )
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to record status change")?;
+        recompute_stage_durations(&mut db_tx, raffle_id).await?;
+        live_events.push(LiveEvent::Status {
+            raffle_id,
+            status: status.to_string(),
+            tx_hash: tx_hash_hex.clone(),
+            block_number: block_number as i64,
+        });
+    }
+
+    // Events indexed before were published then
+    if is_new {
+        for event in &live_events {
+            outbox::enqueue(
+                &mut db_tx,
+                event,
+                block_number as i64,
+                block_hash_hex.as_deref(),
+            )
+            .await?;
+        }
+    }
+
     db_tx
         .commit()
         .await
@@ -768,63 +1818,248 @@ async fn process_log(
     Ok(())
 }
 
+/// Records an anomaly if `kind` doesn't apply to the raffle's current status
+///
+/// The event is applied either way; the anomaly flags the status it may leave behind.
+async fn check_status_transition(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    kind: EventKind,
+    raffle_id: i64,
+    tx_hash: &str,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<&str>,
+) -> anyhow::Result<()> {
+    let allowed = kind.allowed_statuses().unwrap_or_default();
+    let current: Option<String> = sqlx::query_scalar!(
+        "SELECT status FROM raffles WHERE raffle_id = $1 FOR UPDATE",
+        raffle_id,
+    )
+    .fetch_optional(&mut **db_tx)
+    .await
+    .context("failed to fetch raffle status")?;
+    let valid = match current.as_deref() {
+        None => matches!(kind, EventKind::RaffleCreated),
+        Some(status) => {
+            allowed.contains(&status)
+                || kind.resulting_status() == Some(status)
+                    && !matches!(kind, EventKind::RaffleCreated)
+        }
+    };
+    if valid {
+        return Ok(());
+    }
+
+    tracing::warn!(
+        raffle_id,
+        event = ?kind,
+        from_status = current.as_deref().unwrap_or("(not indexed)"),
+        to_status = kind.resulting_status().unwrap_or("-"),
+        tx_hash,
+        "event does not apply to the raffle's status"
+    );
+    sqlx::query!(
+        "INSERT INTO anomalies
+        (raffle_id, event, from_status, to_status, tx_hash, log_index, block_number, block_hash)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (tx_hash, log_index) DO NOTHING",
+        raffle_id,
+        format!("{kind:?}"),
+        current,
+        kind.resulting_status(),
+        tx_hash,
+        log_index,
+        block_number,
+        block_hash,
+    )
+    .execute(&mut **db_tx)
+    .await
+    .context("failed to record anomaly")?;
+    Ok(())
+}
+
+/// Outcome of [`replay_raw_events`]
+pub struct ReplaySummary {
+    pub events: u64,
+    /// Events that failed to process and were skipped
+    pub failed: u64,
+    pub checkpoint: u64,
+}
+
+/// Rebuilds the derived tables by replaying every stored raw event in chain order
+///
+/// Used by `restore-raw` on a database whose only indexed data is a restored
+/// `events_raw`. Events go through [`process_log`] exactly as when indexed live;
+/// ones that fail are logged and skipped. Replayed purchases don't queue whale
+/// alerts. Afterwards the checkpoint is moved to `checkpoint` (or the last replayed
+/// block, if later) so the indexer resumes from there.
+pub async fn replay_raw_events(
+    db_pool: &PgPool,
+    config: &AppConfig,
+    checkpoint: u64,
+) -> anyhow::Result<ReplaySummary> {
+    let events_by_signature = load_event_map(config.randomness_provider_address.is_some())?;
+    let config = AppConfig {
+        whale_webhook_url: None,
+        ..config.clone()
+    };
+
+    let mut summary = ReplaySummary {
+        events: 0,
+        failed: 0,
+        checkpoint,
+    };
+    let mut after = (-1_i64, -1_i64);
+    loop {
+        let rows = sqlx::query_as!(
+            RawEventRow,
+            "SELECT tx_hash, log_index, block_number, block_hash, block_time, address,
+                    topic0, topics, data
+             FROM events_raw
+             WHERE (block_number, log_index) > ($1, $2)
+             ORDER BY block_number, log_index
+             LIMIT $3",
+            after.0,
+            after.1,
+            REPLAY_BATCH_SIZE,
+        )
+        .fetch_all(db_pool)
+        .await
+        .context("failed to read raw events")?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = (last.block_number, last.log_index);
+
+        for row in &rows {
+            summary.events += 1;
+            let result = match raw_event_to_log(row) {
+                Ok(log_entry) => {
+                    process_log(
+                        db_pool,
+                        &events_by_signature,
+                        &log_entry,
+                        row.block_time,
+                        &config,
+                    )
+                    .await
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                summary.failed += 1;
+                tracing::warn!(
+                    tx_hash = %row.tx_hash,
+                    error = %err,
+                    "failed to replay raw event, skipping"
+                );
+            }
+        }
+        summary.checkpoint = summary.checkpoint.max(after.0 as u64);
+        tracing::info!(
+            events = summary.events,
+            block = after.0,
+            "replaying raw events"
+        );
+    }
+
+    set_last_processed_block(db_pool, summary.checkpoint).await?;
+    Ok(summary)
+}
+
+/// One row of `events_raw`, as read by [`replay_raw_events`]
+struct RawEventRow {
+    tx_hash: String,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    address: String,
+    topic0: String,
+    topics: Option<Vec<String>>,
+    data: String,
+}
+
+/// Reconstructs the log of an `events_raw` row
+///
+/// Rows indexed before all topics were stored only have topic0, which is enough for
+/// events without indexed parameters.
+fn raw_event_to_log(row: &RawEventRow) -> anyhow::Result<Log> {
+    let hash =
+        |value: &str| B256::from_str(value).with_context(|| format!("invalid hash: {value}"));
+    let topics = match &row.topics {
+        Some(topics) => topics.clone(),
+        None => vec![row.topic0.clone()],
+    };
+    let data = &row.data;
+    let address = &row.address;
+    Ok(Log {
+        inner: alloy::primitives::Log {
+            address: Address::from_str(address)
+                .with_context(|| format!("invalid address: {address}"))?,
+            data: LogData::new_unchecked(
+                topics
+                    .iter()
+                    .map(|topic| hash(topic))
+                    .collect::<anyhow::Result<_>>()?,
+                hex::decode(data.trim_start_matches("0x"))
+                    .context("invalid event data")?
+                    .into(),
+            ),
+        },
+        block_hash: row.block_hash.as_deref().map(hash).transpose()?,
+        block_number: Some(row.block_number as u64),
+        transaction_hash: Some(hash(&row.tx_hash)?),
+        log_index: Some(row.log_index as u64),
+        ..Default::default()
+    })
+}
+
 // ============================================================================
 // TOKEN EXTRACTION HELPERS
 // ============================================================================
 
 /// Extracts a U256 value from a parsed event log
-fn extract_u256(parsed: &ethers::abi::Log, name: &str) -> anyhow::Result<U256> {
-    let token = parsed
-        .params
-        .iter()
-        .find(|param| param.name == name)
-        .map(|param| &param.value)
-        .ok_or_else(|| anyhow!("missing event parameter: {}", name))?;
-
-    match token {
-        Token::Uint(value) => Ok(*value),
+fn extract_u256(parsed: &DecodedLog, name: &str) -> anyhow::Result<U256> {
+    match extract_param(parsed, name)? {
+        DynSolValue::Uint(value, _) => Ok(*value),
         _ => Err(anyhow!("event parameter '{}' is not a uint", name)),
     }
 }
 
 /// Extracts an Address value from a parsed event log
-fn extract_address(parsed: &ethers::abi::Log, name: &str) -> anyhow::Result<Address> {
-    let token = parsed
-        .params
-        .iter()
-        .find(|param| param.name == name)
-        .map(|param| &param.value)
-        .ok_or_else(|| anyhow!("missing event parameter: {}", name))?;
-
-    match token {
-        Token::Address(value) => Ok(*value),
+fn extract_address(parsed: &DecodedLog, name: &str) -> anyhow::Result<Address> {
+    match extract_param(parsed, name)? {
+        DynSolValue::Address(value) => Ok(*value),
         _ => Err(anyhow!("event parameter '{}' is not an address", name)),
     }
 }
 
 /// Extracts bytes data from a parsed event log
-fn extract_bytes(parsed: &ethers::abi::Log, name: &str) -> anyhow::Result<Vec<u8>> {
-    let token = parsed
-        .params
-        .iter()
-        .find(|param| param.name == name)
-        .map(|param| &param.value)
-        .ok_or_else(|| anyhow!("missing event parameter: {}", name))?;
-
-    match token {
-        Token::Bytes(value) => Ok(value.clone()),
+fn extract_bytes(parsed: &DecodedLog, name: &str) -> anyhow::Result<Vec<u8>> {
+    match extract_param(parsed, name)? {
+        DynSolValue::Bytes(value) => Ok(value.clone()),
         _ => Err(anyhow!("event parameter '{}' is not bytes", name)),
     }
 }
 
+fn extract_param<'a>(parsed: &'a DecodedLog, name: &str) -> anyhow::Result<&'a DynSolValue> {
+    parsed
+        .params
+        .iter()
+        .find(|(param, _)| *param == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| anyhow!("missing event parameter: {}", name))
+}
+
 // Convenience aliases for more descriptive naming at call sites
 #[inline]
-fn token_u256(parsed: &ethers::abi::Log, name: &str) -> anyhow::Result<U256> {
+fn token_u256(parsed: &DecodedLog, name: &str) -> anyhow::Result<U256> {
     extract_u256(parsed, name)
 }
 
 #[inline]
-fn token_address(parsed: &ethers::abi::Log, name: &str) -> anyhow::Result<Address> {
+fn token_address(parsed: &DecodedLog, name: &str) -> anyhow::Result<Address> {
     extract_address(parsed, name)
 }
 
@@ -834,10 +2069,7 @@ fn token_address(parsed: &ethers::abi::Log, name: &str) -> anyhow::Result<Addres
 
 /// Converts U256 to i64, returning error if value overflows
 fn u256_to_i64(value: U256) -> anyhow::Result<i64> {
-    if value > U256::from(i64::MAX as u64) {
-        return Err(anyhow!("U256 value {} overflows i64", value));
-    }
-    Ok(value.as_u64() as i64)
+    i64::try_from(value).map_err(|_| anyhow!("U256 value {} overflows i64", value))
 }
 
 /// Converts a Unix timestamp U256 to DateTime<Utc>
@@ -847,48 +2079,320 @@ fn u256_to_datetime(value: U256) -> anyhow::Result<DateTime<Utc>> {
         .ok_or_else(|| anyhow!("invalid Unix timestamp: {}", seconds))
 }
 
+/// Converts a block timestamp to DateTime<Utc>
+fn timestamp_to_datetime(seconds: u64) -> anyhow::Result<DateTime<Utc>> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| DateTime::<Utc>::from_timestamp(seconds, 0))
+        .ok_or_else(|| anyhow!("invalid Unix timestamp: {}", seconds))
+}
+
 // ============================================================================
 // DATABASE HELPERS
 // ============================================================================
 
 /// Gets the last processed block from indexer_state
 async fn get_last_processed_block(pool: &PgPool) -> anyhow::Result<u64> {
-    let row = sqlx::query("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+    let value = sqlx::query_scalar!("SELECT last_processed_block FROM indexer_state WHERE id = 1")
         .fetch_one(pool)
         .await
         .context("failed to fetch indexer state")?;
 
-    let value: i64 = row
-        .try_get("last_processed_block")
-        .context("failed to read last_processed_block")?;
-
     Ok(value as u64)
 }
 
+/// Reads whether an admin left the indexer paused
+pub async fn load_paused(pool: &PgPool) -> anyhow::Result<bool> {
+    let paused: Option<bool> = sqlx::query_scalar!(
+        r#"SELECT paused_at IS NOT NULL AS "paused!" FROM indexer_state WHERE id = 1"#
+    )
+    .fetch_optional(pool)
+    .await
+    .context("failed to fetch indexer pause state")?;
+    Ok(paused.unwrap_or(false))
+}
+
 /// Updates the last processed block in indexer_state
 async fn set_last_processed_block(pool: &PgPool, block: u64) -> anyhow::Result<()> {
-    sqlx::query(
+    sqlx::query!(
         "UPDATE indexer_state SET last_processed_block = $1, updated_at = now() WHERE id = 1",
+        block as i64,
     )
-    .bind(block as i64)
     .execute(pool)
     .await
     .context("failed to update last processed block")?;
     Ok(())
 }
 
+/// Records the chain's finalized block; it never moves back, even if a lagging RPC
+/// node reports an older one
+async fn set_finalized_block(pool: &PgPool, block: u64) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE indexer_state SET finalized_block = GREATEST(finalized_block, $1) WHERE id = 1",
+        block as i64,
+    )
+    .execute(pool)
+    .await
+    .context("failed to update finalized block")?;
+    Ok(())
+}
+
+/// Moves the last processed block from `from` to `to`, returning false if it no longer
+/// reads `from`
+///
+/// The orphan check ([`crate::orphans`]) may rewind the cursor while a batch runs. The
+/// batch's rows stay, and the next cycle starts from the rewound block.
+async fn advance_last_processed_block(pool: &PgPool, from: u64, to: u64) -> anyhow::Result<bool> {
+    let updated = sqlx::query!(
+        "UPDATE indexer_state SET last_processed_block = $2, updated_at = now()
+         WHERE id = 1 AND last_processed_block = $1",
+        from as i64,
+        to as i64,
+    )
+    .execute(pool)
+    .await
+    .context("failed to update last processed block")?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+/// Inserts a log into `events_raw`, returning whether it is new or newly decoded
+///
+/// A log already stored with the same `decoded` flag is left alone, so re-processing
+/// is idempotent; one stored undecoded is flipped once the ABIs know its event.
+async fn store_raw_event(
+    conn: &mut sqlx::PgConnection,
+    log_entry: &Log,
+    block_time: Option<DateTime<Utc>>,
+    decoded: bool,
+) -> anyhow::Result<bool> {
+    let tx_hash = log_entry
+        .transaction_hash
+        .ok_or_else(|| anyhow!("log missing transaction hash"))?;
+    let log_index = log_entry
+        .log_index
+        .ok_or_else(|| anyhow!("log missing log index"))?;
+    let block_number = log_entry
+        .block_number
+        .ok_or_else(|| anyhow!("log missing block number"))?;
+    let topics: Vec<String> = log_entry
+        .topics()
+        .iter()
+        .map(|topic| format!("{:#x}", topic))
+        .collect();
+
+    let stored = sqlx::query!(
+        "INSERT INTO events_raw (tx_hash, log_index, block_number, block_hash, block_time, address, topic0, topics, data, decoded)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         ON CONFLICT (tx_hash, log_index) DO UPDATE SET decoded = excluded.decoded
+         WHERE events_raw.decoded <> excluded.decoded",
+        format!("{:#x}", tx_hash),
+        log_index as i64,
+        block_number as i64,
+        log_entry.block_hash.map(|hash| format!("{:#x}", hash)),
+        block_time,
+        format!("{:#x}", log_entry.address()),
+        format!(
+        "{:#x}",
+        log_entry.topics().first().copied().unwrap_or_default()
+    ),
+        &topics,
+        format!("0x{}", hex::encode(&log_entry.data().data)),
+        decoded,
+    )
+    .execute(conn)
+    .await
+    .context("failed to store raw event")?
+    .rows_affected();
+    Ok(stored > 0)
+}
+
+/// Recomputes `total_tickets`, `unique_buyers` and `pot` for a raffle from its stored
+/// purchases and refunds
+///
+/// Totals are derived from the event tables instead of being incremented, so replaying
+/// a log (e.g. after a cursor rewind) can never double-count. Finalized raffles keep
+/// their pot at zero since the contract pays it out on finalization.
+pub(crate) async fn recompute_raffle_totals(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    raffle_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE raffles r
+        SET total_tickets = (
+                SELECT COALESCE(SUM(p.count), 0) FROM purchases p WHERE p.raffle_id = r.raffle_id
+            ),
+            unique_buyers = (
+                SELECT COUNT(DISTINCT p.buyer) FROM purchases p WHERE p.raffle_id = r.raffle_id
+            ),
+            pot = CASE
+                WHEN r.status = 'FINALIZED' THEN r.pot
+                ELSE (
+                    SELECT COALESCE(SUM(p.amount), 0) FROM purchases p WHERE p.raffle_id = r.raffle_id
+                ) - (
+                    SELECT COALESCE(SUM(f.amount), 0) FROM refunds f WHERE f.raffle_id = r.raffle_id
+                )
+            END,
+            updated_at = now()
+        WHERE r.raffle_id = $1",
+        raffle_id,
+    )
+    .execute(&mut **db_tx)
+    .await
+    .context("failed to recompute raffle totals")?;
+    Ok(())
+}
+
+/// Log a ledger posting comes from
+struct LedgerSource<'a> {
+    tx_hash: &'a str,
+    log_index: i64,
+    block_number: i64,
+    block_hash: Option<&'a str>,
+    block_time: Option<DateTime<Utc>>,
+}
+
+/// Posts `amount` moving from one `(account, address)` to another as two balanced
+/// ledger entries; zero amounts post nothing
+async fn post_ledger(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    source: &LedgerSource<'_>,
+    raffle_id: i64,
+    entry: &str,
+    from: (&str, &str),
+    to: (&str, &str),
+    amount: U256,
+) -> anyhow::Result<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "INSERT INTO ledger
+        (raffle_id, entry, account, address, amount, tx_hash, log_index, block_number, block_hash, block_time)
+        SELECT $1, $2, l.account, l.address, l.amount, $8, $9, $10, $11, $12
+        FROM (VALUES ($3, $4, -$7::text::numeric), ($5, $6, $7::text::numeric)) AS l (account, address, amount)
+        ON CONFLICT (tx_hash, log_index, entry, account) DO NOTHING",
+        raffle_id,
+        entry,
+        from.0,
+        from.1,
+        to.0,
+        to.1,
+        amount.to_string(),
+        source.tx_hash,
+        source.log_index,
+        source.block_number,
+        source.block_hash,
+        source.block_time,
+    )
+    .execute(&mut **db_tx)
+    .await
+    .with_context(|| format!("failed to post {entry} to the ledger"))?;
+    Ok(())
+}
+
+/// Sets a raffle's keeper to the latest stored `KeeperUpdated`, or its creator without one
+///
+/// The current keeper is the latest update on-chain, regardless of processing order.
+pub(crate) async fn recompute_raffle_keeper(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    raffle_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE raffles r
+        SET keeper = COALESCE(
+                (SELECT k.new_keeper FROM keeper_updates k
+                 WHERE k.raffle_id = r.raffle_id
+                 ORDER BY k.block_number DESC, k.log_index DESC
+                 LIMIT 1),
+                r.creator
+            ),
+            updated_at = now()
+        WHERE raffle_id = $1",
+        raffle_id,
+    )
+    .execute(&mut **db_tx)
+    .await
+    .context("failed to update raffle keeper")?;
+    Ok(())
+}
+
+/// Recomputes a raffle's row in `raffle_stage_durations` from its recorded status changes
+pub(crate) async fn recompute_stage_durations(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    raffle_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "INSERT INTO raffle_stage_durations
+        (raffle_id, closed_at, randomness_requested_at, randomness_fulfilled_at, finalized_at,
+         close_to_request_secs, request_to_fulfill_secs, fulfill_to_finalize_secs)
+        SELECT $1, closed_at, randomness_requested_at, randomness_fulfilled_at, finalized_at,
+            EXTRACT(EPOCH FROM randomness_requested_at - closed_at)::bigint,
+            EXTRACT(EPOCH FROM randomness_fulfilled_at - randomness_requested_at)::bigint,
+            EXTRACT(EPOCH FROM finalized_at - randomness_fulfilled_at)::bigint
+        FROM (
+            SELECT
+                MIN(block_time) FILTER (WHERE status = 'CLOSED') AS closed_at,
+                MIN(block_time) FILTER (WHERE status = 'RANDOM_REQUESTED') AS randomness_requested_at,
+                MIN(block_time) FILTER (WHERE status = 'RANDOM_FULFILLED') AS randomness_fulfilled_at,
+                MIN(block_time) FILTER (WHERE status = 'FINALIZED') AS finalized_at
+            FROM raffle_status_changes
+            WHERE raffle_id = $1
+        ) t
+        ON CONFLICT (raffle_id) DO UPDATE
+        SET closed_at = excluded.closed_at,
+            randomness_requested_at = excluded.randomness_requested_at,
+            randomness_fulfilled_at = excluded.randomness_fulfilled_at,
+            finalized_at = excluded.finalized_at,
+            close_to_request_secs = excluded.close_to_request_secs,
+            request_to_fulfill_secs = excluded.request_to_fulfill_secs,
+            fulfill_to_finalize_secs = excluded.fulfill_to_finalize_secs,
+            updated_at = now()",
+        raffle_id,
+    )
+    .execute(&mut **db_tx)
+    .await
+    .context("failed to update stage durations")?;
+    Ok(())
+}
+
+/// Queues a refund reminder for every subscribed buyer of a raffle that started refunding
+/// or was canceled
+///
+/// Buyers with an indexed refund are skipped. The first claimer's refund is logged after
+/// `RefundsStarted`, so the notifier re-checks before delivering. Re-processing the event
+/// queues nothing new.
+async fn enqueue_refund_reminders(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    raffle_id: i64,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "INSERT INTO refund_reminders (wallet, raffle_id)
+        SELECT DISTINCT p.buyer, p.raffle_id
+        FROM purchases p
+        JOIN refund_reminder_subscriptions s ON s.wallet = p.buyer
+        WHERE p.raffle_id = $1
+          AND NOT EXISTS (
+              SELECT 1 FROM refunds f WHERE f.raffle_id = p.raffle_id AND f.buyer = p.buyer
+          )
+        ON CONFLICT (wallet, raffle_id) DO NOTHING",
+        raffle_id,
+    )
+    .execute(&mut **db_tx)
+    .await
+    .context("failed to enqueue refund reminders")?;
+    Ok(())
+}
+
 /// Loads all known raffle addresses from the database
 async fn load_raffle_addresses(pool: &PgPool) -> anyhow::Result<Vec<Address>> {
-    let rows = sqlx::query("SELECT raffle_address FROM raffles ORDER BY raffle_id")
+    let rows = sqlx::query_scalar!("SELECT raffle_address FROM raffles ORDER BY raffle_id")
         .fetch_all(pool)
         .await
         .context("failed to fetch raffle addresses")?;
 
     let mut addresses = Vec::with_capacity(rows.len());
-    for row in rows {
-        let address_str: String = row
-            .try_get("raffle_address")
-            .context("failed to read raffle_address")?;
+    for address_str in rows {
         let address = Address::from_str(&address_str)
             .with_context(|| format!("invalid address format: {}", address_str))?;
         addresses.push(address);
@@ -896,3 +2400,113 @@ async fn load_raffle_addresses(pool: &PgPool) -> anyhow::Result<Vec<Address>> {
 
     Ok(addresses)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> HeadTracker {
+        HeadTracker {
+            head: None,
+            caught_up_at: None,
+            block_time: None,
+            finalized_checked_at: None,
+            interval: Duration::from_secs(2),
+            min_interval: Duration::from_millis(500),
+            max_interval: Duration::from_secs(10),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polls_at_the_smoothed_block_time() {
+        let mut head = tracker();
+        head.observe(100);
+        assert_eq!(head.interval, Duration::from_secs(2));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        head.observe(101);
+        assert_eq!(head.block_time, Some(Duration::from_secs(1)));
+        assert_eq!(head.interval, Duration::from_secs(1));
+
+        // A slower block moves the estimate a quarter of the way
+        tokio::time::advance(Duration::from_secs(5)).await;
+        head.observe(102);
+        assert_eq!(head.interval, Duration::from_secs(2));
+
+        // Several blocks at once count as several block times
+        tokio::time::advance(Duration::from_secs(2)).await;
+        head.observe(106);
+        assert_eq!(head.interval, Duration::from_millis(1625));
+
+        // Never faster than the minimum interval
+        for block in 107..120 {
+            tokio::time::advance(Duration::from_millis(100)).await;
+            head.observe(block);
+        }
+        assert_eq!(head.interval, Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_while_the_head_stands_still() {
+        let mut head = tracker();
+        head.observe(100);
+        head.observe(100);
+        assert_eq!(head.interval, Duration::from_secs(3));
+        // A lagging node reporting an older head counts the same
+        head.observe(99);
+        assert_eq!(head.interval, Duration::from_millis(4500));
+        for _ in 0..10 {
+            head.observe(100);
+        }
+        assert_eq!(head.interval, Duration::from_secs(10));
+
+        // The next block resets the interval to the block time
+        tokio::time::advance(Duration::from_secs(3)).await;
+        head.observe(101);
+        assert_eq!(head.interval, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn caught_up_until_the_head_moves() {
+        let mut head = tracker();
+        assert!(!head.is_caught_up(100));
+        head.mark_caught_up(100);
+        assert!(head.is_caught_up(99));
+        assert!(head.is_caught_up(100));
+        assert!(!head.is_caught_up(101));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn checks_finality_on_a_slower_cadence() {
+        let mut head = tracker();
+        assert!(head.finalized_check_due());
+        head.mark_finalized_checked();
+        assert!(!head.finalized_check_due());
+
+        tokio::time::advance(FINALIZED_CHECK_INTERVAL - Duration::from_millis(1)).await;
+        assert!(!head.finalized_check_due());
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert!(head.finalized_check_due());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_returns_on_shutdown() {
+        let head = tracker();
+        let shutdown = CancellationToken::new();
+
+        let started = Instant::now();
+        head.wait(&shutdown).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+        let started = Instant::now();
+        tokio::join!(head.wait(&shutdown), async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            shutdown.cancel();
+        });
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+
+        let started = Instant::now();
+        head.wait(&shutdown).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,838 @@
+//! Keeper: drives raffles through their lifecycle
+//!
+//! When a keeper signer is configured (`KEEPER_SIGNER`, see [`crate::signer`]),
+//! the keeper periodically looks for indexed
+//! raffles its wallet operates (as keeper or creator) that are waiting on an
+//! operator call, and sends it:
+//! - `close()` once an ACTIVE raffle has ended or sold out
+//! - `requestRandom()` once a raffle with tickets is CLOSED
+//! - `finalize()` once randomness is fulfilled
+//!
+//! Calls are gas-estimated first; a call that would revert (e.g. the raffle's
+//! state moved on since the last indexing cycle) is skipped, not sent.
+//!
+//! # Transaction management
+//! [`TxManager`] assigns nonces locally and tracks every transaction until it is
+//! mined. A transaction still pending after `KEEPER_TX_STUCK_SECS` is replaced at
+//! the same nonce with its gas price bumped by `KEEPER_GAS_BUMP_PERCENT` (capped
+//! at `KEEPER_MAX_GAS_PRICE_GWEI`), so one underpriced transaction can't wedge
+//! every later one behind it. Every submission is recorded in `keeper_txs`, and
+//! pending ones are reloaded on restart.
+//!
+//! # Security Considerations
+//! - Keys stay with the signer (KMS or a remote signer in production)
+//! - Gas prices are capped so a fee spike can't drain the keeper wallet
+//! - All RPC calls have timeouts to prevent hanging
+
+use crate::config::AppConfig;
+use crate::rpc::{BudgetedHttp, RpcBudget};
+use crate::signer::{self, KeeperSigner};
+use anyhow::Context;
+use chrono::Utc;
+use ethers::providers::{JsonRpcClient, Middleware, Provider};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockNumber, Bytes, H256, TransactionRequest, U256};
+use ethers::utils::id;
+use sqlx::{PgPool, Row};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Timeout for individual RPC calls
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum raffles acted on per cycle
+const MAX_ACTIONS_PER_CYCLE: i64 = 20;
+
+/// Reverted attempts after which the keeper stops retrying an action
+const MAX_REVERTED_ATTEMPTS: i64 = 3;
+
+/// Headroom added to gas estimates (percent)
+const GAS_LIMIT_HEADROOM_PERCENT: u64 = 20;
+
+/// Operator calls the keeper sends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeeperAction {
+    Close,
+    RequestRandom,
+    Finalize,
+}
+
+impl KeeperAction {
+    /// Value stored in `keeper_txs.action`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeeperAction::Close => "close",
+            KeeperAction::RequestRandom => "request_random",
+            KeeperAction::Finalize => "finalize",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "close" => Some(KeeperAction::Close),
+            "request_random" => Some(KeeperAction::RequestRandom),
+            "finalize" => Some(KeeperAction::Finalize),
+            _ => None,
+        }
+    }
+
+    /// ABI-encoded call (all three functions take no arguments)
+    fn calldata(self) -> Bytes {
+        let signature = match self {
+            KeeperAction::Close => "close()",
+            KeeperAction::RequestRandom => "requestRandom()",
+            KeeperAction::Finalize => "finalize()",
+        };
+        Bytes::from(id(signature).to_vec())
+    }
+}
+
+/// Latest broadcast transaction for one nonce (the key in [`TxManager::pending`])
+struct PendingTx {
+    /// `keeper_txs.id` of the latest attempt
+    id: i64,
+    raffle_id: i64,
+    action: KeeperAction,
+    to: Address,
+    gas_limit: U256,
+    gas_price: U256,
+    attempt: i32,
+    /// Hashes of every attempt at this nonce (any of them may be mined)
+    hashes: Vec<(i64, H256)>,
+    submitted_at: Instant,
+}
+
+/// Keeper transaction settings
+struct TxPolicy {
+    stuck_after: Duration,
+    bump_percent: u64,
+    max_gas_price: U256,
+}
+
+/// Nonce assignment, submission and replacement of keeper transactions
+///
+/// Generic over the JSON-RPC client so the tests can run it against a mock chain.
+pub struct TxManager<P: JsonRpcClient = BudgetedHttp> {
+    provider: Provider<P>,
+    signer: KeeperSigner,
+    chain_id: u64,
+    db: PgPool,
+    policy: TxPolicy,
+    /// Next nonce to assign (`None` until synced from the node)
+    next_nonce: Option<U256>,
+    /// In-flight transactions by nonce
+    pending: BTreeMap<U256, PendingTx>,
+}
+
+impl<P: JsonRpcClient> TxManager<P> {
+    fn sender(&self) -> String {
+        format!("{:#x}", self.signer.address())
+    }
+
+    /// Reloads transactions left pending by a previous run
+    async fn load_pending(&mut self) -> anyhow::Result<()> {
+        let rows = sqlx::query(
+            "SELECT k.id, k.raffle_id, k.action, k.nonce::text AS nonce, k.tx_hash,
+                    k.gas_price::text AS gas_price, k.gas_limit::text AS gas_limit, k.attempt,
+                    k.submitted_at, r.raffle_address
+             FROM keeper_txs k
+             JOIN raffles r ON r.raffle_id = k.raffle_id
+             WHERE k.sender = $1
+               AND k.status IN ('pending', 'replaced')
+               AND k.nonce IN (
+                   SELECT nonce FROM keeper_txs WHERE sender = $1 AND status = 'pending'
+               )
+             ORDER BY k.id",
+        )
+        .bind(self.sender())
+        .fetch_all(&self.db)
+        .await
+        .context("failed to load pending keeper transactions")?;
+
+        for row in rows {
+            let id: i64 = row.try_get("id")?;
+            let action: String = row.try_get("action")?;
+            let Some(action) = KeeperAction::parse(&action) else {
+                continue;
+            };
+            let nonce = U256::from_dec_str(row.try_get("nonce")?)?;
+            let tx_hash = H256::from_str(row.try_get("tx_hash")?)?;
+            let submitted_at: chrono::DateTime<Utc> = row.try_get("submitted_at")?;
+            let age = (Utc::now() - submitted_at).to_std().unwrap_or_default();
+
+            // Rows are ordered by id, so later attempts overwrite earlier ones
+            let hashes = match self.pending.remove(&nonce) {
+                Some(previous) => previous.hashes,
+                None => Vec::new(),
+            };
+            let mut tx = PendingTx {
+                id,
+                raffle_id: row.try_get("raffle_id")?,
+                action,
+                to: Address::from_str(row.try_get("raffle_address")?)?,
+                gas_limit: U256::from_dec_str(row.try_get("gas_limit")?)?,
+                gas_price: U256::from_dec_str(row.try_get("gas_price")?)?,
+                attempt: row.try_get("attempt")?,
+                hashes,
+                submitted_at: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+            };
+            tx.hashes.push((id, tx_hash));
+            self.pending.insert(nonce, tx);
+        }
+        if !self.pending.is_empty() {
+            tracing::info!(
+                count = self.pending.len(),
+                "resumed tracking pending keeper transactions"
+            );
+        }
+        Ok(())
+    }
+
+    /// Re-reads the next nonce from the node (pending block)
+    async fn sync_nonce(&mut self) -> anyhow::Result<U256> {
+        let nonce = rpc(self
+            .provider
+            .get_transaction_count(self.signer.address(), Some(BlockNumber::Pending.into())))
+        .await
+        .context("failed to read keeper nonce")?;
+        // Never reuse a nonce we are still tracking
+        let nonce = match self.pending.keys().next_back() {
+            Some(highest) => nonce.max(*highest + 1),
+            None => nonce,
+        };
+        self.next_nonce = Some(nonce);
+        Ok(nonce)
+    }
+
+    /// Whether an action for the raffle is already in flight
+    fn is_pending(&self, raffle_id: i64, action: KeeperAction) -> bool {
+        self.pending
+            .values()
+            .any(|tx| tx.raffle_id == raffle_id && tx.action == action)
+    }
+
+    /// Estimates, signs and broadcasts an operator call
+    ///
+    /// Returns `Ok(false)` without sending when the call would revert or the
+    /// network gas price is above the cap.
+    async fn submit(
+        &mut self,
+        raffle_id: i64,
+        to: Address,
+        action: KeeperAction,
+    ) -> anyhow::Result<bool> {
+        let from = self.signer.address();
+        let call: TypedTransaction = TransactionRequest::new()
+            .from(from)
+            .to(to)
+            .data(action.calldata())
+            .into();
+        let estimate = match rpc(self.provider.estimate_gas(&call, None)).await {
+            Ok(estimate) => estimate,
+            Err(err) => {
+                tracing::debug!(raffle_id, action = action.as_str(), error = %err, "keeper call not executable, skipping");
+                return Ok(false);
+            }
+        };
+        let gas_limit = estimate + estimate * GAS_LIMIT_HEADROOM_PERCENT / 100;
+
+        let gas_price = rpc(self.provider.get_gas_price())
+            .await
+            .context("failed to read gas price")?;
+        if gas_price > self.policy.max_gas_price {
+            tracing::warn!(
+                raffle_id,
+                action = action.as_str(),
+                gas_price = %gas_price,
+                max_gas_price = %self.policy.max_gas_price,
+                "network gas price above KEEPER_MAX_GAS_PRICE_GWEI, deferring"
+            );
+            return Ok(false);
+        }
+
+        let nonce = match self.next_nonce {
+            Some(nonce) => nonce,
+            None => self.sync_nonce().await?,
+        };
+        let tx_hash = match self
+            .broadcast(to, action, nonce, gas_limit, gas_price)
+            .await
+        {
+            Ok(tx_hash) => tx_hash,
+            Err(err) => {
+                // Our view of the nonce may be off (e.g. the wallet was used elsewhere)
+                self.next_nonce = None;
+                return Err(err);
+            }
+        };
+        self.next_nonce = Some(nonce + 1);
+
+        let id = self
+            .record(raffle_id, action, nonce, tx_hash, gas_price, gas_limit, 1)
+            .await?;
+        tracing::info!(
+            raffle_id,
+            action = action.as_str(),
+            nonce = %nonce,
+            tx_hash = %format!("{:#x}", tx_hash),
+            "keeper transaction sent"
+        );
+        self.pending.insert(
+            nonce,
+            PendingTx {
+                id,
+                raffle_id,
+                action,
+                to,
+                gas_limit,
+                gas_price,
+                attempt: 1,
+                hashes: vec![(id, tx_hash)],
+                submitted_at: Instant::now(),
+            },
+        );
+        Ok(true)
+    }
+
+    /// Signs a legacy transaction and sends it, returning its hash
+    async fn broadcast(
+        &self,
+        to: Address,
+        action: KeeperAction,
+        nonce: U256,
+        gas_limit: U256,
+        gas_price: U256,
+    ) -> anyhow::Result<H256> {
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(self.signer.address())
+            .to(to)
+            .data(action.calldata())
+            .nonce(nonce)
+            .gas(gas_limit)
+            .gas_price(gas_price)
+            .chain_id(self.chain_id)
+            .into();
+        let raw = self
+            .signer
+            .sign_transaction(&tx)
+            .await
+            .context("failed to sign keeper transaction")?;
+        let tx_hash = signer::transaction_hash(&raw);
+        rpc(self.provider.send_raw_transaction(raw))
+            .await
+            .context("failed to send keeper transaction")?;
+        Ok(tx_hash)
+    }
+
+    /// Inserts a `keeper_txs` row for a broadcast transaction
+    #[allow(clippy::too_many_arguments)]
+    async fn record(
+        &self,
+        raffle_id: i64,
+        action: KeeperAction,
+        nonce: U256,
+        tx_hash: H256,
+        gas_price: U256,
+        gas_limit: U256,
+        attempt: i32,
+    ) -> anyhow::Result<i64> {
+        sqlx::query_scalar(
+            "INSERT INTO keeper_txs
+                (raffle_id, action, sender, nonce, tx_hash, gas_price, gas_limit, attempt)
+             VALUES ($1, $2, $3, $4::numeric, $5, $6::numeric, $7::numeric, $8)
+             RETURNING id",
+        )
+        .bind(raffle_id)
+        .bind(action.as_str())
+        .bind(self.sender())
+        .bind(nonce.to_string())
+        .bind(format!("{:#x}", tx_hash))
+        .bind(gas_price.to_string())
+        .bind(gas_limit.to_string())
+        .bind(attempt)
+        .fetch_one(&self.db)
+        .await
+        .context("failed to record keeper transaction")
+    }
+
+    /// Checks in-flight transactions: records mined ones, replaces stuck ones
+    async fn poll(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mined_nonce = rpc(self
+            .provider
+            .get_transaction_count(self.signer.address(), Some(BlockNumber::Latest.into())))
+        .await
+        .context("failed to read keeper nonce")?;
+
+        let nonces: Vec<U256> = self.pending.keys().copied().collect();
+        for nonce in nonces {
+            let Some(tx) = self.pending.get(&nonce) else {
+                continue;
+            };
+            let raffle_id = tx.raffle_id;
+            let mut mined = None;
+            for (id, hash) in &tx.hashes {
+                if let Some(receipt) = rpc(self.provider.get_transaction_receipt(*hash))
+                    .await
+                    .context("failed to read keeper transaction receipt")?
+                    && receipt.block_number.is_some()
+                {
+                    mined = Some((*id, receipt));
+                    break;
+                }
+            }
+
+            if let Some((id, receipt)) = mined {
+                let success = receipt.status.is_some_and(|status| status.as_u64() == 1);
+                let status = if success { "confirmed" } else { "reverted" };
+                self.finish(
+                    nonce,
+                    id,
+                    status,
+                    receipt.block_number.map(|b| b.as_u64() as i64),
+                )
+                .await?;
+                if success {
+                    tracing::info!(raffle_id, nonce = %nonce, "keeper transaction confirmed");
+                } else {
+                    tracing::warn!(raffle_id, nonce = %nonce, "keeper transaction reverted");
+                }
+                self.pending.remove(&nonce);
+            } else if nonce < mined_nonce {
+                // The nonce was used by a transaction we don't know about
+                let id = tx.id;
+                self.finish(nonce, id, "dropped", None).await?;
+                tracing::warn!(raffle_id, nonce = %nonce, "keeper nonce consumed by another transaction");
+                self.pending.remove(&nonce);
+            } else if tx.submitted_at.elapsed() >= self.policy.stuck_after {
+                self.replace(nonce).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the mined (or dropped) attempt and supersedes the others at its nonce
+    async fn finish(
+        &self,
+        nonce: U256,
+        id: i64,
+        status: &str,
+        block_number: Option<i64>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.db.begin().await?;
+        sqlx::query(
+            "UPDATE keeper_txs SET status = $2, block_number = $3, updated_at = now()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(block_number)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE keeper_txs SET status = 'dropped', updated_at = now()
+             WHERE sender = $1 AND nonce = $2::numeric AND id <> $3 AND status = 'pending'",
+        )
+        .bind(self.sender())
+        .bind(nonce.to_string())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit()
+            .await
+            .context("failed to update keeper transaction")?;
+        Ok(())
+    }
+
+    /// Re-sends a stuck transaction at the same nonce with a bumped gas price
+    async fn replace(&mut self, nonce: U256) -> anyhow::Result<()> {
+        let Some(tx) = self.pending.get(&nonce) else {
+            return Ok(());
+        };
+        if tx.gas_price >= self.policy.max_gas_price {
+            tracing::warn!(
+                raffle_id = tx.raffle_id,
+                nonce = %nonce,
+                "keeper transaction stuck at KEEPER_MAX_GAS_PRICE_GWEI, waiting"
+            );
+            return Ok(());
+        }
+
+        let network_price = rpc(self.provider.get_gas_price())
+            .await
+            .context("failed to read gas price")?;
+        let bumped = tx.gas_price + tx.gas_price * self.policy.bump_percent / 100;
+        let gas_price = bumped.max(network_price).min(self.policy.max_gas_price);
+
+        let (raffle_id, action, to, gas_limit, attempt, old_id) = (
+            tx.raffle_id,
+            tx.action,
+            tx.to,
+            tx.gas_limit,
+            tx.attempt + 1,
+            tx.id,
+        );
+        let tx_hash = match self
+            .broadcast(to, action, nonce, gas_limit, gas_price)
+            .await
+        {
+            Ok(tx_hash) => tx_hash,
+            Err(err) => {
+                // Typically "replacement transaction underpriced" or the original
+                // being mined meanwhile; retried on the next cycle
+                let error = format!("{err:#}");
+                tracing::warn!(raffle_id, nonce = %nonce, error = %error, "failed to replace keeper transaction");
+                sqlx::query("UPDATE keeper_txs SET error = $2, updated_at = now() WHERE id = $1")
+                    .bind(old_id)
+                    .bind(error)
+                    .execute(&self.db)
+                    .await
+                    .context("failed to update keeper transaction")?;
+                return Ok(());
+            }
+        };
+
+        let id = self
+            .record(
+                raffle_id, action, nonce, tx_hash, gas_price, gas_limit, attempt,
+            )
+            .await?;
+        sqlx::query(
+            "UPDATE keeper_txs SET status = 'replaced', replaced_by = $2, updated_at = now()
+             WHERE id = $1",
+        )
+        .bind(old_id)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .context("failed to update replaced keeper transaction")?;
+
+        tracing::info!(
+            raffle_id,
+            action = action.as_str(),
+            nonce = %nonce,
+            attempt,
+            gas_price = %gas_price,
+            tx_hash = %format!("{:#x}", tx_hash),
+            "replaced stuck keeper transaction"
+        );
+        if let Some(tx) = self.pending.get_mut(&nonce) {
+            tx.id = id;
+            tx.gas_price = gas_price;
+            tx.attempt = attempt;
+            tx.hashes.push((id, tx_hash));
+            tx.submitted_at = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+/// Runs an RPC call with [`RPC_TIMEOUT`]
+async fn rpc<T, E>(call: impl Future<Output = Result<T, E>>) -> anyhow::Result<T>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    Ok(tokio::time::timeout(RPC_TIMEOUT, call)
+        .await
+        .context("RPC call timed out")??)
+}
+
+/// Runs the keeper loop until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig, rpc_budget: RpcBudget) -> anyhow::Result<()> {
+    let Some(signer_config) = &config.keeper_signer else {
+        return Ok(());
+    };
+    let poll_interval = Duration::from_secs(config.keeper_poll_interval_secs);
+
+    // KMS and remote signers are network services; wait for them instead of giving up
+    let signer = loop {
+        match KeeperSigner::connect(signer_config).await {
+            Ok(signer) => break signer,
+            Err(err) => {
+                tracing::warn!(error = %format!("{err:#}"), "keeper signer unavailable, retrying");
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    };
+    let provider = rpc_budget
+        .provider(&config.rpc_url, "keeper")
+        .context("invalid RPC_URL")?;
+
+    let mut manager = TxManager {
+        provider,
+        signer,
+        chain_id: config.chain_id,
+        db,
+        policy: TxPolicy {
+            stuck_after: Duration::from_secs(config.keeper_tx_stuck_secs),
+            bump_percent: config.keeper_gas_bump_percent,
+            max_gas_price: U256::from(config.keeper_max_gas_price_gwei) * U256::exp10(9),
+        },
+        next_nonce: None,
+        pending: BTreeMap::new(),
+    };
+    tracing::info!(
+        address = %manager.sender(),
+        signer = manager.signer.kind(),
+        "keeper started"
+    );
+    manager.load_pending().await?;
+
+    loop {
+        if let Err(err) = run_keeper_cycle(&mut manager).await {
+            tracing::warn!(error = %format!("{err:#}"), "keeper cycle failed");
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+async fn run_keeper_cycle(manager: &mut TxManager) -> anyhow::Result<()> {
+    manager.poll().await?;
+
+    // Raffles operated by this wallet that are waiting on an operator call,
+    // excluding actions already done or given up on
+    let rows = sqlx::query(
+        "WITH due AS (
+            SELECT raffle_id, raffle_address,
+                CASE
+                    WHEN status = 'ACTIVE' THEN 'close'
+                    WHEN status = 'CLOSED' THEN 'request_random'
+                    ELSE 'finalize'
+                END AS action
+            FROM raffles
+            WHERE (keeper = $1 OR creator = $1)
+              AND ((status = 'ACTIVE'
+                    AND (end_time <= now() OR (max_tickets > 0 AND total_tickets >= max_tickets)))
+                OR (status = 'CLOSED' AND total_tickets > 0)
+                OR status = 'RANDOM_FULFILLED')
+        )
+        SELECT raffle_id, raffle_address, action
+        FROM due d
+        WHERE NOT EXISTS (
+            SELECT 1 FROM keeper_txs k
+            WHERE k.raffle_id = d.raffle_id AND k.action = d.action
+              AND k.status IN ('pending', 'confirmed')
+        )
+          AND (
+            SELECT count(*) FROM keeper_txs k
+            WHERE k.raffle_id = d.raffle_id AND k.action = d.action AND k.status = 'reverted'
+          ) < $2
+        ORDER BY raffle_id
+        LIMIT $3",
+    )
+    .bind(manager.sender())
+    .bind(MAX_REVERTED_ATTEMPTS)
+    .bind(MAX_ACTIONS_PER_CYCLE)
+    .fetch_all(&manager.db)
+    .await
+    .context("failed to load raffles due for keeper actions")?;
+
+    for row in rows {
+        let raffle_id: i64 = row.try_get("raffle_id")?;
+        let action: String = row.try_get("action")?;
+        let Some(action) = KeeperAction::parse(&action) else {
+            continue;
+        };
+        if manager.is_pending(raffle_id, action) {
+            continue;
+        }
+        let to =
+            Address::from_str(row.try_get("raffle_address")?).context("invalid raffle address")?;
+        manager.submit(raffle_id, to, action).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{CHAIN_ID, MockChain, TestDb};
+
+    const KEEPER_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const RAFFLE_ADDRESS: &str = "0x00000000000000000000000000000000000000a1";
+
+    fn gwei(value: u64) -> U256 {
+        U256::from(value) * U256::exp10(9)
+    }
+
+    /// A database with raffle 1 indexed, or `None` without `TEST_DATABASE_URL`
+    async fn database() -> Option<TestDb> {
+        let db = TestDb::create().await.unwrap()?;
+        sqlx::query(
+            "INSERT INTO raffles
+                (raffle_id, raffle_address, creator, end_time, ticket_price, max_tickets,
+                 fee_bps, fee_recipient, status)
+             VALUES (1, $1, $1, now(), 1000000, 0, 0, $1, 'ACTIVE')",
+        )
+        .bind(RAFFLE_ADDRESS)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        Some(db)
+    }
+
+    /// A manager that considers every transaction stuck, bumping by 50% up to 10 gwei
+    fn manager(db: &TestDb, chain: &MockChain) -> TxManager<MockChain> {
+        TxManager {
+            provider: Provider::new(chain.clone()),
+            signer: KeeperSigner::Local(KEEPER_KEY.parse().unwrap()),
+            chain_id: CHAIN_ID,
+            db: db.pool.clone(),
+            policy: TxPolicy {
+                stuck_after: Duration::ZERO,
+                bump_percent: 50,
+                max_gas_price: gwei(10),
+            },
+            next_nonce: None,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// `(attempt, status, gas_price)` of every recorded transaction
+    async fn recorded(db: &TestDb) -> Vec<(i32, String, String)> {
+        sqlx::query_as("SELECT attempt, status, gas_price::text FROM keeper_txs ORDER BY id")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    fn raffle() -> Address {
+        Address::from_str(RAFFLE_ADDRESS).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resyncs_the_nonce_after_a_failed_broadcast() {
+        let Some(db) = database().await else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+        let chain = MockChain::new(CHAIN_ID, 1);
+        let mut manager = manager(&db, &chain);
+        chain.set_nonce(manager.signer.address(), 3);
+        chain.reject_sends(1);
+
+        assert!(
+            manager
+                .submit(1, raffle(), KeeperAction::Close)
+                .await
+                .is_err()
+        );
+        assert_eq!(manager.next_nonce, None);
+
+        // The wallet was used elsewhere before the retry
+        chain.set_nonce(manager.signer.address(), 5);
+        assert!(
+            manager
+                .submit(1, raffle(), KeeperAction::Close)
+                .await
+                .unwrap()
+        );
+        let sent = chain.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].nonce, U256::from(5));
+        assert_eq!(manager.next_nonce, Some(U256::from(6)));
+    }
+
+    #[tokio::test]
+    async fn caps_replacements_at_the_max_gas_price() {
+        let Some(db) = database().await else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+        let chain = MockChain::new(CHAIN_ID, 1);
+        let mut manager = manager(&db, &chain);
+        chain.set_gas_price(gwei(8));
+        assert!(
+            manager
+                .submit(1, raffle(), KeeperAction::Close)
+                .await
+                .unwrap()
+        );
+
+        // 8 gwei bumped by 50% is 12, above the cap
+        manager.poll().await.unwrap();
+        let sent = chain.sent_transactions();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].nonce, sent[0].nonce);
+        assert_eq!(sent[1].gas_price, Some(gwei(10)));
+
+        // Already at the cap: waits instead of sending again
+        manager.poll().await.unwrap();
+        assert_eq!(chain.sent_transactions().len(), 2);
+        assert_eq!(
+            recorded(&db).await,
+            [
+                (1, "replaced".to_string(), gwei(8).to_string()),
+                (2, "pending".to_string(), gwei(10).to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn drops_a_nonce_consumed_by_another_transaction() {
+        let Some(db) = database().await else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+        let chain = MockChain::new(CHAIN_ID, 1);
+        let mut manager = manager(&db, &chain);
+        assert!(
+            manager
+                .submit(1, raffle(), KeeperAction::Close)
+                .await
+                .unwrap()
+        );
+
+        chain.set_nonce(manager.signer.address(), 1);
+        manager.poll().await.unwrap();
+        assert!(manager.pending.is_empty());
+        assert_eq!(
+            recorded(&db).await,
+            [(1, "dropped".to_string(), gwei(1).to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn reloads_every_attempt_of_a_replaced_nonce() {
+        let Some(db) = database().await else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+        let chain = MockChain::new(CHAIN_ID, 1);
+        let mut manager = manager(&db, &chain);
+        assert!(
+            manager
+                .submit(1, raffle(), KeeperAction::Close)
+                .await
+                .unwrap()
+        );
+        manager.poll().await.unwrap();
+        let sent = chain.sent_transactions();
+        assert_eq!(sent.len(), 2);
+
+        let mut restarted = self::manager(&db, &chain);
+        restarted.load_pending().await.unwrap();
+        let tx = &restarted.pending[&U256::zero()];
+        assert_eq!(tx.attempt, 2);
+        assert_eq!(
+            tx.hashes.iter().map(|(_, hash)| *hash).collect::<Vec<_>>(),
+            [sent[0].hash, sent[1].hash]
+        );
+
+        // The original attempt can still be the one mined
+        chain.mine(sent[0].hash, true);
+        restarted.poll().await.unwrap();
+        assert!(restarted.pending.is_empty());
+        assert_eq!(
+            recorded(&db).await,
+            [
+                (1, "confirmed".to_string(), gwei(1).to_string()),
+                (2, "dropped".to_string(), "1500000000".to_string()),
+            ]
+        );
+    }
+}
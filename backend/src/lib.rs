@@ -0,0 +1,699 @@
+//! Ticket Arcade Backend
+//!
+//! A Rust backend that indexes on-chain events from the Arc L1 blockchain
+//! and exposes REST APIs for the React frontend.
+//!
+//! # Architecture
+//! - **Indexer**: Polls RPC for contract events, stores in PostgreSQL
+//! - **API Server**: Axum-based REST API serving indexed data
+//!
+//! # Running
+//! ```bash
+//! # Set up environment
+//! cp .env.example .env
+//! # Start Postgres
+//! docker compose up -d
+//! # Run migrations
+//! sqlx migrate run --source migrations
+//! # Run the backend
+//! cargo run
+//! # Validate configuration, database and RPC without starting services
+//! cargo run -- check-config
+//! # Back up raw events / rebuild an empty database from a backup
+//! cargo run -- backup-raw
+//! cargo run -- restore-raw <key>
+//! # Measure indexer throughput and API latencies on a scratch database
+//! cargo run --release -- bench
+//! ```
+
+pub mod abi;
+mod admin_audit;
+mod analytics;
+mod api;
+mod api_keys;
+mod archive;
+mod auth;
+mod aws;
+mod backup;
+pub mod bench;
+mod cache;
+mod chain;
+mod circuit;
+mod cli;
+mod cohorts;
+mod config;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+mod digest;
+mod drain;
+mod error;
+mod export;
+mod extract;
+mod format;
+mod geo;
+pub mod indexer;
+mod keeper;
+mod live;
+mod mempool;
+mod metrics;
+mod notify;
+mod orphans;
+mod outbox;
+mod payouts;
+mod recovery;
+mod referrals;
+mod rpc;
+mod schema;
+mod signatures;
+mod signer;
+mod snapshots;
+mod state;
+mod storage;
+mod stuck;
+mod sybil;
+mod tasks;
+#[cfg(test)]
+mod testkit;
+mod verify;
+
+use axum::error_handling::HandleErrorLayer;
+use axum::{Json, Router, http::StatusCode, response::IntoResponse, routing::get};
+use config::PoolConfig;
+use error::ApiError;
+use ethers::signers::{LocalWallet, Signer};
+use serde_json::json;
+use sqlx::PgPool;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use state::AppState;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tasks::{Restart, Stop};
+use tokio::net::TcpListener;
+use tokio::signal;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower::{BoxError, ServiceBuilder};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Database connection pool timeout
+const DB_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs the command given on the command line (by default the server)
+pub async fn run() -> anyhow::Result<()> {
+    // Load .env file (ignore errors if not present)
+    dotenvy::dotenv().ok();
+
+    // Initialize tracing with environment filter; slow queries are also counted.
+    // The filter applies to these layers only, so tokio-console sees every task.
+    let logging = tracing_subscriber::fmt::layer()
+        .and_then(metrics::SlowQueryLayer)
+        .with_filter(EnvFilter::from_default_env().add_directive("info".parse()?));
+    let subscriber = tracing_subscriber::registry().with(logging);
+    #[cfg(feature = "diagnostics")]
+    let subscriber = subscriber.with(console_subscriber::spawn());
+    subscriber.init();
+
+    // Dispatch one-off subcommands before starting any services
+    match cli::Command::parse(std::env::args().skip(1))? {
+        cli::Command::Serve => {}
+        cli::Command::CheckConfig => {
+            let passed = cli::check_config().await;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        cli::Command::BackupRaw { deployment, key } => {
+            return cli::backup_raw(deployment, key).await;
+        }
+        cli::Command::RestoreRaw { deployment, key } => {
+            return cli::restore_raw(deployment, key).await;
+        }
+        cli::Command::Bench {
+            deployment,
+            options,
+        } => {
+            return cli::bench(deployment, options).await;
+        }
+        cli::Command::VerifyRaffle { options } => {
+            let verified = cli::verify_raffle(options).await?;
+            std::process::exit(if verified { 0 } else { 1 });
+        }
+    }
+
+    // Load and validate configuration (one entry per deployment)
+    let configs = config::AppConfig::load_all()?;
+    let bind_addr = configs[0].bind_addr.clone();
+    let error_format = configs[0].api_error_format;
+    let shutdown_grace = Duration::from_secs(configs[0].shutdown_grace_secs);
+
+    // Parse bind address
+    let addr: SocketAddr = bind_addr
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid BIND_ADDR: {}", e))?;
+
+    // Start every deployment's pool and background tasks, then mount its routes.
+    // A single unnamed deployment is served at /v1 and /v2; named ones at
+    // /v1/{deployment} and /v2/{deployment}.
+    let mut app = Router::<()>::new();
+    let mut deployments = Vec::with_capacity(configs.len());
+    let shutdown = CancellationToken::new();
+    let mut tasks = tasks::TaskManager::new(shutdown.clone());
+    let in_flight = drain::InFlight::default();
+    for config in configs {
+        let deployment = start_deployment(config, &mut tasks).await?;
+        app = app.merge(deployment_router(deployment.state.clone()));
+        deployments.push(deployment);
+    }
+    let app = app
+        .route("/health", get(health_check))
+        .route("/metrics", get(metrics::metrics_handler));
+    #[cfg(feature = "diagnostics")]
+    let app = app.route("/debug/runtime", get(diagnostics::runtime_stats));
+    let app = app
+        .layer(axum::middleware::from_fn(recovery::catch_panic))
+        .layer(axum::middleware::from_fn_with_state(
+            in_flight.clone(),
+            drain::track,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            error_format,
+            error::problem_details,
+        ));
+    let app = with_options(app);
+
+    // Start HTTP server
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!(%addr, "backend listening");
+
+    // Run server with graceful shutdown. On the signal the listener closes and indexers
+    // are told to stop, so they finish their batch while connections drain. A fatal
+    // background task failure shuts down the same way, then exits with its error.
+    let mut server = tokio::spawn(
+        // Peer addresses let the country screen look up clients without a proxy header
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .into_future(),
+    );
+    let mut fatal = None;
+    tokio::select! {
+        result = &mut server => {
+            // The server only stops by itself on an accept error
+            result??;
+        }
+        _ = shutdown_signal() => shutdown.cancel(),
+        err = tasks.fatal_error() => {
+            tracing::error!(error = %format!("{err:#}"), "background task failed, shutting down");
+            shutdown.cancel();
+            fatal = Some(err);
+        }
+    }
+
+    // Drain for at most SHUTDOWN_GRACE_SECONDS, then exit regardless
+    tracing::info!(grace_secs = shutdown_grace.as_secs(), "shutting down...");
+    let deadline = Instant::now() + shutdown_grace;
+    match tokio::time::timeout_at(deadline, &mut server).await {
+        Ok(result) => result??,
+        Err(_) => {
+            let abandoned = in_flight.snapshot();
+            tracing::warn!(
+                count = abandoned.len(),
+                requests = ?abandoned,
+                "grace period over, abandoning in-flight requests"
+            );
+            server.abort();
+        }
+    }
+    // Indexers get whatever is left of the grace period to finish their batch
+    tasks.shutdown(deadline).await;
+    for deployment in deployments {
+        deployment.state.db.close().await;
+        deployment.indexer_pool.close().await;
+    }
+    tracing::info!("shutdown complete");
+
+    match fatal {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Answers OPTIONS on every route of `app` (see [`api::allow_options`])
+///
+/// axum sets the `Allow` header of 405 responses outside of route layers, so the
+/// middleware wraps the whole router instead, as a fallback service.
+fn with_options(app: Router) -> Router {
+    Router::new()
+        .fallback_service(app)
+        .layer(axum::middleware::from_fn(api::allow_options))
+}
+
+/// The `/v1` and `/v2` routes of one deployment, with caching headers and API key
+/// enforcement
+///
+/// Requests beyond `API_MAX_CONCURRENT_REQUESTS`, or running longer than
+/// `API_REQUEST_TIMEOUT_MS`, are answered 503 right away, so a slow database sheds
+/// load instead of queueing connections until the pool and the listener stall. Both
+/// versions draw from the same limit.
+fn deployment_router(state: AppState) -> Router {
+    let deployment = state
+        .config
+        .deployment
+        .clone()
+        .unwrap_or_else(|| "default".to_string());
+    let permits = Arc::new(Semaphore::new(state.config.api_max_concurrent_requests));
+    let versioned = |routes: Router<AppState>| {
+        let deployment = deployment.clone();
+        let overload = ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(move |err: BoxError| {
+                let error = ApiError::from_middleware(err);
+                match error {
+                    ApiError::RequestTimeout => {
+                        metrics::record_rejected_request(&deployment, "timeout")
+                    }
+                    ApiError::Overloaded => {
+                        metrics::record_rejected_request(&deployment, "overloaded")
+                    }
+                    _ => {}
+                }
+                std::future::ready(error)
+            }))
+            .load_shed()
+            .layer(GlobalConcurrencyLimitLayer::with_semaphore(permits.clone()))
+            .timeout(Duration::from_millis(state.config.api_request_timeout_ms));
+
+        routes
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                api::cache_control,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                api_keys::enforce,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                admin_audit::record,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                geo::screen,
+            ))
+            .layer(overload)
+            .with_state(state.clone())
+    };
+
+    Router::new()
+        .nest(&state.config.route_prefix(), versioned(api::router()))
+        .nest(
+            &state.config.route_prefix_v2(),
+            // Outermost, so overload and timeout errors are enveloped too
+            versioned(api::v2_router()).layer(axum::middleware::from_fn(error::envelope)),
+        )
+}
+
+/// A running deployment: its API state and indexer pool
+struct Deployment {
+    state: AppState,
+    indexer_pool: PgPool,
+}
+
+/// Connects a deployment's database and spawns its background tasks on `tasks`
+///
+/// The indexer is fatal and stops cooperatively on shutdown; the other tasks are
+/// restarted after failures and aborted on shutdown.
+async fn start_deployment(
+    config: config::AppConfig,
+    tasks: &mut tasks::TaskManager,
+) -> anyhow::Result<Deployment> {
+    let name = config.deployment.as_deref().unwrap_or("default");
+    let span = tracing::info_span!("deployment", name);
+    let group = tasks::TaskGroup::new(name, span.clone());
+    tracing::info!(
+        parent: &span,
+        chain_id = config.chain_id,
+        start_block = config.start_block,
+        "configuration loaded"
+    );
+
+    // Separate pools so slow API queries can't starve the indexer. API connections
+    // carry a statement_timeout; indexer connections don't.
+    let db_pool = connect_pool(config.api_pg_connect_options()?, &config.api_pool).await?;
+    let indexer_pool = connect_pool(config.pg_connect_options()?, &config.indexer_pool).await?;
+
+    // Refuse to run against a schema that is behind (or ahead of) this binary
+    let schema = schema::inspect(&indexer_pool)
+        .await
+        .map_err(|err| anyhow::anyhow!("failed to read applied migrations: {err}"))?;
+    if !schema.is_compatible() {
+        match config.schema_check {
+            schema::SchemaCheck::Fail => anyhow::bail!(
+                "database schema is incompatible ({schema}); run `sqlx migrate run --source migrations` \
+                 or deploy the matching release"
+            ),
+            schema::SchemaCheck::Warn => {
+                tracing::warn!(parent: &span, %schema, "database schema is incompatible, starting anyway");
+            }
+        }
+    } else if !schema.modified.is_empty() {
+        tracing::warn!(parent: &span, %schema, "applied migrations differ from their files");
+    }
+
+    tracing::info!(
+        parent: &span,
+        statement_timeout_ms = config.api_statement_timeout_ms,
+        api_max_connections = config.api_pool.max_connections,
+        indexer_max_connections = config.indexer_pool.max_connections,
+        "database connection established"
+    );
+
+    let attestation_signer = attestation_signer(&config)?;
+    if let Some(signer) = &attestation_signer {
+        tracing::info!(parent: &span, signer = %format!("{:#x}", signer.address()), "attestation signing enabled");
+    }
+
+    // Request budget shared by everything calling RPC_URL
+    let rpc_budget = rpc::RpcBudget::from_config(&config);
+
+    // Contract reads for API handlers (on-chain fallbacks), on their own RPC budget and,
+    // when they use RPC_URL too, the shared one
+    let api_rpc_budget = if config.api_rpc_url == config.rpc_url {
+        rpc_budget.clone()
+    } else {
+        rpc::RpcBudget::unlimited(config.deployment.as_deref().unwrap_or("default"))
+    };
+    let chain = chain::ChainReader::new(
+        &config.api_rpc_url,
+        &config.raffle_factory_address,
+        config.api_rpc_rate_limit,
+        &api_rpc_budget,
+    )?;
+
+    // Optional mempool watcher for pending purchases
+    let pending = config.mempool_watcher_enabled.then(|| {
+        mempool::PendingPurchases::new(Duration::from_secs(config.pending_purchase_ttl_secs))
+    });
+    if let Some(store) = pending.clone() {
+        let db = indexer_pool.clone();
+        let (rpc_url, rpc_budget) = (config.rpc_url.clone(), rpc_budget.clone());
+        let poll_interval = Duration::from_millis(config.indexer_poll_interval_ms);
+        tasks.spawn(
+            &group,
+            "mempool",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| {
+                mempool::run(
+                    db.clone(),
+                    rpc_url.clone(),
+                    rpc_budget.clone(),
+                    poll_interval,
+                    store.clone(),
+                )
+            },
+        );
+    }
+
+    // Live updates from the indexer to WebSocket subscribers
+    let live = live::channel();
+    let indexer_status = indexer::IndexerStatus::new(circuit::CircuitBreaker::new(
+        config.deployment.as_deref().unwrap_or("default"),
+        config.rpc_circuit_failure_threshold,
+        Duration::from_secs(config.rpc_circuit_probe_interval_secs),
+    ));
+
+    // A pause survives restarts, so a redeploy mid-incident doesn't resume indexing
+    indexer_status.set_paused(indexer::load_paused(&indexer_pool).await?);
+    if indexer_status.is_paused() {
+        tracing::warn!(
+            parent: &span,
+            "indexer paused by an admin; POST /v1/admin/indexer/resume to continue"
+        );
+    }
+
+    // Cache for the raffle list, the busiest endpoint
+    let raffle_list_cache = (config.raffle_list_cache_ttl_ms > 0).then(|| {
+        cache::SwrCache::new(
+            Duration::from_millis(config.raffle_list_cache_ttl_ms),
+            Duration::from_secs(config.raffle_list_cache_max_stale_secs),
+        )
+    });
+
+    // Cache for embed widget payloads, which third-party pages poll
+    let embed_ttl = Duration::from_secs(config.embed_cache_ttl_secs);
+    let embed_cache = cache::SwrCache::new(embed_ttl, embed_ttl * api::EMBED_STALE_FACTOR);
+
+    // Cache for head block and gas prices, so frontend polling doesn't reach the RPC
+    let network_ttl = Duration::from_secs(config.network_cache_ttl_secs);
+    let network_cache = cache::SwrCache::new(network_ttl, network_ttl * api::NETWORK_STALE_FACTOR);
+
+    // Country screening, with the GeoLite2 database loaded up front
+    let geo = config
+        .geo
+        .as_ref()
+        .map(geo::GeoScreen::load)
+        .transpose()
+        .map_err(|err| anyhow::anyhow!("failed to set up country screening: {err:#}"))?
+        .map(Arc::new);
+
+    // Create shared application state
+    let state = AppState {
+        db: db_pool.clone(),
+        indexer_db: indexer_pool.clone(),
+        config: config.clone(),
+        chain,
+        attestation_signer,
+        pending,
+        live: live.clone(),
+        indexer: indexer_status.clone(),
+        raffle_list_cache,
+        embed_cache,
+        network_cache,
+        api_keys: api_keys::ApiKeyGuard::default(),
+        tasks: group.clone(),
+        geo,
+    };
+
+    // Optional keeper sending close/requestRandom/finalize for its raffles
+    if config.keeper_signer.is_some() {
+        let (db, config, rpc_budget) = (indexer_pool.clone(), config.clone(), rpc_budget.clone());
+        tasks.spawn(
+            &group,
+            "keeper",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| keeper::run(db.clone(), config.clone(), rpc_budget.clone()),
+        );
+    }
+
+    // Refund reminder delivery to subscriber webhooks
+    let (db, notify_config) = (indexer_pool.clone(), config.clone());
+    tasks.spawn(
+        &group,
+        "notify",
+        Restart::OnFailure,
+        Stop::Abort,
+        move |_| notify::run(db.clone(), notify_config.clone()),
+    );
+
+    // Live events written by the indexer, published to WebSocket subscribers
+    let (db, outbox_config, outbox_live) = (indexer_pool.clone(), config.clone(), live.clone());
+    tasks.spawn(
+        &group,
+        "outbox",
+        Restart::OnFailure,
+        Stop::Abort,
+        move |_| outbox::run(db.clone(), outbox_config.clone(), outbox_live.clone()),
+    );
+
+    // Daily digest compiled once each UTC day is indexed
+    let (db, digest_config, status) =
+        (indexer_pool.clone(), config.clone(), indexer_status.clone());
+    tasks.spawn(
+        &group,
+        "digest",
+        Restart::OnFailure,
+        Stop::Abort,
+        move |_| digest::run(db.clone(), digest_config.clone(), status.clone()),
+    );
+
+    // Participant cohorts recomputed from the indexed purchases
+    if config.cohort_refresh_interval_secs > 0 {
+        let (db, config) = (indexer_pool.clone(), config.clone());
+        tasks.spawn(
+            &group,
+            "cohorts",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| cohorts::run(db.clone(), config.clone()),
+        );
+    }
+
+    // Sybil clusters flagged on participant listings
+    if config.sybil_interval_secs > 0 {
+        let (db, config, rpc_budget) = (indexer_pool.clone(), config.clone(), rpc_budget.clone());
+        tasks.spawn(
+            &group,
+            "sybil",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| sybil::run(db.clone(), config.clone(), rpc_budget.clone()),
+        );
+    }
+
+    // Alerts on raffles stuck past their SLA
+    if config.stuck_webhook_url.is_some() {
+        let (db, stuck_config, status) =
+            (indexer_pool.clone(), config.clone(), indexer_status.clone());
+        tasks.spawn(
+            &group,
+            "stuck",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| stuck::run(db.clone(), stuck_config.clone(), status.clone()),
+        );
+    }
+
+    // Optional archival of completed raffles into the *_archive tables
+    if config.archive_after_days.is_some() {
+        let (db, config) = (indexer_pool.clone(), config.clone());
+        tasks.spawn(
+            &group,
+            "archive",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| archive::run(db.clone(), config.clone()),
+        );
+    }
+
+    // Purge of events from blocks that reorgs replaced
+    if config.orphan_check_blocks > 0 {
+        let (db, config, rpc_budget) = (indexer_pool.clone(), config.clone(), rpc_budget.clone());
+        tasks.spawn(
+            &group,
+            "orphans",
+            Restart::OnFailure,
+            Stop::Graceful,
+            move |shutdown| orphans::run(db.clone(), config.clone(), rpc_budget.clone(), shutdown),
+        );
+    }
+
+    // Confirmation of winner payouts from their transaction receipts
+    if config.payout_check_interval_secs > 0 {
+        let (db, config, rpc_budget) = (indexer_pool.clone(), config.clone(), rpc_budget.clone());
+        tasks.spawn(
+            &group,
+            "payouts",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| payouts::run(db.clone(), config.clone(), rpc_budget.clone()),
+        );
+    }
+
+    // Optional Parquet snapshots to object storage
+    if config.export.is_some() {
+        let (db, config) = (indexer_pool.clone(), config.clone());
+        tasks.spawn(
+            &group,
+            "export",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| export::run(db.clone(), config.clone()),
+        );
+    }
+
+    // The indexer: without it the deployment serves stale data, so failures are fatal
+    let db = indexer_pool.clone();
+    tasks.spawn(
+        &group,
+        "indexer",
+        Restart::Never,
+        Stop::Graceful,
+        move |shutdown| {
+            indexer::run(
+                db.clone(),
+                config.clone(),
+                indexer_status.clone(),
+                rpc_budget.clone(),
+                shutdown,
+            )
+        },
+    );
+
+    Ok(Deployment {
+        state,
+        indexer_pool,
+    })
+}
+
+/// Creates a database connection pool, failing after [`DB_CONNECT_TIMEOUT`]
+async fn connect_pool(options: PgConnectOptions, pool: &PoolConfig) -> anyhow::Result<PgPool> {
+    tokio::time::timeout(
+        DB_CONNECT_TIMEOUT,
+        PgPoolOptions::new()
+            .max_connections(pool.max_connections)
+            .min_connections(pool.min_connections)
+            .acquire_timeout(pool.acquire_timeout)
+            .max_lifetime(pool.max_lifetime)
+            .idle_timeout(pool.idle_timeout)
+            .connect_with(options),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("database connection timed out"))?
+    .map_err(|e| anyhow::anyhow!("failed to connect to database: {}", e))
+}
+
+/// Parses the optional attestation signing key, bound to the configured chain
+fn attestation_signer(config: &config::AppConfig) -> anyhow::Result<Option<LocalWallet>> {
+    config
+        .attestation_signing_key
+        .as_deref()
+        .map(|key| {
+            key.parse::<LocalWallet>()
+                .map(|wallet| wallet.with_chain_id(config.chain_id))
+                .map_err(|_| anyhow::anyhow!("ATTESTATION_SIGNING_KEY is not a valid private key"))
+        })
+        .transpose()
+}
+
+/// Health check endpoint
+///
+/// Returns 200 OK with JSON body `{"status": "ok"}`.
+/// Used by load balancers and monitoring systems.
+async fn health_check() -> impl IntoResponse {
+    let body = json!({ "status": "ok" });
+    (StatusCode::OK, Json(body))
+}
+
+/// Waits for shutdown signals (Ctrl+C or SIGTERM)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("shutdown signal received");
+}
@@ -0,0 +1,206 @@
+//! Live update stream over WebSocket
+//!
+//! The indexer writes a [`LiveEvent`] for every newly stored purchase and raffle
+//! status change to the outbox, which publishes it once committed (see
+//! [`crate::outbox`]). `GET /v1/ws` upgrades to a WebSocket where clients subscribe to
+//! specific raffle IDs (or all raffles) and receive matching events as JSON.
+//!
+//! Client messages:
+//! - `{"action": "subscribe", "raffle_ids": [1, 2]}` / `{"action": "subscribe", "all": true}`
+//! - `{"action": "unsubscribe", "raffle_ids": [1]}` / `{"action": "unsubscribe", "all": true}`
+//!
+//! # Security Considerations
+//! - Subscriptions per connection are capped at [`MAX_SUBSCRIPTIONS`]
+//! - Slow clients never block the indexer: the broadcast channel is bounded and a
+//!   lagging connection skips missed events and is told how many it lost
+//! - Oversized client messages are rejected
+
+use crate::state::AppState;
+use axum::{
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before it starts lagging
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+/// Maximum raffle IDs a single connection may subscribe to
+const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// Maximum size of a client message in bytes
+const MAX_MESSAGE_BYTES: usize = 4096;
+
+/// An indexed change pushed to WebSocket subscribers
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A ticket purchase was indexed
+    Purchase {
+        raffle_id: i64,
+        buyer: String,
+        start_index: i64,
+        end_index: i64,
+        count: i64,
+        amount: String,
+        /// Reached the whale purchase threshold (`WHALE_MIN_TICKETS` / `WHALE_MIN_AMOUNT`)
+        whale: bool,
+        tx_hash: String,
+        block_number: i64,
+    },
+    /// A raffle's on-chain status changed
+    Status {
+        raffle_id: i64,
+        status: String,
+        tx_hash: String,
+        block_number: i64,
+    },
+}
+
+impl LiveEvent {
+    fn raffle_id(&self) -> i64 {
+        match self {
+            LiveEvent::Purchase { raffle_id, .. } | LiveEvent::Status { raffle_id, .. } => {
+                *raffle_id
+            }
+        }
+    }
+}
+
+/// Creates the sender shared by the outbox dispatcher (publisher) and API (subscribers)
+pub fn channel() -> broadcast::Sender<LiveEvent> {
+    broadcast::channel(CHANNEL_CAPACITY).0
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    Subscribe,
+    Unsubscribe,
+}
+
+#[derive(Deserialize)]
+struct ClientMessage {
+    action: Action,
+    #[serde(default)]
+    raffle_ids: Vec<i64>,
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscriptions { all: bool, raffle_ids: Vec<i64> },
+    Lagged { skipped: u64 },
+    Error { message: &'a str },
+}
+
+/// Per-connection subscription state
+#[derive(Default)]
+struct Subscriptions {
+    all: bool,
+    raffle_ids: HashSet<i64>,
+}
+
+impl Subscriptions {
+    fn matches(&self, event: &LiveEvent) -> bool {
+        self.all || self.raffle_ids.contains(&event.raffle_id())
+    }
+
+    fn apply(&mut self, msg: ClientMessage) -> Result<(), &'static str> {
+        match msg.action {
+            Action::Subscribe => {
+                self.all |= msg.all;
+                let new_ids: HashSet<i64> = msg
+                    .raffle_ids
+                    .into_iter()
+                    .filter(|id| !self.raffle_ids.contains(id))
+                    .collect();
+                if self.raffle_ids.len() + new_ids.len() > MAX_SUBSCRIPTIONS {
+                    return Err("too many subscriptions");
+                }
+                self.raffle_ids.extend(new_ids);
+            }
+            Action::Unsubscribe => {
+                if msg.all {
+                    self.all = false;
+                }
+                for id in msg.raffle_ids {
+                    self.raffle_ids.remove(&id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot(&self) -> ServerMessage<'static> {
+        let mut raffle_ids: Vec<i64> = self.raffle_ids.iter().copied().collect();
+        raffle_ids.sort_unstable();
+        ServerMessage::Subscriptions {
+            all: self.all,
+            raffle_ids,
+        }
+    }
+}
+
+/// GET /v1/ws - Upgrade to a live update stream
+pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
+    let events = state.live.subscribe();
+    ws.max_message_size(MAX_MESSAGE_BYTES)
+        .on_upgrade(move |socket| handle_socket(socket, events))
+}
+
+async fn handle_socket(mut socket: WebSocket, mut events: broadcast::Receiver<LiveEvent>) {
+    let mut subscriptions = Subscriptions::default();
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let Some(Ok(message)) = incoming else {
+                    break;
+                };
+                let reply = match message {
+                    Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+                        Ok(msg) => match subscriptions.apply(msg) {
+                            Ok(()) => subscriptions.snapshot(),
+                            Err(message) => ServerMessage::Error { message },
+                        },
+                        Err(_) => ServerMessage::Error { message: "invalid message" },
+                    },
+                    Message::Close(_) => break,
+                    // Pings are answered automatically; binary frames are ignored
+                    _ => continue,
+                };
+                if send_json(&mut socket, &reply).await.is_err() {
+                    break;
+                }
+            }
+            event = events.recv() => {
+                let sent = match event {
+                    Ok(event) if subscriptions.matches(&event) => {
+                        send_json(&mut socket, &event).await
+                    }
+                    Ok(_) => Ok(()),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        send_json(&mut socket, &ServerMessage::Lagged { skipped }).await
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn send_json<T: Serialize>(socket: &mut WebSocket, value: &T) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(value).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
@@ -0,0 +1,211 @@
+//! Pending purchase watcher
+//!
+//! Optionally polls the RPC node's pending-transaction filter and decodes
+//! `buyTickets(uint32)` calls sent to known raffles. Matches are kept in memory
+//! so buyers can see their purchase while it waits for confirmation.
+//!
+//! Entries are never written to the database: they are unconfirmed, expire after
+//! a configurable TTL and are hidden by the API once the indexer stores the
+//! matching purchase.
+//!
+//! # Security Considerations
+//! - All RPC calls have timeouts to prevent hanging
+//! - The in-memory store is bounded to prevent memory exhaustion
+
+use crate::rpc::{RpcBudget, RpcProvider};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use ethers::providers::{Middleware, StreamExt};
+use ethers::types::{Address, H256, U256};
+use ethers::utils::id;
+use sqlx::{PgPool, Row};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Timeout for individual RPC calls
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the set of watched raffles is reloaded and expired entries purged
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Backoff when the pending-transaction filter fails (e.g. unsupported by the node)
+const ERROR_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Maximum number of pending purchases held in memory
+const MAX_PENDING: usize = 10_000;
+
+/// An unconfirmed `buyTickets` transaction seen in the mempool
+#[derive(Clone, Debug)]
+pub struct PendingPurchase {
+    pub tx_hash: String,
+    pub raffle_address: String,
+    pub buyer: String,
+    pub count: u32,
+    pub seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Shared in-memory store of pending purchases, keyed by transaction hash
+#[derive(Clone)]
+pub struct PendingPurchases {
+    entries: Arc<Mutex<HashMap<String, PendingPurchase>>>,
+    ttl: chrono::Duration,
+}
+
+impl PendingPurchases {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl: chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+        }
+    }
+
+    /// Returns unexpired pending purchases for a raffle, oldest first
+    pub fn for_raffle(&self, raffle_address: &str) -> Vec<PendingPurchase> {
+        let now = Utc::now();
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut pending: Vec<PendingPurchase> = entries
+            .values()
+            .filter(|p| p.expires_at > now && p.raffle_address.eq_ignore_ascii_case(raffle_address))
+            .cloned()
+            .collect();
+        pending.sort_by_key(|p| p.seen_at);
+        pending
+    }
+
+    fn insert(&self, purchase: PendingPurchase) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_PENDING && !entries.contains_key(&purchase.tx_hash) {
+            return;
+        }
+        entries.insert(purchase.tx_hash.clone(), purchase);
+    }
+
+    fn purge_expired(&self) {
+        let now = Utc::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, p| p.expires_at > now);
+    }
+}
+
+/// Runs the mempool watcher indefinitely
+///
+/// Failures of the pending-transaction filter are logged and retried after a
+/// backoff; the watcher never takes the rest of the process down.
+pub async fn run(
+    db_pool: PgPool,
+    rpc_url: String,
+    rpc_budget: RpcBudget,
+    poll_interval: Duration,
+    store: PendingPurchases,
+) -> anyhow::Result<()> {
+    let provider = rpc_budget
+        .provider(&rpc_url, "mempool")?
+        .interval(poll_interval);
+    tracing::info!("mempool watcher started");
+
+    loop {
+        if let Err(err) = watch(&db_pool, &provider, &store).await {
+            tracing::warn!(error = %err, "mempool watcher failed, retrying after backoff");
+            tokio::time::sleep(ERROR_BACKOFF).await;
+        }
+    }
+}
+
+/// Consumes the pending-transaction filter until it errors or ends
+async fn watch(
+    db_pool: &PgPool,
+    provider: &RpcProvider,
+    store: &PendingPurchases,
+) -> anyhow::Result<()> {
+    let selector: [u8; 4] = id("buyTickets(uint32)");
+    let mut raffles = load_active_raffles(db_pool).await?;
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+
+    let mut stream = tokio::time::timeout(RPC_TIMEOUT, provider.watch_pending_transactions())
+        .await
+        .context("pending filter creation timed out")?
+        .context("failed to create pending transaction filter")?;
+
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                raffles = load_active_raffles(db_pool).await?;
+                store.purge_expired();
+            }
+            next = stream.next() => {
+                let Some(tx_hash) = next else {
+                    anyhow::bail!("pending transaction stream ended");
+                };
+                if let Err(err) = inspect_transaction(provider, store, &raffles, selector, tx_hash).await {
+                    tracing::debug!(tx_hash = ?tx_hash, error = %err, "failed to inspect pending transaction");
+                }
+            }
+        }
+    }
+}
+
+/// Fetches a pending transaction and records it if it buys tickets in a known raffle
+async fn inspect_transaction(
+    provider: &RpcProvider,
+    store: &PendingPurchases,
+    raffles: &HashSet<Address>,
+    selector: [u8; 4],
+    tx_hash: H256,
+) -> anyhow::Result<()> {
+    let Some(tx) = tokio::time::timeout(RPC_TIMEOUT, provider.get_transaction(tx_hash))
+        .await
+        .context("get_transaction timed out")??
+    else {
+        return Ok(());
+    };
+
+    // Already mined: the indexer will pick it up
+    if tx.block_number.is_some() {
+        return Ok(());
+    }
+    let Some(to) = tx.to.filter(|to| raffles.contains(to)) else {
+        return Ok(());
+    };
+    // buyTickets(uint32 count): 4-byte selector + one ABI word
+    let input = tx.input.as_ref();
+    if input.len() != 36 || input[..4] != selector {
+        return Ok(());
+    }
+    let count = U256::from_big_endian(&input[4..36]);
+    if count.is_zero() || count > U256::from(u32::MAX) {
+        return Ok(());
+    }
+
+    let seen_at = Utc::now();
+    store.insert(PendingPurchase {
+        tx_hash: format!("{:#x}", tx_hash),
+        raffle_address: format!("{:#x}", to),
+        buyer: format!("{:#x}", tx.from),
+        count: count.as_u32(),
+        seen_at,
+        expires_at: seen_at + store.ttl,
+    });
+    Ok(())
+}
+
+/// Loads the addresses of raffles that can still accept purchases
+async fn load_active_raffles(pool: &PgPool) -> anyhow::Result<HashSet<Address>> {
+    let rows = sqlx::query("SELECT raffle_address FROM raffles WHERE status = 'ACTIVE'")
+        .fetch_all(pool)
+        .await
+        .context("failed to fetch active raffles")?;
+
+    let mut addresses = HashSet::with_capacity(rows.len());
+    for row in rows {
+        let address: String = row
+            .try_get("raffle_address")
+            .context("failed to read raffle_address")?;
+        if let Ok(address) = Address::from_str(&address) {
+            addresses.insert(address);
+        }
+    }
+    Ok(addresses)
+}
@@ -0,0 +1,337 @@
+//! Process metrics in Prometheus text format
+//!
+//! Served at `GET /metrics`. Metrics are process-wide and shared by every
+//! deployment; per-deployment series carry the deployment name as a label.
+//!
+//! # Slow queries
+//! sqlx logs statements slower than `SLOW_QUERY_THRESHOLD_MS` as `WARN` events on
+//! the `sqlx::query` target. [`SlowQueryLayer`] counts those events, labelled with
+//! the `query_source` of the innermost enclosing span: the matched route for API
+//! requests, or the indexer phase. Wrap code that issues queries in
+//! [`query_span`] to tag them.
+
+use crate::circuit::CircuitState;
+use axum::http::header;
+use axum::response::IntoResponse;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span field naming what issued the queries inside the span
+const QUERY_SOURCE_FIELD: &str = "query_source";
+
+/// Label used for slow queries issued outside any tagged span
+const UNKNOWN_SOURCE: &str = "unknown";
+
+/// Slow query counts by query source
+static SLOW_QUERIES: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// Current RPC circuit state by circuit (deployment) name
+static CIRCUIT_STATES: Mutex<BTreeMap<String, CircuitState>> = Mutex::new(BTreeMap::new());
+
+/// RPC circuit transition counts by circuit name and target state
+static CIRCUIT_TRANSITIONS: Mutex<BTreeMap<(String, &'static str), u64>> =
+    Mutex::new(BTreeMap::new());
+
+/// API requests answered 503 by the timeout or load-shedding layer, by deployment
+/// and reason
+static REJECTED_REQUESTS: Mutex<BTreeMap<(String, &'static str), u64>> =
+    Mutex::new(BTreeMap::new());
+
+/// API requests from restricted regions by deployment and action (block or watermark)
+static GEO_SCREENED: Mutex<BTreeMap<(String, &'static str), u64>> = Mutex::new(BTreeMap::new());
+
+/// Outbound RPC requests by deployment and caller
+static RPC_REQUESTS: Mutex<BTreeMap<(String, &'static str), RpcRequestCounts>> =
+    Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct RpcRequestCounts {
+    sent: u64,
+    /// Sent after waiting for the request budget
+    delayed: u64,
+    wait_seconds: f64,
+    /// Dropped because the request budget was exhausted
+    dropped: u64,
+}
+
+const CIRCUIT_STATE_VALUES: [CircuitState; 3] = [
+    CircuitState::Closed,
+    CircuitState::Open,
+    CircuitState::HalfOpen,
+];
+
+/// Creates a span tagging the queries issued inside it with `source`
+pub fn query_span(source: &str) -> tracing::Span {
+    tracing::info_span!("query", query_source = source)
+}
+
+/// GET /metrics - Prometheus text exposition
+pub async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(),
+    )
+}
+
+fn render() -> String {
+    let mut out = String::new();
+    out.push_str(
+        "# HELP backend_slow_queries_total SQL statements slower than SLOW_QUERY_THRESHOLD_MS\n",
+    );
+    out.push_str("# TYPE backend_slow_queries_total counter\n");
+    let slow_queries = SLOW_QUERIES.lock().unwrap_or_else(|e| e.into_inner());
+    for (source, count) in slow_queries.iter() {
+        let _ = writeln!(
+            out,
+            "backend_slow_queries_total{{source=\"{}\"}} {count}",
+            escape_label(source)
+        );
+    }
+    drop(slow_queries);
+
+    out.push_str(
+        "# HELP backend_rpc_circuit_state Current indexer RPC circuit state (1 = active)\n",
+    );
+    out.push_str("# TYPE backend_rpc_circuit_state gauge\n");
+    let circuit_states = CIRCUIT_STATES.lock().unwrap_or_else(|e| e.into_inner());
+    for (circuit, current) in circuit_states.iter() {
+        for state in CIRCUIT_STATE_VALUES {
+            let _ = writeln!(
+                out,
+                "backend_rpc_circuit_state{{circuit=\"{}\",state=\"{}\"}} {}",
+                escape_label(circuit),
+                state.as_str(),
+                u8::from(*current == state)
+            );
+        }
+    }
+    drop(circuit_states);
+
+    out.push_str(
+        "# HELP backend_rpc_circuit_transitions_total Indexer RPC circuit state transitions\n",
+    );
+    out.push_str("# TYPE backend_rpc_circuit_transitions_total counter\n");
+    let transitions = CIRCUIT_TRANSITIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    for ((circuit, to), count) in transitions.iter() {
+        let _ = writeln!(
+            out,
+            "backend_rpc_circuit_transitions_total{{circuit=\"{}\",to=\"{to}\"}} {count}",
+            escape_label(circuit)
+        );
+    }
+    drop(transitions);
+
+    out.push_str(
+        "# HELP backend_api_requests_rejected_total API requests rejected with 503 before completing\n",
+    );
+    out.push_str("# TYPE backend_api_requests_rejected_total counter\n");
+    let rejected = REJECTED_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    for ((deployment, reason), count) in rejected.iter() {
+        let _ = writeln!(
+            out,
+            "backend_api_requests_rejected_total{{deployment=\"{}\",reason=\"{reason}\"}} {count}",
+            escape_label(deployment)
+        );
+    }
+    drop(rejected);
+
+    out.push_str(
+        "# HELP backend_api_geo_screened_total API requests from restricted regions, blocked or watermarked\n",
+    );
+    out.push_str("# TYPE backend_api_geo_screened_total counter\n");
+    let screened = GEO_SCREENED.lock().unwrap_or_else(|e| e.into_inner());
+    for ((deployment, action), count) in screened.iter() {
+        let _ = writeln!(
+            out,
+            "backend_api_geo_screened_total{{deployment=\"{}\",action=\"{action}\"}} {count}",
+            escape_label(deployment)
+        );
+    }
+    drop(screened);
+
+    let rpc_requests = RPC_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    write_rpc_counter(
+        &mut out,
+        &rpc_requests,
+        "backend_rpc_requests_total",
+        "Outbound RPC requests sent",
+        |counts| counts.sent.to_string(),
+    );
+    write_rpc_counter(
+        &mut out,
+        &rpc_requests,
+        "backend_rpc_requests_delayed_total",
+        "Outbound RPC requests that waited for the RPC_RATE_LIMIT budget",
+        |counts| counts.delayed.to_string(),
+    );
+    write_rpc_counter(
+        &mut out,
+        &rpc_requests,
+        "backend_rpc_budget_wait_seconds_total",
+        "Time outbound RPC requests spent waiting for the RPC_RATE_LIMIT budget",
+        |counts| format!("{:.3}", counts.wait_seconds),
+    );
+    write_rpc_counter(
+        &mut out,
+        &rpc_requests,
+        "backend_rpc_requests_dropped_total",
+        "Outbound RPC requests dropped because the RPC_RATE_LIMIT budget was exhausted",
+        |counts| counts.dropped.to_string(),
+    );
+    out
+}
+
+/// Writes one counter of the outbound RPC request counts, labelled by deployment and caller
+fn write_rpc_counter(
+    out: &mut String,
+    requests: &BTreeMap<(String, &'static str), RpcRequestCounts>,
+    name: &str,
+    help: &str,
+    value: impl Fn(&RpcRequestCounts) -> String,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for ((deployment, caller), counts) in requests {
+        let _ = writeln!(
+            out,
+            "{name}{{deployment=\"{}\",caller=\"{caller}\"}} {}",
+            escape_label(deployment),
+            value(counts)
+        );
+    }
+}
+
+/// Records the current state of an RPC circuit
+pub fn set_circuit_state(circuit: &str, state: CircuitState) {
+    let mut states = CIRCUIT_STATES.lock().unwrap_or_else(|e| e.into_inner());
+    states.insert(circuit.to_string(), state);
+}
+
+/// Records an RPC circuit state transition
+pub fn record_circuit_transition(circuit: &str, to: CircuitState) {
+    set_circuit_state(circuit, to);
+    let mut transitions = CIRCUIT_TRANSITIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    *transitions
+        .entry((circuit.to_string(), to.as_str()))
+        .or_insert(0) += 1;
+}
+
+/// Records an API request rejected by the router's `timeout` or `overloaded` layer
+pub fn record_rejected_request(deployment: &str, reason: &'static str) {
+    let mut rejected = REJECTED_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    *rejected
+        .entry((deployment.to_string(), reason))
+        .or_insert(0) += 1;
+}
+
+/// Records an API request from a restricted region (see [`crate::geo`])
+pub fn record_geo_screened(deployment: &str, action: &'static str) {
+    let mut screened = GEO_SCREENED.lock().unwrap_or_else(|e| e.into_inner());
+    *screened
+        .entry((deployment.to_string(), action))
+        .or_insert(0) += 1;
+}
+
+/// Records an outbound RPC request sent after waiting `wait` for the request budget
+pub fn record_rpc_request(deployment: &str, caller: &'static str, wait: Duration) {
+    let mut requests = RPC_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    let counts = requests
+        .entry((deployment.to_string(), caller))
+        .or_default();
+    counts.sent += 1;
+    if !wait.is_zero() {
+        counts.delayed += 1;
+        counts.wait_seconds += wait.as_secs_f64();
+    }
+}
+
+/// Records an outbound RPC request dropped because the request budget was exhausted
+pub fn record_rpc_dropped(deployment: &str, caller: &'static str) {
+    let mut requests = RPC_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    requests
+        .entry((deployment.to_string(), caller))
+        .or_default()
+        .dropped += 1;
+}
+
+/// Escapes a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn record_slow_query(source: &str) {
+    let mut slow_queries = SLOW_QUERIES.lock().unwrap_or_else(|e| e.into_inner());
+    match slow_queries.get_mut(source) {
+        Some(count) => *count += 1,
+        None => {
+            slow_queries.insert(source.to_string(), 1);
+        }
+    }
+}
+
+/// Tracing layer counting sqlx slow-statement events by query source
+pub struct SlowQueryLayer;
+
+/// Span extension holding the span's `query_source` field
+struct QuerySource(String);
+
+impl<S> Layer<S> for SlowQueryLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = QuerySourceVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(source), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(QuerySource(source));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Only sqlx's slow-statement events carry a `slow_threshold` field
+        if metadata.target() != "sqlx::query" || metadata.fields().field("slow_threshold").is_none()
+        {
+            return;
+        }
+
+        let source = ctx.event_scope(event).and_then(|scope| {
+            scope.into_iter().find_map(|span| {
+                span.extensions()
+                    .get::<QuerySource>()
+                    .map(|source| source.0.clone())
+            })
+        });
+        record_slow_query(source.as_deref().unwrap_or(UNKNOWN_SOURCE));
+    }
+}
+
+struct QuerySourceVisitor(Option<String>);
+
+impl Visit for QuerySourceVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == QUERY_SOURCE_FIELD {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == QUERY_SOURCE_FIELD {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
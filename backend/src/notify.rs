@@ -0,0 +1,629 @@
+//! Webhook notifications
+//!
+//! Delivers notifications queued by the indexer to webhooks:
+//! - Refund reminders: the indexer queues one in `refund_reminders` for every
+//!   subscribed buyer when a raffle enters REFUNDING or is canceled; it is POSTed to
+//!   the wallet's webhook from `refund_reminder_subscriptions`
+//! - Whale purchases: the indexer queues one in `whale_alerts` for every purchase
+//!   reaching `WHALE_MIN_TICKETS` or `WHALE_MIN_AMOUNT`; it is POSTed to
+//!   `WHALE_WEBHOOK_URL`
+//! - Daily digests: the digest job queues one in `digest_deliveries` for every
+//!   digest it compiles (see [`crate::digest`]); it is POSTed to `DIGEST_WEBHOOK_URL`
+//! - Stuck raffles: the stuck job queues one in `stuck_alerts` for every raffle stuck
+//!   past the SLA of its status (see [`crate::stuck`]); it is POSTed to `STUCK_WEBHOOK_URL`
+//!
+//! # Delivery
+//! A 2xx response marks a notification delivered. Anything else is retried with
+//! exponential backoff (from 30 seconds, capped at an hour) until
+//! `WEBHOOK_MAX_ATTEMPTS` is reached, after which it is marked failed. A buyer who
+//! claimed their refund before delivery is skipped, as is a stuck alert for a raffle that
+//! has moved on since. Due rows are leased with
+//! `FOR UPDATE SKIP LOCKED`, so several replicas can run the notifier.
+//!
+//! Every attempt is recorded in `webhook_attempts` with the response status. Failed
+//! notifications stay failed until replayed through the admin API.
+//!
+//! With a signing secret, each request carries
+//! `X-Webhook-Signature: sha256=<hex HMAC-SHA256 of the body>`. The secret is
+//! `WEBHOOK_SIGNING_SECRET` until one is rotated in through the admin API (stored in
+//! `webhook_signing_secrets`). For `WEBHOOK_SECRET_OVERLAP_SECS` after a rotation the
+//! header carries a signature with the previous secret too:
+//! `sha256=<new>,sha256=<previous>`.
+//!
+//! # Security Considerations
+//! - Redirects are not followed, so a webhook can't bounce requests elsewhere
+//! - Response bodies are never read or logged
+//! - Webhook URLs must be https (checked when subscribing)
+
+use crate::config::AppConfig;
+use crate::digest::Digest;
+use crate::format;
+use anyhow::Context;
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::time::{Duration, Instant};
+
+/// Timeout for one webhook request
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notifications leased per queue and cycle
+const BATCH_SIZE: i64 = 50;
+
+/// Webhook requests in flight at once
+const DELIVERY_CONCURRENCY: usize = 8;
+
+/// How long a leased notification is hidden from other notifiers
+const LEASE_SECS: f64 = 300.0;
+
+/// First retry delay; doubled per failed attempt
+const RETRY_BASE: Duration = Duration::from_secs(30);
+
+/// Longest retry delay
+const RETRY_MAX: Duration = Duration::from_secs(60 * 60);
+
+/// Prefix of generated signing secrets
+const SIGNING_SECRET_PREFIX: &str = "whsec_";
+
+/// Random bytes in a generated signing secret
+const SIGNING_SECRET_BYTES: usize = 32;
+
+/// Body of a refund reminder webhook
+#[derive(Serialize)]
+struct RefundReminderPayload {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
+    chain_id: u64,
+    wallet: String,
+    raffle_id: i64,
+    raffle_address: String,
+    /// Refundable amount in token base units
+    refund_amount: String,
+    sent_at: String,
+}
+
+/// Body of a whale purchase webhook
+#[derive(Serialize)]
+struct WhalePurchasePayload {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
+    chain_id: u64,
+    raffle_id: i64,
+    raffle_address: String,
+    buyer: String,
+    tickets: i64,
+    /// Amount paid in token base units
+    amount: String,
+    /// Amount paid as a decimal string in `token_symbol`
+    amount_formatted: Option<String>,
+    token_symbol: String,
+    tx_hash: String,
+    block_number: i64,
+    sent_at: String,
+}
+
+/// Body of a daily digest webhook
+#[derive(Serialize)]
+struct DailyDigestPayload {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
+    chain_id: u64,
+    #[serde(flatten)]
+    digest: Digest,
+    sent_at: String,
+}
+
+/// Body of a stuck raffle webhook
+#[derive(Serialize)]
+struct RaffleStuckPayload {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deployment: Option<String>,
+    chain_id: u64,
+    raffle_id: i64,
+    raffle_address: String,
+    status: String,
+    keeper: Option<String>,
+    /// When the raffle reached `status`
+    since: String,
+    stuck_secs: i64,
+    sla_secs: u64,
+    sent_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Payload {
+    RefundReminder(RefundReminderPayload),
+    WhalePurchase(WhalePurchasePayload),
+    DailyDigest(DailyDigestPayload),
+    RaffleStuck(RaffleStuckPayload),
+}
+
+/// Queue table a notification was leased from
+#[derive(Clone, Copy, Debug)]
+enum Queue {
+    RefundReminders,
+    WhaleAlerts,
+    DigestDeliveries,
+    StuckAlerts,
+}
+
+impl Queue {
+    fn table(self) -> &'static str {
+        match self {
+            Queue::RefundReminders => "refund_reminders",
+            Queue::WhaleAlerts => "whale_alerts",
+            Queue::DigestDeliveries => "digest_deliveries",
+            Queue::StuckAlerts => "stuck_alerts",
+        }
+    }
+}
+
+/// A leased notification with everything needed to deliver it
+struct Due {
+    queue: Queue,
+    id: i64,
+    attempts: i32,
+    webhook_url: String,
+    /// No longer relevant (refund already claimed, raffle no longer stuck); marked skipped
+    /// without delivery
+    skip: bool,
+    payload: Payload,
+}
+
+/// Outcome of one delivery attempt
+struct Attempt {
+    /// HTTP status of the response, if there was one
+    response_status: Option<u16>,
+    duration: Duration,
+    /// `Err` holds a short description safe to store
+    result: Result<(), String>,
+}
+
+/// Generates a random webhook signing secret
+pub fn generate_signing_secret() -> anyhow::Result<String> {
+    let mut bytes = [0u8; SIGNING_SECRET_BYTES];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("failed to generate random signing secret"))?;
+    Ok(format!("{SIGNING_SECRET_PREFIX}{}", hex::encode(bytes)))
+}
+
+/// Runs the notifier loop until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig) -> anyhow::Result<()> {
+    let http = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .context("failed to build webhook HTTP client")?;
+    let poll_interval = Duration::from_secs(config.webhook_poll_interval_secs);
+
+    loop {
+        if let Err(err) = run_notify_cycle(&db, &http, &config).await {
+            tracing::warn!(error = %format!("{err:#}"), "notifier cycle failed");
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+async fn run_notify_cycle(
+    db: &PgPool,
+    http: &reqwest::Client,
+    config: &AppConfig,
+) -> anyhow::Result<()> {
+    let mut due = lease_refund_reminders(db, config).await?;
+    if let Some(webhook_url) = &config.whale_webhook_url {
+        due.extend(lease_whale_alerts(db, config, webhook_url).await?);
+    }
+    if let Some(webhook_url) = &config.digest_webhook_url {
+        due.extend(lease_digest_deliveries(db, config, webhook_url).await?);
+    }
+    if let Some(webhook_url) = &config.stuck_webhook_url {
+        due.extend(lease_stuck_alerts(db, config, webhook_url).await?);
+    }
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let secrets = signing_secrets(db, config).await?;
+    let secrets = &secrets;
+    let outcomes: Vec<(Due, Option<Attempt>)> = stream::iter(due)
+        .map(|notification| async move {
+            if notification.skip {
+                return (notification, None);
+            }
+            let attempt = deliver(http, secrets, &notification).await;
+            (notification, Some(attempt))
+        })
+        .buffer_unordered(DELIVERY_CONCURRENCY)
+        .collect()
+        .await;
+
+    for (notification, attempt) in outcomes {
+        record_outcome(db, config, &notification, attempt).await?;
+    }
+    Ok(())
+}
+
+/// Secrets to sign payloads with, newest first
+///
+/// The newest rotated secret replaces `WEBHOOK_SIGNING_SECRET`. Within
+/// `WEBHOOK_SECRET_OVERLAP_SECS` of a rotation, the secret it replaced is included.
+async fn signing_secrets(db: &PgPool, config: &AppConfig) -> anyhow::Result<Vec<String>> {
+    let rows = sqlx::query(
+        "SELECT secret, created_at > now() - make_interval(secs => $1) AS overlapping
+         FROM webhook_signing_secrets
+         ORDER BY id DESC
+         LIMIT 2",
+    )
+    .bind(config.webhook_secret_overlap_secs as f64)
+    .fetch_all(db)
+    .await
+    .context("failed to load webhook signing secrets")?;
+
+    let Some(newest) = rows.first() else {
+        return Ok(config.webhook_signing_secret.iter().cloned().collect());
+    };
+    let mut secrets = vec![newest.try_get::<String, _>("secret")?];
+    if newest.try_get("overlapping")? {
+        let previous = match rows.get(1) {
+            Some(row) => Some(row.try_get("secret")?),
+            None => config.webhook_signing_secret.clone(),
+        };
+        secrets.extend(previous);
+    }
+    Ok(secrets)
+}
+
+/// Leases due reminders by pushing their next attempt past the lease
+async fn lease_refund_reminders(db: &PgPool, config: &AppConfig) -> anyhow::Result<Vec<Due>> {
+    let rows = sqlx::query(
+        "WITH due AS (
+            SELECT id FROM refund_reminders
+            WHERE status = 'pending' AND next_attempt_at <= now()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE refund_reminders r
+        SET next_attempt_at = now() + make_interval(secs => $2)
+        FROM due, refund_reminder_subscriptions s, raffles ra
+        WHERE r.id = due.id AND s.wallet = r.wallet AND ra.raffle_id = r.raffle_id
+        RETURNING r.id, r.wallet, r.raffle_id, r.attempts, s.webhook_url, ra.raffle_address,
+            EXISTS (
+                SELECT 1 FROM refunds f WHERE f.raffle_id = r.raffle_id AND f.buyer = r.wallet
+            ) AS claimed,
+            (
+                SELECT COALESCE(SUM(p.amount), 0) FROM purchases p
+                WHERE p.raffle_id = r.raffle_id AND p.buyer = r.wallet
+            )::text AS refund_amount",
+    )
+    .bind(BATCH_SIZE)
+    .bind(LEASE_SECS)
+    .fetch_all(db)
+    .await
+    .context("failed to lease due refund reminders")?;
+
+    let sent_at = Utc::now().to_rfc3339();
+    rows.into_iter()
+        .map(|row| {
+            Ok(Due {
+                queue: Queue::RefundReminders,
+                id: row.try_get("id")?,
+                attempts: row.try_get("attempts")?,
+                webhook_url: row.try_get("webhook_url")?,
+                skip: row.try_get("claimed")?,
+                payload: Payload::RefundReminder(RefundReminderPayload {
+                    kind: "refund_available",
+                    deployment: config.deployment.clone(),
+                    chain_id: config.chain_id,
+                    wallet: row.try_get("wallet")?,
+                    raffle_id: row.try_get("raffle_id")?,
+                    raffle_address: row.try_get("raffle_address")?,
+                    refund_amount: row.try_get("refund_amount")?,
+                    sent_at: sent_at.clone(),
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Leases due whale alerts by pushing their next attempt past the lease
+async fn lease_whale_alerts(
+    db: &PgPool,
+    config: &AppConfig,
+    webhook_url: &str,
+) -> anyhow::Result<Vec<Due>> {
+    let rows = sqlx::query(
+        "WITH due AS (
+            SELECT id FROM whale_alerts
+            WHERE status = 'pending' AND next_attempt_at <= now()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE whale_alerts w
+        SET next_attempt_at = now() + make_interval(secs => $2)
+        FROM due, purchases p, raffles ra
+        WHERE w.id = due.id AND p.id = w.purchase_id AND ra.raffle_id = p.raffle_id
+        RETURNING w.id, w.attempts, p.raffle_id, ra.raffle_address, p.buyer,
+            p.count::bigint AS tickets, p.amount::text AS amount, p.tx_hash, p.block_number",
+    )
+    .bind(BATCH_SIZE)
+    .bind(LEASE_SECS)
+    .fetch_all(db)
+    .await
+    .context("failed to lease due whale alerts")?;
+
+    let sent_at = Utc::now().to_rfc3339();
+    rows.into_iter()
+        .map(|row| {
+            let amount: String = row.try_get("amount")?;
+            Ok(Due {
+                queue: Queue::WhaleAlerts,
+                id: row.try_get("id")?,
+                attempts: row.try_get("attempts")?,
+                webhook_url: webhook_url.to_string(),
+                skip: false,
+                payload: Payload::WhalePurchase(WhalePurchasePayload {
+                    kind: "whale_purchase",
+                    deployment: config.deployment.clone(),
+                    chain_id: config.chain_id,
+                    raffle_id: row.try_get("raffle_id")?,
+                    raffle_address: row.try_get("raffle_address")?,
+                    buyer: row.try_get("buyer")?,
+                    tickets: row.try_get("tickets")?,
+                    amount_formatted: format::format_units(&amount, config.token_decimals),
+                    amount,
+                    token_symbol: config.token_symbol.clone(),
+                    tx_hash: row.try_get("tx_hash")?,
+                    block_number: row.try_get("block_number")?,
+                    sent_at: sent_at.clone(),
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Leases due digest deliveries by pushing their next attempt past the lease
+async fn lease_digest_deliveries(
+    db: &PgPool,
+    config: &AppConfig,
+    webhook_url: &str,
+) -> anyhow::Result<Vec<Due>> {
+    let rows = sqlx::query(
+        "WITH due AS (
+            SELECT id FROM digest_deliveries
+            WHERE status = 'pending' AND next_attempt_at <= now()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE digest_deliveries d
+        SET next_attempt_at = now() + make_interval(secs => $2)
+        FROM due, digests g
+        WHERE d.id = due.id AND g.id = d.digest_id
+        RETURNING d.id, d.attempts, g.summary::text AS summary",
+    )
+    .bind(BATCH_SIZE)
+    .bind(LEASE_SECS)
+    .fetch_all(db)
+    .await
+    .context("failed to lease due digest deliveries")?;
+
+    let sent_at = Utc::now().to_rfc3339();
+    rows.into_iter()
+        .map(|row| {
+            let summary: String = row.try_get("summary")?;
+            Ok(Due {
+                queue: Queue::DigestDeliveries,
+                id: row.try_get("id")?,
+                attempts: row.try_get("attempts")?,
+                webhook_url: webhook_url.to_string(),
+                skip: false,
+                payload: Payload::DailyDigest(DailyDigestPayload {
+                    kind: "daily_digest",
+                    deployment: config.deployment.clone(),
+                    chain_id: config.chain_id,
+                    digest: serde_json::from_str(&summary).context("invalid stored digest")?,
+                    sent_at: sent_at.clone(),
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Leases due stuck alerts by pushing their next attempt past the lease
+async fn lease_stuck_alerts(
+    db: &PgPool,
+    config: &AppConfig,
+    webhook_url: &str,
+) -> anyhow::Result<Vec<Due>> {
+    let rows = sqlx::query(
+        "WITH due AS (
+            SELECT a.id, ra.raffle_address, ra.status, ra.keeper
+            FROM stuck_alerts a
+            LEFT JOIN raffles_all ra ON ra.raffle_id = a.raffle_id
+            WHERE a.status = 'pending' AND a.next_attempt_at <= now()
+            ORDER BY a.next_attempt_at
+            LIMIT $1
+            FOR UPDATE OF a SKIP LOCKED
+        )
+        UPDATE stuck_alerts s
+        SET next_attempt_at = now() + make_interval(secs => $2)
+        FROM due
+        WHERE s.id = due.id
+        RETURNING s.id, s.attempts, s.raffle_id, COALESCE(due.raffle_address, '') AS raffle_address,
+            s.raffle_status, due.status IS DISTINCT FROM s.raffle_status AS moved_on, due.keeper,
+            s.since, EXTRACT(EPOCH FROM now() - s.since)::bigint AS stuck_secs",
+    )
+    .bind(BATCH_SIZE)
+    .bind(LEASE_SECS)
+    .fetch_all(db)
+    .await
+    .context("failed to lease due stuck alerts")?;
+
+    let sent_at = Utc::now().to_rfc3339();
+    rows.into_iter()
+        .map(|row| {
+            let status: String = row.try_get("raffle_status")?;
+            let since: chrono::DateTime<Utc> = row.try_get("since")?;
+            let sla_secs = if status == "CLOSED" {
+                config.stuck_closed_sla_secs
+            } else {
+                config.stuck_random_requested_sla_secs
+            };
+            Ok(Due {
+                queue: Queue::StuckAlerts,
+                id: row.try_get("id")?,
+                attempts: row.try_get("attempts")?,
+                webhook_url: webhook_url.to_string(),
+                skip: row.try_get("moved_on")?,
+                payload: Payload::RaffleStuck(RaffleStuckPayload {
+                    kind: "raffle_stuck",
+                    deployment: config.deployment.clone(),
+                    chain_id: config.chain_id,
+                    raffle_id: row.try_get("raffle_id")?,
+                    raffle_address: row.try_get("raffle_address")?,
+                    status,
+                    keeper: row.try_get("keeper")?,
+                    since: since.to_rfc3339(),
+                    stuck_secs: row.try_get("stuck_secs")?,
+                    sla_secs,
+                    sent_at: sent_at.clone(),
+                }),
+            })
+        })
+        .collect()
+}
+
+/// POSTs one notification, signed with each of `secrets`
+async fn deliver(http: &reqwest::Client, secrets: &[String], notification: &Due) -> Attempt {
+    let started = Instant::now();
+    let body = match serde_json::to_vec(&notification.payload) {
+        Ok(body) => body,
+        Err(err) => {
+            return Attempt {
+                response_status: None,
+                duration: started.elapsed(),
+                result: Err(err.to_string()),
+            };
+        }
+    };
+    let mut request = http
+        .post(&notification.webhook_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if !secrets.is_empty() {
+        let signatures: Vec<String> = secrets
+            .iter()
+            .map(|secret| {
+                let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+                format!("sha256={}", hex::encode(hmac::sign(&key, &body)))
+            })
+            .collect();
+        request = request.header("X-Webhook-Signature", signatures.join(","));
+    }
+
+    let (response_status, result) = match request.body(body).send().await {
+        Ok(response) if response.status().is_success() => (Some(response.status()), Ok(())),
+        Ok(response) => (
+            Some(response.status()),
+            Err(format!("webhook responded with {}", response.status())),
+        ),
+        Err(err) if err.is_timeout() => (None, Err("webhook timed out".to_string())),
+        Err(_) => (None, Err("webhook unreachable".to_string())),
+    };
+    Attempt {
+        response_status: response_status.map(|status| status.as_u16()),
+        duration: started.elapsed(),
+        result,
+    }
+}
+
+async fn record_outcome(
+    db: &PgPool,
+    config: &AppConfig,
+    notification: &Due,
+    attempt: Option<Attempt>,
+) -> anyhow::Result<()> {
+    let table = notification.queue.table();
+    let attempts = notification.attempts + 1;
+    let Some(attempt) = attempt else {
+        sqlx::query(&format!(
+            "UPDATE {table} SET status = 'skipped' WHERE id = $1"
+        ))
+        .bind(notification.id)
+        .execute(db)
+        .await?;
+        return Ok(());
+    };
+
+    sqlx::query(
+        "INSERT INTO webhook_attempts
+            (queue, notification_id, attempt, response_status, error, duration_ms)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(table)
+    .bind(notification.id)
+    .bind(attempts)
+    .bind(attempt.response_status.map(|status| status as i16))
+    .bind(attempt.result.as_ref().err())
+    .bind(i32::try_from(attempt.duration.as_millis()).unwrap_or(i32::MAX))
+    .execute(db)
+    .await?;
+
+    match attempt.result {
+        Ok(()) => {
+            sqlx::query(&format!(
+                "UPDATE {table}
+                SET status = 'delivered', attempts = $2, delivered_at = now(), last_error = NULL
+                WHERE id = $1"
+            ))
+            .bind(notification.id)
+            .bind(attempts)
+            .execute(db)
+            .await?;
+        }
+        Err(error) => {
+            let exhausted = attempts as u32 >= config.webhook_max_attempts;
+            let delay = RETRY_BASE
+                .saturating_mul(1 << (attempts - 1).min(16))
+                .min(RETRY_MAX);
+            tracing::warn!(
+                queue = table,
+                id = notification.id,
+                attempts,
+                error = %error,
+                exhausted,
+                "webhook delivery failed"
+            );
+            sqlx::query(&format!(
+                "UPDATE {table}
+                SET status = CASE WHEN $3 THEN 'failed' ELSE 'pending' END,
+                    attempts = $2,
+                    last_error = $4,
+                    next_attempt_at = now() + make_interval(secs => $5)
+                WHERE id = $1"
+            ))
+            .bind(notification.id)
+            .bind(attempts)
+            .bind(exhausted)
+            .bind(&error)
+            .bind(delay.as_secs_f64())
+            .execute(db)
+            .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,277 @@
+//! Cleanup of events from orphaned blocks
+//!
+//! The indexer pins every stored log to its block hash but never looks back, so a log
+//! from a block that a reorg replaced stays indexed. Short of a full reorg rollback,
+//! this job compares the last `ORPHAN_CHECK_BLOCKS` indexed blocks with the node's
+//! canonical chain every `ORPHAN_CHECK_INTERVAL_SECS`. For blocks that are no longer
+//! canonical it, in one transaction:
+//!
+//! - deletes their purchases (with whale alerts), refunds, keeper updates, randomness
+//!   provider rows, status anomalies, ledger entries, status changes and payouts,
+//!   recomputes the totals, keeper and stage durations of the affected raffles and
+//!   clears the payout of those whose payout was orphaned (stamping them changed at the
+//!   first orphaned block, see `GET /v1/changes`),
+//! - deletes their live events that the outbox hasn't published yet,
+//! - deletes their `events_raw` rows, and
+//! - rewinds the indexer cursor to just before the oldest of them, so the logs of the
+//!   canonical blocks, including transactions re-included there, are indexed.
+//!
+//! Lifecycle events (creation, status transitions, the winner draw) changed the `raffles`
+//! row itself, which deleting rows can't undo. Re-indexing applies them again if they were
+//! re-included; if they weren't, the raffle stays stale and the job logs the transaction
+//! for an operator to rebuild from `events_raw` (`backup-raw`, then `restore-raw`).
+
+use crate::config::AppConfig;
+use crate::indexer::{self, ChainClient};
+use crate::rpc::RpcBudget;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Timeout for individual RPC calls
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs the orphan check until `shutdown` is cancelled
+///
+/// A purge in progress is finished first, so shutdown never leaves it half applied.
+pub async fn run(
+    db: PgPool,
+    config: AppConfig,
+    rpc_budget: RpcBudget,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    if config.orphan_check_blocks == 0 {
+        return Ok(());
+    }
+    let provider = rpc_budget
+        .alloy_provider(&config.rpc_url, "orphans")
+        .context("invalid RPC_URL")?;
+    let interval = Duration::from_secs(config.orphan_check_interval_secs);
+    while !shutdown.is_cancelled() {
+        if let Err(err) = check_recent_blocks(&db, &provider, config.orphan_check_blocks).await {
+            tracing::warn!(error = %format!("{err:#}"), "orphan check failed");
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+    Ok(())
+}
+
+/// Purges the events of the last `window` indexed blocks that are no longer canonical,
+/// returning how many such blocks were found
+pub(crate) async fn check_recent_blocks<C: ChainClient>(
+    db: &PgPool,
+    chain: &C,
+    window: u64,
+) -> anyhow::Result<usize> {
+    let cursor: i64 =
+        sqlx::query_scalar("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+            .fetch_one(db)
+            .await
+            .context("failed to fetch indexer state")?;
+
+    // Finalized blocks can't be orphaned
+    let indexed: Vec<(i64, String)> = sqlx::query_as(
+        "SELECT DISTINCT block_number, block_hash FROM events_raw
+         WHERE block_number > GREATEST($1, (SELECT finalized_block FROM indexer_state WHERE id = 1))
+           AND block_number <= $2 AND block_hash IS NOT NULL
+         ORDER BY block_number",
+    )
+    .bind(cursor.saturating_sub(window as i64))
+    .bind(cursor)
+    .fetch_all(db)
+    .await
+    .context("failed to fetch indexed blocks")?;
+
+    let mut orphaned = Vec::new();
+    for (number, hash) in indexed {
+        let canonical = tokio::time::timeout(RPC_TIMEOUT, chain.block_hash(number as u64))
+            .await
+            .context("get_block timed out")?
+            .with_context(|| format!("failed to fetch block {number}"))?;
+        // A node that doesn't know the block yet (lagging behind the indexer's) can't
+        // tell whether it is orphaned
+        if canonical.is_some_and(|canonical| format!("{canonical:#x}") != hash) {
+            orphaned.push((number, hash));
+        }
+    }
+    if !orphaned.is_empty() {
+        purge(db, &orphaned).await?;
+    }
+    Ok(orphaned.len())
+}
+
+/// Deletes everything indexed from `orphaned` blocks and rewinds the cursor before them
+async fn purge(db: &PgPool, orphaned: &[(i64, String)]) -> anyhow::Result<()> {
+    let numbers: Vec<i64> = orphaned.iter().map(|(number, _)| *number).collect();
+    let hashes: Vec<&str> = orphaned.iter().map(|(_, hash)| hash.as_str()).collect();
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+
+    // Decoded events without a row of their own changed a raffle directly
+    let lifecycle: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT e.tx_hash, e.block_number
+         FROM events_raw e
+         JOIN UNNEST($1::bigint[], $2::text[]) AS o (block_number, block_hash)
+           ON o.block_number = e.block_number AND o.block_hash = e.block_hash
+         WHERE e.decoded
+           AND NOT EXISTS (SELECT 1 FROM purchases t WHERE (t.tx_hash, t.log_index) = (e.tx_hash, e.log_index))
+           AND NOT EXISTS (SELECT 1 FROM refunds t WHERE (t.tx_hash, t.log_index) = (e.tx_hash, e.log_index))
+           AND NOT EXISTS (SELECT 1 FROM keeper_updates t WHERE (t.tx_hash, t.log_index) = (e.tx_hash, e.log_index))
+           AND NOT EXISTS (SELECT 1 FROM randomness_requests t WHERE (t.tx_hash, t.log_index) = (e.tx_hash, e.log_index))
+           AND NOT EXISTS (SELECT 1 FROM randomness_fulfillments t WHERE (t.tx_hash, t.log_index) = (e.tx_hash, e.log_index))
+         ORDER BY e.block_number, e.log_index",
+    )
+    .bind(&numbers)
+    .bind(&hashes)
+    .fetch_all(&mut *db_tx)
+    .await
+    .context("failed to find orphaned lifecycle events")?;
+
+    sqlx::query(
+        "DELETE FROM whale_alerts WHERE purchase_id IN (
+             SELECT p.id FROM purchases p
+             JOIN UNNEST($1::bigint[], $2::text[]) AS o (block_number, block_hash)
+               ON o.block_number = p.block_number AND o.block_hash = p.block_hash
+         )",
+    )
+    .bind(&numbers)
+    .bind(&hashes)
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to delete orphaned whale alerts")?;
+
+    // Published events are out; unpublished ones are withdrawn
+    sqlx::query(
+        "DELETE FROM outbox t
+         USING UNNEST($1::bigint[], $2::text[]) AS o (block_number, block_hash)
+         WHERE o.block_number = t.block_number AND o.block_hash = t.block_hash
+           AND t.published_at IS NULL",
+    )
+    .bind(&numbers)
+    .bind(&hashes)
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to delete orphaned outbox events")?;
+
+    let mut totals_changed = BTreeSet::new();
+    let mut keeper_changed = BTreeSet::new();
+    let mut stages_changed = BTreeSet::new();
+    let mut payouts_changed = BTreeSet::new();
+    let mut rows = 0;
+    for table in [
+        "purchases",
+        "refunds",
+        "keeper_updates",
+        "randomness_requests",
+        "randomness_fulfillments",
+        "anomalies",
+        "ledger",
+        "raffle_status_changes",
+        "payouts",
+    ] {
+        let raffle_ids: Vec<Option<i64>> = sqlx::query_scalar(&format!(
+            "DELETE FROM {table} t
+             USING UNNEST($1::bigint[], $2::text[]) AS o (block_number, block_hash)
+             WHERE o.block_number = t.block_number AND o.block_hash = t.block_hash
+             RETURNING t.raffle_id"
+        ))
+        .bind(&numbers)
+        .bind(&hashes)
+        .fetch_all(&mut *db_tx)
+        .await
+        .with_context(|| format!("failed to delete orphaned {table}"))?;
+        rows += raffle_ids.len();
+        match table {
+            "purchases" | "refunds" => totals_changed.extend(raffle_ids.into_iter().flatten()),
+            "keeper_updates" => keeper_changed.extend(raffle_ids.into_iter().flatten()),
+            "raffle_status_changes" => stages_changed.extend(raffle_ids.into_iter().flatten()),
+            "payouts" => payouts_changed.extend(raffle_ids.into_iter().flatten()),
+            _ => {}
+        }
+    }
+    // The raffle's payout columns came from the orphaned PayoutsCompleted; re-indexing
+    // sets them again if the transaction was re-included
+    sqlx::query(
+        "UPDATE raffles
+         SET prize_amount = NULL, fee_amount = NULL, payout_tx = NULL, updated_at = now()
+         WHERE raffle_id = ANY($1)",
+    )
+    .bind(payouts_changed.iter().copied().collect::<Vec<i64>>())
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to clear orphaned raffle payouts")?;
+    for raffle_id in &totals_changed {
+        indexer::recompute_raffle_totals(&mut db_tx, *raffle_id).await?;
+    }
+    for raffle_id in &keeper_changed {
+        indexer::recompute_raffle_keeper(&mut db_tx, *raffle_id).await?;
+    }
+    for raffle_id in &stages_changed {
+        indexer::recompute_stage_durations(&mut db_tx, *raffle_id).await?;
+    }
+
+    // Recomputed raffles changed again, as of the first block to be re-indexed
+    let rewind_to = numbers.iter().min().copied().unwrap_or_default() - 1;
+    let recomputed: Vec<i64> = totals_changed
+        .iter()
+        .chain(&keeper_changed)
+        .chain(&stages_changed)
+        .chain(&payouts_changed)
+        .copied()
+        .collect();
+    sqlx::query(
+        "UPDATE raffles SET updated_block = GREATEST(updated_block, $2) WHERE raffle_id = ANY($1)",
+    )
+    .bind(&recomputed)
+    .bind(rewind_to + 1)
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to stamp recomputed raffles")?;
+
+    let events = sqlx::query(
+        "DELETE FROM events_raw e
+         USING UNNEST($1::bigint[], $2::text[]) AS o (block_number, block_hash)
+         WHERE o.block_number = e.block_number AND o.block_hash = e.block_hash",
+    )
+    .bind(&numbers)
+    .bind(&hashes)
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to delete orphaned raw events")?
+    .rows_affected();
+
+    sqlx::query(
+        "UPDATE indexer_state
+         SET last_processed_block = LEAST(last_processed_block, $1), updated_at = now()
+         WHERE id = 1",
+    )
+    .bind(rewind_to)
+    .execute(&mut *db_tx)
+    .await
+    .context("failed to rewind indexer cursor")?;
+
+    db_tx
+        .commit()
+        .await
+        .context("failed to commit transaction")?;
+
+    tracing::warn!(
+        blocks = ?numbers,
+        events,
+        rows,
+        rewind_to,
+        "purged events of orphaned blocks"
+    );
+    for (tx_hash, block_number) in lifecycle {
+        tracing::error!(
+            tx_hash,
+            block_number,
+            "orphaned lifecycle event; its raffle is stale unless the transaction was re-included"
+        );
+    }
+    Ok(())
+}
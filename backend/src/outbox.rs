@@ -0,0 +1,153 @@
+//! Transactional outbox for live events
+//!
+//! The indexer doesn't publish live events itself: [`enqueue`] writes each one to the
+//! `outbox` table in the transaction that stores the rows it describes, so an event
+//! exists exactly when its rows were committed. A crash between commit and publish
+//! can't lose it, and a failed transaction can't announce rows that were rolled back.
+//!
+//! The dispatcher drains the table every `OUTBOX_POLL_INTERVAL_MS`: unpublished rows
+//! are leased in id order with `FOR UPDATE SKIP LOCKED`, sent to the live channel
+//! (see [`crate::live`]) and stamped `published_at`. Delivery is at-least-once: if the
+//! stamp fails to commit, the rows are sent again on the next cycle. The orphan check
+//! deletes unpublished events of blocks that a reorg replaced, and published rows are
+//! deleted after [`RETENTION`].
+//!
+//! Webhook notifications have their own queues written the same way
+//! (`refund_reminders`, `whale_alerts`, `digest_deliveries`, `stuck_alerts`; see
+//! [`crate::notify`]).
+
+use crate::config::AppConfig;
+use crate::live::LiveEvent;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Topic of events for WebSocket subscribers
+const TOPIC_LIVE: &str = "live";
+
+/// Events leased per cycle
+const BATCH_SIZE: i64 = 500;
+
+/// How long published events are kept
+const RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often published events past [`RETENTION`] are deleted
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Writes `event` to the outbox as part of `db_tx`
+///
+/// `block_number` and `block_hash` are those of the log the event was indexed from,
+/// so the orphan check can withdraw it.
+pub async fn enqueue(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    event: &LiveEvent,
+    block_number: i64,
+    block_hash: Option<&str>,
+) -> anyhow::Result<()> {
+    let payload = serde_json::to_string(event).context("failed to serialize live event")?;
+    sqlx::query(
+        "INSERT INTO outbox (topic, payload, block_number, block_hash)
+         VALUES ($1, $2::text::jsonb, $3, $4)",
+    )
+    .bind(TOPIC_LIVE)
+    .bind(payload)
+    .bind(block_number)
+    .bind(block_hash)
+    .execute(&mut **db_tx)
+    .await
+    .context("failed to enqueue live event")?;
+    Ok(())
+}
+
+/// Runs the outbox dispatcher until the task is aborted
+pub async fn run(
+    db: PgPool,
+    config: AppConfig,
+    live: broadcast::Sender<LiveEvent>,
+) -> anyhow::Result<()> {
+    let interval = Duration::from_millis(config.outbox_poll_interval_ms);
+    let mut last_prune: Option<Instant> = None;
+    loop {
+        let published = match publish_pending(&db, &live).await {
+            Ok(published) => published,
+            Err(err) => {
+                tracing::warn!(error = %format!("{err:#}"), "outbox dispatch failed");
+                0
+            }
+        };
+
+        if last_prune.is_none_or(|at| at.elapsed() >= PRUNE_INTERVAL) {
+            last_prune = Some(Instant::now());
+            if let Err(err) = prune(&db).await {
+                tracing::warn!(error = %format!("{err:#}"), "outbox prune failed");
+            }
+        }
+
+        // A full batch means more are waiting
+        if published < BATCH_SIZE as usize {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Publishes the oldest unpublished events, returning how many were leased
+pub(crate) async fn publish_pending(
+    db: &PgPool,
+    live: &broadcast::Sender<LiveEvent>,
+) -> anyhow::Result<usize> {
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+    let rows: Vec<(i64, String, String)> = sqlx::query_as(
+        "SELECT id, topic, payload::text FROM outbox
+         WHERE published_at IS NULL
+         ORDER BY id
+         LIMIT $1
+         FOR UPDATE SKIP LOCKED",
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *db_tx)
+    .await
+    .context("failed to lease outbox events")?;
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let mut ids = Vec::with_capacity(rows.len());
+    for (id, topic, payload) in rows {
+        ids.push(id);
+        // Undecodable events are stamped too, so they can't block the ones after them
+        match topic.as_str() {
+            TOPIC_LIVE => match serde_json::from_str::<LiveEvent>(&payload) {
+                // Errors only mean there are no subscribers right now
+                Ok(event) => {
+                    let _ = live.send(event);
+                }
+                Err(err) => {
+                    tracing::warn!(id, error = %err, "dropping undecodable outbox event");
+                }
+            },
+            _ => tracing::warn!(id, topic, "dropping outbox event with unknown topic"),
+        }
+    }
+
+    sqlx::query("UPDATE outbox SET published_at = now() WHERE id = ANY($1)")
+        .bind(&ids)
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to mark outbox events published")?;
+    db_tx
+        .commit()
+        .await
+        .context("failed to commit transaction")?;
+    Ok(ids.len())
+}
+
+/// Deletes published events older than [`RETENTION`]
+async fn prune(db: &PgPool) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM outbox WHERE published_at < now() - make_interval(secs => $1)")
+        .bind(RETENTION.as_secs_f64())
+        .execute(db)
+        .await
+        .context("failed to delete published outbox events")?;
+    Ok(())
+}
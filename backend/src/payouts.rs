@@ -0,0 +1,136 @@
+//! Confirmation of winner payouts
+//!
+//! `WinnerSelected` and `PayoutsCompleted` say a raffle paid out, but not whether the
+//! tokens arrived. Every `PAYOUT_CHECK_INTERVAL_SECS` this job reads the receipt of
+//! each indexed payout transaction not checked yet and records in `payouts.confirmed`
+//! whether it succeeded with the token transfers `finalize()` makes: the prize from
+//! the raffle to the winner and the fee to the fee recipient (amounts of 0 aren't
+//! transferred). Raffle details show the result as `payout_confirmed`.
+//!
+//! A receipt the node doesn't know (yet, or anymore after a reorg) is retried on the
+//! next run.
+
+use crate::config::AppConfig;
+use crate::indexer::{ChainClient, TxReceipt};
+use crate::rpc::RpcBudget;
+use alloy::primitives::{Address, B256, U256, keccak256};
+use anyhow::Context;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Timeout for individual RPC calls
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Payouts checked per run
+const BATCH_SIZE: i64 = 100;
+
+/// Runs the payout check until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig, rpc_budget: RpcBudget) -> anyhow::Result<()> {
+    let provider = rpc_budget
+        .alloy_provider(&config.rpc_url, "payouts")
+        .context("invalid RPC_URL")?;
+    let interval = Duration::from_secs(config.payout_check_interval_secs);
+    loop {
+        match confirm_payouts(&db, &provider).await {
+            Ok(0) => {}
+            Ok(checked) => tracing::info!(checked, "payouts checked"),
+            Err(err) => tracing::warn!(error = %format!("{err:#}"), "payout check failed"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Checks the receipts of unchecked payouts, returning how many were settled
+pub(crate) async fn confirm_payouts<C: ChainClient>(
+    db: &PgPool,
+    chain: &C,
+) -> anyhow::Result<usize> {
+    let rows = sqlx::query(
+        "SELECT p.raffle_id, p.tx_hash, p.winner, p.fee_recipient,
+            p.prize_amount::text AS prize_amount, p.fee_amount::text AS fee_amount,
+            r.raffle_address
+         FROM payouts p
+         JOIN raffles_all r ON r.raffle_id = p.raffle_id
+         WHERE p.confirmed IS NULL
+         ORDER BY p.block_number
+         LIMIT $1",
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(db)
+    .await
+    .context("failed to fetch unchecked payouts")?;
+
+    let mut checked = 0;
+    for row in rows {
+        let raffle_id: i64 = row.try_get("raffle_id")?;
+        let tx_hash: String = row.try_get("tx_hash")?;
+        let receipt = tokio::time::timeout(
+            RPC_TIMEOUT,
+            chain.transaction_receipt(B256::from_str(&tx_hash).context("invalid payout tx hash")?),
+        )
+        .await
+        .context("get_transaction_receipt timed out")?
+        .with_context(|| format!("failed to fetch receipt of {tx_hash}"))?;
+        let Some(receipt) = receipt else {
+            continue;
+        };
+
+        let address = |column: &str| -> anyhow::Result<Address> {
+            let value: String = row.try_get(column)?;
+            Address::from_str(&value).with_context(|| format!("invalid {column}"))
+        };
+        let amount = |column: &str| -> anyhow::Result<U256> {
+            let value: String = row.try_get(column)?;
+            U256::from_str(&value).with_context(|| format!("invalid {column}"))
+        };
+        let mut expected: HashMap<Address, U256> = HashMap::new();
+        for (recipient, value) in [
+            (address("winner")?, amount("prize_amount")?),
+            (address("fee_recipient")?, amount("fee_amount")?),
+        ] {
+            if !value.is_zero() {
+                *expected.entry(recipient).or_default() += value;
+            }
+        }
+        let confirmed =
+            receipt.success && transfers_from(&receipt, address("raffle_address")?) == expected;
+        if !confirmed {
+            tracing::warn!(
+                raffle_id,
+                tx_hash,
+                success = receipt.success,
+                "payout transaction lacks the expected transfers"
+            );
+        }
+
+        sqlx::query("UPDATE payouts SET confirmed = $2, confirmed_at = now() WHERE raffle_id = $1")
+            .bind(raffle_id)
+            .bind(confirmed)
+            .execute(db)
+            .await
+            .context("failed to record payout confirmation")?;
+        checked += 1;
+    }
+    Ok(checked)
+}
+
+/// Sums the ERC-20 `Transfer` amounts out of `sender` in a receipt, by recipient
+fn transfers_from(receipt: &TxReceipt, sender: Address) -> HashMap<Address, U256> {
+    let transfer = keccak256("Transfer(address,address,uint256)");
+    let mut transfers: HashMap<Address, U256> = HashMap::new();
+    for log_entry in &receipt.logs {
+        let [topic0, from, to] = log_entry.topics() else {
+            continue;
+        };
+        if *topic0 != transfer || Address::from_word(*from) != sender {
+            continue;
+        }
+        let Some(value) = U256::try_from_be_slice(&log_entry.data().data) else {
+            continue;
+        };
+        *transfers.entry(Address::from_word(*to)).or_default() += value;
+    }
+    transfers
+}
@@ -0,0 +1,102 @@
+//! Request IDs and panic recovery
+//!
+//! [`catch_panic`] wraps every HTTP request. It tags the request with an ID (the
+//! client's `X-Request-Id` when usable, otherwise a random one), runs it inside a
+//! `request` span carrying that ID and echoes the ID in the `X-Request-Id` response
+//! header, so a client report can be matched to the server's log lines.
+//!
+//! A handler that panics would otherwise abort the connection without a response.
+//! Here the panic is logged with the request's method, path and ID, and the client
+//! gets the standard error body: `500` with code `INTERNAL` and the ID in `details`.
+
+use crate::error::ApiError;
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures::FutureExt;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::Instrument;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied request ID that is kept; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Random bytes in a generated request ID
+const REQUEST_ID_BYTES: usize = 8;
+
+/// Fallback IDs for the (unexpected) case that the system RNG fails
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Middleware assigning request IDs and turning handler panics into `500` responses
+pub async fn catch_panic(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(generate_request_id);
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let span = tracing::info_span!("request", request_id = %request_id);
+
+    let result = AssertUnwindSafe(next.run(request))
+        .catch_unwind()
+        .instrument(span.clone())
+        .await;
+    let mut response = match result {
+        Ok(response) => response,
+        Err(panic) => {
+            span.in_scope(|| {
+                tracing::error!(
+                    %method,
+                    path,
+                    panic = panic_message(panic.as_ref()),
+                    "request handler panicked"
+                );
+            });
+            ApiError::Panicked {
+                request_id: request_id.clone(),
+            }
+            .into_response()
+        }
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Client IDs are kept if short and made of characters that are safe in logs
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+fn generate_request_id() -> String {
+    let mut bytes = [0u8; REQUEST_ID_BYTES];
+    match SystemRandom::new().fill(&mut bytes) {
+        Ok(()) => hex::encode(bytes),
+        Err(_) => format!("local-{}", NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)),
+    }
+}
+
+/// The message passed to `panic!`, if it was a string
+pub fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "non-string panic payload"
+    }
+}
@@ -0,0 +1,131 @@
+//! Referral codes
+//!
+//! Creators register codes (`POST /v1/referral-codes`, signed by the creator's wallet)
+//! and front ends append one to the calldata of `buyTickets` transactions. Solidity
+//! ignores bytes after the ABI-encoded arguments, so attribution needs no contract
+//! change. The suffix is the code in ASCII, one byte with its length, and the marker
+//! [`SUFFIX_MARKER`] (`REF1`):
+//!
+//! ```text
+//! <abi-encoded buyTickets call> <code> <uint8 code length> "REF1"
+//! ```
+//!
+//! With `REFERRAL_TRACKING` set, the indexer fetches the calldata of every
+//! transaction with a `TicketsBought` event ([`record_batch`]) and stores decoded codes
+//! in `purchase_referrals`. Stats (`GET /v1/referral-codes`) only count purchases in
+//! raffles created by the code's owner, so a code can't claim other creators' sales.
+
+use crate::indexer::ChainClient;
+use alloy::primitives::B256;
+use alloy::rpc::types::Log;
+use anyhow::Context;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Marks a calldata suffix carrying a referral code
+pub const SUFFIX_MARKER: &[u8; 4] = b"REF1";
+
+/// Bounds on the length of a code
+pub const MIN_CODE_LEN: usize = 3;
+pub const MAX_CODE_LEN: usize = 32;
+
+/// Timeout for individual RPC calls
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Transactions whose calldata is fetched at once
+const INPUT_CONCURRENCY: usize = 8;
+
+/// Normalizes a code to upper case, `None` if it isn't 3-32 of `A-Z`, `0-9`, `_`, `-`
+pub fn normalize_code(code: &str) -> Option<String> {
+    let valid = (MIN_CODE_LEN..=MAX_CODE_LEN).contains(&code.len())
+        && code
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-');
+    valid.then(|| code.to_ascii_uppercase())
+}
+
+/// Decodes the referral code appended to a transaction's calldata, if any
+pub fn decode_suffix(input: &[u8]) -> Option<String> {
+    let rest = input.strip_suffix(SUFFIX_MARKER)?;
+    let (&len, rest) = rest.split_last()?;
+    let code = rest.get(rest.len().checked_sub(len as usize)?..)?;
+    normalize_code(std::str::from_utf8(code).ok()?)
+}
+
+/// Records the referral codes of the transactions behind `logs`' purchases
+///
+/// `purchase_topic` is the `TicketsBought` signature. Transactions are looked up
+/// concurrently; a failed lookup fails the batch, so it is retried with it.
+pub(crate) async fn record_batch<C: ChainClient>(
+    db: &PgPool,
+    chain: &C,
+    logs: &[Log],
+    purchase_topic: B256,
+) -> anyhow::Result<usize> {
+    let mut purchases: Vec<(B256, u64)> = logs
+        .iter()
+        .filter(|log_entry| log_entry.topics().first() == Some(&purchase_topic))
+        .filter_map(|log_entry| Some((log_entry.transaction_hash?, log_entry.block_number?)))
+        .collect();
+    purchases.sort_unstable();
+    purchases.dedup_by_key(|(tx_hash, _)| *tx_hash);
+    if purchases.is_empty() {
+        return Ok(0);
+    }
+
+    let referrals: Vec<(B256, u64, String)> = stream::iter(purchases)
+        .map(|(tx_hash, block_number)| async move {
+            let input = tokio::time::timeout(RPC_TIMEOUT, chain.transaction_input(tx_hash))
+                .await
+                .context("get_transaction_by_hash timed out")?
+                .with_context(|| format!("failed to fetch transaction {tx_hash:#x}"))?;
+            let code = input.and_then(|input| decode_suffix(&input));
+            anyhow::Ok(code.map(|code| (tx_hash, block_number, code)))
+        })
+        .buffer_unordered(INPUT_CONCURRENCY)
+        .try_filter_map(|referral| async move { Ok(referral) })
+        .try_collect()
+        .await?;
+
+    for (tx_hash, block_number, code) in &referrals {
+        sqlx::query(
+            "INSERT INTO purchase_referrals (tx_hash, code, block_number) VALUES ($1, $2, $3)
+             ON CONFLICT (tx_hash) DO UPDATE
+             SET code = EXCLUDED.code, block_number = EXCLUDED.block_number",
+        )
+        .bind(format!("{tx_hash:#x}"))
+        .bind(code)
+        .bind(*block_number as i64)
+        .execute(db)
+        .await
+        .context("failed to store purchase referral")?;
+    }
+    Ok(referrals.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_suffix(call: &[u8], code: &str) -> Vec<u8> {
+        let mut input = call.to_vec();
+        input.extend_from_slice(code.as_bytes());
+        input.push(code.len() as u8);
+        input.extend_from_slice(SUFFIX_MARKER);
+        input
+    }
+
+    #[test]
+    fn decodes_codes_after_the_call() {
+        let call = [0xab; 68];
+        assert_eq!(
+            decode_suffix(&with_suffix(&call, "alice_01")).as_deref(),
+            Some("ALICE_01")
+        );
+        assert_eq!(decode_suffix(&call), None);
+        assert_eq!(decode_suffix(&with_suffix(&call, "a!")), None);
+        // A length pointing before the start of the calldata
+        assert_eq!(decode_suffix(&[3, b'R', b'E', b'F', b'1']), None);
+    }
+}
@@ -0,0 +1,380 @@
+//! Shared request budget for outbound RPC calls
+//!
+//! A deployment talks to `RPC_URL` from several places at once: the indexer, the
+//! orphan check, the keeper, the mempool watcher and API on-chain fallbacks. Each used
+//! to pace itself, so their bursts added up past the provider's plan limit and came
+//! back as storms of 429s. Now every JSON-RPC request they send goes through one
+//! [`RpcBudget`] per deployment, a token bucket refilled at `RPC_RATE_LIMIT` requests
+//! per second (0 disables it).
+//!
+//! When the bucket is empty, `RPC_RATE_LIMIT_MODE` decides what happens to a request:
+//! - `queue` (default): it waits for its turn. Waiting counts against the caller's own
+//!   RPC timeout, so a long queue shows up as timeouts, not as hanging tasks. A request
+//!   whose caller gives up while it waits hands its turn back, so timed-out requests
+//!   don't leave the bucket in debt.
+//! - `drop`: it fails at once with [`RpcClientError::BudgetExhausted`] and the caller
+//!   retries on its own schedule (the indexer backs off, API lookups answer 503).
+//!
+//! Requests sent, delayed and dropped are counted per caller on `GET /metrics`.
+//!
+//! The budget wraps both RPC stacks while the chain layer moves from ethers-rs to
+//! alloy: [`RpcBudget::provider`] builds an ethers provider (keeper, mempool watcher,
+//! API reads) and [`RpcBudget::alloy_provider`] an alloy one (indexer, orphan check).
+
+use crate::config::AppConfig;
+use crate::metrics;
+use alloy::providers::RootProvider;
+use alloy::rpc::client::ClientBuilder;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{TransportError, TransportErrorKind, TransportFut};
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpClientError, JsonRpcClient, JsonRpcError, Provider, ProviderError, RpcError,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
+use tower::{Layer, Service};
+
+/// ethers provider whose requests draw on an [`RpcBudget`]
+pub type RpcProvider = Provider<BudgetedHttp>;
+
+/// alloy provider whose requests draw on an [`RpcBudget`]
+pub type AlloyProvider = RootProvider;
+
+/// What happens to a request when the budget is exhausted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RpcBudgetMode {
+    /// Wait until the bucket refills
+    #[default]
+    Queue,
+    /// Fail immediately
+    Drop,
+}
+
+/// Token bucket shared by every RPC client of a deployment
+#[derive(Clone, Debug)]
+pub struct RpcBudget {
+    deployment: Arc<str>,
+    /// `None` when unlimited
+    bucket: Option<Arc<Bucket>>,
+    mode: RpcBudgetMode,
+}
+
+impl RpcBudget {
+    /// A budget of `per_second` requests per second; 0 means unlimited
+    pub fn new(deployment: &str, per_second: u32, mode: RpcBudgetMode) -> Self {
+        Self {
+            deployment: deployment.into(),
+            bucket: (per_second > 0).then(|| Arc::new(Bucket::new(per_second))),
+            mode,
+        }
+    }
+
+    /// The `RPC_RATE_LIMIT` budget of a deployment
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::new(
+            config.deployment.as_deref().unwrap_or("default"),
+            config.rpc_rate_limit,
+            config.rpc_rate_limit_mode,
+        )
+    }
+
+    /// A budget that only counts requests
+    pub fn unlimited(deployment: &str) -> Self {
+        Self::new(deployment, 0, RpcBudgetMode::Queue)
+    }
+
+    /// Creates a provider for `url` whose requests are labelled `caller` in metrics
+    pub fn provider(&self, url: &str, caller: &'static str) -> anyhow::Result<RpcProvider> {
+        Ok(Provider::new(BudgetedHttp {
+            inner: Http::from_str(url)?,
+            budget: self.clone(),
+            caller,
+        }))
+    }
+
+    /// Creates an alloy provider for `url` whose requests are labelled `caller` in metrics
+    pub fn alloy_provider(&self, url: &str, caller: &'static str) -> anyhow::Result<AlloyProvider> {
+        let client = ClientBuilder::default()
+            .layer(BudgetLayer {
+                budget: self.clone(),
+                caller,
+            })
+            .http(url.parse()?);
+        Ok(RootProvider::new(client))
+    }
+
+    /// Takes one request from the budget, waiting for it in queue mode
+    async fn acquire(&self, caller: &'static str) -> Result<(), RpcClientError> {
+        let wait = match &self.bucket {
+            None => Duration::ZERO,
+            Some(bucket) => match bucket.take(self.mode == RpcBudgetMode::Queue) {
+                Some(wait) => wait,
+                None => {
+                    metrics::record_rpc_dropped(&self.deployment, caller);
+                    return Err(RpcClientError::BudgetExhausted);
+                }
+            },
+        };
+        if let Some(bucket) = self.bucket.as_deref().filter(|_| !wait.is_zero()) {
+            let mut reservation = Reservation {
+                bucket,
+                sent: false,
+            };
+            tokio::time::sleep(wait).await;
+            reservation.sent = true;
+        }
+        metrics::record_rpc_request(&self.deployment, caller, wait);
+        Ok(())
+    }
+}
+
+/// Token bucket holding at most one second of budget, so idle periods don't build up
+/// a burst
+#[derive(Debug)]
+struct Bucket {
+    /// Available requests (negative while requests are queued) and when they were last
+    /// topped up
+    state: Mutex<(f64, Instant)>,
+    per_second: f64,
+}
+
+impl Bucket {
+    fn new(per_second: u32) -> Self {
+        let per_second = f64::from(per_second);
+        Self {
+            state: Mutex::new((per_second, Instant::now())),
+            per_second,
+        }
+    }
+
+    /// Takes one request and returns how long to wait before sending it
+    ///
+    /// An empty bucket returns `None`, unless `queue` is set: then the request is
+    /// reserved behind those already waiting.
+    fn take(&self, queue: bool) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (available, last_refill) = &mut *state;
+        self.refill(available, last_refill);
+        if *available >= 1.0 {
+            *available -= 1.0;
+            return Some(Duration::ZERO);
+        }
+        if !queue {
+            return None;
+        }
+        *available -= 1.0;
+        Some(Duration::from_secs_f64(-*available / self.per_second))
+    }
+
+    /// Returns a reserved request that won't be sent
+    ///
+    /// Requests queued behind it keep their wait; later ones queue behind less debt.
+    fn give_back(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (available, last_refill) = &mut *state;
+        self.refill(available, last_refill);
+        *available = (*available + 1.0).min(self.per_second);
+    }
+
+    /// Adds the budget accrued since `last_refill`, up to one second's worth
+    fn refill(&self, available: &mut f64, last_refill: &mut Instant) {
+        let now = Instant::now();
+        *available = (*available
+            + now.duration_since(*last_refill).as_secs_f64() * self.per_second)
+            .min(self.per_second);
+        *last_refill = now;
+    }
+}
+
+/// A queued request's turn in a [`Bucket`], given back if dropped before it is sent
+///
+/// Callers wrap requests in their own timeout, which drops the waiting future.
+struct Reservation<'a> {
+    bucket: &'a Bucket,
+    sent: bool,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.sent {
+            self.bucket.give_back();
+        }
+    }
+}
+
+/// HTTP JSON-RPC transport drawing on an [`RpcBudget`] before every request
+#[derive(Clone, Debug)]
+pub struct BudgetedHttp {
+    inner: Http,
+    budget: RpcBudget,
+    caller: &'static str,
+}
+
+/// Errors from [`BudgetedHttp`]
+#[derive(Debug, thiserror::Error)]
+pub enum RpcClientError {
+    /// Dropped because the budget was exhausted (`RPC_RATE_LIMIT_MODE=drop`)
+    #[error("RPC request budget exhausted")]
+    BudgetExhausted,
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+}
+
+impl RpcError for RpcClientError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            RpcClientError::Http(err) => err.as_error_response(),
+            RpcClientError::BudgetExhausted => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            RpcClientError::Http(err) => err.as_serde_error(),
+            RpcClientError::BudgetExhausted => None,
+        }
+    }
+}
+
+impl From<RpcClientError> for ProviderError {
+    fn from(err: RpcClientError) -> Self {
+        match err {
+            RpcClientError::Http(err) => err.into(),
+            err => ProviderError::JsonRpcClientError(Box::new(err)),
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for BudgetedHttp {
+    type Error = RpcClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        self.budget.acquire(self.caller).await?;
+        Ok(self.inner.request(method, params).await?)
+    }
+}
+
+/// Tower layer drawing on an [`RpcBudget`] before every request of an alloy transport
+///
+/// Each request of a batch counts against the budget.
+#[derive(Clone, Debug)]
+struct BudgetLayer {
+    budget: RpcBudget,
+    caller: &'static str,
+}
+
+impl<S> Layer<S> for BudgetLayer {
+    type Service = BudgetService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BudgetService {
+            inner,
+            budget: self.budget.clone(),
+            caller: self.caller,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BudgetService<S> {
+    inner: S,
+    budget: RpcBudget,
+    caller: &'static str,
+}
+
+impl<S> Service<RequestPacket> for BudgetService<S>
+where
+    S: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Clone
+        + Send
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        // The clone is ready only once polled; send through the instance that was
+        let ready = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, ready);
+        let (budget, caller) = (self.budget.clone(), self.caller);
+        Box::pin(async move {
+            let requests = request.as_batch().map_or(1, |batch| batch.len());
+            for _ in 0..requests {
+                budget
+                    .acquire(caller)
+                    .await
+                    .map_err(TransportErrorKind::custom)?;
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn queues_or_drops_requests_over_the_budget() {
+        let queued = Bucket::new(2);
+        assert_eq!(queued.take(true), Some(Duration::ZERO));
+        assert_eq!(queued.take(true), Some(Duration::ZERO));
+        // Each queued request waits behind the previous one
+        assert_eq!(queued.take(true), Some(Duration::from_millis(500)));
+        assert_eq!(queued.take(true), Some(Duration::from_secs(1)));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(queued.take(true), Some(Duration::from_millis(500)));
+
+        let dropped = Bucket::new(1);
+        assert_eq!(dropped.take(false), Some(Duration::ZERO));
+        assert_eq!(dropped.take(false), None);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(dropped.take(false), Some(Duration::ZERO));
+        // Idle time doesn't build up more than a second of budget
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(dropped.take(false), Some(Duration::ZERO));
+        assert_eq!(dropped.take(false), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_waits_give_their_turn_back() {
+        let budget = RpcBudget::new("test", 1, RpcBudgetMode::Queue);
+        budget.acquire("test").await.unwrap();
+
+        // Callers giving up long before their turn, as under an RPC timeout
+        for _ in 0..20 {
+            let waiting = tokio::time::timeout(Duration::from_millis(10), budget.acquire("test"));
+            assert!(waiting.await.is_err());
+        }
+
+        // Without the returned turns this would queue behind 20 seconds of debt
+        let start = Instant::now();
+        budget.acquire("test").await.unwrap();
+        assert!(start.elapsed() <= Duration::from_secs(1));
+        // A completed wait keeps its turn
+        let start = Instant::now();
+        budget.acquire("test").await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+    }
+}
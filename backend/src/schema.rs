@@ -0,0 +1,197 @@
+//! Schema compatibility check
+//!
+//! Migrations are applied out of band (`sqlx migrate run --source migrations`), so a
+//! backend can be started against a database that is behind it. It would then fail
+//! much later, mid-indexing, on a missing column. Instead each deployment compares the
+//! database's `_sqlx_migrations` table with the migrations compiled into the binary
+//! before starting anything, and refuses to start when:
+//!
+//! - a migration is pending (the schema is behind),
+//! - a migration unknown to this binary was applied (a newer release migrated the
+//!   database, or it belongs to something else), or
+//! - a migration failed part-way.
+//!
+//! `SCHEMA_CHECK=warn` logs these problems instead, for read-only replicas whose
+//! migrations are applied elsewhere. Applied migrations whose file changed since are
+//! only warned about: sqlx refuses to migrate such a database, but the columns exist.
+
+use sqlx::PgPool;
+use sqlx::Row;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::PgRow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The migrations under `migrations/`, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// What to do with an incompatible schema (`SCHEMA_CHECK`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchemaCheck {
+    /// Refuse to start (default)
+    #[default]
+    Fail,
+    /// Log a warning and start anyway
+    Warn,
+}
+
+/// One row of `_sqlx_migrations`
+struct AppliedMigration {
+    version: i64,
+    success: bool,
+    checksum: Vec<u8>,
+}
+
+/// Differences between the database and the embedded migrations, by version
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    /// Embedded migrations not applied yet
+    pub pending: Vec<i64>,
+    /// Applied migrations this binary doesn't know
+    pub unknown: Vec<i64>,
+    /// Migrations recorded as failed
+    pub failed: Vec<i64>,
+    /// Applied migrations whose file changed since
+    pub modified: Vec<i64>,
+}
+
+impl SchemaReport {
+    /// Whether the backend can run against the schema (modified files aside)
+    pub fn is_compatible(&self) -> bool {
+        self.pending.is_empty() && self.unknown.is_empty() && self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = [
+            ("pending", &self.pending),
+            ("unknown to this release", &self.unknown),
+            ("failed", &self.failed),
+            ("modified since applied", &self.modified),
+        ];
+        let mut first = true;
+        for (label, versions) in parts {
+            if versions.is_empty() {
+                continue;
+            }
+            if !first {
+                write!(f, "; ")?;
+            }
+            first = false;
+            let versions: Vec<String> = versions.iter().map(i64::to_string).collect();
+            write!(f, "{label}: {}", versions.join(", "))?;
+        }
+        if first {
+            write!(f, "up to date")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the migrations applied to the database behind `pool` with [`MIGRATOR`]
+///
+/// A database without `_sqlx_migrations` has every migration pending.
+pub async fn inspect(pool: &PgPool) -> Result<SchemaReport, sqlx::Error> {
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+    let applied = if has_table {
+        sqlx::query("SELECT version, success, checksum FROM _sqlx_migrations")
+            .try_map(|row: PgRow| {
+                Ok(AppliedMigration {
+                    version: row.try_get("version")?,
+                    success: row.try_get("success")?,
+                    checksum: row.try_get("checksum")?,
+                })
+            })
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+    Ok(compare(&MIGRATOR, &applied))
+}
+
+fn compare(migrator: &Migrator, applied: &[AppliedMigration]) -> SchemaReport {
+    let embedded: HashMap<i64, &[u8]> = migrator
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| (migration.version, migration.checksum.as_ref()))
+        .collect();
+    let applied_versions: HashMap<i64, &AppliedMigration> = applied
+        .iter()
+        .map(|migration| (migration.version, migration))
+        .collect();
+
+    let mut report = SchemaReport::default();
+    for (version, checksum) in &embedded {
+        match applied_versions.get(version) {
+            None => report.pending.push(*version),
+            Some(migration) if !migration.success => report.failed.push(*version),
+            Some(migration) if migration.checksum != *checksum => report.modified.push(*version),
+            Some(_) => {}
+        }
+    }
+    for migration in applied {
+        if !embedded.contains_key(&migration.version) {
+            if migration.success {
+                report.unknown.push(migration.version);
+            } else {
+                report.failed.push(migration.version);
+            }
+        }
+    }
+    report.pending.sort_unstable();
+    report.unknown.sort_unstable();
+    report.failed.sort_unstable();
+    report.modified.sort_unstable();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied_all() -> Vec<AppliedMigration> {
+        MIGRATOR
+            .iter()
+            .map(|migration| AppliedMigration {
+                version: migration.version,
+                success: true,
+                checksum: migration.checksum.to_vec(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_schema_differences() {
+        let mut applied = applied_all();
+        assert!(compare(&MIGRATOR, &applied).is_compatible());
+
+        let latest = applied.pop().unwrap().version;
+        applied[0].checksum = vec![0];
+        applied[1].success = false;
+        applied.push(AppliedMigration {
+            version: 99990101000000,
+            success: true,
+            checksum: vec![],
+        });
+
+        let report = compare(&MIGRATOR, &applied);
+        assert!(!report.is_compatible());
+        assert_eq!(
+            report,
+            SchemaReport {
+                pending: vec![latest],
+                unknown: vec![99990101000000],
+                failed: vec![applied[1].version],
+                modified: vec![applied[0].version],
+            }
+        );
+        assert_eq!(
+            compare(&MIGRATOR, &[]).pending.len(),
+            MIGRATOR.iter().count()
+        );
+    }
+}
@@ -0,0 +1,529 @@
+//! EIP-712 signed requests
+//!
+//! Endpoints that act on behalf of a wallet (subscriptions, preferences, ...)
+//! accept an EIP-712 typed-data message signed by that wallet. This module is the
+//! single place that hashes those messages, recovers the signer and enforces
+//! replay protection; endpoints only declare their [`MessageType`].
+//!
+//! # Message conventions
+//! Every message type has these fields, in addition to its own:
+//! - `wallet` (`address`) - the wallet the request acts for; must be the signer
+//! - `nonce` (`uint256`) - chosen by the wallet, usable once (`signature_nonces`)
+//! - `deadline` (`uint256`) - unix time after which the signature is rejected, at
+//!   most [`MAX_DEADLINE_WINDOW`] ahead
+//!
+//! # Domain separation
+//! The domain binds signatures to one deployment: its chain ID and factory address
+//! (`verifyingContract`). Clients read it from `GET /v1/chain` (`signing_domain`).
+//!
+//! # Security Considerations
+//! - Signatures are checked against the declared `wallet`, never trusted on their own
+//! - A nonce is consumed atomically (primary key on `(wallet, nonce)`), in the
+//!   transaction of the action it authorizes, so a failed action leaves it unused
+//! - Deadlines are capped so the nonce table only holds short-lived rows
+
+use crate::config::AppConfig;
+use chrono::{DateTime, TimeZone, Utc};
+use ethers::types::transaction::eip712::{EIP712Domain, Eip712DomainType, Types, hash_struct};
+use ethers::types::{Address, RecoveryMessage, Signature, U256};
+use ethers::utils::keccak256;
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// `name` of the EIP-712 domain
+const DOMAIN_NAME: &str = "Ticket Arcade";
+
+/// `version` of the EIP-712 domain; bump to invalidate all outstanding signatures
+const DOMAIN_VERSION: &str = "1";
+
+/// Furthest a deadline may lie in the future
+pub const MAX_DEADLINE_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Fields every message type starts with
+const COMMON_FIELDS: [(&str, &str); 3] = [
+    ("wallet", "address"),
+    ("nonce", "uint256"),
+    ("deadline", "uint256"),
+];
+
+/// Errors from verifying a signed request
+#[derive(Debug, thiserror::Error)]
+pub enum SignatureError {
+    /// The message doesn't match its declared type
+    #[error("invalid message: {0}")]
+    InvalidMessage(String),
+    #[error("signature deadline has passed")]
+    Expired,
+    #[error("signature deadline is too far in the future")]
+    DeadlineTooFar,
+    #[error("invalid signature")]
+    InvalidSignature,
+    /// The signature is valid but not from the message's `wallet`
+    #[error("signature is not from the message wallet")]
+    WrongSigner,
+    #[error("nonce has already been used")]
+    Replayed,
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+/// EIP-712 domain of one deployment, serialized as wallets expect it
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningDomain {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub chain_id: u64,
+    pub verifying_contract: String,
+}
+
+impl SigningDomain {
+    pub fn for_config(config: &AppConfig) -> Self {
+        Self {
+            name: DOMAIN_NAME,
+            version: DOMAIN_VERSION,
+            chain_id: config.chain_id,
+            verifying_contract: config.raffle_factory_address.to_lowercase(),
+        }
+    }
+
+    fn separator(&self) -> Result<[u8; 32], SignatureError> {
+        let verifying_contract = Address::from_str(&self.verifying_contract)
+            .map_err(|_| SignatureError::InvalidMessage("invalid verifying contract".into()))?;
+        Ok(EIP712Domain {
+            name: Some(self.name.to_string()),
+            version: Some(self.version.to_string()),
+            chain_id: Some(U256::from(self.chain_id)),
+            verifying_contract: Some(verifying_contract),
+            salt: None,
+        }
+        .separator())
+    }
+}
+
+/// A signed request type: its EIP-712 primary type and the fields after [`COMMON_FIELDS`]
+pub struct MessageType {
+    pub primary_type: &'static str,
+    pub fields: &'static [(&'static str, &'static str)],
+}
+
+impl MessageType {
+    fn types(&self) -> Types {
+        let fields = COMMON_FIELDS
+            .iter()
+            .chain(self.fields)
+            .map(|(name, ty)| Eip712DomainType {
+                name: name.to_string(),
+                r#type: ty.to_string(),
+            })
+            .collect();
+        Types::from([(self.primary_type.to_string(), fields)])
+    }
+
+    /// Full type string, e.g. `Subscribe(address wallet,uint256 nonce,uint256 deadline,...)`
+    pub fn encode_type(&self) -> String {
+        let fields: Vec<String> = COMMON_FIELDS
+            .iter()
+            .chain(self.fields)
+            .map(|(name, ty)| format!("{ty} {name}"))
+            .collect();
+        format!("{}({})", self.primary_type, fields.join(","))
+    }
+}
+
+/// A verified request: the signing wallet and the deadline of its signature
+#[derive(Debug)]
+pub struct Verified {
+    pub wallet: Address,
+    pub deadline: DateTime<Utc>,
+}
+
+/// Verifies a signed message and consumes its nonce in `db_tx`
+///
+/// `message` must be a JSON object with exactly the fields of `kind` (including
+/// the common `wallet`, `nonce` and `deadline`). `signature` is the 65-byte hex
+/// signature returned by `eth_signTypedData_v4`. Callers commit `db_tx` together
+/// with the action the message authorizes; until then concurrent replays of the
+/// nonce wait on its row.
+pub async fn verify(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    domain: &SigningDomain,
+    kind: &MessageType,
+    message: &Value,
+    signature: &str,
+) -> Result<Verified, SignatureError> {
+    let verified = recover(domain, kind, message, signature)?;
+    let nonce = parse_uint(&message["nonce"], "nonce")?;
+
+    // Consume the nonce; the primary key makes concurrent replays fail too
+    let wallet = format!("{:#x}", verified.wallet);
+    let inserted = sqlx::query(
+        "INSERT INTO signature_nonces (wallet, nonce, primary_type, expires_at)
+         VALUES ($1, $2::numeric, $3, $4)
+         ON CONFLICT (wallet, nonce) DO NOTHING",
+    )
+    .bind(&wallet)
+    .bind(nonce.to_string())
+    .bind(kind.primary_type)
+    .bind(verified.deadline)
+    .execute(&mut **db_tx)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Err(SignatureError::Replayed);
+    }
+
+    // Expired nonces can't be replayed anyway (the deadline check rejects them)
+    sqlx::query("DELETE FROM signature_nonces WHERE wallet = $1 AND expires_at < now()")
+        .bind(&wallet)
+        .execute(&mut **db_tx)
+        .await?;
+
+    Ok(verified)
+}
+
+/// Checks the message shape and deadline and recovers the signer, without touching nonces
+fn recover(
+    domain: &SigningDomain,
+    kind: &MessageType,
+    message: &Value,
+    signature: &str,
+) -> Result<Verified, SignatureError> {
+    let object = message
+        .as_object()
+        .ok_or_else(|| SignatureError::InvalidMessage("message must be an object".into()))?;
+    let expected = COMMON_FIELDS.len() + kind.fields.len();
+    let known = COMMON_FIELDS
+        .iter()
+        .chain(kind.fields)
+        .all(|(name, _)| object.contains_key(*name));
+    if object.len() != expected || !known {
+        return Err(SignatureError::InvalidMessage(format!(
+            "expected fields of {}",
+            kind.encode_type()
+        )));
+    }
+
+    let wallet = message["wallet"]
+        .as_str()
+        .and_then(|wallet| Address::from_str(wallet).ok())
+        .ok_or_else(|| SignatureError::InvalidMessage("wallet must be an address".into()))?;
+
+    let deadline = parse_uint(&message["deadline"], "deadline")?;
+    let now = Utc::now();
+    let deadline = (deadline <= U256::from(i64::MAX as u64))
+        .then(|| Utc.timestamp_opt(deadline.as_u64() as i64, 0).single())
+        .flatten()
+        .ok_or(SignatureError::DeadlineTooFar)?;
+    if deadline <= now {
+        return Err(SignatureError::Expired);
+    }
+    let max_window =
+        chrono::Duration::from_std(MAX_DEADLINE_WINDOW).unwrap_or(chrono::Duration::MAX);
+    if deadline > now + max_window {
+        return Err(SignatureError::DeadlineTooFar);
+    }
+
+    let digest = signing_digest(domain, kind.primary_type, &kind.types(), message)?;
+    let signature = Signature::from_str(signature.trim_start_matches("0x"))
+        .map_err(|_| SignatureError::InvalidSignature)?;
+    let signer = signature
+        .recover(RecoveryMessage::Hash(digest.into()))
+        .map_err(|_| SignatureError::InvalidSignature)?;
+    if signer != wallet {
+        return Err(SignatureError::WrongSigner);
+    }
+    Ok(Verified { wallet, deadline })
+}
+
+/// The EIP-712 digest a wallet signs for `message` of `primary_type`
+fn signing_digest(
+    domain: &SigningDomain,
+    primary_type: &str,
+    types: &Types,
+    message: &Value,
+) -> Result<[u8; 32], SignatureError> {
+    let struct_hash = hash_struct(primary_type, message, types)
+        .map_err(|err| SignatureError::InvalidMessage(err.to_string()))?;
+    Ok(keccak256(
+        [&[0x19, 0x01], &domain.separator()?[..], &struct_hash[..]].concat(),
+    ))
+}
+
+/// Parses a `uint256` given as a JSON number or decimal/hex string
+fn parse_uint(value: &Value, field: &str) -> Result<U256, SignatureError> {
+    let parsed = match value {
+        Value::Number(number) => number.as_u64().map(U256::from),
+        Value::String(text) => match text.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).ok(),
+            None => U256::from_dec_str(text).ok(),
+        },
+        _ => None,
+    };
+    parsed.ok_or_else(|| SignatureError::InvalidMessage(format!("{field} must be a uint256")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::TestDb;
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::types::H256;
+    use serde_json::json;
+
+    const TEST_MESSAGE: MessageType = MessageType {
+        primary_type: "Subscribe",
+        fields: &[("raffleId", "uint256")],
+    };
+
+    fn domain() -> SigningDomain {
+        SigningDomain {
+            name: DOMAIN_NAME,
+            version: DOMAIN_VERSION,
+            chain_id: 31337,
+            verifying_contract: "0x00000000000000000000000000000000000fac70".to_string(),
+        }
+    }
+
+    fn wallet(key: &str) -> LocalWallet {
+        key.parse().unwrap()
+    }
+
+    fn subscribe_message(wallet: &LocalWallet, nonce: u64, deadline: i64) -> Value {
+        json!({
+            "wallet": format!("{:#x}", wallet.address()),
+            "nonce": nonce,
+            "deadline": deadline,
+            "raffleId": 3,
+        })
+    }
+
+    fn sign(wallet: &LocalWallet, message: &Value) -> String {
+        let types = TEST_MESSAGE.types();
+        let digest = signing_digest(&domain(), TEST_MESSAGE.primary_type, &types, message).unwrap();
+        format!("0x{}", wallet.sign_hash(H256::from(digest)).unwrap())
+    }
+
+    fn in_secs(secs: i64) -> i64 {
+        Utc::now().timestamp() + secs
+    }
+
+    /// The `Mail` example of the EIP-712 specification
+    #[test]
+    fn hashes_the_eip712_mail_example() {
+        let types = Types::from([
+            (
+                "Mail".to_string(),
+                vec![
+                    Eip712DomainType {
+                        name: "from".into(),
+                        r#type: "Person".into(),
+                    },
+                    Eip712DomainType {
+                        name: "to".into(),
+                        r#type: "Person".into(),
+                    },
+                    Eip712DomainType {
+                        name: "contents".into(),
+                        r#type: "string".into(),
+                    },
+                ],
+            ),
+            (
+                "Person".to_string(),
+                vec![
+                    Eip712DomainType {
+                        name: "name".into(),
+                        r#type: "string".into(),
+                    },
+                    Eip712DomainType {
+                        name: "wallet".into(),
+                        r#type: "address".into(),
+                    },
+                ],
+            ),
+        ]);
+        let mail = json!({
+            "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+            "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+            "contents": "Hello, Bob!",
+        });
+        let domain = SigningDomain {
+            name: "Ether Mail",
+            version: "1",
+            chain_id: 1,
+            verifying_contract: "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC".to_string(),
+        };
+
+        assert_eq!(
+            hex::encode(hash_struct("Mail", &mail, &types).unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            hex::encode(domain.separator().unwrap()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        let digest = signing_digest(&domain, "Mail", &types, &mail).unwrap();
+        assert_eq!(
+            hex::encode(digest),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+
+        // The specification's signature by Cow (private key keccak256("cow"))
+        let signature = Signature {
+            r: U256::from_str_radix(
+                "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d",
+                16,
+            )
+            .unwrap(),
+            s: U256::from_str_radix(
+                "07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562",
+                16,
+            )
+            .unwrap(),
+            v: 28,
+        };
+        assert_eq!(
+            signature
+                .recover(RecoveryMessage::Hash(digest.into()))
+                .unwrap(),
+            Address::from_str("0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826").unwrap()
+        );
+    }
+
+    #[test]
+    fn recovers_a_wallet_signature() {
+        let signer = wallet("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        let deadline = in_secs(600);
+        let message = subscribe_message(&signer, 1, deadline);
+        let verified =
+            recover(&domain(), &TEST_MESSAGE, &message, &sign(&signer, &message)).unwrap();
+        assert_eq!(verified.wallet, signer.address());
+        assert_eq!(verified.deadline.timestamp(), deadline);
+
+        // Bound to the domain: another chain's signature doesn't recover to the wallet
+        let other_chain = SigningDomain {
+            chain_id: 1,
+            ..domain()
+        };
+        assert!(matches!(
+            recover(
+                &other_chain,
+                &TEST_MESSAGE,
+                &message,
+                &sign(&signer, &message)
+            ),
+            Err(SignatureError::WrongSigner)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let signer = wallet("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        let other = wallet("0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef");
+        let check = |message: &Value, signature: &str| {
+            recover(&domain(), &TEST_MESSAGE, message, signature)
+        };
+
+        let message = subscribe_message(&signer, 1, in_secs(600));
+        assert!(matches!(
+            check(&message, &sign(&other, &message)),
+            Err(SignatureError::WrongSigner)
+        ));
+
+        let expired = subscribe_message(&signer, 1, in_secs(-10));
+        assert!(matches!(
+            check(&expired, &sign(&signer, &expired)),
+            Err(SignatureError::Expired)
+        ));
+
+        let too_far = subscribe_message(&signer, 1, in_secs(2 * 24 * 60 * 60));
+        assert!(matches!(
+            check(&too_far, &sign(&signer, &too_far)),
+            Err(SignatureError::DeadlineTooFar)
+        ));
+
+        let signature = sign(&signer, &message);
+        let mut extra = message.clone();
+        extra["extra"] = json!(1);
+        assert!(matches!(
+            check(&extra, &signature),
+            Err(SignatureError::InvalidMessage(_))
+        ));
+
+        assert!(matches!(
+            check(&message, "0x1234"),
+            Err(SignatureError::InvalidSignature)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_replayed_nonce() {
+        let Some(db) = TestDb::create().await.unwrap() else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+        let signer = wallet("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        let message = subscribe_message(&signer, 7, in_secs(600));
+        let signature = sign(&signer, &message);
+
+        let mut db_tx = db.pool.begin().await.unwrap();
+        verify(&mut db_tx, &domain(), &TEST_MESSAGE, &message, &signature)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+        let mut db_tx = db.pool.begin().await.unwrap();
+        assert!(matches!(
+            verify(&mut db_tx, &domain(), &TEST_MESSAGE, &message, &signature).await,
+            Err(SignatureError::Replayed)
+        ));
+        drop(db_tx);
+
+        // Nonces are per wallet
+        let other = wallet("0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef");
+        let message = subscribe_message(&other, 7, in_secs(600));
+        let mut db_tx = db.pool.begin().await.unwrap();
+        verify(
+            &mut db_tx,
+            &domain(),
+            &TEST_MESSAGE,
+            &message,
+            &sign(&other, &message),
+        )
+        .await
+        .unwrap();
+        db_tx.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rolled_back_actions_keep_the_nonce() {
+        let Some(db) = TestDb::create().await.unwrap() else {
+            eprintln!("TEST_DATABASE_URL is unset, skipping");
+            return;
+        };
+        let signer = wallet("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        let message = subscribe_message(&signer, 8, in_secs(600));
+        let signature = sign(&signer, &message);
+
+        // The action failed after verifying, so its transaction is rolled back
+        let mut db_tx = db.pool.begin().await.unwrap();
+        verify(&mut db_tx, &domain(), &TEST_MESSAGE, &message, &signature)
+            .await
+            .unwrap();
+        db_tx.rollback().await.unwrap();
+        let nonces: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM signature_nonces")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(nonces, 0);
+
+        // The same signature can be retried
+        let mut db_tx = db.pool.begin().await.unwrap();
+        verify(&mut db_tx, &domain(), &TEST_MESSAGE, &message, &signature)
+            .await
+            .unwrap();
+        db_tx.commit().await.unwrap();
+    }
+}
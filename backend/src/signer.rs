@@ -0,0 +1,508 @@
+//! Transaction signers for the keeper
+//!
+//! The keeper wallet's key can live in one of three places, selected by
+//! `KEEPER_SIGNER`:
+//! - `local` - a raw private key in `KEEPER_PRIVATE_KEY` (development only)
+//! - `kms` - an AWS KMS `ECC_SECG_P256K1` key (`KEEPER_KMS_KEY_ID`); the key never
+//!   leaves KMS, which only returns signatures over transaction hashes
+//! - `remote` - a remote signer (e.g. Web3Signer or Clef) at `KEEPER_REMOTE_SIGNER_URL`
+//!   answering `eth_signTransaction` for `KEEPER_ADDRESS`
+//!
+//! # AWS credentials
+//! KMS requests are signed with SigV4 using the default credential chain (see
+//! [`crate::aws`]).
+//!
+//! # Security Considerations
+//! - Private keys, AWS secrets and signatures are never logged
+//! - Transactions returned by a remote signer are decoded and checked against the
+//!   request (signer address, nonce, recipient, calldata) before they are sent
+//! - KMS signatures are normalized to low-s, as Ethereum requires
+
+use crate::aws::{self, CredentialProvider};
+use crate::config::KeeperSignerConfig;
+use anyhow::Context;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::Utc;
+use ethers::core::k256::ecdsa::{RecoveryId, Signature as EcdsaSignature, VerifyingKey};
+use ethers::core::utils::public_key_to_address;
+use ethers::signers::{LocalWallet, Signer, to_eip155_v};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes, H256, Signature, U256};
+use ethers::utils::{keccak256, rlp::Rlp};
+use serde_json::{Value, json};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Timeout for signer HTTP requests (KMS, credential endpoints, remote signer)
+const SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signs keeper transactions with the configured backend
+pub enum KeeperSigner {
+    Local(LocalWallet),
+    Kms(KmsSigner),
+    Remote(RemoteSigner),
+}
+
+impl KeeperSigner {
+    /// Builds the signer and resolves its address
+    ///
+    /// KMS reads the public key (checking credentials and key access); a remote
+    /// signer is asked for its accounts, which must include `KEEPER_ADDRESS` if
+    /// the signer supports `eth_accounts`.
+    pub async fn connect(config: &KeeperSignerConfig) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(SIGNER_TIMEOUT)
+            .build()
+            .context("failed to build HTTP client")?;
+        match config {
+            KeeperSignerConfig::Local { private_key } => private_key
+                .parse::<LocalWallet>()
+                .map(KeeperSigner::Local)
+                .map_err(|_| anyhow::anyhow!("KEEPER_PRIVATE_KEY is not a valid private key")),
+            KeeperSignerConfig::Kms {
+                key_id,
+                region,
+                endpoint,
+            } => KmsSigner::connect(http, key_id, region, endpoint.as_deref())
+                .await
+                .map(KeeperSigner::Kms),
+            KeeperSignerConfig::Remote { url, address } => {
+                let address =
+                    Address::from_str(address).context("KEEPER_ADDRESS is not a valid address")?;
+                let signer = RemoteSigner {
+                    http,
+                    url: url.clone(),
+                    address,
+                };
+                signer.check_account().await?;
+                Ok(KeeperSigner::Remote(signer))
+            }
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        match self {
+            KeeperSigner::Local(wallet) => wallet.address(),
+            KeeperSigner::Kms(kms) => kms.address,
+            KeeperSigner::Remote(remote) => remote.address,
+        }
+    }
+
+    /// `KEEPER_SIGNER` value of this backend
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KeeperSigner::Local(_) => "local",
+            KeeperSigner::Kms(_) => "kms",
+            KeeperSigner::Remote(_) => "remote",
+        }
+    }
+
+    /// Signs a transaction (which must carry its chain id), returning the raw signed bytes
+    pub async fn sign_transaction(&self, tx: &TypedTransaction) -> anyhow::Result<Bytes> {
+        let chain_id = tx
+            .chain_id()
+            .context("transaction has no chain id")?
+            .as_u64();
+        match self {
+            KeeperSigner::Local(wallet) => {
+                let signature = wallet
+                    .clone()
+                    .with_chain_id(chain_id)
+                    .sign_transaction(tx)
+                    .await
+                    .context("failed to sign transaction")?;
+                Ok(tx.rlp_signed(&signature))
+            }
+            KeeperSigner::Kms(kms) => {
+                let signature = kms.sign_digest(tx.sighash(), chain_id).await?;
+                Ok(tx.rlp_signed(&signature))
+            }
+            KeeperSigner::Remote(remote) => remote.sign_transaction(tx).await,
+        }
+    }
+}
+
+// ============================================================================
+// AWS KMS
+// ============================================================================
+
+/// AWS KMS asymmetric key signing over the KMS JSON API
+pub struct KmsSigner {
+    client: KmsClient,
+    public_key: VerifyingKey,
+    address: Address,
+}
+
+/// SigV4-signed client for one KMS key
+struct KmsClient {
+    http: reqwest::Client,
+    key_id: String,
+    region: String,
+    /// `https://kms.{region}.amazonaws.com` unless `KEEPER_KMS_ENDPOINT` is set
+    endpoint: String,
+    credentials: CredentialProvider,
+}
+
+impl KmsSigner {
+    async fn connect(
+        http: reqwest::Client,
+        key_id: &str,
+        region: &str,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let client = KmsClient {
+            http,
+            key_id: key_id.to_string(),
+            region: region.to_string(),
+            endpoint: endpoint
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| format!("https://kms.{region}.amazonaws.com")),
+            credentials: CredentialProvider::from_chain(),
+        };
+
+        let response = client
+            .request("GetPublicKey", json!({ "KeyId": key_id }))
+            .await
+            .context("KMS GetPublicKey failed")?;
+        let spki = decode_base64_field(&response, "PublicKey")?;
+        // DER SubjectPublicKeyInfo; a secp256k1 key ends with the 65-byte uncompressed point
+        let point = spki
+            .len()
+            .checked_sub(65)
+            .map(|start| &spki[start..])
+            .filter(|point| point[0] == 0x04)
+            .context("KMS key is not an uncompressed secp256k1 public key (ECC_SECG_P256K1)")?;
+        let public_key =
+            VerifyingKey::from_sec1_bytes(point).context("KMS key is not a secp256k1 key")?;
+        Ok(Self {
+            client,
+            address: public_key_to_address(&public_key),
+            public_key,
+        })
+    }
+
+    /// Signs a 32-byte digest and returns an EIP-155 Ethereum signature
+    async fn sign_digest(&self, digest: H256, chain_id: u64) -> anyhow::Result<Signature> {
+        let response = self
+            .client
+            .request(
+                "Sign",
+                json!({
+                    "KeyId": self.client.key_id,
+                    "Message": BASE64.encode(digest.as_bytes()),
+                    "MessageType": "DIGEST",
+                    "SigningAlgorithm": "ECDSA_SHA_256",
+                }),
+            )
+            .await
+            .context("KMS Sign failed")?;
+        let der = decode_base64_field(&response, "Signature")?;
+        let signature =
+            EcdsaSignature::from_der(&der).context("KMS returned an invalid signature")?;
+        // Ethereum only accepts low-s signatures; KMS may return either
+        let signature = signature.normalize_s().unwrap_or(signature);
+
+        // KMS doesn't return the recovery id: find the one that yields our key
+        let recovery_id = [0u8, 1]
+            .into_iter()
+            .find(|&id| {
+                RecoveryId::from_byte(id)
+                    .and_then(|id| {
+                        VerifyingKey::recover_from_prehash(digest.as_bytes(), &signature, id).ok()
+                    })
+                    .is_some_and(|key| key == self.public_key)
+            })
+            .context("KMS signature does not match the key")?;
+
+        let (r, s) = signature.split_bytes();
+        Ok(Signature {
+            r: U256::from_big_endian(&r),
+            s: U256::from_big_endian(&s),
+            v: to_eip155_v(recovery_id, chain_id),
+        })
+    }
+}
+
+impl KmsClient {
+    /// Sends a SigV4-signed KMS JSON API request
+    async fn request(&self, action: &str, body: Value) -> anyhow::Result<Value> {
+        let credentials = self.credentials.get(&self.http).await?;
+        let body = serde_json::to_vec(&body)?;
+        let target = format!("TrentService.{action}");
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, host)| host)
+            .to_string();
+
+        // Canonical headers must be sorted by name
+        let now = Utc::now();
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host),
+            ("x-amz-date", aws::amz_date(now)),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.push(("x-amz-target", target));
+
+        let payload_hash = aws::sha256_hex(&body);
+        let authorization = aws::authorization(
+            &credentials,
+            &self.region,
+            "kms",
+            &aws::SignedRequest {
+                method: "POST",
+                path: "/",
+                query: "",
+                headers: &headers,
+                payload_hash: &payload_hash,
+            },
+            now,
+        );
+
+        let mut request = self
+            .http
+            .post(format!("{}/", self.endpoint))
+            .header("authorization", authorization)
+            .body(body);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.context("KMS request failed")?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .context("KMS returned an invalid response")?;
+        if !status.is_success() {
+            // Error bodies carry `__type` and `message`, never key material
+            anyhow::bail!(
+                "KMS {action} returned {status}: {} {}",
+                body["__type"].as_str().unwrap_or("unknown error"),
+                body["message"]
+                    .as_str()
+                    .or(body["Message"].as_str())
+                    .unwrap_or_default()
+            );
+        }
+        Ok(body)
+    }
+}
+
+fn decode_base64_field(body: &Value, field: &str) -> anyhow::Result<Vec<u8>> {
+    let encoded = body[field]
+        .as_str()
+        .with_context(|| format!("KMS response has no {field}"))?;
+    BASE64
+        .decode(encoded)
+        .with_context(|| format!("KMS {field} is not valid base64"))
+}
+
+// ============================================================================
+// REMOTE SIGNER
+// ============================================================================
+
+/// Remote signer speaking Ethereum JSON-RPC (`eth_signTransaction`)
+pub struct RemoteSigner {
+    http: reqwest::Client,
+    url: String,
+    address: Address,
+}
+
+impl RemoteSigner {
+    /// Checks that the signer manages `KEEPER_ADDRESS`, when it lists its accounts
+    async fn check_account(&self) -> anyhow::Result<()> {
+        let accounts = match self.call("eth_accounts", json!([])).await {
+            Ok(accounts) => accounts,
+            Err(RemoteError::Rpc(message)) => {
+                tracing::debug!(error = %message, "remote signer does not list accounts");
+                return Ok(());
+            }
+            Err(RemoteError::Transport(err)) => return Err(err),
+        };
+        let managed = accounts.as_array().is_some_and(|accounts| {
+            accounts.iter().any(|account| {
+                account
+                    .as_str()
+                    .and_then(|account| Address::from_str(account).ok())
+                    == Some(self.address)
+            })
+        });
+        if !managed {
+            anyhow::bail!(
+                "remote signer does not manage KEEPER_ADDRESS {:#x}",
+                self.address
+            );
+        }
+        Ok(())
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> anyhow::Result<Bytes> {
+        let request = json!({
+            "from": self.address,
+            "to": tx.to_addr(),
+            "gas": tx.gas(),
+            "gasPrice": tx.gas_price(),
+            "nonce": tx.nonce(),
+            "value": tx.value().copied().unwrap_or_default(),
+            "data": tx.data().cloned().unwrap_or_default(),
+            "chainId": tx.chain_id(),
+        });
+        let result = self
+            .call("eth_signTransaction", json!([request]))
+            .await
+            .map_err(|err| match err {
+                RemoteError::Rpc(message) => anyhow::anyhow!("remote signer refused: {message}"),
+                RemoteError::Transport(err) => err,
+            })?;
+        // Web3Signer returns the raw transaction; Clef and geth wrap it as {raw, tx}
+        let raw = result
+            .as_str()
+            .or_else(|| result["raw"].as_str())
+            .context("remote signer returned no signed transaction")?;
+        let raw = Bytes::from_str(raw).context("remote signer returned invalid hex")?;
+        self.verify(tx, &raw)?;
+        Ok(raw)
+    }
+
+    /// Ensures the signed transaction is the one requested, signed by our address
+    fn verify(&self, requested: &TypedTransaction, raw: &Bytes) -> anyhow::Result<()> {
+        let (signed, signature) = TypedTransaction::decode_signed(&Rlp::new(raw))
+            .context("remote signer returned an undecodable transaction")?;
+        let signer = signature
+            .recover(signed.sighash())
+            .context("remote signer returned an invalid signature")?;
+        let matches = signer == self.address
+            && signed.nonce() == requested.nonce()
+            && signed.to_addr() == requested.to_addr()
+            && signed.data() == requested.data()
+            && signed.chain_id() == requested.chain_id();
+        if !matches {
+            anyhow::bail!("remote signer returned a different transaction than requested");
+        }
+        Ok(())
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RemoteError> {
+        let response: Value = self
+            .http
+            .post(&self.url)
+            .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("remote signer request failed")
+            .map_err(RemoteError::Transport)?
+            .json()
+            .await
+            .context("remote signer returned an invalid response")
+            .map_err(RemoteError::Transport)?;
+        if let Some(error) = response.get("error") {
+            return Err(RemoteError::Rpc(
+                error["message"]
+                    .as_str()
+                    .unwrap_or("unknown error")
+                    .to_string(),
+            ));
+        }
+        Ok(response["result"].clone())
+    }
+}
+
+enum RemoteError {
+    /// The signer answered with a JSON-RPC error
+    Rpc(String),
+    /// The signer could not be reached or answered garbage
+    Transport(anyhow::Error),
+}
+
+/// Hash of a raw signed transaction
+pub fn transaction_hash(raw: &Bytes) -> H256 {
+    H256::from(keccak256(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use ethers::core::k256::ecdsa::SigningKey;
+    use ethers::core::k256::ecdsa::signature::hazmat::PrehashSigner;
+    use ethers::types::RecoveryMessage;
+
+    const KMS_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    /// KMS `Sign` stand-in returning the high-s form of each signature
+    async fn kms_sign(
+        State(key): State<SigningKey>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Json<Value> {
+        assert_eq!(headers["x-amz-target"], "TrentService.Sign");
+        assert!(
+            headers["authorization"]
+                .to_str()
+                .unwrap()
+                .starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/")
+        );
+        // Sent as `application/x-amz-json-1.1`, which the `Json` extractor rejects
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let digest = BASE64.decode(body["Message"].as_str().unwrap()).unwrap();
+        let signature: EcdsaSignature = key.sign_prehash(&digest).unwrap();
+        let (r, s) = signature.split_scalars();
+        let high_s = EcdsaSignature::from_scalars(r, -s).unwrap();
+        assert!(high_s.normalize_s().is_some());
+        Json(json!({ "Signature": BASE64.encode(high_s.to_der()) }))
+    }
+
+    #[tokio::test]
+    async fn normalizes_a_high_s_kms_signature() {
+        let key = SigningKey::from_slice(&hex::decode(KMS_KEY).unwrap()).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let kms = Router::new()
+            .route("/", post(kms_sign))
+            .with_state(key.clone());
+        tokio::spawn(async move { axum::serve(listener, kms).await.unwrap() });
+
+        let public_key = *key.verifying_key();
+        let signer = KmsSigner {
+            client: KmsClient {
+                http: reqwest::Client::new(),
+                key_id: "test-key".to_string(),
+                region: "us-east-1".to_string(),
+                endpoint,
+                credentials: CredentialProvider::fixed(
+                    "AKIDEXAMPLE".to_string(),
+                    "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+                ),
+            },
+            address: public_key_to_address(&public_key),
+            public_key,
+        };
+        assert_eq!(
+            signer.address,
+            Address::from_str("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23").unwrap()
+        );
+
+        let digest = H256::from(keccak256(b"keeper transaction"));
+        let signature = signer.sign_digest(digest, 31337).await.unwrap();
+        let half_order = U256::from_str_radix(
+            "7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0",
+            16,
+        )
+        .unwrap();
+        assert!(signature.s <= half_order);
+        assert!([31337 * 2 + 35, 31337 * 2 + 36].contains(&signature.v));
+        assert_eq!(
+            signature.recover(RecoveryMessage::Hash(digest)).unwrap(),
+            signer.address
+        );
+    }
+}
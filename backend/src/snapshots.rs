@@ -0,0 +1,165 @@
+//! Response snapshots of terminal raffles
+//!
+//! Most detail, proof and purchases requests are for historical raffles, whose responses
+//! can never change again. A raffle is terminal when it is `FINALIZED` with its payout
+//! checked (see [`crate::payouts`]) or `REFUNDING`/`CANCELED` with nothing left in the
+//! pot, its `end_time` has passed (the countdown fields stopped), and its last indexed
+//! change (`updated_block`) is at or below the chain's finalized block, so no reorg can
+//! undo it, and not after the indexer cursor (which an admin may have moved back to
+//! re-index it). Without finality from the RPC node nothing is terminal.
+//!
+//! The first default request (no `?format=` or paging parameters) for a terminal raffle
+//! stores the serialized body in `raffle_snapshots`; later requests are answered from it
+//! after a single primary key lookup instead of the live queries, and get the route's
+//! finalized cache lifetime. Snapshots carry a fingerprint of [`FORMAT_VERSION`] and the
+//! config the bodies depend on, so a changed explorer URL or response shape rebuilds
+//! them. Blocklist changes drop the purchases snapshots, whose `buyer_blocked` flags
+//! they affect ([`drop_purchases`]), and cursor moves drop them all ([`drop_all`]).
+//! Snapshots are only served while their raffle is terminal.
+
+use crate::config::AppConfig;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Row};
+
+/// Bump when the JSON of a snapshotted response changes, so stored bodies are rebuilt
+const FORMAT_VERSION: u32 = 1;
+
+/// Condition on `raffles_all r` for raffles whose responses can't change any more
+const TERMINAL_SQL: &str = "(
+        (r.status = 'FINALIZED' AND EXISTS (
+            SELECT 1 FROM payouts p WHERE p.raffle_id = r.raffle_id AND p.confirmed IS NOT NULL
+        ))
+        OR (r.status IN ('REFUNDING', 'CANCELED') AND r.pot = 0)
+    )
+    AND (r.end_time IS NULL OR r.end_time <= now())
+    AND block_is_finalized(r.updated_block)
+    AND r.updated_block <= (SELECT last_processed_block FROM indexer_state WHERE id = 1)";
+
+/// Response a snapshot stands in for
+#[derive(Clone, Copy)]
+pub(crate) enum Kind {
+    /// `GET /v1/raffles/:raffle_id`
+    Details,
+    /// `GET /v1/raffles/:raffle_id/proof`
+    Proof,
+    /// The first page of `GET /v1/raffles/:raffle_id/purchases`
+    Purchases,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Details => "details",
+            Kind::Proof => "proof",
+            Kind::Purchases => "purchases",
+        }
+    }
+}
+
+/// A stored response body
+pub(crate) struct Snapshot {
+    pub(crate) body: String,
+    /// `Last-Modified` of the response, if it has one
+    pub(crate) last_modified: Option<DateTime<Utc>>,
+}
+
+/// Result of [`lookup`]
+pub(crate) struct Lookup {
+    /// The stored response, if there is a current one
+    pub(crate) snapshot: Option<Snapshot>,
+    /// Whether the raffle is terminal, so a freshly built response should be stored
+    pub(crate) terminal: bool,
+}
+
+/// Fingerprint of the snapshot format and the config response bodies depend on
+fn fingerprint(config: &AppConfig) -> String {
+    format!("{FORMAT_VERSION}:{}", config.explorer_base_url)
+}
+
+/// Reads a raffle's current snapshot of `kind`, and whether the raffle is terminal
+///
+/// Raffles that aren't indexed or terminal are reported without a snapshot.
+pub(crate) async fn lookup(
+    db: &PgPool,
+    config: &AppConfig,
+    raffle_id: i64,
+    kind: Kind,
+) -> Result<Lookup, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT s.body, s.last_modified, t.terminal
+         FROM raffles_all r
+         CROSS JOIN LATERAL (SELECT {TERMINAL_SQL} AS terminal) t
+         LEFT JOIN raffle_snapshots s
+           ON t.terminal AND s.raffle_id = r.raffle_id AND s.kind = $2 AND s.fingerprint = $3
+         WHERE r.raffle_id = $1"
+    ))
+    .bind(raffle_id)
+    .bind(kind.as_str())
+    .bind(fingerprint(config))
+    .fetch_optional(db)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(Lookup {
+            snapshot: None,
+            terminal: false,
+        });
+    };
+    let body: Option<String> = row.try_get("body")?;
+    let last_modified: Option<DateTime<Utc>> = row.try_get("last_modified")?;
+    Ok(Lookup {
+        snapshot: body.map(|body| Snapshot {
+            body,
+            last_modified,
+        }),
+        terminal: row.try_get("terminal")?,
+    })
+}
+
+/// Stores (or replaces an outdated) snapshot of a terminal raffle's response
+pub(crate) async fn store(
+    db: &PgPool,
+    config: &AppConfig,
+    raffle_id: i64,
+    kind: Kind,
+    snapshot: &Snapshot,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO raffle_snapshots (raffle_id, kind, fingerprint, body, last_modified)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (raffle_id, kind) DO UPDATE
+         SET fingerprint = EXCLUDED.fingerprint,
+             body = EXCLUDED.body,
+             last_modified = EXCLUDED.last_modified,
+             created_at = now()",
+    )
+    .bind(raffle_id)
+    .bind(kind.as_str())
+    .bind(fingerprint(config))
+    .bind(&snapshot.body)
+    .bind(snapshot.last_modified)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Drops every purchases snapshot, after the address blocklist changed
+pub(crate) async fn drop_purchases(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let dropped = sqlx::query("DELETE FROM raffle_snapshots WHERE kind = $1")
+        .bind(Kind::Purchases.as_str())
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(dropped)
+}
+
+/// Drops every snapshot, when the indexer cursor is moved to re-index blocks
+pub(crate) async fn drop_all(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<u64, sqlx::Error> {
+    let dropped = sqlx::query("DELETE FROM raffle_snapshots")
+        .execute(&mut **db_tx)
+        .await?
+        .rows_affected();
+    Ok(dropped)
+}
@@ -3,7 +3,18 @@
 //! Shared state passed to all Axum handlers via the [`axum::extract::State`] extractor.
 //! Contains the database pool and validated configuration.
 
+use crate::api_keys::ApiKeyGuard;
+use crate::cache::SwrCache;
+use crate::chain::ChainReader;
 use crate::config::AppConfig;
+use crate::geo::GeoScreen;
+use crate::indexer::IndexerStatus;
+use crate::live::LiveEvent;
+use crate::mempool::PendingPurchases;
+use crate::tasks::TaskGroup;
+use ethers::signers::LocalWallet;
+use std::sync::Arc;
+use tokio::sync::broadcast;
 
 /// Shared application state for Axum handlers.
 ///
@@ -22,6 +33,42 @@ pub struct AppState {
     /// PostgreSQL connection pool.
     pub db: sqlx::PgPool,
 
+    /// Pool of the indexer and background jobs; handlers only read its utilization.
+    pub indexer_db: sqlx::PgPool,
+
     /// Application configuration loaded from environment.
     pub config: AppConfig,
+
+    /// Read-only contract access for on-chain fallbacks.
+    pub chain: ChainReader,
+
+    /// Signer for result attestations (`None` when `ATTESTATION_SIGNING_KEY` is unset).
+    pub attestation_signer: Option<LocalWallet>,
+
+    /// Unconfirmed purchases seen in the mempool (`None` when the watcher is disabled).
+    pub pending: Option<PendingPurchases>,
+
+    /// Publisher of live updates; WebSocket connections subscribe to it.
+    pub live: broadcast::Sender<LiveEvent>,
+
+    /// Indexer progress (chain head) published by the background indexer.
+    pub indexer: IndexerStatus,
+
+    /// Stale-while-revalidate cache for `GET /v1/raffles` (`None` when disabled).
+    pub raffle_list_cache: Option<SwrCache>,
+
+    /// Stale-while-revalidate cache for `GET /v1/embed/raffles/:raffle_id`.
+    pub embed_cache: SwrCache,
+
+    /// Stale-while-revalidate cache for `GET /v1/network`.
+    pub network_cache: SwrCache,
+
+    /// Lookup cache and rate limiter for issued API keys.
+    pub api_keys: ApiKeyGuard,
+
+    /// Health of the deployment's supervised background tasks.
+    pub tasks: TaskGroup,
+
+    /// Country screening of API requests (`None` when no countries are restricted).
+    pub geo: Option<Arc<GeoScreen>>,
 }
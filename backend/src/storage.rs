@@ -0,0 +1,283 @@
+//! S3-compatible object storage client
+//!
+//! Used by the Parquet export job (see [`crate::export`]) and the raw event backups
+//! (see [`crate::backup`]). Works with AWS S3, GCS through its XML API with HMAC keys,
+//! MinIO, R2, ...: objects are addressed path-style and every request is SigV4-signed
+//! (see [`crate::aws`]).
+
+use crate::aws::{self, CredentialProvider};
+use crate::config::ObjectStoreConfig;
+use anyhow::Context;
+use chrono::Utc;
+
+/// Timeout of a single request (one object or one part)
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// SigV4-signed client for one bucket
+pub struct Bucket {
+    http: reqwest::Client,
+    /// Scheme and host of the endpoint, e.g. `https://s3.us-east-1.amazonaws.com`
+    origin: String,
+    host: String,
+    /// URI-encoded path of the bucket, e.g. `/exports`
+    base_path: String,
+    region: String,
+    credentials: CredentialProvider,
+}
+
+/// An object uploaded in parts, completed by [`Bucket::complete_multipart`]
+pub struct MultipartUpload {
+    key: String,
+    upload_id: String,
+    /// ETags of the uploaded parts, in part order
+    etags: Vec<String>,
+}
+
+impl Bucket {
+    pub fn new(store: &ObjectStoreConfig) -> anyhow::Result<Self> {
+        let endpoint =
+            reqwest::Url::parse(&store.endpoint).context("storage endpoint is not a valid URL")?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => anyhow::bail!("storage endpoint has no host"),
+        };
+        let base_path = format!(
+            "{}/{}",
+            endpoint.path().trim_end_matches('/'),
+            uri_encode(&store.bucket)
+        );
+        let credentials = match &store.access_key {
+            Some((id, secret)) => CredentialProvider::fixed(id.clone(), secret.clone()),
+            None => CredentialProvider::from_chain(),
+        };
+        Ok(Self {
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("failed to build HTTP client")?,
+            origin: format!("{}://{host}", endpoint.scheme()),
+            host,
+            base_path,
+            region: store.region.clone(),
+            credentials,
+        })
+    }
+
+    /// Uploads an object with a single PUT
+    pub async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> anyhow::Result<()> {
+        self.send("PUT", key, &[], Some(content_type), body)
+            .await
+            .context("upload request failed")?;
+        Ok(())
+    }
+
+    /// Starts downloading an object; read the body with [`reqwest::Response::chunk`]
+    pub async fn get(&self, key: &str) -> anyhow::Result<reqwest::Response> {
+        self.send("GET", key, &[], None, Vec::new())
+            .await
+            .context("download request failed")
+    }
+
+    /// Starts an upload of an object too large to buffer
+    ///
+    /// Every part except the last must be at least 5 MiB.
+    pub async fn start_multipart(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> anyhow::Result<MultipartUpload> {
+        let response = self
+            .send(
+                "POST",
+                key,
+                &[("uploads", "")],
+                Some(content_type),
+                Vec::new(),
+            )
+            .await
+            .context("failed to start multipart upload")?;
+        let body = response.text().await?;
+        let upload_id = xml_element(&body, "UploadId")
+            .ok_or_else(|| anyhow::anyhow!("storage returned no upload ID"))?;
+        Ok(MultipartUpload {
+            key: key.to_string(),
+            upload_id: upload_id.to_string(),
+            etags: Vec::new(),
+        })
+    }
+
+    /// Uploads the next part of a multipart upload
+    pub async fn upload_part(
+        &self,
+        upload: &mut MultipartUpload,
+        body: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let part_number = (upload.etags.len() + 1).to_string();
+        let response = self
+            .send(
+                "PUT",
+                &upload.key,
+                &[
+                    ("partNumber", &part_number),
+                    ("uploadId", &upload.upload_id),
+                ],
+                None,
+                body,
+            )
+            .await
+            .with_context(|| format!("failed to upload part {part_number}"))?;
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|etag| etag.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("storage returned no ETag for part {part_number}"))?;
+        upload.etags.push(etag.to_string());
+        Ok(())
+    }
+
+    /// Assembles the uploaded parts into the object
+    pub async fn complete_multipart(&self, upload: MultipartUpload) -> anyhow::Result<()> {
+        let parts: String = upload
+            .etags
+            .iter()
+            .enumerate()
+            .map(|(index, etag)| {
+                format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+                    index + 1
+                )
+            })
+            .collect();
+        let body = format!("<CompleteMultipartUpload>{parts}</CompleteMultipartUpload>");
+        let response = self
+            .send(
+                "POST",
+                &upload.key,
+                &[("uploadId", &upload.upload_id)],
+                Some("application/xml"),
+                body.into_bytes(),
+            )
+            .await
+            .context("failed to complete multipart upload")?;
+        // S3 can report a failed completion with a 200 status
+        let body = response.text().await?;
+        if let Some(code) = xml_element(&body, "Code") {
+            anyhow::bail!("storage failed to complete multipart upload: {code}");
+        }
+        Ok(())
+    }
+
+    /// Discards the parts of an upload that won't be completed
+    pub async fn abort_multipart(&self, upload: MultipartUpload) -> anyhow::Result<()> {
+        self.send(
+            "DELETE",
+            &upload.key,
+            &[("uploadId", &upload.upload_id)],
+            None,
+            Vec::new(),
+        )
+        .await
+        .context("failed to abort multipart upload")?;
+        Ok(())
+    }
+
+    /// Sends a signed request for an object, failing on non-success statuses
+    ///
+    /// `query` must be sorted by name.
+    async fn send(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> anyhow::Result<reqwest::Response> {
+        let credentials = self.credentials.get(&self.http).await?;
+        let path = format!(
+            "{}/{}",
+            self.base_path,
+            key.split('/').map(uri_encode).collect::<Vec<_>>().join("/")
+        );
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", uri_encode(name), uri_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let payload_hash = aws::sha256_hex(&body);
+
+        // Canonical headers must be sorted by name
+        let now = Utc::now();
+        let mut headers = Vec::new();
+        if let Some(content_type) = content_type {
+            headers.push(("content-type", content_type.to_string()));
+        }
+        headers.extend([
+            ("host", self.host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", aws::amz_date(now)),
+        ]);
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = aws::authorization(
+            &credentials,
+            &self.region,
+            "s3",
+            &aws::SignedRequest {
+                method,
+                path: &path,
+                query: &query,
+                headers: &headers,
+                payload_hash: &payload_hash,
+            },
+            now,
+        );
+
+        let mut url = format!("{}{path}", self.origin);
+        if !query.is_empty() {
+            url = format!("{url}?{query}");
+        }
+        let method = reqwest::Method::from_bytes(method.as_bytes())?;
+        let mut request = self
+            .http
+            .request(method, url)
+            .header("authorization", authorization)
+            .body(body);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            // Error bodies are XML with a code and message, never credentials
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "storage returned {status}: {}",
+                body.chars().take(300).collect::<String>()
+            );
+        }
+        Ok(response)
+    }
+}
+
+/// Percent-encodes one path segment or query component as SigV4 expects (RFC 3986
+/// unreserved kept)
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Text of the first `<name>` element of an S3 XML response
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    Some(&xml[start..end])
+}
@@ -0,0 +1,132 @@
+//! Stuck raffle detection
+//!
+//! A raffle is stuck when it has been `CLOSED` for longer than `STUCK_CLOSED_SLA_SECS` or
+//! `RANDOM_REQUESTED` for longer than `STUCK_RANDOM_REQUESTED_SLA_SECS`: the keeper or the
+//! randomness provider hasn't moved it on. Time is counted from the block time of the
+//! transition (`raffle_status_changes`), or the raffle's last update when it wasn't
+//! recorded. An SLA of 0 disables the check for that status. `GET /v1/raffles/stuck`
+//! lists stuck raffles ([`find`]).
+//!
+//! With `STUCK_WEBHOOK_URL` set, this job checks every [`CHECK_INTERVAL`] and queues a
+//! `stuck_alerts` row for every raffle newly stuck in a status (once per status), which the notifier POSTs
+//! (see [`crate::notify`]). Nothing is queued while the indexer is more than
+//! [`MAX_LAG_BLOCKS`] behind, as the raffle may have moved on in a block not indexed yet.
+
+use crate::config::AppConfig;
+use crate::indexer::IndexerStatus;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+/// How often the job looks for stuck raffles
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Largest indexer lag at which stuck raffles are alerted on
+const MAX_LAG_BLOCKS: u64 = 50;
+
+/// Raffles past the SLA of their status (`$1`: CLOSED SLA, `$2`: RANDOM_REQUESTED SLA)
+const STUCK_RAFFLES_SQL: &str = "
+    SELECT r.raffle_id, r.raffle_address, r.status, r.keeper, s.since, sla.secs AS sla_secs,
+        EXTRACT(EPOCH FROM now() - s.since)::bigint AS stuck_secs
+    FROM raffles r
+    JOIN (VALUES ('CLOSED', $1::bigint), ('RANDOM_REQUESTED', $2::bigint)) AS sla (status, secs)
+      ON sla.status = r.status
+    CROSS JOIN LATERAL (
+        SELECT COALESCE(
+            (SELECT MAX(c.block_time) FROM raffle_status_changes c
+             WHERE c.raffle_id = r.raffle_id AND c.status = r.status),
+            r.updated_at
+        ) AS since
+    ) s
+    WHERE sla.secs > 0 AND s.since <= now() - make_interval(secs => sla.secs)";
+
+/// A raffle past the SLA of its status
+#[derive(Serialize)]
+pub struct StuckRaffle {
+    pub raffle_id: i64,
+    pub raffle_address: String,
+    pub status: String,
+    pub keeper: Option<String>,
+    /// When the raffle reached `status`
+    pub since: DateTime<Utc>,
+    pub stuck_secs: i64,
+    pub sla_secs: i64,
+}
+
+/// Lists stuck raffles, longest stuck first
+pub async fn find(db: &PgPool, config: &AppConfig, limit: i64) -> sqlx::Result<Vec<StuckRaffle>> {
+    let rows = sqlx::query(&format!(
+        "{STUCK_RAFFLES_SQL}
+         ORDER BY s.since, r.raffle_id
+         LIMIT $3"
+    ))
+    .bind(config.stuck_closed_sla_secs as i64)
+    .bind(config.stuck_random_requested_sla_secs as i64)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(StuckRaffle {
+                raffle_id: row.try_get("raffle_id")?,
+                raffle_address: row.try_get("raffle_address")?,
+                status: row.try_get("status")?,
+                keeper: row.try_get("keeper")?,
+                since: row.try_get("since")?,
+                stuck_secs: row.try_get("stuck_secs")?,
+                sla_secs: row.try_get("sla_secs")?,
+            })
+        })
+        .collect()
+}
+
+/// Runs the stuck check until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig, indexer: IndexerStatus) -> anyhow::Result<()> {
+    loop {
+        if let Err(err) = enqueue_alerts(&db, &config, &indexer).await {
+            tracing::warn!(error = %format!("{err:#}"), "stuck check failed");
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+/// Queues an alert for every stuck raffle not alerted on in its current status yet,
+/// returning how many were queued
+pub(crate) async fn enqueue_alerts(
+    db: &PgPool,
+    config: &AppConfig,
+    indexer: &IndexerStatus,
+) -> anyhow::Result<usize> {
+    let indexed_block: Option<i64> =
+        sqlx::query_scalar("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+            .fetch_optional(db)
+            .await
+            .context("failed to read indexer checkpoint")?;
+    let caught_up = indexer
+        .head_block()
+        .zip(indexed_block)
+        .is_some_and(|(head, indexed)| head.saturating_sub(indexed as u64) <= MAX_LAG_BLOCKS);
+    if !caught_up {
+        tracing::debug!("indexer behind, stuck check postponed");
+        return Ok(0);
+    }
+
+    let queued: Vec<(i64, String, DateTime<Utc>)> = sqlx::query_as(&format!(
+        "INSERT INTO stuck_alerts (raffle_id, raffle_status, since)
+         SELECT raffle_id, status, since FROM ({STUCK_RAFFLES_SQL}) stuck
+         ON CONFLICT (raffle_id, raffle_status) DO NOTHING
+         RETURNING raffle_id, raffle_status, since"
+    ))
+    .bind(config.stuck_closed_sla_secs as i64)
+    .bind(config.stuck_random_requested_sla_secs as i64)
+    .fetch_all(db)
+    .await
+    .context("failed to enqueue stuck alerts")?;
+    for (raffle_id, status, since) in &queued {
+        tracing::warn!(raffle_id, %status, %since, "raffle stuck past its SLA");
+    }
+    Ok(queued.len())
+}
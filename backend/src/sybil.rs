@@ -0,0 +1,256 @@
+//! Sybil clustering
+//!
+//! One operator spreading purchases over many wallets inflates "unique participant"
+//! numbers. Every `SYBIL_INTERVAL_SECS` this job links buyers on two signals and
+//! stores the connected groups (two or more addresses) in `address_clusters`:
+//! - `co_purchase`: two buyers bought tickets of the same raffle in the same block in
+//!   at least `SYBIL_MIN_SHARED_BLOCKS` different raffles, as scripted wallets do
+//! - `shared_sender`: purchases for different buyers were sent by the same account
+//!   other than the buyer, which pays for (funds) them; the sender is part of the
+//!   cluster when it bought tickets itself. Senders come from transaction receipts,
+//!   looked up only with `SYBIL_SENDER_LOOKUP` set, up to [`SENDER_BATCH`] per run
+//!   and cached in `purchase_tx_senders`
+//!
+//! Links are transitive: a cluster is a connected component of the link graph, named
+//! after its lowest address. Every run recomputes all clusters in one transaction.
+//! The flags are heuristics for review by creators and admins (shown on
+//! `GET /v1/raffles/:raffle_id/participants?include_clusters=true`), not proof:
+//! friends buying together in a busy block can be linked too.
+
+use crate::config::AppConfig;
+use crate::indexer::ChainClient;
+use crate::rpc::RpcBudget;
+use alloy::primitives::B256;
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Timeout for individual RPC calls
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Purchase transactions whose sender is looked up per run
+const SENDER_BATCH: i64 = 500;
+
+/// Link signal names, as stored in `address_clusters.reasons`
+const CO_PURCHASE: &str = "co_purchase";
+const SHARED_SENDER: &str = "shared_sender";
+
+/// Cluster flag of a participant
+#[derive(Clone, Serialize)]
+pub struct ClusterFlag {
+    /// Lowest address of the cluster
+    pub id: String,
+    /// Addresses in the cluster
+    pub size: i32,
+    /// Signals that linked it: `co_purchase`, `shared_sender`
+    pub reasons: Vec<String>,
+}
+
+/// Runs the sybil job until the task is aborted
+pub async fn run(db: PgPool, config: AppConfig, rpc_budget: RpcBudget) -> anyhow::Result<()> {
+    let provider = rpc_budget
+        .alloy_provider(&config.rpc_url, "sybil")
+        .context("invalid RPC_URL")?;
+    let interval = Duration::from_secs(config.sybil_interval_secs);
+    loop {
+        if config.sybil_sender_lookup {
+            match lookup_senders(&db, &provider).await {
+                Ok(0) => {}
+                Ok(looked_up) => tracing::debug!(looked_up, "purchase senders looked up"),
+                Err(err) => {
+                    tracing::warn!(error = %format!("{err:#}"), "purchase sender lookup failed")
+                }
+            }
+        }
+        match refresh(&db, &config).await {
+            Ok(clustered) => tracing::debug!(clustered, "sybil clusters refreshed"),
+            Err(err) => tracing::warn!(error = %format!("{err:#}"), "sybil clustering failed"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Stores the senders of purchase transactions not looked up yet, returning how many
+pub(crate) async fn lookup_senders<C: ChainClient>(
+    db: &PgPool,
+    chain: &C,
+) -> anyhow::Result<usize> {
+    let tx_hashes: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT p.tx_hash
+         FROM purchases_all p
+         WHERE NOT EXISTS (SELECT 1 FROM purchase_tx_senders s WHERE s.tx_hash = p.tx_hash)
+         LIMIT $1",
+    )
+    .bind(SENDER_BATCH)
+    .fetch_all(db)
+    .await
+    .context("failed to fetch purchase transactions")?;
+
+    let mut looked_up = 0;
+    for tx_hash in tx_hashes {
+        let receipt = tokio::time::timeout(
+            RPC_TIMEOUT,
+            chain
+                .transaction_receipt(B256::from_str(&tx_hash).context("invalid purchase tx hash")?),
+        )
+        .await
+        .context("get_transaction_receipt timed out")?
+        .with_context(|| format!("failed to fetch receipt of {tx_hash}"))?;
+        // Unknown to the node (yet); retried on the next run
+        let Some(receipt) = receipt else {
+            continue;
+        };
+
+        sqlx::query(
+            "INSERT INTO purchase_tx_senders (tx_hash, sender) VALUES ($1, $2)
+             ON CONFLICT (tx_hash) DO NOTHING",
+        )
+        .bind(&tx_hash)
+        .bind(format!("{:#x}", receipt.from))
+        .execute(db)
+        .await
+        .context("failed to store purchase sender")?;
+        looked_up += 1;
+    }
+    Ok(looked_up)
+}
+
+/// Recomputes every cluster, returning how many addresses are clustered
+pub(crate) async fn refresh(db: &PgPool, config: &AppConfig) -> anyhow::Result<usize> {
+    let mut links = Links::default();
+
+    let pairs = sqlx::query(
+        "WITH buys AS (SELECT DISTINCT raffle_id, block_number, buyer FROM purchases_all)
+         SELECT a.buyer AS a, b.buyer AS b
+         FROM buys a
+         JOIN buys b
+           ON b.raffle_id = a.raffle_id AND b.block_number = a.block_number AND b.buyer > a.buyer
+         GROUP BY a.buyer, b.buyer
+         HAVING COUNT(DISTINCT a.raffle_id) >= $1",
+    )
+    .bind(config.sybil_min_shared_blocks)
+    .fetch_all(db)
+    .await
+    .context("failed to find co-purchases")?;
+    for row in pairs {
+        links.link(row.try_get("a")?, row.try_get("b")?, CO_PURCHASE);
+    }
+
+    let groups = sqlx::query(
+        "SELECT s.sender,
+            array_agg(DISTINCT p.buyer) AS buyers,
+            EXISTS (SELECT 1 FROM purchases_all o WHERE o.buyer = s.sender) AS sender_bought
+         FROM purchases_all p
+         JOIN purchase_tx_senders s ON s.tx_hash = p.tx_hash
+         WHERE s.sender <> p.buyer
+         GROUP BY s.sender",
+    )
+    .fetch_all(db)
+    .await
+    .context("failed to group purchases by sender")?;
+    for row in groups {
+        let mut members: Vec<String> = row.try_get("buyers")?;
+        if row.try_get("sender_bought")? {
+            members.push(row.try_get("sender")?);
+        }
+        for pair in members.windows(2) {
+            links.link(pair[0].clone(), pair[1].clone(), SHARED_SENDER);
+        }
+    }
+
+    let clusters = links.clusters();
+    let mut db_tx = db.begin().await.context("failed to begin transaction")?;
+    // Replicas refreshing at the same time would otherwise both insert
+    sqlx::query("LOCK TABLE address_clusters IN EXCLUSIVE MODE")
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to lock clusters")?;
+    sqlx::query("DELETE FROM address_clusters")
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to clear clusters")?;
+    let mut clustered = 0;
+    for (members, flag) in &clusters {
+        sqlx::query(
+            "INSERT INTO address_clusters (address, cluster_id, cluster_size, reasons)
+             SELECT address, $2, $3, $4 FROM unnest($1::text[]) AS address",
+        )
+        .bind(members)
+        .bind(&flag.id)
+        .bind(flag.size)
+        .bind(&flag.reasons)
+        .execute(&mut *db_tx)
+        .await
+        .context("failed to store cluster")?;
+        clustered += members.len();
+    }
+    db_tx.commit().await.context("failed to commit clusters")?;
+    Ok(clustered)
+}
+
+/// Union-find over addresses, remembering which signals linked each component
+#[derive(Default)]
+struct Links {
+    parent: BTreeMap<String, String>,
+    reasons: BTreeMap<String, BTreeSet<&'static str>>,
+}
+
+impl Links {
+    fn root(&mut self, address: &str) -> String {
+        let mut root = address.to_string();
+        while let Some(parent) = self.parent.get(&root).filter(|parent| **parent != root) {
+            root = parent.clone();
+        }
+        // Path compression
+        let mut node = address.to_string();
+        while node != root {
+            let next = self.parent.insert(node, root.clone()).unwrap_or_default();
+            node = next;
+        }
+        root
+    }
+
+    fn link(&mut self, a: String, b: String, reason: &'static str) {
+        self.parent.entry(a.clone()).or_insert_with(|| a.clone());
+        self.parent.entry(b.clone()).or_insert_with(|| b.clone());
+        let (root_a, root_b) = (self.root(&a), self.root(&b));
+        let mut reasons = self.reasons.remove(&root_a).unwrap_or_default();
+        if root_a != root_b {
+            reasons.extend(self.reasons.remove(&root_b).unwrap_or_default());
+            self.parent.insert(root_b, root_a.clone());
+        }
+        reasons.insert(reason);
+        self.reasons.insert(root_a, reasons);
+    }
+
+    /// Members (sorted) and flag of every cluster
+    fn clusters(mut self) -> Vec<(Vec<String>, ClusterFlag)> {
+        let addresses: Vec<String> = self.parent.keys().cloned().collect();
+        let mut members: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for address in addresses {
+            let root = self.root(&address);
+            members.entry(root).or_default().push(address);
+        }
+        members
+            .into_iter()
+            .map(|(root, members)| {
+                let flag = ClusterFlag {
+                    // Keys iterate in order, so the first member is the lowest
+                    id: members[0].clone(),
+                    size: members.len() as i32,
+                    reasons: self
+                        .reasons
+                        .get(&root)
+                        .into_iter()
+                        .flatten()
+                        .map(|reason| reason.to_string())
+                        .collect(),
+                };
+                (members, flag)
+            })
+            .collect()
+    }
+}
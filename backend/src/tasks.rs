@@ -0,0 +1,352 @@
+//! Supervised background tasks
+//!
+//! Every deployment runs long-lived jobs next to the API: the indexer, the keeper, the
+//! webhook notifier and the scheduled digest, archive and export jobs. They all run on
+//! one [`TaskManager`], a `JoinSet` that main owns, instead of detached `tokio::spawn`
+//! calls whose failures were only logged.
+//!
+//! Each task has a [`Restart`] policy. A task that may fail transiently is restarted
+//! with exponential backoff; a task the deployment can't work without (the indexer)
+//! is fatal, and its failure shuts the process down so the orchestrator restarts it.
+//! Panics count as failures. The state of every task is recorded in its deployment's
+//! [`TaskGroup`], which `GET /v1/status` reports.
+
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::{Id, JoinSet};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, Span};
+
+/// First delay before restarting a failed task
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between restarts; a task that ran this long starts over at [`MIN_BACKOFF`]
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// What happens when a task fails (returns an error or panics)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restart {
+    /// The failure is fatal: main shuts the process down with the error
+    Never,
+    /// Restart after a delay doubling from 1 second up to 5 minutes
+    OnFailure,
+}
+
+/// How a task stops on shutdown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// Dropped as soon as shutdown starts
+    Abort,
+    /// Watches the shutdown token and gets the rest of the grace period to return
+    Graceful,
+}
+
+/// Lifecycle state of a supervised task
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Failed and waiting out its backoff
+    Restarting,
+    /// Returned without error, or stopped for shutdown
+    Stopped,
+    /// Failed fatally
+    Failed,
+}
+
+/// Health of one task, as reported by `GET /v1/status`
+///
+/// Error messages are only logged: they can carry RPC URLs or webhook endpoints.
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskSnapshot {
+    pub name: &'static str,
+    pub state: TaskState,
+    /// Restarts since the process started
+    pub restarts: u32,
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
+
+/// The tasks of one deployment: their tracing span and health
+#[derive(Clone)]
+pub struct TaskGroup {
+    deployment: String,
+    span: Span,
+    tasks: Arc<Mutex<BTreeMap<&'static str, TaskSnapshot>>>,
+}
+
+impl TaskGroup {
+    pub fn new(deployment: &str, span: Span) -> Self {
+        Self {
+            deployment: deployment.to_string(),
+            span,
+            tasks: Arc::default(),
+        }
+    }
+
+    /// Every task of the deployment, by name
+    pub fn snapshot(&self) -> Vec<TaskSnapshot> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn update(&self, name: &'static str, apply: impl FnOnce(&mut TaskSnapshot)) {
+        let mut tasks = self.lock();
+        let task = tasks.entry(name).or_insert_with(|| TaskSnapshot {
+            name,
+            state: TaskState::Running,
+            restarts: 0,
+            last_failure_at: None,
+        });
+        apply(task);
+    }
+
+    fn set_state(&self, name: &'static str, state: TaskState) {
+        self.update(name, |task| task.state = state);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, TaskSnapshot>> {
+        // The map stays consistent even if a holder panicked
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Owns every background task of the process
+pub struct TaskManager {
+    set: JoinSet<anyhow::Result<()>>,
+    /// `deployment/name` of each task still in the set
+    labels: HashMap<Id, String>,
+    shutdown: CancellationToken,
+}
+
+impl TaskManager {
+    /// A manager whose [`Stop::Graceful`] tasks watch `shutdown`
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            set: JoinSet::new(),
+            labels: HashMap::new(),
+            shutdown,
+        }
+    }
+
+    /// Supervises the futures returned by `run` under `name` in `group`
+    ///
+    /// `run` is called again for every restart and receives the shutdown token.
+    pub fn spawn<F, Fut>(
+        &mut self,
+        group: &TaskGroup,
+        name: &'static str,
+        restart: Restart,
+        stop: Stop,
+        run: F,
+    ) where
+        F: FnMut(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        group.set_state(name, TaskState::Running);
+        let supervised = supervise(
+            group.clone(),
+            name,
+            restart,
+            stop,
+            run,
+            self.shutdown.clone(),
+        )
+        .instrument(group.span.clone());
+        let handle = self.set.spawn(supervised);
+        self.labels
+            .insert(handle.id(), format!("{}/{name}", group.deployment));
+    }
+
+    /// Waits for a task to fail fatally and returns its error
+    ///
+    /// Never completes while no task does.
+    pub async fn fatal_error(&mut self) -> anyhow::Error {
+        while let Some(joined) = self.set.join_next_with_id().await {
+            match joined {
+                Ok((id, Ok(()))) => {
+                    self.labels.remove(&id);
+                }
+                Ok((id, Err(err))) => {
+                    let label = self.labels.remove(&id).unwrap_or_default();
+                    return err.context(format!("task {label} failed"));
+                }
+                // Panics are caught by the supervisor, so this is an abort
+                Err(err) => {
+                    self.labels.remove(&err.id());
+                }
+            }
+        }
+        std::future::pending().await
+    }
+
+    /// Stops every task, waiting until `deadline` for [`Stop::Graceful`] ones
+    pub async fn shutdown(mut self, deadline: Instant) {
+        self.shutdown.cancel();
+        let drain = async {
+            while let Some(joined) = self.set.join_next_with_id().await {
+                match joined {
+                    Ok((id, result)) => {
+                        let label = self.labels.remove(&id).unwrap_or_default();
+                        if let Err(err) = result {
+                            tracing::error!(task = %label, error = %format!("{err:#}"), "task failed during shutdown");
+                        }
+                    }
+                    Err(err) => {
+                        self.labels.remove(&err.id());
+                    }
+                }
+            }
+        };
+        if tokio::time::timeout_at(deadline, drain).await.is_err() {
+            // Aborting the indexer mid-batch leaves its cursor behind committed rows;
+            // the batch is indexed again on the next start
+            let mut abandoned: Vec<&String> = self.labels.values().collect();
+            abandoned.sort();
+            tracing::warn!(tasks = ?abandoned, "grace period over, abandoning background tasks");
+            self.set.abort_all();
+        }
+    }
+}
+
+/// Runs a task until it stops, restarting it according to `restart`
+///
+/// Returns an error only for a fatal failure.
+async fn supervise<F, Fut>(
+    group: TaskGroup,
+    name: &'static str,
+    restart: Restart,
+    stop: Stop,
+    mut run: F,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()>
+where
+    F: FnMut(CancellationToken) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        let task = AssertUnwindSafe(run(shutdown.clone())).catch_unwind();
+        let outcome = match stop {
+            Stop::Graceful => task.await,
+            Stop::Abort => tokio::select! {
+                outcome = task => outcome,
+                _ = shutdown.cancelled() => Ok(Ok(())),
+            },
+        };
+        let err = match outcome {
+            Ok(Ok(())) => {
+                group.set_state(name, TaskState::Stopped);
+                if !shutdown.is_cancelled() {
+                    tracing::info!(task = name, "task stopped");
+                }
+                return Ok(());
+            }
+            Ok(Err(err)) => err,
+            Err(panic) => anyhow::anyhow!("panicked: {}", crate::recovery::panic_message(&*panic)),
+        };
+        group.update(name, |task| task.last_failure_at = Some(Utc::now()));
+
+        if restart == Restart::Never || shutdown.is_cancelled() {
+            group.set_state(name, TaskState::Failed);
+            return Err(err);
+        }
+        if started.elapsed() >= MAX_BACKOFF {
+            backoff = MIN_BACKOFF;
+        }
+        tracing::error!(
+            task = name,
+            error = %format!("{err:#}"),
+            retry_in_secs = backoff.as_secs(),
+            "task failed, restarting"
+        );
+        group.set_state(name, TaskState::Restarting);
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.cancelled() => {
+                group.set_state(name, TaskState::Stopped);
+                return Ok(());
+            }
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        group.update(name, |task| {
+            task.state = TaskState::Running;
+            task.restarts += 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn state_of(group: &TaskGroup, name: &str) -> TaskSnapshot {
+        group
+            .snapshot()
+            .into_iter()
+            .find(|task| task.name == name)
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_failed_tasks_and_propagates_fatal_ones() {
+        let shutdown = CancellationToken::new();
+        let mut manager = TaskManager::new(shutdown.clone());
+        let group = TaskGroup::new("test", Span::none());
+
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = attempts.clone();
+        manager.spawn(
+            &group,
+            "flaky",
+            Restart::OnFailure,
+            Stop::Abort,
+            move |_| {
+                let attempt = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if attempt < 2 {
+                        panic!("attempt {attempt}");
+                    }
+                    std::future::pending().await
+                }
+            },
+        );
+        manager.spawn(
+            &group,
+            "critical",
+            Restart::Never,
+            Stop::Graceful,
+            |shutdown| async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                if shutdown.is_cancelled() {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("lost the chain"))
+                }
+            },
+        );
+
+        let err = manager.fatal_error().await;
+        assert_eq!(err.to_string(), "task test/critical failed");
+        assert_eq!(state_of(&group, "critical").state, TaskState::Failed);
+
+        let flaky = state_of(&group, "flaky");
+        assert_eq!(flaky.state, TaskState::Running);
+        assert_eq!(flaky.restarts, 2);
+        assert!(flaky.last_failure_at.is_some());
+
+        manager
+            .shutdown(Instant::now() + Duration::from_secs(1))
+            .await;
+        assert_eq!(state_of(&group, "flaky").state, TaskState::Stopped);
+    }
+}
@@ -0,0 +1,420 @@
+//! Scripted in-memory chain
+//!
+//! Blocks are appended (and dropped, to simulate reorgs) by the test; the indexer
+//! reads them through [`ChainClient`] exactly as it reads a node.
+//!
+//! The chain also answers the JSON-RPC calls the keeper makes through an ethers
+//! `Provider` (nonces, gas price, raw transactions and their receipts). Broadcast
+//! transactions stay in a mempool until the test mines them.
+
+use crate::indexer::{ChainClient, TxReceipt};
+use alloy::primitives::{Address, B256, Bytes, LogData, keccak256};
+use alloy::rpc::types::{BlockNumberOrTag, Log};
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, JsonRpcError, MockError};
+use ethers::types::{H160, H256, Transaction, TransactionReceipt, U64, U256};
+use ethers::utils::rlp;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+/// Gas used by every transaction sent through the JSON-RPC side
+const MOCK_GAS_ESTIMATE: u64 = 100_000;
+
+/// A log to include in the next block; block and position fields are filled in by
+/// [`MockChain::push_block`]
+pub struct PendingLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Vec<u8>,
+    pub tx_hash: B256,
+    /// Sender reported in the transaction's receipt
+    pub from: Address,
+    /// Calldata of the transaction
+    pub input: Bytes,
+}
+
+/// In-memory chain served to the indexer
+///
+/// Cheap to clone; all clones share the same blocks.
+#[derive(Clone)]
+pub struct MockChain {
+    chain_id: u64,
+    state: Arc<Mutex<ChainState>>,
+}
+
+struct ChainState {
+    /// Number of the first block
+    first_block: u64,
+    /// Canonical blocks, in order
+    blocks: Vec<MockBlock>,
+    /// Reorgs so far, mixed into block hashes so replaced blocks get new ones
+    reorgs: u64,
+    /// Block reported for the `safe` and `finalized` tags (`None`: tags unsupported)
+    finalized: Option<u64>,
+    node: NodeState,
+}
+
+/// Accounts and transactions of the JSON-RPC side
+struct NodeState {
+    gas_price: U256,
+    /// Next nonce of each sender, counting mined transactions only
+    nonces: HashMap<H160, U256>,
+    /// Every accepted raw transaction, in broadcast order
+    sent: Vec<Transaction>,
+    /// Block and success of mined transactions
+    mined: HashMap<H256, (u64, bool)>,
+    /// Broadcasts to reject before accepting again
+    rejected_sends: usize,
+}
+
+impl NodeState {
+    /// `eth_getTransactionCount`; the pending count includes the mempool
+    fn transaction_count(&self, address: H160, pending: bool) -> U256 {
+        let mined = self.nonces.get(&address).copied().unwrap_or_default();
+        if !pending {
+            return mined;
+        }
+        self.sent
+            .iter()
+            .filter(|tx| {
+                tx.from == address && tx.nonce >= mined && !self.mined.contains_key(&tx.hash)
+            })
+            .map(|tx| tx.nonce + 1)
+            .fold(mined, U256::max)
+    }
+
+    fn receipt(&self, hash: H256) -> Option<TransactionReceipt> {
+        let (block, success) = self.mined.get(&hash)?;
+        let tx = self.sent.iter().find(|tx| tx.hash == hash)?;
+        Some(TransactionReceipt {
+            transaction_hash: hash,
+            block_number: Some(U64::from(*block)),
+            from: tx.from,
+            to: tx.to,
+            gas_used: Some(U256::from(MOCK_GAS_ESTIMATE)),
+            status: Some(U64::from(u64::from(*success))),
+            ..Default::default()
+        })
+    }
+}
+
+impl ChainState {
+    /// A transaction of a canonical block
+    fn transaction(&self, tx_hash: B256) -> Option<&MockTx> {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .find(|tx| tx.hash == tx_hash)
+    }
+}
+
+struct MockBlock {
+    number: u64,
+    hash: B256,
+    timestamp: u64,
+    logs: Vec<Log>,
+    /// Transactions in the block, in order
+    transactions: Vec<MockTx>,
+}
+
+struct MockTx {
+    hash: B256,
+    from: Address,
+    input: Bytes,
+}
+
+impl MockChain {
+    /// An empty chain whose first block will be `first_block`
+    pub fn new(chain_id: u64, first_block: u64) -> Self {
+        Self {
+            chain_id,
+            state: Arc::new(Mutex::new(ChainState {
+                first_block,
+                blocks: Vec::new(),
+                reorgs: 0,
+                finalized: None,
+                node: NodeState {
+                    gas_price: U256::exp10(9),
+                    nonces: HashMap::new(),
+                    sent: Vec::new(),
+                    mined: HashMap::new(),
+                    rejected_sends: 0,
+                },
+            })),
+        }
+    }
+
+    /// Appends a block holding `logs`, in order, and returns its number
+    pub fn push_block(&self, timestamp: u64, logs: Vec<PendingLog>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let number = state.first_block + state.blocks.len() as u64;
+        let hash = keccak256(format!("block {number} fork {}", state.reorgs));
+
+        let mut tx_hashes: Vec<B256> = Vec::new();
+        let mut transactions: Vec<MockTx> = Vec::new();
+        let logs = logs
+            .into_iter()
+            .enumerate()
+            .map(|(log_index, pending)| {
+                let tx_index = match tx_hashes.iter().position(|tx| *tx == pending.tx_hash) {
+                    Some(index) => index,
+                    None => {
+                        tx_hashes.push(pending.tx_hash);
+                        transactions.push(MockTx {
+                            hash: pending.tx_hash,
+                            from: pending.from,
+                            input: pending.input.clone(),
+                        });
+                        tx_hashes.len() - 1
+                    }
+                };
+                Log {
+                    inner: alloy::primitives::Log {
+                        address: pending.address,
+                        data: LogData::new_unchecked(pending.topics, pending.data.into()),
+                    },
+                    block_hash: Some(hash),
+                    block_number: Some(number),
+                    block_timestamp: None,
+                    transaction_hash: Some(pending.tx_hash),
+                    transaction_index: Some(tx_index as u64),
+                    log_index: Some(log_index as u64),
+                    removed: false,
+                }
+            })
+            .collect();
+
+        state.blocks.push(MockBlock {
+            number,
+            hash,
+            timestamp,
+            logs,
+            transactions,
+        });
+        number
+    }
+
+    /// Drops the latest `depth` blocks; blocks pushed afterwards get new hashes
+    pub fn reorg(&self, depth: usize) {
+        let mut state = self.state.lock().unwrap();
+        let kept = state.blocks.len().saturating_sub(depth);
+        state.blocks.truncate(kept);
+        state.reorgs += 1;
+    }
+
+    /// Reports `block` as the `safe` and `finalized` block from now on
+    pub fn finalize(&self, block: u64) {
+        self.state.lock().unwrap().finalized = Some(block);
+    }
+
+    /// Sets the price returned by `eth_gasPrice` (1 gwei by default)
+    pub fn set_gas_price(&self, wei: U256) {
+        self.state.lock().unwrap().node.gas_price = wei;
+    }
+
+    /// Makes `nonce` the next nonce of `address`, as if it had sent transactions
+    /// through another node
+    pub fn set_nonce(&self, address: H160, nonce: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .node
+            .nonces
+            .insert(address, U256::from(nonce));
+    }
+
+    /// Rejects the next `count` raw transactions with a JSON-RPC error
+    pub fn reject_sends(&self, count: usize) {
+        self.state.lock().unwrap().node.rejected_sends = count;
+    }
+
+    /// Raw transactions accepted so far, in broadcast order
+    pub fn sent_transactions(&self) -> Vec<Transaction> {
+        self.state.lock().unwrap().node.sent.clone()
+    }
+
+    /// Mines a broadcast transaction in a new block and returns the block number
+    ///
+    /// Other transactions at its nonce can't be mined anymore.
+    pub fn mine(&self, hash: H256, success: bool) -> u64 {
+        let tx = self
+            .sent_transactions()
+            .into_iter()
+            .find(|tx| tx.hash == hash)
+            .expect("transaction was not broadcast");
+        let timestamp = {
+            let state = self.state.lock().unwrap();
+            state.blocks.last().map_or(0, |block| block.timestamp + 1)
+        };
+        let number = self.push_block(timestamp, Vec::new());
+
+        let mut state = self.state.lock().unwrap();
+        let block = state.blocks.last_mut().unwrap();
+        block.transactions.push(MockTx {
+            hash: B256::from(hash.0),
+            from: Address::from(tx.from.0),
+            input: Bytes::from(tx.input.to_vec()),
+        });
+        let nonce = state.node.nonces.entry(tx.from).or_default();
+        *nonce = (*nonce).max(tx.nonce + 1);
+        state.node.mined.insert(hash, (number, success));
+        number
+    }
+
+    /// Answers a JSON-RPC call of the keeper
+    fn node_request(&self, method: &str, params: Value) -> Result<Value, MockError> {
+        let param = |index: usize| params.get(index).cloned().unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        Ok(match method {
+            "eth_chainId" => json!(U64::from(self.chain_id)),
+            "eth_gasPrice" => json!(state.node.gas_price),
+            "eth_estimateGas" => json!(U256::from(MOCK_GAS_ESTIMATE)),
+            "eth_getTransactionCount" => {
+                let address: H160 = serde_json::from_value(param(0))?;
+                let pending = param(1) == "pending";
+                json!(state.node.transaction_count(address, pending))
+            }
+            "eth_sendRawTransaction" => {
+                if state.node.rejected_sends > 0 {
+                    state.node.rejected_sends -= 1;
+                    return Err(rpc_error("nonce too low"));
+                }
+                let raw: ethers::types::Bytes = serde_json::from_value(param(0))?;
+                let tx: Transaction =
+                    rlp::decode(&raw).map_err(|_| rpc_error("invalid raw transaction"))?;
+                let hash = tx.hash;
+                state.node.sent.push(tx);
+                json!(hash)
+            }
+            "eth_getTransactionReceipt" => {
+                let hash: H256 = serde_json::from_value(param(0))?;
+                json!(state.node.receipt(hash))
+            }
+            _ => return Err(rpc_error(&format!("method {method} not supported"))),
+        })
+    }
+
+    fn head(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        match state.blocks.last() {
+            Some(block) => block.number,
+            None => state.first_block.saturating_sub(1),
+        }
+    }
+}
+
+impl ChainClient for MockChain {
+    async fn chain_id(&self) -> anyhow::Result<u64> {
+        Ok(self.chain_id)
+    }
+
+    async fn block_number(&self) -> anyhow::Result<u64> {
+        Ok(self.head())
+    }
+
+    async fn logs(
+        &self,
+        addresses: Vec<Address>,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<Log>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .blocks
+            .iter()
+            .filter(|block| (from_block..=to_block).contains(&block.number))
+            .flat_map(|block| &block.logs)
+            .filter(|log_entry| addresses.contains(&log_entry.address()))
+            .cloned()
+            .collect())
+    }
+
+    async fn block_timestamp(&self, block_number: u64) -> anyhow::Result<Option<u64>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .blocks
+            .iter()
+            .find(|block| block.number == block_number)
+            .map(|block| block.timestamp))
+    }
+
+    async fn block_hash(&self, block_number: u64) -> anyhow::Result<Option<B256>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .blocks
+            .iter()
+            .find(|block| block.number == block_number)
+            .map(|block| block.hash))
+    }
+
+    async fn tagged_block_number(&self, tag: BlockNumberOrTag) -> anyhow::Result<Option<u64>> {
+        Ok(match tag {
+            BlockNumberOrTag::Latest => Some(self.head()),
+            BlockNumberOrTag::Safe | BlockNumberOrTag::Finalized => {
+                self.state.lock().unwrap().finalized
+            }
+            _ => None,
+        })
+    }
+
+    /// Transactions are known by their logs; all of them succeeded
+    async fn transaction_receipt(&self, tx_hash: B256) -> anyhow::Result<Option<TxReceipt>> {
+        let state = self.state.lock().unwrap();
+        let from = state
+            .transaction(tx_hash)
+            .map(|tx| tx.from)
+            .unwrap_or_default();
+        let logs: Vec<Log> = state
+            .blocks
+            .iter()
+            .flat_map(|block| &block.logs)
+            .filter(|log_entry| log_entry.transaction_hash == Some(tx_hash))
+            .cloned()
+            .collect();
+        Ok((!logs.is_empty()).then_some(TxReceipt {
+            success: true,
+            from,
+            logs,
+        }))
+    }
+
+    async fn transaction_input(&self, tx_hash: B256) -> anyhow::Result<Option<Bytes>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.transaction(tx_hash).map(|tx| tx.input.clone()))
+    }
+}
+
+impl Debug for MockChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockChain")
+            .field("chain_id", &self.chain_id)
+            .field("head", &self.head())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for MockChain {
+    type Error = MockError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let result = self.node_request(method, serde_json::to_value(params)?)?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+fn rpc_error(message: &str) -> MockError {
+    MockError::JsonRpcError(JsonRpcError {
+        code: -32000,
+        message: message.to_string(),
+        data: None,
+    })
+}
@@ -0,0 +1,143 @@
+//! Temporary databases for end-to-end tests
+//!
+//! Each [`TestDb`] is a fresh database on the server at `TEST_DATABASE_URL`, migrated
+//! from `migrations/` and dropped again when the `TestDb` is dropped (also when the
+//! test panics). Databases are named `raffle_test_<pid>_<n>`, so one left behind by a
+//! killed test run is easy to spot.
+//!
+//! With `TEST_DATABASE_ISOLATION=schema` each test gets a schema of that name inside
+//! the `TEST_DATABASE_URL` database instead, selected through `search_path` (as
+//! `DATABASE_SCHEMA` does). Creating a schema is cheaper than creating a database and
+//! only needs the `CREATE` privilege on the database, not `CREATEDB`.
+
+use sqlx::PgPool;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Distinguishes the databases of concurrently running tests
+static NEXT_DATABASE: AtomicU64 = AtomicU64::new(0);
+
+/// What a [`TestDb`] owns on the test server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Isolation {
+    /// A database of its own (default)
+    Database,
+    /// A schema in the `TEST_DATABASE_URL` database (`TEST_DATABASE_ISOLATION=schema`)
+    Schema,
+}
+
+impl Isolation {
+    /// Reads `TEST_DATABASE_ISOLATION`
+    fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("TEST_DATABASE_ISOLATION").as_deref() {
+            Err(_) | Ok("") | Ok("database") => Ok(Self::Database),
+            Ok("schema") => Ok(Self::Schema),
+            Ok(other) => anyhow::bail!(
+                "TEST_DATABASE_ISOLATION must be \"database\" or \"schema\", got {other:?}"
+            ),
+        }
+    }
+}
+
+pub struct TestDb {
+    /// Connection string of the temporary database
+    pub url: String,
+    /// Schema holding the tables with [`Isolation::Schema`], to be used as `DATABASE_SCHEMA`
+    pub schema: Option<String>,
+    pub pool: PgPool,
+    admin_url: String,
+    name: String,
+}
+
+impl TestDb {
+    /// Creates and migrates a temporary database, isolated as `TEST_DATABASE_ISOLATION`
+    /// says
+    ///
+    /// Returns `None` when `TEST_DATABASE_URL` is unset, so test runs without a
+    /// database skip the end-to-end tests instead of failing.
+    pub async fn create() -> anyhow::Result<Option<Self>> {
+        Self::create_with(Isolation::from_env()?).await
+    }
+
+    /// Creates and migrates a temporary database or schema
+    ///
+    /// Returns `None` when `TEST_DATABASE_URL` is unset.
+    pub async fn create_with(isolation: Isolation) -> anyhow::Result<Option<Self>> {
+        let Ok(admin_url) = std::env::var("TEST_DATABASE_URL") else {
+            return Ok(None);
+        };
+        let name = format!(
+            "raffle_test_{}_{}",
+            std::process::id(),
+            NEXT_DATABASE.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let admin = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&admin_url)
+            .await?;
+        let create = match isolation {
+            Isolation::Database => format!("CREATE DATABASE {name}"),
+            Isolation::Schema => format!("CREATE SCHEMA {name}"),
+        };
+        sqlx::query(&create).execute(&admin).await?;
+        admin.close().await;
+
+        let (url, schema, options) = match isolation {
+            Isolation::Database => {
+                let mut url = reqwest::Url::parse(&admin_url)?;
+                url.set_path(&name);
+                let options = PgConnectOptions::from_str(url.as_str())?;
+                (url.to_string(), None, options)
+            }
+            Isolation::Schema => {
+                let options = PgConnectOptions::from_str(&admin_url)?
+                    .options([("search_path", name.as_str())]);
+                (admin_url.clone(), Some(name.clone()), options)
+            }
+        };
+        let db = Self {
+            url,
+            schema,
+            pool: PgPoolOptions::new()
+                .max_connections(5)
+                .connect_with(options)
+                .await?,
+            admin_url,
+            name,
+        };
+        crate::schema::MIGRATOR.run(&db.pool).await?;
+        Ok(Some(db))
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        // Drop runs outside any async context (possibly while unwinding), so the
+        // database is dropped from a thread with its own runtime
+        let admin_url = self.admin_url.clone();
+        let drop = match self.schema {
+            None => format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", self.name),
+            Some(_) => format!("DROP SCHEMA IF EXISTS {} CASCADE", self.name),
+        };
+        let dropped = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            runtime.block_on(async {
+                let admin = PgPoolOptions::new()
+                    .max_connections(1)
+                    .connect(&admin_url)
+                    .await?;
+                sqlx::query(&drop).execute(&admin).await?;
+                admin.close().await;
+                anyhow::Ok(())
+            })
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("failed to drop test database {}", self.name);
+        }
+    }
+}
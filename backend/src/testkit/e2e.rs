@@ -0,0 +1,1350 @@
+//! End-to-end scenarios: fixtures played through the indexer and checked via the API
+
+use super::{Fixture, Isolation, TestApp, TestDb};
+use crate::live::LiveEvent;
+use axum::body::Bytes;
+use axum::http::{Method, header};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+/// Plays a fixture through a fresh app and returns the app for further checks
+///
+/// `overrides` take precedence over the fixture's config. `None` when
+/// `TEST_DATABASE_URL` is unset and the test should be skipped.
+async fn start_fixture(fixture: &str, overrides: &[(&str, &str)]) -> Option<TestApp> {
+    let fixture = Fixture::parse(fixture).unwrap();
+    let overrides: Vec<(&str, &str)> = overrides
+        .iter()
+        .copied()
+        .chain(
+            fixture
+                .config
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+        .collect();
+    let Some(app) = TestApp::start(fixture.start_block, &overrides)
+        .await
+        .unwrap_or_else(|err| panic!("{err:#}"))
+    else {
+        eprintln!("TEST_DATABASE_URL is unset, skipping");
+        return None;
+    };
+    if let Err(err) = fixture.run(&app).await {
+        panic!("{}\n{err:#}", fixture.description);
+    }
+    Some(app)
+}
+
+#[tokio::test]
+async fn happy_path() {
+    start_fixture(include_str!("fixtures/happy_path.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn refund_path() {
+    start_fixture(include_str!("fixtures/refund_path.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn reorg() {
+    start_fixture(include_str!("fixtures/reorg.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn orphaned_blocks() {
+    start_fixture(include_str!("fixtures/orphaned_blocks.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn draw_estimate() {
+    start_fixture(include_str!("fixtures/draw_estimate.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn buyer_overlap() {
+    start_fixture(include_str!("fixtures/buyer_overlap.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn finalized_head() {
+    start_fixture(include_str!("fixtures/finalized_head.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn out_of_order_status() {
+    start_fixture(include_str!("fixtures/out_of_order_status.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn unconfirmed_payout() {
+    start_fixture(include_str!("fixtures/unconfirmed_payout.json"), &[]).await;
+}
+
+#[tokio::test]
+async fn blocked_addresses() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    for address in [
+        "0x00000000000000000000000000000000000000c1",
+        "0x00000000000000000000000000000000000000b1",
+    ] {
+        sqlx::query(
+            "INSERT INTO blocked_addresses (address, reason, blocked_by) VALUES ($1, 'test', 'test')",
+        )
+        .bind(address)
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+    }
+
+    let (_, raffles) = app.get("/v1/raffles").await.unwrap();
+    assert_eq!(raffles, serde_json::json!([]));
+    let (_, raffles) = app.get("/v1/raffles?include_blocked=true").await.unwrap();
+    assert_eq!(raffles.as_array().map(Vec::len), Some(1));
+
+    let (_, purchases) = app.get("/v1/raffles/1/purchases").await.unwrap();
+    let flags: Vec<_> = purchases
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|purchase| (purchase["buyer"].clone(), purchase["buyer_blocked"].clone()))
+        .collect();
+    assert!(!flags.is_empty());
+    assert!(flags.iter().all(|(buyer, blocked)| {
+        blocked.as_bool() == Some(buyer == "0x00000000000000000000000000000000000000b1")
+    }));
+}
+
+#[tokio::test]
+async fn v2_cursor_paging() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    // One purchase per page until the cursor runs out
+    let mut start_indexes = Vec::new();
+    let mut path = "/v2/raffles/1/purchases?limit=1".to_string();
+    loop {
+        let (_, page) = app.get(&path).await.unwrap();
+        assert_eq!(page["data"].as_array().map(Vec::len), Some(1));
+        start_indexes.push(page["data"][0]["start_index"].clone());
+        match page["page"]["next_cursor"].as_str() {
+            Some(cursor) => path = format!("/v2/raffles/1/purchases?limit=1&cursor={cursor}"),
+            None => break,
+        }
+    }
+    assert_eq!(start_indexes, [0, 6, 10]);
+
+    // A cursor of another list is rejected in the v2 error envelope
+    let (_, raffles) = app.get("/v2/raffles?limit=1").await.unwrap();
+    assert!(raffles["page"]["next_cursor"].is_null());
+    let (_, page) = app.get("/v2/raffles/1/purchases?limit=1").await.unwrap();
+    let cursor = page["page"]["next_cursor"].as_str().unwrap();
+    let (status, error) = app
+        .get(&format!("/v2/raffles?cursor={cursor}"))
+        .await
+        .unwrap();
+    assert_eq!(status, 400);
+    assert_eq!(error["error"]["code"], "INVALID_PARAMETER");
+    assert_eq!(error["error"]["details"]["param"], "cursor");
+}
+
+#[tokio::test]
+async fn last_modified() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    for path in ["/v1/raffles/1", "/v1/raffles/1/proof", "/v2/raffles/1"] {
+        let response = app.get_with_headers(path, &[]).await.unwrap();
+        assert_eq!(response.status(), 200, "{path}");
+        let last_modified = response.headers()[header::LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = app
+            .get_with_headers(path, &[("if-modified-since", &last_modified)])
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 304, "{path}");
+        assert_eq!(response.headers()[header::LAST_MODIFIED], *last_modified);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty(), "{path}");
+
+        let response = app
+            .get_with_headers(
+                path,
+                &[("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{path}");
+    }
+}
+
+#[tokio::test]
+async fn raffle_list_cache() {
+    let Some(app) = start_fixture(
+        include_str!("fixtures/happy_path.json"),
+        &[("RAFFLE_LIST_CACHE_TTL_MS", "60000")],
+    )
+    .await
+    else {
+        return;
+    };
+
+    // Every query parameter is part of the key
+    for (path, expected) in [
+        ("/v1/raffles", "MISS"),
+        ("/v1/raffles", "HIT"),
+        ("/v1/raffles?limit=1", "MISS"),
+        ("/v1/raffles?offset=1", "MISS"),
+        ("/v1/raffles?status=CLOSED", "MISS"),
+        ("/v1/raffles?format=hex", "MISS"),
+        ("/v1/raffles?limit=1", "HIT"),
+    ] {
+        let response = app.get_with_headers(path, &[]).await.unwrap();
+        assert_eq!(response.status(), 200, "{path}");
+        assert_eq!(response.headers()["x-cache"], expected, "{path}");
+    }
+
+    // Headers aren't, as they don't change the page; the admin-only view bypasses
+    // the cache altogether
+    sqlx::query("UPDATE raffles SET winner = '0x00000000000000000000000000000000000000ff'")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+    let response = app
+        .get_with_headers("/v1/raffles", &[("accept-language", "de")])
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-cache"], "HIT");
+    let (_, cached) = app.get("/v1/raffles").await.unwrap();
+    assert_eq!(
+        cached[0]["winner"],
+        "0x00000000000000000000000000000000000000b2"
+    );
+
+    let response = app
+        .get_with_headers("/v1/raffles?include_blocked=true", &[])
+        .await
+        .unwrap();
+    assert!(!response.headers().contains_key("x-cache"));
+    assert_eq!(
+        response.headers()[header::CACHE_CONTROL],
+        "private, no-store"
+    );
+    let (_, live) = app.get("/v1/raffles?include_blocked=true").await.unwrap();
+    assert_eq!(
+        live[0]["winner"],
+        "0x00000000000000000000000000000000000000ff"
+    );
+}
+
+#[tokio::test]
+async fn purchase_stream() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+    let (_, page) = app.get("/v1/raffles/1/purchases").await.unwrap();
+
+    let lines = |response: axum::response::Response| async move {
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.ends_with(b"\n"));
+        body.split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect::<Vec<serde_json::Value>>()
+    };
+
+    // The page limit doesn't apply to the stream
+    let response = app
+        .get_with_headers(
+            "/v1/raffles/1/purchases?limit=1",
+            &[("accept", "application/json, application/x-ndjson")],
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[header::VARY], "accept");
+    assert_eq!(lines(response).await, page.as_array().unwrap().clone());
+
+    let response = app
+        .get_with_headers(
+            "/v1/raffles/1/purchases.ndjson?after_block=101&after_log_index=0&format=decimal",
+            &[],
+        )
+        .await
+        .unwrap();
+    let purchases = lines(response).await;
+    let amounts: Vec<&str> = purchases
+        .iter()
+        .map(|purchase| purchase["amount_formatted"].as_str().unwrap())
+        .collect();
+    assert_eq!(amounts, ["4", "2"]);
+    assert_eq!(purchases[0]["tx_hash"], page[1]["tx_hash"]);
+
+    // Without the header the purchases are paged as before
+    let (_, first) = app.get("/v1/raffles/1/purchases?limit=1").await.unwrap();
+    assert_eq!(first.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn resolve_tickets() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+    let b = |suffix: &str| format!("0x{suffix:0>40}");
+
+    // Answers keep the request order and repeats; index 12 was never sold
+    let request = serde_json::json!({ "indices": [9, 0, 11, 5, 6, 12, 9] });
+    let (status, resolved) = app
+        .post("/v1/raffles/1/tickets/resolve", request)
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(resolved["raffle_id"], 1);
+    assert_eq!(resolved["total_tickets"], 12);
+    let tickets: Vec<_> = resolved["tickets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ticket| {
+            (
+                ticket["index"].as_i64().unwrap(),
+                ticket["owner"].as_str().map(str::to_string),
+                ticket["start_index"].as_i64(),
+                ticket["end_index"].as_i64(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        tickets,
+        [
+            (9, Some(b("b2")), Some(6), Some(9)),
+            (0, Some(b("b1")), Some(0), Some(5)),
+            (11, Some(b("b1")), Some(10), Some(11)),
+            (5, Some(b("b1")), Some(0), Some(5)),
+            (6, Some(b("b2")), Some(6), Some(9)),
+            (12, None, None, None),
+            (9, Some(b("b2")), Some(6), Some(9)),
+        ]
+    );
+    assert!(resolved["tickets"][5]["tx_hash"].is_null());
+
+    // The winning ticket resolves to the indexed winner
+    let (_, raffle) = app.get("/v1/raffles/1").await.unwrap();
+    assert_eq!(raffle["winning_index"], 9);
+    assert_eq!(raffle["winner"], resolved["tickets"][0]["owner"]);
+
+    let too_many: Vec<i64> = (0..1001).collect();
+    for indices in [
+        serde_json::json!([]),
+        serde_json::json!([-1]),
+        serde_json::json!(too_many),
+    ] {
+        let request = serde_json::json!({ "indices": indices });
+        let (status, error) = app
+            .post("/v1/raffles/1/tickets/resolve", request)
+            .await
+            .unwrap();
+        assert_eq!(status, 400);
+        assert_eq!(error["code"], "INVALID_PARAMETER");
+    }
+    let request = serde_json::json!({ "indices": [0] });
+    let (status, error) = app
+        .post("/v1/raffles/9/tickets/resolve", request)
+        .await
+        .unwrap();
+    assert_eq!(status, 404);
+    assert_eq!(error["code"], "RAFFLE_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn api_key_quota() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    let (status, minted) = app
+        .post(
+            "/v1/admin/api-keys",
+            serde_json::json!({ "name": "partner", "daily_quota": 2 }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, 201);
+    let key = minted["key"].as_str().unwrap().to_string();
+    let keyed = |path: &'static str| {
+        let key = key.clone();
+        let app = &app;
+        async move {
+            app.get_with_headers(path, &[("x-api-key", &key)])
+                .await
+                .unwrap()
+        }
+    };
+
+    for remaining in ["1", "0"] {
+        let response = keyed("/v1/raffles").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-ratelimit-limit"], "2");
+        assert_eq!(response.headers()["x-ratelimit-remaining"], remaining);
+    }
+    let response = keyed("/v1/raffles").await;
+    assert_eq!(response.status(), 429);
+    let reset = response.headers()["x-ratelimit-reset"].clone();
+    assert_eq!(response.headers()[header::RETRY_AFTER], reset);
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+
+    // Usage stays readable over quota and shows the rejected request
+    let response = keyed("/v1/usage").await;
+    assert_eq!(response.status(), 200);
+    let rejected: i64 = sqlx::query_scalar("SELECT rejected FROM api_key_usage")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(rejected, 1);
+
+    // A new UTC day starts a new count
+    sqlx::query("UPDATE api_key_usage SET day = day - 1")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+    let response = keyed("/v1/raffles").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "1");
+
+    // Unknown and revoked keys are refused
+    let response = app
+        .get_with_headers("/v1/raffles", &[("x-api-key", "ta_unknown")])
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let (_, revoked) = app
+        .post(
+            "/v1/admin/api-keys",
+            serde_json::json!({ "name": "former" }),
+        )
+        .await
+        .unwrap();
+    let response = app
+        .send(
+            Method::DELETE,
+            &format!("/v1/admin/api-keys/{}", revoked["id"]),
+            &[],
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let response = app
+        .get_with_headers(
+            "/v1/raffles",
+            &[("x-api-key", revoked["key"].as_str().unwrap())],
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn daily_digest() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    let (status, _) = app.get("/v1/digests/latest").await.unwrap();
+    assert_eq!(status, 404);
+
+    let day = chrono::NaiveDate::from_ymd_opt(2025, 10, 9).unwrap();
+    assert!(app.store_digest(day).await.unwrap());
+    // Compiled once, however often the job runs
+    assert!(!app.store_digest(day).await.unwrap());
+
+    let (status, latest) = app.get("/v1/digests/latest?format=decimal").await.unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(latest["day"], "2025-10-09");
+    assert_eq!(latest["new_raffles"], 1);
+    assert_eq!(latest["purchases"], 3);
+    assert_eq!(latest["tickets"], 12);
+    assert_eq!(latest["unique_buyers"], 2);
+    assert_eq!(latest["volume"], "12000000");
+    assert_eq!(latest["volume_formatted"], "12");
+    assert_eq!(latest["biggest_pots"].as_array().unwrap().len(), 1);
+    assert_eq!(latest["biggest_pots"][0]["pot"], "12000000");
+    assert_eq!(latest["biggest_pots"][0]["tickets"], 12);
+    assert_eq!(latest["winners"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        latest["winners"][0]["winner"],
+        "0x00000000000000000000000000000000000000b2"
+    );
+    assert_eq!(latest["winners"][0]["prize_amount_formatted"], "11.4");
+
+    // A quiet day still gets a digest, which becomes the latest; without a webhook
+    // nothing is queued for delivery
+    assert!(app.store_digest(day.succ_opt().unwrap()).await.unwrap());
+    let (_, latest) = app.get("/v1/digests/latest").await.unwrap();
+    assert_eq!(latest["day"], "2025-10-10");
+    assert_eq!(latest["purchases"], 0);
+    assert_eq!(latest["volume"], "0");
+    assert_eq!(latest["biggest_pots"], serde_json::json!([]));
+    let deliveries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM digest_deliveries")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries, 0);
+}
+
+#[tokio::test]
+async fn archived_raffles() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+    let (_, before) = app.get("/v1/raffles/1").await.unwrap();
+    let (_, purchases_before) = app.get("/v1/raffles/1/purchases").await.unwrap();
+
+    // Completed, but changed too recently
+    assert_eq!(app.archive(7).await.unwrap(), 0);
+
+    sqlx::query("UPDATE raffles SET updated_at = now() - interval '8 days'")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(app.archive(7).await.unwrap(), 1);
+    let counts: (i64, i64, i64) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM raffles), (SELECT COUNT(*) FROM raffles_archive),
+                (SELECT COUNT(*) FROM purchases_archive)",
+    )
+    .fetch_one(&app.db.pool)
+    .await
+    .unwrap();
+    assert_eq!(counts, (0, 1, 3));
+
+    // Lists only cover the hot tables; the raffle itself is still served
+    let (_, list) = app.get("/v1/raffles").await.unwrap();
+    assert_eq!(list, serde_json::json!([]));
+    let (status, after) = app.get("/v1/raffles/1").await.unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(after, before);
+    let (_, purchases_after) = app.get("/v1/raffles/1/purchases").await.unwrap();
+    assert_eq!(purchases_after, purchases_before);
+}
+
+/// Serves an object store that keeps every object PUT to it by path and returns it
+/// on GET. Returns its endpoint and the stored objects.
+async fn mock_bucket() -> (String, Arc<Mutex<HashMap<String, Bytes>>>) {
+    let objects: Arc<Mutex<HashMap<String, Bytes>>> = Arc::default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let bucket = axum::Router::new().fallback({
+        let objects = objects.clone();
+        move |method: Method, uri: axum::http::Uri, body: Bytes| async move {
+            let mut objects = objects.lock().unwrap();
+            if method == Method::GET {
+                return match objects.get(uri.path()) {
+                    Some(object) => (axum::http::StatusCode::OK, object.clone()),
+                    None => (axum::http::StatusCode::NOT_FOUND, Bytes::new()),
+                };
+            }
+            objects.insert(uri.path().to_string(), body);
+            (axum::http::StatusCode::OK, Bytes::new())
+        }
+    });
+    tokio::spawn(async move { axum::serve(listener, bucket).await.unwrap() });
+    (endpoint, objects)
+}
+
+#[tokio::test]
+async fn parquet_export() {
+    use arrow_array::cast::AsArray;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let (endpoint, objects) = mock_bucket().await;
+    let Some(app) = start_fixture(
+        include_str!("fixtures/happy_path.json"),
+        &[
+            ("EXPORT_BUCKET", "arcade-data"),
+            ("EXPORT_ENDPOINT", &endpoint),
+            ("EXPORT_ACCESS_KEY_ID", "AKIDEXAMPLE"),
+            ("EXPORT_SECRET_ACCESS_KEY", "secret"),
+        ],
+    )
+    .await
+    else {
+        return;
+    };
+
+    app.export_snapshot().await.unwrap();
+    let (status, snapshots) = app.get("/v1/admin/exports").await.unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(snapshots.as_array().unwrap().len(), 1);
+    let snapshot = &snapshots[0];
+    assert_eq!(snapshot["bucket"], "arcade-data");
+    assert_eq!(snapshot["block_number"], 107);
+    let files = snapshot["files"].as_array().unwrap();
+    let rows: Vec<(&str, u64)> = files
+        .iter()
+        .map(|file| {
+            (
+                file["table"].as_str().unwrap(),
+                file["rows"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(rows, [("raffles", 1), ("purchases", 3), ("refunds", 0)]);
+
+    // Every listed file was uploaded and reads back as Parquet
+    let objects = objects.lock().unwrap().clone();
+    assert_eq!(objects.len(), 3);
+    let object = |table: &str| {
+        let file = files.iter().find(|file| file["table"] == table).unwrap();
+        let key = file["key"].as_str().unwrap();
+        assert!(key.starts_with(&format!(
+            "snapshots/{}/",
+            snapshot["snapshot"].as_str().unwrap()
+        )));
+        let body = objects[&format!("/arcade-data/{key}")].clone();
+        assert_eq!(body.len() as u64, file["bytes"].as_u64().unwrap());
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(body)
+            .unwrap()
+            .build()
+            .unwrap();
+        reader.next().unwrap().unwrap()
+    };
+    let purchases = object("purchases");
+    let amounts: Vec<&str> = purchases
+        .column_by_name("amount")
+        .unwrap()
+        .as_string::<i32>()
+        .iter()
+        .map(Option::unwrap)
+        .collect();
+    assert_eq!(amounts, ["6000000", "4000000", "2000000"]);
+    let raffles = object("raffles");
+    assert_eq!(
+        raffles
+            .column_by_name("winner")
+            .unwrap()
+            .as_string::<i32>()
+            .value(0),
+        "0x00000000000000000000000000000000000000b2"
+    );
+
+    // The next snapshot is only due after EXPORT_INTERVAL_SECS
+    app.export_snapshot().await.unwrap();
+    let (_, snapshots) = app.get("/v1/admin/exports").await.unwrap();
+    assert_eq!(snapshots.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn raw_event_backup_round_trip() {
+    let (endpoint, objects) = mock_bucket().await;
+    let bucket = [
+        ("BACKUP_BUCKET", "arcade-backups"),
+        ("BACKUP_ENDPOINT", endpoint.as_str()),
+        ("BACKUP_ACCESS_KEY_ID", "AKIDEXAMPLE"),
+        ("BACKUP_SECRET_ACCESS_KEY", "secret"),
+    ];
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &bucket).await else {
+        return;
+    };
+    let key = "backups/happy_path.ndjson.gz";
+    crate::backup::backup_raw(&app.config, Some(key.to_string()))
+        .await
+        .unwrap();
+    assert!(
+        objects
+            .lock()
+            .unwrap()
+            .contains_key("/arcade-backups/backups/happy_path.ndjson.gz")
+    );
+
+    // Restoring into the indexed database itself is refused
+    let err = crate::backup::restore_raw(&app.config, key)
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("freshly migrated"), "{err:#}");
+
+    // A fresh database rebuilds the same raffle from the raw events alone
+    let restored = TestApp::start(1, &bucket).await.unwrap().unwrap();
+    crate::backup::restore_raw(&restored.config, key)
+        .await
+        .unwrap();
+    for path in [
+        "/v1/raffles/1",
+        "/v1/raffles/1/purchases",
+        "/v1/raffles/1/stats/histogram",
+    ] {
+        let (status, mut original) = app.get(path).await.unwrap();
+        assert_eq!(status, 200, "{path}");
+        let (status, mut rebuilt) = restored.get(path).await.unwrap();
+        assert_eq!(status, 200, "{path}");
+        // Payout confirmations come from receipts, not events, and are checked again
+        if let Some(confirmed) = rebuilt.get_mut("payout_confirmed") {
+            assert_eq!(original["payout_confirmed"], true);
+            assert_eq!(confirmed.take(), serde_json::Value::Null);
+            original["payout_confirmed"] = serde_json::Value::Null;
+        }
+        // Rows were inserted again, so only their insertion times differ
+        for value in [&mut original, &mut rebuilt] {
+            for row in value.as_array_mut().into_iter().flatten() {
+                row.as_object_mut().unwrap().remove("created_at");
+            }
+        }
+        assert_eq!(rebuilt, original, "{path}");
+    }
+    let mut checkpoints = Vec::new();
+    for app in [&app, &restored] {
+        let checkpoint: i64 =
+            sqlx::query_scalar("SELECT last_processed_block FROM indexer_state WHERE id = 1")
+                .fetch_one(&app.db.pool)
+                .await
+                .unwrap();
+        checkpoints.push(checkpoint);
+    }
+    assert_eq!(checkpoints[0], checkpoints[1]);
+
+    // Backups of another chain are rejected before anything is inserted
+    let other_chain = TestApp::start(1, &[bucket.as_slice(), &[("CHAIN_ID", "1")]].concat())
+        .await
+        .unwrap()
+        .unwrap();
+    let err = crate::backup::restore_raw(&other_chain.config, key)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("backup is for chain"),
+        "{err:#}"
+    );
+    let events: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events_raw")
+        .fetch_one(&other_chain.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(events, 0);
+}
+
+#[tokio::test]
+async fn raffle_snapshots() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    // The fixture's chain has no finality, so nothing is snapshotted yet
+    let paths = [
+        "/v1/raffles/1",
+        "/v1/raffles/1/proof",
+        "/v1/raffles/1/purchases",
+    ];
+    let count = "SELECT COUNT(*) FROM raffle_snapshots";
+    for path in paths {
+        app.get(path).await.unwrap();
+    }
+    let snapshots: i64 = sqlx::query_scalar(count)
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(snapshots, 0);
+
+    sqlx::query("UPDATE indexer_state SET finalized_block = last_processed_block")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+    let mut stored = Vec::new();
+    for path in paths {
+        let response = app.get_with_headers(path, &[]).await.unwrap();
+        assert_eq!(response.status(), 200, "{path}");
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=3600, s-maxage=3600",
+            "{path}"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        stored.push(serde_json::from_slice::<serde_json::Value>(&body).unwrap());
+    }
+    let snapshots: i64 = sqlx::query_scalar(count)
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(snapshots, 3);
+
+    // Served from the snapshot without reading the raffle; other formats stay live
+    sqlx::query("UPDATE raffles SET winner = '0x00000000000000000000000000000000000000ff'")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+    for (path, stored) in paths.iter().zip(&stored) {
+        let (_, body) = app.get(path).await.unwrap();
+        assert_eq!(body, *stored, "{path}");
+    }
+    let (_, details) = app.get("/v1/raffles/1?format=hex").await.unwrap();
+    assert_eq!(
+        details["winner"],
+        "0x00000000000000000000000000000000000000ff"
+    );
+}
+
+#[tokio::test]
+async fn head_and_options() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    // HEAD answers with the GET headers, without the body
+    for path in [
+        "/v1/raffles",
+        "/v1/raffles/1",
+        "/v2/raffles/1",
+        "/v1/status",
+    ] {
+        let get = app.get_with_headers(path, &[]).await.unwrap();
+        let head = app.send(Method::HEAD, path, &[]).await.unwrap();
+        assert_eq!(head.status(), 200, "{path}");
+        for name in [
+            header::CONTENT_TYPE,
+            header::CONTENT_LENGTH,
+            header::CACHE_CONTROL,
+        ] {
+            assert_eq!(
+                head.headers().get(&name),
+                get.headers().get(&name),
+                "{path} {name}"
+            );
+        }
+        let body = axum::body::to_bytes(head.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty(), "{path}");
+    }
+
+    for (path, allow) in [
+        ("/v1/raffles/1", "GET,HEAD,OPTIONS"),
+        ("/v2/raffles/1", "GET,HEAD,OPTIONS"),
+        ("/v1/admin/blocklist/0xab", "PUT,DELETE,OPTIONS"),
+        ("/v1/embed/raffles/1", "GET,HEAD,OPTIONS"),
+    ] {
+        let response = app.send(Method::OPTIONS, path, &[]).await.unwrap();
+        assert_eq!(response.status(), 204, "{path}");
+        assert_eq!(response.headers()[header::ALLOW], allow, "{path}");
+    }
+    let response = app
+        .send(Method::DELETE, "/v1/raffles/1", &[])
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 405);
+    assert_eq!(response.headers()[header::ALLOW], "GET,HEAD,OPTIONS");
+    let response = app.send(Method::OPTIONS, "/v1/nope", &[]).await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn purchase_curve_gaps() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    // The last purchase three minutes later, the first one without a block time
+    sqlx::query(
+        "UPDATE purchases SET block_time = CASE count
+            WHEN 2 THEN block_time + INTERVAL '3 minutes'
+            WHEN 6 THEN NULL
+            ELSE block_time
+         END",
+    )
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+
+    let (status, curve) = app
+        .get("/v1/raffles/1/purchase-curve?interval=1m")
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(curve["total_tickets"], 12);
+    assert_eq!(curve["untimed_tickets"], 6);
+    let points: Vec<_> = curve["points"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| {
+            (
+                point["bucket_start"].as_str().unwrap().to_string(),
+                point["tickets"].as_i64().unwrap(),
+                point["cumulative_tickets"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        points,
+        [
+            ("2025-10-09T08:53:00Z".to_string(), 4, 4),
+            ("2025-10-09T08:54:00Z".to_string(), 0, 4),
+            ("2025-10-09T08:55:00Z".to_string(), 0, 4),
+            ("2025-10-09T08:56:00Z".to_string(), 2, 6),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn participant_cohorts() {
+    let Some(app) = start_fixture(include_str!("fixtures/buyer_overlap.json"), &[]).await else {
+        return;
+    };
+
+    let (_, report) = app.get("/v1/analytics/cohorts").await.unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({ "computed_at": null, "cohorts": [] })
+    );
+
+    // b1 comes back two weeks after the cohort's first week
+    sqlx::query(
+        "UPDATE purchases SET block_time = block_time + INTERVAL '14 days'
+         WHERE raffle_id = 2 AND buyer = '0x00000000000000000000000000000000000000b1'",
+    )
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+    assert_eq!(app.refresh_cohorts().await.unwrap(), 1);
+
+    let (status, report) = app.get("/v1/analytics/cohorts").await.unwrap();
+    assert_eq!(status, 200);
+    assert!(report["computed_at"].is_string());
+    let cohort = &report["cohorts"][0];
+    assert_eq!(cohort["cohort_week"], "2025-10-06");
+    assert_eq!(cohort["buyers"], 3);
+    assert_eq!(cohort["repeat_buyers"], 2);
+    assert_eq!(cohort["repeat_rate"], 0.6667);
+    assert_eq!(cohort["tickets"], 19);
+    let retention: Vec<_> = cohort["retention"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|week| (week["week_offset"].clone(), week["active_buyers"].clone()))
+        .collect();
+    assert_eq!(
+        retention,
+        [
+            (0.into(), 3.into()),
+            (1.into(), 0.into()),
+            (2.into(), 1.into())
+        ]
+    );
+
+    let (status, _) = app.get("/v1/analytics/cohorts?weeks=0").await.unwrap();
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn sybil_clusters() {
+    let Some(app) = start_fixture(include_str!("fixtures/sybil_clusters.json"), &[]).await else {
+        return;
+    };
+    assert_eq!(app.refresh_sybil_clusters().await.unwrap(), 4);
+
+    let response = app
+        .get_with_headers("/v1/raffles/2/participants?include_clusters=true", &[])
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers()[header::CACHE_CONTROL],
+        "private, no-store"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let participants: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let clusters: Vec<_> = participants
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|participant| (participant["buyer"].clone(), participant["cluster"].clone()))
+        .collect();
+    let b = |suffix: &str| format!("0x{suffix:0>40}");
+    let flag =
+        |id: &str, reason: &str| serde_json::json!({ "id": b(id), "size": 2, "reasons": [reason] });
+    assert_eq!(
+        clusters,
+        [
+            (b("b1").into(), flag("b1", "co_purchase")),
+            (b("b2").into(), flag("b1", "co_purchase")),
+            (b("b4").into(), flag("b3", "shared_sender")),
+            (b("b5").into(), serde_json::Value::Null),
+        ]
+    );
+
+    // Flags are only shown on request, and only to admins or the creator
+    let (_, participants) = app.get("/v1/raffles/2/participants").await.unwrap();
+    assert!(participants[0].get("cluster").is_none());
+    let response = app
+        .router
+        .clone()
+        .oneshot(
+            axum::http::Request::get("/v1/raffles/2/participants?include_clusters=true")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn referral_codes() {
+    let Some(app) = start_fixture(
+        include_str!("fixtures/referral_codes.json"),
+        &[("REFERRAL_TRACKING", "true")],
+    )
+    .await
+    else {
+        return;
+    };
+
+    // Registration needs a wallet signature; store the codes directly
+    let b = |suffix: &str| format!("0x{suffix:0>40}");
+    sqlx::query(
+        "INSERT INTO referral_codes (code, owner, created_at)
+         VALUES ('ALICE', $1, now() - interval '1 minute'), ('BOB', $2, now())",
+    )
+    .bind(b("c1"))
+    .bind(b("c2"))
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+
+    let (status, code) = app.get("/v1/referral-codes/alice").await.unwrap();
+    assert_eq!(status, 200);
+    let expected = serde_json::json!({
+        "code": "ALICE",
+        "owner": b("c1"),
+        "purchases": 3,
+        "buyers": 2,
+        "new_buyers": 1,
+        "tickets": 6,
+        "volume": "6000000",
+    });
+    for (field, value) in expected.as_object().unwrap() {
+        assert_eq!(&code[field], value, "{field}");
+    }
+    // b3's purchase in c2's raffle carries the code but isn't c1's to claim
+    assert_eq!(code["raffles"].as_array().unwrap().len(), 1);
+    assert_eq!(code["raffles"][0]["raffle_id"], 1);
+    assert_eq!(code["raffles"][0]["new_buyers"], 1);
+
+    let (_, codes) = app.get("/v1/referral-codes").await.unwrap();
+    let listed: Vec<_> = codes
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| (code["code"].clone(), code["purchases"].clone()))
+        .collect();
+    assert_eq!(
+        listed,
+        [("ALICE".into(), 3.into()), ("BOB".into(), 0.into())]
+    );
+    let (_, codes) = app
+        .get(&format!("/v1/referral-codes?owner={}", b("c2")))
+        .await
+        .unwrap();
+    assert_eq!(codes.as_array().unwrap().len(), 1);
+    assert_eq!(codes[0]["code"], "BOB");
+    assert!(codes[0].get("raffles").is_none());
+
+    // Codes are checked before the signature
+    let request = serde_json::json!({ "message": { "code": "a!" }, "signature": "0x" });
+    let (status, error) = app.post("/v1/referral-codes", request).await.unwrap();
+    assert_eq!(status, 400);
+    assert_eq!(error["code"], "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn webhook_replay() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    // A whale alert that used up its attempts, the last one answered 500
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO whale_alerts (purchase_id, status, attempts, last_error)
+         SELECT MIN(id), 'failed', 8, 'webhook responded with 500' FROM purchases
+         RETURNING id",
+    )
+    .fetch_one(&app.db.pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO webhook_attempts
+            (queue, notification_id, attempt, response_status, error, duration_ms)
+         VALUES ('whale_alerts', $1, 8, 500, 'webhook responded with 500', 12)",
+    )
+    .bind(id)
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+
+    let (_, attempts) = app
+        .get("/v1/admin/webhooks/attempts?queue=whale_alerts&failed=true")
+        .await
+        .unwrap();
+    assert_eq!(attempts[0]["notification_id"], id);
+    assert_eq!(attempts[0]["response_status"], 500);
+
+    let replay = serde_json::json!({ "queue": "whale_alerts" });
+    let (status, replayed) = app
+        .post("/v1/admin/webhooks/replay", replay.clone())
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(replayed["replayed"], serde_json::json!([id]));
+    let (pending, attempts): (String, i32) =
+        sqlx::query_as("SELECT status, attempts FROM whale_alerts WHERE id = $1")
+            .bind(id)
+            .fetch_one(&app.db.pool)
+            .await
+            .unwrap();
+    assert_eq!((pending.as_str(), attempts), ("pending", 0));
+
+    // Only failed notifications are replayed
+    let (_, replayed) = app.post("/v1/admin/webhooks/replay", replay).await.unwrap();
+    assert_eq!(replayed["replayed"], serde_json::json!([]));
+
+    let (status, rotated) = app
+        .post("/v1/admin/webhooks/signing-secret", serde_json::json!({}))
+        .await
+        .unwrap();
+    assert_eq!(status, 201);
+    assert!(rotated["secret"].as_str().unwrap().starts_with("whsec_"));
+    let (_, webhooks) = app.get("/v1/admin/webhooks").await.unwrap();
+    assert_eq!(webhooks["signing"]["source"], "rotated");
+    assert_eq!(webhooks["signing"]["secret_id"], rotated["id"]);
+}
+
+#[tokio::test]
+async fn outbox_matches_indexed_rows() {
+    let Some(app) = start_fixture(include_str!("fixtures/orphaned_blocks.json"), &[]).await else {
+        return;
+    };
+
+    // Nothing is published until the dispatcher runs, and the purchase of the orphaned
+    // block was withdrawn with it
+    let mut events = app.subscribe_live();
+    assert!(events.try_recv().is_err());
+    let published = app.publish_outbox().await.unwrap();
+    assert!(published > 0);
+    assert_eq!(app.publish_outbox().await.unwrap(), 0);
+
+    let mut announced = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let LiveEvent::Purchase {
+            tx_hash,
+            start_index,
+            ..
+        } = event
+        {
+            announced.push((tx_hash, start_index));
+        }
+    }
+    announced.sort();
+    let indexed: Vec<(String, i64)> =
+        sqlx::query_as("SELECT tx_hash, start_index FROM purchases ORDER BY tx_hash, start_index")
+            .fetch_all(&app.db.pool)
+            .await
+            .unwrap();
+    assert_eq!(announced, indexed);
+}
+
+#[tokio::test]
+async fn orphaned_payout() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    // The block holding the draw and the payouts is replaced by an empty one
+    app.chain.reorg(1);
+    app.chain.push_block(1760000100, Vec::new());
+    assert_eq!(app.check_orphans().await.unwrap(), 1);
+
+    let payouts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM payouts")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(payouts, 0);
+    let payout_tx: Option<String> =
+        sqlx::query_scalar("SELECT payout_tx FROM raffles WHERE raffle_id = 1")
+            .fetch_one(&app.db.pool)
+            .await
+            .unwrap();
+    assert_eq!(payout_tx, None);
+    let (_, raffle) = app.get("/v1/raffles/1").await.unwrap();
+    assert!(raffle["prize_amount"].is_null(), "{raffle}");
+}
+
+#[tokio::test]
+async fn stuck_raffles() {
+    let Some(app) = start_fixture(include_str!("fixtures/draw_estimate.json"), &[]).await else {
+        return;
+    };
+
+    // Raffle 2 closed in a block from long ago and was never drawn
+    let (status, stuck) = app.get("/v1/raffles/stuck").await.unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(stuck.as_array().unwrap().len(), 1);
+    assert_eq!(stuck[0]["raffle_id"], 2);
+    assert_eq!(stuck[0]["status"], "CLOSED");
+    assert_eq!(stuck[0]["sla_secs"], 900);
+    assert!(stuck[0]["stuck_secs"].as_i64().unwrap() > 900);
+
+    // One alert per raffle and status, however often the check runs
+    assert_eq!(app.check_stuck().await.unwrap(), 1);
+    assert_eq!(app.check_stuck().await.unwrap(), 0);
+    let (raffle_id, raffle_status): (i64, String) =
+        sqlx::query_as("SELECT raffle_id, raffle_status FROM stuck_alerts")
+            .fetch_one(&app.db.pool)
+            .await
+            .unwrap();
+    assert_eq!((raffle_id, raffle_status.as_str()), (2, "CLOSED"));
+}
+
+#[tokio::test]
+async fn schema_isolation() {
+    let Some(first) = TestDb::create_with(Isolation::Schema).await.unwrap() else {
+        eprintln!("TEST_DATABASE_URL is unset, skipping");
+        return;
+    };
+    let second = TestDb::create_with(Isolation::Schema)
+        .await
+        .unwrap()
+        .unwrap();
+    let schema = first.schema.clone().unwrap();
+
+    sqlx::query(
+        "INSERT INTO blocked_addresses (address, reason, blocked_by)
+         VALUES ('0x00000000000000000000000000000000000000b1', 'test', 'test')",
+    )
+    .execute(&first.pool)
+    .await
+    .unwrap();
+    let count = "SELECT COUNT(*) FROM blocked_addresses";
+    let first_count: i64 = sqlx::query_scalar(count)
+        .fetch_one(&first.pool)
+        .await
+        .unwrap();
+    let second_count: i64 = sqlx::query_scalar(count)
+        .fetch_one(&second.pool)
+        .await
+        .unwrap();
+    assert_eq!((first_count, second_count), (1, 0));
+
+    drop(first);
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM information_schema.schemata WHERE schema_name = $1)",
+    )
+    .bind(&schema)
+    .fetch_one(&second.pool)
+    .await
+    .unwrap();
+    assert!(!exists, "schema {schema} was not dropped");
+}
+
+#[tokio::test]
+async fn result_attestation() {
+    // First development account of Anvil and Hardhat
+    let key = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    let signer: ethers::types::Address = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+        .parse()
+        .unwrap();
+    let Some(app) = start_fixture(
+        include_str!("fixtures/happy_path.json"),
+        &[("ATTESTATION_SIGNING_KEY", key)],
+    )
+    .await
+    else {
+        return;
+    };
+
+    let (status, body) = app.get("/v1/raffles/1/attestation").await.unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(body["scheme"], "eip191");
+    assert_eq!(body["signer"], format!("{signer:#x}"));
+
+    // The message is the compact payload JSON, in the documented key order
+    let message = body["message"].as_str().unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(message).unwrap(),
+        body["payload"]
+    );
+    let keys = [
+        "version",
+        "chain_id",
+        "raffle_id",
+        "raffle_address",
+        "randomness",
+        "total_tickets",
+        "winning_index",
+        "winner",
+        "randomness_tx",
+        "randomness_block",
+        "finalized_tx",
+        "finalized_block",
+    ];
+    let positions: Vec<usize> = keys
+        .iter()
+        .map(|key| message.find(&format!("\"{key}\":")).unwrap())
+        .collect();
+    assert!(positions.is_sorted(), "{message}");
+    assert!(message.starts_with(r#"{"version":1,"chain_id":31337,"raffle_id":1,"#));
+    assert!(!message.contains(' '), "{message}");
+
+    let signature: ethers::types::Signature = body["signature"].as_str().unwrap().parse().unwrap();
+    assert_eq!(signature.recover(message).unwrap(), signer);
+    // Any change to the signed bytes recovers a different address
+    let tampered = message.replace(r#""winning_index":9"#, r#""winning_index":8"#);
+    assert_ne!(tampered, message);
+    assert_ne!(signature.recover(tampered).unwrap(), signer);
+}
+
+#[tokio::test]
+async fn attestation_requires_finalized_raffle() {
+    let key = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    let Some(app) = start_fixture(
+        include_str!("fixtures/draw_estimate.json"),
+        &[("ATTESTATION_SIGNING_KEY", key)],
+    )
+    .await
+    else {
+        return;
+    };
+
+    // Raffle 2 closed but was never drawn
+    let (status, error) = app.get("/v1/raffles/2/attestation").await.unwrap();
+    assert_eq!(status, 409);
+    assert_eq!(error["code"], "RAFFLE_NOT_FINALIZED");
+    let (status, _) = app.get("/v1/raffles/99/attestation").await.unwrap();
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn attestation_disabled() {
+    let Some(app) = start_fixture(include_str!("fixtures/happy_path.json"), &[]).await else {
+        return;
+    };
+
+    let (status, error) = app.get("/v1/raffles/1/attestation").await.unwrap();
+    assert_eq!(status, 503);
+    assert_eq!(error["code"], "FEATURE_DISABLED");
+}
@@ -0,0 +1,285 @@
+//! JSON fixtures: scripted event sequences and the API responses they should produce
+//!
+//! A fixture lists `steps`, each optionally dropping blocks from the head (`reorg`)
+//! and then appending `blocks` of events; the indexer catches up after every step.
+//! With `check_orphans`, the orphan check then runs and the indexer catches up again.
+//! With `check_payouts`, the payout check then reads the receipts of indexed payouts.
+//! `finalize` sets the block the chain reports for the `safe` and `finalized` tags;
+//! until a step sets it, the chain doesn't support them.
+//! Once all steps ran, every entry of `expect` is requested from the API and its
+//! body must contain the expected JSON: objects may have extra fields, arrays must
+//! match element by element.
+//!
+//! Events are given by ABI name with arguments by parameter name. Numbers may be
+//! JSON numbers or decimal strings; bytes are `0x`-prefixed hex. Events sharing a
+//! `tx` label share a transaction hash, which is how a reorg re-includes a
+//! transaction; without a label every event gets its own transaction.
+
+use super::{FACTORY_ADDRESS, PROVIDER_ADDRESS, TestApp, chain::PendingLog};
+use alloy::dyn_abi::{DynSolType, DynSolValue, Specifier};
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::{Address, B256, Bytes, U256, keccak256};
+use anyhow::{Context, anyhow, bail};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    /// What the scenario covers
+    pub description: String,
+    /// Number of the first block (also the indexer's `START_BLOCK`)
+    pub start_block: u64,
+    /// Configuration variables overriding the test defaults, e.g. `INDEXER_HEAD_TAG`
+    #[serde(default)]
+    pub config: BTreeMap<String, String>,
+    pub steps: Vec<Step>,
+    pub expect: Vec<Expectation>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Blocks dropped from the head before `blocks` are appended
+    #[serde(default)]
+    pub reorg: usize,
+    pub blocks: Vec<BlockSpec>,
+    /// Block reported as `safe` and `finalized` from this step on
+    pub finalize: Option<u64>,
+    /// Run the orphan check after indexing, then index again
+    #[serde(default)]
+    pub check_orphans: bool,
+    /// Run the payout check last
+    #[serde(default)]
+    pub check_payouts: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockSpec {
+    pub timestamp: u64,
+    #[serde(default)]
+    pub events: Vec<EventSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventSpec {
+    /// Contract whose ABI declares the event
+    pub contract: Contract,
+    /// Emitter; defaults to the configured factory or provider, required for raffles and
+    /// the token
+    pub address: Option<Address>,
+    pub event: String,
+    pub tx: Option<String>,
+    /// Sender of the transaction, reported in its receipt (default: the zero address);
+    /// the first event of a transaction sets it, as for `input`
+    pub from: Option<Address>,
+    /// Calldata of the transaction (default: empty)
+    #[serde(default)]
+    pub input: Bytes,
+    #[serde(default)]
+    pub args: serde_json::Map<String, Value>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Contract {
+    Factory,
+    Raffle,
+    Provider,
+    /// Payment token; its events aren't indexed but show up in receipts
+    Token,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// Request path, e.g. `/v1/raffles/1`
+    pub path: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    pub body: Value,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl Fixture {
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(json).context("invalid fixture")
+    }
+
+    /// Plays every step against `app`, indexing after each, then checks `expect`
+    pub async fn run(&self, app: &TestApp) -> anyhow::Result<()> {
+        let mut unlabeled_txs = 0;
+        for (step_index, step) in self.steps.iter().enumerate() {
+            app.chain.reorg(step.reorg);
+            if let Some(block) = step.finalize {
+                app.chain.finalize(block);
+            }
+            for block in &step.blocks {
+                let mut logs = Vec::with_capacity(block.events.len());
+                for event in &block.events {
+                    let label = match &event.tx {
+                        Some(label) => label.clone(),
+                        None => {
+                            unlabeled_txs += 1;
+                            format!("unlabeled {unlabeled_txs}")
+                        }
+                    };
+                    logs.push(
+                        encode_event(app, event, keccak256(label))
+                            .with_context(|| format!("step {step_index}: {}", event.event))?,
+                    );
+                }
+                app.chain.push_block(block.timestamp, logs);
+            }
+            app.index()
+                .await
+                .with_context(|| format!("indexing after step {step_index} failed"))?;
+            if step.check_orphans {
+                app.check_orphans()
+                    .await
+                    .with_context(|| format!("orphan check after step {step_index} failed"))?;
+                app.index().await.with_context(|| {
+                    format!("indexing after the orphan check of step {step_index} failed")
+                })?;
+            }
+            if step.check_payouts {
+                app.check_payouts()
+                    .await
+                    .with_context(|| format!("payout check after step {step_index} failed"))?;
+            }
+        }
+
+        for expectation in &self.expect {
+            let (status, body) = app.get(&expectation.path).await?;
+            if status.as_u16() != expectation.status {
+                bail!(
+                    "GET {}: expected status {}, got {status}: {body}",
+                    expectation.path,
+                    expectation.status
+                );
+            }
+            if let Err(mismatch) = check_subset(&expectation.body, &body, "$") {
+                bail!("GET {}: {mismatch}\nbody: {body:#}", expectation.path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encodes an event as its topics and data, as the contract would emit it
+fn encode_event(app: &TestApp, spec: &EventSpec, tx_hash: B256) -> anyhow::Result<PendingLog> {
+    let (abi, default_address): (&JsonAbi, Option<&str>) = match spec.contract {
+        Contract::Factory => (&app.abis.factory, Some(FACTORY_ADDRESS)),
+        Contract::Raffle => (&app.abis.raffle, None),
+        Contract::Provider => (&app.abis.provider, Some(PROVIDER_ADDRESS)),
+        Contract::Token => (&app.abis.token, None),
+    };
+    let address = match (spec.address, default_address) {
+        (Some(address), _) => address,
+        (None, Some(address)) => Address::from_str(address)?,
+        (None, None) => bail!("raffle and token events need an address"),
+    };
+    let event = abi
+        .event(&spec.event)
+        .and_then(|events| events.first())
+        .ok_or_else(|| anyhow!("no event {}", spec.event))?;
+
+    if let Some(unknown) = spec
+        .args
+        .keys()
+        .find(|name| !event.inputs.iter().any(|param| &param.name == *name))
+    {
+        bail!("{} has no parameter {unknown}", spec.event);
+    }
+
+    let mut topics = vec![event.selector()];
+    let mut data = Vec::new();
+    for param in &event.inputs {
+        let value = spec
+            .args
+            .get(&param.name)
+            .ok_or_else(|| anyhow!("missing argument {}", param.name))?;
+        let kind = param.resolve()?;
+        let token = to_value(&kind, value).with_context(|| param.name.clone())?;
+        if param.indexed {
+            if kind.is_dynamic() {
+                bail!("indexed dynamic parameters are not supported");
+            }
+            topics.push(B256::from_slice(&token.abi_encode()));
+        } else {
+            data.push(token);
+        }
+    }
+    Ok(PendingLog {
+        address,
+        topics,
+        data: DynSolValue::Tuple(data).abi_encode_params(),
+        tx_hash,
+        from: spec.from.unwrap_or_default(),
+        input: spec.input.clone(),
+    })
+}
+
+fn to_value(kind: &DynSolType, value: &Value) -> anyhow::Result<DynSolValue> {
+    Ok(match (kind, value) {
+        (DynSolType::Uint(size), Value::Number(number)) => DynSolValue::Uint(
+            U256::from(
+                number
+                    .as_u64()
+                    .ok_or_else(|| anyhow!("{number} is not a uint"))?,
+            ),
+            *size,
+        ),
+        (DynSolType::Uint(size), Value::String(number)) => {
+            DynSolValue::Uint(U256::from_str_radix(number, 10)?, *size)
+        }
+        (DynSolType::Address, Value::String(address)) => {
+            DynSolValue::Address(Address::from_str(address)?)
+        }
+        (DynSolType::Bytes, Value::String(bytes)) => DynSolValue::Bytes(hex::decode(
+            bytes
+                .strip_prefix("0x")
+                .ok_or_else(|| anyhow!("bytes must be 0x-prefixed"))?,
+        )?),
+        (DynSolType::Bool, Value::Bool(flag)) => DynSolValue::Bool(*flag),
+        (kind, value) => bail!("can't encode {value} as {kind}"),
+    })
+}
+
+/// Checks that `actual` contains `expected`, describing the first difference
+fn check_subset(expected: &Value, actual: &Value, path: &str) -> Result<(), String> {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                let path = format!("{path}.{key}");
+                let actual = actual
+                    .get(key)
+                    .ok_or_else(|| format!("{path} is missing"))?;
+                check_subset(expected, actual, &path)?;
+            }
+            Ok(())
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            if expected.len() != actual.len() {
+                return Err(format!(
+                    "{path} has {} elements, expected {}",
+                    actual.len(),
+                    expected.len()
+                ));
+            }
+            for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                check_subset(expected, actual, &format!("{path}[{index}]"))?;
+            }
+            Ok(())
+        }
+        (expected, actual) if expected == actual => Ok(()),
+        (expected, actual) => Err(format!("{path} is {actual}, expected {expected}")),
+    }
+}
@@ -0,0 +1,281 @@
+//! End-to-end test kit
+//!
+//! Runs the full indexer → database → API pipeline without a node: a [`MockChain`]
+//! serves scripted blocks to the real indexer, which writes to a temporary database
+//! ([`TestDb`]) that the real API router then reads. Scenarios are JSON fixtures under
+//! `src/testkit/fixtures/` (see [`fixture`] for the format).
+//!
+//! The tests need a Postgres server: set `TEST_DATABASE_URL` to a connection string
+//! of a user allowed to create databases. Without it they are skipped.
+//!
+//! Events are encoded with the human-readable ABIs in [`crate::abi`] rather than the
+//! Hardhat artifacts, so the tests run without compiling the contracts.
+
+mod chain;
+mod db;
+mod e2e;
+mod fixture;
+
+pub use chain::MockChain;
+pub use db::{Isolation, TestDb};
+pub use fixture::Fixture;
+
+use crate::abi::Abis;
+use crate::circuit::CircuitBreaker;
+use crate::config::AppConfig;
+use crate::indexer::{self, IndexerStatus};
+use crate::live::{self, LiveEvent};
+use crate::rpc::RpcBudget;
+use crate::state::AppState;
+use crate::tasks::TaskGroup;
+use crate::{api, api_keys, cache, chain::ChainReader};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::response::Response;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tower::ServiceExt;
+
+pub const CHAIN_ID: u64 = 31337;
+pub const FACTORY_ADDRESS: &str = "0x00000000000000000000000000000000000fac70";
+pub const PROVIDER_ADDRESS: &str = "0x000000000000000000000000000000000000d7a2";
+/// Sent with every request, so fixtures can check admin endpoints
+pub const ADMIN_API_KEY: &str = "test-admin-key";
+
+/// The indexer and API of one deployment, over a mock chain and a temporary database
+pub struct TestApp {
+    pub chain: MockChain,
+    pub abis: Abis,
+    pub config: AppConfig,
+    pub db: TestDb,
+    router: Router,
+    live: broadcast::Sender<LiveEvent>,
+    status: IndexerStatus,
+}
+
+impl TestApp {
+    /// Starts an app indexing from `start_block`, or `None` without `TEST_DATABASE_URL`
+    ///
+    /// `overrides` take precedence over the test configuration.
+    pub async fn start(
+        start_block: u64,
+        overrides: &[(&str, &str)],
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(db) = TestDb::create().await? else {
+            return Ok(None);
+        };
+        let start_block = start_block.to_string();
+        let chain_id = CHAIN_ID.to_string();
+        // The first occurrence of a variable wins
+        let mut vars = overrides.to_vec();
+        vars.extend([
+            ("DATABASE_URL", db.url.as_str()),
+            ("RPC_URL", "http://127.0.0.1:1"),
+            ("CHAIN_ID", chain_id.as_str()),
+            ("START_BLOCK", start_block.as_str()),
+            ("RAFFLE_FACTORY_ADDRESS", FACTORY_ADDRESS),
+            ("RANDOMNESS_PROVIDER_ADDRESS", PROVIDER_ADDRESS),
+            ("ADMIN_API_KEY", ADMIN_API_KEY),
+            // Small batches so scenarios span several indexing cycles
+            ("INDEXER_BATCH_SIZE", "2"),
+            ("INDEXER_POLL_INTERVAL_MS", "1"),
+            ("INDEXER_POLL_MIN_INTERVAL_MS", "1"),
+            // Every request reads the database, so steps are visible immediately
+            ("RAFFLE_LIST_CACHE_TTL_MS", "0"),
+        ]);
+        if let Some(schema) = &db.schema {
+            vars.push(("DATABASE_SCHEMA", schema.as_str()));
+        }
+        let config = AppConfig::from_vars(&vars)?;
+
+        let live = live::channel();
+        let status = IndexerStatus::new(CircuitBreaker::new("test", 5, Duration::from_secs(1)));
+        let embed_ttl = Duration::from_secs(config.embed_cache_ttl_secs);
+        let state = AppState {
+            db: db.pool.clone(),
+            indexer_db: db.pool.clone(),
+            config: config.clone(),
+            chain: ChainReader::new(
+                &config.api_rpc_url,
+                &config.raffle_factory_address,
+                config.api_rpc_rate_limit,
+                &RpcBudget::unlimited("test"),
+            )?,
+            attestation_signer: crate::attestation_signer(&config)?,
+            pending: None,
+            live: live.clone(),
+            indexer: status.clone(),
+            raffle_list_cache: (config.raffle_list_cache_ttl_ms > 0).then(|| {
+                cache::SwrCache::new(
+                    Duration::from_millis(config.raffle_list_cache_ttl_ms),
+                    Duration::from_secs(config.raffle_list_cache_max_stale_secs),
+                )
+            }),
+            embed_cache: cache::SwrCache::new(embed_ttl, embed_ttl * api::EMBED_STALE_FACTOR),
+            network_cache: cache::SwrCache::new(Duration::from_secs(1), Duration::from_secs(1)),
+            api_keys: api_keys::ApiKeyGuard::default(),
+            tasks: TaskGroup::new("test", tracing::Span::none()),
+            geo: None,
+        };
+
+        Ok(Some(Self {
+            chain: MockChain::new(CHAIN_ID, config.start_block),
+            abis: Abis::new(),
+            router: crate::with_options(crate::deployment_router(state)),
+            config,
+            db,
+            live,
+            status,
+        }))
+    }
+
+    /// Indexes the mock chain up to its current head
+    pub async fn index(&self) -> anyhow::Result<()> {
+        let events_by_signature = indexer::build_event_map(
+            &self.abis.factory,
+            &self.abis.raffle,
+            Some(&self.abis.provider),
+        )?;
+        indexer::index_to_head(
+            self.db.pool.clone(),
+            self.config.clone(),
+            self.chain.clone(),
+            events_by_signature,
+            self.status.clone(),
+        )
+        .await
+    }
+
+    /// Runs one orphan check against the mock chain (see [`crate::orphans`])
+    pub async fn check_orphans(&self) -> anyhow::Result<usize> {
+        crate::orphans::check_recent_blocks(
+            &self.db.pool,
+            &self.chain,
+            self.config.orphan_check_blocks,
+        )
+        .await
+    }
+
+    /// Publishes the live events in the outbox (see [`crate::outbox`]), returning how many
+    pub async fn publish_outbox(&self) -> anyhow::Result<usize> {
+        crate::outbox::publish_pending(&self.db.pool, &self.live).await
+    }
+
+    /// Subscribes to the live events published by [`Self::publish_outbox`]
+    pub fn subscribe_live(&self) -> broadcast::Receiver<LiveEvent> {
+        self.live.subscribe()
+    }
+
+    /// Runs one stuck check (see [`crate::stuck`]), returning how many alerts were queued
+    pub async fn check_stuck(&self) -> anyhow::Result<usize> {
+        crate::stuck::enqueue_alerts(&self.db.pool, &self.config, &self.status).await
+    }
+
+    /// Looks up purchase senders on the mock chain and recomputes the sybil clusters
+    /// (see [`crate::sybil`]), returning how many addresses are clustered
+    pub async fn refresh_sybil_clusters(&self) -> anyhow::Result<usize> {
+        crate::sybil::lookup_senders(&self.db.pool, &self.chain).await?;
+        crate::sybil::refresh(&self.db.pool, &self.config).await
+    }
+
+    /// Recomputes the participant cohorts (see [`crate::cohorts`])
+    pub async fn refresh_cohorts(&self) -> anyhow::Result<u64> {
+        crate::cohorts::refresh(&self.db.pool).await
+    }
+
+    /// Compiles and stores the digest of `day` (see [`crate::digest`])
+    pub async fn store_digest(&self, day: chrono::NaiveDate) -> anyhow::Result<bool> {
+        crate::digest::store(&self.db.pool, &self.config, day).await
+    }
+
+    /// Archives the raffles completed `after_days` ago (see [`crate::archive`]), returning
+    /// how many were moved
+    pub async fn archive(&self, after_days: i32) -> anyhow::Result<u64> {
+        crate::archive::archive_completed(&self.db.pool, after_days).await
+    }
+
+    /// Writes an export snapshot to the configured bucket unless one is recent (see
+    /// [`crate::export`])
+    pub async fn export_snapshot(&self) -> anyhow::Result<()> {
+        let export = self
+            .config
+            .export
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("EXPORT_BUCKET is unset"))?;
+        let bucket = crate::storage::Bucket::new(&export.store)?;
+        crate::export::run_export_cycle(&self.db.pool, export, &bucket).await
+    }
+
+    /// Runs one payout check against the mock chain (see [`crate::payouts`])
+    pub async fn check_payouts(&self) -> anyhow::Result<usize> {
+        crate::payouts::confirm_payouts(&self.db.pool, &self.chain).await
+    }
+
+    /// Sends `GET path` to the API and returns the status and JSON body
+    pub async fn get(&self, path: &str) -> anyhow::Result<(StatusCode, serde_json::Value)> {
+        let response = self
+            .router
+            .clone()
+            .oneshot(
+                Request::get(path)
+                    .header("authorization", format!("Bearer {ADMIN_API_KEY}"))
+                    .body(Body::empty())?,
+            )
+            .await?;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        Ok((status, serde_json::from_slice(&body)?))
+    }
+
+    /// Sends `GET path` with extra request headers and returns the raw response
+    pub async fn get_with_headers(
+        &self,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> anyhow::Result<Response> {
+        self.send(Method::GET, path, headers).await
+    }
+
+    /// Sends a body-less request with extra request headers and returns the raw response
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> anyhow::Result<Response> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("authorization", format!("Bearer {ADMIN_API_KEY}"));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        Ok(self
+            .router
+            .clone()
+            .oneshot(request.body(Body::empty())?)
+            .await?)
+    }
+
+    /// Sends `POST path` with a JSON body to the API and returns the status and JSON body
+    pub async fn post(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> anyhow::Result<(StatusCode, serde_json::Value)> {
+        let response = self
+            .router
+            .clone()
+            .oneshot(
+                Request::post(path)
+                    .header("authorization", format!("Bearer {ADMIN_API_KEY}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&body)?))?,
+            )
+            .await?;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        Ok((status, serde_json::from_slice(&body)?))
+    }
+}
@@ -0,0 +1,519 @@
+//! Independent raffle verification (`verify-raffle` subcommand)
+//!
+//! Lets anyone who doesn't trust a hosted instance check a raffle with nothing but
+//! this binary and an RPC endpoint: no database or deployment configuration is
+//! needed. The command
+//!
+//! 1. looks the raffle up on the factory (`raffles(id)`),
+//! 2. fetches every log of the raffle contract from `--from-block` to the head,
+//! 3. replays them: ticket ranges must be contiguous from index 0, the pot is the
+//!    purchases minus refunds and payouts, and the winner is the owner of
+//!    `randomness % totalTickets`, and
+//! 4. compares the result with the contract's own views and, with `--api-url`, with
+//!    what the API serves for the raffle.
+//!
+//! Every comparison is printed as a PASS/FAIL line like `check-config`.
+
+use crate::abi::{Abis, DecodedEvent, LogDecoder};
+use crate::cli::{Report, probe};
+use crate::rpc::{AlloyProvider, RpcBudget};
+use alloy::dyn_abi::{DynSolValue, FunctionExt, JsonAbiExt};
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::{Filter, Log, TransactionInput, TransactionRequest};
+use anyhow::Context;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Blocks per `eth_getLogs` request, as the indexer's default batch size
+const LOG_CHUNK_BLOCKS: u64 = 2000;
+
+/// Timeout for the API requests
+const API_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Factory and raffle views the checks read; the events come from [`Abis`]
+const VIEWS: &[&str] = &[
+    "function raffles(uint256) external view returns (address)",
+    "function totalTickets() external view returns (uint32)",
+    "function pot() external view returns (uint256)",
+    "function randomness() external view returns (uint256)",
+    "function winningIndex() external view returns (uint256)",
+    "function winner() external view returns (address)",
+];
+
+/// Raffle and endpoints to verify against
+#[derive(Clone, Debug, Default)]
+pub struct VerifyOptions {
+    pub raffle_id: u64,
+    /// RPC endpoint (default: `RPC_URL`)
+    pub rpc_url: Option<String>,
+    /// Factory contract (default: `RAFFLE_FACTORY_ADDRESS`)
+    pub factory: Option<String>,
+    /// First block to read logs from (default: `START_BLOCK`, else 0)
+    pub from_block: Option<u64>,
+    /// Base URL of an API instance to compare with, e.g. `https://api.example.com`
+    pub api_url: Option<String>,
+}
+
+/// What the raffle's logs add up to
+#[derive(Debug, Default)]
+struct Replay {
+    /// Purchased ranges in log order: `(buyer, start_index, end_index)`
+    ranges: Vec<(Address, u64, u64)>,
+    total_tickets: u64,
+    pot: U256,
+    randomness: Option<U256>,
+    /// Winner and winning index as emitted by `WinnerSelected`
+    selected: Option<(Address, U256)>,
+    /// Purchases whose range doesn't start where the previous one ended
+    gaps: Vec<String>,
+}
+
+impl Replay {
+    /// Applies decoded logs in chain order
+    ///
+    /// Lifecycle events without an effect on the totals are skipped, and so is a log
+    /// missing a parameter (`None`).
+    fn apply(&mut self, event: &DecodedEvent) -> Option<()> {
+        match event.event.as_str() {
+            "TicketsBought" => {
+                let buyer = address_param(event, "buyer")?;
+                let start = uint_param(event, "startIndex")?.to::<u64>();
+                let end = uint_param(event, "endIndex")?.to::<u64>();
+                let count = uint_param(event, "count")?.to::<u64>();
+                if start != self.total_tickets || end + 1 != start + count {
+                    self.gaps.push(format!(
+                        "{buyer:#x} bought {start}..={end} ({count} tickets) after {} tickets",
+                        self.total_tickets
+                    ));
+                }
+                self.total_tickets += count;
+                self.pot += uint_param(event, "amountPaid")?;
+                self.ranges.push((buyer, start, end));
+            }
+            "RefundClaimed" => {
+                self.pot = self.pot.saturating_sub(uint_param(event, "amount")?);
+            }
+            "RandomnessFulfilled" => {
+                self.randomness = Some(uint_param(event, "randomness")?);
+            }
+            "WinnerSelected" => {
+                let paid = uint_param(event, "prizeAmount")? + uint_param(event, "feeAmount")?;
+                // The contract pays out the whole pot on finalization
+                self.pot = self.pot.saturating_sub(paid);
+                self.selected = Some((
+                    address_param(event, "winner")?,
+                    uint_param(event, "winningIndex")?,
+                ));
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    /// `randomness % totalTickets`, once randomness was delivered
+    fn winning_index(&self) -> Option<u64> {
+        let randomness = self.randomness?;
+        (self.total_tickets > 0).then(|| (randomness % U256::from(self.total_tickets)).to::<u64>())
+    }
+
+    /// Buyer of the range containing `index`
+    fn owner_of(&self, index: u64) -> Option<Address> {
+        self.ranges
+            .iter()
+            .find(|(_, start, end)| (*start..=*end).contains(&index))
+            .map(|(buyer, _, _)| *buyer)
+    }
+}
+
+/// An integer parameter of a decoded log (rendered as a decimal string)
+fn uint_param(event: &DecodedEvent, name: &str) -> Option<U256> {
+    event.params.get(name)?.as_str()?.parse().ok()
+}
+
+/// An address parameter of a decoded log
+fn address_param(event: &DecodedEvent, name: &str) -> Option<Address> {
+    Address::from_str(event.params.get(name)?.as_str()?).ok()
+}
+
+/// Calls the views in [`VIEWS`]
+struct Views {
+    provider: AlloyProvider,
+    abi: JsonAbi,
+}
+
+impl Views {
+    fn new(provider: AlloyProvider) -> Self {
+        Self {
+            provider,
+            abi: JsonAbi::parse(VIEWS.iter().copied()).expect("view ABI"),
+        }
+    }
+
+    /// Calls `name` on `contract` and returns its single output
+    async fn call(
+        &self,
+        contract: Address,
+        name: &str,
+        args: &[DynSolValue],
+    ) -> anyhow::Result<DynSolValue> {
+        let function = &self.abi.function(name).context("unknown view")?[0];
+        let request = TransactionRequest::default()
+            .to(contract)
+            .input(TransactionInput::new(
+                function.abi_encode_input(args)?.into(),
+            ));
+        let output = self.provider.call(request).await?;
+        function
+            .abi_decode_output(&output)?
+            .into_iter()
+            .next()
+            .context("view returned nothing")
+    }
+
+    async fn uint(&self, contract: Address, name: &str) -> anyhow::Result<U256> {
+        let value = self.call(contract, name, &[]).await?;
+        value
+            .as_uint()
+            .map(|(value, _)| value)
+            .with_context(|| format!("{name}() returned {value:?}"))
+    }
+
+    async fn address(
+        &self,
+        contract: Address,
+        name: &str,
+        args: &[DynSolValue],
+    ) -> anyhow::Result<Address> {
+        let value = self.call(contract, name, args).await?;
+        value
+            .as_address()
+            .with_context(|| format!("{name}() returned {value:?}"))
+    }
+}
+
+/// Verifies a raffle from chain data and prints a report
+///
+/// Returns `true` when every check passed.
+pub async fn run(options: VerifyOptions) -> anyhow::Result<bool> {
+    let rpc_url = match options.rpc_url {
+        Some(url) => url,
+        None => std::env::var("RPC_URL")
+            .map_err(|_| anyhow::anyhow!("pass --rpc-url or set RPC_URL"))?,
+    };
+    let factory = match options.factory {
+        Some(address) => address,
+        None => std::env::var("RAFFLE_FACTORY_ADDRESS")
+            .map_err(|_| anyhow::anyhow!("pass --factory or set RAFFLE_FACTORY_ADDRESS"))?,
+    };
+    let factory = Address::from_str(&factory)
+        .map_err(|_| anyhow::anyhow!("{factory} is not a valid factory address"))?;
+    let from_block = match options.from_block {
+        Some(block) => block,
+        None => std::env::var("START_BLOCK")
+            .ok()
+            .map(|block| block.parse())
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("START_BLOCK must be a valid u64"))?
+            .unwrap_or(0),
+    };
+    // Nothing else shares the endpoint, so the budget only labels the requests
+    let provider = RpcBudget::unlimited("default")
+        .alloy_provider(&rpc_url, "verify")
+        .map_err(|err| anyhow::anyhow!("invalid RPC URL ({err})"))?;
+    let views = Views::new(provider.clone());
+    let raffle_id = options.raffle_id;
+    let mut report = Report::default();
+
+    let raffle_address = probe(views.address(
+        factory,
+        "raffles",
+        &[DynSolValue::from(U256::from(raffle_id))],
+    ))
+    .await
+    .map_err(|err| anyhow::anyhow!("failed to look up raffle {raffle_id}: {err}"))?;
+    if raffle_address.is_zero() {
+        anyhow::bail!("raffle {raffle_id} does not exist on factory {factory:#x}");
+    }
+    report.pass("raffle", format!("{raffle_address:#x}"));
+
+    let replay = replay_logs(&provider, raffle_address, from_block).await?;
+    if replay.gaps.is_empty() {
+        report.pass(
+            "ticket ranges",
+            format!(
+                "{} purchases cover tickets 0..{}",
+                replay.ranges.len(),
+                replay.total_tickets
+            ),
+        );
+    } else {
+        for gap in &replay.gaps {
+            report.fail("ticket ranges", gap);
+        }
+    }
+    let winning_index = replay.winning_index();
+    let winner = winning_index.and_then(|index| replay.owner_of(index));
+    match (&replay.selected, winning_index, winner) {
+        (Some((selected, selected_index)), Some(index), Some(owner)) => {
+            check(
+                &mut report,
+                "winning index (WinnerSelected)",
+                *selected_index,
+                U256::from(index),
+            );
+            check(&mut report, "winner (WinnerSelected)", *selected, owner);
+        }
+        (Some(_), _, _) => report.fail(
+            "winner",
+            "WinnerSelected emitted, but the logs don't determine a winner",
+        ),
+        (None, _, _) => report.skip("winner", "no winner selected yet"),
+    }
+
+    match probe(views.uint(raffle_address, "totalTickets")).await {
+        Ok(total) => check(
+            &mut report,
+            "total tickets (contract)",
+            total,
+            U256::from(replay.total_tickets),
+        ),
+        Err(err) => report.fail("total tickets (contract)", err),
+    }
+    match probe(views.uint(raffle_address, "pot")).await {
+        Ok(pot) => check(&mut report, "pot (contract)", pot, replay.pot),
+        Err(err) => report.fail("pot (contract)", err),
+    }
+    if let Some(randomness) = replay.randomness {
+        match probe(views.uint(raffle_address, "randomness")).await {
+            Ok(stored) => check(&mut report, "randomness (contract)", stored, randomness),
+            Err(err) => report.fail("randomness (contract)", err),
+        }
+        if let Some(index) = winning_index {
+            match probe(views.uint(raffle_address, "winningIndex")).await {
+                Ok(stored) => check(
+                    &mut report,
+                    "winning index (contract)",
+                    stored,
+                    U256::from(index),
+                ),
+                Err(err) => report.fail("winning index (contract)", err),
+            }
+        }
+    }
+    if let Some(winner) = winner.filter(|_| replay.selected.is_some()) {
+        match probe(views.address(raffle_address, "winner", &[])).await {
+            Ok(stored) => check(&mut report, "winner (contract)", stored, winner),
+            Err(err) => report.fail("winner (contract)", err),
+        }
+    }
+
+    match options.api_url {
+        Some(api_url) => {
+            compare_api(
+                &mut report,
+                &api_url,
+                raffle_id,
+                &replay,
+                winning_index,
+                winner,
+            )
+            .await
+        }
+        None => report.skip("api", "no --api-url given"),
+    }
+
+    Ok(finish(report))
+}
+
+/// Reads and replays every log of the raffle contract from `from_block` to the head
+async fn replay_logs(
+    provider: &AlloyProvider,
+    raffle_address: Address,
+    from_block: u64,
+) -> anyhow::Result<Replay> {
+    let head = probe(provider.get_block_number())
+        .await
+        .map_err(|err| anyhow::anyhow!("failed to fetch the head block: {err}"))?;
+    let mut logs: Vec<Log> = Vec::new();
+    let mut start = from_block;
+    while start <= head {
+        let end = (start + LOG_CHUNK_BLOCKS - 1).min(head);
+        let filter = Filter::new()
+            .address(raffle_address)
+            .from_block(start)
+            .to_block(end);
+        let chunk = probe(provider.get_logs(&filter)).await.map_err(|err| {
+            anyhow::anyhow!("failed to fetch logs of blocks {start}-{end}: {err}")
+        })?;
+        logs.extend(chunk);
+        start = end + 1;
+    }
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    let decoder = LogDecoder::new(&Abis::new());
+    let mut replay = Replay::default();
+    for log in logs {
+        if let Some(event) = decoder.decode(log.topics().to_vec(), log.data().data.to_vec()) {
+            replay.apply(&event);
+        }
+    }
+    Ok(replay)
+}
+
+/// Compares the replay with the API's raffle details
+async fn compare_api(
+    report: &mut Report,
+    api_url: &str,
+    raffle_id: u64,
+    replay: &Replay,
+    winning_index: Option<u64>,
+    winner: Option<Address>,
+) {
+    let url = format!("{}/v1/raffles/{raffle_id}", api_url.trim_end_matches('/'));
+    let details = async {
+        reqwest::Client::new()
+            .get(&url)
+            .timeout(API_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await
+    };
+    let details = match details.await {
+        Ok(details) => details,
+        Err(err) => {
+            report.fail("api", format!("GET {url}: {err}"));
+            return;
+        }
+    };
+
+    check(
+        report,
+        "total tickets (api)",
+        details["total_tickets"].as_u64(),
+        Some(replay.total_tickets),
+    );
+    check(
+        report,
+        "pot (api)",
+        details["pot"]
+            .as_str()
+            .and_then(|pot| pot.parse::<U256>().ok()),
+        Some(replay.pot),
+    );
+    if let Some(randomness) = replay.randomness {
+        check(
+            report,
+            "randomness (api)",
+            details["randomness"]
+                .as_str()
+                .and_then(|value| value.parse::<U256>().ok()),
+            Some(randomness),
+        );
+        check(
+            report,
+            "winning index (api)",
+            details["winning_index"].as_u64(),
+            winning_index,
+        );
+    }
+    if replay.selected.is_some() {
+        check(
+            report,
+            "winner (api)",
+            details["winner"]
+                .as_str()
+                .and_then(|value| Address::from_str(value).ok()),
+            winner,
+        );
+    }
+}
+
+/// Reports whether a value read elsewhere equals the one recomputed from the logs
+fn check<T: PartialEq + std::fmt::Debug>(report: &mut Report, name: &str, actual: T, expected: T) {
+    if actual == expected {
+        report.pass(name, format!("{actual:?}"));
+    } else {
+        report.fail(name, format!("{actual:?}, logs give {expected:?}"));
+    }
+}
+
+fn finish(report: Report) -> bool {
+    if report.failures == 0 {
+        println!("\nRaffle verified.");
+        true
+    } else {
+        println!("\n{} check(s) failed.", report.failures);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::B256;
+
+    fn topic(value: u64) -> B256 {
+        B256::from(U256::from(value).to_be_bytes::<32>())
+    }
+
+    fn body(values: &[U256]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_be_bytes::<32>())
+            .collect()
+    }
+
+    /// Encodes a log of a raffle event and decodes it like [`replay_logs`]
+    fn decoded(name: &str, indexed: &[B256], values: &[U256]) -> DecodedEvent {
+        let abis = Abis::new();
+        let mut topics = vec![abis.raffle.event(name).unwrap()[0].selector()];
+        topics.extend_from_slice(indexed);
+        LogDecoder::new(&abis).decode(topics, body(values)).unwrap()
+    }
+
+    fn bought(buyer: Address, start: u32, count: u32) -> DecodedEvent {
+        decoded(
+            "TicketsBought",
+            &[topic(1), buyer.into_word()],
+            &[
+                U256::from(start),
+                U256::from(start + count - 1),
+                U256::from(count),
+                U256::from(count) * U256::from(1_000_000),
+            ],
+        )
+    }
+
+    #[test]
+    fn replays_purchases_and_the_draw() {
+        let (a, b) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+        let mut replay = Replay::default();
+        replay.apply(&bought(a, 0, 6));
+        replay.apply(&bought(b, 6, 4));
+        replay.apply(&decoded(
+            "RandomnessFulfilled",
+            &[topic(1)],
+            &[U256::from(1), U256::from(123_456_789u64)],
+        ));
+        assert!(replay.gaps.is_empty());
+        assert_eq!(replay.total_tickets, 10);
+        assert_eq!(replay.pot, U256::from(10_000_000u64));
+        // 123456789 % 10
+        assert_eq!(replay.winning_index(), Some(9));
+        assert_eq!(replay.owner_of(9), Some(b));
+
+        replay.apply(&decoded(
+            "RefundClaimed",
+            &[topic(1), a.into_word()],
+            &[U256::from(1), U256::from(1_000_000)],
+        ));
+        assert_eq!(replay.pot, U256::from(9_000_000u64));
+
+        replay.apply(&bought(a, 12, 1));
+        assert_eq!(replay.gaps.len(), 1);
+    }
+}